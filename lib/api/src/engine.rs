@@ -1,8 +1,28 @@
 use core::ops::Deref;
+use std::sync::Arc;
+
+use js_sys::WebAssembly;
+use wasmer_types::MemoryType;
 
 use crate::js::engine as engine_imp;
 pub(crate) use crate::js::engine::default_engine;
 
+/// A hook for providing the memories created with
+/// [`Memory::new()`][crate::Memory::new], see
+/// [`Engine::set_memory_creator()`].
+///
+/// This makes it possible to reuse a `WebAssembly.Memory` which was created
+/// elsewhere in JS, e.g. one shared with another library, instead of always
+/// allocating a new one.
+pub trait MemoryCreator: Send + Sync {
+    /// Returns the memory to use for a memory of type `ty`, or `None` to
+    /// allocate a new one.
+    ///
+    /// The memory needs to be at least `ty.minimum` pages big, and needs to
+    /// be shared if `ty.shared` is set.
+    fn create_memory(&self, ty: &MemoryType) -> Option<WebAssembly::Memory>;
+}
+
 /// The engine type
 #[derive(Clone, Debug)]
 pub struct Engine(pub(crate) engine_imp::Engine);
@@ -12,6 +32,15 @@ impl Engine {
     pub fn deterministic_id(&self) -> &str {
         self.0.deterministic_id()
     }
+
+    /// Use `creator` to provide the memories created in stores using this
+    /// engine.
+    ///
+    /// Memories defined by a module (rather than imported) are always
+    /// allocated by the browser's WebAssembly engine.
+    pub fn set_memory_creator(&mut self, creator: impl MemoryCreator + 'static) {
+        self.0.set_memory_creator(Arc::new(creator));
+    }
 }
 
 impl AsEngineRef for Engine {
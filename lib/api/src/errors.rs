@@ -1,6 +1,7 @@
 use crate::js::trap::Trap;
+use std::any::Any;
 use std::fmt;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use thiserror::Error;
 use wasmer_types::ImportError;
 use wasmer_types::{FrameInfo, TrapCode};
@@ -154,6 +155,10 @@ impl RuntimeError {
 
     /// Returns a reference the `message` stored in `Trap`.
     pub fn message(&self) -> String {
+        // Prefer the engine's own description of the error.
+        if let Some(message) = self.inner.source.js_message() {
+            return message.to_string();
+        }
         if let Some(trap_code) = self.inner.trap_code {
             trap_code.message().to_string()
         } else {
@@ -163,15 +168,29 @@ impl RuntimeError {
 
     /// Returns a list of function frames in WebAssembly code that led to this
     /// trap happening.
+    ///
+    /// Frames are recovered from the JS stack trace, so `func_start` isn't
+    /// known and [`FrameInfo::func_offset()`] is the same as
+    /// [`FrameInfo::module_offset()`].
     pub fn trace(&self) -> &[FrameInfo] {
         &self.inner.wasm_trace
     }
 
     /// Returns trap code, if it's a Trap
+    ///
+    /// Traps raised by the JS engine are recognized from the message of the
+    /// `WebAssembly.RuntimeError`, which differs between browsers, so
+    /// unrecognized messages have no trap code.
     pub fn to_trap(self) -> Option<TrapCode> {
         self.inner.trap_code
     }
 
+    /// Returns the full JS stack trace of the error, including the JS and
+    /// WebAssembly frames, if it was raised by the JS engine.
+    pub fn js_stack(&self) -> Option<&str> {
+        self.inner.source.js_stack()
+    }
+
     // /// Returns trap code, if it's a Trap
     // pub fn to_source(self) -> &'static Trap {
     //     &self.inner.as_ref().source
@@ -255,6 +274,55 @@ impl From<Box<dyn std::error::Error + Send + Sync>> for RuntimeError {
     }
 }
 
+/// A panic that occurred inside of a host function.
+///
+/// Panics are caught at the boundary of host functions and raised as a
+/// [`RuntimeError`], which can be downcast to this type to inspect the
+/// panic.
+#[derive(Error)]
+#[error("host function panicked: {message}")]
+pub struct HostPanic {
+    message: String,
+    payload: Mutex<Option<Box<dyn Any + Send + 'static>>>,
+}
+
+impl HostPanic {
+    pub(crate) fn new(payload: Box<dyn Any + Send + 'static>) -> Self {
+        let message = if let Some(msg) = payload.downcast_ref::<&str>() {
+            msg.to_string()
+        } else if let Some(msg) = payload.downcast_ref::<String>() {
+            msg.clone()
+        } else {
+            "Box<dyn Any>".to_string()
+        };
+        Self {
+            message,
+            payload: Mutex::new(Some(payload)),
+        }
+    }
+
+    /// Returns the panic message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Takes the payload of the panic, so that it can be resumed
+    /// using [`std::panic::resume_unwind`].
+    ///
+    /// Returns `None` if the payload has already been taken.
+    pub fn take_payload(&self) -> Option<Box<dyn Any + Send + 'static>> {
+        self.payload.lock().unwrap().take()
+    }
+}
+
+impl fmt::Debug for HostPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostPanic")
+            .field("message", &self.message)
+            .finish()
+    }
+}
+
 /// Error that can occur during atomic operations. (notify/wait)
 // Non-exhaustive to allow for future variants without breaking changes!
 #[derive(PartialEq, Eq, Debug, Error)]
@@ -266,6 +334,13 @@ pub enum AtomicsError {
     TooManyWaiters,
     /// Atomic operations are disabled.
     AtomicsDisabled,
+    /// The address is not aligned to the size of the atomic access.
+    UnalignedAddress,
+    /// The address is outside the bounds of the memory.
+    OutOfBounds,
+    /// Blocking waits are not allowed on the current thread (for example the
+    /// browser main thread).
+    WaitNotAllowed,
 }
 
 impl std::fmt::Display for AtomicsError {
@@ -274,6 +349,9 @@ impl std::fmt::Display for AtomicsError {
             Self::Unimplemented => write!(f, "Atomic operations are not supported"),
             Self::TooManyWaiters => write!(f, "Too many waiters for address"),
             Self::AtomicsDisabled => write!(f, "Atomic operations are disabled"),
+            Self::UnalignedAddress => write!(f, "Unaligned atomic access"),
+            Self::OutOfBounds => write!(f, "Atomic access out of bounds"),
+            Self::WaitNotAllowed => write!(f, "Atomic wait is not allowed on this thread"),
         }
     }
 }
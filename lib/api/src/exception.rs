@@ -0,0 +1,67 @@
+use std::fmt;
+
+use crate::js::exception as exception_impl;
+use crate::store::AsStoreMut;
+use crate::{RuntimeError, Tag, Value};
+
+/// A WebAssembly exception, as defined by the exception-handling proposal.
+///
+/// Exceptions thrown by WebAssembly code and not caught there surface in
+/// the host as a [`RuntimeError`] which can be downcast to an `Exception`.
+/// Its payload can then be read back with the [`Tag`] it was thrown with.
+///
+/// Host functions can throw an exception into WebAssembly code by returning
+/// it as their error, where it can be caught by a `catch` clause for its tag.
+///
+/// # Usage
+///
+/// ```ignore
+/// # use wasmer::{Exception, FunctionEnvMut, RuntimeError, Tag, Value};
+/// fn fail(mut env: FunctionEnvMut<Tag>) -> Result<(), RuntimeError> {
+///     let tag = env.data().clone();
+///     Err(Exception::new(&mut env, &tag, &[Value::I32(42)])?.into())
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exception(pub(crate) exception_impl::Exception);
+
+impl Exception {
+    /// Creates a new `Exception` for `tag`, carrying the given `payload`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the payload doesn't match the parameters of the
+    /// tag's [`TagType`][crate::TagType].
+    pub fn new(
+        store: &mut impl AsStoreMut,
+        tag: &Tag,
+        payload: &[Value],
+    ) -> Result<Self, RuntimeError> {
+        Ok(Self(exception_impl::Exception::new(store, tag, payload)?))
+    }
+
+    /// Returns whether the exception was thrown with `tag`.
+    pub fn is(&self, tag: &Tag) -> bool {
+        self.0.is(tag)
+    }
+
+    /// Returns the values carried by the exception, or `None` if it wasn't
+    /// thrown with `tag`.
+    pub fn payload(&self, store: &mut impl AsStoreMut, tag: &Tag) -> Option<Vec<Value>> {
+        self.0.payload(store, tag)
+    }
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "uncaught WebAssembly exception")
+    }
+}
+
+impl std::error::Error for Exception {}
+
+impl From<Exception> for RuntimeError {
+    fn from(exception: Exception) -> Self {
+        Self::user(Box::new(exception))
+    }
+}
@@ -30,8 +30,9 @@ pub struct Memory(pub(crate) memory_impl::Memory);
 impl Memory {
     /// Creates a new host `Memory` from the provided [`MemoryType`].
     ///
-    /// This function will construct the `Memory` using the store
-    /// `BaseTunables`.
+    /// If the store's [`Engine`][crate::Engine] has a
+    /// [`MemoryCreator`][crate::MemoryCreator], it gets the chance to provide
+    /// an existing memory first.
     ///
     /// # Example
     ///
@@ -116,6 +117,23 @@ impl Memory {
         self.0.grow(store, delta)
     }
 
+    /// Registers a callback that is invoked with the new size of the memory
+    /// after it has grown.
+    ///
+    /// Growing a memory detaches its underlying `ArrayBuffer`, so any cached
+    /// [`MemoryView`] or typed array obtained from it must be re-created. Growth
+    /// performed by the host is reported immediately, while growth performed by
+    /// the guest is reported the next time a view of the memory is created.
+    ///
+    /// The callbacks are shared by all the handles to this memory.
+    pub fn on_grow(
+        &self,
+        store: &impl AsStoreRef,
+        callback: impl Fn(Pages) + Send + Sync + 'static,
+    ) {
+        self.0.on_grow(store, callback)
+    }
+
     /// Grows the memory to at least a minimum size. If the memory is already big enough
     /// for the min size then this function does nothing
     pub fn grow_at_least(
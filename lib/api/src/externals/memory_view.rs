@@ -1,8 +1,9 @@
 use super::memory::{Memory, MemoryBuffer};
 use crate::store::AsStoreRef;
-use crate::MemoryAccessError;
+use crate::{AtomicsError, MemoryAccessError};
 use std::mem::MaybeUninit;
 use std::ops::Range;
+use std::time::Duration;
 use wasmer_types::Pages;
 
 use crate::js::externals::memory_view as memory_view_impl;
@@ -139,6 +140,73 @@ impl<'a> MemoryView<'a> {
         self.0.write_u8(offset, val)
     }
 
+    /// Returns a `Uint8Array` that shares the given range of the memory,
+    /// without copying it.
+    ///
+    /// Like the view itself, the returned array must not be used after the
+    /// memory has grown.
+    pub fn as_uint8array(
+        &self,
+        range: Range<u64>,
+    ) -> Result<js_sys::Uint8Array, MemoryAccessError> {
+        self.0.as_uint8array(range)
+    }
+
+    /// Copies `dst.length` bytes starting at `offset` into a JS typed array,
+    /// without an intermediate buffer.
+    pub fn copy_to_typed_array(
+        &self,
+        offset: u64,
+        dst: &js_sys::Uint8Array,
+    ) -> Result<(), MemoryAccessError> {
+        self.0.copy_to_typed_array(offset, dst)
+    }
+
+    /// Copies a JS typed array into the memory starting at `offset`, without
+    /// an intermediate buffer.
+    pub fn copy_from_typed_array(
+        &self,
+        offset: u64,
+        src: &js_sys::Uint8Array,
+    ) -> Result<(), MemoryAccessError> {
+        self.0.copy_from_typed_array(offset, src)
+    }
+
+    /// Returns whether the memory is shared between threads, i.e. backed by
+    /// a `SharedArrayBuffer`.
+    pub fn is_shared(&self) -> bool {
+        self.0.is_shared()
+    }
+
+    /// Atomically loads the 32-bit value at the given offset.
+    ///
+    /// Fails if the memory is not shared or the offset is not 4-byte aligned.
+    pub fn atomic_load32(&self, offset: u64) -> Result<i32, AtomicsError> {
+        self.0.atomic_load32(offset)
+    }
+
+    /// Blocks until the 32-bit value at `offset` is notified, with the
+    /// semantics of `memory.atomic.wait32`.
+    ///
+    /// Returns `0` when woken up, `1` if the value did not equal `expected`
+    /// and `2` if the timeout elapsed.
+    pub fn atomic_wait32(
+        &self,
+        offset: u64,
+        expected: i32,
+        timeout: Option<Duration>,
+    ) -> Result<u32, AtomicsError> {
+        self.0.atomic_wait32(offset, expected, timeout)
+    }
+
+    /// Wakes up to `count` waiters on the 32-bit value at `offset`, with the
+    /// semantics of `memory.atomic.notify`.
+    ///
+    /// Returns the number of waiters that were woken up.
+    pub fn atomic_notify(&self, offset: u64, count: u32) -> Result<u32, AtomicsError> {
+        self.0.atomic_notify(offset, count)
+    }
+
     /// Copies the memory and returns it as a vector of bytes
     pub fn copy_to_vec(&self) -> Result<Vec<u8>, MemoryAccessError> {
         self.copy_range_to_vec(0..self.data_size())
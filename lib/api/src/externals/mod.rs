@@ -3,12 +3,14 @@ mod global;
 pub(crate) mod memory;
 mod memory_view;
 mod table;
+mod tag;
 
 pub use self::function::{Function, HostFunction};
 pub use self::global::Global;
 pub use self::memory::{Memory, MemoryLocation, SharedMemory};
 pub use self::memory_view::MemoryView;
 pub use self::table::Table;
+pub use self::tag::Tag;
 
 use crate::exports::{ExportError, Exportable};
 use crate::ExternType;
@@ -32,6 +34,8 @@ pub enum Extern {
     Table(Table),
     /// A external [`Memory`].
     Memory(Memory),
+    /// A external [`Tag`].
+    Tag(Tag),
 }
 
 impl Extern {
@@ -42,6 +46,7 @@ impl Extern {
             Self::Memory(ft) => ExternType::Memory(ft.ty(store)),
             Self::Table(tt) => ExternType::Table(tt.ty(store)),
             Self::Global(gt) => ExternType::Global(gt.ty(store)),
+            Self::Tag(tt) => ExternType::Tag(tt.ty(store)),
         }
     }
 
@@ -52,6 +57,7 @@ impl Extern {
             VMExtern::Memory(m) => Self::Memory(Memory::from_vm_extern(store, m)),
             VMExtern::Global(g) => Self::Global(Global::from_vm_extern(store, g)),
             VMExtern::Table(t) => Self::Table(Table::from_vm_extern(store, t)),
+            VMExtern::Tag(t) => Self::Tag(Tag::from_vm_extern(store, t)),
         }
     }
 
@@ -62,6 +68,7 @@ impl Extern {
             Self::Global(g) => g.is_from_store(store),
             Self::Memory(m) => m.is_from_store(store),
             Self::Table(t) => t.is_from_store(store),
+            Self::Tag(t) => t.is_from_store(store),
         }
     }
 
@@ -72,6 +79,7 @@ impl Extern {
             Self::Global(g) => g.to_vm_extern(),
             Self::Memory(m) => m.to_vm_extern(),
             Self::Table(t) => t.to_vm_extern(),
+            Self::Tag(t) => t.to_vm_extern(),
         }
     }
 }
@@ -93,6 +101,7 @@ impl fmt::Debug for Extern {
                 Self::Global(_) => "Global(...)",
                 Self::Memory(_) => "Memory(...)",
                 Self::Table(_) => "Table(...)",
+                Self::Tag(_) => "Tag(...)",
             }
         )
     }
@@ -121,3 +130,9 @@ impl From<Table> for Extern {
         Self::Table(r)
     }
 }
+
+impl From<Tag> for Extern {
+    fn from(r: Tag) -> Self {
+        Self::Tag(r)
+    }
+}
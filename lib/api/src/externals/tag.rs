@@ -0,0 +1,56 @@
+use crate::js::externals::tag as tag_impl;
+
+use crate::exports::{ExportError, Exportable};
+use crate::store::{AsStoreMut, AsStoreRef};
+use crate::vm::{VMExtern, VMExternTag};
+use crate::Extern;
+use crate::RuntimeError;
+use crate::TagType;
+
+/// A WebAssembly `tag` instance, as defined by the exception-handling
+/// proposal.
+///
+/// A tag identifies a kind of exception and describes the values carried
+/// by it. Exceptions thrown by WebAssembly code can be inspected from the
+/// host with the tag they were thrown with, and the host can throw
+/// exceptions into WebAssembly code with an [`Exception`][crate::Exception].
+///
+/// Spec: <https://webassembly.github.io/exception-handling/core/exec/runtime.html#tag-instances>
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tag(pub(crate) tag_impl::Tag);
+
+impl Tag {
+    /// Creates a new `Tag` with the provided [`TagType`] definition.
+    pub fn new(store: &mut impl AsStoreMut, ty: TagType) -> Result<Self, RuntimeError> {
+        Ok(Self(tag_impl::Tag::new(store, ty)?))
+    }
+
+    /// Returns the [`TagType`] of the `Tag`.
+    pub fn ty(&self, store: &impl AsStoreRef) -> TagType {
+        self.0.ty(store)
+    }
+
+    pub(crate) fn from_vm_extern(store: &mut impl AsStoreMut, extern_: VMExternTag) -> Self {
+        Self(tag_impl::Tag::from_vm_extern(store, extern_))
+    }
+
+    /// Checks whether this `Tag` can be used with the given context.
+    pub fn is_from_store(&self, store: &impl AsStoreRef) -> bool {
+        self.0.is_from_store(store)
+    }
+
+    pub(crate) fn to_vm_extern(&self) -> VMExtern {
+        self.0.to_vm_extern()
+    }
+}
+
+impl std::cmp::Eq for Tag {}
+
+impl<'a> Exportable<'a> for Tag {
+    fn get_self_from_extern(_extern: &'a Extern) -> Result<&'a Self, ExportError> {
+        match _extern {
+            Extern::Tag(tag) => Ok(tag),
+            _ => Err(ExportError::IncompatibleType),
+        }
+    }
+}
@@ -1,14 +1,16 @@
 use std::{any::Any, fmt::Debug, marker::PhantomData};
 
+use crate::js::store::KeepAlive;
 use crate::store::{AsStoreMut, AsStoreRef, StoreHandle, StoreMut, StoreObjects, StoreRef};
 use crate::vm::VMFunctionEnvironment;
 
 #[derive(Debug)]
-#[repr(transparent)]
 /// An opaque reference to a function environment.
-/// The function environment data is owned by the `Store`.
+/// The function environment data is owned by the `Store`, and is kept alive
+/// for as long as a `FunctionEnv` pointing to it exists.
 pub struct FunctionEnv<T> {
     pub(crate) handle: StoreHandle<VMFunctionEnvironment>,
+    pub(crate) keep_alive: KeepAlive,
     marker: PhantomData<T>,
 }
 
@@ -18,13 +20,9 @@ impl<T> FunctionEnv<T> {
     where
         T: Any + Send + 'static + Sized,
     {
-        Self {
-            handle: StoreHandle::new(
-                store.as_store_mut().objects_mut(),
-                VMFunctionEnvironment::new(value),
-            ),
-            marker: PhantomData,
-        }
+        let objects = store.objects_mut();
+        let handle = StoreHandle::new(objects, VMFunctionEnvironment::new(value));
+        Self::from_handle(handle, objects)
     }
 
     /// Get the data as reference
@@ -39,9 +37,12 @@ impl<T> FunctionEnv<T> {
             .unwrap()
     }
 
-    #[allow(dead_code)] // This function is only used in js
-    pub(crate) fn from_handle(handle: StoreHandle<VMFunctionEnvironment>) -> Self {
+    pub(crate) fn from_handle(
+        handle: StoreHandle<VMFunctionEnvironment>,
+        objects: &mut StoreObjects,
+    ) -> Self {
         Self {
+            keep_alive: objects.keep_alive(handle.internal_handle()),
             handle,
             marker: PhantomData,
         }
@@ -90,6 +91,7 @@ impl<T> Clone for FunctionEnv<T> {
     fn clone(&self) -> Self {
         Self {
             handle: self.handle.clone(),
+            keep_alive: self.keep_alive.clone(),
             marker: self.marker,
         }
     }
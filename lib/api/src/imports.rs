@@ -2,9 +2,11 @@
 //! manipulate and access a wasm module's imports including memories, tables, globals, and
 //! functions.
 
+use crate::store::AsStoreMut;
 use crate::{Exports, Extern, LinkError, Module};
 use std::collections::HashMap;
 use std::fmt;
+use wasm_bindgen::JsError;
 use wasmer_types::ImportError;
 
 /// All of the import data used when instantiating.
@@ -37,6 +39,7 @@ use wasmer_types::ImportError;
 #[derive(Clone, Default)]
 pub struct Imports {
     pub(crate) map: HashMap<(String, String), Extern>,
+    pub(crate) lazy_fallback: bool,
 }
 
 impl Imports {
@@ -45,6 +48,30 @@ impl Imports {
         Default::default()
     }
 
+    /// Create `Imports` from a plain JS imports object, like the ones passed
+    /// to `WebAssembly.instantiate()`.
+    ///
+    /// The object is walked as `{namespace: {name: value}}`, where each value
+    /// is either a JS function, which gets wrapped as a [`Function`], or a
+    /// `WebAssembly.Memory`.
+    ///
+    /// JS functions don't carry a WebAssembly signature, so the resulting
+    /// functions report an empty [`FunctionType`]. That doesn't matter when
+    /// they are passed to [`Instance::new()`], but calling them from Rust
+    /// requires [`Function::call()`] with the arguments the JS function
+    /// expects.
+    ///
+    /// [`Function`]: crate::Function
+    /// [`Function::call()`]: crate::Function::call
+    /// [`FunctionType`]: crate::FunctionType
+    /// [`Instance::new()`]: crate::Instance::new
+    pub fn from_js_object(
+        store: &mut impl AsStoreMut,
+        object: js_sys::Object,
+    ) -> Result<Self, JsError> {
+        crate::js::as_js::imports_from_js_object(store, &object)
+    }
+
     /// Gets an export given a module and a name
     ///
     /// # Usage
@@ -121,6 +148,26 @@ impl Imports {
             .insert((ns.to_string(), name.to_string()), val.into());
     }
 
+    /// Satisfy unresolved function imports with stubs when instantiating.
+    ///
+    /// Instead of failing to link, each function import that isn't provided
+    /// (neither here nor in the [`ImportsObj`]) is replaced by a stub that
+    /// traps with a descriptive error when it is called. This lets
+    /// partially-linked modules, like ones with optional Emscripten imports,
+    /// be instantiated as long as they never call the missing functions.
+    ///
+    /// Other kinds of imports still need to be provided.
+    ///
+    /// # Usage
+    /// ```no_run
+    /// # use wasmer::Imports;
+    /// let import_object = Imports::new().with_lazy_fallback();
+    /// ```
+    pub fn with_lazy_fallback(mut self) -> Self {
+        self.lazy_fallback = true;
+        self
+    }
+
     /// Returns the contents of a namespace as an `Exports`.
     ///
     /// Returns `None` if the namespace doesn't exist.
@@ -32,19 +32,21 @@ impl Instance {
     /// [`imports!`]: crate::imports!
     /// [`Imports!`]: crate::Imports!
     ///
+    /// Instantiation goes through the promise-based `WebAssembly.instantiate()`,
+    /// so awaiting the returned future doesn't block the browser's main thread
+    /// while big modules are being instantiated.
+    ///
     /// ```
     /// # use wasmer::{imports, Store, Module, Global, Value, Instance};
-    /// # use wasmer::FunctionEnv;
-    /// # fn main() -> anyhow::Result<()> {
+    /// # async fn run() -> anyhow::Result<()> {
     /// let mut store = Store::default();
-    /// let env = FunctionEnv::new(&mut store, ());
-    /// let module = Module::new(&store, "(module)")?;
+    /// let module = Module::new("(module)").await?;
     /// let imports = imports!{
     ///   "host" => {
     ///     "var" => Global::new(&mut store, Value::I32(2))
     ///   }
     /// };
-    /// let instance = Instance::new(&mut store, &module, &imports)?;
+    /// let instance = Instance::new(&mut store, &module, &imports, Default::default()).await?;
     /// # Ok(())
     /// # }
     /// ```
@@ -56,6 +58,9 @@ impl Instance {
     /// Those are, as defined by the spec:
     ///  * Link errors that happen when plugging the imports into the instance
     ///  * Runtime errors that happen when running the module `start` function.
+    ///
+    /// Imports coming from another [`Store`][crate::Store] are reported as
+    /// [`InstantiationError::DifferentStores`].
     #[allow(clippy::result_large_err)]
     pub async fn new(
         store: &mut impl AsStoreMut,
@@ -0,0 +1,452 @@
+//! Interruption of WebAssembly code which never calls into the host.
+//!
+//! The JS engine gives us no way to stop a running WebAssembly function, so
+//! the epoch deadline of a [`Store`][crate::Store] is only checked when
+//! execution passes through the host. [`Interruption`] rewrites the module's
+//! bytecode so that its loops also pass through the host every so often:
+//! each loop iteration counts down a global of the module, and once it
+//! reaches zero the loop calls an import provided by the store, which traps
+//! with [`TrapCode::Interrupt`] if the deadline has been reached.
+//!
+//! [`TrapCode::Interrupt`]: crate::TrapCode::Interrupt
+
+use std::fmt;
+
+use wasmer_types::CompileError;
+use wasmparser::{
+    BinaryReader, BinaryReaderError, ConstExpr, ElementSectionReader, ExternalKind, FunctionBody,
+    GlobalSectionReader, Operator, Parser, Payload, RefType, TypeRef,
+};
+
+use crate::metering::{skip_count, write_name, write_section, write_u32};
+
+/// The module name of the import the loops check the epoch deadline with.
+pub(crate) const INTERRUPT_NAMESPACE: &str = "__wasmer_interrupt";
+/// The field name of the import the loops check the epoch deadline with.
+pub(crate) const INTERRUPT_NAME: &str = "check";
+
+const TYPE_SECTION: u8 = 1;
+const IMPORT_SECTION: u8 = 2;
+const GLOBAL_SECTION: u8 = 6;
+const EXPORT_SECTION: u8 = 7;
+const START_SECTION: u8 = 8;
+const ELEMENT_SECTION: u8 = 9;
+const CODE_SECTION: u8 = 10;
+/// Stands for the end of the module among the section ids
+const END: u8 = u8::MAX;
+
+/// Instruments WebAssembly modules so that the epoch deadline of the
+/// [`Store`][crate::Store] they run in interrupts them even when they never
+/// call into the host, e.g. when they are stuck in a loop.
+///
+/// The deadline is checked every `interval` loop iterations, so the larger
+/// the interval, the cheaper the instrumentation but the later the guest is
+/// interrupted once the deadline is reached.
+///
+/// The check is an imported function, which shifts the indexes of the
+/// functions the module defines. The `name` section, which refers to them,
+/// is dropped.
+///
+/// # Usage
+///
+/// ```ignore
+/// # use wasmer::{Interruption, Module, Store};
+/// # async fn run(wasm: &[u8]) -> anyhow::Result<()> {
+/// let mut store = Store::default();
+/// let wasm = Interruption::default().instrument(wasm)?;
+/// let module = Module::new(wasm).await?;
+/// store.set_epoch_deadline(1);
+/// // From another worker, once the guest has run for long enough:
+/// store.interrupt_handle().increment_epoch();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Interruption {
+    interval: u32,
+}
+
+impl Interruption {
+    /// Create a new [`Interruption`] which checks the epoch deadline every
+    /// `interval` loop iterations.
+    pub fn new(interval: u32) -> Self {
+        Interruption {
+            interval: interval.max(1),
+        }
+    }
+
+    /// Instrument a WebAssembly binary.
+    ///
+    /// Only the binary format is supported, so text modules need to be
+    /// converted first.
+    pub fn instrument(&self, wasm: &[u8]) -> Result<Vec<u8>, CompileError> {
+        if Parser::is_component(wasm) {
+            return Err(CompileError::Validate(
+                "only core WebAssembly modules can be interrupted".to_string(),
+            ));
+        }
+
+        self.rewrite(wasm)
+            .map_err(|e| CompileError::Validate(e.message().to_string()))
+    }
+
+    fn rewrite(&self, wasm: &[u8]) -> Result<Vec<u8>, BinaryReaderError> {
+        let mut output = Vec::with_capacity(wasm.len() + wasm.len() / 8);
+        // The type of the check is added after all other types, its import
+        // after all other imports, so it takes the index of the first
+        // defined function and shifts the others by one. The countdown is
+        // defined after all other globals.
+        let mut check_type = 0;
+        let mut check_func = 0;
+        let mut countdown = 0;
+        let (mut typed, mut imported, mut counted) = (false, false, false);
+        let mut code = Vec::new();
+        let mut bodies_left = 0;
+
+        for payload in Parser::new(0).parse_all(wasm) {
+            let payload = payload?;
+
+            // Add the sections the module doesn't have, in their order, once
+            // the sections which follow them come. The end of the module
+            // follows them all.
+            let next = match &payload {
+                Payload::End(_) => Some(END),
+                other => other.as_section().map(|(id, _)| id),
+            };
+            if let Some(next) = next {
+                if !typed && next > TYPE_SECTION {
+                    let mut contents = Vec::new();
+                    write_u32(&mut contents, 1);
+                    write_check_type(&mut contents);
+                    write_section(&mut output, TYPE_SECTION, &contents);
+                    typed = true;
+                }
+                if !imported && next > IMPORT_SECTION {
+                    let mut contents = Vec::new();
+                    write_u32(&mut contents, 1);
+                    write_check_import(&mut contents, check_type);
+                    write_section(&mut output, IMPORT_SECTION, &contents);
+                    imported = true;
+                }
+                if !counted && follows_globals(next) {
+                    let mut contents = Vec::new();
+                    write_u32(&mut contents, 1);
+                    self.write_countdown(&mut contents);
+                    write_section(&mut output, GLOBAL_SECTION, &contents);
+                    counted = true;
+                }
+            }
+
+            let shift = |index: u32| shift_func(index, check_func);
+            match payload {
+                Payload::Version { range, .. } => {
+                    output.extend_from_slice(&wasm[range]);
+                }
+                Payload::TypeSection(types) => {
+                    for group in types.clone() {
+                        check_type += group?.types().len() as u32;
+                    }
+
+                    let mut contents = Vec::new();
+                    write_u32(&mut contents, types.count() + 1);
+                    contents.extend_from_slice(&wasm[skip_count(wasm, types.range())?]);
+                    write_check_type(&mut contents);
+                    write_section(&mut output, TYPE_SECTION, &contents);
+                    typed = true;
+                }
+                Payload::ImportSection(imports) => {
+                    for import in imports.clone() {
+                        match import?.ty {
+                            TypeRef::Func(_) => check_func += 1,
+                            TypeRef::Global(_) => countdown += 1,
+                            _ => {}
+                        }
+                    }
+
+                    let mut contents = Vec::new();
+                    write_u32(&mut contents, imports.count() + 1);
+                    contents.extend_from_slice(&wasm[skip_count(wasm, imports.range())?]);
+                    write_check_import(&mut contents, check_type);
+                    write_section(&mut output, IMPORT_SECTION, &contents);
+                    imported = true;
+                }
+                Payload::GlobalSection(globals) => {
+                    countdown += globals.count();
+
+                    let mut contents = Vec::new();
+                    write_u32(&mut contents, globals.count() + 1);
+                    rewrite_globals(wasm, globals, check_func, &mut contents)?;
+                    self.write_countdown(&mut contents);
+                    write_section(&mut output, GLOBAL_SECTION, &contents);
+                    counted = true;
+                }
+                Payload::ExportSection(exports) => {
+                    let mut contents = Vec::new();
+                    write_u32(&mut contents, exports.count());
+                    for export in exports {
+                        let export = export?;
+                        let (kind, index) = match export.kind {
+                            ExternalKind::Func => (0x00, shift(export.index)),
+                            ExternalKind::Table => (0x01, export.index),
+                            ExternalKind::Memory => (0x02, export.index),
+                            ExternalKind::Global => (0x03, export.index),
+                            ExternalKind::Tag => (0x04, export.index),
+                        };
+                        write_name(&mut contents, export.name);
+                        contents.push(kind);
+                        write_u32(&mut contents, index);
+                    }
+                    write_section(&mut output, EXPORT_SECTION, &contents);
+                }
+                Payload::StartSection { func, .. } => {
+                    let mut contents = Vec::new();
+                    write_u32(&mut contents, shift(func));
+                    write_section(&mut output, START_SECTION, &contents);
+                }
+                Payload::ElementSection(elements) => {
+                    let mut contents = Vec::new();
+                    write_u32(&mut contents, elements.count());
+                    rewrite_elements(wasm, elements, check_func, &mut contents)?;
+                    write_section(&mut output, ELEMENT_SECTION, &contents);
+                }
+                Payload::CodeSectionStart { count, .. } => {
+                    write_u32(&mut code, count);
+                    bodies_left = count;
+                    if bodies_left == 0 {
+                        write_section(&mut output, CODE_SECTION, &code);
+                    }
+                }
+                Payload::CodeSectionEntry(body) => {
+                    let body = self.instrument_body(wasm, &body, check_func, countdown)?;
+                    write_u32(&mut code, body.len() as u32);
+                    code.extend_from_slice(&body);
+
+                    bodies_left -= 1;
+                    if bodies_left == 0 {
+                        write_section(&mut output, CODE_SECTION, &code);
+                    }
+                }
+                // The names of the functions would be off by one
+                Payload::CustomSection(section) if section.name() == "name" => {}
+                other => {
+                    if let Some((id, range)) = other.as_section() {
+                        write_section(&mut output, id, &wasm[range]);
+                    }
+                }
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// Check the deadline at the start of every loop iteration, and shift
+    /// the indexes of the functions the body refers to.
+    fn instrument_body(
+        &self,
+        wasm: &[u8],
+        body: &FunctionBody<'_>,
+        check_func: u32,
+        countdown: u32,
+    ) -> Result<Vec<u8>, BinaryReaderError> {
+        let mut operators = body.get_operators_reader()?;
+        let locals = body.range().start..operators.original_position();
+        let mut output = wasm[locals].to_vec();
+
+        while !operators.eof() {
+            let start = operators.original_position();
+            let operator = operators.read()?;
+            let end = operators.original_position();
+
+            match operator {
+                Operator::Call { function_index } => {
+                    output.push(0x10);
+                    write_u32(&mut output, shift_func(function_index, check_func));
+                }
+                Operator::ReturnCall { function_index } => {
+                    output.push(0x12);
+                    write_u32(&mut output, shift_func(function_index, check_func));
+                }
+                Operator::RefFunc { function_index } => {
+                    output.push(0xd2);
+                    write_u32(&mut output, shift_func(function_index, check_func));
+                }
+                Operator::Loop { .. } => {
+                    output.extend_from_slice(&wasm[start..end]);
+                    self.write_check(&mut output, check_func, countdown);
+                }
+                _ => output.extend_from_slice(&wasm[start..end]),
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// Count down, and once zero is reached, rewind the countdown and check
+    /// the deadline.
+    fn write_check(&self, output: &mut Vec<u8>, check_func: u32, countdown: u32) {
+        // global.get $countdown
+        output.push(0x23);
+        write_u32(output, countdown);
+        // i32.const 1
+        output.extend_from_slice(&[0x41, 0x01]);
+        // i32.sub
+        output.push(0x6b);
+        // global.set $countdown
+        output.push(0x24);
+        write_u32(output, countdown);
+        // global.get $countdown
+        output.push(0x23);
+        write_u32(output, countdown);
+        // i32.eqz
+        output.push(0x45);
+        // if
+        output.extend_from_slice(&[0x04, 0x40]);
+        // i32.const $interval
+        output.push(0x41);
+        write_i32(output, self.interval as i32);
+        // global.set $countdown
+        output.push(0x24);
+        write_u32(output, countdown);
+        // call $check
+        output.push(0x10);
+        write_u32(output, check_func);
+        // end
+        output.push(0x0b);
+    }
+
+    /// A mutable `i32` global starting at the interval.
+    fn write_countdown(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(&[0x7f, 0x01, 0x41]);
+        write_i32(output, self.interval as i32);
+        output.push(0x0b);
+    }
+}
+
+impl Default for Interruption {
+    /// The deadline is checked every 1000 loop iterations.
+    fn default() -> Self {
+        Interruption::new(1_000)
+    }
+}
+
+impl fmt::Debug for Interruption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Interruption")
+            .field("interval", &self.interval)
+            .finish()
+    }
+}
+
+/// Whether the section comes after the global section.
+fn follows_globals(id: u8) -> bool {
+    // The tag section (13) comes before it, the data count section (12)
+    // after it.
+    matches!(id, 7..=12 | END)
+}
+
+fn shift_func(index: u32, check_func: u32) -> u32 {
+    if index >= check_func {
+        index + 1
+    } else {
+        index
+    }
+}
+
+/// Copy the globals, shifting the functions their initializers refer to.
+fn rewrite_globals(
+    wasm: &[u8],
+    globals: GlobalSectionReader<'_>,
+    check_func: u32,
+    output: &mut Vec<u8>,
+) -> Result<(), BinaryReaderError> {
+    for global in globals.into_iter_with_offsets() {
+        let (start, global) = global?;
+        let init = global.init_expr.get_binary_reader().original_position();
+        output.extend_from_slice(&wasm[start..init]);
+        rewrite_const_expr(wasm, &global.init_expr, check_func, output)?;
+    }
+    Ok(())
+}
+
+/// Copy the element segments, shifting the functions they refer to.
+fn rewrite_elements(
+    wasm: &[u8],
+    elements: ElementSectionReader<'_>,
+    check_func: u32,
+    output: &mut Vec<u8>,
+) -> Result<(), BinaryReaderError> {
+    for element in elements {
+        let range = element?.range;
+        let mut reader = BinaryReader::new_with_offset(&wasm[range.clone()], range.start);
+
+        // Everything up to the items is copied as is: the table, offset and
+        // type of the segment, depending on its flags
+        let flags = reader.read_var_u32()?;
+        let expressions = flags & 0b100 != 0;
+        if flags & 0b001 == 0 {
+            if flags & 0b010 != 0 {
+                reader.read_var_u32()?;
+            }
+            reader.read::<ConstExpr<'_>>()?;
+        }
+        if flags & 0b011 != 0 {
+            if expressions {
+                reader.read::<RefType>()?;
+            } else {
+                reader.read_u8()?;
+            }
+        }
+        output.extend_from_slice(&wasm[range.start..reader.original_position()]);
+
+        let count = reader.read_var_u32()?;
+        write_u32(output, count);
+        for _ in 0..count {
+            if expressions {
+                let expr = reader.read::<ConstExpr<'_>>()?;
+                rewrite_const_expr(wasm, &expr, check_func, output)?;
+            } else {
+                write_u32(output, shift_func(reader.read_var_u32()?, check_func));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn rewrite_const_expr(
+    wasm: &[u8],
+    expr: &ConstExpr<'_>,
+    check_func: u32,
+    output: &mut Vec<u8>,
+) -> Result<(), BinaryReaderError> {
+    let mut operators = expr.get_operators_reader();
+    while !operators.eof() {
+        let start = operators.original_position();
+        let operator = operators.read()?;
+        let end = operators.original_position();
+        match operator {
+            Operator::RefFunc { function_index } => {
+                output.push(0xd2);
+                write_u32(output, shift_func(function_index, check_func));
+            }
+            _ => output.extend_from_slice(&wasm[start..end]),
+        }
+    }
+    Ok(())
+}
+
+/// The type of the check, `[] -> []`.
+fn write_check_type(output: &mut Vec<u8>) {
+    output.extend_from_slice(&[0x60, 0x00, 0x00]);
+}
+
+/// An import of the check function.
+fn write_check_import(output: &mut Vec<u8>, check_type: u32) {
+    write_name(output, INTERRUPT_NAMESPACE);
+    write_name(output, INTERRUPT_NAME);
+    output.push(0x00);
+    write_u32(output, check_type);
+}
+
+fn write_i32(output: &mut Vec<u8>, value: i32) {
+    crate::metering::write_i64(output, value.into());
+}
@@ -1,20 +1,22 @@
 use js_sys::Function as JsFunction;
-use js_sys::WebAssembly::{Memory as JsMemory, Table as JsTable};
+use js_sys::WebAssembly::{Memory as JsMemory, Table as JsTable, Tag as JsTag};
 use std::collections::HashMap;
 use std::convert::TryInto;
 use wasm_bindgen::JsCast;
 use wasm_bindgen::{JsError, JsValue};
-use wasmer_types::ExternType;
+use wasmer_types::{ExternType, MemoryType, Pages, WASM_PAGE_SIZE};
 
 use crate::imports::Imports;
 use crate::instance::Instance;
+use crate::js::extern_ref::ExternRef as JsExternRef;
+use crate::js::externals::function as function_impl;
 use crate::js::instance::Instance as JsInstance;
-use crate::js::vm::{VMFunction, VMGlobal, VMMemory, VMTable};
+use crate::js::vm::{VMFunction, VMGlobal, VMMemory, VMTable, VMTag};
 use crate::js::wasm_bindgen_polyfill::Global as JsGlobal;
 use crate::store::{AsStoreMut, AsStoreRef};
 use crate::value::Value;
 use crate::Type;
-use crate::{Extern, Function, Global, Memory, Table};
+use crate::{Exception, Extern, ExternRef, Function, Global, Memory, Table, Tag};
 
 /// Convert the given type to a [`JsValue`].
 pub trait AsJs: Sized {
@@ -31,7 +33,7 @@ pub trait AsJs: Sized {
 }
 
 #[inline]
-pub fn param_from_js(ty: &Type, js_val: &JsValue) -> Value {
+pub fn param_from_js(store: &mut impl AsStoreMut, ty: &Type, js_val: &JsValue) -> Value {
     match ty {
         Type::I32 => Value::I32(js_val.as_f64().unwrap() as _),
         Type::I64 => Value::I64(if js_val.is_bigint() {
@@ -45,17 +47,30 @@ pub fn param_from_js(ty: &Type, js_val: &JsValue) -> Value {
             let big_num: u128 = js_sys::BigInt::from(js_val.clone()).try_into().unwrap();
             Value::V128(big_num)
         }
-        Type::ExternRef | Type::FuncRef => unimplemented!(
-            "The type `{:?}` is not yet supported in the JS Function API",
-            ty
-        ),
+        Type::FuncRef => {
+            if js_val.is_null() || js_val.is_undefined() {
+                Value::FuncRef(None)
+            } else {
+                let function = js_val.clone().unchecked_into::<JsFunction>();
+                Value::FuncRef(Some(Function(function_impl::Function::from_js_function(
+                    store, function,
+                ))))
+            }
+        }
+        Type::ExternRef => {
+            if js_val.is_null() || js_val.is_undefined() {
+                Value::ExternRef(None)
+            } else {
+                Value::ExternRef(Some(ExternRef(JsExternRef::from_jsvalue(store, js_val))))
+            }
+        }
     }
 }
 
 impl AsJs for Value {
     type DefinitionType = Type;
 
-    fn as_jsvalue(&self, _store: &impl AsStoreRef) -> JsValue {
+    fn as_jsvalue(&self, store: &impl AsStoreRef) -> JsValue {
         match self {
             Self::I32(i) => JsValue::from(*i),
             Self::I64(i) => JsValue::from(*i),
@@ -64,16 +79,33 @@ impl AsJs for Value {
             Self::V128(v) => JsValue::from(*v),
             Self::FuncRef(Some(func)) => func.0.handle.function.clone().into(),
             Self::FuncRef(None) => JsValue::null(),
-            Self::ExternRef(_) => unimplemented!(),
+            Self::ExternRef(Some(extern_ref)) => extern_ref.as_jsvalue(store),
+            Self::ExternRef(None) => JsValue::null(),
         }
     }
 
     fn from_jsvalue(
-        _store: &mut impl AsStoreMut,
+        store: &mut impl AsStoreMut,
         type_: &Self::DefinitionType,
         value: &JsValue,
     ) -> Result<Self, JsError> {
-        Ok(param_from_js(type_, value))
+        Ok(param_from_js(store, type_, value))
+    }
+}
+
+impl AsJs for ExternRef {
+    type DefinitionType = ();
+
+    fn as_jsvalue(&self, store: &impl AsStoreRef) -> JsValue {
+        self.0.as_jsvalue(store)
+    }
+
+    fn from_jsvalue(
+        store: &mut impl AsStoreMut,
+        _type_: &Self::DefinitionType,
+        value: &JsValue,
+    ) -> Result<Self, JsError> {
+        Ok(Self(JsExternRef::from_jsvalue(store, value)))
     }
 }
 
@@ -154,10 +186,63 @@ impl AsJs for Imports {
             }
         }
 
-        Ok(Self { map })
+        Ok(Self {
+            map,
+            ..Default::default()
+        })
     }
 }
 
+/// Build [`Imports`] from a `{namespace: {name: value}}` object without
+/// knowing which module they will be used with.
+///
+/// Since JS functions don't carry a WebAssembly signature, they are wrapped
+/// with an empty one. Memories are described by their current size.
+pub(crate) fn imports_from_js_object(
+    store: &mut impl AsStoreMut,
+    object: &js_sys::Object,
+) -> Result<Imports, JsError> {
+    let mut imports = Imports::new();
+    for module_entry in js_sys::Object::entries(object).iter() {
+        let module_entry: js_sys::Array = module_entry.into();
+        let module_name = module_entry.get(0).as_string().unwrap_or_default();
+        let namespace = module_entry.get(1);
+        if !namespace.is_object() {
+            return Err(JsError::new(&format!(
+                "The import namespace \"{module_name}\" must be an object, but received {namespace:?}",
+            )));
+        }
+
+        for import_entry in js_sys::Object::entries(namespace.unchecked_ref()).iter() {
+            let import_entry: js_sys::Array = import_entry.into();
+            let import_name = import_entry.get(0).as_string().unwrap_or_default();
+            let value = import_entry.get(1);
+
+            let extern_ = if let Some(function) = value.dyn_ref::<JsFunction>() {
+                let function = function_impl::Function::from_js_function(store, function.clone());
+                Extern::Function(Function(function))
+            } else if let Some(memory) = value.dyn_ref::<JsMemory>() {
+                let buffer = memory.buffer();
+                let shared = buffer.is_instance_of::<js_sys::SharedArrayBuffer>();
+                let byte_length = js_sys::Reflect::get(&buffer, &"byteLength".into())
+                    .ok()
+                    .and_then(|length| length.as_f64())
+                    .unwrap_or_default() as u64;
+                let pages = (byte_length / WASM_PAGE_SIZE as u64) as u32;
+                let ty = MemoryType::new(Pages(pages), None, shared);
+                Extern::Memory(Memory::from_jsvalue(store, &ty, &value)?)
+            } else {
+                return Err(JsError::new(&format!(
+                    "Unsupported value for the import \"{module_name}\".\"{import_name}\": {value:?}",
+                )));
+            };
+            imports.define(&module_name, &import_name, extern_);
+        }
+    }
+
+    Ok(imports)
+}
+
 impl AsJs for Extern {
     type DefinitionType = ExternType;
 
@@ -167,6 +252,7 @@ impl AsJs for Extern {
             Self::Function(function) => function.0.handle.function.clone().into(),
             Self::Table(table) => table.0.handle.table.clone().into(),
             Self::Global(global) => global.0.handle.global.clone().into(),
+            Self::Tag(tag) => tag.0.handle.tag.clone().into(),
         }
     }
 
@@ -192,6 +278,7 @@ impl AsJs for Extern {
             ExternType::Table(table_type) => {
                 Ok(Self::Table(Table::from_jsvalue(store, table_type, val)?))
             }
+            ExternType::Tag(tag_type) => Ok(Self::Tag(Tag::from_jsvalue(store, tag_type, val)?)),
         }
     }
 }
@@ -332,3 +419,53 @@ impl AsJs for Table {
         }
     }
 }
+
+impl AsJs for Tag {
+    type DefinitionType = crate::TagType;
+
+    fn as_jsvalue(&self, _store: &impl AsStoreRef) -> wasm_bindgen::JsValue {
+        self.0.handle.tag.clone().into()
+    }
+
+    fn from_jsvalue(
+        store: &mut impl AsStoreMut,
+        tag_type: &Self::DefinitionType,
+        value: &JsValue,
+    ) -> Result<Self, JsError> {
+        if value.is_instance_of::<JsTag>() {
+            Ok(Tag::from_vm_extern(
+                store,
+                VMTag::new(value.clone().unchecked_into::<JsTag>(), tag_type.clone()),
+            ))
+        } else {
+            Err(JsError::new(&format!(
+                "Extern expect to be of type Tag, but received {:?}",
+                value
+            )))
+        }
+    }
+}
+
+impl AsJs for Exception {
+    type DefinitionType = ();
+
+    fn as_jsvalue(&self, _store: &impl AsStoreRef) -> wasm_bindgen::JsValue {
+        self.0.handle.clone().into()
+    }
+
+    fn from_jsvalue(
+        _store: &mut impl AsStoreMut,
+        _: &Self::DefinitionType,
+        value: &JsValue,
+    ) -> Result<Self, JsError> {
+        match value.dyn_ref::<js_sys::WebAssembly::Exception>() {
+            Some(exception) => Ok(Exception(crate::js::exception::Exception::from_js(
+                exception.clone(),
+            ))),
+            None => Err(JsError::new(&format!(
+                "Expected a WebAssembly exception, but received {:?}",
+                value
+            ))),
+        }
+    }
+}
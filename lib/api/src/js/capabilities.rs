@@ -0,0 +1,98 @@
+use std::sync::OnceLock;
+
+use js_sys::{Reflect, Uint8Array, WebAssembly};
+use wasm_bindgen::JsValue;
+
+const HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+/// `(module (memory 1 1 shared))`
+const THREADS: &[u8] = &[0x05, 0x04, 0x01, 0x03, 0x01, 0x01];
+
+/// `(module (func (result v128) v128.const i64x2 0 0))`
+const SIMD: &[u8] = &[
+    0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7b, 0x03, 0x02, 0x01, 0x00, 0x0a, 0x16, 0x01, 0x14, 0x00,
+    0xfd, 0x0c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x0b,
+];
+
+/// `(module (memory i64 1))`
+const MEMORY64: &[u8] = &[0x05, 0x03, 0x01, 0x04, 0x01];
+
+/// `(module (func return_call 0))`
+const TAIL_CALLS: &[u8] = &[
+    0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x03, 0x02, 0x01, 0x00, 0x0a, 0x06, 0x01, 0x04, 0x00, 0x12,
+    0x00, 0x0b,
+];
+
+/// `(module (tag))`
+const EXCEPTIONS: &[u8] = &[
+    0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x0d, 0x03, 0x01, 0x00, 0x00,
+];
+
+/// The WebAssembly features supported by the JS engine the module is
+/// running on.
+///
+/// Features are detected by validating tiny modules which use them, so the
+/// results reflect what the engine accepts rather than what the browser
+/// claims to support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EngineCapabilities {
+    /// Shared memories and atomics can be used, which requires the page to
+    /// be cross-origin isolated.
+    pub threads: bool,
+    /// 128-bit SIMD instructions.
+    pub simd: bool,
+    /// Memories indexed with 64-bit addresses.
+    pub memory64: bool,
+    /// The `return_call` family of instructions.
+    pub tail_calls: bool,
+    /// Exception tags and the instructions to throw and catch exceptions.
+    pub exceptions: bool,
+    /// The JavaScript Promise Integration API, which lets WebAssembly code
+    /// suspend on a JS promise.
+    pub jspi: bool,
+}
+
+impl EngineCapabilities {
+    /// Detect the capabilities of the current JS engine.
+    ///
+    /// The detection only runs once, later calls return the same result.
+    pub fn detect() -> Self {
+        static CAPABILITIES: OnceLock<EngineCapabilities> = OnceLock::new();
+
+        *CAPABILITIES.get_or_init(|| {
+            let global = js_sys::global();
+            let webassembly = Reflect::get(&global, &"WebAssembly".into()).unwrap_or_default();
+
+            let capabilities = EngineCapabilities {
+                threads: validates(THREADS) && cross_origin_isolated(&global),
+                simd: validates(SIMD),
+                memory64: validates(MEMORY64),
+                tail_calls: validates(TAIL_CALLS),
+                exceptions: validates(EXCEPTIONS),
+                jspi: has(&webassembly, "Suspending"),
+            };
+            tracing::debug!(?capabilities, "detected the JS engine capabilities");
+            capabilities
+        })
+    }
+}
+
+fn validates(sections: &[u8]) -> bool {
+    let module = [&HEADER[..], sections].concat();
+    WebAssembly::validate(&Uint8Array::from(&module[..])).unwrap_or(false)
+}
+
+fn has(target: &JsValue, key: &str) -> bool {
+    target.is_object() && Reflect::has(target, &key.into()).unwrap_or(false)
+}
+
+/// Shared memories can only be sent to other workers when the page is
+/// cross-origin isolated. Outside of browsers there is no such restriction,
+/// so we only check that `SharedArrayBuffer` is available.
+fn cross_origin_isolated(global: &JsValue) -> bool {
+    match Reflect::get(global, &"crossOriginIsolated".into()) {
+        Ok(isolated) if !isolated.is_undefined() => isolated.is_truthy(),
+        _ => has(global, "SharedArrayBuffer"),
+    }
+}
@@ -1,17 +1,33 @@
+use std::{fmt, sync::Arc};
+
+use crate::engine::MemoryCreator;
+
 /// A WebAssembly `Universal` Engine.
-#[derive(Clone, Debug)]
-pub struct Engine;
+#[derive(Clone, Default)]
+pub struct Engine {
+    memory_creator: Option<Arc<dyn MemoryCreator>>,
+}
 
 impl Engine {
     pub(crate) fn deterministic_id(&self) -> &str {
         // All js engines have the same id
         "js-generic"
     }
+
+    pub(crate) fn set_memory_creator(&mut self, creator: Arc<dyn MemoryCreator>) {
+        self.memory_creator = Some(creator);
+    }
+
+    pub(crate) fn memory_creator(&self) -> Option<&dyn MemoryCreator> {
+        self.memory_creator.as_deref()
+    }
 }
 
-impl Default for Engine {
-    fn default() -> Self {
-        Engine
+impl fmt::Debug for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Engine")
+            .field("memory_creator", &self.memory_creator.is_some())
+            .finish()
     }
 }
 
@@ -1,5 +1,5 @@
 use crate::js::trap::Trap;
-use crate::RuntimeError;
+use crate::{Exception, RuntimeError};
 use wasm_bindgen::prelude::*;
 
 impl From<Trap> for RuntimeError {
@@ -15,14 +15,16 @@ impl From<Trap> for RuntimeError {
 }
 
 impl From<RuntimeError> for JsValue {
-    fn from(_err: RuntimeError) -> Self {
-        // err.inner.source.into()
-        unimplemented!();
+    fn from(err: RuntimeError) -> Self {
+        // Exceptions are thrown as-is, so WebAssembly code can catch them.
+        if let Some(exception) = err.downcast_ref::<Exception>() {
+            return exception.0.handle.clone().into();
+        }
+        Trap::user(Box::new(err)).into()
     }
 }
 
 pub(crate) fn raise(error: Box<dyn std::error::Error + Send + Sync>) -> ! {
-    let error = Trap::user(error);
-    let js_error: JsValue = error.into();
+    let js_error: JsValue = RuntimeError::user(error).into();
     wasm_bindgen::throw_val(js_error)
 }
@@ -0,0 +1,71 @@
+use js_sys::{Array, WebAssembly};
+
+use crate::errors::RuntimeError;
+use crate::js::as_js::{param_from_js, AsJs};
+use crate::js::js_handle::JsHandle;
+use crate::store::AsStoreMut;
+use crate::value::Value;
+use crate::Tag;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exception {
+    pub(crate) handle: JsHandle<WebAssembly::Exception>,
+}
+
+// The exception is only ever sent along with the `RuntimeError` it was
+// raised as, and the `JsHandle` checks it stays on the thread it came from.
+unsafe impl Send for Exception {}
+unsafe impl Sync for Exception {}
+
+impl Exception {
+    pub fn new(
+        store: &mut impl AsStoreMut,
+        tag: &Tag,
+        payload: &[Value],
+    ) -> Result<Self, RuntimeError> {
+        let ty = tag.ty(store);
+        let types = payload.iter().map(Value::ty).collect::<Vec<_>>();
+        if types != ty.params() {
+            return Err(RuntimeError::new(format!(
+                "the exception payload {:?} doesn't match the tag type {}",
+                types, ty
+            )));
+        }
+        if payload.iter().any(|value| !value.is_from_store(store)) {
+            return Err(RuntimeError::new("cannot pass Value across contexts"));
+        }
+
+        let args = payload
+            .iter()
+            .map(|value| value.as_jsvalue(store))
+            .collect::<Array>();
+        let exception = WebAssembly::Exception::new(&tag.0.handle.tag, &args)?;
+        Ok(Self::from_js(exception))
+    }
+
+    pub(crate) fn from_js(exception: WebAssembly::Exception) -> Self {
+        Self {
+            handle: JsHandle::new(exception),
+        }
+    }
+
+    pub fn is(&self, tag: &Tag) -> bool {
+        self.handle.is(&tag.0.handle.tag)
+    }
+
+    pub fn payload(&self, store: &mut impl AsStoreMut, tag: &Tag) -> Option<Vec<Value>> {
+        if !self.is(tag) {
+            return None;
+        }
+
+        let ty = tag.ty(store);
+        ty.params()
+            .iter()
+            .enumerate()
+            .map(|(i, param)| {
+                let arg = self.handle.get_arg(&tag.0.handle.tag, i as u32).ok()?;
+                Some(param_from_js(store, param, &arg))
+            })
+            .collect()
+    }
+}
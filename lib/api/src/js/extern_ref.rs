@@ -1,39 +1,86 @@
 use std::any::Any;
 
-use crate::js::vm::VMExternRef;
+use wasm_bindgen::JsValue;
+
+use crate::js::store::{InternalStoreHandle, KeepAlive, StoreHandle, StoreObjects};
+use crate::js::vm::{VMExternObj, VMExternRef};
 use crate::store::{AsStoreMut, AsStoreRef};
 
 #[derive(Debug, Clone)]
-#[repr(transparent)]
-pub struct ExternRef;
+pub struct ExternRef {
+    handle: StoreHandle<VMExternObj>,
+    _keep_alive: KeepAlive,
+}
 
 impl ExternRef {
-    pub fn new<T>(_store: &mut impl AsStoreMut, _value: T) -> Self
+    pub fn new<T>(store: &mut impl AsStoreMut, value: T) -> Self
     where
         T: Any + Send + Sync + 'static + Sized,
     {
-        unimplemented!("ExternRef is not yet supported in Javascript");
+        let objects = store.objects_mut();
+        let index = objects.insert_extern_obj(VMExternObj::new(value));
+        Self::from_index(objects, index)
+    }
+
+    fn from_index(objects: &mut StoreObjects, index: usize) -> Self {
+        let handle = unsafe {
+            StoreHandle::from_internal(
+                objects.id(),
+                InternalStoreHandle::from_index(index + 1).unwrap(),
+            )
+        };
+        Self::from_handle(objects, handle)
+    }
+
+    fn from_handle(objects: &mut StoreObjects, handle: StoreHandle<VMExternObj>) -> Self {
+        Self {
+            _keep_alive: objects.keep_alive(handle.internal_handle()),
+            handle,
+        }
     }
 
-    pub fn downcast<'a, T>(&self, _store: &'a impl AsStoreRef) -> Option<&'a T>
+    pub fn downcast<'a, T>(&self, store: &'a impl AsStoreRef) -> Option<&'a T>
     where
         T: Any + Send + Sync + 'static + Sized,
     {
-        unimplemented!("ExternRef is not yet supported in Javascript");
+        self.handle
+            .get(store.as_store_ref().objects())
+            .as_ref()?
+            .downcast_ref::<T>()
+    }
+
+    /// Wraps a JS value received from JavaScript or WebAssembly.
+    ///
+    /// If the value represents an object already known to the store,
+    /// a reference to that object is returned.
+    pub(crate) fn from_jsvalue(store: &mut impl AsStoreMut, value: &JsValue) -> Self {
+        let objects = store.objects_mut();
+        let index = match objects.extern_obj_index(value) {
+            Some(index) => index,
+            None => objects.insert_extern_obj(VMExternObj::from_js(value.clone())),
+        };
+        Self::from_index(objects, index)
+    }
+
+    /// Returns the JS value representing this reference.
+    pub(crate) fn as_jsvalue(&self, store: &impl AsStoreRef) -> JsValue {
+        self.handle.get(store.as_store_ref().objects()).js().clone()
     }
 
     pub(crate) fn vm_externref(&self) -> VMExternRef {
-        unimplemented!("ExternRef is not yet supported in Javascript");
+        VMExternRef(self.handle.internal_handle())
     }
 
     pub(crate) unsafe fn from_vm_externref(
-        _store: &mut impl AsStoreMut,
-        _vm_externref: VMExternRef,
+        store: &mut impl AsStoreMut,
+        vm_externref: VMExternRef,
     ) -> Self {
-        unimplemented!("ExternRef is not yet supported in Javascript");
+        let objects = store.objects_mut();
+        let handle = StoreHandle::from_internal(objects.id(), vm_externref.0);
+        Self::from_handle(objects, handle)
     }
 
-    pub fn is_from_store(&self, _store: &impl AsStoreRef) -> bool {
-        true
+    pub fn is_from_store(&self, store: &impl AsStoreRef) -> bool {
+        self.handle.store_id() == store.as_store_ref().objects().id()
     }
 }
@@ -1,56 +1,97 @@
-use crate::errors::RuntimeError;
+use crate::errors::{HostPanic, RuntimeError};
 use crate::externals::function::{HostFunction, HostFunctionKind, WithEnv, WithoutEnv};
 use crate::function_env::{FunctionEnv, FunctionEnvMut};
 use crate::js::as_js::{param_from_js, AsJs}; /* ValFuncRef */
-use crate::js::store::{InternalStoreHandle, StoreHandle};
+use crate::js::store::{InternalStoreHandle, KeepAlive, StoreHandle, StoreObject};
 use crate::js::vm::{VMExtern, VMFuncRef, VMFunction, VMFunctionCallback, VMFunctionEnvironment};
 use crate::native_type::{FromToNativeWasmType, IntoResult, NativeWasmTypeInto, WasmTypeList};
-use crate::store::{AsStoreMut, AsStoreRef, StoreMut};
+use crate::store::{AsStoreMut, AsStoreRef, HostCall, StoreMut};
 use crate::value::Value;
 use std::fmt;
 use std::iter::FromIterator;
 use std::marker::PhantomData;
 use std::panic::{self, AssertUnwindSafe};
 
-use wasmer_types::{FunctionType, NativeWasmType, RawValue};
+use wasmer_types::{FunctionType, NativeWasmType, RawValue, Type};
 
-use js_sys::{Array, Function as JSFunction};
+use js_sys::{Array, Function as JSFunction, Reflect};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 
 #[inline]
-fn result_to_js(val: &Value) -> JsValue {
+fn result_to_js(store: &impl AsStoreRef, val: &Value) -> JsValue {
     match val {
         Value::I32(i) => JsValue::from_f64(*i as _),
         Value::I64(i) => JsValue::from_f64(*i as _),
         Value::F32(f) => JsValue::from_f64(*f as _),
         Value::F64(f) => JsValue::from_f64(*f),
         Value::V128(f) => JsValue::from_f64(*f as _),
-        val => unimplemented!(
-            "The value `{:?}` is not yet supported in the JS Function API",
-            val
-        ),
+        Value::FuncRef(Some(func)) => func.0.handle.function.clone().into(),
+        Value::FuncRef(None) => JsValue::null(),
+        Value::ExternRef(Some(extern_ref)) => extern_ref.as_jsvalue(store),
+        Value::ExternRef(None) => JsValue::null(),
+    }
+}
+
+/// Convert the value returned by a JS function into its results.
+fn results_from_js(store: &mut impl AsStoreMut, types: &[Type], result: JsValue) -> Vec<Value> {
+    match types.len() {
+        0 => vec![],
+        1 => vec![param_from_js(store, &types[0], &result)],
+        _n => Array::from(&result)
+            .iter()
+            .zip(types)
+            .map(|(js_val, ty)| param_from_js(store, ty, &js_val))
+            .collect(),
     }
 }
 
 #[inline]
-fn results_to_js_array(values: &[Value]) -> Array {
-    Array::from_iter(values.iter().map(result_to_js))
+fn results_to_js_array(store: &impl AsStoreRef, values: &[Value]) -> Array {
+    Array::from_iter(values.iter().map(|val| result_to_js(store, val)))
 }
 
 #[derive(Clone, PartialEq)]
 pub struct Function {
     pub(crate) handle: VMFunction,
+    pub(crate) keep_alive: KeepAlive,
 }
 
 // Function can't be Send in js because it dosen't support `structuredClone`
 // https://developer.mozilla.org/en-US/docs/Web/API/structuredClone
 // unsafe impl Send for Function {}
 
-impl From<VMFunction> for Function {
-    fn from(handle: VMFunction) -> Self {
-        Self { handle }
-    }
+/// The signature of an exported WebAssembly function, if the engine
+/// supports the type reflection proposal, through `WebAssembly.Function.type()`.
+fn reflect_function_type(function: &JSFunction) -> Option<FunctionType> {
+    let webassembly = Reflect::get(&js_sys::global(), &"WebAssembly".into()).ok()?;
+    let constructor = Reflect::get(&webassembly, &"Function".into()).ok()?;
+    let type_of: JSFunction = Reflect::get(&constructor, &"type".into())
+        .ok()?
+        .dyn_into()
+        .ok()?;
+    let descriptor = type_of.call1(&constructor, function).ok()?;
+
+    let types = |key: &str| -> Option<Vec<Type>> {
+        let types: Array = Reflect::get(&descriptor, &key.into())
+            .ok()?
+            .dyn_into()
+            .ok()?;
+        types
+            .iter()
+            .map(|ty| match ty.as_string()?.as_str() {
+                "i32" => Some(Type::I32),
+                "i64" => Some(Type::I64),
+                "f32" => Some(Type::F32),
+                "f64" => Some(Type::F64),
+                "v128" => Some(Type::V128),
+                "anyfunc" | "funcref" => Some(Type::FuncRef),
+                "externref" => Some(Type::ExternRef),
+                _ => None,
+            })
+            .collect()
+    };
+    Some(FunctionType::new(types("parameters")?, types("results")?))
 }
 
 impl Function {
@@ -81,13 +122,15 @@ impl Function {
         let wrapped_func: JsValue = match function_type.results().len() {
             0 => Closure::wrap(Box::new(move |args: &Array| {
                 let mut store: StoreMut = unsafe { StoreMut::from_raw(raw_store as _) };
-                let env: FunctionEnvMut<T> = raw_env.clone().into_mut(&mut store);
+                store.inner.check_epoch_deadline()?;
+                store.inner.host_calls.increment();
                 let wasm_arguments = function_type
                     .params()
                     .iter()
                     .enumerate()
-                    .map(|(i, param)| param_from_js(param, &args.get(i as u32)))
+                    .map(|(i, param)| param_from_js(&mut store, param, &args.get(i as u32)))
                     .collect::<Vec<_>>();
+                let env: FunctionEnvMut<T> = raw_env.clone().into_mut(&mut store);
                 let _results = func(env, &wasm_arguments)?;
                 Ok(())
             })
@@ -95,29 +138,33 @@ impl Function {
             .into_js_value(),
             1 => Closure::wrap(Box::new(move |args: &Array| {
                 let mut store: StoreMut = unsafe { StoreMut::from_raw(raw_store as _) };
-                let env: FunctionEnvMut<T> = raw_env.clone().into_mut(&mut store);
+                store.inner.check_epoch_deadline()?;
+                store.inner.host_calls.increment();
                 let wasm_arguments = function_type
                     .params()
                     .iter()
                     .enumerate()
-                    .map(|(i, param)| param_from_js(param, &args.get(i as u32)))
+                    .map(|(i, param)| param_from_js(&mut store, param, &args.get(i as u32)))
                     .collect::<Vec<_>>();
+                let env: FunctionEnvMut<T> = raw_env.clone().into_mut(&mut store);
                 let results = func(env, &wasm_arguments)?;
-                return Ok(result_to_js(&results[0]));
+                Ok(result_to_js(&store, &results[0]))
             })
                 as Box<dyn FnMut(&Array) -> Result<JsValue, JsValue>>)
             .into_js_value(),
             _n => Closure::wrap(Box::new(move |args: &Array| {
                 let mut store: StoreMut = unsafe { StoreMut::from_raw(raw_store as _) };
-                let env: FunctionEnvMut<T> = raw_env.clone().into_mut(&mut store);
+                store.inner.check_epoch_deadline()?;
+                store.inner.host_calls.increment();
                 let wasm_arguments = function_type
                     .params()
                     .iter()
                     .enumerate()
-                    .map(|(i, param)| param_from_js(param, &args.get(i as u32)))
+                    .map(|(i, param)| param_from_js(&mut store, param, &args.get(i as u32)))
                     .collect::<Vec<_>>();
+                let env: FunctionEnvMut<T> = raw_env.clone().into_mut(&mut store);
                 let results = func(env, &wasm_arguments)?;
-                return Ok(results_to_js_array(&results));
+                Ok(results_to_js_array(&store, &results))
             })
                 as Box<dyn FnMut(&Array) -> Result<Array, JsValue>>)
             .into_js_value(),
@@ -126,7 +173,7 @@ impl Function {
         let dyn_func =
             JSFunction::new_with_args("f", "return f(Array.prototype.slice.call(arguments, 1))");
         let binded_func = dyn_func.bind1(&JsValue::UNDEFINED, &wrapped_func);
-        let vm_function = VMFunction::new(binded_func, func_ty);
+        let vm_function = VMFunction::new_host(binded_func, func_ty);
         Self::from_vm_extern(&mut store, vm_function)
     }
 
@@ -137,7 +184,7 @@ impl Function {
         Args: WasmTypeList,
         Rets: WasmTypeList,
     {
-        let store = store.as_store_mut();
+        let mut store = store.as_store_mut();
         if std::mem::size_of::<F>() != 0 {
             Self::closures_unsupported_panic();
         }
@@ -153,10 +200,8 @@ impl Function {
             &JsValue::from_f64(store.as_raw() as *mut u8 as usize as f64),
         );
         let ty = function.ty();
-        let vm_function = VMFunction::new(binded_func, ty);
-        Self {
-            handle: vm_function,
-        }
+        let vm_function = VMFunction::new_host(binded_func, ty);
+        Self::from_vm_extern(&mut store, vm_function)
     }
 
     pub fn new_typed_with_env<T, F, Args, Rets>(
@@ -169,7 +214,7 @@ impl Function {
         Args: WasmTypeList,
         Rets: WasmTypeList,
     {
-        let store = store.as_store_mut();
+        let mut store = store.as_store_mut();
         if std::mem::size_of::<F>() != 0 {
             Self::closures_unsupported_panic();
         }
@@ -186,10 +231,14 @@ impl Function {
             &JsValue::from_f64(env.handle.internal_handle().index() as f64),
         );
         let ty = function.ty();
-        let vm_function = VMFunction::new(binded_func, ty);
-        Self {
-            handle: vm_function,
-        }
+        let vm_function = VMFunction::new_host(binded_func, ty);
+        let function = Self::from_vm_extern(&mut store, vm_function);
+
+        // The function only refers to its environment by index, so the
+        // environment has to live at least as long as the function does.
+        let index = function.vm_funcref(&store).0.index() - 1;
+        VMFunction::list_mut(store.objects_mut()).add_dependency(index, env.keep_alive.clone());
+        function
     }
 
     pub fn ty(&self, _store: &impl AsStoreRef) -> FunctionType {
@@ -212,6 +261,8 @@ impl Function {
         store: &mut impl AsStoreMut,
         params: &[Value],
     ) -> Result<Box<[Value]>, RuntimeError> {
+        store.as_store_ref().inner.check_epoch_deadline()?;
+
         // Annotation is here to prevent spurious IDE warnings.
         let arr = js_sys::Array::new_with_length(params.len() as u32);
 
@@ -224,40 +275,114 @@ impl Function {
         }
 
         let result =
-            js_sys::Reflect::apply(&self.handle.function, &wasm_bindgen::JsValue::NULL, &arr)?;
-
-        let result_types = self.handle.ty.results();
-        match result_types.len() {
-            0 => Ok(Box::new([])),
-            1 => {
-                let value = param_from_js(&result_types[0], &result);
-                Ok(vec![value].into_boxed_slice())
-            }
-            _n => {
-                let result_array: Array = result.into();
-                Ok(result_array
-                    .iter()
-                    .enumerate()
-                    .map(|(i, js_val)| param_from_js(&result_types[i], &js_val))
-                    .collect::<Vec<_>>()
-                    .into_boxed_slice())
+            js_sys::Reflect::apply(&self.handle.function, &wasm_bindgen::JsValue::NULL, &arr)
+                .map_err(|e| store.as_store_ref().inner.out_of_fuel_or(e.into()))?;
+
+        Ok(results_from_js(store, self.handle.ty.results(), result).into_boxed_slice())
+    }
+
+    /// Wrap the function so that calls to it go through the store's
+    /// [`HostCallInterceptor`][crate::HostCallInterceptor], if one is set
+    /// when the function is called.
+    ///
+    /// The returned JS function is meant to be imported as `module`.`name`.
+    pub(crate) fn intercepted(
+        &self,
+        store: &mut impl AsStoreMut,
+        module: &str,
+        name: &str,
+    ) -> JsValue {
+        let raw_store = store.as_store_mut().as_raw() as *mut u8;
+        let function = self.handle.function.clone();
+        let ty = self.handle.ty.clone();
+        let (module, name) = (module.to_string(), name.to_string());
+
+        let wrapped_func: JsValue = Closure::wrap(Box::new(move |args: &Array| {
+            let mut store: StoreMut = unsafe { StoreMut::from_raw(raw_store as _) };
+            let Some(interceptor) = store.inner.host_call_interceptor.clone() else {
+                return js_sys::Reflect::apply(&function, &JsValue::UNDEFINED, args);
+            };
+
+            let wasm_arguments = ty
+                .params()
+                .iter()
+                .enumerate()
+                .map(|(i, param)| param_from_js(&mut store, param, &args.get(i as u32)))
+                .collect::<Vec<_>>();
+            let call = HostCall {
+                module: &module,
+                name: &name,
+                args: &wasm_arguments,
+            };
+            interceptor.before_call(&mut store, &call)?;
+
+            match js_sys::Reflect::apply(&function, &JsValue::UNDEFINED, args) {
+                Ok(result) => {
+                    let results = results_from_js(&mut store, ty.results(), result.clone());
+                    interceptor.after_call(&mut store, &call, Ok(&results));
+                    Ok(result)
+                }
+                Err(error) => {
+                    let error = RuntimeError::from(error);
+                    interceptor.after_call(&mut store, &call, Err(&error));
+                    Err(error.into())
+                }
             }
+        })
+            as Box<dyn FnMut(&Array) -> Result<JsValue, JsValue>>)
+        .into_js_value();
+
+        let dyn_func =
+            JSFunction::new_with_args("f", "return f(Array.prototype.slice.call(arguments, 1))");
+        dyn_func.bind1(&JsValue::UNDEFINED, &wrapped_func).into()
+    }
+
+    pub(crate) fn from_vm_extern(store: &mut impl AsStoreMut, internal: VMFunction) -> Self {
+        // Register the function in the store, so that it can be referenced
+        // as a `funcref`. Already registered functions are reused.
+        let objects = store.objects_mut();
+        let (index, handle) = match objects.function_index(&internal.function) {
+            Some(index) => (index, VMFunction::list(objects).get(index).clone()),
+            None => (objects.insert_function(internal.clone()), internal),
+        };
+        Self {
+            keep_alive: VMFunction::list_mut(objects).keep_alive(index),
+            handle,
         }
     }
 
-    pub(crate) fn from_vm_extern(_store: &mut impl AsStoreMut, internal: VMFunction) -> Self {
-        Self { handle: internal }
+    /// Wraps a function coming from JS, e.g. read from a table or passed
+    /// in an imports object.
+    ///
+    /// Its signature is the one it was registered in the store with, or
+    /// else the one reflected by the engine. Plain JS functions, and those
+    /// of engines which don't support the type reflection proposal, are
+    /// given an empty signature.
+    pub(crate) fn from_js_function(store: &mut impl AsStoreMut, function: JSFunction) -> Self {
+        let objects = store.objects_mut();
+        let ty = match objects.function_index(&function) {
+            Some(index) => VMFunction::list(objects).get(index).ty.clone(),
+            None => reflect_function_type(&function)
+                .unwrap_or_else(|| FunctionType::new(vec![], vec![])),
+        };
+        Self::from_vm_extern(store, VMFunction::new(function, ty))
     }
 
-    pub(crate) fn vm_funcref(&self, _store: &impl AsStoreRef) -> VMFuncRef {
-        unimplemented!();
+    pub(crate) fn vm_funcref(&self, store: &impl AsStoreRef) -> VMFuncRef {
+        let index = store
+            .as_store_ref()
+            .objects()
+            .function_index(&self.handle.function)
+            .expect("function is not registered in the store");
+        VMFuncRef(InternalStoreHandle::from_index(index + 1).unwrap())
     }
 
-    pub(crate) unsafe fn from_vm_funcref(
-        _store: &mut impl AsStoreMut,
-        _funcref: VMFuncRef,
-    ) -> Self {
-        unimplemented!();
+    pub(crate) unsafe fn from_vm_funcref(store: &mut impl AsStoreMut, funcref: VMFuncRef) -> Self {
+        let objects = store.objects_mut();
+        Self {
+            handle: funcref.0.get(objects).clone(),
+            keep_alive: objects.keep_alive(funcref.0),
+        }
     }
 
     #[track_caller]
@@ -353,13 +478,17 @@ macro_rules! impl_host_function {
                     {
                         let mut store = StoreMut::from_raw(store_ptr as *mut _);
                         let mut store2 = StoreMut::from_raw(store_ptr as *mut _);
+                        if let Err(trap) = store.inner.check_epoch_deadline() {
+                            crate::js::errors::raise(Box::new(trap));
+                        }
+                        store.inner.host_calls.increment();
 
                         let result = {
                             // let env: &Env = unsafe { &*(ptr as *const u8 as *const Env) };
                             let func: &Func = &*(&() as *const () as *const Func);
                             panic::catch_unwind(AssertUnwindSafe(|| {
                                 let handle: StoreHandle<VMFunctionEnvironment> = StoreHandle::from_internal(store2.objects_mut().id(), InternalStoreHandle::from_index(handle_index).unwrap());
-                                let env: FunctionEnvMut<T> = FunctionEnv::from_handle(handle).into_mut(&mut store2);
+                                let env: FunctionEnvMut<T> = FunctionEnv::from_handle(handle, store2.objects_mut()).into_mut(&mut store2);
                                 func(env, $( FromToNativeWasmType::from_native(NativeWasmTypeInto::from_abi(&mut store, $x)) ),* ).into_result()
                             }))
                         };
@@ -368,7 +497,7 @@ macro_rules! impl_host_function {
                             Ok(Ok(result)) => return result.into_c_struct(&mut store),
                             #[allow(deprecated)]
                             Ok(Err(trap)) => crate::js::errors::raise(Box::new(trap)),
-                            Err(_panic) => unimplemented!(),
+                            Err(panic) => crate::js::errors::raise(Box::new(HostPanic::new(panic))),
                         }
                     }
 
@@ -403,6 +532,10 @@ macro_rules! impl_host_function {
                         // let env: &Env = unsafe { &*(ptr as *const u8 as *const Env) };
                         let func: &Func = &*(&() as *const () as *const Func);
                         let mut store = StoreMut::from_raw(store_ptr as *mut _);
+                        if let Err(trap) = store.inner.check_epoch_deadline() {
+                            crate::js::errors::raise(Box::new(trap));
+                        }
+                        store.inner.host_calls.increment();
 
                         let result = panic::catch_unwind(AssertUnwindSafe(|| {
                             func($( FromToNativeWasmType::from_native(NativeWasmTypeInto::from_abi(&mut store, $x)) ),* ).into_result()
@@ -412,7 +545,7 @@ macro_rules! impl_host_function {
                             Ok(Ok(result)) => return result.into_c_struct(&mut store),
                             #[allow(deprecated)]
                             Ok(Err(trap)) => crate::js::errors::raise(Box::new(trap)),
-                            Err(_panic) => unimplemented!(),
+                            Err(panic) => crate::js::errors::raise(Box::new(HostPanic::new(panic))),
                         }
                     }
 
@@ -1,4 +1,5 @@
 use crate::errors::RuntimeError;
+use crate::js::as_js::{param_from_js, AsJs};
 use crate::js::wasm_bindgen_polyfill::Global as JSGlobal;
 use crate::store::{AsStoreMut, AsStoreRef};
 use crate::value::Value;
@@ -43,6 +44,8 @@ impl Global {
             Value::I64(i) => ("i64", JsValue::from_f64(i as _)),
             Value::F32(f) => ("f32", JsValue::from_f64(f as _)),
             Value::F64(f) => ("f64", JsValue::from_f64(f)),
+            Value::FuncRef(_) => ("anyfunc", val.as_jsvalue(store)),
+            Value::ExternRef(_) => ("externref", val.as_jsvalue(store)),
             _ => unimplemented!("The type is not yet supported in the JS Global API"),
         };
         // This is the value type as string, even though is incorrectly called "value"
@@ -54,7 +57,7 @@ impl Global {
             &mutability.is_mutable().into(),
         )?;
 
-        let js_global = JSGlobal::new(&descriptor, &value).unwrap();
+        let js_global = JSGlobal::new(&descriptor, &value)?;
         let vm_global = VMGlobal::new(js_global, global_ty);
 
         Ok(Self::from_vm_extern(store, vm_global))
@@ -84,16 +87,7 @@ impl Global {
                 Type::V128 => RawValue {
                     u128: value.as_f64().unwrap_or_default() as _,
                 },
-                Type::FuncRef => {
-                    unimplemented!();
-                    // Self::FuncRef(VMFuncRef::from_raw(raw).map(|f| Function::from_vm_funcref(store, f)))
-                }
-                Type::ExternRef => {
-                    unimplemented!();
-                    // Self::ExternRef(
-                    //     VMExternRef::from_raw(raw).map(|e| ExternRef::from_vm_externref(store, e)),
-                    // )
-                }
+                Type::FuncRef | Type::ExternRef => return param_from_js(store, &ty.ty, &value),
             };
             Value::from_raw(store, ty.ty, raw)
         }
@@ -117,6 +111,7 @@ impl Global {
             Value::I64(i) => JsValue::from_f64(i as _),
             Value::F32(f) => JsValue::from_f64(f as _),
             Value::F64(f) => JsValue::from_f64(f),
+            Value::FuncRef(_) | Value::ExternRef(_) => val.as_jsvalue(store),
             _ => {
                 return Err(RuntimeError::new(
                     "The type is not yet supported in the JS Global API".to_owned(),
@@ -129,7 +124,7 @@ impl Global {
 
     pub(crate) fn from_vm_extern(store: &mut impl AsStoreMut, vm_global: VMGlobal) -> Self {
         use crate::js::store::StoreObject;
-        VMGlobal::list_mut(store.objects_mut()).push(vm_global.clone());
+        VMGlobal::list_mut(store.objects_mut()).insert(vm_global.clone());
         Self { handle: vm_global }
     }
 
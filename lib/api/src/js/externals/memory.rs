@@ -1,10 +1,13 @@
+use crate::externals::memory::{MemoryLocation, SharedMemoryOps};
 use crate::js::vm::{VMExtern, VMMemory};
 use crate::mem_access::MemoryAccessError;
 use crate::store::{AsStoreMut, AsStoreRef, StoreObjects};
-use crate::MemoryType;
+use crate::{AtomicsError, MemoryType};
 use std::marker::PhantomData;
 use std::mem::MaybeUninit;
 use std::slice;
+use std::sync::Arc;
+use std::time::Duration;
 
 use tracing::warn;
 
@@ -12,7 +15,7 @@ use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use wasmer_types::Pages;
 
-use super::memory_view::MemoryView;
+use super::memory_view::{view_length, view_subarray, MemoryView};
 
 pub use wasmer_types::MemoryError;
 
@@ -46,6 +49,11 @@ extern "C" {
     /// [MDN documentation](https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/WebAssembly/Memory/grow)
     #[wasm_bindgen(catch, method, js_namespace = WebAssembly)]
     pub fn grow(this: &JSMemory, pages: u32) -> Result<u32, JsValue>;
+
+    /// The `grow()` method of a memory64 `Memory` object, which takes and
+    /// returns page counts as `BigInt`s.
+    #[wasm_bindgen(catch, method, js_namespace = WebAssembly, js_name = grow)]
+    pub fn grow64(this: &JSMemory, pages: u64) -> Result<u64, JsValue>;
 }
 
 #[derive(Debug, Clone)]
@@ -70,18 +78,70 @@ unsafe impl Sync for Memory {}
 
 impl Memory {
     pub fn new(store: &mut impl AsStoreMut, ty: MemoryType) -> Result<Self, MemoryError> {
-        let vm_memory = VMMemory::new(Self::js_memory_from_type(&ty)?, ty);
+        let created = store
+            .as_store_ref()
+            .engine()
+            .0
+            .memory_creator()
+            .and_then(|creator| creator.create_memory(&ty));
+        let js_memory = match created {
+            Some(js_memory) => Self::check_created_memory(js_memory, &ty)?,
+            None => Self::js_memory_from_type(&ty)?,
+        };
+        let vm_memory = VMMemory::new(js_memory, ty);
         Ok(Self::from_vm_extern(store, vm_memory))
     }
 
+    /// Checks that a memory provided by a [`MemoryCreator`] can be used as a
+    /// memory of type `ty`.
+    ///
+    /// [`MemoryCreator`]: crate::MemoryCreator
+    fn check_created_memory(
+        js_memory: js_sys::WebAssembly::Memory,
+        ty: &MemoryType,
+    ) -> Result<js_sys::WebAssembly::Memory, MemoryError> {
+        let view = MemoryView::new_raw(&js_memory);
+        if ty.shared && !view.is_shared() {
+            return Err(MemoryError::MemoryNotShared);
+        }
+        if view.size() < ty.minimum {
+            return Err(MemoryError::InvalidMemory {
+                reason: format!(
+                    "the provided memory has {} pages but at least {} are required",
+                    view.size().0,
+                    ty.minimum.0
+                ),
+            });
+        }
+
+        Ok(js_memory)
+    }
+
     pub(crate) fn js_memory_from_type(
         ty: &MemoryType,
     ) -> Result<js_sys::WebAssembly::Memory, MemoryError> {
         let descriptor = js_sys::Object::new();
 
-        js_sys::Reflect::set(&descriptor, &"initial".into(), &ty.minimum.0.into()).unwrap();
-        if let Some(max) = ty.maximum {
-            js_sys::Reflect::set(&descriptor, &"maximum".into(), &max.0.into()).unwrap();
+        // memory64 limits are expressed as `BigInt`s.
+        let pages = |pages: Pages| -> JsValue {
+            if ty.is_64() {
+                (pages.0 as u64).into()
+            } else {
+                pages.0.into()
+            }
+        };
+        if ty.is_64() {
+            js_sys::Reflect::set(&descriptor, &"address".into(), &"i64".into()).unwrap();
+        }
+        js_sys::Reflect::set(&descriptor, &"initial".into(), &pages(ty.minimum)).unwrap();
+        // Shared memories must declare a maximum, so default to the largest
+        // size allowed.
+        let maximum = match ty.maximum {
+            None if ty.shared => Some(Pages(wasmer_types::WASM_MAX_PAGES)),
+            max => max,
+        };
+        if let Some(max) = maximum {
+            js_sys::Reflect::set(&descriptor, &"maximum".into(), &pages(max)).unwrap();
         }
         js_sys::Reflect::set(&descriptor, &"shared".into(), &ty.shared.into()).unwrap();
 
@@ -117,6 +177,15 @@ impl Memory {
         MemoryView::new(self, store)
     }
 
+    pub fn on_grow(
+        &self,
+        _store: &impl AsStoreRef,
+        callback: impl Fn(Pages) + Send + Sync + 'static,
+    ) {
+        let size = MemoryView::new_raw(&self.handle.memory).size();
+        self.handle.grow_hooks.add(size, Arc::new(callback));
+    }
+
     pub fn grow<IntoPages>(
         &self,
         store: &mut impl AsStoreMut,
@@ -128,7 +197,12 @@ impl Memory {
         let pages = delta.into();
         let js_memory = &self.handle.memory;
         let our_js_memory: &JSMemory = JsCast::unchecked_from_js_ref(js_memory);
-        let new_pages = our_js_memory.grow(pages.0).map_err(|err| {
+        let new_pages = if self.handle.ty.is_64() {
+            our_js_memory.grow64(pages.0 as u64).map(|p| p as u32)
+        } else {
+            our_js_memory.grow(pages.0)
+        };
+        let new_pages = new_pages.map_err(|err| {
             if err.is_instance_of::<js_sys::RangeError>() {
                 MemoryError::CouldNotGrow {
                     current: self.view(&store.as_store_ref()).size(),
@@ -138,6 +212,7 @@ impl Memory {
                 MemoryError::Generic(err.as_string().unwrap())
             }
         })?;
+        self.handle.grow_hooks.notify(Pages(new_pages) + pages);
         Ok(Pages(new_pages))
     }
 
@@ -182,8 +257,30 @@ impl Memory {
     }
 
     pub fn as_shared(&self, _store: &impl AsStoreRef) -> Option<crate::SharedMemory> {
-        // Not supported.
-        None
+        if !MemoryView::new_raw(&self.handle.memory).is_shared() {
+            return None;
+        }
+        let ops = SharedMemoryHandle {
+            memory: self.handle.clone(),
+        };
+        Some(crate::SharedMemory::new(self.clone().into(), ops))
+    }
+}
+
+/// Atomic operations on a memory backed by a `SharedArrayBuffer`.
+struct SharedMemoryHandle {
+    memory: VMMemory,
+}
+
+impl SharedMemoryOps for SharedMemoryHandle {
+    fn notify(&self, dst: MemoryLocation, count: u32) -> Result<u32, AtomicsError> {
+        MemoryView::new_raw(&self.memory.memory).atomic_notify(dst.address as u64, count)
+    }
+
+    fn wait(&self, dst: MemoryLocation, timeout: Option<Duration>) -> Result<u32, AtomicsError> {
+        let view = MemoryView::new_raw(&self.memory.memory);
+        let expected = view.atomic_load32(dst.address as u64)?;
+        view.atomic_wait32(dst.address as u64, expected, timeout)
     }
 }
 
@@ -218,16 +315,16 @@ impl<'a> MemoryBuffer<'a> {
             .checked_add(buf.len() as u64)
             .ok_or(MemoryAccessError::Overflow)?;
         let view = unsafe { &*(self.base) };
-        if end > view.length().into() {
+        if end > view_length(view) {
             warn!(
                 "attempted to read ({} bytes) beyond the bounds of the memory view ({} > {})",
                 buf.len(),
                 end,
-                view.length()
+                view_length(view)
             );
             return Err(MemoryAccessError::HeapOutOfBounds);
         }
-        view.subarray(offset as _, end as _)
+        view_subarray(view, offset, end)
             .copy_to(unsafe { &mut slice::from_raw_parts_mut(buf.as_mut_ptr(), buf.len()) });
         Ok(())
     }
@@ -241,17 +338,17 @@ impl<'a> MemoryBuffer<'a> {
             .checked_add(buf.len() as u64)
             .ok_or(MemoryAccessError::Overflow)?;
         let view = unsafe { &*(self.base) };
-        if end > view.length().into() {
+        if end > view_length(view) {
             warn!(
                 "attempted to read ({} bytes) beyond the bounds of the memory view ({} > {})",
                 buf.len(),
                 end,
-                view.length()
+                view_length(view)
             );
             return Err(MemoryAccessError::HeapOutOfBounds);
         }
         let buf_ptr = buf.as_mut_ptr() as *mut u8;
-        view.subarray(offset as _, end as _)
+        view_subarray(view, offset, end)
             .copy_to(unsafe { &mut slice::from_raw_parts_mut(buf_ptr, buf.len()) });
 
         Ok(unsafe { slice::from_raw_parts_mut(buf_ptr, buf.len()) })
@@ -262,16 +359,16 @@ impl<'a> MemoryBuffer<'a> {
             .checked_add(data.len() as u64)
             .ok_or(MemoryAccessError::Overflow)?;
         let view = unsafe { &mut *(self.base) };
-        if end > view.length().into() {
+        if end > view_length(view) {
             warn!(
                 "attempted to write ({} bytes) beyond the bounds of the memory view ({} > {})",
                 data.len(),
                 end,
-                view.length()
+                view_length(view)
             );
             return Err(MemoryAccessError::HeapOutOfBounds);
         }
-        view.subarray(offset as _, end as _).copy_from(data);
+        view_subarray(view, offset, end).copy_from(data);
 
         Ok(())
     }
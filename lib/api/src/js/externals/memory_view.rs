@@ -1,13 +1,42 @@
-use std::{convert::TryInto, marker::PhantomData, mem::MaybeUninit, ops::Range, slice};
-use wasm_bindgen::JsCast;
-use wasmer_types::{Bytes, Pages};
+use std::{
+    convert::TryInto, marker::PhantomData, mem::MaybeUninit, ops::Range, slice, time::Duration,
+};
+use wasm_bindgen::{prelude::*, JsCast};
+use wasmer_types::{Pages, WASM_PAGE_SIZE};
 
 use crate::{
     js::externals::memory::{Memory, MemoryBuffer},
     mem_access::MemoryAccessError,
     store::AsStoreRef,
+    AtomicsError,
 };
 
+#[wasm_bindgen]
+extern "C" {
+    /// A `Uint8Array` indexed with `f64` offsets rather than `u32`, so that
+    /// views over memory64 heaps larger than 4GiB can be addressed.
+    #[wasm_bindgen(js_name = Uint8Array, extends = js_sys::Uint8Array)]
+    #[derive(Clone, Debug)]
+    pub(crate) type JSUint8Array;
+
+    #[wasm_bindgen(method, getter, js_name = length)]
+    fn length_f64(this: &JSUint8Array) -> f64;
+
+    #[wasm_bindgen(method, js_name = subarray)]
+    fn subarray_f64(this: &JSUint8Array, begin: f64, end: f64) -> js_sys::Uint8Array;
+}
+
+/// Returns the length of `view` in bytes.
+pub(crate) fn view_length(view: &js_sys::Uint8Array) -> u64 {
+    view.unchecked_ref::<JSUint8Array>().length_f64() as u64
+}
+
+/// Returns the `start..end` byte range of `view`, without copying.
+pub(crate) fn view_subarray(view: &js_sys::Uint8Array, start: u64, end: u64) -> js_sys::Uint8Array {
+    view.unchecked_ref::<JSUint8Array>()
+        .subarray_f64(start as f64, end as f64)
+}
+
 /// A WebAssembly `memory` view.
 ///
 /// A memory view is used to read and write to the linear memory.
@@ -23,19 +52,18 @@ pub struct MemoryView<'a> {
 
 impl<'a> MemoryView<'a> {
     pub(crate) fn new(memory: &Memory, _store: &'a (impl AsStoreRef + ?Sized)) -> Self {
-        Self::new_raw(&memory.handle.memory)
+        let view = Self::new_raw(&memory.handle.memory);
+        // Report any growth performed by the guest since the last view.
+        memory.handle.grow_hooks.notify(view.size());
+        view
     }
 
     pub(crate) fn new_raw(memory: &js_sys::WebAssembly::Memory) -> Self {
         let buffer = memory.buffer();
 
         // This also works for SharedArrayBuffer.
-        let size = buffer
-            .unchecked_ref::<js_sys::ArrayBuffer>()
-            .byte_length()
-            .into();
-
         let view = js_sys::Uint8Array::new(&buffer);
+        let size = view_length(&view);
 
         Self {
             view,
@@ -97,7 +125,7 @@ impl<'a> MemoryView<'a> {
     /// assert_eq!(m.size(), Pages(1));
     /// ```
     pub fn size(&self) -> Pages {
-        Bytes(self.size as usize).try_into().unwrap()
+        Pages((self.size / WASM_PAGE_SIZE as u64) as u32)
     }
 
     #[inline]
@@ -116,23 +144,18 @@ impl<'a> MemoryView<'a> {
     /// This method is guaranteed to be safe (from the host side) in the face of
     /// concurrent writes.
     pub fn read(&self, offset: u64, data: &mut [u8]) -> Result<(), MemoryAccessError> {
-        let view = &self.view;
-        let offset: u32 = offset.try_into().map_err(|_| MemoryAccessError::Overflow)?;
-        let len: u32 = data
-            .len()
-            .try_into()
-            .map_err(|_| MemoryAccessError::Overflow)?;
+        let len = data.len() as u64;
         let end = offset.checked_add(len).ok_or(MemoryAccessError::Overflow)?;
-        if end > view.length() {
+        if end > self.size {
             tracing::warn!(
                 "attempted to read ({} bytes) beyond the bounds of the memory view ({} > {})",
                 len,
                 end,
-                view.length()
+                self.size
             );
             Err(MemoryAccessError::HeapOutOfBounds)?;
         }
-        view.subarray(offset, end).copy_to(data);
+        view_subarray(&self.view, offset, end).copy_to(data);
         Ok(())
     }
 
@@ -141,17 +164,18 @@ impl<'a> MemoryView<'a> {
     /// This method is guaranteed to be safe (from the host side) in the face of
     /// concurrent writes.
     pub fn read_u8(&self, offset: u64) -> Result<u8, MemoryAccessError> {
-        let view = &self.view;
-        let offset: u32 = offset.try_into().map_err(|_| MemoryAccessError::Overflow)?;
-        if offset >= view.length() {
+        if offset >= self.size {
             tracing::warn!(
                 "attempted to read beyond the bounds of the memory view ({} >= {})",
                 offset,
-                view.length()
+                self.size
             );
             Err(MemoryAccessError::HeapOutOfBounds)?;
         }
-        Ok(view.get_index(offset))
+        match u32::try_from(offset) {
+            Ok(offset) => Ok(self.view.get_index(offset)),
+            Err(_) => Ok(view_subarray(&self.view, offset, offset + 1).get_index(0)),
+        }
     }
 
     /// Safely reads bytes from the memory at the given offset.
@@ -169,19 +193,14 @@ impl<'a> MemoryView<'a> {
         offset: u64,
         buf: &'b mut [MaybeUninit<u8>],
     ) -> Result<&'b mut [u8], MemoryAccessError> {
-        let view = &self.view;
-        let offset: u32 = offset.try_into().map_err(|_| MemoryAccessError::Overflow)?;
-        let len: u32 = buf
-            .len()
-            .try_into()
-            .map_err(|_| MemoryAccessError::Overflow)?;
+        let len = buf.len() as u64;
         let end = offset.checked_add(len).ok_or(MemoryAccessError::Overflow)?;
-        if end > view.length() {
+        if end > self.size {
             tracing::warn!(
                 "attempted to read ({} bytes) beyond the bounds of the memory view ({} > {})",
                 len,
                 end,
-                view.length()
+                self.size
             );
             Err(MemoryAccessError::HeapOutOfBounds)?;
         }
@@ -193,7 +212,7 @@ impl<'a> MemoryView<'a> {
         }
         let buf = unsafe { slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, buf.len()) };
 
-        view.subarray(offset, end).copy_to(buf);
+        view_subarray(&self.view, offset, end).copy_to(buf);
         Ok(buf)
     }
 
@@ -205,23 +224,18 @@ impl<'a> MemoryView<'a> {
     /// This method is guaranteed to be safe (from the host side) in the face of
     /// concurrent reads/writes.
     pub fn write(&self, offset: u64, data: &[u8]) -> Result<(), MemoryAccessError> {
-        let offset: u32 = offset.try_into().map_err(|_| MemoryAccessError::Overflow)?;
-        let len: u32 = data
-            .len()
-            .try_into()
-            .map_err(|_| MemoryAccessError::Overflow)?;
-        let view = &self.view;
+        let len = data.len() as u64;
         let end = offset.checked_add(len).ok_or(MemoryAccessError::Overflow)?;
-        if end > view.length() {
+        if end > self.size {
             tracing::warn!(
                 "attempted to write ({} bytes) beyond the bounds of the memory view ({} > {})",
                 len,
                 end,
-                view.length()
+                self.size
             );
             Err(MemoryAccessError::HeapOutOfBounds)?;
         }
-        view.subarray(offset, end).copy_from(data);
+        view_subarray(&self.view, offset, end).copy_from(data);
         Ok(())
     }
 
@@ -230,20 +244,162 @@ impl<'a> MemoryView<'a> {
     /// This method is guaranteed to be safe (from the host side) in the face of
     /// concurrent writes.
     pub fn write_u8(&self, offset: u64, val: u8) -> Result<(), MemoryAccessError> {
-        let view = &self.view;
-        let offset: u32 = offset.try_into().map_err(|_| MemoryAccessError::Overflow)?;
-        if offset >= view.length() {
+        if offset >= self.size {
             tracing::warn!(
                 "attempted to write beyond the bounds of the memory view ({} >= {})",
                 offset,
-                view.length()
+                self.size
             );
             Err(MemoryAccessError::HeapOutOfBounds)?;
         }
-        view.set_index(offset, val);
+        match u32::try_from(offset) {
+            Ok(offset) => self.view.set_index(offset, val),
+            Err(_) => view_subarray(&self.view, offset, offset + 1).set_index(0, val),
+        }
+        Ok(())
+    }
+
+    /// Checks that `start..end` lies within the view.
+    fn check_range(&self, start: u64, end: u64) -> Result<(), MemoryAccessError> {
+        if start > end {
+            Err(MemoryAccessError::Overflow)?;
+        }
+        if end > self.size {
+            tracing::warn!(
+                "attempted to access ({} bytes) beyond the bounds of the memory view ({} > {})",
+                end - start,
+                end,
+                self.size
+            );
+            Err(MemoryAccessError::HeapOutOfBounds)?;
+        }
+        Ok(())
+    }
+
+    /// Returns a `Uint8Array` sharing the given range of the memory, without
+    /// copying it.
+    ///
+    /// The returned array is detached once the memory grows, after which it
+    /// has a length of zero (or, for shared memories, no longer reflects the
+    /// whole memory).
+    pub fn as_uint8array(
+        &self,
+        range: Range<u64>,
+    ) -> Result<js_sys::Uint8Array, MemoryAccessError> {
+        self.check_range(range.start, range.end)?;
+        Ok(view_subarray(&self.view, range.start, range.end))
+    }
+
+    /// Copies `dst.length` bytes of the memory at `offset` into `dst`.
+    ///
+    /// The copy is performed by the JS engine, without going through an
+    /// intermediate buffer.
+    pub fn copy_to_typed_array(
+        &self,
+        offset: u64,
+        dst: &js_sys::Uint8Array,
+    ) -> Result<(), MemoryAccessError> {
+        let end = offset
+            .checked_add(view_length(dst))
+            .ok_or(MemoryAccessError::Overflow)?;
+        self.check_range(offset, end)?;
+        dst.set(&view_subarray(&self.view, offset, end), 0);
+        Ok(())
+    }
+
+    /// Copies the contents of `src` into the memory at `offset`.
+    ///
+    /// The copy is performed by the JS engine, without going through an
+    /// intermediate buffer.
+    pub fn copy_from_typed_array(
+        &self,
+        offset: u64,
+        src: &js_sys::Uint8Array,
+    ) -> Result<(), MemoryAccessError> {
+        let end = offset
+            .checked_add(view_length(src))
+            .ok_or(MemoryAccessError::Overflow)?;
+        self.check_range(offset, end)?;
+        view_subarray(&self.view, offset, end).set(src, 0);
         Ok(())
     }
 
+    /// Returns whether the memory is backed by a `SharedArrayBuffer`.
+    pub fn is_shared(&self) -> bool {
+        self.view
+            .buffer()
+            .is_instance_of::<js_sys::SharedArrayBuffer>()
+    }
+
+    /// Returns an `Int32Array` over the whole memory along with the index of
+    /// the 32-bit cell at `offset`.
+    fn atomic_cell(&self, offset: u64) -> Result<(js_sys::Int32Array, u32), AtomicsError> {
+        if !self.is_shared() {
+            return Err(AtomicsError::Unimplemented);
+        }
+        if offset % 4 != 0 {
+            return Err(AtomicsError::UnalignedAddress);
+        }
+        if offset.checked_add(4).map_or(true, |end| end > self.size) {
+            return Err(AtomicsError::OutOfBounds);
+        }
+        let index: u32 = (offset / 4)
+            .try_into()
+            .map_err(|_| AtomicsError::OutOfBounds)?;
+        let cells = js_sys::Int32Array::new(&self.view.buffer());
+        Ok((cells, index))
+    }
+
+    /// Atomically loads the 32-bit value at the given offset.
+    pub fn atomic_load32(&self, offset: u64) -> Result<i32, AtomicsError> {
+        let (cells, index) = self.atomic_cell(offset)?;
+        js_sys::Atomics::load(&cells, index).map_err(|_| AtomicsError::Unimplemented)
+    }
+
+    /// Blocks the current thread until the 32-bit value at `offset` is
+    /// notified, following the semantics of `memory.atomic.wait32`.
+    ///
+    /// Returns `0` if woken by a notify, `1` if the value did not match
+    /// `expected` and `2` if the timeout elapsed.
+    ///
+    /// Browsers do not allow blocking the main thread, in which case
+    /// [`AtomicsError::WaitNotAllowed`] is returned.
+    pub fn atomic_wait32(
+        &self,
+        offset: u64,
+        expected: i32,
+        timeout: Option<Duration>,
+    ) -> Result<u32, AtomicsError> {
+        let (cells, index) = self.atomic_cell(offset)?;
+        let result = match timeout {
+            Some(timeout) => js_sys::Atomics::wait_with_timeout(
+                &cells,
+                index,
+                expected,
+                timeout.as_secs_f64() * 1000.0,
+            ),
+            None => js_sys::Atomics::wait(&cells, index, expected),
+        }
+        .map_err(|_| AtomicsError::WaitNotAllowed)?;
+
+        match String::from(result).as_str() {
+            "ok" => Ok(0),
+            "not-equal" => Ok(1),
+            "timed-out" => Ok(2),
+            _ => Err(AtomicsError::Unimplemented),
+        }
+    }
+
+    /// Wakes up to `count` threads waiting on the 32-bit value at `offset`,
+    /// following the semantics of `memory.atomic.notify`.
+    ///
+    /// Returns the number of threads that were woken up.
+    pub fn atomic_notify(&self, offset: u64, count: u32) -> Result<u32, AtomicsError> {
+        let (cells, index) = self.atomic_cell(offset)?;
+        js_sys::Atomics::notify_with_count(&cells, index, count)
+            .map_err(|_| AtomicsError::Unimplemented)
+    }
+
     /// Copies the memory and returns it as a vector of bytes
     #[allow(unused)]
     pub fn copy_to_vec(&self) -> Result<Vec<u8>, MemoryAccessError> {
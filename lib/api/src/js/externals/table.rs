@@ -1,10 +1,55 @@
 use crate::errors::RuntimeError;
+use crate::js::as_js::{param_from_js, AsJs};
+use crate::js::trap::Trap;
 use crate::store::{AsStoreMut, AsStoreRef};
 use crate::value::Value;
 use crate::vm::VMExternTable;
-use crate::vm::{VMExtern, VMFunction, VMTable};
-use crate::{FunctionType, TableType};
-use js_sys::Function;
+use crate::vm::{VMExtern, VMTable};
+use crate::{TableType, Type};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasmer_types::TrapCode;
+
+#[wasm_bindgen]
+extern "C" {
+    /// The `WebAssembly.Table()` constructor creates a new `Table` object
+    /// which is an array of references.
+    ///
+    /// # Reimplementation
+    ///
+    /// We re-implement the table accessors because `wasm-bindgen` declares
+    /// them for tables of functions only, while tables can hold any
+    /// reference value.
+    ///
+    /// [MDN documentation](https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/WebAssembly/Table)
+    #[wasm_bindgen(js_namespace = WebAssembly, extends = js_sys::Object, typescript_type = "WebAssembly.Table")]
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub type JSTable;
+
+    /// The `get()` prototype method of the `WebAssembly.Table()` object
+    /// retrieves the reference stored at a given index.
+    ///
+    /// [MDN documentation](https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/WebAssembly/Table/get)
+    #[wasm_bindgen(catch, method, js_namespace = WebAssembly)]
+    pub fn get(this: &JSTable, index: u32) -> Result<JsValue, JsValue>;
+
+    /// The `set()` prototype method of the `WebAssembly.Table` object
+    /// mutates a reference stored at a given index to a different value.
+    ///
+    /// [MDN documentation](https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/WebAssembly/Table/set)
+    #[wasm_bindgen(catch, method, js_namespace = WebAssembly)]
+    pub fn set(this: &JSTable, index: u32, value: &JsValue) -> Result<(), JsValue>;
+
+    /// The `grow()` prototype method of the `WebAssembly.Table` object
+    /// increases the size of the table by a specified number of elements,
+    /// filling them with the given reference.
+    ///
+    /// Returns the previous length of the table.
+    ///
+    /// [MDN documentation](https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/WebAssembly/Table/grow)
+    #[wasm_bindgen(catch, method, js_namespace = WebAssembly)]
+    pub fn grow(this: &JSTable, delta: u32, value: &JsValue) -> Result<u32, JsValue>;
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Table {
@@ -15,17 +60,45 @@ pub struct Table {
 // https://developer.mozilla.org/en-US/docs/Web/API/structuredClone
 // unsafe impl Send for Table {}
 
-fn set_table_item(table: &VMTable, item_index: u32, item: &Function) -> Result<(), RuntimeError> {
-    table.table.set(item_index, item).map_err(|e| e.into())
+fn js_table(table: &VMTable) -> &JSTable {
+    JsCast::unchecked_from_js_ref(&table.table)
+}
+
+fn out_of_bounds() -> RuntimeError {
+    let code = TrapCode::TableAccessOutOfBounds;
+    RuntimeError::new_from_source(Trap::user(Box::new(code)), vec![], Some(code))
 }
 
-fn get_function(store: &mut impl AsStoreMut, val: Value) -> Result<Function, RuntimeError> {
+/// Checks that the `len` elements starting at `index` are within the table.
+fn check_bounds(table: &VMTable, index: u32, len: u32) -> Result<(), RuntimeError> {
+    match index.checked_add(len) {
+        Some(end) if end <= table.table.length() => Ok(()),
+        _ => Err(out_of_bounds()),
+    }
+}
+
+fn set_table_item(table: &VMTable, item_index: u32, item: &JsValue) -> Result<(), RuntimeError> {
+    js_table(table).set(item_index, item).map_err(|e| e.into())
+}
+
+fn get_item(
+    store: &mut impl AsStoreMut,
+    ty: &TableType,
+    val: Value,
+) -> Result<JsValue, RuntimeError> {
     if !val.is_from_store(store) {
         return Err(RuntimeError::new("cannot pass Value across contexts"));
     }
+    if val.ty() != ty.ty {
+        return Err(RuntimeError::new(format!(
+            "cannot store a value of type {} in a table of type {}",
+            val.ty(),
+            ty.ty
+        )));
+    }
     match val {
-        Value::FuncRef(Some(ref func)) => Ok(func.0.handle.function.clone().into_inner()),
-        // Only funcrefs is supported by the spec atm
+        Value::FuncRef(_) | Value::ExternRef(_) => Ok(val.as_jsvalue(store)),
+        // Only references are supported by the spec atm
         _ => unimplemented!("The {val:?} is not yet supported"),
     }
 }
@@ -42,15 +115,19 @@ impl Table {
         if let Some(max) = ty.maximum {
             js_sys::Reflect::set(&descriptor, &"maximum".into(), &max.into())?;
         }
-        js_sys::Reflect::set(&descriptor, &"element".into(), &"anyfunc".into())?;
+        let element = match ty.ty {
+            Type::ExternRef => "externref",
+            _ => "anyfunc",
+        };
+        js_sys::Reflect::set(&descriptor, &"element".into(), &element.into())?;
 
         let js_table = js_sys::WebAssembly::Table::new(&descriptor)?;
         let table = VMTable::new(js_table, ty);
 
         let num_elements = table.table.length();
-        let func = get_function(&mut store, init)?;
+        let item = get_item(&mut store, &ty, init)?;
         for i in 0..num_elements {
-            set_table_item(&table, i, &func)?;
+            set_table_item(&table, i, &item)?;
         }
 
         Ok(Self { handle: table })
@@ -65,14 +142,9 @@ impl Table {
     }
 
     pub fn get(&self, store: &mut impl AsStoreMut, index: u32) -> Option<Value> {
-        if let Some(func) = self.handle.table.get(index).ok() {
-            let ty = FunctionType::new(vec![], vec![]);
-            let vm_function = VMFunction::new(func, ty);
-            let function = crate::Function::from_vm_extern(store, vm_function);
-            Some(Value::FuncRef(Some(function)))
-        } else {
-            None
-        }
+        check_bounds(&self.handle, index, 1).ok()?;
+        let item = js_table(&self.handle).get(index).ok()?;
+        Some(param_from_js(store, &self.handle.ty.ty, &item))
     }
 
     pub fn set(
@@ -81,7 +153,8 @@ impl Table {
         index: u32,
         val: Value,
     ) -> Result<(), RuntimeError> {
-        let item = get_function(store, val)?;
+        check_bounds(&self.handle, index, 1)?;
+        let item = get_item(store, &self.handle.ty, val)?;
         set_table_item(&self.handle, index, &item)
     }
 
@@ -91,22 +164,53 @@ impl Table {
 
     pub fn grow(
         &self,
-        _store: &mut impl AsStoreMut,
-        _delta: u32,
-        _init: Value,
+        store: &mut impl AsStoreMut,
+        delta: u32,
+        init: Value,
     ) -> Result<u32, RuntimeError> {
-        unimplemented!();
+        let item = get_item(store, &self.handle.ty, init)?;
+        js_table(&self.handle).grow(delta, &item).map_err(|e| {
+            if e.is_instance_of::<js_sys::RangeError>() {
+                RuntimeError::new(format!(
+                    "failed to grow table by `{}` elements from `{}`",
+                    delta,
+                    self.handle.table.length()
+                ))
+            } else {
+                e.into()
+            }
+        })
     }
 
     pub fn copy(
         _store: &mut impl AsStoreMut,
-        _dst_table: &Self,
-        _dst_index: u32,
-        _src_table: &Self,
-        _src_index: u32,
-        _len: u32,
+        dst_table: &Self,
+        dst_index: u32,
+        src_table: &Self,
+        src_index: u32,
+        len: u32,
     ) -> Result<(), RuntimeError> {
-        unimplemented!("Table.copy is not natively supported in Javascript");
+        if dst_table.handle.ty.ty != src_table.handle.ty.ty {
+            return Err(RuntimeError::new(
+                "cross-`Table.copy` only works with tables of the same type",
+            ));
+        }
+        check_bounds(&src_table.handle, src_index, len)?;
+        check_bounds(&dst_table.handle, dst_index, len)?;
+
+        // `Table.copy` is not exposed to JavaScript, so the elements are
+        // copied one by one, back to front when the ranges may overlap.
+        let (src, dst) = (js_table(&src_table.handle), js_table(&dst_table.handle));
+        let copy = |i: u32| -> Result<(), RuntimeError> {
+            let item = src.get(src_index + i)?;
+            dst.set(dst_index + i, &item)?;
+            Ok(())
+        };
+        if dst_index > src_index {
+            (0..len).rev().try_for_each(copy)
+        } else {
+            (0..len).try_for_each(copy)
+        }
     }
 
     pub(crate) fn from_vm_extern(_store: &mut impl AsStoreMut, vm_extern: VMExternTable) -> Self {
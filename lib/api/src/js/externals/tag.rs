@@ -0,0 +1,59 @@
+use crate::errors::RuntimeError;
+use crate::store::{AsStoreMut, AsStoreRef};
+use crate::vm::{VMExtern, VMExternTag, VMTag};
+use crate::{TagType, Type};
+use js_sys::{Array, WebAssembly};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tag {
+    pub(crate) handle: VMTag,
+}
+
+// Tag can't be Send in js because it dosen't support `structuredClone`
+// https://developer.mozilla.org/en-US/docs/Web/API/structuredClone
+// unsafe impl Send for Tag {}
+
+impl Tag {
+    pub fn new(_store: &mut impl AsStoreMut, ty: TagType) -> Result<Self, RuntimeError> {
+        let parameters = ty
+            .params()
+            .iter()
+            .map(|param| {
+                let name = match param {
+                    Type::I32 => "i32",
+                    Type::I64 => "i64",
+                    Type::F32 => "f32",
+                    Type::F64 => "f64",
+                    Type::V128 => "v128",
+                    Type::ExternRef => "externref",
+                    Type::FuncRef => "anyfunc",
+                };
+                wasm_bindgen::JsValue::from_str(name)
+            })
+            .collect::<Array>();
+
+        let descriptor = js_sys::Object::new();
+        js_sys::Reflect::set(&descriptor, &"parameters".into(), &parameters)?;
+        let js_tag = WebAssembly::Tag::new(&descriptor)?;
+
+        Ok(Self {
+            handle: VMTag::new(js_tag, ty),
+        })
+    }
+
+    pub fn to_vm_extern(&self) -> VMExtern {
+        VMExtern::Tag(self.handle.clone())
+    }
+
+    pub fn ty(&self, _store: &impl AsStoreRef) -> TagType {
+        self.handle.ty.clone()
+    }
+
+    pub(crate) fn from_vm_extern(_store: &mut impl AsStoreMut, vm_extern: VMExternTag) -> Self {
+        Self { handle: vm_extern }
+    }
+
+    pub fn is_from_store(&self, _store: &impl AsStoreRef) -> bool {
+        true
+    }
+}
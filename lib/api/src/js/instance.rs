@@ -1,6 +1,7 @@
 use crate::exports::{Exports, ExportsObj};
 use crate::imports::{Imports, ImportsObj};
 use crate::js::as_js::AsJs;
+use crate::js::store::KeepAlive;
 use crate::js::vm::VMInstance;
 use crate::module::Module;
 use crate::store::AsStoreMut;
@@ -11,6 +12,9 @@ use js_sys::WebAssembly;
 #[derive(Clone, PartialEq, Eq)]
 pub struct Instance {
     pub(crate) _handle: JsHandle<VMInstance>,
+    /// The imported functions, which have to stay in the store for as long as
+    /// the instance can call them.
+    _imports: Vec<KeepAlive>,
 }
 
 // Instance can't be Send in js because it dosen't support `structuredClone`
@@ -27,10 +31,20 @@ impl Instance {
         let instance = module
             .0
             .instantiate(&mut store, imports, imports_obj)
-            .await
-            .map_err(|e| InstantiationError::Start(e))?;
+            .await?;
 
-        Self::from_module_and_instance(store, &module, instance)
+        let (mut instance, exports, exports_obj) =
+            Self::from_module_and_instance(store, module, instance)?;
+        instance._imports = module
+            .imports()
+            .filter_map(|import| imports.get_export(import.module(), import.name()))
+            .filter_map(|import| match import {
+                Extern::Function(function) => Some(function.0.keep_alive),
+                _ => None,
+            })
+            .collect();
+
+        Ok((instance, exports, exports_obj))
     }
 
     /// Creates a Wasmer `Instance` from a Wasmer `Module` and a WebAssembly Instance
@@ -56,6 +70,7 @@ impl Instance {
 
         let instance = Instance {
             _handle: JsHandle::new(instance),
+            _imports: Vec::new(),
         };
 
         Ok((instance, exports, ExportsObj(instance_exports)))
@@ -1,6 +1,8 @@
-mod as_js;
+pub(crate) mod as_js;
+pub(crate) mod capabilities;
 pub(crate) mod engine;
 pub(crate) mod errors;
+pub(crate) mod exception;
 pub(crate) mod extern_ref;
 pub(crate) mod externals;
 pub(crate) mod instance;
@@ -11,6 +13,9 @@ pub(crate) mod store;
 pub(crate) mod trap;
 pub(crate) mod typed_function;
 pub(crate) mod vm;
-mod wasm_bindgen_polyfill;
+pub(crate) mod wasm_bindgen_polyfill;
 
-pub use self::{as_js::AsJs, js_handle::current_thread_id, module::ModuleTypeHints};
+pub use self::{
+    as_js::AsJs, capabilities::EngineCapabilities, js_handle::current_thread_id,
+    module::ModuleTypeHints,
+};
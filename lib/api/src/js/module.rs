@@ -1,17 +1,32 @@
-use crate::errors::RuntimeError;
+use crate::errors::{LinkError, RuntimeError};
 use crate::imports::{Imports, ImportsObj};
+use crate::interruption::{INTERRUPT_NAME, INTERRUPT_NAMESPACE};
 use crate::js::AsJs;
+use crate::metering::{FUEL_NAME, FUEL_NAMESPACE};
 use crate::store::AsStoreMut;
 use crate::vm::VMInstance;
 use crate::IntoBytes;
 use crate::{errors::InstantiationError, js::js_handle::JsHandle};
-use crate::{ExportType, ImportType};
+use crate::{ExportType, Extern, Function, ImportType};
 use bytes::Bytes;
 use js_sys::{Reflect, Uint8Array, WebAssembly};
+use std::sync::Arc;
 use tracing::{trace, warn};
 use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_futures::JsFuture;
-use wasmer_types::{CompileError, ExportsIterator, ExternType, ImportsIterator, ModuleInfo};
+use wasmer_types::{
+    CompileError, CustomSectionIndex, ExportsIterator, ExternType, FunctionIndex, FunctionType,
+    ImportError, ImportsIterator, ModuleInfo,
+};
+
+fn js_compile_error(err: JsValue) -> CompileError {
+    let message = if let Some(err) = err.dyn_ref::<js_sys::Error>() {
+        String::from(err.message())
+    } else {
+        err.as_string().unwrap_or_else(|| format!("{err:?}"))
+    };
+    CompileError::Validate(message)
+}
 
 /// WebAssembly in the browser doesn't yet output the descriptor/types
 /// corresponding to each extern (import and export).
@@ -38,6 +53,14 @@ pub struct Module {
     name: Option<String>,
     /// WebAssembly type hints
     pub type_hints: ModuleTypeHints,
+    /// Information parsed from the binary, including the custom sections
+    /// and the name section.
+    info: Arc<ModuleInfo>,
+    /// Offsets of the bodies of the locally defined functions.
+    function_offsets: Arc<Vec<u32>>,
+    /// The names of all the custom sections, in order, and where their
+    /// data is in the info.
+    custom_sections: Arc<Vec<(String, CustomSectionIndex)>>,
     /// Raw bytes.
     pub raw_bytes: Bytes,
 }
@@ -71,6 +94,32 @@ impl Module {
         Ok(Self::from_module_and_binary(module, binary))
     }
 
+    /// Creates a new WebAssembly module by compiling the body of a `fetch`
+    /// response while it is being downloaded.
+    pub(crate) async fn from_response(response: web_sys::Response) -> Result<Self, CompileError> {
+        if !response.ok() {
+            return Err(CompileError::Resource(format!(
+                "failed to fetch the module: HTTP status {}",
+                response.status()
+            )));
+        }
+
+        // The raw bytes are still needed to parse the type hints, so a copy
+        // of the body is read alongside the streaming compilation.
+        let body = response.clone().map_err(js_compile_error)?;
+        let module = WebAssembly::compile_streaming(&js_sys::Promise::resolve(&response));
+        let buffer = body.array_buffer().map_err(js_compile_error)?;
+
+        let module: WebAssembly::Module = JsFuture::from(module)
+            .await
+            .map_err(js_compile_error)?
+            .unchecked_into();
+        let buffer = JsFuture::from(buffer).await.map_err(js_compile_error)?;
+        let binary = Uint8Array::new(&buffer).to_vec();
+
+        Ok(Self::from_module_and_binary(module, binary))
+    }
+
     /// Creates a new WebAssembly module from the compiled module and its binary data.
     pub(crate) fn from_module_and_binary(
         module: WebAssembly::Module,
@@ -97,7 +146,10 @@ impl Module {
         Self {
             module: JsHandle::new(module),
             type_hints,
-            name: info.info.name,
+            name: info.info.name.clone(),
+            info: Arc::new(info.info),
+            function_offsets: Arc::new(info.function_offsets),
+            custom_sections: Arc::new(info.custom_sections),
             raw_bytes: binary,
         }
     }
@@ -115,19 +167,69 @@ impl Module {
         store: &mut impl AsStoreMut,
         imports: &Imports,
         imports_obj: ImportsObj,
-    ) -> Result<VMInstance, RuntimeError> {
+    ) -> Result<VMInstance, InstantiationError> {
         // Ensure all imports come from the same store.
         if imports
             .into_iter()
             .any(|(_, import)| !import.is_from_store(store))
         {
-            return Err(RuntimeError::user(Box::new(
-                InstantiationError::DifferentStores,
-            )));
+            return Err(InstantiationError::DifferentStores);
         }
 
+        let imports_object = self
+            .resolve_imports(store, imports, imports_obj)
+            .map_err(InstantiationError::Link)?;
+
+        tracing::trace!(
+            "instantiating module {}",
+            self.name.as_deref().unwrap_or_default()
+        );
+        // `WebAssembly.instantiate()` does the work asynchronously instead of
+        // blocking the current thread like `new WebAssembly.Instance()` does,
+        // which matters for big modules on the browser's main thread.
+        JsFuture::from(WebAssembly::instantiate_module(
+            &self.module,
+            &imports_object,
+        ))
+        .await
+        .map(|v| v.unchecked_into())
+        .map_err(|e| {
+            if e.is_instance_of::<WebAssembly::LinkError>() {
+                InstantiationError::Link(LinkError::Trap(e.into()))
+            } else {
+                InstantiationError::Start(e.into())
+            }
+        })
+    }
+
+    /// Add the resolved imports to the JS imports object.
+    #[allow(clippy::result_large_err)]
+    fn resolve_imports(
+        &self,
+        store: &mut impl AsStoreMut,
+        imports: &Imports,
+        imports_obj: ImportsObj,
+    ) -> Result<js_sys::Object, LinkError> {
         let imports_object = imports_obj.0;
 
+        if self.is_metered() {
+            let fuel = store.as_store_mut().inner.fuel_global().clone();
+            let namespace = js_sys::Object::new();
+            js_sys::Reflect::set(&namespace, &FUEL_NAME.into(), &fuel).map_err(js_link_error)?;
+            js_sys::Reflect::set(&imports_object, &FUEL_NAMESPACE.into(), &namespace)
+                .map_err(js_link_error)?;
+        }
+
+        if self.is_interruptible() {
+            // Traps once the epoch deadline of the store is reached
+            let check = Function::new_typed(store, || {}).as_jsvalue(&store.as_store_ref());
+            let namespace = js_sys::Object::new();
+            js_sys::Reflect::set(&namespace, &INTERRUPT_NAME.into(), &check)
+                .map_err(js_link_error)?;
+            js_sys::Reflect::set(&imports_object, &INTERRUPT_NAMESPACE.into(), &namespace)
+                .map_err(js_link_error)?;
+        }
+
         for import_type in self.imports() {
             let resolved_import = imports.get_export(import_type.module(), import_type.name());
 
@@ -147,22 +249,32 @@ impl Module {
             if let Some(import) = resolved_import {
                 // Get or create the import namespace.
                 let mut import_namespace =
-                    js_sys::Reflect::get(&imports_object, &import_type.module().into())?;
+                    js_sys::Reflect::get(&imports_object, &import_type.module().into())
+                        .map_err(js_link_error)?;
                 if import_namespace.is_undefined() {
                     import_namespace = js_sys::Object::new().into();
                     js_sys::Reflect::set(
                         &imports_object,
                         &import_type.module().into(),
                         &import_namespace.clone().into(),
-                    )?;
+                    )
+                    .map_err(js_link_error)?;
                 }
 
                 // Set the import on the namespace.
-                js_sys::Reflect::set(
-                    &import_namespace,
-                    &import_type.name().into(),
-                    &import.as_jsvalue(&store.as_store_ref()),
-                )?;
+                let value = match import {
+                    Extern::Function(function)
+                        if function.0.handle.host
+                            && store.as_store_ref().inner.host_call_interceptor.is_some() =>
+                    {
+                        function
+                            .0
+                            .intercepted(store, import_type.module(), import_type.name())
+                    }
+                    _ => import.as_jsvalue(&store.as_store_ref()),
+                };
+                js_sys::Reflect::set(&import_namespace, &import_type.name().into(), &value)
+                    .map_err(js_link_error)?;
 
                 trace!(
                     "resolved import {}:{} with internal function",
@@ -171,9 +283,11 @@ impl Module {
                 );
             } else {
                 let import_namespace =
-                    js_sys::Reflect::get(&imports_object, &import_type.module().into())?;
+                    js_sys::Reflect::get(&imports_object, &import_type.module().into())
+                        .map_err(js_link_error)?;
                 let defined = !import_namespace.is_undefined()
-                    && !js_sys::Reflect::get(&import_namespace, &import_type.name().into())?
+                    && !js_sys::Reflect::get(&import_namespace, &import_type.name().into())
+                        .map_err(js_link_error)?
                         .is_undefined();
 
                 if defined {
@@ -182,31 +296,50 @@ impl Module {
                         import_type.module(),
                         import_type.name()
                     );
-                } else {
-                    // in case the import is not found, the JS Wasm VM will handle
-                    // the error for us, so we don't need to handle it
-                    warn!(
-                        "import {}:{} not found",
+                } else if let (true, ExternType::Function(ty)) =
+                    (imports.lazy_fallback, import_type.ty())
+                {
+                    let stub = unresolved_import_stub(
+                        store,
+                        import_type.module(),
+                        import_type.name(),
+                        ty.clone(),
+                    );
+                    let mut import_namespace = import_namespace;
+                    if import_namespace.is_undefined() {
+                        import_namespace = js_sys::Object::new().into();
+                        js_sys::Reflect::set(
+                            &imports_object,
+                            &import_type.module().into(),
+                            &import_namespace,
+                        )
+                        .map_err(js_link_error)?;
+                    }
+                    js_sys::Reflect::set(
+                        &import_namespace,
+                        &import_type.name().into(),
+                        &stub.as_jsvalue(&store.as_store_ref()),
+                    )
+                    .map_err(js_link_error)?;
+
+                    trace!(
+                        "resolved import {}:{} with a trapping stub",
                         import_type.module(),
                         import_type.name()
                     );
+                } else {
+                    // JS would fail with a `TypeError` rather than a
+                    // `LinkError`, which is reported here instead
+                    return Err(LinkError::Import(
+                        import_type.module().to_string(),
+                        import_type.name().to_string(),
+                        ImportError::UnknownImport(import_type.ty().clone()),
+                    ));
                 }
             }
         }
 
-        tracing::trace!(
-            "instantiating module {}",
-            self.name.as_deref().unwrap_or_default()
-        );
-        let module = JsFuture::from(WebAssembly::instantiate_module(
-            &self.module,
-            &imports_object,
-        ))
-        .await
-        .map(|v| v.dyn_into().unwrap())
-        .map_err(|e: JsValue| -> RuntimeError { e.into() })?;
-
-        Ok(module)
+        Ok(imports_object)
     }
 
     pub fn name(&self) -> Option<&str> {
@@ -222,9 +355,25 @@ impl Module {
         true
     }
 
+    /// Whether the module was instrumented by [`crate::Metering`].
+    fn is_metered(&self) -> bool {
+        self.info
+            .imports
+            .keys()
+            .any(|key| key.module == FUEL_NAMESPACE && key.field == FUEL_NAME)
+    }
+
+    /// Whether the module was instrumented by [`crate::Interruption`].
+    fn is_interruptible(&self) -> bool {
+        self.info
+            .imports
+            .keys()
+            .any(|key| key.module == INTERRUPT_NAMESPACE && key.field == INTERRUPT_NAME)
+    }
+
     pub fn imports<'a>(&'a self) -> ImportsIterator<impl Iterator<Item = ImportType> + 'a> {
         let imports = WebAssembly::Module::imports(&self.module);
-        let iter = imports
+        let imports = imports
             .iter()
             .enumerate()
             .map(move |(i, val)| {
@@ -239,9 +388,15 @@ impl Module {
                 let extern_type = self.type_hints.imports.get(i).unwrap().clone();
                 ImportType::new(&module, &field, extern_type)
             })
-            .collect::<Vec<_>>()
-            .into_iter();
-        ImportsIterator::new(iter, imports.length() as usize)
+            // The fuel counter of metered modules is provided by the store.
+            .filter(|import| !(import.module() == FUEL_NAMESPACE && import.name() == FUEL_NAME))
+            // So is the deadline check of interruptible modules.
+            .filter(|import| {
+                !(import.module() == INTERRUPT_NAMESPACE && import.name() == INTERRUPT_NAME)
+            })
+            .collect::<Vec<_>>();
+        let len = imports.len();
+        ImportsIterator::new(imports.into_iter(), len)
     }
 
     pub fn exports<'a>(&'a self) -> ExportsIterator<impl Iterator<Item = ExportType> + 'a> {
@@ -263,21 +418,70 @@ impl Module {
     }
 
     pub fn custom_sections<'a>(&'a self, name: &'a str) -> impl Iterator<Item = Box<[u8]>> + 'a {
-        WebAssembly::Module::custom_sections(&self.module, name)
+        self.custom_sections
             .iter()
-            .map(move |buf_val| {
-                let typebuf: js_sys::Uint8Array = js_sys::Uint8Array::new(&buf_val);
-                typebuf.to_vec().into_boxed_slice()
-            })
-            .collect::<Vec<Box<[u8]>>>()
-            .into_iter()
+            .filter(move |(section_name, _)| section_name == name)
+            .map(|(_, index)| self.info.custom_sections_data[*index].clone())
+    }
+
+    pub fn custom_section_names(&self) -> impl Iterator<Item = &str> + '_ {
+        let mut names = self
+            .custom_sections
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        // Each name is reported where it first appears
+        let mut seen = std::collections::HashSet::new();
+        names.retain(|name| seen.insert(*name));
+        names.into_iter()
+    }
+
+    pub fn function_name(&self, index: u32) -> Option<&str> {
+        self.info
+            .function_names
+            .get(&FunctionIndex::from_u32(index))
+            .map(|name| name.as_str())
+    }
+
+    pub fn function_names(&self) -> impl Iterator<Item = (u32, &str)> + '_ {
+        let mut names: Vec<_> = self
+            .info
+            .function_names
+            .iter()
+            .map(|(index, name)| (index.as_u32(), name.as_str()))
+            .collect();
+        names.sort_unstable_by_key(|(index, _)| *index);
+        names.into_iter()
+    }
+
+    pub fn function_offset(&self, index: u32) -> Option<u32> {
+        let local = index.checked_sub(self.info.num_imported_functions as u32)?;
+        self.function_offsets.get(local as usize).copied()
     }
 
     pub(crate) fn info(&self) -> &ModuleInfo {
-        unimplemented!()
+        &self.info
     }
 }
 
+/// The imports can't be plugged in, such as when the imports object throws,
+/// which happens while linking.
+fn js_link_error(err: JsValue) -> LinkError {
+    LinkError::Trap(err.into())
+}
+
+/// Create a function which traps when called, standing in for an import
+/// that couldn't be resolved.
+fn unresolved_import_stub(
+    store: &mut impl AsStoreMut,
+    module: &str,
+    name: &str,
+    ty: FunctionType,
+) -> Function {
+    let message = format!("called the unresolved import \"{module}\".\"{name}\"");
+    Function::new(store, ty, move |_| Err(RuntimeError::new(message.clone())))
+}
+
 impl From<crate::module::Module> for WebAssembly::Module {
     fn from(value: crate::module::Module) -> Self {
         value.0.module.into_inner()
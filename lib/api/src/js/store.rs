@@ -1,30 +1,36 @@
-pub(crate) use objects::{InternalStoreHandle, StoreObject};
+pub(crate) use objects::{InternalStoreHandle, KeepAlive, StoreObject};
 pub use objects::{StoreHandle, StoreObjects};
 
 mod objects {
-    use std::{fmt, marker::PhantomData, num::NonZeroUsize};
-
+    use std::{
+        fmt,
+        marker::PhantomData,
+        num::NonZeroUsize,
+        sync::{Arc, Weak},
+    };
+
+    use js_sys::{Function as JsFunction, Map};
     use wasm_bindgen::JsValue;
 
-    use crate::js::vm::{VMFunctionEnvironment, VMGlobal};
+    use crate::js::vm::{VMExternObj, VMFunction, VMFunctionEnvironment, VMGlobal};
 
     pub use wasmer_types::StoreId;
 
     /// Trait to represent an object managed by a context. This is implemented on
     /// the VM types managed by the context.
     pub trait StoreObject: Sized {
-        fn list(store: &StoreObjects) -> &Vec<Self>;
-        fn list_mut(store: &mut StoreObjects) -> &mut Vec<Self>;
+        fn list(store: &StoreObjects) -> &Slots<Self>;
+        fn list_mut(store: &mut StoreObjects) -> &mut Slots<Self>;
     }
 
     macro_rules! impl_store_object {
         ($($field:ident => $ty:ty,)*) => {
             $(
                 impl StoreObject for $ty {
-                    fn list(store: &StoreObjects) -> &Vec<Self> {
+                    fn list(store: &StoreObjects) -> &Slots<Self> {
                         &store.$field
                     }
-                    fn list_mut(store: &mut StoreObjects) -> &mut Vec<Self> {
+                    fn list_mut(store: &mut StoreObjects) -> &mut Slots<Self> {
                         &mut store.$field
                     }
                 }
@@ -36,7 +42,10 @@ mod objects {
         // Note: we store the globals in order to be able to access them later via
         // `StoreObjects::iter_globals`.
         globals => VMGlobal,
-        // functions => VMFunction,
+        // Functions are stored so that they can be referenced by index when
+        // passed around as `funcref` raw values.
+        functions => VMFunction,
+        extern_objs => VMExternObj,
         // tables => VMTable,
         // memories => VMMemory,
         // The function environments are the only things attached to a store,
@@ -45,12 +54,183 @@ mod objects {
         function_environments => VMFunctionEnvironment,
     }
 
+    /// Keeps an object managed by a context alive.
+    ///
+    /// Every handle given out to users holds one of these, and
+    /// [`StoreObjects::gc()`] reclaims the objects which no longer have any.
+    #[derive(Clone)]
+    pub(crate) struct KeepAlive(Arc<()>);
+
+    impl PartialEq for KeepAlive {
+        fn eq(&self, other: &Self) -> bool {
+            Arc::ptr_eq(&self.0, &other.0)
+        }
+    }
+
+    impl Eq for KeepAlive {}
+
+    impl fmt::Debug for KeepAlive {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_tuple("KeepAlive").finish()
+        }
+    }
+
+    struct Slot<T> {
+        value: T,
+        owners: Weak<()>,
+        /// Other objects which have to outlive this one.
+        dependencies: Vec<KeepAlive>,
+    }
+
+    /// The objects of one kind managed by a context.
+    ///
+    /// Slots of reclaimed objects are reused, so the indices used by handles
+    /// don't grow without bound.
+    pub struct Slots<T> {
+        slots: Vec<Option<Slot<T>>>,
+        free: Vec<usize>,
+    }
+
+    impl<T> Default for Slots<T> {
+        fn default() -> Self {
+            Self {
+                slots: Vec::new(),
+                free: Vec::new(),
+            }
+        }
+    }
+
+    impl<T: fmt::Debug> fmt::Debug for Slots<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_list()
+                .entries(self.iter().map(|(_, value)| value))
+                .finish()
+        }
+    }
+
+    impl<T> Slots<T> {
+        /// Stores an object, returning its index.
+        ///
+        /// The object isn't kept alive until [`Slots::keep_alive()`] is called.
+        pub(crate) fn insert(&mut self, value: T) -> usize {
+            let slot = Slot {
+                value,
+                owners: Weak::new(),
+                dependencies: Vec::new(),
+            };
+            match self.free.pop() {
+                Some(index) => {
+                    self.slots[index] = Some(slot);
+                    index
+                }
+                None => {
+                    self.slots.push(Some(slot));
+                    self.slots.len() - 1
+                }
+            }
+        }
+
+        /// Returns the object at `index`.
+        ///
+        /// Panics if the object has been reclaimed.
+        pub(crate) fn get(&self, index: usize) -> &T {
+            &self.slot(index).value
+        }
+
+        /// Returns the object at `index` mutably.
+        ///
+        /// Panics if the object has been reclaimed.
+        pub(crate) fn get_mut(&mut self, index: usize) -> &mut T {
+            &mut self.slot_mut(index).value
+        }
+
+        /// Iterates over the objects which haven't been reclaimed, along with
+        /// their index.
+        pub(crate) fn iter(&self) -> impl Iterator<Item = (usize, &T)> {
+            self.slots
+                .iter()
+                .enumerate()
+                .filter_map(|(index, slot)| Some((index, &slot.as_ref()?.value)))
+        }
+
+        /// Returns a [`KeepAlive`] preventing the object at `index` from
+        /// being reclaimed.
+        pub(crate) fn keep_alive(&mut self, index: usize) -> KeepAlive {
+            let slot = self.slot_mut(index);
+            match slot.owners.upgrade() {
+                Some(owners) => KeepAlive(owners),
+                None => {
+                    let owners = Arc::new(());
+                    slot.owners = Arc::downgrade(&owners);
+                    KeepAlive(owners)
+                }
+            }
+        }
+
+        /// Keeps `dependency` alive for as long as the object at `index`.
+        pub(crate) fn add_dependency(&mut self, index: usize, dependency: KeepAlive) {
+            self.slot_mut(index).dependencies.push(dependency);
+        }
+
+        /// Reclaims the objects which are no longer kept alive, handing them
+        /// to `reclaim`, and returns how many were reclaimed.
+        fn collect(&mut self, mut reclaim: impl FnMut(T)) -> usize {
+            let mut reclaimed = 0;
+            for (index, slot) in self.slots.iter_mut().enumerate() {
+                if matches!(slot, Some(s) if s.owners.strong_count() == 0) {
+                    reclaim(slot.take().unwrap().value);
+                    self.free.push(index);
+                    reclaimed += 1;
+                }
+            }
+            reclaimed
+        }
+
+        fn slot(&self, index: usize) -> &Slot<T> {
+            self.slots[index]
+                .as_ref()
+                .expect("object used after being reclaimed")
+        }
+
+        fn slot_mut(&mut self, index: usize) -> &mut Slot<T> {
+            self.slots[index]
+                .as_mut()
+                .expect("object used after being reclaimed")
+        }
+    }
+
+    fn value_mut<T>(slot: &mut Option<Slot<T>>) -> &mut T {
+        &mut slot
+            .as_mut()
+            .expect("object used after being reclaimed")
+            .value
+    }
+
+    /// The index of each object of one kind in a context, keyed by the JS
+    /// value representing it, so that objects are found without going
+    /// through all of them.
+    #[derive(Debug)]
+    struct JsIndices(Map);
+
+    unsafe impl Send for JsIndices {}
+    unsafe impl Sync for JsIndices {}
+
+    impl Default for JsIndices {
+        fn default() -> Self {
+            Self(Map::new())
+        }
+    }
+
     /// Set of objects managed by a context.
     #[derive(Default, Debug)]
     pub struct StoreObjects {
         id: StoreId,
-        globals: Vec<VMGlobal>,
-        function_environments: Vec<VMFunctionEnvironment>,
+        globals: Slots<VMGlobal>,
+        functions: Slots<VMFunction>,
+        function_indices: JsIndices,
+        extern_objs: Slots<VMExternObj>,
+        extern_obj_indices: JsIndices,
+        function_environments: Slots<VMFunctionEnvironment>,
     }
 
     impl StoreObjects {
@@ -73,25 +253,88 @@ mod objects {
             b: InternalStoreHandle<T>,
         ) -> (&mut T, &mut T) {
             assert_ne!(a.index(), b.index());
-            let list = T::list_mut(self);
-            if a.index() < b.index() {
-                let (low, high) = list.split_at_mut(b.index());
-                (&mut low[a.index()], &mut high[0])
+            let slots = &mut T::list_mut(self).slots;
+            let (a, b) = (a.index() - 1, b.index() - 1);
+            let (a, b) = if a < b {
+                let (low, high) = slots.split_at_mut(b);
+                (&mut low[a], &mut high[0])
             } else {
-                let (low, high) = list.split_at_mut(a.index());
-                (&mut high[0], &mut low[a.index()])
-            }
+                let (low, high) = slots.split_at_mut(a);
+                (&mut high[0], &mut low[b])
+            };
+            (value_mut(a), value_mut(b))
+        }
+
+        /// Stores a function which isn't in the context yet, returning its
+        /// index.
+        pub(crate) fn insert_function(&mut self, function: VMFunction) -> usize {
+            let key = JsValue::from(function.function.clone());
+            let index = self.functions.insert(function);
+            self.function_indices.0.set(&key, &JsValue::from(index));
+            index
+        }
+
+        /// Returns the index of a function of the context.
+        pub(crate) fn function_index(&self, function: &JsFunction) -> Option<usize> {
+            let index = self.function_indices.0.get(function).as_f64()?;
+            Some(index as usize)
+        }
+
+        /// Stores an extern object which isn't in the context yet, returning
+        /// its index.
+        pub(crate) fn insert_extern_obj(&mut self, obj: VMExternObj) -> usize {
+            let key = obj.js().clone();
+            let index = self.extern_objs.insert(obj);
+            self.extern_obj_indices.0.set(&key, &JsValue::from(index));
+            index
+        }
+
+        /// Returns the index of the extern object of the context which is
+        /// represented by a JS value.
+        pub(crate) fn extern_obj_index(&self, value: &JsValue) -> Option<usize> {
+            let index = self.extern_obj_indices.0.get(value).as_f64()?;
+            Some(index as usize)
+        }
+
+        /// Returns a [`KeepAlive`] preventing the object behind `handle` from
+        /// being reclaimed.
+        pub(crate) fn keep_alive<T: StoreObject>(
+            &mut self,
+            handle: InternalStoreHandle<T>,
+        ) -> KeepAlive {
+            T::list_mut(self).keep_alive(handle.index() - 1)
+        }
+
+        /// Reclaims the function environments, functions and extern objects
+        /// which are no longer referenced by any handle, returning how many
+        /// were reclaimed.
+        ///
+        /// Globals are never reclaimed, since snapshots refer to them by
+        /// position.
+        pub(crate) fn gc(&mut self) -> usize {
+            // Functions can keep environments alive, so they are reclaimed
+            // first.
+            let function_indices = &self.function_indices.0;
+            let extern_obj_indices = &self.extern_obj_indices.0;
+            self.functions.collect(|function| {
+                function_indices.delete(&function.function);
+            }) + self.extern_objs.collect(|obj| {
+                extern_obj_indices.delete(obj.js());
+            }) + self.function_environments.collect(drop)
         }
 
         /// Return an immutable iterator over all globals
-        pub fn iter_globals(&self) -> core::slice::Iter<VMGlobal> {
-            self.globals.iter()
+        pub fn iter_globals(&self) -> impl Iterator<Item = &VMGlobal> {
+            self.globals.iter().map(|(_, global)| global)
         }
 
         /// Return an vector of all globals and converted to u128
+        ///
+        /// Reference-typed globals have no raw representation and are
+        /// reported as `0`.
         pub fn as_u128_globals(&self) -> Vec<u128> {
             self.iter_globals()
-                .map(|v| v.global.value().as_f64().unwrap() as u128)
+                .map(|v| v.global.value().as_f64().unwrap_or_default() as u128)
                 .collect()
         }
 
@@ -99,9 +342,14 @@ mod objects {
         /// Safety: the caller should check that the raw value is compatible
         /// with destination VMGlobal type
         pub fn set_global_unchecked(&self, idx: usize, new_val: u128) {
-            assert!(idx < self.globals.len());
+            let global = self.globals.get(idx);
 
-            let g = &self.globals[idx].global;
+            // Reference-typed globals can't be restored from a raw value.
+            if global.ty.ty.is_ref() {
+                return;
+            }
+
+            let g = &global.global;
             let cur_val = g.value().as_f64().unwrap();
             let new_val = new_val as f64;
             if cur_val != new_val {
@@ -233,23 +481,21 @@ mod objects {
     impl<T: StoreObject> InternalStoreHandle<T> {
         /// Moves the given object into a context and returns a handle to it.
         pub fn new(store: &mut StoreObjects, val: T) -> Self {
-            let list = T::list_mut(store);
-            let idx = NonZeroUsize::new(list.len() + 1).unwrap();
-            list.push(val);
+            let index = T::list_mut(store).insert(val);
             Self {
-                idx,
+                idx: NonZeroUsize::new(index + 1).unwrap(),
                 marker: PhantomData,
             }
         }
 
         /// Returns a reference to the object that this handle points to.
         pub fn get<'a>(&self, store: &'a StoreObjects) -> &'a T {
-            &T::list(store)[self.idx.get() - 1]
+            T::list(store).get(self.idx.get() - 1)
         }
 
         /// Returns a mutable reference to the object that this handle points to.
         pub fn get_mut<'a>(&self, store: &'a mut StoreObjects) -> &'a mut T {
-            &mut T::list_mut(store)[self.idx.get() - 1]
+            T::list_mut(store).get_mut(self.idx.get() - 1)
         }
 
         pub(crate) fn index(&self) -> usize {
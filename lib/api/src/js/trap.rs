@@ -5,8 +5,9 @@ use std::{
 
 use js_sys::Reflect;
 use wasm_bindgen::{prelude::*, JsValue};
+use wasmer_types::{FrameInfo, SourceLoc, TrapCode};
 
-use crate::RuntimeError;
+use crate::{Exception, RuntimeError};
 
 #[derive(Debug)]
 enum InnerTrap {
@@ -53,6 +54,22 @@ impl Trap {
             _ => false,
         }
     }
+
+    /// The message of the JS error this trap was created from, if any.
+    pub(crate) fn js_message(&self) -> Option<&str> {
+        match &self.inner {
+            InnerTrap::Js(trap) => trap.message.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// The stack trace of the JS error this trap was created from, if any.
+    pub(crate) fn js_stack(&self) -> Option<&str> {
+        match &self.inner {
+            InnerTrap::Js(trap) => trap.stack.as_deref(),
+            _ => None,
+        }
+    }
 }
 
 #[wasm_bindgen]
@@ -90,9 +107,26 @@ impl From<JsValue> for RuntimeError {
             }
         }
 
-        RuntimeError::from(Trap {
-            inner: InnerTrap::Js(value.into()),
-        })
+        if let Some(exception) = value.dyn_ref::<js_sys::WebAssembly::Exception>() {
+            let exception = crate::js::exception::Exception::from_js(exception.clone());
+            return Exception(exception).into();
+        }
+
+        let trap = JsTrap::from(value);
+        let trap_code = trap.trap_code;
+        let wasm_trace = trap
+            .stack
+            .as_deref()
+            .map(|stack| stack.lines().filter_map(parse_wasm_frame).collect())
+            .unwrap_or_default();
+
+        RuntimeError::new_from_source(
+            Trap {
+                inner: InnerTrap::Js(trap),
+            },
+            wasm_trace,
+            trap_code,
+        )
     }
 }
 
@@ -142,38 +176,136 @@ fn downcast_from_ptr(value: &JsValue) -> Option<Trap> {
 
 /// A `Send+Sync` version of a JavaScript error.
 #[derive(Debug)]
-enum JsTrap {
-    /// An error message.
-    Message(String),
-    /// Unable to determine the underlying error.
-    Unknown,
+struct JsTrap {
+    /// The error message, if one could be determined.
+    message: Option<String>,
+    /// The JS stack trace, including the WebAssembly frames.
+    stack: Option<String>,
+    /// The trap code, if the error was raised by the WebAssembly engine.
+    trap_code: Option<TrapCode>,
 }
 
 impl From<JsValue> for JsTrap {
     fn from(value: JsValue) -> Self {
         // Let's try some easy special cases first
         if let Some(error) = value.dyn_ref::<js_sys::Error>() {
-            return JsTrap::Message(error.message().into());
-        }
+            let message = String::from(error.message());
+            let stack = Reflect::get(error, &"stack".into())
+                .ok()
+                .and_then(|stack| stack.as_string());
+            let trap_code = if value.is_instance_of::<js_sys::WebAssembly::RuntimeError>() {
+                trap_code_from_message(&message)
+            } else if is_stack_overflow(error) {
+                Some(TrapCode::StackOverflow)
+            } else {
+                None
+            };
 
-        if let Some(s) = value.as_string() {
-            return JsTrap::Message(s);
+            return JsTrap {
+                message: Some(message),
+                stack,
+                trap_code,
+            };
         }
 
         // Otherwise, we'll try to stringify the error and hope for the best
-        if let Some(obj) = value.dyn_ref::<js_sys::Object>() {
-            return JsTrap::Message(obj.to_string().into());
-        }
+        let message = value.as_string().or_else(|| {
+            value
+                .dyn_ref::<js_sys::Object>()
+                .map(|obj| obj.to_string().into())
+        });
 
-        JsTrap::Unknown
+        JsTrap {
+            message,
+            stack: None,
+            trap_code: None,
+        }
     }
 }
 
 impl Display for JsTrap {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            JsTrap::Message(m) => write!(f, "{m}"),
-            JsTrap::Unknown => write!(f, "unknown"),
+        match &self.message {
+            Some(m) => write!(f, "{m}"),
+            None => write!(f, "unknown"),
         }
     }
 }
+
+/// Browsers don't expose the trap code of a `WebAssembly.RuntimeError`, so
+/// it has to be recovered from the (engine-specific) message.
+fn trap_code_from_message(message: &str) -> Option<TrapCode> {
+    let message = message.to_lowercase();
+    let contains = |needles: &[&str]| needles.iter().any(|needle| message.contains(needle));
+
+    let code = if contains(&["unreachable"]) {
+        TrapCode::UnreachableCodeReached
+    } else if contains(&["divide by zero", "remainder by zero"]) {
+        TrapCode::IntegerDivisionByZero
+    } else if contains(&["integer overflow", "divide result unrepresentable"]) {
+        TrapCode::IntegerOverflow
+    } else if contains(&["unrepresentable", "conversion to integer"]) {
+        TrapCode::BadConversionToInteger
+    } else if contains(&["unaligned"]) {
+        TrapCode::UnalignedAtomic
+    } else if contains(&["signature mismatch"]) {
+        TrapCode::BadSignature
+    } else if contains(&["null"]) {
+        TrapCode::IndirectCallToNull
+    } else if contains(&["table"]) {
+        TrapCode::TableAccessOutOfBounds
+    } else if contains(&["out of bounds"]) {
+        TrapCode::HeapAccessOutOfBounds
+    } else {
+        return None;
+    };
+
+    Some(code)
+}
+
+fn is_stack_overflow(error: &js_sys::Error) -> bool {
+    let message = String::from(error.message());
+    // "Maximum call stack size exceeded" in Chrome and Safari, "too much
+    // recursion" in Firefox.
+    message.contains("call stack size exceeded") || message.contains("too much recursion")
+}
+
+/// Parse a WebAssembly frame from a JS stack trace.
+///
+/// Chrome formats them as `at name (wasm://wasm/module-hash:wasm-function[1]:0x45)`
+/// and Firefox as `name@https://example.com/module.wasm:wasm-function[1]:0x45`.
+/// Other lines are ignored.
+fn parse_wasm_frame(line: &str) -> Option<FrameInfo> {
+    const MARKER: &str = ":wasm-function[";
+
+    let line = line.trim();
+    let (function_name, location) = match line.strip_prefix("at ") {
+        Some(rest) => match rest.split_once(" (") {
+            Some((name, location)) => (Some(name), location.trim_end_matches(')')),
+            None => (None, rest),
+        },
+        None => match line.split_once('@') {
+            Some((name, location)) => (Some(name), location),
+            None => (None, line),
+        },
+    };
+
+    let (module, rest) = location.split_once(MARKER)?;
+    let (func_index, offset) = rest.split_once(']')?;
+    let func_index = func_index.parse().ok()?;
+    let offset = offset
+        .strip_prefix(":0x")
+        .and_then(|offset| u32::from_str_radix(offset, 16).ok())
+        .unwrap_or_default();
+    let module_name = module.rsplit('/').next().unwrap_or(module);
+
+    Some(FrameInfo::new(
+        module_name.to_string(),
+        func_index,
+        function_name
+            .filter(|name| !name.is_empty())
+            .map(String::from),
+        SourceLoc::default(),
+        SourceLoc::new(offset),
+    ))
+}
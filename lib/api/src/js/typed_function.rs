@@ -31,6 +31,8 @@ macro_rules! impl_native_traits {
             pub fn call(&self, mut store: &mut impl AsStoreMut, $( $x: $x, )* ) -> Result<Rets, RuntimeError> where
             $( $x: FromToNativeWasmType + NativeWasmTypeInto, )*
             {
+                store.as_store_ref().inner.check_epoch_deadline()?;
+
                 #[allow(unused_unsafe)]
                 let params_list: Vec<_> = unsafe {
                     vec![ $( ($x::WASM_TYPE, $x.into_raw(store) ) ),* ]
@@ -41,14 +43,15 @@ macro_rules! impl_native_traits {
                         &Array::from_iter(params_list.clone()
                         .into_iter()
                         .map(|(b, a)| Value::from_raw(store, b, a).as_jsvalue(store)))
-                    })?;
+                    })
+                    .map_err(|e| store.as_store_ref().inner.out_of_fuel_or(e.into()))?;
                 let mut rets_list_array = Rets::empty_array();
                 let mut_rets = rets_list_array.as_mut() as *mut [RawValue] as *mut RawValue;
                 match Rets::size() {
                     0 => {},
                     1 => unsafe {
                         let ty = Rets::wasm_types()[0];
-                        let val = param_from_js(&ty, &results);
+                        let val = param_from_js(&mut store, &ty, &results);
                         *mut_rets = val.as_raw(&mut store);
                     }
                     _n => {
@@ -56,7 +59,7 @@ macro_rules! impl_native_traits {
                         for (i, ret_type) in Rets::wasm_types().iter().enumerate() {
                             let ret = results.get(i as u32);
                             unsafe {
-                                let val = param_from_js(&ret_type, &ret);
+                                let val = param_from_js(&mut store, &ret_type, &ret);
                                 let slot = mut_rets.add(i);
                                 *slot = val.as_raw(&mut store);
                             }
@@ -4,26 +4,147 @@
 /// This module should not be needed any longer (with the exception of the memory)
 /// once the type reflection is added to the WebAssembly JS API.
 /// https://github.com/WebAssembly/js-types/
-use std::{any::Any, fmt};
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex, Weak,
+    },
+};
 
 use js_sys::{
     Function as JsFunction,
-    WebAssembly::{self, Memory as JsMemory, Table as JsTable},
+    WebAssembly::{self, Memory as JsMemory, Table as JsTable, Tag as JsTag},
 };
 use serde::{Deserialize, Serialize};
 use tracing::trace;
 use wasm_bindgen::{JsCast, JsValue};
 use wasmer_types::{
-    FunctionType, GlobalType, MemoryError, MemoryType, Pages, RawValue, TableType, WASM_PAGE_SIZE,
+    FunctionType, GlobalType, MemoryError, MemoryType, Pages, RawValue, TableType, TagType,
+    WASM_PAGE_SIZE,
 };
 
-use crate::js::{js_handle::JsHandle, wasm_bindgen_polyfill::Global as JsGlobal};
+use crate::js::{
+    js_handle::JsHandle, store::InternalStoreHandle, wasm_bindgen_polyfill::Global as JsGlobal,
+};
 
 /// Represents linear memory that is managed by the javascript runtime
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug)]
 pub struct VMMemory {
     pub(crate) memory: JsHandle<JsMemory>,
     pub(crate) ty: MemoryType,
+    pub(crate) grow_hooks: MemoryGrowHooks,
+}
+
+impl PartialEq for VMMemory {
+    fn eq(&self, other: &Self) -> bool {
+        self.memory == other.memory && self.ty == other.ty
+    }
+}
+
+/// Callbacks invoked with the new size of a memory once it has grown.
+///
+/// The hooks are shared by every handle to the same memory on a thread,
+/// whether it was cloned, imported or exported, as they are found from the
+/// JS memory itself. Growth performed by the host is reported immediately,
+/// while growth performed by the guest (`memory.grow`) is reported the next
+/// time a view of the memory is created.
+#[derive(Clone)]
+pub(crate) struct MemoryGrowHooks(Arc<MemoryGrowHooksInner>);
+
+type GrowHook = Arc<dyn Fn(Pages) + Send + Sync>;
+
+struct MemoryGrowHooksInner {
+    /// The last size reported to the hooks, or `u32::MAX` if unknown.
+    last_size: AtomicU32,
+    hooks: Mutex<Vec<GrowHook>>,
+}
+
+impl Default for MemoryGrowHooks {
+    fn default() -> Self {
+        Self(Arc::new(MemoryGrowHooksInner {
+            last_size: AtomicU32::new(u32::MAX),
+            hooks: Mutex::new(Vec::new()),
+        }))
+    }
+}
+
+impl fmt::Debug for MemoryGrowHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryGrowHooks")
+            .field("hooks", &self.0.hooks.lock().unwrap().len())
+            .finish()
+    }
+}
+
+thread_local! {
+    static GROW_HOOKS: RefCell<GrowHooksRegistry> = RefCell::new(GrowHooksRegistry::new());
+}
+
+/// The hooks of the memories wrapped on this thread, which the JS memories
+/// are mapped to by id, as long as a handle to them is alive.
+struct GrowHooksRegistry {
+    ids: js_sys::WeakMap,
+    hooks: HashMap<u32, Weak<MemoryGrowHooksInner>>,
+    next_id: u32,
+}
+
+impl GrowHooksRegistry {
+    fn new() -> Self {
+        Self {
+            ids: js_sys::WeakMap::new(),
+            hooks: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    fn get_or_insert(&mut self, memory: &JsMemory) -> MemoryGrowHooks {
+        if let Some(id) = self.ids.get(memory).as_f64() {
+            if let Some(inner) = self.hooks.get(&(id as u32)).and_then(Weak::upgrade) {
+                return MemoryGrowHooks(inner);
+            }
+        }
+        // The hooks of the memories with no handle left go with them
+        self.hooks.retain(|_, hooks| hooks.strong_count() > 0);
+
+        let hooks = MemoryGrowHooks::default();
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.ids.set(memory, &JsValue::from(id));
+        self.hooks.insert(id, Arc::downgrade(&hooks.0));
+        hooks
+    }
+}
+
+impl MemoryGrowHooks {
+    /// The hooks of `memory`, shared with the other handles to it.
+    pub(crate) fn of(memory: &JsMemory) -> Self {
+        GROW_HOOKS.with(|registry| registry.borrow_mut().get_or_insert(memory))
+    }
+
+    /// Registers a new hook for a memory that currently has `size` pages.
+    pub(crate) fn add(&self, size: Pages, hook: GrowHook) {
+        let mut hooks = self.0.hooks.lock().unwrap();
+        self.0.last_size.store(size.0, Ordering::SeqCst);
+        hooks.push(hook);
+    }
+
+    /// Reports the current size of the memory, invoking the hooks if it
+    /// changed since the last report.
+    pub(crate) fn notify(&self, size: Pages) {
+        if self.0.last_size.swap(size.0, Ordering::SeqCst) == size.0 {
+            return;
+        }
+        // The hooks are called without holding the lock so that they can
+        // register further hooks or inspect the memory.
+        let hooks = self.0.hooks.lock().unwrap().clone();
+        for hook in hooks {
+            hook(size);
+        }
+    }
 }
 
 unsafe impl Send for VMMemory {}
@@ -32,13 +153,14 @@ unsafe impl Sync for VMMemory {}
 #[derive(Serialize, Deserialize)]
 struct DummyBuffer {
     #[serde(rename = "byteLength")]
-    byte_length: u32,
+    byte_length: u64,
 }
 
 impl VMMemory {
     /// Creates a new memory directly from a WebAssembly javascript object
     pub fn new(memory: JsMemory, ty: MemoryType) -> Self {
         Self {
+            grow_hooks: MemoryGrowHooks::of(&memory),
             memory: JsHandle::new(memory),
             ty,
         }
@@ -53,7 +175,7 @@ impl VMMemory {
         if dummy.byte_length == 0 {
             return 0;
         }
-        dummy.byte_length / WASM_PAGE_SIZE as u32
+        (dummy.byte_length / WASM_PAGE_SIZE as u64) as u32
     }
 
     /// Attempts to clone this memory (if its clonable)
@@ -85,7 +207,12 @@ impl VMMemory {
 
             let our_js_memory: &crate::js::externals::memory::JSMemory =
                 JsCast::unchecked_from_js_ref(&new_memory);
-            our_js_memory.grow(pages as u32).map_err(|err| {
+            let grown = if self.ty.is_64() {
+                our_js_memory.grow64(pages as u64).map(|p| p as u32)
+            } else {
+                our_js_memory.grow(pages as u32)
+            };
+            grown.map_err(|err| {
                 if err.is_instance_of::<js_sys::RangeError>() {
                     let cur_pages = dst_size;
                     MemoryError::CouldNotGrow {
@@ -106,10 +233,7 @@ impl VMMemory {
 
         trace!("memory copy finished (size={})", dst.size().bytes().0);
 
-        Ok(Self {
-            memory: JsHandle::new(new_memory),
-            ty: self.ty.clone(),
-        })
+        Ok(Self::new(new_memory, self.ty))
     }
 }
 
@@ -165,11 +289,30 @@ impl VMTable {
     }
 }
 
+/// The VM Tag type
+#[derive(Clone, Debug, PartialEq)]
+pub struct VMTag {
+    pub(crate) tag: JsTag,
+    pub(crate) ty: TagType,
+}
+
+unsafe impl Send for VMTag {}
+unsafe impl Sync for VMTag {}
+
+impl VMTag {
+    pub(crate) fn new(tag: JsTag, ty: TagType) -> Self {
+        Self { tag, ty }
+    }
+}
+
 /// The VM Function type
 #[derive(Clone)]
 pub struct VMFunction {
     pub(crate) function: JsHandle<JsFunction>,
     pub(crate) ty: FunctionType,
+    /// Whether the function was created by the host with one of the
+    /// `Function` constructors.
+    pub(crate) host: bool,
 }
 
 unsafe impl Send for VMFunction {}
@@ -180,6 +323,14 @@ impl VMFunction {
         Self {
             function: JsHandle::new(function),
             ty,
+            host: false,
+        }
+    }
+
+    pub(crate) fn new_host(function: JsFunction, ty: FunctionType) -> Self {
+        Self {
+            host: true,
+            ..Self::new(function, ty)
         }
     }
 }
@@ -211,6 +362,9 @@ pub enum VMExtern {
 
     /// A global export value.
     Global(VMGlobal),
+
+    /// A tag export value.
+    Tag(VMTag),
 }
 
 pub type VMInstance = WebAssembly::Instance;
@@ -242,42 +396,96 @@ impl VMFunctionEnvironment {
     }
 }
 
-pub(crate) struct VMExternRef;
+/// Underlying object referenced by a `VMExternRef`.
+///
+/// Every object has a JS value representing it when it is passed to
+/// JavaScript or WebAssembly. For objects created by the host this is an
+/// empty JS object that only serves as an identity token.
+#[derive(Debug)]
+pub struct VMExternObj {
+    contents: Option<Box<dyn Any + Send + Sync + 'static>>,
+    js: JsHandle<JsValue>,
+}
+
+unsafe impl Send for VMExternObj {}
+unsafe impl Sync for VMExternObj {}
+
+impl VMExternObj {
+    /// Wraps the given host value to expose it to Wasm code as an `externref`.
+    pub fn new(val: impl Any + Send + Sync + 'static) -> Self {
+        Self {
+            contents: Some(Box::new(val)),
+            js: JsHandle::new(js_sys::Object::new().into()),
+        }
+    }
+
+    /// Wraps an arbitrary JS value that was passed as an `externref`.
+    pub fn from_js(js: JsValue) -> Self {
+        Self {
+            contents: None,
+            js: JsHandle::new(js),
+        }
+    }
+
+    /// Returns a reference to the underlying host value, if any.
+    #[allow(clippy::should_implement_trait)]
+    pub fn as_ref(&self) -> Option<&(dyn Any + Send + Sync + 'static)> {
+        self.contents.as_deref()
+    }
+
+    /// Returns the JS value representing this object.
+    pub fn js(&self) -> &JsValue {
+        &self.js
+    }
+}
+
+/// A reference to an extern object registered in the store.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct VMExternRef(pub(crate) InternalStoreHandle<VMExternObj>);
 
 impl VMExternRef {
     /// Converts the `VMExternRef` into a `RawValue`.
     pub fn into_raw(self) -> RawValue {
-        unimplemented!();
+        RawValue {
+            externref: self.0.index(),
+        }
     }
 
     /// Extracts a `VMExternRef` from a `RawValue`.
     ///
     /// # Safety
     /// `raw` must be a valid `VMExternRef` instance.
-    pub unsafe fn from_raw(_raw: RawValue) -> Option<Self> {
-        unimplemented!();
+    pub unsafe fn from_raw(raw: RawValue) -> Option<Self> {
+        InternalStoreHandle::from_index(raw.externref).map(Self)
     }
 }
 
 #[repr(C)]
 pub struct VMFunctionBody(u8);
 
+/// A reference to a function registered in the store.
+///
+/// JS functions cannot be represented as a raw pointer, thus the raw
+/// value of a `funcref` is the index of the function within the store.
 #[repr(transparent)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
-pub(crate) struct VMFuncRef;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct VMFuncRef(pub(crate) InternalStoreHandle<VMFunction>);
 
 impl VMFuncRef {
     /// Converts the `VMFuncRef` into a `RawValue`.
     pub fn into_raw(self) -> RawValue {
-        unimplemented!();
+        RawValue {
+            funcref: self.0.index(),
+        }
     }
 
     /// Extracts a `VMFuncRef` from a `RawValue`.
     ///
     /// # Safety
-    /// `raw.funcref` must be a valid pointer.
-    pub unsafe fn from_raw(_raw: RawValue) -> Option<Self> {
-        unimplemented!();
+    /// `raw.funcref` must be zero or a valid function index of the store.
+    pub unsafe fn from_raw(raw: RawValue) -> Option<Self> {
+        InternalStoreHandle::from_index(raw.funcref).map(Self)
     }
 }
 
@@ -287,5 +495,6 @@ pub(crate) type VMExternTable = VMTable;
 pub(crate) type VMExternMemory = VMMemory;
 pub(crate) type VMExternGlobal = VMGlobal;
 pub(crate) type VMExternFunction = VMFunction;
+pub(crate) type VMExternTag = VMTag;
 
 pub type VMFunctionCallback = *const VMFunctionBody;
@@ -29,18 +29,22 @@ compile_error!("target architecture must be `wasm32`");
 mod access;
 mod engine;
 mod errors;
+mod exception;
 mod exports;
 mod extern_ref;
 mod externals;
 mod function_env;
 mod imports;
 mod instance;
+mod interruption;
 mod into_bytes;
 mod mem_access;
+mod metering;
 mod module;
 mod native_type;
 mod ptr;
 mod store;
+mod store_snapshot;
 mod typed_function;
 mod value;
 pub mod vm;
@@ -51,22 +55,30 @@ mod js;
 pub use js::*;
 
 pub use crate::externals::{
-    Extern, Function, Global, HostFunction, Memory, MemoryLocation, MemoryView, SharedMemory, Table,
+    Extern, Function, Global, HostFunction, Memory, MemoryLocation, MemoryView, SharedMemory,
+    Table, Tag,
 };
 pub use access::WasmSliceAccess;
-pub use engine::{AsEngineRef, Engine, EngineRef};
-pub use errors::{AtomicsError, InstantiationError, LinkError, RuntimeError};
+pub use engine::{AsEngineRef, Engine, EngineRef, MemoryCreator};
+pub use errors::{AtomicsError, HostPanic, InstantiationError, LinkError, RuntimeError};
+pub use exception::Exception;
 pub use exports::{ExportError, Exportable, Exports, ExportsIterator, ExportsObj};
 pub use extern_ref::ExternRef;
 pub use function_env::{FunctionEnv, FunctionEnvMut};
 pub use imports::{Imports, ImportsObj};
 pub use instance::Instance;
+pub use interruption::Interruption;
 pub use into_bytes::IntoBytes;
 pub use mem_access::{MemoryAccessError, WasmRef, WasmSlice, WasmSliceIter};
+pub use metering::Metering;
 pub use module::{IoCompileError, Module};
 pub use native_type::{FromToNativeWasmType, NativeWasmTypeInto, WasmTypeList};
 pub use ptr::{Memory32, Memory64, MemorySize, WasmPtr, WasmPtr64};
-pub use store::{AsStoreMut, AsStoreRef, Store, StoreId, StoreMut, StoreObjects, StoreRef};
+pub use store::{
+    AsStoreMut, AsStoreRef, HostCall, HostCallCounter, HostCallInterceptor, InterruptHandle, Store,
+    StoreId, StoreMut, StoreObjects, StoreRef,
+};
+pub use store_snapshot::{RestoreError, SnapshotError, StoreSnapshot};
 pub use typed_function::TypedFunction;
 pub use value::Value;
 
@@ -75,9 +87,10 @@ pub use value::Value;
 pub use wasmer_derive::ValueType;
 pub use wasmer_types::{
     is_wasm, Bytes, CompileError, DeserializeError, ExportIndex, ExportType, ExternType, FrameInfo,
-    FunctionType, GlobalInit, GlobalType, ImportType, LocalFunctionIndex, MemoryError, MemoryType,
-    MiddlewareError, Mutability, Pages, ParseCpuFeatureError, SerializeError, TableType, Type,
-    ValueType, WasmError, WasmResult, WASM_MAX_PAGES, WASM_MIN_PAGES, WASM_PAGE_SIZE,
+    FunctionType, GlobalInit, GlobalType, ImportType, IndexType, LocalFunctionIndex, MemoryError,
+    MemoryType, MiddlewareError, Mutability, Pages, ParseCpuFeatureError, SerializeError,
+    TableType, TagType, TrapCode, Type, ValueType, WasmError, WasmResult, WASM_MAX_PAGES,
+    WASM_MIN_PAGES, WASM_PAGE_SIZE,
 };
 pub use wasmparser;
 
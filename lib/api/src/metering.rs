@@ -0,0 +1,349 @@
+//! Fuel metering for WebAssembly modules.
+//!
+//! The JS engine runs WebAssembly natively, so there is no compiler pipeline
+//! we could hook into to count instructions. Instead, [`Metering`] rewrites
+//! the module's bytecode before it is compiled: every function charges the
+//! cost of its instructions against a fuel counter shared with the
+//! [`Store`][crate::Store], and traps with [`TrapCode::OutOfFuel`] once the
+//! counter drops below zero.
+//!
+//! [`TrapCode::OutOfFuel`]: crate::TrapCode::OutOfFuel
+
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+use wasmer_types::CompileError;
+use wasmparser::{
+    BinaryReader, BinaryReaderError, ExternalKind, FunctionBody, Operator, Parser, Payload, TypeRef,
+};
+
+/// The module name of the import used to share the fuel counter with the
+/// store.
+pub(crate) const FUEL_NAMESPACE: &str = "__wasmer_metering";
+/// The field name of the import used to share the fuel counter with the
+/// store.
+pub(crate) const FUEL_NAME: &str = "fuel";
+
+const IMPORT_SECTION: u8 = 2;
+const EXPORT_SECTION: u8 = 7;
+const CODE_SECTION: u8 = 10;
+
+/// Instruments WebAssembly modules so they consume fuel as they run.
+///
+/// The remaining fuel is tracked by the [`Store`][crate::Store] the module
+/// is instantiated in, see [`Store::set_fuel()`][crate::Store::set_fuel]. A
+/// store starts with no fuel, so it needs to be given some before calling
+/// into a metered module.
+///
+/// # Usage
+///
+/// ```ignore
+/// # use wasmer::{Metering, Module, Store};
+/// # async fn run(wasm: &[u8]) -> anyhow::Result<()> {
+/// let mut store = Store::default();
+/// let wasm = Metering::default().instrument(wasm)?;
+/// let module = Module::new(wasm).await?;
+/// store.set_fuel(10_000);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Metering {
+    cost_function: Arc<dyn Fn(&Operator<'_>) -> u64 + Send + Sync>,
+}
+
+impl Metering {
+    /// Create a new [`Metering`] which uses `cost_function` to determine how
+    /// much fuel each instruction consumes.
+    pub fn new(cost_function: impl Fn(&Operator<'_>) -> u64 + Send + Sync + 'static) -> Self {
+        Metering {
+            cost_function: Arc::new(cost_function),
+        }
+    }
+
+    /// Instrument a WebAssembly binary.
+    ///
+    /// Only the binary format is supported, so text modules need to be
+    /// converted first.
+    pub fn instrument(&self, wasm: &[u8]) -> Result<Vec<u8>, CompileError> {
+        if Parser::is_component(wasm) {
+            return Err(CompileError::Validate(
+                "only core WebAssembly modules can be metered".to_string(),
+            ));
+        }
+
+        self.rewrite(wasm)
+            .map_err(|e| CompileError::Validate(e.message().to_string()))
+    }
+
+    fn rewrite(&self, wasm: &[u8]) -> Result<Vec<u8>, BinaryReaderError> {
+        let mut output = Vec::with_capacity(wasm.len() + wasm.len() / 8);
+        // The fuel counter is imported after all other globals, so it takes
+        // the index of the first defined global and shifts the others by one.
+        let mut fuel_global = 0;
+        let mut fuel_imported = false;
+        let mut code = Vec::new();
+        let mut bodies_left = 0;
+
+        for payload in Parser::new(0).parse_all(wasm) {
+            let payload = payload?;
+
+            // Add an import section if the module doesn't have one. Custom
+            // sections (id 0) can appear anywhere and the type section is
+            // the only one which comes before the imports.
+            let past_imports = match &payload {
+                Payload::End(_) => true,
+                other => matches!(other.as_section(), Some((id, _)) if id > IMPORT_SECTION),
+            };
+            if !fuel_imported && past_imports {
+                let mut contents = Vec::new();
+                write_u32(&mut contents, 1);
+                write_fuel_import(&mut contents);
+                write_section(&mut output, IMPORT_SECTION, &contents);
+                fuel_imported = true;
+            }
+
+            match payload {
+                Payload::Version { range, .. } => {
+                    output.extend_from_slice(&wasm[range]);
+                }
+                Payload::ImportSection(imports) => {
+                    for import in imports.clone() {
+                        if let TypeRef::Global(_) = import?.ty {
+                            fuel_global += 1;
+                        }
+                    }
+
+                    let mut contents = Vec::new();
+                    write_u32(&mut contents, imports.count() + 1);
+                    contents.extend_from_slice(&wasm[skip_count(wasm, imports.range())?]);
+                    write_fuel_import(&mut contents);
+                    write_section(&mut output, IMPORT_SECTION, &contents);
+                    fuel_imported = true;
+                }
+                Payload::ExportSection(exports) => {
+                    let mut contents = Vec::new();
+                    write_u32(&mut contents, exports.count());
+                    for export in exports {
+                        let export = export?;
+                        let (kind, index) = match export.kind {
+                            ExternalKind::Func => (0x00, export.index),
+                            ExternalKind::Table => (0x01, export.index),
+                            ExternalKind::Memory => (0x02, export.index),
+                            ExternalKind::Global => (0x03, shift_global(export.index, fuel_global)),
+                            ExternalKind::Tag => (0x04, export.index),
+                        };
+                        write_name(&mut contents, export.name);
+                        contents.push(kind);
+                        write_u32(&mut contents, index);
+                    }
+                    write_section(&mut output, EXPORT_SECTION, &contents);
+                }
+                Payload::CodeSectionStart { count, .. } => {
+                    write_u32(&mut code, count);
+                    bodies_left = count;
+                    if bodies_left == 0 {
+                        write_section(&mut output, CODE_SECTION, &code);
+                    }
+                }
+                Payload::CodeSectionEntry(body) => {
+                    let body = self.instrument_body(wasm, &body, fuel_global)?;
+                    write_u32(&mut code, body.len() as u32);
+                    code.extend_from_slice(&body);
+
+                    bodies_left -= 1;
+                    if bodies_left == 0 {
+                        write_section(&mut output, CODE_SECTION, &code);
+                    }
+                }
+                other => {
+                    if let Some((id, range)) = other.as_section() {
+                        write_section(&mut output, id, &wasm[range]);
+                    }
+                }
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// Charge for the instructions in a function body, one block of
+    /// straight-line code at a time.
+    fn instrument_body(
+        &self,
+        wasm: &[u8],
+        body: &FunctionBody<'_>,
+        fuel_global: u32,
+    ) -> Result<Vec<u8>, BinaryReaderError> {
+        let mut operators = body.get_operators_reader()?;
+        let locals = body.range().start..operators.original_position();
+        let mut output = wasm[locals].to_vec();
+
+        let mut block = Vec::new();
+        let mut cost = 0_u64;
+
+        while !operators.eof() {
+            let start = operators.original_position();
+            let operator = operators.read()?;
+            let end = operators.original_position();
+
+            cost = cost.saturating_add((self.cost_function)(&operator));
+            match operator {
+                Operator::GlobalGet { global_index } => {
+                    block.push(0x23);
+                    write_u32(&mut block, shift_global(global_index, fuel_global));
+                }
+                Operator::GlobalSet { global_index } => {
+                    block.push(0x24);
+                    write_u32(&mut block, shift_global(global_index, fuel_global));
+                }
+                _ => block.extend_from_slice(&wasm[start..end]),
+            }
+
+            if ends_block(&operator) {
+                write_charge(&mut output, fuel_global, cost);
+                output.append(&mut block);
+                cost = 0;
+            }
+        }
+
+        write_charge(&mut output, fuel_global, cost);
+        output.append(&mut block);
+
+        Ok(output)
+    }
+}
+
+impl Default for Metering {
+    /// Every instruction costs one unit of fuel.
+    fn default() -> Self {
+        Metering::new(|_| 1)
+    }
+}
+
+impl fmt::Debug for Metering {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metering").finish_non_exhaustive()
+    }
+}
+
+/// Whether execution might not continue with the next instruction.
+fn ends_block(operator: &Operator<'_>) -> bool {
+    matches!(
+        operator,
+        Operator::Block { .. }
+            | Operator::Loop { .. }
+            | Operator::If { .. }
+            | Operator::Else
+            | Operator::End
+            | Operator::Br { .. }
+            | Operator::BrIf { .. }
+            | Operator::BrTable { .. }
+            | Operator::BrOnNull { .. }
+            | Operator::BrOnNonNull { .. }
+            | Operator::Return
+            | Operator::Unreachable
+            | Operator::Call { .. }
+            | Operator::CallIndirect { .. }
+            | Operator::CallRef { .. }
+            | Operator::ReturnCall { .. }
+            | Operator::ReturnCallIndirect { .. }
+            | Operator::ReturnCallRef { .. }
+            | Operator::Try { .. }
+            | Operator::Catch { .. }
+            | Operator::CatchAll
+            | Operator::Delegate { .. }
+            | Operator::Throw { .. }
+            | Operator::Rethrow { .. }
+    )
+}
+
+fn shift_global(index: u32, fuel_global: u32) -> u32 {
+    if index >= fuel_global {
+        index + 1
+    } else {
+        index
+    }
+}
+
+/// The range of a section's entries, skipping the leading entry count.
+pub(crate) fn skip_count(
+    wasm: &[u8],
+    range: Range<usize>,
+) -> Result<Range<usize>, BinaryReaderError> {
+    let mut reader = BinaryReader::new_with_offset(&wasm[range.clone()], range.start);
+    reader.read_var_u32()?;
+    Ok(reader.original_position()..range.end)
+}
+
+/// Subtract `cost` from the fuel counter and trap if it drops below zero.
+fn write_charge(output: &mut Vec<u8>, fuel_global: u32, cost: u64) {
+    if cost == 0 {
+        return;
+    }
+
+    // global.get $fuel
+    output.push(0x23);
+    write_u32(output, fuel_global);
+    // i64.const $cost
+    output.push(0x42);
+    write_i64(output, cost.min(i64::MAX as u64) as i64);
+    // i64.sub
+    output.push(0x7d);
+    // global.set $fuel
+    output.push(0x24);
+    write_u32(output, fuel_global);
+    // global.get $fuel
+    output.push(0x23);
+    write_u32(output, fuel_global);
+    // i64.const 0
+    output.extend_from_slice(&[0x42, 0x00]);
+    // i64.lt_s
+    output.push(0x53);
+    // if (unreachable) end
+    output.extend_from_slice(&[0x04, 0x40, 0x00, 0x0b]);
+}
+
+/// An import of a mutable `i64` global.
+fn write_fuel_import(output: &mut Vec<u8>) {
+    write_name(output, FUEL_NAMESPACE);
+    write_name(output, FUEL_NAME);
+    output.extend_from_slice(&[0x03, 0x7e, 0x01]);
+}
+
+pub(crate) fn write_section(output: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    output.push(id);
+    write_u32(output, contents.len() as u32);
+    output.extend_from_slice(contents);
+}
+
+pub(crate) fn write_name(output: &mut Vec<u8>, name: &str) {
+    write_u32(output, name.len() as u32);
+    output.extend_from_slice(name.as_bytes());
+}
+
+pub(crate) fn write_u32(output: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            output.push(byte);
+            return;
+        }
+        output.push(byte | 0x80);
+    }
+}
+
+pub(crate) fn write_i64(output: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            output.push(byte);
+            return;
+        }
+        output.push(byte | 0x80);
+    }
+}
@@ -125,6 +125,15 @@ impl Module {
         Ok(Self(module_imp::Module::from_binary(binary).await?))
     }
 
+    /// Creates a new WebAssembly module from a `fetch` response, using
+    /// `WebAssembly.compileStreaming` so that the module is compiled while it
+    /// is being downloaded.
+    ///
+    /// The response must be served with the `application/wasm` content type.
+    pub async fn from_response(response: web_sys::Response) -> Result<Self, CompileError> {
+        Ok(Self(module_imp::Module::from_response(response).await?))
+    }
+
     /// Creates a new WebAssembly module from the compiled module and its binary data.
     pub fn from_module_and_binary(module: WebAssembly::Module, binary: &[u8]) -> Self {
         Self(module_imp::Module::from_module_and_binary(
@@ -278,6 +287,39 @@ impl Module {
         self.0.custom_sections(name)
     }
 
+    /// Returns the names of the custom sections in the module, in the
+    /// order in which they first appear in the WebAssembly bytecode.
+    ///
+    /// Each name is only reported once, even if several sections share it.
+    /// Use [`Module::custom_sections`] to read their contents.
+    pub fn custom_section_names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.0.custom_section_names()
+    }
+
+    /// Returns the name of the function at `index`, as recorded in the
+    /// module's name section.
+    ///
+    /// The index covers both imported and locally defined functions, matching
+    /// [`FrameInfo::func_index`], so it can be used to symbolize traps.
+    pub fn function_name(&self, index: u32) -> Option<&str> {
+        self.0.function_name(index)
+    }
+
+    /// Returns all the function names recorded in the module's name section,
+    /// ordered by function index.
+    pub fn function_names(&self) -> impl Iterator<Item = (u32, &str)> + '_ {
+        self.0.function_names()
+    }
+
+    /// Returns the offset of the body of the function at `index` in the
+    /// WebAssembly bytecode, or `None` for imported functions.
+    ///
+    /// Subtracting it from [`FrameInfo::module_offset`] gives the offset of
+    /// a frame within its function.
+    pub fn function_offset(&self, index: u32) -> Option<u32> {
+        self.0.function_offset(index)
+    }
+
     /// The ABI of the [`ModuleInfo`] is very unstable, we refactor it very often.
     /// This function is public because in some cases it can be useful to get some
     /// extra information from the module.
@@ -10,15 +10,16 @@ use core::convert::TryFrom;
 use std::vec::Vec;
 use wasmer_types::entity::EntityRef;
 use wasmer_types::{
-    ExportIndex, FunctionIndex, FunctionType, GlobalIndex, GlobalType, ImportIndex, MemoryIndex,
-    MemoryType, ModuleInfo, Pages, SignatureIndex, TableIndex, TableType, Type,
+    CustomSectionIndex, ExportIndex, FunctionIndex, FunctionType, GlobalIndex, GlobalType,
+    ImportIndex, IndexType, MemoryIndex, MemoryType, ModuleInfo, Pages, SignatureIndex, TableIndex,
+    TableType, TagIndex, Type,
 };
 
 use wasmparser::{
     self, BinaryReaderError, Export, ExportSectionReader, ExternalKind, FunctionSectionReader,
     GlobalSectionReader, GlobalType as WPGlobalType, ImportSectionReader, MemorySectionReader,
-    MemoryType as WPMemoryType, NameSectionReader, Parser, Payload, TableSectionReader, TypeRef,
-    TypeSectionReader,
+    MemoryType as WPMemoryType, NameSectionReader, Parser, Payload, TableSectionReader,
+    TagSectionReader, TypeRef, TypeSectionReader,
 };
 
 pub type WasmResult<T> = Result<T, String>;
@@ -26,6 +27,11 @@ pub type WasmResult<T> = Result<T, String>;
 #[derive(Default)]
 pub struct ModuleInfoPolyfill {
     pub(crate) info: ModuleInfo,
+    /// Offsets of the bodies of the locally defined functions in the binary
+    pub(crate) function_offsets: Vec<u32>,
+    /// The names of all the custom sections, in the order of the binary,
+    /// and where their data is in the info
+    pub(crate) custom_sections: Vec<(String, CustomSectionIndex)>,
 }
 
 impl ModuleInfoPolyfill {
@@ -149,6 +155,27 @@ impl ModuleInfoPolyfill {
         Ok(())
     }
 
+    pub(crate) fn declare_tag_import(
+        &mut self,
+        sig_index: SignatureIndex,
+        module: &str,
+        field: &str,
+    ) -> WasmResult<()> {
+        debug_assert_eq!(
+            self.info.tags.len(),
+            self.info.num_imported_tags,
+            "Imported tags must be declared first"
+        );
+        self.declare_import(
+            ImportIndex::Tag(TagIndex::from_u32(self.info.num_imported_tags as _)),
+            module,
+            field,
+        )?;
+        self.info.tags.push(sig_index);
+        self.info.num_imported_tags += 1;
+        Ok(())
+    }
+
     pub(crate) fn reserve_func_types(&mut self, num: u32) -> WasmResult<()> {
         self.info
             .functions
@@ -197,6 +224,16 @@ impl ModuleInfoPolyfill {
         Ok(())
     }
 
+    pub(crate) fn reserve_tags(&mut self, num: u32) -> WasmResult<()> {
+        self.info.tags.reserve_exact(usize::try_from(num).unwrap());
+        Ok(())
+    }
+
+    pub(crate) fn declare_tag(&mut self, sig_index: SignatureIndex) -> WasmResult<()> {
+        self.info.tags.push(sig_index);
+        Ok(())
+    }
+
     pub(crate) fn reserve_exports(&mut self, num: u32) -> WasmResult<()> {
         self.info.exports.reserve(usize::try_from(num).unwrap());
         Ok(())
@@ -239,10 +276,37 @@ impl ModuleInfoPolyfill {
         self.declare_export(ExportIndex::Global(global_index), name)
     }
 
+    pub(crate) fn declare_tag_export(&mut self, tag_index: TagIndex, name: &str) -> WasmResult<()> {
+        self.declare_export(ExportIndex::Tag(tag_index), name)
+    }
+
     pub(crate) fn declare_module_name(&mut self, name: &str) -> WasmResult<()> {
         self.info.name = Some(name.to_string());
         Ok(())
     }
+
+    pub(crate) fn declare_function_name(
+        &mut self,
+        func_index: FunctionIndex,
+        name: &str,
+    ) -> WasmResult<()> {
+        self.info
+            .function_names
+            .insert(func_index, name.to_string());
+        Ok(())
+    }
+
+    pub(crate) fn declare_custom_section(&mut self, name: &str, data: &[u8]) -> WasmResult<()> {
+        let index = self.info.custom_sections_data.push(data.into());
+        // The info only indexes the first section with a given name, the
+        // others are still listed with it
+        self.info
+            .custom_sections
+            .entry(name.to_string())
+            .or_insert(index);
+        self.custom_sections.push((name.to_string(), index));
+        Ok(())
+    }
 }
 
 fn transform_err(err: BinaryReaderError) -> String {
@@ -280,13 +344,22 @@ pub fn translate_module<'data>(data: &'data [u8]) -> WasmResult<ModuleInfoPolyfi
                 parse_global_section(globals, &mut module_info)?;
             }
 
+            Payload::TagSection(tags) => {
+                parse_tag_section(tags, &mut module_info)?;
+            }
+
             Payload::ExportSection(exports) => {
                 parse_export_section(exports, &mut module_info)?;
             }
 
+            Payload::CodeSectionEntry(body) => {
+                module_info.function_offsets.push(body.range().start as u32);
+            }
+
             Payload::CustomSection(sectionreader) => {
                 // We still add the custom section data, but also read it as name section reader
                 let name = sectionreader.name();
+                module_info.declare_custom_section(name, sectionreader.data())?;
                 if name == "name" {
                     parse_name_section(
                         NameSectionReader::new(sectionreader.data(), sectionreader.data_offset()),
@@ -387,8 +460,12 @@ pub fn parse_import_section<'data>(
                     field_name,
                 )?;
             }
-            TypeRef::Tag(_) => {
-                unimplemented!("exception handling not implemented yet")
+            TypeRef::Tag(tag) => {
+                module_info.declare_tag_import(
+                    SignatureIndex::from_u32(tag.func_type_idx),
+                    module_name,
+                    field_name,
+                )?;
             }
             TypeRef::Memory(WPMemoryType {
                 shared,
@@ -396,14 +473,16 @@ pub fn parse_import_section<'data>(
                 initial,
                 maximum,
             }) => {
-                if memory64 {
-                    unimplemented!("64bit memory not implemented yet");
-                }
                 module_info.declare_memory_import(
                     MemoryType {
                         minimum: Pages(initial as u32),
                         maximum: maximum.map(|p| Pages(p as u32)),
                         shared,
+                        index_type: if memory64 {
+                            IndexType::I64
+                        } else {
+                            IndexType::I32
+                        },
                     },
                     module_name,
                     field_name,
@@ -484,13 +563,15 @@ pub fn parse_memory_section(
             initial,
             maximum,
         } = entry.map_err(transform_err)?;
-        if memory64 {
-            unimplemented!("64bit memory not implemented yet");
-        }
         module_info.declare_memory(MemoryType {
             minimum: Pages(initial as u32),
             maximum: maximum.map(|p| Pages(p as u32)),
             shared,
+            index_type: if memory64 {
+                IndexType::I64
+            } else {
+                IndexType::I32
+            },
         })?;
     }
 
@@ -519,6 +600,21 @@ pub fn parse_global_section(
     Ok(())
 }
 
+/// Parses the Tag section of the wasm module.
+pub fn parse_tag_section(
+    tags: TagSectionReader,
+    module_info: &mut ModuleInfoPolyfill,
+) -> WasmResult<()> {
+    module_info.reserve_tags(tags.count())?;
+
+    for entry in tags {
+        let tag = entry.map_err(transform_err)?;
+        module_info.declare_tag(SignatureIndex::from_u32(tag.func_type_idx))?;
+    }
+
+    Ok(())
+}
+
 /// Parses the Export section of the wasm module.
 pub fn parse_export_section<'data>(
     exports: ExportSectionReader<'data>,
@@ -550,9 +646,7 @@ pub fn parse_export_section<'data>(
             ExternalKind::Global => {
                 module_info.declare_global_export(GlobalIndex::new(index), name)?
             }
-            ExternalKind::Tag => {
-                unimplemented!("exception handling not implemented yet")
-            }
+            ExternalKind::Tag => module_info.declare_tag_export(TagIndex::new(index), name)?,
         }
     }
     Ok(())
@@ -571,15 +665,15 @@ pub fn parse_name_section<'data>(
 ) -> WasmResult<()> {
     while let Some(Ok(subsection)) = names.next() {
         match subsection {
-            wasmparser::Name::Function(_function_subsection) => {
-                //for naming in function_subsection.into_iter().flatten() {
-                //    if naming.index != std::u32::MAX {
-                //        environ.declare_function_name(
-                //            FunctionIndex::from_u32(naming.index),
-                //            naming.name,
-                //        )?;
-                //    }
-                //}
+            wasmparser::Name::Function(function_subsection) => {
+                for naming in function_subsection.into_iter().flatten() {
+                    if naming.index != u32::MAX {
+                        module_info.declare_function_name(
+                            FunctionIndex::from_u32(naming.index),
+                            naming.name,
+                        )?;
+                    }
+                }
             }
             wasmparser::Name::Module {
                 name,
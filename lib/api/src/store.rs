@@ -1,10 +1,20 @@
 use crate::engine::{AsEngineRef, Engine, EngineRef};
+use crate::errors::RuntimeError;
+use crate::js::trap::Trap;
+use crate::js::wasm_bindgen_polyfill::Global as JSGlobal;
+use crate::value::Value;
 use derivative::Derivative;
 use std::{
     fmt,
     ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
 };
+use wasm_bindgen::JsValue;
 pub use wasmer_types::StoreId;
+use wasmer_types::TrapCode;
 
 pub use crate::js::store::{StoreHandle, StoreObjects};
 
@@ -17,6 +27,174 @@ pub(crate) struct StoreInner {
     pub(crate) objects: StoreObjects,
     #[derivative(Debug = "ignore")]
     pub(crate) engine: Engine,
+    pub(crate) interrupts: InterruptHandle,
+    pub(crate) epoch_deadline: u64,
+    /// The fuel counter imported by metered modules, created on first use.
+    fuel: Option<JSGlobal>,
+    #[derivative(Debug = "ignore")]
+    pub(crate) host_call_interceptor: Option<Arc<dyn HostCallInterceptor>>,
+    pub(crate) host_calls: HostCallCounter,
+}
+
+impl StoreInner {
+    /// Traps if the epoch deadline has been reached.
+    ///
+    /// The JS engine gives us no way to stop running WebAssembly code, so
+    /// this is checked whenever execution passes through the host: when
+    /// calling an exported function and when the guest calls an import.
+    pub(crate) fn check_epoch_deadline(&self) -> Result<(), RuntimeError> {
+        if self.interrupts.epoch() < self.epoch_deadline {
+            return Ok(());
+        }
+
+        Err(trap(TrapCode::Interrupt))
+    }
+
+    /// The global holding the fuel left for metered modules.
+    pub(crate) fn fuel_global(&mut self) -> &JSGlobal {
+        self.fuel.get_or_insert_with(|| {
+            let descriptor = js_sys::Object::new();
+            js_sys::Reflect::set(&descriptor, &"value".into(), &"i64".into()).unwrap();
+            js_sys::Reflect::set(&descriptor, &"mutable".into(), &true.into()).unwrap();
+            JSGlobal::new(&descriptor, &JsValue::from(0_i64))
+                .expect("unable to create the fuel global")
+        })
+    }
+
+    /// The remaining fuel, which goes negative once it has been exhausted.
+    fn fuel(&self) -> i64 {
+        self.fuel
+            .as_ref()
+            .and_then(|global| i64::try_from(global.value()).ok())
+            .unwrap_or_default()
+    }
+
+    fn set_fuel(&mut self, fuel: u64) {
+        let fuel = i64::try_from(fuel).unwrap_or(i64::MAX);
+        self.fuel_global().set_value(&JsValue::from(fuel));
+    }
+
+    fn add_fuel(&mut self, fuel: u64) {
+        let remaining = self.fuel().max(0) as u64;
+        self.set_fuel(remaining.saturating_add(fuel));
+    }
+
+    /// Metered modules trap with an `unreachable` instruction when they run
+    /// out of fuel, so report it with the right trap code instead.
+    pub(crate) fn out_of_fuel_or(&self, error: RuntimeError) -> RuntimeError {
+        if self.fuel.is_some() && self.fuel() < 0 {
+            trap(TrapCode::OutOfFuel)
+        } else {
+            error
+        }
+    }
+
+    fn set_epoch_deadline(&mut self, ticks_beyond_current: u64) {
+        self.epoch_deadline = self.interrupts.epoch().saturating_add(ticks_beyond_current);
+    }
+}
+
+fn trap(code: TrapCode) -> RuntimeError {
+    RuntimeError::new_from_source(Trap::user(Box::new(code)), vec![], Some(code))
+}
+
+/// A call from WebAssembly into a host function, as seen by a
+/// [`HostCallInterceptor`].
+#[derive(Debug)]
+pub struct HostCall<'a> {
+    /// The module the function was imported from.
+    pub module: &'a str,
+    /// The name the function was imported as.
+    pub name: &'a str,
+    /// The arguments passed to the function.
+    pub args: &'a [Value],
+}
+
+/// A hook wrapping every call from WebAssembly into a host function, set
+/// with [`Store::set_host_call_interceptor()`].
+///
+/// This lets cross-cutting concerns like logging or metering of host calls
+/// be implemented in one place instead of wrapping each import.
+///
+/// Only functions created with the [`Function`][crate::Function]
+/// constructors and imported by instances created while an interceptor is
+/// set are intercepted. Calls made directly from the host with
+/// [`Function::call()`][crate::Function::call] aren't.
+pub trait HostCallInterceptor: Send + Sync {
+    /// Called before the host function runs.
+    ///
+    /// Returning an error aborts the call, and the error is raised in the
+    /// calling WebAssembly code instead.
+    fn before_call(
+        &self,
+        _store: &mut StoreMut<'_>,
+        _call: &HostCall<'_>,
+    ) -> Result<(), RuntimeError> {
+        Ok(())
+    }
+
+    /// Called once the host function has returned, with its results or the
+    /// error it raised.
+    fn after_call(
+        &self,
+        _store: &mut StoreMut<'_>,
+        _call: &HostCall<'_>,
+        _result: Result<&[Value], &RuntimeError>,
+    ) {
+    }
+}
+
+/// A handle used to interrupt the WebAssembly code running in a [`Store`]
+/// from the outside, e.g. from a timer or another worker.
+///
+/// Each store has an epoch counter which is advanced with
+/// [`InterruptHandle::increment_epoch()`]. Once it reaches the deadline set
+/// with [`Store::set_epoch_deadline()`], execution traps with
+/// [`TrapCode::Interrupt`] the next time it passes through the host, i.e.
+/// when the guest calls an imported function or when an exported function is
+/// called.
+///
+/// Code which never calls into the host, e.g. a tight loop, is only
+/// interrupted once its module is instrumented with
+/// [`Interruption`][crate::Interruption], which makes its loops check the
+/// deadline every so often. Otherwise it needs to run in a worker that gets
+/// terminated instead.
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle {
+    epoch: Arc<AtomicU64>,
+}
+
+impl InterruptHandle {
+    /// Advance the epoch counter by one tick.
+    pub fn increment_epoch(&self) {
+        self.epoch.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// The current value of the epoch counter.
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::SeqCst)
+    }
+}
+
+/// Counts the calls from WebAssembly into host functions of a [`Store`].
+///
+/// The counter can be read from other workers, e.g. to attribute the cost
+/// of the code running in a store. Like the [`InterruptHandle`], it is
+/// updated whenever the guest calls an imported function.
+#[derive(Debug, Clone, Default)]
+pub struct HostCallCounter {
+    calls: Arc<AtomicU64>,
+}
+
+impl HostCallCounter {
+    /// The number of host calls counted so far.
+    pub fn count(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn increment(&self) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+    }
 }
 
 /// The store represents all global state that can be manipulated by
@@ -39,6 +217,11 @@ impl Store {
             inner: Box::new(StoreInner {
                 objects: Default::default(),
                 engine: engine.into(),
+                interrupts: InterruptHandle::default(),
+                epoch_deadline: u64::MAX,
+                fuel: None,
+                host_call_interceptor: None,
+                host_calls: HostCallCounter::default(),
             }),
         }
     }
@@ -63,6 +246,83 @@ impl Store {
     pub fn id(&self) -> StoreId {
         self.inner.objects.id()
     }
+
+    /// Returns a handle that can be used to interrupt the execution of this
+    /// store by incrementing its epoch.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.inner.interrupts.clone()
+    }
+
+    /// Returns the counter of the calls from WebAssembly into host functions
+    /// of this store.
+    pub fn host_call_counter(&self) -> HostCallCounter {
+        self.inner.host_calls.clone()
+    }
+
+    /// Count the calls from WebAssembly into host functions with `counter`
+    /// from now on, e.g. to count the calls of each thread separately.
+    pub fn set_host_call_counter(&mut self, counter: HostCallCounter) {
+        self.inner.host_calls = counter;
+    }
+
+    /// Interrupt execution once the epoch has been incremented
+    /// `ticks_beyond_current` more times.
+    ///
+    /// By default there is no deadline. See [`InterruptHandle`] for when the
+    /// deadline is checked.
+    pub fn set_epoch_deadline(&mut self, ticks_beyond_current: u64) {
+        self.inner.set_epoch_deadline(ticks_beyond_current);
+    }
+
+    /// Returns the fuel left for modules instrumented with
+    /// [`Metering`][crate::Metering].
+    pub fn fuel_remaining(&self) -> u64 {
+        self.inner.fuel().max(0) as u64
+    }
+
+    /// Sets the fuel left for modules instrumented with
+    /// [`Metering`][crate::Metering].
+    ///
+    /// Once it is exhausted, they trap with [`TrapCode::OutOfFuel`].
+    pub fn set_fuel(&mut self, fuel: u64) {
+        self.inner.set_fuel(fuel);
+    }
+
+    /// Adds to the fuel left for modules instrumented with
+    /// [`Metering`][crate::Metering].
+    pub fn add_fuel(&mut self, fuel: u64) {
+        self.inner.add_fuel(fuel);
+    }
+
+    /// Reclaims the objects of this store which are no longer used, returning
+    /// how many were reclaimed.
+    ///
+    /// Function environments, functions and extern references are kept alive
+    /// by their handles (e.g. [`FunctionEnv`][crate::FunctionEnv] or
+    /// [`ExternRef`][crate::ExternRef]), by the host functions using them and
+    /// by the instances importing them. Once all of those have been dropped,
+    /// their slots in the store can be reused.
+    ///
+    /// Objects which are only referenced from WebAssembly, like an
+    /// `externref` stored in a table, aren't visible to the store, so a
+    /// handle needs to be kept for as long as they are in use.
+    pub fn gc(&mut self) -> usize {
+        self.inner.objects.gc()
+    }
+
+    /// Wrap every call from WebAssembly into a host function with
+    /// `interceptor`, replacing the previous one.
+    ///
+    /// The interceptor applies to the imports of instances created from now
+    /// on. See [`HostCallInterceptor`] for which calls are intercepted.
+    pub fn set_host_call_interceptor(&mut self, interceptor: impl HostCallInterceptor + 'static) {
+        self.inner.host_call_interceptor = Some(Arc::new(interceptor));
+    }
+
+    /// Stop intercepting host calls.
+    pub fn clear_host_call_interceptor(&mut self) {
+        self.inner.host_call_interceptor = None;
+    }
 }
 
 impl PartialEq for Store {
@@ -162,6 +422,47 @@ impl<'a> StoreMut<'a> {
         a.inner.objects.id() == b.inner.objects.id()
     }
 
+    /// Interrupt execution once the epoch has been incremented
+    /// `ticks_beyond_current` more times.
+    ///
+    /// This lets host functions extend the deadline of a running store. See
+    /// [`Store::set_epoch_deadline()`].
+    pub fn set_epoch_deadline(&mut self, ticks_beyond_current: u64) {
+        self.inner.set_epoch_deadline(ticks_beyond_current);
+    }
+
+    /// Returns the counter of host calls. See [`Store::host_call_counter()`].
+    pub fn host_call_counter(&self) -> HostCallCounter {
+        self.inner.host_calls.clone()
+    }
+
+    /// Replaces the counter of host calls. See
+    /// [`Store::set_host_call_counter()`].
+    pub fn set_host_call_counter(&mut self, counter: HostCallCounter) {
+        self.inner.host_calls = counter;
+    }
+
+    /// Returns the fuel left for metered modules. See [`Store::fuel_remaining()`].
+    pub fn fuel_remaining(&self) -> u64 {
+        self.inner.fuel().max(0) as u64
+    }
+
+    /// Sets the fuel left for metered modules. See [`Store::set_fuel()`].
+    pub fn set_fuel(&mut self, fuel: u64) {
+        self.inner.set_fuel(fuel);
+    }
+
+    /// Adds to the fuel left for metered modules. See [`Store::add_fuel()`].
+    pub fn add_fuel(&mut self, fuel: u64) {
+        self.inner.add_fuel(fuel);
+    }
+
+    /// Reclaims the objects of this store which are no longer used. See
+    /// [`Store::gc()`].
+    pub fn gc(&mut self) -> usize {
+        self.inner.objects.gc()
+    }
+
     #[allow(unused)]
     pub(crate) fn engine_and_objects_mut(&mut self) -> (&Engine, &mut StoreObjects) {
         (&self.inner.engine, &mut self.inner.objects)
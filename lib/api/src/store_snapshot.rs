@@ -0,0 +1,415 @@
+//! Moving the state of a running instance to another Web Worker.
+
+use std::collections::HashSet;
+
+use js_sys::{Array, Object, Reflect, Uint8Array, WebAssembly};
+use thiserror::Error;
+use wasm_bindgen::{JsCast, JsError, JsValue};
+use wasmer_types::{
+    entity::EntityRef, ExportIndex, IndexType, MemoryError, MemoryType, Pages, WASM_PAGE_SIZE,
+};
+
+use crate::exports::ExportError;
+use crate::externals::{Extern, Memory};
+use crate::instance::Instance;
+use crate::module::Module;
+use crate::store::{AsStoreMut, AsStoreRef};
+use crate::vm::VMMemory;
+use crate::Mutability;
+
+const MODULE_KEY: &str = "module";
+const BINARY_KEY: &str = "binary";
+const MEMORIES_KEY: &str = "memories";
+const DEFINED_MEMORIES_KEY: &str = "definedMemories";
+const GLOBALS_KEY: &str = "globals";
+const NAME_KEY: &str = "name";
+const MEMORY_KEY: &str = "memory";
+const DATA_KEY: &str = "data";
+const MAXIMUM_KEY: &str = "maximum";
+const VALUE_KEY: &str = "value";
+const INDEX_KEY: &str = "index";
+
+/// The transferable state of an [`Instance`] and the [`Store`][crate::Store]
+/// it lives in.
+///
+/// A snapshot holds the instance's [`Module`], its exported memories and the
+/// values of its exported mutable globals. The memories it imports are
+/// recreated for the new instance to import, while the contents of the ones
+/// it defines are written into the memories the new instance defines. The
+/// globals of an instance can
+/// only be read from the outside when they are exported, so instances with
+/// other mutable globals, or with mutable globals holding references, can't
+/// be snapshotted. Imported globals belong to whoever provides them, and
+/// aren't part of the snapshot. It can be converted into a plain
+/// JS object with [`StoreSnapshot::to_js()`], posted to another Web Worker,
+/// and turned back into a snapshot there with [`StoreSnapshot::from_js()`].
+///
+/// Shared memories are sent as-is, so both workers keep operating on the
+/// same memory. Other memories can't be shared between workers and are
+/// copied instead. A shared memory the instance defines can't be given to
+/// the new instance, which defines its own, so it can't be snapshotted.
+///
+/// # Usage
+///
+/// ```ignore
+/// # use wasmer::{Imports, Instance, Store, StoreSnapshot};
+/// # async fn run(snapshot: wasm_bindgen::JsValue) -> anyhow::Result<()> {
+/// // On the receiving worker
+/// let snapshot = StoreSnapshot::from_js(snapshot).map_err(anyhow::Error::msg)?;
+/// let mut store = Store::default();
+/// let mut imports = Imports::new();
+/// for (name, memory) in snapshot.restore_memories(&mut store)? {
+///     imports.define("env", &name, memory);
+/// }
+/// let instance =
+///     Instance::new(&mut store, snapshot.module(), &imports, Default::default()).await?;
+/// snapshot.restore_instance(&mut store, &instance)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct StoreSnapshot {
+    module: Module,
+    memories: Vec<(String, MemorySnapshot)>,
+    /// The contents of the memories the instance defines
+    defined_memories: Vec<(String, Vec<u8>)>,
+    globals: Vec<(String, JsValue)>,
+}
+
+#[derive(Debug, Clone)]
+enum MemorySnapshot {
+    Shared(VMMemory),
+    Copied { ty: MemoryType, data: Vec<u8> },
+}
+
+/// The reasons an instance can't be snapshotted.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SnapshotError {
+    /// The instance defines a mutable global which isn't exported, so its
+    /// value can't be read.
+    #[error("the mutable global {0} isn't exported, so its value can't be captured")]
+    UnexportedGlobal(u32),
+    /// The instance exports a mutable global holding a reference, which
+    /// can't be posted to another worker.
+    #[error("the mutable global \"{0}\" holds a reference, which can't be captured")]
+    ReferenceGlobal(String),
+    /// The instance defines a shared memory, which the restored instance
+    /// couldn't share as it defines its own.
+    #[error("the shared memory \"{0}\" is defined by the instance, so it can't be captured")]
+    DefinedSharedMemory(String),
+}
+
+/// The reasons a snapshot can't be restored into an instance.
+#[derive(Debug, Clone, Error)]
+pub enum RestoreError {
+    /// The instance doesn't export what was snapshotted, as it should.
+    #[error(transparent)]
+    Export(#[from] ExportError),
+    /// A memory of the instance can't hold the snapshotted contents.
+    #[error(transparent)]
+    Memory(#[from] MemoryError),
+}
+
+impl StoreSnapshot {
+    /// Capture the module, memories and globals exported by `instance`.
+    ///
+    /// Fails if the instance has mutable globals which can't be captured.
+    pub fn capture(store: &impl AsStoreRef, instance: &Instance) -> Result<Self, SnapshotError> {
+        let info = instance.module().info();
+        let exported = info
+            .exports
+            .values()
+            .filter_map(|export| match export {
+                ExportIndex::Global(index) => Some(*index),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        let defined = info.globals.iter().skip(info.num_imported_globals);
+        for (index, ty) in defined {
+            if ty.mutability == Mutability::Var && !exported.contains(&index) {
+                return Err(SnapshotError::UnexportedGlobal(index.as_u32()));
+            }
+        }
+
+        let mut memories = Vec::new();
+        let mut defined_memories = Vec::new();
+        let mut globals = Vec::new();
+
+        for (name, export) in instance.exports.iter() {
+            match export {
+                Extern::Memory(memory) => {
+                    let vm_memory = memory.0.handle.clone();
+                    let imported = matches!(
+                        info.exports.get(name),
+                        Some(ExportIndex::Memory(index))
+                            if index.index() < info.num_imported_memories
+                    );
+                    if !imported {
+                        if memory.ty(store).shared {
+                            return Err(SnapshotError::DefinedSharedMemory(name.clone()));
+                        }
+                        let data = Uint8Array::new(&vm_memory.memory.buffer()).to_vec();
+                        defined_memories.push((name.clone(), data));
+                        continue;
+                    }
+                    let snapshot = if memory.ty(store).shared {
+                        MemorySnapshot::Shared(vm_memory)
+                    } else {
+                        MemorySnapshot::Copied {
+                            ty: vm_memory.ty,
+                            data: Uint8Array::new(&vm_memory.memory.buffer()).to_vec(),
+                        }
+                    };
+                    memories.push((name.clone(), snapshot));
+                }
+                Extern::Global(global) => {
+                    let ty = global.ty(store);
+                    if ty.mutability != Mutability::Var {
+                        continue;
+                    }
+                    if ty.ty.is_ref() {
+                        return Err(SnapshotError::ReferenceGlobal(name.clone()));
+                    }
+                    globals.push((name.clone(), global.0.handle.global.value()));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(StoreSnapshot {
+            module: instance.module().clone(),
+            memories,
+            defined_memories,
+            globals,
+        })
+    }
+
+    /// The module the snapshotted instance was created from.
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    /// Recreate the snapshotted memories which the instance imported in
+    /// `store`, along with the names they were exported under.
+    ///
+    /// Shared memories refer to the same underlying memory as the original,
+    /// while copied memories are initialized with the snapshotted contents.
+    pub fn restore_memories(
+        &self,
+        store: &mut impl AsStoreMut,
+    ) -> Result<Vec<(String, Memory)>, MemoryError> {
+        self.memories
+            .iter()
+            .map(|(name, snapshot)| {
+                let memory = match snapshot {
+                    MemorySnapshot::Shared(vm_memory) => {
+                        Memory::new_from_existing(store, vm_memory.clone())
+                    }
+                    MemorySnapshot::Copied { ty, data } => {
+                        let ty = MemoryType {
+                            minimum: Pages((data.len() / WASM_PAGE_SIZE) as u32),
+                            ..*ty
+                        };
+                        let memory = Memory::new(store, ty)?;
+                        memory.view(store).write(0, data).map_err(|e| {
+                            MemoryError::Generic(format!("failed to restore the memory - {e}"))
+                        })?;
+                        memory
+                    }
+                };
+                Ok((name.clone(), memory))
+            })
+            .collect()
+    }
+
+    /// Restore the state of `instance`, created from the snapshotted module:
+    /// the memories it defines get their snapshotted contents, and its
+    /// exported globals their snapshotted values.
+    pub fn restore_instance(
+        &self,
+        store: &mut impl AsStoreMut,
+        instance: &Instance,
+    ) -> Result<(), RestoreError> {
+        for (name, data) in &self.defined_memories {
+            let memory = instance.exports.get_memory(name)?;
+            memory.grow_at_least(store, data.len() as u64)?;
+            memory
+                .view(store)
+                .write(0, data)
+                .map_err(|e| MemoryError::Generic(format!("failed to restore the memory - {e}")))?;
+        }
+
+        for (name, value) in &self.globals {
+            let global = instance.exports.get_global(name)?;
+            if global.ty(store).mutability != Mutability::Var {
+                return Err(ExportError::IncompatibleType.into());
+            }
+            global.0.handle.global.set_value(value);
+        }
+
+        Ok(())
+    }
+
+    /// Convert the snapshot into a JS object which can be sent to another
+    /// worker with `postMessage()`.
+    pub fn to_js(&self) -> JsValue {
+        let memories = Array::new();
+        for (name, snapshot) in &self.memories {
+            let entry = Object::new();
+            set(&entry, NAME_KEY, &name.into());
+            match snapshot {
+                MemorySnapshot::Shared(vm_memory) => {
+                    set(&entry, MEMORY_KEY, &vm_memory.memory);
+                    set(&entry, MAXIMUM_KEY, &maximum_to_js(vm_memory.ty.maximum));
+                    set(&entry, INDEX_KEY, &index_to_js(vm_memory.ty.index_type));
+                }
+                MemorySnapshot::Copied { ty, data } => {
+                    set(&entry, DATA_KEY, &Uint8Array::from(&data[..]));
+                    set(&entry, MAXIMUM_KEY, &maximum_to_js(ty.maximum));
+                    set(&entry, INDEX_KEY, &index_to_js(ty.index_type));
+                }
+            }
+            memories.push(&entry);
+        }
+
+        let defined_memories = Array::new();
+        for (name, data) in &self.defined_memories {
+            let entry = Object::new();
+            set(&entry, NAME_KEY, &name.into());
+            set(&entry, DATA_KEY, &Uint8Array::from(&data[..]));
+            defined_memories.push(&entry);
+        }
+
+        let globals = Array::new();
+        for (name, value) in &self.globals {
+            let entry = Object::new();
+            set(&entry, NAME_KEY, &name.into());
+            set(&entry, VALUE_KEY, value);
+            globals.push(&entry);
+        }
+
+        let snapshot = Object::new();
+        set(&snapshot, MODULE_KEY, &JsValue::from(self.module.clone()));
+        set(
+            &snapshot,
+            BINARY_KEY,
+            &Uint8Array::from(&self.module.serialize()[..]),
+        );
+        set(&snapshot, MEMORIES_KEY, &memories);
+        set(&snapshot, DEFINED_MEMORIES_KEY, &defined_memories);
+        set(&snapshot, GLOBALS_KEY, &globals);
+        snapshot.into()
+    }
+
+    /// Turn an object created by [`StoreSnapshot::to_js()`] back into a
+    /// snapshot.
+    pub fn from_js(value: JsValue) -> Result<Self, JsError> {
+        let module: WebAssembly::Module = get(&value, MODULE_KEY)?
+            .dyn_into()
+            .map_err(|_| JsError::new("the snapshot doesn't contain a module"))?;
+        let binary: Uint8Array = get(&value, BINARY_KEY)?
+            .dyn_into()
+            .map_err(|_| JsError::new("the snapshot doesn't contain the module's binary"))?;
+        let module = Module::from_module_and_binary(module, &binary.to_vec());
+
+        let mut memories = Vec::new();
+        for entry in array(&value, MEMORIES_KEY)? {
+            let name = name(&entry)?;
+            let maximum = get(&entry, MAXIMUM_KEY)?
+                .as_f64()
+                .map(|max| Pages(max as u32));
+            let index_type = index_from_js(&get(&entry, INDEX_KEY)?)?;
+
+            let snapshot = match get(&entry, MEMORY_KEY)?.dyn_into::<WebAssembly::Memory>() {
+                Ok(memory) => {
+                    let minimum =
+                        Uint8Array::new(&memory.buffer()).length() / WASM_PAGE_SIZE as u32;
+                    let ty = MemoryType {
+                        index_type,
+                        ..MemoryType::new(minimum, maximum.map(|max| max.0), true)
+                    };
+                    MemorySnapshot::Shared(VMMemory::new(memory, ty))
+                }
+                Err(_) => {
+                    let data: Uint8Array = get(&entry, DATA_KEY)?.dyn_into().map_err(|_| {
+                        JsError::new(&format!("the snapshot of memory \"{name}\" has no data"))
+                    })?;
+                    let data = data.to_vec();
+                    let minimum = (data.len() / WASM_PAGE_SIZE) as u32;
+                    let ty = MemoryType {
+                        index_type,
+                        ..MemoryType::new(minimum, maximum.map(|max| max.0), false)
+                    };
+                    MemorySnapshot::Copied { ty, data }
+                }
+            };
+            memories.push((name, snapshot));
+        }
+
+        let mut defined_memories = Vec::new();
+        for entry in array(&value, DEFINED_MEMORIES_KEY)? {
+            let name = name(&entry)?;
+            let data: Uint8Array = get(&entry, DATA_KEY)?.dyn_into().map_err(|_| {
+                JsError::new(&format!("the snapshot of memory \"{name}\" has no data"))
+            })?;
+            defined_memories.push((name, data.to_vec()));
+        }
+
+        let mut globals = Vec::new();
+        for entry in array(&value, GLOBALS_KEY)? {
+            globals.push((name(&entry)?, get(&entry, VALUE_KEY)?));
+        }
+
+        Ok(StoreSnapshot {
+            module,
+            memories,
+            defined_memories,
+            globals,
+        })
+    }
+}
+
+fn maximum_to_js(maximum: Option<Pages>) -> JsValue {
+    maximum.map_or(JsValue::UNDEFINED, |max| max.0.into())
+}
+
+/// The index type of a memory, named as in the memory descriptors of the JS
+/// API.
+fn index_to_js(index_type: IndexType) -> JsValue {
+    match index_type {
+        IndexType::I32 => "i32".into(),
+        IndexType::I64 => "i64".into(),
+    }
+}
+
+fn index_from_js(value: &JsValue) -> Result<IndexType, JsError> {
+    match value.as_string().as_deref() {
+        // Snapshots which predate memory64 only had 32-bit memories
+        None | Some("i32") => Ok(IndexType::I32),
+        Some("i64") => Ok(IndexType::I64),
+        Some(other) => Err(JsError::new(&format!(
+            "\"{other}\" isn't the index type of a memory"
+        ))),
+    }
+}
+
+fn set(target: &Object, key: &str, value: &JsValue) {
+    Reflect::set(target, &key.into(), value).expect("setting a property on a plain object");
+}
+
+fn get(target: &JsValue, key: &str) -> Result<JsValue, JsError> {
+    Reflect::get(target, &key.into())
+        .map_err(|_| JsError::new(&format!("unable to read \"{key}\" from the snapshot")))
+}
+
+fn array(target: &JsValue, key: &str) -> Result<Array, JsError> {
+    get(target, key)?
+        .dyn_into()
+        .map_err(|_| JsError::new(&format!("\"{key}\" in the snapshot isn't an array")))
+}
+
+fn name(entry: &JsValue) -> Result<String, JsError> {
+    get(entry, NAME_KEY)?
+        .as_string()
+        .ok_or_else(|| JsError::new("a snapshot entry is missing its name"))
+}
@@ -2,9 +2,9 @@
 
 pub(crate) use crate::js::vm::{
     VMExtern, VMExternFunction, VMExternGlobal, VMExternMemory, VMExternRef, VMExternTable,
-    VMFuncRef, VMFunctionCallback, VMFunctionEnvironment, VMInstance, VMTrampoline,
+    VMExternTag, VMFuncRef, VMFunctionCallback, VMFunctionEnvironment, VMInstance, VMTrampoline,
 };
-pub use crate::js::vm::{VMFunction, VMGlobal, VMMemory, VMSharedMemory, VMTable};
+pub use crate::js::vm::{VMFunction, VMGlobal, VMMemory, VMSharedMemory, VMTable, VMTag};
 
 // Deprecated exports
 pub use wasmer_types::{MemoryError, MemoryStyle, TableStyle};
@@ -1,3 +1,4 @@
+use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_test::*;
 
 use wasmer::*;
@@ -54,6 +55,54 @@ fn global_set() {
     assert_eq!(global_i32_mut.get(&mut store), Value::I32(20));
 }
 
+#[wasm_bindgen_test]
+fn global_externref() {
+    let mut store = Store::default();
+    let extern_ref = ExternRef::new(&mut store, String::from("hello"));
+    let global = Global::new_mut(&mut store, Value::ExternRef(Some(extern_ref)));
+
+    let value = global.get(&mut store);
+    let extern_ref = value.unwrap_externref().as_ref().unwrap();
+    assert_eq!(
+        extern_ref.downcast::<String>(&store).map(String::as_str),
+        Some("hello")
+    );
+
+    global
+        .set(&mut store, Value::ExternRef(None))
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    assert!(global.get(&mut store).unwrap_externref().is_none());
+}
+
+#[wasm_bindgen_test]
+fn global_funcref() {
+    let mut store = Store::default();
+    let f = Function::new_typed(&mut store, |num: i32| num + 1);
+    let global = Global::new_mut(&mut store, Value::FuncRef(None));
+    assert_eq!(
+        global.ty(&store),
+        GlobalType {
+            ty: Type::FuncRef,
+            mutability: Mutability::Var
+        }
+    );
+    assert!(global.get(&mut store).unwrap_funcref().is_none());
+
+    global
+        .set(&mut store, Value::FuncRef(Some(f.clone())))
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    let value = global.get(&mut store);
+    let function = value.unwrap_funcref().as_ref().unwrap();
+    assert_eq!(function, &f);
+    let typed = function.typed::<i32, i32>(&store).unwrap();
+    assert_eq!(typed.call(&mut store, 1).unwrap(), 2);
+
+    // Reference globals are still type checked
+    assert!(global.set(&mut store, Value::ExternRef(None)).is_err());
+}
+
 #[wasm_bindgen_test]
 fn table_new() {
     let mut store = Store::default();
@@ -68,48 +117,134 @@ fn table_new() {
         .unwrap();
     assert_eq!(table.ty(&store), table_type);
 
-    // Anyrefs not yet supported
-    // let table_type = TableType {
-    //     ty: Type::ExternRef,
-    //     minimum: 0,
-    //     maximum: None,
-    // };
-    // let table = Table::new(&store, table_type, Value::ExternRef(ExternRef::Null)).map_err(|e| format!("{e:?}"))?;
-    // assert_eq!(*table.ty(), table_type);
+    let table_type = TableType {
+        ty: Type::ExternRef,
+        minimum: 0,
+        maximum: None,
+    };
+    let table = Table::new(&mut store, table_type, Value::ExternRef(None))
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    assert_eq!(table.ty(&store), table_type);
 }
 
 #[wasm_bindgen_test]
 fn table_get() {
-    // Tables are not yet fully supported in Wasm
-    // This test was marked as #[ignore] on -sys, which is why it is commented out.
-
-    //    let mut store = Store::default();
-    //    let table_type = TableType {
-    //        ty: Type::FuncRef,
-    //        minimum: 0,
-    //        maximum: Some(1),
-    //    };
-    //    let f = Function::new_typed(&mut store, |num: i32| num + 1);
-    //    let table = Table::new(&mut store, table_type, Value::FuncRef(Some(f)))
-    //        .map_err(|e| format!("{e:?}"))?;
-    //    assert_eq!(table.ty(&mut store), table_type);
-    //    let _elem = table.get(&mut store, 0).unwrap();
-    //    assert_eq!(elem.funcref().unwrap(), f);
+    let mut store = Store::default();
+    let table_type = TableType {
+        ty: Type::FuncRef,
+        minimum: 0,
+        maximum: Some(1),
+    };
+    let f = Function::new_typed(&mut store, |num: i32| num + 1);
+    let table = Table::new(&mut store, table_type, Value::FuncRef(Some(f.clone())))
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    assert_eq!(table.ty(&store), table_type);
+    assert!(table.get(&mut store, 0).is_none());
+
+    table
+        .grow(&mut store, 1, Value::FuncRef(Some(f.clone())))
+        .unwrap();
+    let elem = table.get(&mut store, 0).unwrap();
+    assert_eq!(elem.unwrap_funcref().as_ref().unwrap(), &f);
+    assert!(table.get(&mut store, 1).is_none());
 }
 
 #[wasm_bindgen_test]
 fn table_set() {
-    // Table set not yet tested
+    let mut store = Store::default();
+    let table_type = TableType {
+        ty: Type::ExternRef,
+        minimum: 2,
+        maximum: None,
+    };
+    let table = Table::new(&mut store, table_type, Value::ExternRef(None)).unwrap();
+
+    let extern_ref = ExternRef::new(&mut store, 42u32);
+    table
+        .set(&mut store, 1, Value::ExternRef(Some(extern_ref)))
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    assert!(table
+        .get(&mut store, 0)
+        .unwrap()
+        .unwrap_externref()
+        .is_none());
+    let value = table.get(&mut store, 1).unwrap();
+    let extern_ref = value.unwrap_externref().as_ref().unwrap();
+    assert_eq!(extern_ref.downcast::<u32>(&store), Some(&42));
+
+    // Setting out of bounds or with the wrong type should error
+    let err = table
+        .set(&mut store, 2, Value::ExternRef(None))
+        .unwrap_err();
+    assert_eq!(err.to_trap(), Some(TrapCode::TableAccessOutOfBounds));
+    assert!(table.set(&mut store, 0, Value::FuncRef(None)).is_err());
 }
 
 #[wasm_bindgen_test]
 fn table_grow() {
-    // Tables are not yet fully supported in Wasm
+    let mut store = Store::default();
+    let table_type = TableType {
+        ty: Type::FuncRef,
+        minimum: 0,
+        maximum: Some(10),
+    };
+    let f = Function::new_typed(&mut store, |num: i32| num + 1);
+    let table = Table::new(&mut store, table_type, Value::FuncRef(Some(f.clone())))
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+
+    // Growing past the maximum should error
+    let old_len = table.grow(&mut store, 12, Value::FuncRef(Some(f.clone())));
+    assert!(old_len.is_err());
+
+    // Growing within the maximum returns the previous size
+    let old_len = table
+        .grow(&mut store, 5, Value::FuncRef(Some(f.clone())))
+        .unwrap();
+    assert_eq!(old_len, 0);
+    assert_eq!(table.size(&store), 5);
+    let elem = table.get(&mut store, 4).unwrap();
+    assert_eq!(elem.unwrap_funcref().as_ref().unwrap(), &f);
 }
 
 #[wasm_bindgen_test]
 fn table_copy() {
-    // TODO: table copy test not yet implemented
+    let mut store = Store::default();
+    let table_type = TableType {
+        ty: Type::ExternRef,
+        minimum: 4,
+        maximum: None,
+    };
+    let src = Table::new(&mut store, table_type, Value::ExternRef(None)).unwrap();
+    let dst = Table::new(&mut store, table_type, Value::ExternRef(None)).unwrap();
+    for i in 0..4u32 {
+        let extern_ref = ExternRef::new(&mut store, i);
+        src.set(&mut store, i, Value::ExternRef(Some(extern_ref)))
+            .unwrap();
+    }
+    let get = |store: &mut Store, table: &Table, index: u32| -> Option<u32> {
+        let value = table.get(store, index).unwrap();
+        let extern_ref = value.unwrap_externref().clone()?;
+        extern_ref.downcast::<u32>(store).copied()
+    };
+
+    Table::copy(&mut store, &dst, 1, &src, 0, 3).unwrap();
+    assert_eq!(get(&mut store, &dst, 0), None);
+    assert_eq!(get(&mut store, &dst, 1), Some(0));
+    assert_eq!(get(&mut store, &dst, 3), Some(2));
+
+    // Overlapping copies within the same table
+    Table::copy(&mut store, &src, 1, &src, 0, 3).unwrap();
+    assert_eq!(get(&mut store, &src, 0), Some(0));
+    assert_eq!(get(&mut store, &src, 1), Some(0));
+    assert_eq!(get(&mut store, &src, 2), Some(1));
+    assert_eq!(get(&mut store, &src, 3), Some(2));
+
+    let err = Table::copy(&mut store, &dst, 2, &src, 0, 3).unwrap_err();
+    assert_eq!(err.to_trap(), Some(TrapCode::TableAccessOutOfBounds));
 }
 
 #[wasm_bindgen_test]
@@ -119,6 +254,7 @@ fn memory_new() {
         shared: false,
         minimum: Pages(0),
         maximum: Some(Pages(10)),
+        index_type: IndexType::I32,
     };
     let memory = Memory::new(&mut store, memory_type)
         .map_err(|e| format!("{e:?}"))
@@ -127,6 +263,97 @@ fn memory_new() {
     assert_eq!(memory.ty(&store), memory_type);
 }
 
+#[wasm_bindgen_test]
+fn memory_on_grow() {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    let mut store = Store::default();
+    let memory = Memory::new(&mut store, MemoryType::new(Pages(1), Some(Pages(8)), false)).unwrap();
+
+    let size = Arc::new(AtomicU32::new(0));
+    memory.on_grow(&store, {
+        let size = size.clone();
+        move |pages| size.store(pages.0, Ordering::SeqCst)
+    });
+
+    // Creating a view without growing does not trigger the hook.
+    memory.view(&store);
+    assert_eq!(size.load(Ordering::SeqCst), 0);
+
+    memory.grow(&mut store, Pages(2)).unwrap();
+    assert_eq!(size.load(Ordering::SeqCst), 3);
+
+    // Hooks are shared between handles of the same memory.
+    memory.clone().grow(&mut store, Pages(1)).unwrap();
+    assert_eq!(size.load(Ordering::SeqCst), 4);
+}
+
+#[wasm_bindgen_test]
+async fn memory_on_grow_through_imports_and_exports() {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    let mut store = Store::default();
+    let memory = Memory::new(&mut store, MemoryType::new(Pages(1), Some(Pages(8)), false)).unwrap();
+    let size = Arc::new(AtomicU32::new(0));
+    memory.on_grow(&store, {
+        let size = size.clone();
+        move |pages| size.store(pages.0, Ordering::SeqCst)
+    });
+
+    let module = Module::new(
+        r#"(module
+            (import "env" "memory" (memory 1 8))
+            (export "memory" (memory 0))
+            (func (export "grow") (param i32) (result i32)
+                (memory.grow (local.get 0))))"#,
+    )
+    .await
+    .map_err(|e| format!("{e:?}"))
+    .unwrap();
+    let imports = imports! { "env" => { "memory" => memory.clone() } };
+    let instance = Instance::new(&mut store, &module, &imports, Default::default())
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+
+    // The exported memory is another handle to the imported one, which
+    // shares its hooks
+    let exported = instance.exports.get_memory("memory").unwrap();
+    exported.grow(&mut store, Pages(2)).unwrap();
+    assert_eq!(size.load(Ordering::SeqCst), 3);
+
+    let grow: TypedFunction<i32, i32> =
+        instance.exports.get_typed_function(&store, "grow").unwrap();
+    assert_eq!(grow.call(&mut store, 1).unwrap(), 3);
+    exported.view(&store);
+    assert_eq!(size.load(Ordering::SeqCst), 4);
+}
+
+#[wasm_bindgen_test]
+fn memory64_new_grow() {
+    let mut store = Store::default();
+    let memory_type = MemoryType::new_64(Pages(1), Some(Pages(4)), false);
+    let memory = Memory::new(&mut store, memory_type)
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    assert_eq!(memory.ty(&store), memory_type);
+    assert_eq!(memory.view(&store).size(), Pages(1));
+
+    assert_eq!(memory.grow(&mut store, Pages(2)).unwrap(), Pages(1));
+    assert_eq!(memory.view(&store).size(), Pages(3));
+
+    let view = memory.view(&store);
+    let ptr: WasmPtr64<u64> = WasmPtr::new(2 * WASM_PAGE_SIZE as u64);
+    ptr.write(&view, 0xdead_beef).unwrap();
+    assert_eq!(ptr.read(&view).unwrap(), 0xdead_beef);
+}
+
 #[wasm_bindgen_test]
 fn memory_grow() {
     let mut store = Store::default();
@@ -150,6 +377,117 @@ fn memory_grow() {
     );
 }
 
+#[wasm_bindgen_test]
+fn memory_typed_array_views() {
+    let mut store = Store::default();
+    let memory = Memory::new(&mut store, MemoryType::new(Pages(1), None, false)).unwrap();
+    let view = memory.view(&store);
+
+    let src = js_sys::Uint8Array::from(&[1u8, 2, 3, 4][..]);
+    view.copy_from_typed_array(16, &src).unwrap();
+
+    let subview = view.as_uint8array(16..20).unwrap();
+    assert_eq!(subview.to_vec(), vec![1, 2, 3, 4]);
+    subview.set_index(0, 42);
+    assert_eq!(view.read_u8(16).unwrap(), 42);
+
+    let dst = js_sys::Uint8Array::new_with_length(2);
+    view.copy_to_typed_array(18, &dst).unwrap();
+    assert_eq!(dst.to_vec(), vec![3, 4]);
+
+    let size = view.data_size();
+    assert!(matches!(
+        view.as_uint8array(size - 1..size + 1),
+        Err(MemoryAccessError::HeapOutOfBounds)
+    ));
+    assert!(matches!(
+        view.copy_from_typed_array(size - 1, &src),
+        Err(MemoryAccessError::HeapOutOfBounds)
+    ));
+}
+
+#[wasm_bindgen_test]
+fn memory_shared_atomics() {
+    let mut store = Store::default();
+    let memory = Memory::new(&mut store, MemoryType::new(Pages(1), None, true))
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    let view = memory.view(&store);
+    assert!(view.is_shared());
+
+    view.write(8, &42i32.to_le_bytes()).unwrap();
+    assert_eq!(view.atomic_load32(8), Ok(42));
+    assert_eq!(view.atomic_notify(8, 1), Ok(0));
+    assert_eq!(
+        view.atomic_notify(6, 1),
+        Err(AtomicsError::UnalignedAddress)
+    );
+    assert_eq!(
+        view.atomic_notify(1 << 16, 1),
+        Err(AtomicsError::OutOfBounds)
+    );
+
+    let shared = memory.as_shared(&store).unwrap();
+    assert_eq!(shared.notify(MemoryLocation::new_32(8), 1), Ok(0));
+
+    let unshared = Memory::new(&mut store, MemoryType::new(Pages(1), None, false)).unwrap();
+    assert!(!unshared.view(&store).is_shared());
+    assert!(unshared.as_shared(&store).is_none());
+}
+
+#[wasm_bindgen_test]
+fn memory_creator_provides_existing_memories() {
+    struct Existing(js_sys::WebAssembly::Memory);
+    // The tests run on a single thread.
+    unsafe impl Send for Existing {}
+    unsafe impl Sync for Existing {}
+
+    impl MemoryCreator for Existing {
+        fn create_memory(&self, ty: &MemoryType) -> Option<js_sys::WebAssembly::Memory> {
+            (!ty.shared).then(|| self.0.clone())
+        }
+    }
+
+    let mut engine = Engine::default();
+    let mut store = Store::new(engine.clone());
+    let existing = Memory::new(&mut store, MemoryType::new(Pages(2), None, false)).unwrap();
+    existing.view(&store).write_u8(12, 34).unwrap();
+    let js_memory: js_sys::WebAssembly::Memory = JsValue::from(existing.try_clone(&store).unwrap())
+        .dyn_into()
+        .unwrap();
+    engine.set_memory_creator(Existing(js_memory));
+    let mut store = Store::new(engine);
+
+    let memory = Memory::new(&mut store, MemoryType::new(Pages(1), None, false)).unwrap();
+    assert_eq!(memory.view(&store).read_u8(12).unwrap(), 34);
+
+    let err = Memory::new(&mut store, MemoryType::new(Pages(3), None, false)).unwrap_err();
+    assert!(matches!(err, MemoryError::InvalidMemory { .. }), "{err}");
+
+    // Memories the creator declines to provide are allocated as usual.
+    let shared = Memory::new(&mut store, MemoryType::new(Pages(1), None, true)).unwrap();
+    assert_eq!(shared.view(&store).read_u8(12).unwrap(), 0);
+}
+
+#[wasm_bindgen_test]
+fn tag_exceptions() {
+    let mut store = Store::default();
+    let tag = Tag::new(&mut store, TagType::new([Type::I32, Type::F64])).unwrap();
+    assert_eq!(tag.ty(&store).params(), [Type::I32, Type::F64]);
+    let other = Tag::new(&mut store, TagType::new([Type::I32, Type::F64])).unwrap();
+
+    let exception = Exception::new(&mut store, &tag, &[Value::I32(1), Value::F64(2.5)]).unwrap();
+    assert!(exception.is(&tag));
+    assert!(!exception.is(&other));
+    assert_eq!(
+        exception.payload(&mut store, &tag),
+        Some(vec![Value::I32(1), Value::F64(2.5)])
+    );
+    assert_eq!(exception.payload(&mut store, &other), None);
+
+    assert!(Exception::new(&mut store, &tag, &[Value::I32(1)]).is_err());
+}
+
 #[wasm_bindgen_test]
 fn function_new() {
     let mut store = Store::default();
@@ -338,3 +676,51 @@ fn function_new_dynamic_env() {
         [Type::I32, Type::F32, Type::F64]
     );
 }
+
+#[wasm_bindgen_test]
+fn function_funcref_roundtrip() {
+    let mut store = Store::default();
+    let function = Function::new_typed(&mut store, |num: i32| num + 1);
+
+    let raw = Value::FuncRef(Some(function.clone())).as_raw(&store);
+    let value = unsafe { Value::from_raw(&mut store, Type::FuncRef, raw) };
+    let roundtripped = value.unwrap_funcref().clone().unwrap();
+    assert_eq!(roundtripped, function);
+    assert_eq!(roundtripped.ty(&store), function.ty(&store));
+
+    let raw = Value::FuncRef(None).as_raw(&store);
+    let value = unsafe { Value::from_raw(&mut store, Type::FuncRef, raw) };
+    assert!(value.unwrap_funcref().is_none());
+}
+
+#[wasm_bindgen_test]
+fn extern_ref_downcast() {
+    let mut store = Store::default();
+    let extern_ref = ExternRef::new(&mut store, 42u32);
+    assert_eq!(extern_ref.downcast::<u32>(&store), Some(&42));
+    assert!(extern_ref.downcast::<String>(&store).is_none());
+}
+
+#[wasm_bindgen_test]
+fn extern_ref_reclaimed_objects_are_forgotten() {
+    let mut store = Store::default();
+    let table_type = TableType {
+        ty: Type::ExternRef,
+        minimum: 1,
+        maximum: None,
+    };
+    let table = Table::new(&mut store, table_type, Value::ExternRef(None)).unwrap();
+    let extern_ref = ExternRef::new(&mut store, 42u32);
+    table
+        .set(&mut store, 0, Value::ExternRef(Some(extern_ref)))
+        .unwrap();
+    assert_eq!(store.gc(), 1);
+
+    // The reclaimed slot is reused, but the JS value left in the table
+    // mustn't be taken for the new object.
+    let reused = ExternRef::new(&mut store, 7u32);
+    let value = table.get(&mut store, 0).unwrap();
+    let extern_ref = value.unwrap_externref().as_ref().unwrap();
+    assert!(extern_ref.downcast::<u32>(&store).is_none());
+    assert_eq!(reused.downcast::<u32>(&store), Some(&7));
+}
@@ -37,3 +37,42 @@ fn data_and_store_mut() {
 
     assert_eq!(data.global.get(&mut storemut), Value::I32(data.value));
 }
+
+#[wasm_bindgen_test]
+fn gc_reclaims_unused_environments() {
+    let mut store = Store::default();
+    let kept = FunctionEnv::new(&mut store, 1_u32);
+    for i in 0..10_u32 {
+        FunctionEnv::new(&mut store, i);
+    }
+    ExternRef::new(&mut store, "dropped");
+
+    assert_eq!(store.gc(), 11);
+    assert_eq!(store.gc(), 0);
+    assert_eq!(*kept.as_ref(&store), 1);
+
+    // Reclaimed slots are reused by new objects.
+    let reused = FunctionEnv::new(&mut store, 2_u32);
+    assert_eq!(*reused.as_ref(&store), 2);
+    assert_eq!(*kept.as_ref(&store), 1);
+}
+
+#[wasm_bindgen_test]
+fn gc_keeps_environments_used_by_functions() {
+    fn get(env: FunctionEnvMut<u32>) -> u32 {
+        *env.data()
+    }
+
+    let mut store = Store::default();
+    let env = FunctionEnv::new(&mut store, 42_u32);
+    let function = Function::new_typed_with_env(&mut store, &env, get);
+    drop(env);
+
+    assert_eq!(store.gc(), 0);
+    let typed: TypedFunction<(), u32> = function.typed(&store).unwrap();
+    assert_eq!(typed.call(&mut store).unwrap(), 42);
+
+    drop(typed);
+    drop(function);
+    assert_eq!(store.gc(), 2);
+}
@@ -164,3 +164,216 @@ async fn back_and_forth_with_imports() {
         .unwrap();
     add_one.call(&mut store, 1).unwrap();
 }
+
+#[wasm_bindgen_test]
+async fn imports_from_js_object() {
+    let mut store = Store::default();
+
+    let wat = r#"(module
+        (func $double (import "host" "double") (param i32) (result i32))
+        (import "env" "memory" (memory 1))
+        (func (export "run") (param i32) (result i32)
+            (i32.store (i32.const 0) (call $double (local.get 0)))
+            (i32.load (i32.const 0))
+        )
+    )"#;
+    let module = Module::new(wat)
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+
+    let descriptor = js_sys::Object::new();
+    js_sys::Reflect::set(&descriptor, &"initial".into(), &1.into()).unwrap();
+    let memory = js_sys::WebAssembly::Memory::new(&descriptor).unwrap();
+    let host = js_sys::Object::new();
+    js_sys::Reflect::set(
+        &host,
+        &"double".into(),
+        &js_sys::Function::new_with_args("x", "return x * 2"),
+    )
+    .unwrap();
+    let env = js_sys::Object::new();
+    js_sys::Reflect::set(&env, &"memory".into(), &memory).unwrap();
+    let object = js_sys::Object::new();
+    js_sys::Reflect::set(&object, &"host".into(), &host).unwrap();
+    js_sys::Reflect::set(&object, &"env".into(), &env).unwrap();
+
+    let imports = Imports::from_js_object(&mut store, object).unwrap();
+    assert!(imports.exists("host", "double"));
+    assert!(matches!(
+        imports.get_export("env", "memory"),
+        Some(Extern::Memory(_))
+    ));
+
+    let instance = Instance::new(&mut store, &module, &imports, Default::default())
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    let run: TypedFunction<i32, i32> = instance.exports.get_typed_function(&store, "run").unwrap();
+    assert_eq!(run.call(&mut store, 21).unwrap(), 42);
+}
+
+#[wasm_bindgen_test]
+async fn epoch_deadline_interrupts_execution() {
+    let mut store = Store::default();
+
+    let wat = r#"(module
+        (func $tick (import "host" "tick"))
+        (func (export "spin")
+            (loop $forever
+                (call $tick)
+                (br $forever))
+        )
+    )"#;
+    let module = Module::new(wat)
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+
+    let handle = store.interrupt_handle();
+    let tick = Function::new(&mut store, FunctionType::new(vec![], vec![]), move |_| {
+        handle.increment_epoch();
+        Ok(vec![])
+    });
+    let imports = imports! {
+        "host" => {
+            "tick" => tick,
+        },
+    };
+    let instance = Instance::new(&mut store, &module, &imports, Default::default())
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    let spin: TypedFunction<(), ()> = instance.exports.get_typed_function(&store, "spin").unwrap();
+
+    store.set_epoch_deadline(10);
+    let err = spin.call(&mut store).unwrap_err();
+    assert_eq!(err.to_trap(), Some(TrapCode::Interrupt));
+    assert_eq!(store.interrupt_handle().epoch(), 10);
+
+    // The deadline stays in place until it is moved again
+    let err = spin.call(&mut store).unwrap_err();
+    assert_eq!(err.to_trap(), Some(TrapCode::Interrupt));
+}
+
+#[wasm_bindgen_test]
+async fn host_call_interceptor_sees_every_host_call() {
+    use std::sync::{Arc, Mutex};
+
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl HostCallInterceptor for Recorder {
+        fn before_call(
+            &self,
+            _store: &mut StoreMut<'_>,
+            call: &HostCall<'_>,
+        ) -> Result<(), RuntimeError> {
+            if call.name == "forbidden" {
+                return Err(RuntimeError::new("forbidden host call"));
+            }
+            let entry = format!("> {}.{}{:?}", call.module, call.name, call.args);
+            self.0.lock().unwrap().push(entry);
+            Ok(())
+        }
+
+        fn after_call(
+            &self,
+            _store: &mut StoreMut<'_>,
+            call: &HostCall<'_>,
+            result: Result<&[Value], &RuntimeError>,
+        ) {
+            let entry = format!("< {}.{} {:?}", call.module, call.name, result.ok());
+            self.0.lock().unwrap().push(entry);
+        }
+    }
+
+    let wat = r#"(module
+        (func $double (import "host" "double") (param i32) (result i32))
+        (func $forbidden (import "host" "forbidden"))
+        (func (export "run") (param i32) (result i32)
+            (call $double (local.get 0)))
+        (func (export "forbidden")
+            (call $forbidden))
+    )"#;
+    let module = Module::new(wat)
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+
+    let mut store = Store::default();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    store.set_host_call_interceptor(Recorder(log.clone()));
+
+    let imports = imports! {
+        "host" => {
+            "double" => Function::new_typed(&mut store, |value: i32| value * 2),
+            "forbidden" => Function::new_typed(&mut store, || {}),
+        },
+    };
+    let instance = Instance::new(&mut store, &module, &imports, Default::default())
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+
+    let run = instance
+        .exports
+        .get_typed_function::<i32, i32>(&store, "run")
+        .unwrap();
+    assert_eq!(run.call(&mut store, 21).unwrap(), 42);
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec!["> host.double[I32(21)]", "< host.double Some([I32(42)])"]
+    );
+
+    let forbidden = instance
+        .exports
+        .get_typed_function::<(), ()>(&store, "forbidden")
+        .unwrap();
+    let err = forbidden.call(&mut store).unwrap_err();
+    assert_eq!(err.message(), "forbidden host call");
+
+    // Interceptors can be removed from instances which have already been
+    // created.
+    store.clear_host_call_interceptor();
+    assert_eq!(run.call(&mut store, 1).unwrap(), 2);
+    assert_eq!(log.lock().unwrap().len(), 2);
+}
+
+#[wasm_bindgen_test]
+async fn host_calls_are_counted() {
+    let wat = r#"(module
+        (func $double (import "host" "double") (param i32) (result i32))
+        (func (export "run") (param i32) (result i32)
+            (call $double (call $double (local.get 0))))
+    )"#;
+    let module = Module::new(wat)
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+
+    let mut store = Store::default();
+    let imports = imports! {
+        "host" => {
+            "double" => Function::new_typed(&mut store, |value: i32| value * 2),
+        },
+    };
+    let instance = Instance::new(&mut store, &module, &imports, Default::default())
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    let run = instance
+        .exports
+        .get_typed_function::<i32, i32>(&store, "run")
+        .unwrap();
+
+    let counter = store.host_call_counter();
+    assert_eq!(run.call(&mut store, 1).unwrap(), 4);
+    assert_eq!(counter.count(), 2);
+
+    // Calls are only counted by the current counter
+    let thread_counter = HostCallCounter::default();
+    store.set_host_call_counter(thread_counter.clone());
+    run.call(&mut store, 1).unwrap();
+    assert_eq!(counter.count(), 2);
+    assert_eq!(thread_counter.count(), 2);
+}
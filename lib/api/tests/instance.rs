@@ -50,6 +50,336 @@ async fn exports_work_after_multiple_instances_have_been_freed() {
     );
 }
 
+#[wasm_bindgen_test]
+async fn instantiation_errors_are_classified() {
+    let mut store = Store::default();
+    let module = Module::new(r#"(module (import "host" "missing" (func)))"#)
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    let err = Instance::new(&mut store, &module, &Imports::new(), Default::default())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(
+            &err,
+            InstantiationError::Link(LinkError::Import(module, name, _))
+                if module == "host" && name == "missing"
+        ),
+        "{err:?}"
+    );
+
+    let module = Module::new(r#"(module (func $start unreachable) (start $start))"#)
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    let err = Instance::new(&mut store, &module, &Imports::new(), Default::default())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, InstantiationError::Start(_)), "{err:?}");
+
+    // An imports object whose namespace can't be read fails the linking
+    let module = Module::new(r#"(module (import "host" "f" (func)))"#)
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    let imports_obj = js_sys::Object::new();
+    let descriptor = js_sys::Object::new();
+    let getter = js_sys::Function::new_no_args("throw new Error('no namespace')");
+    js_sys::Reflect::set(&descriptor, &"get".into(), &getter).unwrap();
+    js_sys::Object::define_property(&imports_obj, &"host".into(), &descriptor);
+    let err = Instance::new(
+        &mut store,
+        &module,
+        &Imports::new(),
+        ImportsObj(imports_obj),
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        matches!(err, InstantiationError::Link(LinkError::Trap(_))),
+        "{err:?}"
+    );
+}
+
+#[wasm_bindgen_test]
+async fn lazy_fallback_imports_trap_when_called() {
+    let mut store = Store::default();
+    let module = Module::new(
+        r#"(module
+            (import "env" "optional" (func $optional (result i32)))
+            (func (export "call_optional") (result i32) (call $optional))
+            (func (export "answer") (result i32) (i32.const 42)))"#,
+    )
+    .await
+    .map_err(|e| format!("{e:?}"))
+    .unwrap();
+
+    let imports = Imports::new().with_lazy_fallback();
+    let instance = Instance::new(&mut store, &module, &imports, Default::default())
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+
+    let answer = instance.exports.get_function("answer").unwrap();
+    assert_eq!(
+        answer.call(&mut store, &[]).unwrap().into_vec(),
+        vec![Value::I32(42)]
+    );
+
+    let call_optional = instance.exports.get_function("call_optional").unwrap();
+    let err = call_optional.call(&mut store, &[]).unwrap_err();
+    assert!(err.message().contains(r#""env"."optional""#), "{err}");
+}
+
+#[wasm_bindgen_test]
+async fn traps_carry_structured_information() {
+    let mut store = Store::default();
+    let module = Module::new(
+        r#"(module
+            (func $crash unreachable)
+            (func (export "run") (call $crash))
+            (func (export "div") (param i32) (result i32)
+                (i32.div_u (i32.const 1) (local.get 0))))"#,
+    )
+    .await
+    .map_err(|e| format!("{e:?}"))
+    .unwrap();
+    let instance = Instance::new(&mut store, &module, &Imports::new(), Default::default())
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+
+    let run = instance.exports.get_function("run").unwrap();
+    let err = run.call(&mut store, &[]).unwrap_err();
+    assert!(err.js_stack().is_some());
+    let functions: Vec<_> = err.trace().iter().map(|f| f.func_index()).collect();
+    assert_eq!(functions, [0, 1]);
+    assert_eq!(err.to_trap(), Some(TrapCode::UnreachableCodeReached));
+
+    let div: TypedFunction<i32, i32> = instance.exports.get_typed_function(&store, "div").unwrap();
+    let err = div.call(&mut store, 0).unwrap_err();
+    assert_eq!(err.to_trap(), Some(TrapCode::IntegerDivisionByZero));
+}
+
+#[wasm_bindgen_test]
+async fn store_snapshots_round_trip_through_js() {
+    let module = Module::new(
+        r#"(module
+            (import "env" "memory" (memory 1))
+            (export "memory" (memory 0))
+            (global (export "counter") (mut i32) (i32.const 0))
+            (func (export "bump") (global.set 0 (i32.add (global.get 0) (i32.const 1)))))"#,
+    )
+    .await
+    .map_err(|e| format!("{e:?}"))
+    .unwrap();
+
+    let mut store = Store::default();
+    let memory = Memory::new(&mut store, MemoryType::new(1, None, false)).unwrap();
+    let imports = imports! { "env" => { "memory" => memory.clone() } };
+    let instance = Instance::new(&mut store, &module, &imports, Default::default())
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    memory.view(&store).write_u8(42, 7).unwrap();
+    let bump = instance.exports.get_function("bump").unwrap();
+    bump.call(&mut store, &[]).unwrap();
+    bump.call(&mut store, &[]).unwrap();
+
+    let js = StoreSnapshot::capture(&store, &instance).unwrap().to_js();
+    let snapshot = StoreSnapshot::from_js(js).unwrap();
+
+    let mut store = Store::default();
+    let mut imports = Imports::new();
+    for (name, memory) in snapshot.restore_memories(&mut store).unwrap() {
+        imports.define("env", &name, memory);
+    }
+    let instance = Instance::new(&mut store, snapshot.module(), &imports, Default::default())
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    snapshot.restore_instance(&mut store, &instance).unwrap();
+
+    let memory = instance.exports.get_memory("memory").unwrap();
+    assert_eq!(memory.view(&store).read_u8(42).unwrap(), 7);
+    let counter = instance.exports.get_global("counter").unwrap();
+    assert_eq!(counter.get(&mut store), Value::I32(2));
+}
+
+#[wasm_bindgen_test]
+async fn store_snapshots_restore_memory64() {
+    let module = Module::new(
+        r#"(module
+            (memory (export "memory") i64 1 4)
+            (global (export "counter") (mut i64) (i64.const 0))
+            (func (export "store") (param i64 i32)
+                (i32.store8 (local.get 0) (local.get 1))
+                (global.set 0 (local.get 0))))"#,
+    )
+    .await
+    .map_err(|e| format!("{e:?}"))
+    .unwrap();
+
+    let mut store = Store::default();
+    let instance = Instance::new(&mut store, &module, &Imports::new(), Default::default())
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    // The contents past the initial size of the memory are restored too
+    let memory = instance.exports.get_memory("memory").unwrap();
+    memory.grow(&mut store, Pages(1)).unwrap();
+    let store_byte = instance.exports.get_function("store").unwrap();
+    store_byte
+        .call(&mut store, &[Value::I64(70_000), Value::I32(7)])
+        .unwrap();
+
+    let js = StoreSnapshot::capture(&store, &instance).unwrap().to_js();
+    let snapshot = StoreSnapshot::from_js(js).unwrap();
+
+    // The memory the instance defines is restored into the new instance,
+    // rather than imported into it
+    let mut store = Store::default();
+    assert!(snapshot.restore_memories(&mut store).unwrap().is_empty());
+    let instance = Instance::new(
+        &mut store,
+        snapshot.module(),
+        &Imports::new(),
+        Default::default(),
+    )
+    .await
+    .map_err(|e| format!("{e:?}"))
+    .unwrap();
+    snapshot.restore_instance(&mut store, &instance).unwrap();
+
+    let memory = instance.exports.get_memory("memory").unwrap();
+    let ty = memory.ty(&store);
+    assert_eq!(ty.index_type, IndexType::I64);
+    assert_eq!(ty.maximum, Some(Pages(4)));
+    assert_eq!(memory.view(&store).size(), Pages(2));
+    assert_eq!(memory.view(&store).read_u8(70_000).unwrap(), 7);
+    let counter = instance.exports.get_global("counter").unwrap();
+    assert_eq!(counter.get(&mut store), Value::I64(70_000));
+}
+
+#[wasm_bindgen_test]
+async fn store_snapshots_need_the_mutable_globals_to_be_exported() {
+    let module = Module::new(
+        r#"(module
+            (global (export "constant") i32 (i32.const 1))
+            (global $hidden (mut i32) (i32.const 0))
+            (global (export "counter") (mut i32) (i32.const 0)))"#,
+    )
+    .await
+    .map_err(|e| format!("{e:?}"))
+    .unwrap();
+
+    let mut store = Store::default();
+    let instance = Instance::new(&mut store, &module, &Imports::new(), Default::default())
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    assert_eq!(
+        StoreSnapshot::capture(&store, &instance).unwrap_err(),
+        SnapshotError::UnexportedGlobal(1)
+    );
+}
+
+#[wasm_bindgen_test]
+async fn store_snapshots_cant_share_the_memories_the_instance_defines() {
+    let module = Module::new(r#"(module (memory (export "memory") 1 1 shared))"#)
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+
+    let mut store = Store::default();
+    let instance = Instance::new(&mut store, &module, &Imports::new(), Default::default())
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    assert_eq!(
+        StoreSnapshot::capture(&store, &instance).unwrap_err(),
+        SnapshotError::DefinedSharedMemory("memory".to_string())
+    );
+}
+
+#[wasm_bindgen_test]
+async fn guest_exceptions_reach_the_host() {
+    let mut store = Store::default();
+    let module = Module::new(
+        r#"
+(module
+  (tag $error (export "error") (param i32))
+  (func (export "fail") (param i32)
+    local.get 0
+    throw $error))
+"#,
+    )
+    .await
+    .unwrap();
+    let instance = Instance::new(&mut store, &module, &Imports::new(), Default::default())
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+
+    let tag = instance.exports.get_tag("error").unwrap().clone();
+    assert_eq!(tag.ty(&store).params(), [Type::I32]);
+
+    let fail = instance.exports.get_function("fail").unwrap();
+    let err = fail.call(&mut store, &[Value::I32(42)]).unwrap_err();
+    let exception = err.downcast_ref::<Exception>().unwrap();
+    assert_eq!(
+        exception.payload(&mut store, &tag),
+        Some(vec![Value::I32(42)])
+    );
+}
+
+#[wasm_bindgen_test]
+async fn host_exceptions_are_caught_by_the_guest() {
+    let mut store = Store::default();
+    let module = Module::new(
+        r#"
+(module
+  (import "env" "error" (tag $error (param i32)))
+  (import "env" "fail" (func $fail))
+  (func (export "run") (result i32)
+    try (result i32)
+      call $fail
+      i32.const 0
+    catch $error
+    end))
+"#,
+    )
+    .await
+    .unwrap();
+
+    let tag = Tag::new(&mut store, TagType::new([Type::I32])).unwrap();
+    let env = FunctionEnv::new(&mut store, tag.clone());
+    let fail = Function::new_with_env(
+        &mut store,
+        &env,
+        FunctionType::new(vec![], vec![]),
+        |mut env: FunctionEnvMut<Tag>, _: &[Value]| {
+            let tag = env.data().clone();
+            Err(Exception::new(&mut env, &tag, &[Value::I32(7)])?.into())
+        },
+    );
+    let imports = imports! {
+        "env" => {
+            "error" => tag,
+            "fail" => fail,
+        },
+    };
+    let instance = Instance::new(&mut store, &module, &imports, Default::default())
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+
+    let run = instance.exports.get_function("run").unwrap();
+    let result = run.call(&mut store, &[]).unwrap();
+    assert_eq!(result.to_vec(), vec![Value::I32(7)]);
+}
+
 #[wasm_bindgen_test]
 fn unit_native_function_env() {
     let mut store = Store::default();
@@ -79,3 +409,37 @@ fn unit_native_function_env() {
         .unwrap();
     assert_eq!(result, expected);
 }
+
+#[wasm_bindgen_test]
+async fn funcrefs_keep_the_type_of_their_function() {
+    let mut store = Store::default();
+    let module = Module::new(
+        r#"(module
+            (func $double (export "double") (param i32) (result i32)
+                (i32.mul (local.get 0) (i32.const 2)))
+            (table (export "table") 1 funcref)
+            (elem (i32.const 0) $double))"#,
+    )
+    .await
+    .map_err(|e| format!("{e:?}"))
+    .unwrap();
+    let instance = Instance::new(&mut store, &module, &Imports::new(), Default::default())
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+
+    // The function in the table is the exported one, with its signature
+    let double = instance.exports.get_function("double").unwrap().clone();
+    let table = instance.exports.get_table("table").unwrap();
+    let element = table.get(&mut store, 0).unwrap();
+    let function = element.unwrap_funcref().clone().unwrap();
+    assert_eq!(function, double);
+    assert_eq!(
+        function.ty(&store),
+        FunctionType::new(vec![Type::I32], vec![Type::I32])
+    );
+    assert_eq!(
+        function.call(&mut store, &[Value::I32(21)]).unwrap()[0],
+        Value::I32(42)
+    );
+}
@@ -0,0 +1,88 @@
+use wasm_bindgen_test::*;
+
+use wasmer::*;
+
+async fn interruptible_instance(store: &mut Store, wat: &str) -> Instance {
+    let wasm = wat::parse_str(wat).unwrap();
+    let wasm = Interruption::new(10).instrument(&wasm).unwrap();
+    let module = Module::new(wasm)
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    assert_eq!(module.imports().count(), 1);
+
+    let handle = store.interrupt_handle();
+    let imports = imports! {
+        "host" => {
+            "tick" => Function::new_typed(store, move || handle.increment_epoch()),
+        },
+    };
+    Instance::new(store, &module, &imports, Default::default())
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap()
+}
+
+#[wasm_bindgen_test]
+async fn interruption_stops_tight_loops() {
+    let mut store = Store::default();
+    let instance = interruptible_instance(
+        &mut store,
+        r#"(module
+            (func $tick (import "host" "tick"))
+            (func (export "spin")
+                (call $tick)
+                (loop $forever (br $forever)))
+        )"#,
+    )
+    .await;
+    let spin: TypedFunction<(), ()> = instance.exports.get_typed_function(&store, "spin").unwrap();
+
+    // The epoch is incremented once the guest runs, which then never calls
+    // into the host again
+    store.set_epoch_deadline(1);
+    let err = spin.call(&mut store).unwrap_err();
+    assert_eq!(err.to_trap(), Some(TrapCode::Interrupt));
+}
+
+#[wasm_bindgen_test]
+async fn interruption_preserves_functions() {
+    let mut store = Store::default();
+    let instance = interruptible_instance(
+        &mut store,
+        r#"(module
+            (func $tick (import "host" "tick"))
+            (type $unary (func (param i32) (result i32)))
+            (table 2 funcref)
+            (elem (i32.const 0) $double $count_down)
+            (global $started (mut i32) (i32.const 0))
+            (global $counted (export "counted") funcref (ref.func $count_down))
+            (start $start)
+            (func $start (global.set $started (i32.const 1)))
+            (func $double (param $n i32) (result i32)
+                (i32.mul (local.get $n) (i32.const 2)))
+            (func $count_down (param $n i32) (result i32)
+                (loop $continue
+                    (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                    (br_if $continue (local.get $n)))
+                (global.get $started))
+            (func (export "run") (param $n i32) (result i32)
+                (i32.add
+                    (call $double (local.get $n))
+                    (call_indirect (type $unary) (local.get $n) (i32.const 1))))
+        )"#,
+    )
+    .await;
+    let run: TypedFunction<i32, i32> = instance.exports.get_typed_function(&store, "run").unwrap();
+
+    // Without a deadline, the loops run to completion
+    assert_eq!(run.call(&mut store, 100).unwrap(), 201);
+    let counted = instance.exports.get_global("counted").unwrap();
+    let Value::FuncRef(Some(counted)) = counted.get(&mut store) else {
+        panic!("the global doesn't hold a function");
+    };
+    assert_eq!(
+        counted.call(&mut store, &[Value::I32(5)]).unwrap()[0],
+        Value::I32(1)
+    );
+}
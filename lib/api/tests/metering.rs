@@ -0,0 +1,79 @@
+use wasm_bindgen_test::*;
+
+use wasmer::*;
+
+async fn metered_instance(store: &mut Store, wat: &str) -> Instance {
+    let wasm = wat::parse_str(wat).unwrap();
+    let wasm = Metering::default().instrument(&wasm).unwrap();
+    let module = Module::new(wasm)
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    assert_eq!(module.imports().count(), 0);
+
+    Instance::new(store, &module, &Imports::new(), Default::default())
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap()
+}
+
+#[wasm_bindgen_test]
+async fn metering_consumes_fuel() {
+    let mut store = Store::default();
+    let instance = metered_instance(
+        &mut store,
+        r#"(module
+            (func (export "count_down") (param $n i32)
+                (loop $continue
+                    (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                    (br_if $continue (local.get $n))))
+        )"#,
+    )
+    .await;
+    let count_down: TypedFunction<i32, ()> = instance
+        .exports
+        .get_typed_function(&store, "count_down")
+        .unwrap();
+
+    // Stores start without any fuel
+    assert_eq!(store.fuel_remaining(), 0);
+    let err = count_down.call(&mut store, 1).unwrap_err();
+    assert_eq!(err.to_trap(), Some(TrapCode::OutOfFuel));
+
+    store.set_fuel(1_000);
+    count_down.call(&mut store, 10).unwrap();
+    let remaining = store.fuel_remaining();
+    assert!(remaining < 1_000 - 10 * 5, "{remaining}");
+
+    let err = count_down.call(&mut store, 1_000).unwrap_err();
+    assert_eq!(err.to_trap(), Some(TrapCode::OutOfFuel));
+    assert_eq!(store.fuel_remaining(), 0);
+
+    store.add_fuel(100);
+    assert_eq!(store.fuel_remaining(), 100);
+    count_down.call(&mut store, 1).unwrap();
+}
+
+#[wasm_bindgen_test]
+async fn metering_preserves_globals() {
+    let mut store = Store::default();
+    store.set_fuel(1_000);
+    let instance = metered_instance(
+        &mut store,
+        r#"(module
+            (global $counter (export "counter") (mut i32) (i32.const 40))
+            (func (export "increment") (result i32)
+                (global.set $counter (i32.add (global.get $counter) (i32.const 2)))
+                (global.get $counter))
+        )"#,
+    )
+    .await;
+    let increment: TypedFunction<(), i32> = instance
+        .exports
+        .get_typed_function(&store, "increment")
+        .unwrap();
+
+    assert_eq!(increment.call(&mut store).unwrap(), 42);
+    let counter = instance.exports.get_global("counter").unwrap();
+    assert_eq!(counter.get(&mut store), Value::I32(42));
+}
@@ -302,3 +302,132 @@ async fn module_custom_sections() {
         vec![2, 2, 36, 105, 1, 0, 0, 0].into_boxed_slice()
     );
 }
+
+#[wasm_bindgen_test]
+async fn module_repeated_custom_sections() {
+    // An empty module with the custom sections `a`, `b` and `a` again
+    let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+    for (name, data) in [("a", &b"first"[..]), ("b", b"other"), ("a", b"second")] {
+        wasm.push(0);
+        wasm.push((1 + name.len() + data.len()) as u8);
+        wasm.push(name.len() as u8);
+        wasm.extend(name.as_bytes());
+        wasm.extend(data);
+    }
+    let module = Module::new(wasm)
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+
+    assert_eq!(
+        module.custom_section_names().collect::<Vec<_>>(),
+        ["a", "b"]
+    );
+    assert_eq!(
+        module.custom_sections("a").collect::<Vec<_>>(),
+        [
+            b"first".to_vec().into_boxed_slice(),
+            b"second".to_vec().into_boxed_slice()
+        ]
+    );
+    assert_eq!(
+        module.custom_sections("b").collect::<Vec<_>>(),
+        [b"other".to_vec().into_boxed_slice()]
+    );
+    assert_eq!(module.custom_sections("c").count(), 0);
+}
+
+#[wasm_bindgen_test]
+async fn module_name_section() {
+    let wat = r#"(module $names
+        (import "host" "log" (func $log (param i32)))
+        (func $add (param i32 i32) (result i32)
+            (i32.add (local.get 0) (local.get 1)))
+        (func (export "run")
+            (call $log (i32.const 1)))
+    )"#;
+    let module = Module::new(wat)
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+
+    assert_eq!(module.name(), Some("names"));
+    assert_eq!(module.custom_section_names().collect::<Vec<_>>(), ["name"]);
+    assert_eq!(module.function_name(0), Some("log"));
+    assert_eq!(module.function_name(1), Some("add"));
+    assert_eq!(module.function_name(2), None);
+    assert_eq!(
+        module.function_names().collect::<Vec<_>>(),
+        [(0, "log"), (1, "add")]
+    );
+}
+
+#[wasm_bindgen_test]
+async fn module_function_offsets() {
+    let wat = r#"(module
+        (import "host" "log" (func $log (param i32)))
+        (func $add (param i32 i32) (result i32)
+            (i32.add (local.get 0) (local.get 1)))
+        (func (export "run")
+            (call $log (i32.const 1)))
+    )"#;
+    let module = Module::new(wat)
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    let bytes = module.serialize();
+
+    assert_eq!(module.function_offset(0), None);
+    let add = module.function_offset(1).unwrap();
+    let run = module.function_offset(2).unwrap();
+    assert!(add < run);
+    // Both bodies start with an empty list of locals
+    assert_eq!(bytes[add as usize], 0);
+    assert_eq!(bytes[run as usize], 0);
+    assert_eq!(module.function_offset(3), None);
+}
+
+fn wasm_response(bytes: &mut [u8], status: u16) -> web_sys::Response {
+    let headers = web_sys::Headers::new().unwrap();
+    headers.set("Content-Type", "application/wasm").unwrap();
+    let init = web_sys::ResponseInit::new();
+    init.set_headers(&headers);
+    init.set_status(status);
+    web_sys::Response::new_with_opt_u8_array_and_init(Some(bytes), &init).unwrap()
+}
+
+#[wasm_bindgen_test]
+async fn module_from_response() {
+    let mut bytes = wat::parse_str(r#"(module $name (func (export "run")))"#).unwrap();
+    let module = Module::from_response(wasm_response(&mut bytes, 200))
+        .await
+        .map_err(|e| format!("{e:?}"))
+        .unwrap();
+    assert_eq!(module.name(), Some("name"));
+    assert_eq!(
+        module.exports().collect::<Vec<_>>(),
+        vec![ExportType::new(
+            "run",
+            ExternType::Function(FunctionType::new(vec![], vec![]))
+        )]
+    );
+
+    let err = Module::from_response(wasm_response(&mut bytes, 404))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, CompileError::Resource(_)));
+}
+
+#[wasm_bindgen_test]
+fn engine_capabilities_match_the_engine() {
+    let capabilities = EngineCapabilities::detect();
+    assert_eq!(capabilities, EngineCapabilities::detect());
+
+    let simd = wat::parse_str(r#"(module (func (result v128) v128.const i64x2 0 0))"#).unwrap();
+    assert_eq!(Module::validate(&simd).is_ok(), capabilities.simd);
+    let exceptions = wat::parse_str(r#"(module (tag))"#).unwrap();
+    assert_eq!(
+        Module::validate(&exceptions).is_ok(),
+        capabilities.exceptions
+    );
+}
@@ -69,6 +69,13 @@ pub enum TrapCode {
 
     /// An atomic memory access was attempted with an unaligned pointer.
     UnalignedAtomic = 10,
+
+    /// Execution was interrupted because the store's epoch deadline was
+    /// reached.
+    Interrupt = 11,
+
+    /// Execution ran out of fuel.
+    OutOfFuel = 12,
 }
 
 impl TrapCode {
@@ -86,6 +93,8 @@ impl TrapCode {
             Self::BadConversionToInteger => "invalid conversion to integer",
             Self::UnreachableCodeReached => "unreachable",
             Self::UnalignedAtomic => "unaligned atomic access",
+            Self::Interrupt => "interrupted",
+            Self::OutOfFuel => "out of fuel",
         }
     }
 }
@@ -104,6 +113,8 @@ impl Display for TrapCode {
             Self::BadConversionToInteger => "bad_toint",
             Self::UnreachableCodeReached => "unreachable",
             Self::UnalignedAtomic => "unalign_atom",
+            Self::Interrupt => "interrupt",
+            Self::OutOfFuel => "out_of_fuel",
         };
         f.write_str(identifier)
     }
@@ -125,6 +136,8 @@ impl FromStr for TrapCode {
             "bad_toint" => Ok(Self::BadConversionToInteger),
             "unreachable" => Ok(Self::UnreachableCodeReached),
             "unalign_atom" => Ok(Self::UnalignedAtomic),
+            "interrupt" => Ok(Self::Interrupt),
+            "out_of_fuel" => Ok(Self::OutOfFuel),
             _ => Err(()),
         }
     }
@@ -135,7 +148,7 @@ mod tests {
     use super::*;
 
     // Everything but user-defined codes.
-    const CODES: [TrapCode; 11] = [
+    const CODES: [TrapCode; 13] = [
         TrapCode::StackOverflow,
         TrapCode::HeapAccessOutOfBounds,
         TrapCode::HeapMisaligned,
@@ -147,6 +160,8 @@ mod tests {
         TrapCode::BadConversionToInteger,
         TrapCode::UnreachableCodeReached,
         TrapCode::UnalignedAtomic,
+        TrapCode::Interrupt,
+        TrapCode::OutOfFuel,
     ];
 
     #[test]
@@ -121,6 +121,8 @@ pub enum ExternType {
     Table(TableType),
     /// This external type is the type of a WebAssembly memory.
     Memory(MemoryType),
+    /// This external type is the type of a WebAssembly exception tag.
+    Tag(TagType),
 }
 
 fn is_global_compatible(exported: GlobalType, imported: GlobalType) -> bool {
@@ -175,11 +177,13 @@ fn is_memory_compatible(
         minimum: exported_minimum,
         maximum: exported_maximum,
         shared: exported_shared,
+        index_type: exported_index_type,
     } = exported;
     let MemoryType {
         minimum: imported_minimum,
         maximum: imported_maximum,
         shared: imported_shared,
+        index_type: imported_index_type,
     } = imported;
 
     imported_minimum.0 <= imported_runtime_size.unwrap_or(exported_minimum.0)
@@ -187,6 +191,7 @@ fn is_memory_compatible(
             || (!exported_maximum.is_none()
                 && imported_maximum.unwrap() >= exported_maximum.unwrap()))
         && exported_shared == imported_shared
+        && exported_index_type == imported_index_type
 }
 
 macro_rules! accessors {
@@ -219,6 +224,7 @@ impl ExternType {
         (Global(GlobalType) global unwrap_global)
         (Table(TableType) table unwrap_table)
         (Memory(MemoryType) memory unwrap_memory)
+        (Tag(TagType) tag unwrap_tag)
     }
     /// Check if two externs are compatible
     pub fn is_compatible_with(&self, other: &Self, runtime_size: Option<u32>) -> bool {
@@ -227,6 +233,7 @@ impl ExternType {
             (Self::Global(a), Self::Global(b)) => is_global_compatible(*a, *b),
             (Self::Table(a), Self::Table(b)) => is_table_compatible(a, b, runtime_size),
             (Self::Memory(a), Self::Memory(b)) => is_memory_compatible(a, b, runtime_size),
+            (Self::Tag(a), Self::Tag(b)) => a == b,
             // The rest of possibilities, are not compatible
             _ => false,
         }
@@ -496,11 +503,14 @@ pub struct MemoryType {
     pub maximum: Option<Pages>,
     /// Whether the memory may be shared between multiple threads.
     pub shared: bool,
+    /// The type of the addresses used to index the memory.
+    #[cfg_attr(feature = "enable-serde", serde(default))]
+    pub index_type: IndexType,
 }
 
 impl MemoryType {
-    /// Creates a new descriptor for a WebAssembly memory given the specified
-    /// limits of the memory.
+    /// Creates a new descriptor for a 32-bit WebAssembly memory given the
+    /// specified limits of the memory.
     pub fn new<IntoPages>(minimum: IntoPages, maximum: Option<IntoPages>, shared: bool) -> Self
     where
         IntoPages: Into<Pages>,
@@ -509,19 +519,101 @@ impl MemoryType {
             minimum: minimum.into(),
             maximum: maximum.map(Into::into),
             shared,
+            index_type: IndexType::I32,
         }
     }
+
+    /// Creates a new descriptor for a 64-bit WebAssembly memory (as defined
+    /// by the memory64 proposal) given the specified limits of the memory.
+    pub fn new_64<IntoPages>(minimum: IntoPages, maximum: Option<IntoPages>, shared: bool) -> Self
+    where
+        IntoPages: Into<Pages>,
+    {
+        Self {
+            index_type: IndexType::I64,
+            ..Self::new(minimum, maximum, shared)
+        }
+    }
+
+    /// Returns whether the memory is indexed with 64-bit addresses.
+    pub fn is_64(&self) -> bool {
+        self.index_type == IndexType::I64
+    }
 }
 
 impl fmt::Display for MemoryType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let shared = if self.shared { "shared" } else { "not shared" };
+        let index_type = if self.is_64() { " i64" } else { "" };
         if let Some(maximum) = self.maximum {
-            write!(f, "{} ({:?}..{:?})", shared, self.minimum, maximum)
+            write!(
+                f,
+                "{}{} ({:?}..{:?})",
+                shared, index_type, self.minimum, maximum
+            )
         } else {
-            write!(f, "{} ({:?}..)", shared, self.minimum)
+            write!(f, "{}{} ({:?}..)", shared, index_type, self.minimum)
+        }
+    }
+}
+
+// Tag Types
+
+/// A descriptor for an exception tag, as defined by the exception-handling
+/// proposal.
+///
+/// A tag describes the values carried by the exceptions thrown with it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+#[derive(RkyvSerialize, RkyvDeserialize, Archive)]
+#[archive_attr(derive(CheckBytes, Debug))]
+pub struct TagType {
+    /// The values carried by exceptions thrown with the tag.
+    params: Box<[Type]>,
+}
+
+impl TagType {
+    /// Creates a new tag descriptor whose exceptions carry values of the
+    /// given types.
+    pub fn new<Params>(params: Params) -> Self
+    where
+        Params: Into<Box<[Type]>>,
+    {
+        Self {
+            params: params.into(),
         }
     }
+
+    /// Parameter types.
+    pub fn params(&self) -> &[Type] {
+        &self.params
+    }
+}
+
+impl fmt::Display for TagType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let params = self
+            .params
+            .iter()
+            .map(|p| format!("{:?}", p))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "[{}]", params)
+    }
+}
+
+/// The type of the addresses used to index a linear memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, CheckBytes)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+#[derive(RkyvSerialize, RkyvDeserialize, Archive)]
+#[archive(as = "Self")]
+#[repr(u8)]
+pub enum IndexType {
+    /// 32-bit addresses, as in the WebAssembly MVP.
+    #[default]
+    I32,
+    /// 64-bit addresses, as defined by the memory64 proposal.
+    I64,
 }
 
 // Import Types
@@ -1,15 +1,17 @@
 use std::cell::LazyCell;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
 use std::{
     collections::BTreeMap,
     fmt::{Debug, Display},
     num::NonZeroUsize,
+    time::Duration,
 };
 
 use js_sys::{JsString, Promise, Uint8Array};
 
 use wasm_bindgen::{prelude::wasm_bindgen, JsCast, JsValue};
-use web_sys::{Window, WorkerGlobalScope};
+use web_sys::{IdbFactory, Window, WorkerGlobalScope};
 
 /// Try to extract the most appropriate error message from a [`JsValue`],
 /// falling back to a generic error message.
@@ -96,6 +98,41 @@ impl GlobalScope {
         now - start as f64
     }
 
+    /// The resolution of [`GlobalScope::now`].
+    ///
+    /// Browsers coarsen their timers to mitigate timing attacks, more so
+    /// when the page is not cross-origin isolated, so the resolution is
+    /// measured the first time it is needed.
+    pub fn timer_resolution(&self) -> Duration {
+        const TICKS: usize = 5;
+        const MAX_MEASURE_MS: f64 = 10.;
+        static RESOLUTION: OnceLock<Duration> = OnceLock::new();
+
+        *RESOLUTION.get_or_init(|| {
+            let mut previous = self.now();
+            let until = previous + MAX_MEASURE_MS;
+            let mut resolution = f64::INFINITY;
+            let mut ticks = 0;
+            while ticks < TICKS {
+                let now = self.now();
+                if now > previous {
+                    resolution = resolution.min(now - previous);
+                    previous = now;
+                    ticks += 1;
+                }
+                if now > until {
+                    break;
+                }
+            }
+
+            if resolution.is_finite() {
+                Duration::from_secs_f64(resolution / 1000.)
+            } else {
+                Duration::from_millis(1)
+            }
+        })
+    }
+
     /// The amount of concurrency available on this system.
     ///
     /// Returns `None` if unable to determine the available concurrency.
@@ -117,6 +154,15 @@ impl GlobalScope {
             .and_then(|obj| obj.as_bool())
     }
 
+    /// The IndexedDB factory, if IndexedDB is available in this context.
+    pub fn indexed_db(&self) -> Option<IdbFactory> {
+        let factory = match self {
+            GlobalScope::Window(window) => window.indexed_db(),
+            GlobalScope::Worker(worker) => worker.indexed_db(),
+        };
+        factory.ok().flatten()
+    }
+
     /// Browser information.
     pub fn navigator(&self) -> NavigatorInfo {
         let user_agent = match self {
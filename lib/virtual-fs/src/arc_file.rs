@@ -130,6 +130,10 @@ where
         let mut inner = self.inner.lock().unwrap();
         inner.set_len(new_size)
     }
+    fn advise(&mut self, offset: u64, len: u64, advice: crate::FileAdvice) -> crate::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.advise(offset, len, advice)
+    }
     fn unlink(&mut self) -> crate::Result<()> {
         let mut inner = self.inner.lock().unwrap();
         inner.unlink()
@@ -138,6 +142,10 @@ where
         let inner = self.inner.lock().unwrap();
         inner.is_open()
     }
+    fn file_id(&self) -> Option<crate::FileId> {
+        let inner = self.inner.lock().unwrap();
+        inner.file_id()
+    }
     fn get_special_fd(&self) -> Option<u32> {
         let inner = self.inner.lock().unwrap();
         inner.get_special_fd()
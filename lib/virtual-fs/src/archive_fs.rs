@@ -0,0 +1,661 @@
+//! Read-only file systems backed by archives: `.zip` files, and the volumes
+//! of `.webc` packages when the `webc` feature is enabled.
+//!
+//! The archive is indexed when the file system is created, the contents of
+//! the files are only decompressed when they're opened.
+
+use std::{
+    collections::BTreeMap,
+    io::Read,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use futures::future::BoxFuture;
+use shared_buffer::OwnedBuffer;
+
+use crate::{
+    DirEntry, FileOpener, FileSystem, FileType, FsError, FsStats, Metadata, OpenOptions,
+    OpenOptionsConfig, ReadDir, Result, StaticFile, VirtualFile,
+};
+
+/// The block size reported by [`FileSystem::statfs`].
+const BLOCK_SIZE: u64 = 4096;
+
+/// The signatures of the records of a zip file.
+const LOCAL_HEADER: u32 = 0x04034b50;
+const CENTRAL_HEADER: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIR: u32 = 0x06054b50;
+const ZIP64_END_OF_CENTRAL_DIR: u32 = 0x06064b50;
+const ZIP64_LOCATOR: u32 = 0x07064b50;
+
+/// How the contents of a file are stored in the archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    Stored,
+    Deflated,
+    /// A compression method or an encryption which isn't supported, the file
+    /// is listed but can't be opened
+    Unsupported,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Dir {
+        modified: u64,
+    },
+    File {
+        data: OwnedBuffer,
+        compression: Compression,
+        size: u64,
+        modified: u64,
+    },
+}
+
+impl Node {
+    fn metadata(&self) -> Metadata {
+        match self {
+            Node::Dir { modified } => Metadata {
+                ft: FileType::new_dir(),
+                accessed: *modified,
+                created: *modified,
+                modified: *modified,
+                len: 0,
+            },
+            Node::File { size, modified, .. } => Metadata {
+                ft: FileType::new_file(),
+                accessed: *modified,
+                created: *modified,
+                modified: *modified,
+                len: *size,
+            },
+        }
+    }
+}
+
+/// A read-only [`FileSystem`] whose directories and files are the entries of
+/// an archive.
+#[derive(Debug, Clone)]
+pub struct ArchiveFileSystem {
+    nodes: Arc<BTreeMap<PathBuf, Node>>,
+}
+
+impl ArchiveFileSystem {
+    /// Indexes a `.zip` file, or a `.webc` package when the `webc` feature is
+    /// enabled, guessing the format from its contents.
+    pub fn from_bytes(bytes: impl Into<OwnedBuffer>) -> Result<Self> {
+        let bytes = bytes.into();
+        if bytes.starts_with(b"\0webc") {
+            #[cfg(feature = "webc")]
+            return Self::from_webc(bytes.into_bytes());
+            #[cfg(not(feature = "webc"))]
+            return Err(FsError::Unsupported);
+        }
+        Self::from_zip(bytes)
+    }
+
+    /// Indexes a `.zip` file. Its entries going above the root with `..`
+    /// are ignored.
+    pub fn from_zip(bytes: impl Into<OwnedBuffer>) -> Result<Self> {
+        let bytes = bytes.into();
+        let mut nodes = Nodes::new();
+        for entry in zip::central_directory(&bytes)? {
+            let entry = entry?;
+            let Some(path) = normalize(Path::new(&entry.name)) else {
+                tracing::warn!(name = %entry.name, "Ignoring a zip entry outside of the archive");
+                continue;
+            };
+
+            if entry.name.ends_with('/') {
+                nodes.insert(
+                    path,
+                    Node::Dir {
+                        modified: entry.modified,
+                    },
+                )?;
+            } else {
+                let data = zip::data(&bytes, &entry)?;
+                nodes.insert(
+                    path,
+                    Node::File {
+                        data,
+                        compression: entry.compression,
+                        size: entry.size,
+                        modified: entry.modified,
+                    },
+                )?;
+            }
+        }
+        Ok(Self {
+            nodes: Arc::new(nodes.0),
+        })
+    }
+
+    /// Indexes a `.webc` package, whose volumes are merged at the root.
+    #[cfg(feature = "webc")]
+    pub fn from_webc(bytes: impl Into<bytes::Bytes>) -> Result<Self> {
+        let container = webc::Container::from_bytes(bytes).map_err(|err| {
+            tracing::warn!(%err, "Invalid webc package");
+            FsError::InvalidData
+        })?;
+
+        fn add_dir(nodes: &mut Nodes, volume: &webc::compat::Volume, path: &Path) -> Result<()> {
+            let entries = volume.read_dir(path).ok_or(FsError::InvalidData)?;
+            for (name, _, metadata) in entries {
+                let path = path.join(name.to_string());
+                match metadata {
+                    webc::compat::Metadata::Dir { .. } => {
+                        nodes.insert(path.clone(), Node::Dir { modified: 0 })?;
+                        add_dir(nodes, volume, &path)?;
+                    }
+                    webc::compat::Metadata::File { .. } => {
+                        let (data, _) = volume
+                            .read_file(path.as_path())
+                            .ok_or(FsError::InvalidData)?;
+                        nodes.insert(
+                            path,
+                            Node::File {
+                                size: data.len() as u64,
+                                data,
+                                compression: Compression::Stored,
+                                modified: 0,
+                            },
+                        )?;
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        let mut nodes = Nodes::new();
+        for volume in container.volumes().values() {
+            add_dir(&mut nodes, volume, Path::new("/"))?;
+        }
+        Ok(Self {
+            nodes: Arc::new(nodes.0),
+        })
+    }
+
+    fn node(&self, path: &Path) -> Result<&Node> {
+        let path = normalize(path).ok_or(FsError::InvalidInput)?;
+        self.nodes.get(&path).ok_or(FsError::EntryNotFound)
+    }
+}
+
+/// The nodes of an archive being indexed.
+struct Nodes(BTreeMap<PathBuf, Node>);
+
+impl Nodes {
+    fn new() -> Self {
+        Nodes(BTreeMap::from([(
+            PathBuf::from("/"),
+            Node::Dir { modified: 0 },
+        )]))
+    }
+
+    /// Adds a node along with its missing ancestors. The entries listed
+    /// twice replace the previous ones, like when extracting the archive.
+    fn insert(&mut self, path: PathBuf, node: Node) -> Result<()> {
+        for ancestor in path.ancestors().skip(1) {
+            match self.0.get(ancestor) {
+                Some(Node::Dir { .. }) => break,
+                Some(Node::File { .. }) => return Err(FsError::InvalidData),
+                None => {
+                    self.0
+                        .insert(ancestor.to_path_buf(), Node::Dir { modified: 0 });
+                }
+            }
+        }
+
+        if let Some(Node::Dir { modified }) = self.0.get_mut(&path) {
+            return match node {
+                // A directory listed after its children keeps them
+                Node::Dir { modified: listed } => {
+                    *modified = listed;
+                    Ok(())
+                }
+                Node::File { .. } => Err(FsError::InvalidData),
+            };
+        }
+        self.0.insert(path, node);
+        Ok(())
+    }
+}
+
+/// Makes `path` absolute and removes its `.` and `..` components, or returns
+/// `None` if it goes above the root.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+        }
+    }
+    Some(normalized)
+}
+
+impl FileSystem for ArchiveFileSystem {
+    fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        self.node(path)?;
+        Err(FsError::InvalidInput)
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        let Node::Dir { .. } = self.node(path)? else {
+            return Err(FsError::BaseNotDirectory);
+        };
+
+        let path = normalize(path).ok_or(FsError::InvalidInput)?;
+        let entries = self
+            .nodes
+            .range(path.clone()..)
+            .skip(1)
+            .take_while(|(child, _)| child.starts_with(&path))
+            .filter(|(child, _)| child.parent() == Some(path.as_path()))
+            .map(|(child, node)| DirEntry {
+                path: child.clone(),
+                metadata: Ok(node.metadata()),
+            })
+            .collect();
+        Ok(ReadDir::new(entries))
+    }
+
+    fn create_dir(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn remove_dir(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn rename<'a>(&'a self, _from: &'a Path, _to: &'a Path) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async { Err(FsError::PermissionDenied) })
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        self.node(path).map(Node::metadata)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        self.metadata(path)
+    }
+
+    fn remove_file(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn statfs(&self, path: &Path) -> Result<FsStats> {
+        self.node(path)?;
+        let blocks = self
+            .nodes
+            .values()
+            .map(|node| match node {
+                Node::File { size, .. } => size.div_ceil(BLOCK_SIZE),
+                Node::Dir { .. } => 0,
+            })
+            .sum();
+
+        Ok(FsStats {
+            block_size: BLOCK_SIZE,
+            blocks,
+            blocks_free: 0,
+            files: self.nodes.len() as u64,
+            files_free: 0,
+        })
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(self)
+    }
+
+    fn mount(
+        &self,
+        _name: String,
+        _path: &Path,
+        _fs: Box<dyn FileSystem + Send + Sync>,
+    ) -> Result<()> {
+        Err(FsError::Unsupported)
+    }
+}
+
+impl FileOpener for ArchiveFileSystem {
+    fn open(
+        &self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        if conf.would_mutate() {
+            return Err(FsError::PermissionDenied);
+        }
+
+        let Node::File {
+            data,
+            compression,
+            size,
+            ..
+        } = self.node(path)?
+        else {
+            return Err(FsError::NotAFile);
+        };
+        let contents = match compression {
+            Compression::Stored => data.clone(),
+            Compression::Deflated => {
+                let mut contents = Vec::with_capacity(*size as usize);
+                flate2::read::DeflateDecoder::new(data.as_slice())
+                    .read_to_end(&mut contents)
+                    .map_err(|_| FsError::InvalidData)?;
+                if contents.len() as u64 != *size {
+                    return Err(FsError::InvalidData);
+                }
+                OwnedBuffer::from(contents)
+            }
+            Compression::Unsupported => return Err(FsError::Unsupported),
+        };
+        Ok(Box::new(StaticFile::new(contents)))
+    }
+}
+
+/// The parsing of the `.zip` format, see the APPNOTE of PKWARE.
+mod zip {
+    use super::*;
+
+    /// A file or directory listed in the central directory.
+    #[derive(Debug)]
+    pub(super) struct Entry {
+        pub name: String,
+        pub compression: Compression,
+        pub compressed_size: u64,
+        pub size: u64,
+        pub modified: u64,
+        /// The offset of the local header of the entry
+        pub offset: u64,
+    }
+
+    /// Reads the little-endian integers of a record.
+    struct Reader<'a>(&'a [u8]);
+
+    impl<'a> Reader<'a> {
+        fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+            if self.0.len() < len {
+                return Err(FsError::InvalidData);
+            }
+            let (bytes, rest) = self.0.split_at(len);
+            self.0 = rest;
+            Ok(bytes)
+        }
+
+        fn u16(&mut self) -> Result<u16> {
+            let bytes = self.bytes(2)?;
+            Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+        }
+
+        fn u32(&mut self) -> Result<u32> {
+            let bytes = self.bytes(4)?;
+            Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+        }
+
+        fn u64(&mut self) -> Result<u64> {
+            let bytes = self.bytes(8)?;
+            Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+        }
+    }
+
+    fn slice(bytes: &[u8], offset: u64, len: u64) -> Result<&[u8]> {
+        let start = usize::try_from(offset).map_err(|_| FsError::InvalidData)?;
+        let len = usize::try_from(len).map_err(|_| FsError::InvalidData)?;
+        bytes
+            .get(start..)
+            .and_then(|bytes| bytes.get(..len))
+            .ok_or(FsError::InvalidData)
+    }
+
+    /// Lists the entries of the central directory.
+    pub(super) fn central_directory(
+        bytes: &[u8],
+    ) -> Result<impl Iterator<Item = Result<Entry>> + '_> {
+        // The end of central directory record is followed by a comment of
+        // up to 64 KiB
+        let eocd = (0..bytes.len().saturating_sub(21))
+            .rev()
+            .take(u16::MAX as usize + 1)
+            .find(|&at| bytes[at..].starts_with(&END_OF_CENTRAL_DIR.to_le_bytes()))
+            .ok_or(FsError::InvalidData)?;
+
+        let mut record = Reader(&bytes[eocd + 10..]);
+        let mut count = u64::from(record.u16()?);
+        let mut size = u64::from(record.u32()?);
+        let mut offset = u64::from(record.u32()?);
+
+        // Archives of more than 65535 entries or 4 GiB have a zip64 record
+        let locator = eocd.checked_sub(20).map(|at| &bytes[at..eocd]);
+        if let Some(mut locator) = locator.map(Reader) {
+            if locator.u32()? == ZIP64_LOCATOR {
+                locator.u32()?;
+                let at = locator.u64()?;
+                let mut record = Reader(slice(bytes, at, 56)?);
+                if record.u32()? != ZIP64_END_OF_CENTRAL_DIR {
+                    return Err(FsError::InvalidData);
+                }
+                record.bytes(20)?;
+                count = record.u64()?;
+                size = record.u64()?;
+                offset = record.u64()?;
+            }
+        }
+
+        let mut directory = Reader(slice(bytes, offset, size)?);
+        Ok((0..count).map(move |_| entry(&mut directory)))
+    }
+
+    fn entry(directory: &mut Reader<'_>) -> Result<Entry> {
+        if directory.u32()? != CENTRAL_HEADER {
+            return Err(FsError::InvalidData);
+        }
+        directory.bytes(4)?;
+        let flags = directory.u16()?;
+        let method = directory.u16()?;
+        let time = directory.u16()?;
+        let date = directory.u16()?;
+        directory.u32()?;
+        let mut compressed_size = u64::from(directory.u32()?);
+        let mut size = u64::from(directory.u32()?);
+        let name_len = directory.u16()?;
+        let extra_len = directory.u16()?;
+        let comment_len = directory.u16()?;
+        directory.bytes(8)?;
+        let mut offset = u64::from(directory.u32()?);
+        let name = String::from_utf8_lossy(directory.bytes(name_len.into())?).into_owned();
+        let mut extra = Reader(directory.bytes(extra_len.into())?);
+        directory.bytes(comment_len.into())?;
+
+        // The sizes and offset which don't fit are in the zip64 extra field
+        while let (Ok(id), Ok(len)) = (extra.u16(), extra.u16()) {
+            let mut field = Reader(extra.bytes(len.into())?);
+            if id != 0x0001 {
+                continue;
+            }
+            if size == u64::from(u32::MAX) {
+                size = field.u64()?;
+            }
+            if compressed_size == u64::from(u32::MAX) {
+                compressed_size = field.u64()?;
+            }
+            if offset == u64::from(u32::MAX) {
+                offset = field.u64()?;
+            }
+        }
+
+        let encrypted = flags & 1 != 0;
+        let compression = match method {
+            _ if encrypted => Compression::Unsupported,
+            0 => Compression::Stored,
+            8 => Compression::Deflated,
+            _ => Compression::Unsupported,
+        };
+        Ok(Entry {
+            name,
+            compression,
+            compressed_size,
+            size,
+            modified: dos_time(date, time),
+            offset,
+        })
+    }
+
+    /// The compressed contents of an entry, which follow its local header.
+    pub(super) fn data(bytes: &OwnedBuffer, entry: &Entry) -> Result<OwnedBuffer> {
+        let mut header = Reader(slice(bytes, entry.offset, 30)?);
+        if header.u32()? != LOCAL_HEADER {
+            return Err(FsError::InvalidData);
+        }
+        header.bytes(22)?;
+        let name_len = header.u16()?;
+        let extra_len = header.u16()?;
+
+        let start = entry.offset + 30 + u64::from(name_len) + u64::from(extra_len);
+        slice(bytes, start, entry.compressed_size)?;
+        let start = start as usize;
+        Ok(bytes.slice(start..start + entry.compressed_size as usize))
+    }
+
+    /// Converts an MS-DOS date and time, in local time which is assumed to be
+    /// UTC, to nanoseconds since the epoch.
+    pub(super) fn dos_time(date: u16, time: u16) -> u64 {
+        let year = 1980 + i64::from(date >> 9);
+        let month = i64::from((date >> 5) & 0xf).clamp(1, 12);
+        let day = i64::from(date & 0x1f).max(1);
+
+        // The days since the epoch of a date of the proleptic Gregorian
+        // calendar, with years starting in March
+        let (y, m) = if month <= 2 {
+            (year - 1, month + 9)
+        } else {
+            (year, month - 3)
+        };
+        let era = y.div_euclid(400);
+        let year_of_era = y - era * 400;
+        let day_of_year = (153 * m + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+
+        let seconds = i64::from(time >> 11) * 3600
+            + i64::from((time >> 5) & 0x3f) * 60
+            + i64::from(time & 0x1f) * 2;
+        (days * 86_400 + seconds) as u64 * 1_000_000_000
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    /// Builds a zip file storing `entries` without compression.
+    fn zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut archive = Vec::new();
+        let mut directory = Vec::new();
+        for (name, data) in entries {
+            let offset = archive.len() as u32;
+            let mut common = Vec::new();
+            common.extend_from_slice(&20u16.to_le_bytes());
+            common.extend_from_slice(&0u16.to_le_bytes());
+            common.extend_from_slice(&0u16.to_le_bytes());
+            // 2024-02-29 12:30:10
+            common.extend_from_slice(&((12 << 11) | (30 << 5) | 5u16).to_le_bytes());
+            common.extend_from_slice(&((44 << 9) | (2 << 5) | 29u16).to_le_bytes());
+            common.extend_from_slice(&0u32.to_le_bytes());
+            common.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            common.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            common.extend_from_slice(&0u16.to_le_bytes());
+
+            archive.extend_from_slice(&LOCAL_HEADER.to_le_bytes());
+            archive.extend_from_slice(&common);
+            archive.extend_from_slice(name.as_bytes());
+            archive.extend_from_slice(data);
+
+            directory.extend_from_slice(&CENTRAL_HEADER.to_le_bytes());
+            directory.extend_from_slice(&20u16.to_le_bytes());
+            directory.extend_from_slice(&common);
+            directory.extend_from_slice(&[0; 10]);
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+
+        let offset = archive.len() as u32;
+        archive.extend_from_slice(&directory);
+        archive.extend_from_slice(&END_OF_CENTRAL_DIR.to_le_bytes());
+        archive.extend_from_slice(&[0; 4]);
+        archive.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        archive.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        archive.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        archive.extend_from_slice(&offset.to_le_bytes());
+        archive.extend_from_slice(&0u16.to_le_bytes());
+        archive
+    }
+
+    #[tokio::test]
+    async fn zip_entries_are_mounted_read_only() {
+        let archive = zip(&[
+            ("assets/", b""),
+            ("assets/logo.svg", b"<svg/>"),
+            ("plugin/main.wasm", b"\0asm"),
+            ("../escape.txt", b"nope"),
+        ]);
+        let fs = ArchiveFileSystem::from_bytes(archive).unwrap();
+
+        let mut names: Vec<_> = fs
+            .read_dir(Path::new("/"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path)
+            .collect();
+        names.sort();
+        assert_eq!(names, [Path::new("/assets"), Path::new("/plugin")]);
+
+        let metadata = fs.metadata(Path::new("/assets/logo.svg")).unwrap();
+        assert_eq!(metadata.len, 6);
+        assert_eq!(metadata.modified, 1_709_209_810_000_000_000);
+        assert!(fs.metadata(Path::new("/plugin")).unwrap().is_dir());
+
+        let mut contents = String::new();
+        fs.new_open_options()
+            .read(true)
+            .open("/assets/logo.svg")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .await
+            .unwrap();
+        assert_eq!(contents, "<svg/>");
+
+        assert_eq!(
+            fs.new_open_options()
+                .write(true)
+                .open("/assets/logo.svg")
+                .map(|_| ()),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(
+            fs.create_dir(Path::new("/more")),
+            Err(FsError::PermissionDenied)
+        );
+    }
+
+    #[test]
+    fn invalid_archives_are_rejected() {
+        assert_eq!(
+            ArchiveFileSystem::from_bytes(b"not an archive".to_vec()).map(|_| ()),
+            Err(FsError::InvalidData)
+        );
+
+        let mut archive = zip(&[("file.txt", b"hello")]);
+        archive.truncate(archive.len() - 30);
+        assert!(ArchiveFileSystem::from_zip(archive).is_err());
+    }
+}
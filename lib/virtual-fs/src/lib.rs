@@ -13,12 +13,14 @@ use std::task::Poll;
 use thiserror::Error;
 
 pub mod arc_file;
+pub mod archive_fs;
 pub mod buffer_file;
 pub mod cow_file;
 mod filesystems;
 pub mod mem_fs;
 pub mod null_file;
 pub(crate) mod ops;
+pub mod overlay_fs;
 pub mod pipe;
 mod static_file;
 pub mod tmp_fs;
@@ -26,10 +28,12 @@ pub mod tmp_fs;
 pub mod limiter;
 
 pub use arc_file::*;
+pub use archive_fs::ArchiveFileSystem;
 pub use buffer_file::*;
 pub use cow_file::*;
 pub use filesystems::FileSystems;
 pub use null_file::*;
+pub use overlay_fs::OverlayFileSystem;
 pub use pipe::*;
 pub use static_file::StaticFile;
 pub use tmp_fs::*;
@@ -44,7 +48,7 @@ pub use tokio::io::{AsyncWrite, AsyncWriteExt};
 
 pub trait ClonableVirtualFile: VirtualFile + Clone {}
 
-pub use ops::{copy_reference, copy_reference_ext, create_dir_all};
+pub use ops::{copy_range, copy_reference, copy_reference_ext, create_dir_all};
 
 pub trait FileSystem: fmt::Debug + Send + Sync + 'static + Upcastable {
     fn readlink(&self, path: &Path) -> Result<PathBuf>;
@@ -58,11 +62,43 @@ pub trait FileSystem: fmt::Debug + Send + Sync + 'static + Upcastable {
     /// yet.
     fn symlink_metadata(&self, path: &Path) -> Result<Metadata>;
     fn remove_file(&self, path: &Path) -> Result<()>;
+    /// Creates a new name `to` for the file at `from`, both names then refer
+    /// to the same contents until one of them is removed.
+    fn hard_link(&self, from: &Path, to: &Path) -> Result<()> {
+        let _ = (from, to);
+        Err(FsError::Unsupported)
+    }
+    /// Reports the capacity and usage of the file system holding `path`,
+    /// which is the mounted file system when `path` is inside a mount.
+    fn statfs(&self, path: &Path) -> Result<FsStats> {
+        let _ = path;
+        Err(FsError::Unsupported)
+    }
+
+    /// Returns the mode and owner of the file at `path`, which are the
+    /// defaults of its type unless they were changed.
+    fn permissions(&self, path: &Path) -> Result<Permissions> {
+        let metadata = self.metadata(path)?;
+        Ok(Permissions::default_for(&metadata.ft))
+    }
+    /// Changes the mode and owner of the file at `path`, like `chmod` and
+    /// `chown`. No access checks are made with them.
+    fn set_permissions(&self, path: &Path, permissions: Permissions) -> Result<()> {
+        let _ = (path, permissions);
+        Err(FsError::Unsupported)
+    }
 
     fn new_open_options(&self) -> OpenOptions;
 
     fn mount(&self, name: String, path: &Path, fs: Box<dyn FileSystem + Send + Sync>)
         -> Result<()>;
+
+    /// Unmounts the file system mounted at `path`, which removes the mount
+    /// point.
+    fn unmount(&self, path: &Path) -> Result<()> {
+        let _ = path;
+        Err(FsError::Unsupported)
+    }
 }
 
 impl dyn FileSystem + 'static {
@@ -114,6 +150,22 @@ where
         (**self).remove_file(path)
     }
 
+    fn hard_link(&self, from: &Path, to: &Path) -> Result<()> {
+        (**self).hard_link(from, to)
+    }
+
+    fn statfs(&self, path: &Path) -> Result<FsStats> {
+        (**self).statfs(path)
+    }
+
+    fn permissions(&self, path: &Path) -> Result<Permissions> {
+        (**self).permissions(path)
+    }
+
+    fn set_permissions(&self, path: &Path, permissions: Permissions) -> Result<()> {
+        (**self).set_permissions(path, permissions)
+    }
+
     fn new_open_options(&self) -> OpenOptions {
         (**self).new_open_options()
     }
@@ -126,6 +178,10 @@ where
     ) -> Result<()> {
         (**self).mount(name, path, fs)
     }
+
+    fn unmount(&self, path: &Path) -> Result<()> {
+        (**self).unmount(path)
+    }
 }
 
 pub trait FileOpener {
@@ -324,6 +380,32 @@ pub trait VirtualFile:
     /// the extra bytes will be allocated and zeroed
     fn set_len(&mut self, new_size: u64) -> Result<()>;
 
+    /// Makes sure the file is at least `offset + len` bytes long, it never
+    /// shrinks. The default grows the file like [`VirtualFile::set_len`],
+    /// file systems may reserve the space or leave it as a hole.
+    fn allocate(&mut self, offset: u64, len: u64) -> Result<()> {
+        let end = offset.checked_add(len).ok_or(FsError::InvalidInput)?;
+        if end > self.size() {
+            self.set_len(end)?;
+        }
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    /// Deallocates a range of the file, which then reads as zeros, without
+    /// changing its size
+    fn punch_hole(&mut self, offset: u64, len: u64) -> Result<()> {
+        Err(FsError::Unsupported)
+    }
+
+    #[allow(unused_variables)]
+    /// Hints how `len` bytes of the file from `offset` will be accessed, or
+    /// the rest of the file when `len` is 0, which file systems may use to
+    /// prefetch or drop cached data. The default ignores the hint.
+    fn advise(&mut self, offset: u64, len: u64, advice: FileAdvice) -> Result<()> {
+        Ok(())
+    }
+
     /// Request deletion of the file
     fn unlink(&mut self) -> Result<()>;
 
@@ -333,6 +415,13 @@ pub trait VirtualFile:
         true
     }
 
+    /// Identifies the file the handle refers to, the same for all the
+    /// handles of the file whatever the name they were opened with. Returns
+    /// `None` when the file system can't tell its files apart
+    fn file_id(&self) -> Option<FileId> {
+        None
+    }
+
     /// Used for "special" files such as `stdin`, `stdout` and `stderr`.
     /// Always returns the same file descriptor (0, 1 or 2). Returns `None`
     /// on normal files
@@ -360,6 +449,20 @@ pub trait VirtualFile:
         })
     }
 
+    /// Copies `len` bytes of `src`, starting at `src_offset`, into this file at
+    /// `offset` and returns the number of bytes copied, which is less than
+    /// `len` when the end of `src` is reached. The default copies through a
+    /// buffer, file systems may copy directly between their files.
+    fn copy_range_from<'a>(
+        &'a mut self,
+        src: &'a mut (dyn VirtualFile + Send + Sync),
+        src_offset: u64,
+        offset: u64,
+        len: u64,
+    ) -> BoxFuture<'a, std::io::Result<u64>> {
+        Box::pin(copy_range(self, src, src_offset, offset, len))
+    }
+
     /// Polls the file for when there is data to be read
     fn poll_read_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<usize>>;
 
@@ -481,6 +584,10 @@ pub enum FsError {
     DirectoryNotEmpty,
     #[error("storage full")]
     StorageFull,
+    /// The operation, like a rename, would cross the boundary between two
+    /// file systems
+    #[error("cross-device link")]
+    CrossDevice,
     /// Some other unhandled error. If you see this, it's probably a bug.
     #[error("unknown error found")]
     UnknownError,
@@ -491,6 +598,15 @@ pub enum FsError {
 
 impl From<io::Error> for FsError {
     fn from(io_error: io::Error) -> Self {
+        // The errors without an `io::ErrorKind` of their own are carried by
+        // the `io::Error`
+        if let Some(fs_error) = io_error
+            .get_ref()
+            .and_then(|error| error.downcast_ref::<FsError>())
+        {
+            return *fs_error;
+        }
+
         match io_error.kind() {
             io::ErrorKind::AddrInUse => FsError::AddressInUse,
             io::ErrorKind::AddrNotAvailable => FsError::AddressNotAvailable,
@@ -546,10 +662,11 @@ impl From<FsError> for io::Error {
             FsError::NoDevice => io::ErrorKind::Other,
             FsError::DirectoryNotEmpty => io::ErrorKind::Other,
             FsError::UnknownError => io::ErrorKind::Other,
-            FsError::StorageFull => io::ErrorKind::Other,
+            // NOTE: Use `io::ErrorKind::StorageFull` once it's stable in the
+            // minimum supported Rust version
+            FsError::StorageFull => return io::Error::other(val),
+            FsError::CrossDevice => return io::Error::other(val),
             FsError::Unsupported => io::ErrorKind::Unsupported,
-            // NOTE: Add this once the "io_error_more" Rust feature is stabilized
-            // FsError::StorageFull => io::ErrorKind::StorageFull,
         };
         kind.into()
     }
@@ -604,6 +721,17 @@ impl DirEntry {
     }
 }
 
+/// Identifies a file, which the hard links of the file share, see
+/// [`VirtualFile::file_id`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId {
+    /// The file system of the file, which no other file system of the
+    /// process shares
+    pub dev: u64,
+    /// The file within its file system
+    pub ino: u64,
+}
+
 #[allow(clippy::len_without_is_empty)] // Clippy thinks it's an iterator.
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 // TODO: review this, proper solution would probably use a trait object internally
@@ -645,6 +773,67 @@ impl Metadata {
     }
 }
 
+/// How a range of a file will be accessed, see [`VirtualFile::advise`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileAdvice {
+    /// No particular access pattern
+    Normal,
+    /// Read from lower offsets to higher offsets
+    Sequential,
+    /// Read in a random order
+    Random,
+    /// Read in the near future
+    WillNeed,
+    /// Not read in the near future
+    DontNeed,
+    /// Read once
+    NoReuse,
+}
+
+/// The capacity and usage of a file system, see [`FileSystem::statfs`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FsStats {
+    /// Size of the blocks the other counts are in
+    pub block_size: u64,
+    /// Total number of blocks
+    pub blocks: u64,
+    /// Number of free blocks
+    pub blocks_free: u64,
+    /// Total number of inodes
+    pub files: u64,
+    /// Number of free inodes
+    pub files_free: u64,
+}
+
+/// The mode and owner of a file, see [`FileSystem::permissions`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Permissions {
+    /// The permission bits, e.g. `0o644`, along with the setuid, setgid and
+    /// sticky bits
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl Permissions {
+    /// The permissions of the files which were never changed, which belong
+    /// to root and can be read by everyone.
+    pub fn default_for(ft: &FileType) -> Self {
+        let mode = if ft.dir {
+            0o755
+        } else if ft.symlink {
+            0o777
+        } else {
+            0o644
+        };
+        Permissions {
+            mode,
+            uid: 0,
+            gid: 0,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 // TODO: review this, proper solution would probably use a trait object internally
 pub struct FileType {
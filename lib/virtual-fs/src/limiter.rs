@@ -1,4 +1,7 @@
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
 
 use crate::FsError;
 
@@ -12,54 +15,160 @@ pub trait FsMemoryLimiter: Send + Sync + std::fmt::Debug {
 
 pub type DynFsMemoryLimiter = Arc<dyn FsMemoryLimiter + Send + Sync>;
 
+/// Limits on the size of a memfs [`FileSystem`], see
+/// [`crate::mem_fs::FileSystem::set_quota`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FsQuota {
+    /// Maximum number of bytes taken by the files, their holes excluded
+    pub bytes: Option<u64>,
+    /// Maximum number of files and directories
+    pub inodes: Option<u64>,
+}
+
+/// A [`FsMemoryLimiter`] failing with [`FsError::StorageFull`] once more
+/// than `limit` bytes are used.
+///
+/// Sharing it between several file systems limits their total size, and the
+/// limiter of each file system can be chained to the shared one to also limit
+/// the size of each of them.
+#[derive(Debug)]
+pub struct QuotaLimiter {
+    limit: u64,
+    used: AtomicU64,
+    parent: Option<DynFsMemoryLimiter>,
+}
+
+impl QuotaLimiter {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            used: AtomicU64::new(0),
+            parent: None,
+        }
+    }
+
+    /// Creates a limiter whose usage also counts towards `parent`.
+    pub fn with_parent(limit: u64, parent: DynFsMemoryLimiter) -> Self {
+        Self {
+            parent: Some(parent),
+            ..Self::new(limit)
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::Acquire)
+    }
+}
+
+impl FsMemoryLimiter for QuotaLimiter {
+    fn on_grow(&self, grown_bytes: usize) -> std::result::Result<(), FsError> {
+        let grown = grown_bytes as u64;
+        self.used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                used.checked_add(grown).filter(|used| *used <= self.limit)
+            })
+            .map_err(|_| FsError::StorageFull)?;
+
+        if let Some(parent) = &self.parent {
+            if let Err(err) = parent.on_grow(grown_bytes) {
+                self.used.fetch_sub(grown, Ordering::AcqRel);
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    fn on_shrink(&self, shrunk_bytes: usize) {
+        let _ = self
+            .used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                Some(used.saturating_sub(shrunk_bytes as u64))
+            });
+        if let Some(parent) = &self.parent {
+            parent.on_shrink(shrunk_bytes);
+        }
+    }
+}
+
 mod tracked_vec {
     use crate::FsError;
 
     use super::DynFsMemoryLimiter;
 
+    /// A buffer whose length is tracked by a [`super::FsMemoryLimiter`],
+    /// its length can only change through its own methods.
     #[derive(Debug)]
     pub struct TrackedVec {
         data: Vec<u8>,
+        limiter: Option<DynFsMemoryLimiter>,
     }
 
     impl TrackedVec {
-        pub fn new(_limiter: Option<DynFsMemoryLimiter>) -> Self {
-            Self { data: Vec::new() }
+        pub fn new(limiter: Option<DynFsMemoryLimiter>) -> Self {
+            Self {
+                data: Vec::new(),
+                limiter,
+            }
         }
 
         pub fn limiter(&self) -> Option<&DynFsMemoryLimiter> {
-            None
+            self.limiter.as_ref()
         }
 
         pub fn with_capacity(
             capacity: usize,
-            _limiter: Option<DynFsMemoryLimiter>,
+            limiter: Option<DynFsMemoryLimiter>,
         ) -> Result<Self, FsError> {
             Ok(Self {
                 data: Vec::with_capacity(capacity),
+                limiter,
             })
         }
 
         pub fn clear(&mut self) {
+            self.shrunk(self.data.len());
             self.data.clear();
         }
 
+        pub fn truncate(&mut self, len: usize) {
+            self.shrunk(self.data.len().saturating_sub(len));
+            self.data.truncate(len);
+        }
+
         pub fn append(&mut self, other: &mut Self) -> Result<(), FsError> {
+            let len = other.data.len();
+            self.grow(len)?;
             self.data.append(&mut other.data);
+            other.shrunk(len);
             Ok(())
         }
 
         pub fn split_off(&mut self, at: usize) -> Result<Self, FsError> {
+            // The bytes split off stay tracked by the same limiter
             let other = self.data.split_off(at);
-            Ok(Self { data: other })
+            Ok(Self {
+                data: other,
+                limiter: self.limiter.clone(),
+            })
         }
 
         pub fn resize(&mut self, new_len: usize, value: u8) -> Result<(), FsError> {
+            let len = self.data.len();
+            if new_len > len {
+                self.grow(new_len - len)?;
+            } else {
+                self.shrunk(len - new_len);
+            }
             self.data.resize(new_len, value);
             Ok(())
         }
 
         pub fn extend_from_slice(&mut self, other: &[u8]) -> Result<(), FsError> {
+            self.grow(other.len())?;
             self.data.extend_from_slice(other);
             Ok(())
         }
@@ -68,10 +177,29 @@ mod tracked_vec {
             self.data.reserve_exact(additional);
             Ok(())
         }
+
+        fn grow(&self, bytes: usize) -> Result<(), FsError> {
+            match &self.limiter {
+                Some(limiter) if bytes > 0 => limiter.on_grow(bytes),
+                _ => Ok(()),
+            }
+        }
+
+        fn shrunk(&self, bytes: usize) {
+            if let Some(limiter) = self.limiter.as_ref().filter(|_| bytes > 0) {
+                limiter.on_shrink(bytes);
+            }
+        }
+    }
+
+    impl Drop for TrackedVec {
+        fn drop(&mut self) {
+            self.shrunk(self.data.len());
+        }
     }
 
     impl std::ops::Deref for TrackedVec {
-        type Target = Vec<u8>;
+        type Target = [u8];
 
         fn deref(&self) -> &Self::Target {
             &self.data
@@ -13,6 +13,7 @@ use crate::limiter::TrackedVec;
 use crate::{CopyOnWriteFile, FsError, Result, VirtualFile};
 use std::borrow::Cow;
 use std::cmp;
+use std::collections::BTreeMap;
 use std::convert::TryInto;
 use std::fmt;
 use std::io;
@@ -100,6 +101,63 @@ impl FileHandle {
             .map_err(|err| *err)?
             .as_mut())
     }
+
+    /// Copies a range of another file of an in-memory file system straight
+    /// between the pages of the files, returns `None` when the copy can't be
+    /// done that way
+    ///
+    /// A copy starting at the cursor advances it like a write, a copy
+    /// elsewhere leaves it in place
+    fn copy_range_in_memory(
+        &mut self,
+        src: &FileHandle,
+        src_offset: u64,
+        offset: u64,
+        len: u64,
+    ) -> Option<io::Result<u64>> {
+        if !self.writable || !src.readable {
+            return None;
+        }
+        let copied = if Arc::ptr_eq(&self.filesystem.inner, &src.filesystem.inner) {
+            let mut fs = self.filesystem.inner.write().ok()?;
+            if self.inode == src.inode {
+                let Some(Node::File(node)) = fs.storage.get_mut(self.inode) else {
+                    return None;
+                };
+                node.copy_range(None, src_offset, offset, len)
+            } else {
+                let Some((Node::File(node), Node::File(src_node))) =
+                    fs.storage.get2_mut(self.inode, src.inode)
+                else {
+                    return None;
+                };
+                node.copy_range(Some(&src_node.file), src_offset, offset, len)
+            }
+        } else {
+            // The file systems are locked in the same order by every copy,
+            // so that copies in both directions can't deadlock
+            let (mut fs, src_fs) =
+                if Arc::as_ptr(&self.filesystem.inner) < Arc::as_ptr(&src.filesystem.inner) {
+                    let fs = self.filesystem.inner.write().ok()?;
+                    (fs, src.filesystem.inner.read().ok()?)
+                } else {
+                    let src_fs = src.filesystem.inner.read().ok()?;
+                    (self.filesystem.inner.write().ok()?, src_fs)
+                };
+            let (Some(Node::File(node)), Some(Node::File(src_node))) = (
+                fs.storage.get_mut(self.inode),
+                src_fs.storage.get(src.inode),
+            ) else {
+                return None;
+            };
+            node.copy_range(Some(&src_node.file), src_offset, offset, len)
+        };
+        match &copied {
+            Ok(copied) if offset == self.cursor => self.cursor += copied,
+            _ => {}
+        }
+        Some(copied)
+    }
 }
 
 impl VirtualFile for FileHandle {
@@ -173,7 +231,7 @@ impl VirtualFile for FileHandle {
 
         let inode = fs.storage.get(self.inode);
         match inode {
-            Some(Node::File(node)) => node.file.len().try_into().unwrap_or(0),
+            Some(Node::File(node)) => node.file.len(),
             Some(Node::OffloadedFile(node)) => node.file.len(),
             Some(Node::ReadOnlyFile(node)) => node.file.len().try_into().unwrap_or(0),
             Some(Node::CustomFile(node)) => {
@@ -202,8 +260,7 @@ impl VirtualFile for FileHandle {
         let inode = fs.storage.get_mut(self.inode);
         match inode {
             Some(Node::File(FileNode { file, metadata, .. })) => {
-                file.buffer
-                    .resize(new_size.try_into().map_err(|_| FsError::UnknownError)?, 0)?;
+                file.set_len(new_size);
                 metadata.len = new_size;
             }
             Some(Node::OffloadedFile(OffloadedFileNode { file, metadata, .. })) => {
@@ -227,52 +284,62 @@ impl VirtualFile for FileHandle {
         Ok(())
     }
 
-    fn unlink(&mut self) -> Result<()> {
-        let filesystem = self.filesystem.clone();
-        let inode = self.inode;
-
-        let (inode_of_parent, position, inode_of_file) = {
-            // Read lock.
-            let fs = filesystem.inner.read().map_err(|_| FsError::Lock)?;
-
-            // The inode of the file.
-            let inode_of_file = inode;
-
-            // Find the position of the file in the parent, and the
-            // inode of the parent.
-            let (position, inode_of_parent) = fs
-                .storage
-                .iter()
-                .find_map(|(inode_of_parent, node)| match node {
-                    Node::Directory(DirectoryNode { children, .. }) => {
-                        children.iter().enumerate().find_map(|(nth, inode)| {
-                            if inode == &inode_of_file {
-                                Some((nth, inode_of_parent))
-                            } else {
-                                None
-                            }
-                        })
-                    }
+    fn allocate(&mut self, offset: u64, len: u64) -> Result<()> {
+        let mut fs = self.filesystem.inner.write().map_err(|_| FsError::Lock)?;
 
-                    _ => None,
-                })
-                .ok_or(FsError::BaseNotDirectory)?;
+        match fs.storage.get_mut(self.inode) {
+            // The space stays a hole until it's written to
+            Some(Node::File(FileNode { file, metadata, .. })) => {
+                file.allocate(offset, len)?;
+                metadata.len = file.len();
+                Ok(())
+            }
+            Some(Node::ReadOnlyFile { .. }) => Err(FsError::PermissionDenied),
+            Some(_) => {
+                let end = offset.checked_add(len).ok_or(FsError::InvalidInput)?;
+                drop(fs);
+                if end > self.size() {
+                    self.set_len(end)?;
+                }
+                Ok(())
+            }
+            None => Err(FsError::NotAFile),
+        }
+    }
 
-            (inode_of_parent, position, inode_of_file)
-        };
+    fn punch_hole(&mut self, offset: u64, len: u64) -> Result<()> {
+        let mut fs = self.filesystem.inner.write().map_err(|_| FsError::Lock)?;
 
-        {
-            // Write lock.
-            let mut fs = filesystem.inner.write().map_err(|_| FsError::Lock)?;
+        match fs.storage.get_mut(self.inode) {
+            Some(Node::File(FileNode { file, .. })) => {
+                file.punch_hole(offset, len);
+                Ok(())
+            }
+            Some(Node::ReadOnlyFile { .. }) => Err(FsError::PermissionDenied),
+            Some(_) => Err(FsError::Unsupported),
+            None => Err(FsError::NotAFile),
+        }
+    }
 
-            // Remove the file from the storage.
-            fs.storage.remove(inode_of_file);
+    fn unlink(&mut self) -> Result<()> {
+        // Write lock.
+        let mut fs = self.filesystem.inner.write().map_err(|_| FsError::Lock)?;
 
-            // Remove the child from the parent directory.
-            fs.remove_child_from_node(inode_of_parent, position)?;
-        }
+        // Find the position of the file in the parent, and the
+        // inode of the parent.
+        let (position, inode_of_parent) = fs.position_and_parent_of(self.inode)?;
 
-        Ok(())
+        // Remove the file from the parent directory, and from the
+        // storage unless it has other names.
+        fs.remove_file_entry(inode_of_parent, position)
+    }
+
+    fn file_id(&self) -> Option<crate::FileId> {
+        let fs = self.filesystem.inner.read().ok()?;
+        Some(crate::FileId {
+            dev: fs.dev,
+            ino: self.inode as u64,
+        })
     }
 
     fn get_special_fd(&self) -> Option<u32> {
@@ -308,6 +375,23 @@ impl VirtualFile for FileHandle {
         }
     }
 
+    fn copy_range_from<'a>(
+        &'a mut self,
+        src: &'a mut (dyn VirtualFile + Send + Sync),
+        src_offset: u64,
+        offset: u64,
+        len: u64,
+    ) -> BoxFuture<'a, std::io::Result<u64>> {
+        let copied = src
+            .upcast_any_ref()
+            .downcast_ref::<FileHandle>()
+            .and_then(|src| self.copy_range_in_memory(src, src_offset, offset, len));
+        match copied {
+            Some(copied) => Box::pin(async move { copied }),
+            None => Box::pin(crate::copy_range(self, src, src_offset, offset, len)),
+        }
+    }
+
     fn copy_reference(
         &mut self,
         src: Box<dyn VirtualFile + Send + Sync + 'static>,
@@ -360,17 +444,12 @@ impl VirtualFile for FileHandle {
 
         let inode = fs.storage.get_mut(self.inode);
         match inode {
-            Some(Node::File(node)) => {
-                let remaining = node.file.buffer.len() - (self.cursor as usize);
-                Poll::Ready(Ok(remaining))
-            }
+            Some(Node::File(node)) => Poll::Ready(Ok(remaining(node.file.len(), self.cursor))),
             Some(Node::OffloadedFile(node)) => {
-                let remaining = node.file.len() as usize - (self.cursor as usize);
-                Poll::Ready(Ok(remaining))
+                Poll::Ready(Ok(remaining(node.file.len(), self.cursor)))
             }
             Some(Node::ReadOnlyFile(node)) => {
-                let remaining = node.file.buffer.len() - (self.cursor as usize);
-                Poll::Ready(Ok(remaining))
+                Poll::Ready(Ok(remaining(node.file.buffer.len() as u64, self.cursor)))
             }
             Some(Node::CustomFile(node)) => {
                 let mut file = node.file.lock().unwrap();
@@ -883,7 +962,7 @@ impl AsyncWrite for FileHandle {
             match inode {
                 Some(Node::File(node)) => {
                     let bytes_written = node.file.write(buf, &mut cursor)?;
-                    node.metadata.len = node.file.len().try_into().unwrap();
+                    node.metadata.len = node.file.len();
                     bytes_written
                 }
                 Some(Node::OffloadedFile(node)) => {
@@ -963,7 +1042,7 @@ impl AsyncWrite for FileHandle {
                         .find(|b| !b.is_empty())
                         .map_or(&[][..], |b| &**b);
                     let bytes_written = node.file.write(buf, &mut cursor)?;
-                    node.metadata.len = node.file.buffer.len() as u64;
+                    node.metadata.len = node.file.len();
                     Poll::Ready(Ok(bytes_written))
                 }
                 Some(Node::OffloadedFile(node)) => {
@@ -1115,8 +1194,9 @@ impl AsyncWrite for FileHandle {
 mod test_read_write_seek {
     use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 
+    use super::PAGE_SIZE;
     use crate::{mem_fs::*, FileSystem as FS};
-    use std::io;
+    use std::{io, pin::Pin};
 
     macro_rules! path {
         ($path:expr) => {
@@ -1394,6 +1474,204 @@ mod test_read_write_seek {
             "failing to read an exact buffer",
         );
     }
+
+    #[tokio::test]
+    async fn test_nothing_to_read_past_the_end() {
+        let fs = FileSystem::default();
+
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo.txt"))
+            .expect("failed to create a new file");
+        file.write_all(b"foobarbaz").await.unwrap();
+
+        file.seek(io::SeekFrom::Start(100)).await.unwrap();
+        let ready =
+            futures::future::poll_fn(|cx| Pin::new(file.as_mut()).poll_read_ready(cx)).await;
+        assert_eq!(ready.unwrap(), 0);
+
+        file.seek(io::SeekFrom::Start(3)).await.unwrap();
+        let ready =
+            futures::future::poll_fn(|cx| Pin::new(file.as_mut()).poll_read_ready(cx)).await;
+        assert_eq!(ready.unwrap(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_copying_ranges() {
+        let fs = FileSystem::default();
+        let other_fs = FileSystem::default();
+
+        let mut src = fs
+            .new_open_options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path!("/src.txt"))
+            .expect("failed to create a new file");
+        src.write_all(b"foobarbaz").await.unwrap();
+        let mut dst = fs
+            .new_open_options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path!("/dst.txt"))
+            .expect("failed to create a new file");
+        let mut other = other_fs
+            .new_open_options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path!("/other.txt"))
+            .expect("failed to create a new file");
+
+        // Between files of the same file system, stopping at the end of the source
+        assert_eq!(
+            dst.copy_range_from(src.as_mut(), 3, 0, 100).await.unwrap(),
+            6
+        );
+        // Between file systems
+        assert_eq!(
+            other.copy_range_from(src.as_mut(), 0, 0, 3).await.unwrap(),
+            3
+        );
+        // Within a file
+        let mut same = fs
+            .new_open_options()
+            .read(true)
+            .open(path!("/src.txt"))
+            .expect("failed to open a file");
+        assert_eq!(
+            src.copy_range_from(same.as_mut(), 6, 9, 3).await.unwrap(),
+            3
+        );
+
+        for (file, expected) in [
+            (&mut src, "foobarbazbaz"),
+            (&mut dst, "barbaz"),
+            (&mut other, "foo"),
+        ] {
+            let mut string = String::new();
+            file.seek(io::SeekFrom::Start(0)).await.unwrap();
+            file.read_to_string(&mut string).await.unwrap();
+            assert_eq!(string, expected);
+            assert_eq!(file.size(), expected.len() as u64);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_copying_ranges_at_an_offset_keeps_the_cursor() {
+        let fs = FileSystem::default();
+
+        let mut src = fs
+            .new_open_options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path!("/src.txt"))
+            .expect("failed to create a new file");
+        src.write_all(b"foobarbaz").await.unwrap();
+        let mut dst = fs
+            .new_open_options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path!("/dst.txt"))
+            .expect("failed to create a new file");
+        dst.write_all(b"0123").await.unwrap();
+
+        // Copying elsewhere than at the cursor doesn't move it
+        assert_eq!(dst.copy_range_from(src.as_mut(), 0, 8, 3).await.unwrap(), 3);
+        assert_eq!(dst.seek(io::SeekFrom::Current(0)).await.unwrap(), 4);
+
+        // Copying at the cursor advances it
+        assert_eq!(dst.copy_range_from(src.as_mut(), 3, 4, 3).await.unwrap(), 3);
+        assert_eq!(dst.seek(io::SeekFrom::Current(0)).await.unwrap(), 7);
+        dst.write_all(b"!").await.unwrap();
+
+        let mut string = String::new();
+        dst.seek(io::SeekFrom::Start(0)).await.unwrap();
+        dst.read_to_string(&mut string).await.unwrap();
+        assert_eq!(string, "0123bar!foo");
+    }
+
+    #[test]
+    pub fn holes() {
+        let mut file = File::new(None);
+
+        // Growing the file leaves a hole
+        file.set_len(3 * PAGE_SIZE);
+        assert!(file.pages.is_empty());
+        file.write(b"foo", &mut (PAGE_SIZE + 1)).unwrap();
+        assert_eq!(file.pages.len(), 1);
+
+        let mut buf = vec![1; 8];
+        let mut cursor = PAGE_SIZE - 2;
+        assert_eq!(file.read(&mut buf, &mut cursor).unwrap(), 8);
+        assert_eq!(buf, b"\0\0\0foo\0\0");
+
+        // Preallocated space is a hole too, the file never shrinks
+        file.allocate(0, PAGE_SIZE).unwrap();
+        assert_eq!(file.len(), 3 * PAGE_SIZE);
+        file.allocate(3 * PAGE_SIZE, 10).unwrap();
+        assert_eq!(file.len(), 3 * PAGE_SIZE + 10);
+        assert!(file.allocate(u64::MAX, 1).is_err());
+
+        // Punching a hole zeroes the range without changing the size
+        file.punch_hole(PAGE_SIZE + 2, 1);
+        let mut cursor = PAGE_SIZE + 1;
+        assert_eq!(file.read(&mut buf[..3], &mut cursor).unwrap(), 3);
+        assert_eq!(&buf[..3], b"f\0o");
+        file.punch_hole(PAGE_SIZE, PAGE_SIZE);
+        assert!(file.pages.is_empty());
+        assert_eq!(file.len(), 3 * PAGE_SIZE + 10);
+
+        // Holes are copied as holes
+        file.write(b"bar", &mut (2 * PAGE_SIZE)).unwrap();
+        let mut copy = File::new(None);
+        assert_eq!(copy.copy_from(&file, 0, 0, file.len()).unwrap(), file.len());
+        assert_eq!(copy.len(), file.len());
+        assert_eq!(copy.pages.keys().collect::<Vec<_>>(), [&2]);
+    }
+
+    #[tokio::test]
+    async fn test_allocating_and_punching_holes() {
+        let fs = FileSystem::default();
+
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo.txt"))
+            .expect("failed to create a new file");
+        file.write_all(b"foobarbaz").await.unwrap();
+
+        file.allocate(4, 8).unwrap();
+        assert_eq!(file.size(), 12);
+        assert!(
+            matches!(fs.metadata(path!("/foo.txt")), Ok(Metadata { len: 12, .. })),
+            "checking the `metadata.len` is 12",
+        );
+        file.allocate(0, 4).unwrap();
+        assert_eq!(file.size(), 12);
+
+        file.punch_hole(3, 3).unwrap();
+        assert_eq!(file.size(), 12);
+
+        let mut buffer = Vec::new();
+        file.seek(io::SeekFrom::Start(0)).await.unwrap();
+        file.read_to_end(&mut buffer).await.unwrap();
+        assert_eq!(buffer, b"foo\0\0\0baz\0\0\0");
+    }
+}
+
+/// How much of a file of `len` bytes is left to read from `cursor`, which
+/// may have been moved past its end.
+fn remaining(len: u64, cursor: u64) -> usize {
+    usize::try_from(len.saturating_sub(cursor)).unwrap_or(usize::MAX)
 }
 
 impl fmt::Debug for FileHandle {
@@ -1407,38 +1685,167 @@ impl fmt::Debug for FileHandle {
     }
 }
 
-/// The real file! It is simply a buffer of bytes with a cursor that
-/// represents a read/write position in the buffer.
+/// Size of the pages the data of a [`File`] is stored in.
+const PAGE_SIZE: u64 = 64 * 1024;
+
+/// The real file! Its data is stored sparsely, in pages of [`PAGE_SIZE`]
+/// bytes which are only allocated once written to. The missing pages, and
+/// the ends of the pages which were only partly written, are holes which
+/// read as zeros, so that preallocated and truncated files take no memory.
 #[derive(Debug)]
 pub(super) struct File {
-    buffer: TrackedVec,
+    pages: BTreeMap<u64, TrackedVec>,
+    len: u64,
+    limiter: Option<crate::limiter::DynFsMemoryLimiter>,
 }
 
 impl File {
     pub(super) fn new(limiter: Option<crate::limiter::DynFsMemoryLimiter>) -> Self {
         Self {
-            buffer: TrackedVec::new(limiter),
+            pages: BTreeMap::new(),
+            len: 0,
+            limiter,
         }
     }
 
     pub(super) fn truncate(&mut self) {
-        self.buffer.clear();
+        self.pages.clear();
+        self.len = 0;
     }
 
-    pub(super) fn len(&self) -> usize {
-        self.buffer.len()
+    pub(super) fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Number of bytes the file takes, without its holes
+    pub(super) fn allocated(&self) -> u64 {
+        self.pages.values().map(|page| page.len() as u64).sum()
+    }
+
+    /// Changes the size of the file, the file grows with a hole
+    pub(super) fn set_len(&mut self, len: u64) {
+        if len < self.len {
+            self.punch_hole(len, self.len - len);
+        }
+        self.len = len;
+    }
+
+    /// Makes sure the file is at least `offset + len` bytes long, the space
+    /// is a hole until it's written to
+    pub(super) fn allocate(&mut self, offset: u64, len: u64) -> Result<()> {
+        let end = offset.checked_add(len).ok_or(FsError::InvalidInput)?;
+        self.len = self.len.max(end);
+        Ok(())
+    }
+
+    /// Turns a range of the file into a hole, its size doesn't change
+    pub(super) fn punch_hole(&mut self, offset: u64, len: u64) {
+        let end = offset.saturating_add(len).min(self.len);
+        if offset >= end {
+            return;
+        }
+        let pages: Vec<u64> = self
+            .pages
+            .range(offset / PAGE_SIZE..=(end - 1) / PAGE_SIZE)
+            .map(|(index, _)| *index)
+            .collect();
+        for index in pages {
+            let page_start = index * PAGE_SIZE;
+            let page = self.pages.get_mut(&index).unwrap();
+            let start = offset.saturating_sub(page_start) as usize;
+            let stop = (end - page_start).min(PAGE_SIZE) as usize;
+            if start >= page.len() {
+                continue;
+            }
+            if stop < page.len() {
+                page[start..stop].fill(0);
+            } else if start == 0 {
+                self.pages.remove(&index);
+            } else {
+                page.truncate(start);
+            }
+        }
+    }
+
+    /// Copies a range of another file into this file at `offset`, the holes
+    /// of the range stay holes
+    pub(super) fn copy_from(
+        &mut self,
+        src: &File,
+        src_offset: u64,
+        offset: u64,
+        len: u64,
+    ) -> io::Result<u64> {
+        let len = len.min(src.len.saturating_sub(src_offset));
+        let mut copied = 0;
+        while copied < len {
+            let from = src_offset + copied;
+            let in_page = (from % PAGE_SIZE) as usize;
+            let chunk = (len - copied).min(PAGE_SIZE - in_page as u64);
+            let mut cursor = offset + copied;
+            let data = match src.pages.get(&(from / PAGE_SIZE)) {
+                Some(page) if in_page < page.len() => {
+                    &page[in_page..page.len().min(in_page + chunk as usize)]
+                }
+                _ => &[][..],
+            };
+            self.write(data, &mut cursor)?;
+            self.write_hole(cursor, chunk - data.len() as u64);
+            copied += chunk;
+        }
+        Ok(copied)
+    }
+
+    /// Copies a range of the file to another place in the file
+    pub(super) fn copy_within(
+        &mut self,
+        src_offset: u64,
+        offset: u64,
+        len: u64,
+    ) -> io::Result<u64> {
+        let mut buf = vec![0u8; len.min(PAGE_SIZE) as usize];
+        let mut copied = 0;
+        while copied < len {
+            let chunk = (len - copied).min(buf.len() as u64) as usize;
+            let read = self.read(&mut buf[..chunk], &mut (src_offset + copied))?;
+            if read == 0 {
+                break;
+            }
+            self.write(&buf[..read], &mut (offset + copied))?;
+            copied += read as u64;
+        }
+        Ok(copied)
+    }
+
+    /// Writes `len` zeros at `offset` as a hole
+    fn write_hole(&mut self, offset: u64, len: u64) {
+        self.punch_hole(offset, len);
+        self.len = self.len.max(offset + len);
     }
 }
 
 impl File {
     pub fn read(&self, buf: &mut [u8], cursor: &mut u64) -> io::Result<usize> {
-        let cur_pos = *cursor as usize;
-        let max_to_read = cmp::min(self.buffer.len() - cur_pos, buf.len());
-        let data_to_copy = &self.buffer[cur_pos..][..max_to_read];
-
-        // SAFETY: `buf[..max_to_read]` and `data_to_copy` have the same size, due to
-        // how `max_to_read` is computed.
-        buf[..max_to_read].copy_from_slice(data_to_copy);
+        let position = *cursor;
+        let max_to_read = cmp::min(self.len.saturating_sub(position), buf.len() as u64) as usize;
+
+        let mut read = 0;
+        while read < max_to_read {
+            let offset = position + read as u64;
+            let in_page = (offset % PAGE_SIZE) as usize;
+            let chunk = cmp::min(max_to_read - read, PAGE_SIZE as usize - in_page);
+            let buf = &mut buf[read..read + chunk];
+            match self.pages.get(&(offset / PAGE_SIZE)) {
+                Some(page) if in_page < page.len() => {
+                    let data = &page[in_page..cmp::min(page.len(), in_page + chunk)];
+                    buf[..data.len()].copy_from_slice(data);
+                    buf[data.len()..].fill(0);
+                }
+                // Holes read as zeros
+                _ => buf.fill(0),
+            }
+            read += chunk;
+        }
 
         *cursor += max_to_read as u64;
 
@@ -1455,9 +1862,9 @@ impl File {
             // Calculate from the beginning, so `0 + offset`.
             io::SeekFrom::Start(offset) => offset.try_into().map_err(to_err)?,
 
-            // Calculate from the end, so `buffer.len() + offset`.
+            // Calculate from the end, so `len + offset`.
             io::SeekFrom::End(offset) => {
-                TryInto::<i64>::try_into(self.buffer.len()).map_err(to_err)? + offset
+                TryInto::<i64>::try_into(self.len).map_err(to_err)? + offset
             }
 
             // Calculate from the current cursor, so `cursor + offset`.
@@ -1477,7 +1884,7 @@ impl File {
         // In this implementation, it's an error to seek beyond the
         // end of the buffer.
         let next_cursor = next_cursor.try_into().map_err(to_err)?;
-        *cursor = cmp::min(self.buffer.len() as u64, next_cursor);
+        *cursor = cmp::min(self.len, next_cursor);
 
         let cursor = *cursor;
         Ok(cursor)
@@ -1486,19 +1893,29 @@ impl File {
 
 impl File {
     pub fn write(&mut self, buf: &[u8], cursor: &mut u64) -> io::Result<usize> {
-        let position = *cursor as usize;
-
-        if position + buf.len() > self.buffer.len() {
-            // Writing past the end of the current buffer, must reallocate
-            let len_after_end = (position + buf.len()) - self.buffer.len();
-            let let_to_end = buf.len() - len_after_end;
-            self.buffer[position..position + let_to_end].copy_from_slice(&buf[0..let_to_end]);
-            self.buffer.extend_from_slice(&buf[let_to_end..buf.len()])?;
-        } else {
-            self.buffer[position..position + buf.len()].copy_from_slice(buf);
+        let position = *cursor;
+        let end = position
+            .checked_add(buf.len() as u64)
+            .ok_or(io::ErrorKind::InvalidInput)?;
+
+        let mut written = 0;
+        while written < buf.len() {
+            let offset = position + written as u64;
+            let in_page = (offset % PAGE_SIZE) as usize;
+            let chunk = cmp::min(buf.len() - written, PAGE_SIZE as usize - in_page);
+            let page = self
+                .pages
+                .entry(offset / PAGE_SIZE)
+                .or_insert_with(|| TrackedVec::new(self.limiter.clone()));
+            if page.len() < in_page + chunk {
+                page.resize(in_page + chunk, 0)?;
+            }
+            page[in_page..in_page + chunk].copy_from_slice(&buf[written..written + chunk]);
+            written += chunk;
         }
+        self.len = self.len.max(end);
 
-        *cursor += buf.len() as u64;
+        *cursor = end;
 
         Ok(buf.len())
     }
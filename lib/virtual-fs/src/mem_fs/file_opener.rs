@@ -398,7 +398,7 @@ impl crate::FileOpener for FileSystem {
 
                         // Move the cursor to the end if needed.
                         if append {
-                            cursor = file.len() as u64;
+                            cursor = file.len();
                         }
                     }
 
@@ -485,6 +485,7 @@ impl crate::FileOpener for FileSystem {
             None if (create_new || create) && (create_new || write || append) => {
                 // Write lock.
                 let mut fs = self.inner.write().map_err(|_| FsError::Lock)?;
+                fs.check_inode_quota()?;
 
                 let metadata = {
                     let time = time();
@@ -3,16 +3,26 @@
 use self::offloaded_file::OffloadBackingStore;
 
 use super::*;
-use crate::{DirEntry, FileType, FsError, Metadata, OpenOptions, ReadDir, Result};
+use crate::{
+    DirEntry, FileType, FsError, FsStats, Metadata, OpenOptions, Permissions, ReadDir, Result,
+};
 use futures::future::{BoxFuture, Either};
 use slab::Slab;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::convert::identity;
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
 use std::fmt;
 use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, RwLock};
 
+/// Size of the blocks reported by [`crate::FileSystem::statfs`].
+const BLOCK_SIZE: u64 = 4096;
+
+/// Capacity reported by the file systems whose capacity wasn't set, see
+/// [`FileSystem::set_capacity`].
+const DEFAULT_CAPACITY: u64 = 4 * 1024 * 1024 * 1024;
+
 /// The in-memory file system!
 ///
 /// This `FileSystem` type can be cloned, it's a light copy of the
@@ -27,6 +37,50 @@ impl FileSystem {
         self.inner.write().unwrap().limiter = Some(limiter);
     }
 
+    /// Gets the memory limiter set by [`Self::set_memory_limiter`] or
+    /// [`Self::set_quota`], so that other file systems can share it.
+    pub fn memory_limiter(&self) -> Option<crate::limiter::DynFsMemoryLimiter> {
+        self.inner.read().unwrap().limiter.clone()
+    }
+
+    /// Sets the capacity in bytes reported by [`crate::FileSystem::statfs`].
+    /// It isn't enforced, the memory used by the file system is limited by
+    /// [`Self::set_memory_limiter`].
+    pub fn set_capacity(&self, capacity: u64) {
+        self.inner.write().unwrap().capacity = capacity;
+    }
+
+    /// Limits the size of the file system, writing to files past the byte
+    /// quota or creating files and directories past the inode quota fails
+    /// with [`FsError::StorageFull`]. The quota is what
+    /// [`crate::FileSystem::statfs`] reports.
+    ///
+    /// The byte quota is chained to the memory limiter set beforehand, if
+    /// any, and only counts the files created afterwards.
+    pub fn set_quota(&self, quota: crate::limiter::FsQuota) {
+        let mut fs = self.inner.write().unwrap();
+        if let Some(bytes) = quota.bytes {
+            let limiter = match fs.limiter.take() {
+                Some(parent) => crate::limiter::QuotaLimiter::with_parent(bytes, parent),
+                None => crate::limiter::QuotaLimiter::new(bytes),
+            };
+            fs.limiter = Some(Arc::new(limiter));
+            fs.capacity = bytes;
+        }
+        fs.inode_quota = quota.inodes;
+    }
+
+    /// Makes the lookups case-insensitive, so that `/Foo/BAR.txt` opens
+    /// `/foo/bar.txt`. Names are still stored as they were created, and
+    /// creating an entry whose name only differs by its case from an
+    /// existing one fails with [`FsError::AlreadyExists`].
+    ///
+    /// Names are compared after Unicode lowercasing, names which aren't
+    /// valid UTF-8 are compared byte by byte.
+    pub fn set_case_insensitive(&self, case_insensitive: bool) {
+        self.inner.write().unwrap().case_insensitive = case_insensitive;
+    }
+
     pub fn new_open_options_ext(&self) -> &FileSystem {
         self
     }
@@ -295,7 +349,7 @@ impl crate::FileSystem for FileSystem {
 
                         entry_path
                     },
-                    metadata: Ok(node.metadata().clone()),
+                    metadata: Ok(guard.resolve_hard_link(node).metadata().clone()),
                 })
                 .collect(),
 
@@ -351,6 +405,7 @@ impl crate::FileSystem for FileSystem {
         {
             // Write lock.
             let mut fs = self.inner.write().map_err(|_| FsError::Lock)?;
+            fs.check_inode_quota()?;
 
             // Creating the directory in the storage.
             let inode_of_directory = fs.storage.vacant_entry().key();
@@ -437,7 +492,7 @@ impl crate::FileSystem for FileSystem {
             let mut fs = self.inner.write().map_err(|_| FsError::Lock)?;
 
             // Remove the directory from the storage.
-            fs.storage.remove(inode_of_directory);
+            fs.remove_node(inode_of_directory);
 
             // Remove the child from the parent directory.
             fs.remove_child_from_node(inode_of_parent, position)?;
@@ -494,7 +549,10 @@ impl crate::FileSystem for FileSystem {
 
             match (inode_of_from_parent, inode_of_to_parent) {
                 (Either::Left(inode_of_from_parent), Either::Left(inode_of_to_parent)) => {
-                    let fs = self.inner.read().map_err(|_| FsError::Lock)?;
+                    // Write lock, held from the lookups to the update so
+                    // that concurrent readers either see the old entries
+                    // or the new ones.
+                    let mut fs = self.inner.write().map_err(|_| FsError::Lock)?;
 
                     // Find the inode of the dest file if it exists
                     let maybe_position_and_inode_of_file = fs
@@ -526,24 +584,21 @@ impl crate::FileSystem for FileSystem {
                         }
                     };
 
-                    drop(fs);
-
                     {
-                        // Write lock.
-                        let mut fs = self.inner.write().map_err(|_| FsError::Lock)?;
-
                         if let Some((position, inode_of_file)) = inode_dest {
-                            // Remove the file from the storage.
-                            match inode_of_file {
-                                InodeResolution::Found(inode_of_file) => {
-                                    fs.storage.remove(inode_of_file);
-                                }
-                                InodeResolution::Redirect(..) => {
-                                    return Err(FsError::InvalidInput);
-                                }
+                            if let InodeResolution::Redirect(..) = inode_of_file {
+                                return Err(FsError::InvalidInput);
                             }
 
-                            fs.remove_child_from_node(inode_of_to_parent, position)?;
+                            // A case-insensitive file system finds the file
+                            // itself when only the case of its name changes.
+                            let is_same_entry = inode_of_from_parent == inode_of_to_parent
+                                && position == position_of_from;
+                            if !is_same_entry {
+                                // Remove the file from the parent directory, and from
+                                // the storage unless it has other names.
+                                fs.remove_file_entry(inode_of_to_parent, position)?;
+                            }
                         }
 
                         // Update the file name, and update the modified time.
@@ -578,10 +633,12 @@ impl crate::FileSystem for FileSystem {
 
                         same_fs.rename(&from_path, &to_path).await
                     } else {
-                        Err(FsError::InvalidInput)
+                        Err(FsError::CrossDevice)
                     }
                 }
-                _ => Err(FsError::InvalidInput),
+                // One of the paths is in a mounted file system and the other
+                // isn't.
+                _ => Err(FsError::CrossDevice),
             }
         })
     }
@@ -656,27 +713,146 @@ impl crate::FileSystem for FileSystem {
             }
         };
 
-        let inode_of_file = match inode_of_file {
-            InodeResolution::Found(a) => a,
-            InodeResolution::Redirect(fs, path) => {
-                return fs.remove_file(path.as_path());
-            }
-        };
+        if let InodeResolution::Redirect(fs, path) = inode_of_file {
+            return fs.remove_file(path.as_path());
+        }
 
         {
             // Write lock.
             let mut fs = self.inner.write().map_err(|_| FsError::Lock)?;
 
-            // Remove the file from the storage.
-            fs.storage.remove(inode_of_file);
+            // Remove the file from the parent directory, and from the
+            // storage unless it has other names.
+            fs.remove_file_entry(inode_of_parent, position)?;
+        }
 
-            // Remove the child from the parent directory.
-            fs.remove_child_from_node(inode_of_parent, position)?;
+        Ok(())
+    }
+
+    fn hard_link(&self, from: &Path, to: &Path) -> Result<()> {
+        // Write lock.
+        let mut fs = self.inner.write().map_err(|_| FsError::Lock)?;
+
+        // Canonicalize the paths.
+        let (_, inode_of_from) = fs.canonicalize(from)?;
+        let to = fs.canonicalize_without_inode(to)?;
+
+        // Check the new path has a parent.
+        let parent_of_to = to.parent().ok_or(FsError::BaseNotDirectory)?;
+
+        // Check the new name.
+        let name_of_to = to.file_name().ok_or(FsError::InvalidInput)?.to_os_string();
+
+        // Find the inodes of the file and of the new parent.
+        let (inode_of_from, inode_of_to_parent) =
+            match (inode_of_from, fs.inode_of_parent(parent_of_to)?) {
+                (InodeResolution::Found(a), InodeResolution::Found(b)) => (a, b),
+                (
+                    InodeResolution::Redirect(from_fs, from_path),
+                    InodeResolution::Redirect(to_fs, mut to_path),
+                ) if Arc::ptr_eq(&from_fs, &to_fs) => {
+                    drop(fs);
+                    to_path.push(name_of_to);
+                    return from_fs.hard_link(&from_path, &to_path);
+                }
+                _ => return Err(FsError::InvalidInput),
+            };
+
+        // Only files can have several names.
+        let metadata = match fs.storage.get(inode_of_from) {
+            Some(Node::Directory(_) | Node::ArcDirectory(_)) => {
+                return Err(FsError::PermissionDenied)
+            }
+            Some(node) => node.metadata().clone(),
+            None => return Err(FsError::UnknownError),
+        };
+
+        if fs
+            .as_parent_get_position_and_inode(inode_of_to_parent, &name_of_to)?
+            .is_some()
+        {
+            return Err(FsError::AlreadyExists);
         }
 
+        // Every name of a file takes an inode.
+        fs.check_inode_quota()?;
+
+        // Creating the link in the storage.
+        let inode_of_link = fs.storage.vacant_entry().key();
+        let real_inode_of_link = fs.storage.insert(Node::HardLink(HardLinkNode {
+            inode: inode_of_link,
+            name: name_of_to,
+            target: inode_of_from,
+            metadata,
+        }));
+
+        assert_eq!(
+            inode_of_link, real_inode_of_link,
+            "new link inode should have been correctly calculated",
+        );
+
+        // Adding the new link to its parent.
+        fs.add_child_to_node(inode_of_to_parent, inode_of_link)?;
+
         Ok(())
     }
 
+    fn statfs(&self, path: &Path) -> Result<FsStats> {
+        // Read lock.
+        let guard = self.inner.read().map_err(|_| FsError::Lock)?;
+        let (fs, path) = match guard.inode_of(path)? {
+            InodeResolution::Found(inode) => match guard.storage.get(inode) {
+                // The mount points report the mounted file system
+                Some(
+                    Node::ArcDirectory(ArcDirectoryNode { fs, path, .. })
+                    | Node::ArcFile(ArcFileNode { fs, path, .. }),
+                ) => (fs.clone(), path.clone()),
+                Some(_) => return Ok(guard.stats()),
+                None => return Err(FsError::UnknownError),
+            },
+            InodeResolution::Redirect(fs, path) => (fs, path),
+        };
+        drop(guard);
+        fs.statfs(path.as_path())
+    }
+
+    fn permissions(&self, path: &Path) -> Result<Permissions> {
+        // Read lock.
+        let guard = self.inner.read().map_err(|_| FsError::Lock)?;
+        match guard.inode_of(path)? {
+            InodeResolution::Found(inode) => {
+                let node = guard.storage.get(inode).ok_or(FsError::UnknownError)?;
+                let node = guard.resolve_hard_link(node);
+                Ok(guard
+                    .permissions
+                    .get(&node.inode())
+                    .copied()
+                    .unwrap_or_else(|| Permissions::default_for(&node.metadata().ft)))
+            }
+            InodeResolution::Redirect(fs, path) => {
+                drop(guard);
+                fs.permissions(path.as_path())
+            }
+        }
+    }
+
+    fn set_permissions(&self, path: &Path, permissions: Permissions) -> Result<()> {
+        // Write lock.
+        let mut fs = self.inner.write().map_err(|_| FsError::Lock)?;
+        match fs.inode_of(path)? {
+            InodeResolution::Found(inode) => {
+                let node = fs.storage.get(inode).ok_or(FsError::UnknownError)?;
+                let inode = fs.resolve_hard_link(node).inode();
+                fs.permissions.insert(inode, permissions);
+                Ok(())
+            }
+            InodeResolution::Redirect(other, path) => {
+                drop(fs);
+                other.set_permissions(path.as_path(), permissions)
+            }
+        }
+    }
+
     fn new_open_options(&self) -> OpenOptions {
         OpenOptions::new(self)
     }
@@ -690,6 +866,53 @@ impl crate::FileSystem for FileSystem {
         let fs: Arc<dyn crate::FileSystem + Send + Sync> = Arc::new(fs);
         self.mount(path.to_owned(), &fs, PathBuf::from("/"))
     }
+
+    fn unmount(&self, path: &Path) -> Result<()> {
+        // Write lock.
+        let mut fs = self.inner.write().map_err(|_| FsError::Lock)?;
+
+        // Canonicalize the path.
+        let path = fs.canonicalize_without_inode(path)?;
+
+        // Check the path has a parent.
+        let parent_of_path = path.parent().ok_or(FsError::BaseNotDirectory)?;
+
+        // Check the name of the mount point.
+        let name_of_directory = path.file_name().ok_or(FsError::InvalidInput)?;
+
+        // Find the parent inode, the mounts within a mount belong to the
+        // mounted file system.
+        let inode_of_parent = match fs.inode_of_parent(parent_of_path)? {
+            InodeResolution::Found(a) => a,
+            InodeResolution::Redirect(other, mut path) => {
+                drop(fs);
+                path.push(name_of_directory);
+                return other.unmount(path.as_path());
+            }
+        };
+
+        // Find the mount point within its parent.
+        let children = match fs.storage.get(inode_of_parent) {
+            Some(Node::Directory(DirectoryNode { children, .. })) => children,
+            _ => return Err(FsError::BaseNotDirectory),
+        };
+        let (position, inode_of_directory) = children
+            .iter()
+            .enumerate()
+            .find_map(|(nth, inode)| match fs.storage.get(*inode) {
+                Some(node) if fs.names_match(node.name(), name_of_directory) => Some((nth, node)),
+                _ => None,
+            })
+            .map(|(nth, node)| match node {
+                Node::ArcDirectory(ArcDirectoryNode { inode, .. }) => Ok((nth, *inode)),
+                _ => Err(FsError::InvalidInput),
+            })
+            .ok_or(FsError::EntryNotFound)??;
+
+        // Remove the mount point from the storage and from its parent.
+        fs.remove_node(inode_of_directory);
+        fs.remove_child_from_node(inode_of_parent, position)
+    }
 }
 
 impl fmt::Debug for FileSystem {
@@ -700,12 +923,24 @@ impl fmt::Debug for FileSystem {
     }
 }
 
+/// The device number of the next file system, see [`crate::FileId`]
+static NEXT_DEV: AtomicU64 = AtomicU64::new(1);
+
 /// The core of the file system. It contains a collection of `Node`s,
 /// indexed by their respective `Inode` in a slab.
 pub(super) struct FileSystemInner {
+    /// Tells the files of the file system apart from those of the others
+    pub(super) dev: u64,
     pub(super) storage: Slab<Node>,
     pub(super) backing_offload: Option<OffloadBackingStore>,
     pub(super) limiter: Option<crate::limiter::DynFsMemoryLimiter>,
+    pub(super) capacity: u64,
+    /// Maximum number of inodes, see [`FileSystem::set_quota`]
+    pub(super) inode_quota: Option<u64>,
+    /// See [`FileSystem::set_case_insensitive`]
+    pub(super) case_insensitive: bool,
+    /// The permissions which were changed from the defaults, by inode
+    pub(super) permissions: HashMap<Inode, Permissions>,
 }
 
 #[derive(Debug)]
@@ -727,6 +962,52 @@ impl InodeResolution {
 }
 
 impl FileSystemInner {
+    /// The capacity and usage of the file system, the holes of the files
+    /// and the mounted file systems take no space.
+    pub(super) fn stats(&self) -> FsStats {
+        let used: u64 = self
+            .storage
+            .iter()
+            .map(|(_, node)| match node {
+                Node::File(FileNode { file, .. }) => file.allocated(),
+                Node::OffloadedFile(OffloadedFileNode { file, .. }) => file.len(),
+                Node::ReadOnlyFile(ReadOnlyFileNode { metadata, .. })
+                | Node::CustomFile(CustomFileNode { metadata, .. }) => metadata.len,
+                _ => 0,
+            })
+            .sum();
+        let blocks = self.capacity / BLOCK_SIZE;
+        let files = self.inode_quota.unwrap_or(blocks);
+        FsStats {
+            block_size: BLOCK_SIZE,
+            blocks,
+            blocks_free: blocks.saturating_sub(used.div_ceil(BLOCK_SIZE)),
+            // An inode per block, like tmpfs(5) gives an inode per page
+            files,
+            files_free: files.saturating_sub(self.storage.len() as u64),
+        }
+    }
+
+    /// Fails if creating another inode would exceed the inode quota.
+    pub(super) fn check_inode_quota(&self) -> Result<()> {
+        match self.inode_quota {
+            Some(quota) if self.storage.len() as u64 >= quota => Err(FsError::StorageFull),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the name of an entry matches a name being looked up.
+    pub(super) fn names_match(&self, name: &OsStr, other: &OsStr) -> bool {
+        if !self.case_insensitive {
+            return name == other;
+        }
+        match (name.to_str(), other.to_str()) {
+            (Some(name), Some(other)) => fold_case(name).eq(fold_case(other)),
+            // Names which aren't valid UTF-8 have no case to fold
+            _ => name == other,
+        }
+    }
+
     /// Get the inode associated to a path if it exists.
     pub(super) fn inode_of(&self, path: &Path) -> Result<InodeResolution> {
         // SAFETY: The root node always exists, so it's safe to unwrap here.
@@ -743,7 +1024,8 @@ impl FileSystemInner {
                 Node::Directory(DirectoryNode { children, .. }) => children
                     .iter()
                     .filter_map(|inode| self.storage.get(*inode))
-                    .find(|node| node.name() == component.as_os_str())
+                    .find(|node| self.names_match(node.name(), component.as_os_str()))
+                    .map(|node| self.resolve_hard_link(node))
                     .ok_or(FsError::EntryNotFound)?,
                 Node::ArcDirectory(ArcDirectoryNode {
                     fs, path: fs_path, ..
@@ -801,7 +1083,7 @@ impl FileSystemInner {
                         name,
                         children,
                         ..
-                    }) if name.as_os_str() == name_of_directory => {
+                    }) if self.names_match(name, name_of_directory) => {
                         if directory_must_be_empty.no() || children.is_empty() {
                             Some(Ok((nth, InodeResolution::Found(*inode))))
                         } else {
@@ -809,7 +1091,7 @@ impl FileSystemInner {
                         }
                     }
                     Node::ArcDirectory(ArcDirectoryNode { name, fs, path, .. })
-                        if name.as_os_str() == name_of_directory =>
+                        if self.names_match(name, name_of_directory) =>
                     {
                         Some(Ok((0, InodeResolution::Redirect(fs.clone(), path.clone()))))
                     }
@@ -848,8 +1130,11 @@ impl FileSystemInner {
                     | Node::ReadOnlyFile(ReadOnlyFileNode { inode, name, .. })
                     | Node::CustomFile(CustomFileNode { inode, name, .. })
                     | Node::ArcFile(ArcFileNode { inode, name, .. })
-                        if name.as_os_str() == name_of_file =>
-                    {
+                    | Node::HardLink(HardLinkNode {
+                        target: inode,
+                        name,
+                        ..
+                    }) if self.names_match(name, name_of_file) => {
                         Some(Some((nth, InodeResolution::Found(*inode))))
                     }
                     _ => None,
@@ -889,7 +1174,8 @@ impl FileSystemInner {
                     | Node::ReadOnlyFile(ReadOnlyFileNode { inode, name, .. })
                     | Node::CustomFile(CustomFileNode { inode, name, .. })
                     | Node::ArcFile(ArcFileNode { inode, name, .. })
-                        if name.as_os_str() == name_of =>
+                    | Node::HardLink(HardLinkNode { inode, name, .. })
+                        if self.names_match(name, name_of) =>
                     {
                         Some(Some((nth, InodeResolution::Found(*inode))))
                     }
@@ -967,6 +1253,94 @@ impl FileSystemInner {
         }
     }
 
+    /// Remove the file at position `position` of a directory node
+    /// represented by `inode`, which is one of the names of the file.
+    ///
+    /// The file is only removed from the storage along with its last
+    /// name. Otherwise it takes over one of its other names, and keeps
+    /// its inode so that its opened handles remain valid.
+    ///
+    /// # Safety
+    ///
+    /// `inode` must represents an existing directory.
+    pub(super) fn remove_file_entry(&mut self, inode: Inode, position: usize) -> Result<()> {
+        let inode_of_entry = match self.storage.get(inode) {
+            Some(Node::Directory(DirectoryNode { children, .. })) => {
+                *children.get(position).ok_or(FsError::UnknownError)?
+            }
+            _ => return Err(FsError::UnknownError),
+        };
+        self.remove_child_from_node(inode, position)?;
+
+        if let Some(Node::HardLink(..)) = self.storage.get(inode_of_entry) {
+            self.remove_node(inode_of_entry);
+            return Ok(());
+        }
+
+        let inode_of_link = self
+            .storage
+            .iter()
+            .find_map(|(inode_of_link, node)| match node {
+                Node::HardLink(HardLinkNode { target, .. }) if *target == inode_of_entry => {
+                    Some(inode_of_link)
+                }
+                _ => None,
+            });
+        let Some(inode_of_link) = inode_of_link else {
+            self.remove_node(inode_of_entry);
+            return Ok(());
+        };
+
+        let (position_of_link, inode_of_link_parent) =
+            self.position_and_parent_of(inode_of_link)?;
+        let name = self.remove_node(inode_of_link).name().to_os_string();
+        self.storage
+            .get_mut(inode_of_entry)
+            .ok_or(FsError::UnknownError)?
+            .set_name(name);
+        match self.storage.get_mut(inode_of_link_parent) {
+            Some(Node::Directory(DirectoryNode { children, .. })) => {
+                children[position_of_link] = inode_of_entry;
+
+                Ok(())
+            }
+            _ => Err(FsError::UnknownError),
+        }
+    }
+
+    /// Removes a node from the storage, along with its permissions.
+    pub(super) fn remove_node(&mut self, inode: Inode) -> Node {
+        self.permissions.remove(&inode);
+        self.storage.remove(inode)
+    }
+
+    /// Find the directory node containing `inode`, and the position of
+    /// `inode` in its children.
+    pub(super) fn position_and_parent_of(&self, inode: Inode) -> Result<(usize, Inode)> {
+        self.storage
+            .iter()
+            .find_map(|(inode_of_parent, node)| match node {
+                Node::Directory(DirectoryNode { children, .. }) => children
+                    .iter()
+                    .position(|child| *child == inode)
+                    .map(|nth| (nth, inode_of_parent)),
+
+                _ => None,
+            })
+            .ok_or(FsError::BaseNotDirectory)
+    }
+
+    /// Get the node a directory entry refers to, which is the linked file
+    /// for hard links.
+    pub(super) fn resolve_hard_link<'a>(&'a self, node: &'a Node) -> &'a Node {
+        match node {
+            Node::HardLink(HardLinkNode { target, .. }) => {
+                self.storage.get(*target).unwrap_or(node)
+            }
+            _ => node,
+        }
+    }
+
     /// Canonicalize a path, i.e. try to resolve to a canonical,
     /// absolute form of the path with all intermediate components
     /// normalized:
@@ -1054,6 +1428,7 @@ impl fmt::Debug for FileSystemInner {
                         Node::CustomFile { .. } => "custom-file",
                         Node::Directory { .. } => "dir",
                         Node::ArcDirectory { .. } => "arc-dir",
+                        Node::HardLink { .. } => "hard-link",
                     },
                     name = node.name().to_string_lossy(),
                     indentation_symbol = " ",
@@ -1085,6 +1460,11 @@ impl fmt::Debug for FileSystemInner {
     }
 }
 
+/// Folds the case of a name for case-insensitive lookups.
+fn fold_case(name: &str) -> impl Iterator<Item = char> + '_ {
+    name.chars().flat_map(char::to_lowercase)
+}
+
 impl Default for FileSystemInner {
     fn default() -> Self {
         let time = time();
@@ -1107,9 +1487,14 @@ impl Default for FileSystemInner {
         }));
 
         Self {
+            dev: NEXT_DEV.fetch_add(1, Ordering::Relaxed),
             storage: slab,
             backing_offload: None,
             limiter: None,
+            capacity: DEFAULT_CAPACITY,
+            inode_quota: None,
+            case_insensitive: false,
+            permissions: HashMap::new(),
         }
     }
 }
@@ -1134,9 +1519,9 @@ impl DirectoryMustBeEmpty {
 mod test_filesystem {
     use std::{borrow::Cow, path::Path};
 
-    use tokio::io::AsyncReadExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-    use crate::{mem_fs::*, ops, DirEntry, FileSystem as FS, FileType, FsError};
+    use crate::{mem_fs::*, ops, DirEntry, FileSystem as FS, FileType, FsError, Permissions};
 
     macro_rules! path {
         ($path:expr) => {
@@ -1472,6 +1857,18 @@ mod test_filesystem {
             "The directory was renamed in the inner FS"
         );
 
+        assert_eq!(
+            fs.rename(path!("/mnt/cat"), path!("/cat")).await,
+            Err(FsError::CrossDevice),
+            "renaming out of a mounted FS",
+        );
+        assert_eq!(
+            fs.rename(path!("/bar/hello1.txt"), path!("/mnt/hello1.txt"))
+                .await,
+            Err(FsError::CrossDevice),
+            "renaming into a mounted FS",
+        );
+
         assert_eq!(
             fs.rename(path!("/bar/hello1.txt"), path!("/bar/world1.txt"))
                 .await,
@@ -1711,6 +2108,96 @@ mod test_filesystem {
         );
     }
 
+    #[tokio::test]
+    async fn test_hard_link() {
+        let fs = FileSystem::default();
+
+        let mut file = fs
+            .new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo.txt"))
+            .unwrap();
+        file.write_all(b"hello").await.unwrap();
+        assert_eq!(fs.create_dir(path!("/bar")), Ok(()));
+
+        assert_eq!(
+            fs.hard_link(path!("/foo.txt"), path!("/bar/baz.txt")),
+            Ok(()),
+            "linking a file",
+        );
+        assert_eq!(
+            fs.hard_link(path!("/foo.txt"), path!("/bar/baz.txt")),
+            Err(FsError::AlreadyExists),
+            "linking to a name that exists",
+        );
+        assert_eq!(
+            fs.hard_link(path!("/bar"), path!("/qux")),
+            Err(FsError::PermissionDenied),
+            "linking a directory",
+        );
+
+        let mut content = String::new();
+        fs.new_open_options()
+            .read(true)
+            .open(path!("/bar/baz.txt"))
+            .unwrap()
+            .read_to_string(&mut content)
+            .await
+            .unwrap();
+        assert_eq!(content, "hello", "both names share the contents");
+        let linked = fs
+            .new_open_options()
+            .read(true)
+            .open(path!("/bar/baz.txt"))
+            .unwrap();
+        assert_eq!(linked.file_id(), file.file_id(), "both names are one file");
+        let other_fs = FileSystem::default();
+        let other = other_fs
+            .new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo.txt"))
+            .unwrap();
+        assert_ne!(other.file_id(), file.file_id(), "file systems differ");
+        let entry = fs.read_dir(path!("/bar")).unwrap().next().unwrap().unwrap();
+        assert_eq!(entry.metadata.unwrap().len(), 5);
+
+        assert_eq!(
+            fs.remove_file(path!("/foo.txt")),
+            Ok(()),
+            "removing the first name",
+        );
+        assert_eq!(
+            fs.metadata(path!("/foo.txt")),
+            Err(FsError::EntryNotFound),
+            "the first name is gone",
+        );
+
+        // The file is still reachable through its opened handle
+        file.write_all(b" world").await.unwrap();
+        let mut content = String::new();
+        fs.new_open_options()
+            .read(true)
+            .open(path!("/bar/baz.txt"))
+            .unwrap()
+            .read_to_string(&mut content)
+            .await
+            .unwrap();
+        assert_eq!(content, "hello world", "the file is kept by its other name");
+
+        assert_eq!(
+            fs.remove_file(path!("/bar/baz.txt")),
+            Ok(()),
+            "removing the last name",
+        );
+        assert_eq!(
+            fs.inner.read().unwrap().storage.len(),
+            2,
+            "storage no longer has the file",
+        );
+    }
+
     #[tokio::test]
     async fn test_readdir() {
         let fs = FileSystem::default();
@@ -1916,6 +2403,207 @@ mod test_filesystem {
         assert!(ops::is_file(&fs, "/top-level/nested/another-file.txt"));
     }
 
+    #[tokio::test]
+    async fn statfs_reports_the_usage_of_each_mount() {
+        let nested = FileSystem::default();
+        nested.set_capacity(256 * 4096);
+        let nested: Arc<dyn crate::FileSystem + Send + Sync> = Arc::new(nested);
+
+        let fs = FileSystem::default();
+        fs.set_capacity(64 * 4096);
+        fs.mount("/nested".into(), &nested, "/".into()).unwrap();
+
+        let stats = fs.statfs(Path::new("/")).unwrap();
+        assert_eq!(stats.block_size, 4096);
+        assert_eq!(stats.blocks, 64);
+        assert_eq!(stats.blocks_free, 64);
+        assert_eq!(stats.files_free, 62);
+
+        let mut file = fs
+            .new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(Path::new("/foo.txt"))
+            .unwrap();
+        file.write_all(&[1; 5000]).await.unwrap();
+        // The holes take no space
+        file.set_len(1024 * 1024).unwrap();
+
+        let stats = fs.statfs(Path::new("/foo.txt")).unwrap();
+        assert_eq!(stats.blocks_free, 62);
+        assert_eq!(stats.files_free, 61);
+
+        let stats = fs.statfs(Path::new("/nested")).unwrap();
+        assert_eq!(stats.blocks, 256);
+        assert_eq!(stats.blocks_free, 256);
+        assert_eq!(stats.files_free, 255);
+        assert_eq!(
+            fs.statfs(Path::new("/nested/missing")),
+            Err(FsError::EntryNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn quotas_are_enforced_on_write_and_create() {
+        let global = Arc::new(crate::limiter::QuotaLimiter::new(12 * 1024));
+        let fs = FileSystem::default();
+        fs.set_memory_limiter(global.clone());
+        fs.set_quota(crate::limiter::FsQuota {
+            bytes: Some(8 * 1024),
+            inodes: Some(3),
+        });
+        let other = FileSystem::default();
+        other.set_memory_limiter(global.clone());
+
+        let mut file = fs
+            .new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(path!("/foo.txt"))
+            .unwrap();
+        file.write_all(&[1; 6 * 1024]).await.unwrap();
+        let err = file.write_all(&[1; 4 * 1024]).await.unwrap_err();
+        assert_eq!(FsError::from(err), FsError::StorageFull);
+        // Growing the file with a hole takes no space
+        file.set_len(1024 * 1024).unwrap();
+
+        let mut file = other
+            .new_open_options()
+            .write(true)
+            .create_new(true)
+            .open(path!("/bar.txt"))
+            .unwrap();
+        let err = file.write_all(&[1; 8 * 1024]).await.unwrap_err();
+        assert_eq!(FsError::from(err), FsError::StorageFull);
+
+        fs.create_dir(path!("/dir")).unwrap();
+        assert_eq!(fs.create_dir(path!("/dir2")), Err(FsError::StorageFull));
+        assert_eq!(fs.statfs(path!("/")).unwrap().files_free, 0);
+
+        // Removing files frees their space
+        fs.remove_file(path!("/foo.txt")).unwrap();
+        drop(file);
+        assert_eq!(global.used(), 0);
+        fs.create_dir(path!("/dir2")).unwrap();
+    }
+
+    #[tokio::test]
+    async fn hard_links_are_limited_by_the_inode_quota() {
+        let fs = FileSystem::default();
+        fs.set_quota(crate::limiter::FsQuota {
+            bytes: None,
+            inodes: Some(3),
+        });
+        ops::touch(&fs, "/foo.txt").unwrap();
+
+        fs.hard_link(path!("/foo.txt"), path!("/bar.txt")).unwrap();
+        assert_eq!(
+            fs.hard_link(path!("/foo.txt"), path!("/baz.txt")),
+            Err(FsError::StorageFull),
+        );
+        assert!(!ops::exists(&fs, "/baz.txt"));
+    }
+
+    #[tokio::test]
+    async fn case_insensitive_lookups_preserve_the_case() {
+        let fs = FileSystem::default();
+        fs.set_case_insensitive(true);
+
+        fs.create_dir(path!("/Program Files")).unwrap();
+        ops::touch(&fs, "/program files/ÉTÉ.TXT").unwrap();
+
+        assert!(fs
+            .metadata(path!("/PROGRAM FILES/été.txt"))
+            .unwrap()
+            .is_file());
+        assert_eq!(
+            fs.create_dir(path!("/PROGRAM FILES")),
+            Err(FsError::AlreadyExists)
+        );
+        let names: Vec<_> = fs
+            .read_dir(path!("/program files"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, vec![OsString::from("ÉTÉ.TXT")]);
+
+        // Changing the case of a name renames the entry in place
+        fs.rename(
+            path!("/Program Files/été.txt"),
+            path!("/Program Files/Été.txt"),
+        )
+        .await
+        .unwrap();
+        let names: Vec<_> = fs
+            .read_dir(path!("/Program Files"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, vec![OsString::from("Été.txt")]);
+
+        fs.set_case_insensitive(false);
+        assert_eq!(
+            fs.metadata(path!("/program files")),
+            Err(FsError::EntryNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn permissions_are_shared_by_hard_links() {
+        let fs = FileSystem::default();
+        fs.create_dir(path!("/dir")).unwrap();
+        ops::touch(&fs, "/dir/a.txt").unwrap();
+        fs.hard_link(path!("/dir/a.txt"), path!("/dir/b.txt"))
+            .unwrap();
+
+        assert_eq!(fs.permissions(path!("/dir")).unwrap().mode, 0o755);
+        assert_eq!(fs.permissions(path!("/dir/a.txt")).unwrap().mode, 0o644);
+
+        let permissions = Permissions {
+            mode: 0o600,
+            uid: 1000,
+            gid: 100,
+        };
+        fs.set_permissions(path!("/dir/b.txt"), permissions)
+            .unwrap();
+        assert_eq!(fs.permissions(path!("/dir/a.txt")), Ok(permissions));
+
+        // The file keeps its permissions under its remaining name, and they
+        // don't outlive it
+        fs.remove_file(path!("/dir/a.txt")).unwrap();
+        assert_eq!(fs.permissions(path!("/dir/b.txt")), Ok(permissions));
+        fs.remove_file(path!("/dir/b.txt")).unwrap();
+        ops::touch(&fs, "/dir/c.txt").unwrap();
+        assert_eq!(fs.permissions(path!("/dir/c.txt")).unwrap().mode, 0o644);
+    }
+
+    #[tokio::test]
+    async fn unmount_removes_the_mount_point() {
+        let top_level = FileSystem::default();
+        ops::touch(&top_level, "/file.txt").unwrap();
+        let top_level: Arc<dyn crate::FileSystem + Send + Sync> = Arc::new(top_level);
+
+        let fs = FileSystem::default();
+        fs.create_dir(path!("/dir")).unwrap();
+        fs.mount("/top-level".into(), &top_level, "/".into())
+            .unwrap();
+
+        assert_eq!(fs.unmount(path!("/dir")), Err(FsError::InvalidInput));
+        assert_eq!(fs.unmount(path!("/missing")), Err(FsError::EntryNotFound));
+        assert_eq!(fs.unmount(path!("/")), Err(FsError::BaseNotDirectory));
+
+        // The paths within a mount are looked up in the mounted file system
+        assert_eq!(
+            fs.unmount(path!("/top-level/file.txt")),
+            Err(FsError::InvalidInput)
+        );
+
+        fs.unmount(path!("/top-level")).unwrap();
+        assert!(!ops::exists(&fs, "/top-level"));
+        assert!(ops::is_file(&top_level, "/file.txt"));
+        assert!(ops::is_dir(&fs, "/dir"));
+    }
+
     #[tokio::test]
     async fn test_merge_flat() {
         let main = FileSystem::default();
@@ -29,6 +29,25 @@ struct FileNode {
     metadata: Metadata,
 }
 
+impl FileNode {
+    /// Copies a range of `src` into this file at `offset`, or a range of this
+    /// file when there is no `src`
+    fn copy_range(
+        &mut self,
+        src: Option<&File>,
+        src_offset: u64,
+        offset: u64,
+        len: u64,
+    ) -> std::io::Result<u64> {
+        let copied = match src {
+            Some(src) => self.file.copy_from(src, src_offset, offset, len),
+            None => self.file.copy_within(src_offset, offset, len),
+        };
+        self.metadata.len = self.file.len();
+        copied
+    }
+}
+
 #[derive(Debug)]
 struct ReadOnlyFileNode {
     inode: Inode,
@@ -79,6 +98,16 @@ struct ArcDirectoryNode {
     metadata: Metadata,
 }
 
+/// An additional name of a file, see [`crate::FileSystem::hard_link`]. The
+/// contents and the metadata are those of the `target` node.
+#[derive(Debug)]
+struct HardLinkNode {
+    inode: Inode,
+    name: OsString,
+    target: Inode,
+    metadata: Metadata,
+}
+
 #[derive(Debug)]
 enum Node {
     File(FileNode),
@@ -88,6 +117,7 @@ enum Node {
     CustomFile(CustomFileNode),
     Directory(DirectoryNode),
     ArcDirectory(ArcDirectoryNode),
+    HardLink(HardLinkNode),
 }
 
 impl Node {
@@ -100,6 +130,7 @@ impl Node {
             Self::CustomFile(CustomFileNode { inode, .. }) => inode,
             Self::Directory(DirectoryNode { inode, .. }) => inode,
             Self::ArcDirectory(ArcDirectoryNode { inode, .. }) => inode,
+            Self::HardLink(HardLinkNode { inode, .. }) => inode,
         }
     }
 
@@ -112,6 +143,7 @@ impl Node {
             Self::CustomFile(CustomFileNode { name, .. }) => name.as_os_str(),
             Self::Directory(DirectoryNode { name, .. }) => name.as_os_str(),
             Self::ArcDirectory(ArcDirectoryNode { name, .. }) => name.as_os_str(),
+            Self::HardLink(HardLinkNode { name, .. }) => name.as_os_str(),
         }
     }
 
@@ -124,6 +156,7 @@ impl Node {
             Self::CustomFile(CustomFileNode { metadata, .. }) => metadata,
             Self::Directory(DirectoryNode { metadata, .. }) => metadata,
             Self::ArcDirectory(ArcDirectoryNode { metadata, .. }) => metadata,
+            Self::HardLink(HardLinkNode { metadata, .. }) => metadata,
         }
     }
 
@@ -136,6 +169,7 @@ impl Node {
             Self::CustomFile(CustomFileNode { metadata, .. }) => metadata,
             Self::Directory(DirectoryNode { metadata, .. }) => metadata,
             Self::ArcDirectory(ArcDirectoryNode { metadata, .. }) => metadata,
+            Self::HardLink(HardLinkNode { metadata, .. }) => metadata,
         }
     }
 
@@ -148,6 +182,7 @@ impl Node {
             Self::CustomFile(CustomFileNode { name, .. }) => *name = new_name,
             Self::Directory(DirectoryNode { name, .. }) => *name = new_name,
             Self::ArcDirectory(ArcDirectoryNode { name, .. }) => *name = new_name,
+            Self::HardLink(HardLinkNode { name, .. }) => *name = new_name,
         }
     }
 }
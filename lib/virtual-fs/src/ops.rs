@@ -3,13 +3,14 @@
 
 use std::{
     collections::VecDeque,
+    io::SeekFrom,
     path::{Path, PathBuf},
 };
 
 use futures::future::BoxFuture;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 
-use crate::{DirEntry, FileSystem, FsError};
+use crate::{DirEntry, FileSystem, FsError, VirtualFile};
 
 /// Does this item exists?
 pub fn exists<F>(fs: &F, path: impl AsRef<Path>) -> bool
@@ -161,6 +162,35 @@ pub fn copy_reference_ext<'a>(
     })
 }
 
+/// Copies `len` bytes of `src`, starting at `src_offset`, into `dst` at
+/// `offset` through a buffer, see [`VirtualFile::copy_range_from`].
+pub async fn copy_range<F>(
+    dst: &mut F,
+    src: &mut (dyn VirtualFile + Send + Sync),
+    src_offset: u64,
+    offset: u64,
+    len: u64,
+) -> Result<u64, std::io::Error>
+where
+    F: VirtualFile + ?Sized,
+{
+    src.seek(SeekFrom::Start(src_offset)).await?;
+    dst.seek(SeekFrom::Start(offset)).await?;
+
+    let mut buf = vec![0u8; len.min(64 * 1024) as usize];
+    let mut copied = 0u64;
+    while copied < len {
+        let chunk = (len - copied).min(buf.len() as u64) as usize;
+        let read = src.read(&mut buf[..chunk]).await?;
+        if read == 0 {
+            break;
+        }
+        dst.write_all(&buf[..read]).await?;
+        copied += read as u64;
+    }
+    Ok(copied)
+}
+
 /// Asynchronously write some bytes to a file.
 ///
 /// This is analogous to [`std::fs::write()`].
@@ -0,0 +1,733 @@
+//! An overlay of a writable file system on top of read-only ones, like
+//! overlayfs(5).
+
+use std::{
+    collections::HashSet,
+    fmt,
+    io::{self, SeekFrom},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures::future::BoxFuture;
+use tokio::io::{AsyncRead, AsyncSeek, AsyncSeekExt, AsyncWrite, ReadBuf};
+
+use crate::{
+    ops, FileAdvice, FileOpener, FileSystem, FileSystems, FsError, FsStats, Metadata, OpenOptions,
+    OpenOptionsConfig, Permissions, ReadDir, Result, VirtualFile,
+};
+
+/// Marks a directory of the upper file system which hides the directory of
+/// the same path in the lower file systems, it's created when a directory
+/// is created where one was removed.
+const OPAQUE_MARKER: &str = ".wh..wh..opq";
+
+/// A writable upper file system on top of a chain of read-only lower file
+/// systems, such as the volumes of packages.
+///
+/// The upper file system shadows the lower ones, which shadow the ones after
+/// them in the chain. The files of the lower file systems are copied up to
+/// the upper one when they're first modified, so that nothing is copied
+/// upfront, and the removed ones are hidden by whiteouts in the upper file
+/// system.
+#[derive(Clone)]
+pub struct OverlayFileSystem<P, S> {
+    primary: Arc<P>,
+    secondaries: S,
+}
+
+impl<P, S> OverlayFileSystem<P, S>
+where
+    P: FileSystem,
+    S: for<'a> FileSystems<'a> + Send + Sync + 'static,
+{
+    /// Creates an overlay of `primary`, the upper file system, on top of
+    /// `secondaries`, the lower ones.
+    pub fn new(primary: P, secondaries: S) -> Self {
+        Self {
+            primary: Arc::new(primary),
+            secondaries,
+        }
+    }
+
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+
+    pub fn secondaries(&self) -> &S {
+        &self.secondaries
+    }
+
+    pub fn secondaries_mut(&mut self) -> &mut S {
+        &mut self.secondaries
+    }
+
+    /// The lower file systems at `path`, which are hidden by a whiteout or
+    /// an opaque directory at the path or at one of its parents.
+    fn lower_layers(&self, path: &Path) -> impl Iterator<Item = &(dyn FileSystem + Send)> + '_ {
+        let hidden = path.ancestors().any(|ancestor| {
+            ops::has_white_out(self.primary.as_ref(), ancestor) || self.is_opaque(ancestor)
+        });
+        self.secondaries
+            .filesystems()
+            .into_iter()
+            .filter(move |_| !hidden)
+    }
+
+    /// Whether `path` is in one of the visible lower file systems
+    fn is_in_lower_layers(&self, path: &Path) -> bool {
+        self.lower_layers(path).any(|fs| ops::exists(fs, path))
+    }
+
+    /// Whether `path` is a directory of the upper file system which hides
+    /// the one of the lower file systems
+    fn is_opaque(&self, path: &Path) -> bool {
+        ops::exists(self.primary.as_ref(), path.join(OPAQUE_MARKER))
+    }
+
+    /// Looks `path` up in the upper file system, then in the lower ones
+    fn lookup<T>(
+        &self,
+        path: &Path,
+        f: impl Fn(&(dyn FileSystem + Send)) -> Result<T>,
+    ) -> Result<T> {
+        // The whiteouts are markers, not files
+        if ops::is_white_out(path).is_some() {
+            return Err(FsError::EntryNotFound);
+        }
+        match f(self.primary.as_ref()) {
+            Err(FsError::EntryNotFound) => {}
+            other => return other,
+        }
+        for fs in self.lower_layers(path) {
+            match f(fs) {
+                Err(FsError::EntryNotFound) => continue,
+                other => return other,
+            }
+        }
+        Err(FsError::EntryNotFound)
+    }
+
+    /// Creates a directory and its parents in the upper file system, when
+    /// they're only in the lower ones
+    fn copy_up_dir(&self, path: &Path) -> Result<()> {
+        if ops::is_dir(self.primary.as_ref(), path) {
+            return Ok(());
+        }
+        if !self.metadata(path)?.is_dir() {
+            return Err(FsError::BaseNotDirectory);
+        }
+        if let Some(parent) = path.parent() {
+            self.copy_up_dir(parent)?;
+        }
+        self.primary.create_dir(path)
+    }
+
+    /// Copies a file or a directory tree, as seen through the overlay, to
+    /// `to` in the upper file system
+    fn copy_up_tree<'a>(&'a self, from: &'a Path, to: &'a Path) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if !self.metadata(from)?.is_dir() {
+                return Ok(ops::copy_reference_ext(self, self.primary.as_ref(), from, to).await?);
+            }
+
+            if !ops::is_dir(self.primary.as_ref(), to) {
+                self.primary.create_dir(to)?;
+            }
+            for entry in self.read_dir(from)? {
+                let entry = entry?;
+                let name = entry.path.file_name().ok_or(FsError::InvalidInput)?;
+                self.copy_up_tree(&entry.path, &to.join(name)).await?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Removes a file or a directory tree from the upper file system
+    fn remove_upper_tree(&self, path: &Path) -> Result<()> {
+        if !self.primary.metadata(path)?.is_dir() {
+            return self.primary.remove_file(path);
+        }
+        for entry in self.primary.read_dir(path)? {
+            self.remove_upper_tree(&entry?.path)?;
+        }
+        self.primary.remove_dir(path)
+    }
+
+    /// Makes `path` visible again after it was removed, a directory created
+    /// there hides the one which was removed
+    fn remove_white_out(&self, path: &Path, is_dir: bool) -> Result<()> {
+        if !ops::has_white_out(self.primary.as_ref(), path) {
+            return Ok(());
+        }
+        ops::remove_white_out(self.primary.as_ref(), path);
+        if is_dir {
+            ops::touch(self.primary.as_ref(), path.join(OPAQUE_MARKER))?;
+        }
+        Ok(())
+    }
+}
+
+impl<P, S> FileSystem for OverlayFileSystem<P, S>
+where
+    P: FileSystem,
+    S: for<'a> FileSystems<'a> + Send + Sync + 'static,
+{
+    fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        self.lookup(path, |fs| fs.readlink(path))
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        if ops::is_white_out(path).is_some() {
+            return Err(FsError::EntryNotFound);
+        }
+
+        let mut entries = Vec::new();
+        let mut names = HashSet::new();
+        let mut found = false;
+        let layers = std::iter::once(self.primary.as_ref() as &(dyn FileSystem + Send))
+            .chain(self.lower_layers(path));
+        for fs in layers {
+            let read_dir = match fs.read_dir(path) {
+                Ok(read_dir) => read_dir,
+                Err(FsError::EntryNotFound) => continue,
+                Err(err) => return Err(err),
+            };
+            found = true;
+
+            for entry in read_dir {
+                let entry = entry?;
+                // The whiteouts hide the entries of the next layers
+                if let Some(hidden) = ops::is_white_out(&entry.path) {
+                    names.extend(hidden.file_name().map(|name| name.to_owned()));
+                    continue;
+                }
+                if let Some(name) = entry.path.file_name() {
+                    if names.insert(name.to_owned()) {
+                        entries.push(entry);
+                    }
+                }
+            }
+        }
+
+        match found {
+            true => Ok(ReadDir::new(entries)),
+            false => Err(FsError::EntryNotFound),
+        }
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        if ops::is_white_out(path).is_some() {
+            return Err(FsError::InvalidInput);
+        }
+        if self.metadata(path).is_ok() {
+            return Err(FsError::AlreadyExists);
+        }
+
+        self.copy_up_dir(path.parent().ok_or(FsError::AlreadyExists)?)?;
+        self.primary.create_dir(path)?;
+        self.remove_white_out(path, true)
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        if !self.metadata(path)?.is_dir() {
+            return Err(FsError::BaseNotDirectory);
+        }
+        if self.read_dir(path)?.next().is_some() {
+            return Err(FsError::DirectoryNotEmpty);
+        }
+
+        let in_lower_layers = self.is_opaque(path) || self.is_in_lower_layers(path);
+        // Only whiteouts and markers are left in the upper directory
+        if ops::exists(self.primary.as_ref(), path) {
+            self.remove_upper_tree(path)?;
+        }
+        if in_lower_layers {
+            ops::create_white_out(self.primary.as_ref(), path)?;
+        }
+        Ok(())
+    }
+
+    fn rename<'a>(&'a self, from: &'a Path, to: &'a Path) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if ops::is_white_out(from).is_some() || ops::is_white_out(to).is_some() {
+                return Err(FsError::InvalidInput);
+            }
+            let is_dir = self.metadata(from)?.is_dir();
+            self.copy_up_dir(to.parent().ok_or(FsError::InvalidInput)?)?;
+
+            if self.is_in_lower_layers(from) {
+                // The lower layers are read-only, what they hold is copied
+                // up and then hidden
+                self.copy_up_tree(from, to).await?;
+                if ops::exists(self.primary.as_ref(), from) {
+                    self.remove_upper_tree(from)?;
+                }
+                ops::create_white_out(self.primary.as_ref(), from)?;
+            } else {
+                let is_opaque = self.is_opaque(from);
+                self.primary.rename(from, to).await?;
+                if is_opaque {
+                    ops::create_white_out(self.primary.as_ref(), from)?;
+                }
+            }
+            self.remove_white_out(to, is_dir)
+        })
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        self.lookup(path, |fs| fs.metadata(path))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        self.lookup(path, |fs| fs.symlink_metadata(path))
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        if self.metadata(path)?.is_dir() {
+            return Err(FsError::NotAFile);
+        }
+
+        if ops::exists(self.primary.as_ref(), path) {
+            self.primary.remove_file(path)?;
+        }
+        if self.is_in_lower_layers(path) {
+            ops::create_white_out(self.primary.as_ref(), path)?;
+        }
+        Ok(())
+    }
+
+    fn hard_link(&self, from: &Path, to: &Path) -> Result<()> {
+        if ops::is_white_out(to).is_some() {
+            return Err(FsError::InvalidInput);
+        }
+        if self.metadata(from)?.is_dir() {
+            return Err(FsError::PermissionDenied);
+        }
+        if self.metadata(to).is_ok() {
+            return Err(FsError::AlreadyExists);
+        }
+
+        if !ops::exists(self.primary.as_ref(), from) {
+            // The files of the lower layers are always ready to be read, so
+            // copying them up doesn't block for long
+            self.copy_up_dir(from.parent().ok_or(FsError::InvalidInput)?)?;
+            futures::executor::block_on(self.copy_up_tree(from, from))?;
+        }
+        self.copy_up_dir(to.parent().ok_or(FsError::InvalidInput)?)?;
+        self.primary.hard_link(from, to)?;
+        self.remove_white_out(to, false)
+    }
+
+    fn statfs(&self, path: &Path) -> Result<FsStats> {
+        self.metadata(path)?;
+        // The lower layers are read-only, only the upper one has free space
+        let path = path
+            .ancestors()
+            .find(|ancestor| ops::exists(self.primary.as_ref(), ancestor))
+            .ok_or(FsError::EntryNotFound)?;
+        self.primary.statfs(path)
+    }
+
+    fn permissions(&self, path: &Path) -> Result<Permissions> {
+        self.lookup(path, |fs| fs.permissions(path))
+    }
+
+    fn set_permissions(&self, path: &Path, permissions: Permissions) -> Result<()> {
+        if ops::is_white_out(path).is_some() {
+            return Err(FsError::InvalidInput);
+        }
+        if !ops::exists(self.primary.as_ref(), path) {
+            if self.metadata(path)?.is_dir() {
+                self.copy_up_dir(path)?;
+            } else {
+                self.copy_up_dir(path.parent().ok_or(FsError::InvalidInput)?)?;
+                futures::executor::block_on(self.copy_up_tree(path, path))?;
+            }
+        }
+        self.primary.set_permissions(path, permissions)
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(self)
+    }
+
+    fn mount(
+        &self,
+        name: String,
+        path: &Path,
+        fs: Box<dyn FileSystem + Send + Sync>,
+    ) -> Result<()> {
+        self.primary.mount(name, path, fs)
+    }
+
+    fn unmount(&self, path: &Path) -> Result<()> {
+        self.primary.unmount(path)
+    }
+}
+
+impl<P, S> FileOpener for OverlayFileSystem<P, S>
+where
+    P: FileSystem,
+    S: for<'a> FileSystems<'a> + Send + Sync + 'static,
+{
+    fn open(
+        &self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        if ops::is_white_out(path).is_some() {
+            return Err(FsError::EntryNotFound);
+        }
+
+        // The files of the upper file system are opened as they are
+        match self.primary.metadata(path) {
+            Ok(_) => {
+                return self
+                    .primary
+                    .new_open_options()
+                    .options(conf.clone())
+                    .open(path)
+            }
+            Err(FsError::EntryNotFound) => {}
+            Err(err) => return Err(err),
+        }
+
+        let writable = conf.write || conf.append;
+        let lower = self.lower_layers(path).find(|fs| ops::exists(*fs, path));
+        let Some(lower) = lower else {
+            if !conf.create && !conf.create_new {
+                return Err(FsError::EntryNotFound);
+            }
+            self.copy_up_dir(path.parent().ok_or(FsError::InvalidInput)?)?;
+            let file = self
+                .primary
+                .new_open_options()
+                .options(conf.clone())
+                .open(path)?;
+            self.remove_white_out(path, false)?;
+            return Ok(file);
+        };
+
+        if conf.create_new {
+            return Err(FsError::AlreadyExists);
+        }
+        if writable {
+            self.copy_up_dir(path.parent().ok_or(FsError::InvalidInput)?)?;
+            // A truncated file has nothing to copy up
+            if conf.truncate {
+                let mut conf = conf.clone();
+                conf.create = true;
+                return self.primary.new_open_options().options(conf).open(path);
+            }
+        }
+
+        let file = lower
+            .new_open_options()
+            .options(OpenOptionsConfig {
+                read: true,
+                write: false,
+                create_new: false,
+                create: false,
+                append: false,
+                truncate: false,
+            })
+            .open(path)?;
+        match writable {
+            true => Ok(Box::new(CopyUpFile {
+                path: path.to_path_buf(),
+                primary: self.primary.clone(),
+                append: conf.append,
+                state: CopyUpState::Lower(file),
+            })),
+            false => Ok(file),
+        }
+    }
+}
+
+impl<P, S> fmt::Debug for OverlayFileSystem<P, S>
+where
+    P: FileSystem,
+    S: for<'a> FileSystems<'a>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct Secondaries<'a, S>(&'a S);
+
+        impl<'a, S> fmt::Debug for Secondaries<'a, S>
+        where
+            S: for<'b> FileSystems<'b>,
+        {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_list().entries(self.0.filesystems()).finish()
+            }
+        }
+
+        f.debug_struct("OverlayFileSystem")
+            .field("primary", &self.primary)
+            .field("secondaries", &Secondaries(&self.secondaries))
+            .finish()
+    }
+}
+
+/// A file of a lower file system opened for writing, which is copied up to
+/// the upper file system before it's first modified.
+#[derive(Debug)]
+struct CopyUpFile {
+    path: PathBuf,
+    primary: Arc<dyn FileSystem + Send + Sync>,
+    append: bool,
+    state: CopyUpState,
+}
+
+#[derive(Debug)]
+enum CopyUpState {
+    Lower(Box<dyn VirtualFile + Send + Sync>),
+    Upper(Box<dyn VirtualFile + Send + Sync>),
+}
+
+impl CopyUpFile {
+    fn file(&self) -> &(dyn VirtualFile + Send + Sync) {
+        match &self.state {
+            CopyUpState::Lower(file) | CopyUpState::Upper(file) => file.as_ref(),
+        }
+    }
+
+    fn file_mut(&mut self) -> Pin<&mut (dyn VirtualFile + Send + Sync)> {
+        match &mut self.state {
+            CopyUpState::Lower(file) | CopyUpState::Upper(file) => Pin::new(file.as_mut()),
+        }
+    }
+
+    /// Copies the file up unless it already was, possibly through another
+    /// handle. The files of the lower layers are always ready to be read, so
+    /// copying them doesn't block for long.
+    fn copy_up(&mut self) -> io::Result<Pin<&mut (dyn VirtualFile + Send + Sync)>> {
+        if let CopyUpState::Lower(lower) = &mut self.state {
+            let copied = ops::exists(self.primary.as_ref(), &self.path);
+            let mut upper = self
+                .primary
+                .new_open_options()
+                .read(true)
+                .write(true)
+                .append(self.append)
+                .create_new(!copied)
+                .open(&self.path)?;
+            futures::executor::block_on(async {
+                let position = lower.stream_position().await?;
+                if !copied {
+                    upper
+                        .copy_range_from(lower.as_mut(), 0, 0, u64::MAX)
+                        .await?;
+                }
+                upper.seek(SeekFrom::Start(position)).await
+            })?;
+            self.state = CopyUpState::Upper(upper);
+        }
+        Ok(self.file_mut())
+    }
+}
+
+impl VirtualFile for CopyUpFile {
+    fn last_accessed(&self) -> u64 {
+        self.file().last_accessed()
+    }
+
+    fn last_modified(&self) -> u64 {
+        self.file().last_modified()
+    }
+
+    fn created_time(&self) -> u64 {
+        self.file().created_time()
+    }
+
+    fn set_times(&mut self, atime: Option<u64>, mtime: Option<u64>) -> Result<()> {
+        self.copy_up()?.set_times(atime, mtime)
+    }
+
+    fn size(&self) -> u64 {
+        self.file().size()
+    }
+
+    fn set_len(&mut self, new_size: u64) -> Result<()> {
+        self.copy_up()?.set_len(new_size)
+    }
+
+    fn allocate(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.copy_up()?.allocate(offset, len)
+    }
+
+    fn punch_hole(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.copy_up()?.punch_hole(offset, len)
+    }
+
+    fn advise(&mut self, offset: u64, len: u64, advice: FileAdvice) -> Result<()> {
+        self.file_mut().advise(offset, len, advice)
+    }
+
+    fn unlink(&mut self) -> Result<()> {
+        match &mut self.state {
+            CopyUpState::Lower(_) => ops::create_white_out(self.primary.as_ref(), &self.path),
+            CopyUpState::Upper(file) => file.unlink(),
+        }
+    }
+
+    fn poll_read_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        self.get_mut().file_mut().poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        self.get_mut().file_mut().poll_write_ready(cx)
+    }
+}
+
+impl AsyncRead for CopyUpFile {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.get_mut().file_mut().poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for CopyUpFile {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut().copy_up() {
+            Ok(file) => file.poll_write(cx, buf),
+            Err(err) => Poll::Ready(Err(err)),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().file_mut().poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().file_mut().poll_shutdown(cx)
+    }
+}
+
+impl AsyncSeek for CopyUpFile {
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        self.get_mut().file_mut().start_seek(position)
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        self.get_mut().file_mut().poll_complete(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::mem_fs::FileSystem as MemFS;
+
+    async fn lower() -> MemFS {
+        let fs = MemFS::default();
+        ops::create_dir_all(&fs, "/dir/nested").unwrap();
+        ops::write(&fs, "/dir/file.txt", b"lower").await.unwrap();
+        ops::write(&fs, "/dir/nested/other.txt", b"other")
+            .await
+            .unwrap();
+        fs
+    }
+
+    fn names(fs: &impl FileSystem, path: &str) -> Vec<String> {
+        let mut names: Vec<_> = fs
+            .read_dir(Path::new(path))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn files_are_copied_up_when_written() {
+        let lower = lower().await;
+        let fs = OverlayFileSystem::new(MemFS::default(), [lower.clone()]);
+
+        let mut file = fs
+            .new_open_options()
+            .read(true)
+            .write(true)
+            .open("/dir/file.txt")
+            .unwrap();
+        let mut buf = [0; 2];
+        file.read_exact(&mut buf).await.unwrap();
+        assert!(!ops::exists(fs.primary(), "/dir/file.txt"));
+
+        file.write_all(b"WER").await.unwrap();
+        assert_eq!(ops::read(&fs, "/dir/file.txt").await.unwrap(), b"loWER");
+        assert_eq!(ops::read(&lower, "/dir/file.txt").await.unwrap(), b"lower");
+
+        // Creating files copies their directories up
+        ops::write(&fs, "/dir/nested/new.txt", b"new")
+            .await
+            .unwrap();
+        assert!(ops::is_dir(fs.primary(), "/dir/nested"));
+        assert_eq!(names(&fs, "/dir/nested"), ["new.txt", "other.txt"]);
+    }
+
+    #[tokio::test]
+    async fn removed_entries_are_whited_out() {
+        let lower = lower().await;
+        let fs = OverlayFileSystem::new(MemFS::default(), [lower.clone()]);
+
+        fs.remove_file(Path::new("/dir/file.txt")).unwrap();
+        assert!(!ops::exists(&fs, "/dir/file.txt"));
+        assert!(ops::exists(&lower, "/dir/file.txt"));
+        assert_eq!(names(&fs, "/dir"), ["nested"]);
+
+        assert_eq!(
+            fs.remove_dir(Path::new("/dir/nested")),
+            Err(FsError::DirectoryNotEmpty)
+        );
+        fs.remove_file(Path::new("/dir/nested/other.txt")).unwrap();
+        fs.remove_dir(Path::new("/dir/nested")).unwrap();
+        assert!(names(&fs, "/dir").is_empty());
+
+        // A directory created in place of a removed one is empty
+        fs.create_dir(Path::new("/dir/nested")).unwrap();
+        assert!(names(&fs, "/dir/nested").is_empty());
+        assert!(!ops::exists(&fs, "/dir/nested/other.txt"));
+
+        ops::write(&fs, "/dir/file.txt", b"upper").await.unwrap();
+        assert_eq!(ops::read(&fs, "/dir/file.txt").await.unwrap(), b"upper");
+        assert_eq!(names(&fs, "/dir"), ["file.txt", "nested"]);
+
+        fs.remove_dir(Path::new("/dir/nested")).unwrap();
+        assert_eq!(names(&fs, "/dir"), ["file.txt"]);
+    }
+
+    #[tokio::test]
+    async fn renaming_copies_trees_up() {
+        let lower = lower().await;
+        let fs = OverlayFileSystem::new(MemFS::default(), [lower.clone()]);
+
+        fs.rename(Path::new("/dir"), Path::new("/renamed"))
+            .await
+            .unwrap();
+        assert!(!ops::exists(&fs, "/dir"));
+        assert_eq!(names(&fs, "/"), ["renamed"]);
+        assert_eq!(
+            ops::read(&fs, "/renamed/nested/other.txt").await.unwrap(),
+            b"other"
+        );
+        assert!(ops::exists(&lower, "/dir/nested/other.txt"));
+
+        fs.rename(Path::new("/renamed"), Path::new("/dir"))
+            .await
+            .unwrap();
+        assert_eq!(names(&fs, "/dir"), ["file.txt", "nested"]);
+        assert_eq!(names(&fs, "/dir/nested"), ["other.txt"]);
+    }
+}
@@ -8,8 +8,8 @@ use std::{
 };
 
 use crate::{
-    limiter::DynFsMemoryLimiter, mem_fs, BoxFuture, FileSystem, Metadata, OpenOptions, ReadDir,
-    Result,
+    limiter::{DynFsMemoryLimiter, FsQuota},
+    mem_fs, BoxFuture, FileSystem, FsStats, Metadata, OpenOptions, Permissions, ReadDir, Result,
 };
 
 #[derive(Debug, Default, Clone)]
@@ -26,6 +26,26 @@ impl TmpFileSystem {
         self.fs.set_memory_limiter(limiter);
     }
 
+    /// See [`mem_fs::FileSystem::memory_limiter`].
+    pub fn memory_limiter(&self) -> Option<DynFsMemoryLimiter> {
+        self.fs.memory_limiter()
+    }
+
+    /// See [`mem_fs::FileSystem::set_quota`].
+    pub fn set_quota(&self, quota: FsQuota) {
+        self.fs.set_quota(quota);
+    }
+
+    /// See [`mem_fs::FileSystem::set_case_insensitive`].
+    pub fn set_case_insensitive(&self, case_insensitive: bool) {
+        self.fs.set_case_insensitive(case_insensitive);
+    }
+
+    /// See [`mem_fs::FileSystem::set_capacity`].
+    pub fn set_capacity(&self, capacity: u64) {
+        self.fs.set_capacity(capacity);
+    }
+
     pub fn new_open_options_ext(&self) -> &mem_fs::FileSystem {
         self.fs.new_open_options_ext()
     }
@@ -93,6 +113,22 @@ impl FileSystem for TmpFileSystem {
         self.fs.remove_file(path)
     }
 
+    fn hard_link(&self, from: &Path, to: &Path) -> Result<()> {
+        self.fs.hard_link(from, to)
+    }
+
+    fn statfs(&self, path: &Path) -> Result<FsStats> {
+        self.fs.statfs(path)
+    }
+
+    fn permissions(&self, path: &Path) -> Result<Permissions> {
+        self.fs.permissions(path)
+    }
+
+    fn set_permissions(&self, path: &Path, permissions: Permissions) -> Result<()> {
+        self.fs.set_permissions(path, permissions)
+    }
+
     fn new_open_options(&self) -> OpenOptions {
         self.fs.new_open_options()
     }
@@ -105,4 +141,8 @@ impl FileSystem for TmpFileSystem {
     ) -> Result<()> {
         FileSystem::mount(&self.fs, name, path, fs)
     }
+
+    fn unmount(&self, path: &Path) -> Result<()> {
+        self.fs.unmount(path)
+    }
 }
@@ -11,6 +11,10 @@ use crate::InlineWaker;
 ///
 /// Works together with [`InlineWaker`] to yield to the execution environment
 /// when possible.
+///
+/// On workers, the waker waits with `Atomics.wait`, so the sleep is neither
+/// clamped like `setTimeout` nor throttled in background tabs. The main
+/// thread is not allowed to wait, so it spins instead.
 #[derive(Debug)]
 pub struct InlineSleep {
     until: f64,
@@ -30,7 +34,7 @@ impl Future for InlineSleep {
             match <&InlineWaker>::try_from(waker) {
                 Ok(inline_waker) => {
                     let remaining = this.until - now;
-                    inline_waker.set_timeout(Duration::from_millis(remaining as _));
+                    inline_waker.set_timeout(Duration::from_secs_f64(remaining / 1000.));
                 }
                 Err(_) => {
                     tracing::warn!(
@@ -48,7 +52,7 @@ impl InlineSleep {
     /// Creates a future that sleeps for the specified amount of time.
     pub fn new(duration: Duration) -> Self {
         Self {
-            until: GlobalScope::current().now() + duration.as_millis() as f64,
+            until: GlobalScope::current().now() + duration.as_secs_f64() * 1000.,
         }
     }
 }
@@ -1,5 +1,7 @@
+use std::cell::RefCell;
 use std::error::Error;
 use std::ptr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 use std::{fmt, mem};
 use std::{
@@ -10,6 +12,12 @@ use std::{
 use futures::Future;
 use utils::GlobalScope;
 
+thread_local! {
+    /// Where the time spent waiting in [`InlineWaker::block_on`] on this
+    /// thread is accumulated, see [`InlineWaker::track_wait_time`].
+    static WAIT_TIME: RefCell<Option<Arc<AtomicU64>>> = const { RefCell::new(None) };
+}
+
 /// Runs a Future on the current thread.
 pub struct InlineWaker {
     signature: [u8; 4],
@@ -59,6 +67,7 @@ impl InlineWaker {
         let mut task = Box::pin(task);
 
         let global = GlobalScope::current();
+        let wait_time = WAIT_TIME.with(|wait_time| wait_time.borrow().clone());
 
         if global.is_wait_allowed() {
             // We loop waiting for the waker to be woken, then we poll again.
@@ -70,6 +79,7 @@ impl InlineWaker {
                         let timeout = inline_waker.timeout.lock().unwrap().take();
 
                         if !*woken {
+                            let waiting_since = wait_time.as_ref().map(|_| global.now());
                             match timeout {
                                 Some(timeout) => {
                                     woken = inline_waker
@@ -80,6 +90,10 @@ impl InlineWaker {
                                 }
                                 None => woken = inline_waker.condvar.wait(woken).unwrap(),
                             }
+                            if let (Some(wait_time), Some(since)) = (&wait_time, waiting_since) {
+                                let nanos = (global.now() - since).max(0.) * 1_000_000.;
+                                wait_time.fetch_add(nanos as u64, Ordering::Relaxed);
+                            }
                         }
 
                         *woken = false;
@@ -101,6 +115,16 @@ impl InlineWaker {
         }
     }
 
+    /// Adds the time the current thread spends waiting in
+    /// [`InlineWaker::block_on`] to `counter` in nanoseconds, until it is
+    /// replaced by another call.
+    ///
+    /// This lets the time a thread is blocked be told apart from the time
+    /// it is running.
+    pub fn track_wait_time(counter: Option<Arc<AtomicU64>>) {
+        WAIT_TIME.with(|wait_time| *wait_time.borrow_mut() = counter);
+    }
+
     /// Sets the timeout after which the waker wakes, even if not explictly woken.
     ///
     /// If multiple timeouts are set, the shortest timeout is used.
@@ -11,6 +11,7 @@ use std::pin::Pin;
 use std::sync::Arc;
 use std::task::Context;
 use std::task::Poll;
+use std::task::Waker;
 use std::time::Duration;
 use thiserror::Error;
 
@@ -19,14 +20,20 @@ use tokio::io::AsyncRead;
 #[cfg(feature = "tokio")]
 use tokio::io::AsyncWrite;
 
+use derivative::Derivative;
 use pin_project_lite::pin_project;
 #[cfg(feature = "rkyv")]
 use rkyv::{Archive, CheckBytes, Deserialize as RkyvDeserialize, Serialize as RkyvSerialize};
 
+#[cfg(feature = "smoltcp")]
+pub mod stack;
+#[cfg(feature = "tls")]
+pub mod tls;
+
 pub use bytes::Bytes;
 pub use bytes::BytesMut;
 use serde::{Deserialize, Serialize};
-pub use virtual_mio::{handler_into_waker, InterestHandler};
+pub use virtual_mio::{handler_into_waker, InterestHandler, InterestType};
 
 pub type Result<T> = std::result::Result<T, NetworkError>;
 
@@ -50,6 +57,26 @@ pub struct IpRoute {
     pub expires_at: Option<Duration>,
 }
 
+/// The lease of the addresses acquired with DHCP.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "rkyv", derive(RkyvSerialize, RkyvDeserialize, Archive))]
+#[cfg_attr(feature = "rkyv", archive_attr(derive(CheckBytes)))]
+pub struct DhcpLease {
+    /// Changes whenever a lease is acquired, changed or lost, starting from
+    /// zero before the first one
+    pub serial: u64,
+    /// The addresses given to the interface, none once the lease is lost
+    pub addrs: Vec<IpCidr>,
+    pub gateway: Option<IpAddr>,
+    pub dns_servers: Vec<IpAddr>,
+    /// The DHCP server which gave the lease
+    pub server: Option<IpAddr>,
+    /// How long the lease lasts each time it's renewed
+    pub lease_time: Option<Duration>,
+    /// How long the lease has left
+    pub expires_in: Option<Duration>,
+}
+
 /// Represents an IO source
 pub trait VirtualIoSource: fmt::Debug + Send + Sync + 'static {
     /// Removes a previously registered waker using a token
@@ -87,6 +114,17 @@ pub trait VirtualNetworking: fmt::Debug + Send + Sync + 'static {
         Err(NetworkError::Unsupported)
     }
 
+    /// The lease of the addresses acquired with [`VirtualNetworking::dhcp_acquire`]
+    async fn dhcp_lease(&self) -> Result<DhcpLease> {
+        Err(NetworkError::Unsupported)
+    }
+
+    /// Waits until the DHCP lease is no longer the one numbered `serial`,
+    /// because it was acquired, changed or lost, and returns the new one
+    async fn dhcp_lease_changed(&self, serial: u64) -> Result<DhcpLease> {
+        Err(NetworkError::Unsupported)
+    }
+
     /// Adds a static IP address to the interface with a netmask prefix
     async fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
         Err(NetworkError::Unsupported)
@@ -202,6 +240,42 @@ pub trait VirtualNetworking: fmt::Debug + Send + Sync + 'static {
 
 pub type DynVirtualNetworking = Arc<dyn VirtualNetworking>;
 
+/// The handler and wakers waiting on a socket, for the sockets which track
+/// their readiness themselves.
+#[derive(Derivative, Default)]
+#[derivative(Debug)]
+pub struct Interest {
+    #[derivative(Debug = "ignore")]
+    pub handler: Option<Box<dyn InterestHandler + Send + Sync>>,
+    pub read_waker: Option<Waker>,
+    pub write_waker: Option<Waker>,
+}
+
+impl Interest {
+    /// Tells the handler about `interest`, and wakes the tasks waiting on
+    /// it, a close or an error waking both the readers and the writers.
+    pub fn notify(&mut self, interest: InterestType) {
+        if let Some(handler) = self.handler.as_mut() {
+            handler.push_interest(interest);
+        }
+        let (read, write) = match interest {
+            InterestType::Readable => (true, false),
+            InterestType::Writable => (false, true),
+            InterestType::Closed | InterestType::Error => (true, true),
+        };
+        if read {
+            if let Some(waker) = self.read_waker.take() {
+                waker.wake();
+            }
+        }
+        if write {
+            if let Some(waker) = self.write_waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
 pub trait VirtualTcpListener: VirtualIoSource + fmt::Debug + Send + Sync + 'static {
     /// Tries to accept a new connection
     fn try_accept(&mut self) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)>;
@@ -283,6 +357,18 @@ pub enum SocketStatus {
     Failed,
 }
 
+/// The parameters of the keep-alive probes of a TCP connection, the ones
+/// which are `None` keep the default of the system.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TcpKeepalive {
+    /// How long the connection is idle before the first probe is sent
+    pub idle: Option<Duration>,
+    /// How long to wait between the probes
+    pub interval: Option<Duration>,
+    /// How many probes are sent before the connection is dropped
+    pub retries: Option<u32>,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum StreamSecurity {
     Unencrypted,
@@ -312,8 +398,15 @@ pub trait VirtualConnectedSocket: VirtualSocket + fmt::Debug + Send + Sync + 'st
     /// Closes the socket
     fn close(&mut self) -> Result<()>;
 
-    /// Tries to read a packet from the socket
+    /// Tries to read a packet from the socket, which returns 0 once the
+    /// peer shut down its writer and everything it sent before was read
     fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize>;
+
+    /// Tries to read a packet from the socket without removing it, so the
+    /// next read returns the same bytes
+    fn try_peek(&mut self, _buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        Err(NetworkError::Unsupported)
+    }
 }
 
 #[async_trait::async_trait]
@@ -430,6 +523,12 @@ pub trait VirtualConnectionlessSocket: VirtualSocket + fmt::Debug + Send + Sync
 
     /// Recv a packet from the socket
     fn try_recv_from(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)>;
+
+    /// Recv a packet from the socket without removing it, so the next
+    /// receive returns the same packet
+    fn try_peek_from(&mut self, _buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)> {
+        Err(NetworkError::Unsupported)
+    }
 }
 
 #[async_trait::async_trait]
@@ -580,6 +679,13 @@ pub trait VirtualTcpSocket: VirtualConnectedSocket + fmt::Debug + Send + Sync +
     /// the connection alive.
     fn keepalive(&self) -> Result<bool>;
 
+    /// Sets when and how often the keep-alive probes are sent, which
+    /// isn't supported by default.
+    fn set_keepalive_params(&mut self, params: TcpKeepalive) -> Result<()> {
+        let _ = params;
+        Err(NetworkError::Unsupported)
+    }
+
     /// When DONT_ROUTE is set the packet will be sent directly
     /// to the interface without passing through the routing logic.
     fn set_dontroute(&mut self, keepalive: bool) -> Result<()>;
@@ -593,7 +699,8 @@ pub trait VirtualTcpSocket: VirtualConnectedSocket + fmt::Debug + Send + Sync +
     fn addr_peer(&self) -> Result<SocketAddr>;
 
     /// Shuts down either the READER or WRITER sides of the socket
-    /// connection.
+    /// connection. Shutting down the WRITER sends the end of the stream
+    /// to the peer while this side can still read what the peer sends.
     fn shutdown(&mut self, how: Shutdown) -> Result<()>;
 
     /// Return true if the socket is closed
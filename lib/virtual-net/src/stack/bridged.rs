@@ -0,0 +1,206 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use super::{StackClock, StackConfig, StackNetworking};
+use crate::{
+    DhcpLease, DynVirtualNetworking, IpCidr, IpRoute, NetworkError, Result, StreamSecurity,
+    VirtualIcmpSocket, VirtualNetworking, VirtualRawSocket, VirtualTcpListener, VirtualTcpSocket,
+    VirtualUdpSocket,
+};
+
+/// Networking which runs a [`StackNetworking`] over the link of a bridged
+/// network, like the one `port_bridge` joins, whose other end carries raw
+/// frames rather than terminating the connections.
+///
+/// Until it's bridged, everything goes to the networking it wraps. Once
+/// [`VirtualNetworking::bridge`] succeeds, the link is taken from the
+/// wrapped networking with [`VirtualNetworking::bind_raw`] and the sockets
+/// are opened on the stack, so the link can't be bound by raw sockets
+/// anymore. Names are still resolved by the wrapped networking.
+///
+/// Unbridging stops opening sockets on the stack, but the sockets already
+/// opened keep it and its link until they are dropped.
+#[derive(Debug)]
+pub struct BridgedStackNetworking {
+    inner: DynVirtualNetworking,
+    config: StackConfig,
+    clock: Arc<dyn StackClock>,
+    stack: Mutex<Option<DynVirtualNetworking>>,
+}
+
+impl BridgedStackNetworking {
+    pub fn new(
+        inner: DynVirtualNetworking,
+        config: StackConfig,
+        clock: Arc<dyn StackClock>,
+    ) -> Self {
+        Self {
+            inner,
+            config,
+            clock,
+            stack: Mutex::new(None),
+        }
+    }
+
+    /// Whether the network is bridged, and the sockets opened on the stack.
+    pub fn is_bridged(&self) -> bool {
+        self.stack.lock().unwrap().is_some()
+    }
+
+    /// The networking the sockets are opened on.
+    fn net(&self) -> DynVirtualNetworking {
+        match self.stack.lock().unwrap().as_ref() {
+            Some(stack) => stack.clone(),
+            None => self.inner.clone(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for BridgedStackNetworking {
+    async fn bridge(
+        &self,
+        network: &str,
+        access_token: &str,
+        security: StreamSecurity,
+    ) -> Result<()> {
+        if self.is_bridged() {
+            return Err(NetworkError::AlreadyExists);
+        }
+        self.inner.bridge(network, access_token, security).await?;
+        let link = match self.inner.bind_raw().await {
+            Ok(link) => link,
+            Err(err) => {
+                self.inner.unbridge().await.ok();
+                return Err(err);
+            }
+        };
+
+        let stack = StackNetworking::new(link, self.config, self.clock.clone());
+        let mut current = self.stack.lock().unwrap();
+        if current.is_some() {
+            // Another call bridged the network meanwhile
+            return Err(NetworkError::AlreadyExists);
+        }
+        *current = Some(Arc::new(stack));
+        Ok(())
+    }
+
+    async fn unbridge(&self) -> Result<()> {
+        self.stack.lock().unwrap().take();
+        self.inner.unbridge().await
+    }
+
+    async fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        self.net().dhcp_acquire().await
+    }
+
+    async fn dhcp_lease(&self) -> Result<DhcpLease> {
+        self.net().dhcp_lease().await
+    }
+
+    async fn dhcp_lease_changed(&self, serial: u64) -> Result<DhcpLease> {
+        self.net().dhcp_lease_changed(serial).await
+    }
+
+    async fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        self.net().ip_add(ip, prefix).await
+    }
+
+    async fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        self.net().ip_remove(ip).await
+    }
+
+    async fn ip_clear(&self) -> Result<()> {
+        self.net().ip_clear().await
+    }
+
+    async fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        self.net().ip_list().await
+    }
+
+    async fn mac(&self) -> Result<[u8; 6]> {
+        self.net().mac().await
+    }
+
+    async fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        self.net().gateway_set(ip).await
+    }
+
+    async fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        self.net()
+            .route_add(cidr, via_router, preferred_until, expires_at)
+            .await
+    }
+
+    async fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        self.net().route_remove(cidr).await
+    }
+
+    async fn route_clear(&self) -> Result<()> {
+        self.net().route_clear().await
+    }
+
+    async fn route_list(&self) -> Result<Vec<IpRoute>> {
+        self.net().route_list().await
+    }
+
+    /// The link belongs to the stack once bridged.
+    async fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        if self.is_bridged() {
+            return Err(NetworkError::AddressInUse);
+        }
+        self.inner.bind_raw().await
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        self.net()
+            .listen_tcp(addr, only_v6, reuse_port, reuse_addr)
+            .await
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        self.net().bind_udp(addr, reuse_port, reuse_addr).await
+    }
+
+    async fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        self.net().bind_icmp(addr).await
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        self.net().connect_tcp(addr, peer).await
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        self.inner.resolve(host, port, dns_server).await
+    }
+}
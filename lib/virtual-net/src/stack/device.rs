@@ -0,0 +1,96 @@
+use std::mem::MaybeUninit;
+
+use smoltcp::{
+    phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken},
+    time::Instant,
+};
+
+use crate::VirtualRawSocket;
+
+/// The length of the header of an Ethernet frame, which doesn't count in
+/// the MTU.
+const ETHERNET_HEADER_LEN: usize = 14;
+
+/// The link of the stack, as a device of smoltcp.
+#[derive(Debug)]
+pub(super) struct LinkDevice {
+    pub link: Box<dyn VirtualRawSocket + Sync>,
+    pub medium: Medium,
+    pub mtu: usize,
+}
+
+impl LinkDevice {
+    /// The largest frame exchanged over the link.
+    fn max_frame_len(&self) -> usize {
+        match self.medium {
+            Medium::Ethernet => self.mtu + ETHERNET_HEADER_LEN,
+            _ => self.mtu,
+        }
+    }
+}
+
+pub(super) struct LinkRxToken {
+    frame: Vec<u8>,
+}
+
+impl RxToken for LinkRxToken {
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        f(&mut self.frame)
+    }
+}
+
+pub(super) struct LinkTxToken<'a> {
+    link: &'a mut (dyn VirtualRawSocket + Sync),
+}
+
+impl TxToken for LinkTxToken<'_> {
+    /// The frames the link can't take are dropped, like on a busy network,
+    /// which TCP recovers from.
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut frame = vec![0; len];
+        let ret = f(&mut frame);
+        self.link.try_send(&frame).ok();
+        ret
+    }
+}
+
+impl Device for LinkDevice {
+    type RxToken<'a> = LinkRxToken;
+    type TxToken<'a> = LinkTxToken<'a>;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let mut buf = vec![MaybeUninit::new(0); self.max_frame_len()];
+        // A failed link is found out by the sockets timing out
+        let Ok(len) = self.link.try_recv(&mut buf) else {
+            return None;
+        };
+        let frame = buf[..len]
+            .iter()
+            .map(|b| unsafe { b.assume_init() })
+            .collect();
+        let rx = LinkRxToken { frame };
+        let tx = LinkTxToken {
+            link: &mut *self.link,
+        };
+        Some((rx, tx))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(LinkTxToken {
+            link: &mut *self.link,
+        })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = self.medium;
+        caps.max_transmission_unit = self.max_frame_len();
+        caps
+    }
+}
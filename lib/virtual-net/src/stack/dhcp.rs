@@ -0,0 +1,153 @@
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    task::Waker,
+    time::Duration,
+};
+
+use smoltcp::{
+    iface::SocketHandle,
+    socket::dhcpv4,
+    time::Instant,
+    wire::{DhcpRepr, IpCidr as StackCidr, Ipv4Address, Ipv4Cidr},
+};
+
+use super::driver::StackState;
+use crate::{DhcpLease, IpCidr};
+
+/// The DHCP client of the stack, and the lease it holds.
+#[derive(Debug, Default)]
+pub(super) struct Dhcp {
+    /// The socket of the client, once addresses were acquired with it
+    client: Option<SocketHandle>,
+    serial: u64,
+    lease: Option<Lease>,
+    /// The tasks waiting for the lease to change
+    wakers: Vec<Waker>,
+}
+
+/// What the DHCP server gave the stack.
+#[derive(Debug)]
+struct Lease {
+    address: Ipv4Cidr,
+    router: Option<Ipv4Address>,
+    dns_servers: Vec<IpAddr>,
+    server: Ipv4Address,
+    lease_time: Option<Duration>,
+    renew_time: Option<Duration>,
+    /// When the lease was given, or last changed
+    acquired_at: Instant,
+}
+
+impl Dhcp {
+    pub fn lease(&self, now: Instant) -> DhcpLease {
+        let Some(lease) = self.lease.as_ref() else {
+            return DhcpLease {
+                serial: self.serial,
+                ..Default::default()
+            };
+        };
+        let expires_in = lease.lease_time.map(|lease_time| {
+            // The client renews the lease without telling when nothing
+            // changes, so it's taken as renewed on time
+            let renew_time = lease.renew_time.unwrap_or(lease_time / 2);
+            let elapsed = Duration::from(now - lease.acquired_at);
+            let since_renewal = match renew_time.is_zero() {
+                true => elapsed,
+                false => Duration::from_nanos((elapsed.as_nanos() % renew_time.as_nanos()) as u64),
+            };
+            lease_time.saturating_sub(since_renewal)
+        });
+        DhcpLease {
+            serial: self.serial,
+            addrs: vec![IpCidr {
+                ip: Ipv4Addr::from(lease.address.address()).into(),
+                prefix: lease.address.prefix_len(),
+            }],
+            gateway: lease.router.map(|ip| Ipv4Addr::from(ip).into()),
+            dns_servers: lease.dns_servers.clone(),
+            server: Some(Ipv4Addr::from(lease.server).into()),
+            lease_time: lease.lease_time,
+            expires_in,
+        }
+    }
+
+    /// Wakes `waker` once the lease changes.
+    pub fn register_waker(&mut self, waker: &Waker) {
+        if !self.wakers.iter().any(|w| w.will_wake(waker)) {
+            self.wakers.push(waker.clone());
+        }
+    }
+}
+
+impl StackState {
+    /// Starts the DHCP client of the stack, unless it runs already.
+    pub fn start_dhcp(&mut self) {
+        if self.dhcp.client.is_some() {
+            return;
+        }
+        let mut socket = dhcpv4::Socket::new();
+        // The lease time is only found in the packet which gave the lease.
+        // The socket lives as long as the stack, and so does its buffer
+        socket.set_receive_packet_buffer(vec![0; self.device.mtu].leak());
+        self.dhcp.client = Some(self.sockets.add(socket));
+    }
+
+    /// Configures the interface with what the DHCP client was given, or
+    /// unconfigures it once the lease is lost.
+    pub fn update_dhcp(&mut self, now: Instant) {
+        let Some(client) = self.dhcp.client else {
+            return;
+        };
+        let lease = match self.sockets.get_mut::<dhcpv4::Socket>(client).poll() {
+            None => return,
+            Some(dhcpv4::Event::Configured(config)) => {
+                let repr = config
+                    .packet
+                    .as_ref()
+                    .and_then(|packet| DhcpRepr::parse(packet).ok());
+                let secs = |secs: Option<u32>| secs.map(|secs| Duration::from_secs(secs.into()));
+                Some(Lease {
+                    address: config.address,
+                    router: config.router,
+                    dns_servers: config
+                        .dns_servers
+                        .iter()
+                        .map(|ip| Ipv4Addr::from(*ip).into())
+                        .collect(),
+                    server: config.server.identifier,
+                    lease_time: secs(repr.as_ref().and_then(|repr| repr.lease_duration)),
+                    renew_time: secs(repr.as_ref().and_then(|repr| repr.renew_duration)),
+                    acquired_at: now,
+                })
+            }
+            // The client starts deconfigured, which changes nothing
+            Some(dhcpv4::Event::Deconfigured) if self.dhcp.lease.is_none() => return,
+            Some(dhcpv4::Event::Deconfigured) => None,
+        };
+
+        if let Some(old) = self.dhcp.lease.take() {
+            let cidr = StackCidr::Ipv4(old.address);
+            self.iface
+                .update_ip_addrs(|addrs| addrs.retain(|addr| *addr != cidr));
+            if old.router.is_some() {
+                self.iface.routes_mut().remove_default_ipv4_route();
+            }
+        }
+        if let Some(new) = lease.as_ref() {
+            let cidr = StackCidr::Ipv4(new.address);
+            self.iface.update_ip_addrs(|addrs| {
+                if !addrs.contains(&cidr) {
+                    addrs.push(cidr).ok();
+                }
+            });
+            if let Some(router) = new.router {
+                self.iface.routes_mut().add_default_ipv4_route(router).ok();
+            }
+        }
+        self.dhcp.lease = lease;
+        self.dhcp.serial += 1;
+        for waker in self.dhcp.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
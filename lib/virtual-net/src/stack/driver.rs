@@ -0,0 +1,386 @@
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    ops::Range,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Wake, Waker},
+    time::Duration,
+};
+
+use derivative::Derivative;
+use smoltcp::{
+    iface::{Interface, SocketHandle, SocketSet},
+    socket::{icmp, tcp, udp},
+    time::Instant,
+    wire::IpListenEndpoint,
+};
+use virtual_mio::{InterestHandler, InterestType};
+
+use super::{device::LinkDevice, dhcp::Dhcp, StackClock};
+use crate::Interest;
+
+/// The ports given to the sockets bound to port 0.
+const EPHEMERAL_PORTS: Range<u16> = 49152..65535;
+
+/// The shortest wait for a timer of the stack, so that a timer which is
+/// already due doesn't keep the stack polling.
+const MIN_TIMER_DELAY: Duration = Duration::from_millis(1);
+
+/// What kind of socket of smoltcp a [`Watch`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Kind {
+    Tcp,
+    Udp,
+    Icmp,
+}
+
+/// The handler and wakers waiting on a socket, with what they were last
+/// told.
+#[derive(Debug)]
+pub(super) struct Watch {
+    pub kind: Kind,
+    pub interest: Interest,
+    readable: bool,
+    writable: bool,
+    closed: bool,
+    /// Whether the stream stopped reading, what it receives is then
+    /// dropped
+    pub read_shutdown: bool,
+    pub linger: Option<Duration>,
+    /// Whether the handle to the socket was dropped, it's removed once the
+    /// connection is closed
+    pub orphaned: bool,
+}
+
+impl Watch {
+    pub fn new(kind: Kind) -> Self {
+        Self {
+            kind,
+            interest: Interest::default(),
+            readable: false,
+            writable: false,
+            closed: false,
+            read_shutdown: false,
+            linger: None,
+            orphaned: false,
+        }
+    }
+
+    /// Hands the current readiness of the socket to a new handler.
+    pub fn set_handler(&mut self, mut handler: Box<dyn InterestHandler + Send + Sync>) {
+        if self.readable {
+            handler.push_interest(InterestType::Readable);
+        }
+        if self.writable {
+            handler.push_interest(InterestType::Writable);
+        }
+        self.interest.handler = Some(handler);
+    }
+}
+
+/// The listening sockets of a TCP listener, each taking one connection.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub(super) struct Listener {
+    pub endpoint: IpListenEndpoint,
+    pub sockets: Vec<SocketHandle>,
+    #[derivative(Debug = "ignore")]
+    pub handler: Option<Box<dyn InterestHandler + Send + Sync>>,
+    pub waker: Option<Waker>,
+    readable: bool,
+}
+
+impl Listener {
+    pub fn new(endpoint: IpListenEndpoint, sockets: Vec<SocketHandle>) -> Self {
+        Self {
+            endpoint,
+            sockets,
+            handler: None,
+            waker: None,
+            readable: false,
+        }
+    }
+}
+
+/// The interface of the stack and its sockets.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub(super) struct StackState {
+    #[derivative(Debug = "ignore")]
+    pub iface: Interface,
+    pub device: LinkDevice,
+    #[derivative(Debug = "ignore")]
+    pub sockets: SocketSet<'static>,
+    pub watches: HashMap<SocketHandle, Watch>,
+    /// The TCP listeners, by port
+    pub listeners: HashMap<u16, Listener>,
+    pub udp_ports: HashSet<u16>,
+    pub icmp_idents: HashSet<u16>,
+    pub dhcp: Dhcp,
+    next_port: u16,
+    next_ident: u16,
+    /// Wakes the stack for its next timer, which is due at `timer_at`
+    #[derivative(Debug = "ignore")]
+    timer: Option<Pin<Box<dyn Future<Output = ()> + Send + Sync>>>,
+    timer_at: Option<Instant>,
+}
+
+impl StackState {
+    pub fn new(iface: Interface, device: LinkDevice) -> Self {
+        Self {
+            iface,
+            device,
+            sockets: SocketSet::new(Vec::new()),
+            watches: HashMap::new(),
+            listeners: HashMap::new(),
+            udp_ports: HashSet::new(),
+            icmp_idents: HashSet::new(),
+            dhcp: Dhcp::default(),
+            next_port: EPHEMERAL_PORTS.start,
+            next_ident: 1,
+            timer: None,
+            timer_at: None,
+        }
+    }
+
+    pub fn tcp(&mut self, handle: SocketHandle) -> &mut tcp::Socket<'static> {
+        self.sockets.get_mut(handle)
+    }
+
+    pub fn watch(&mut self, handle: SocketHandle) -> &mut Watch {
+        self.watches
+            .get_mut(&handle)
+            .expect("the watch of a socket lives as long as its handle")
+    }
+
+    /// Picks a free ephemeral port.
+    pub fn ephemeral_port(&mut self, in_use: impl Fn(&Self, u16) -> bool) -> Option<u16> {
+        for _ in EPHEMERAL_PORTS {
+            let port = self.next_port;
+            self.next_port = match port + 1 {
+                next if EPHEMERAL_PORTS.contains(&next) => next,
+                _ => EPHEMERAL_PORTS.start,
+            };
+            if !in_use(self, port) {
+                return Some(port);
+            }
+        }
+        None
+    }
+
+    /// Picks a free identifier for the echo requests of an ICMP socket.
+    pub fn icmp_ident(&mut self) -> Option<u16> {
+        for _ in 0..u16::MAX {
+            let ident = self.next_ident;
+            self.next_ident = self.next_ident.checked_add(1).unwrap_or(1);
+            if self.icmp_idents.insert(ident) {
+                return Some(ident);
+            }
+        }
+        None
+    }
+
+    /// Tells the handlers and the wakers of the sockets whose readiness
+    /// changed, and forgets the orphaned connections once they're closed.
+    fn notify(&mut self) {
+        let sockets = &mut self.sockets;
+        self.watches.retain(|handle, watch| {
+            let (readable, writable, closed) = match watch.kind {
+                Kind::Tcp => {
+                    let socket = sockets.get_mut::<tcp::Socket>(*handle);
+                    if watch.orphaned && !socket.is_open() {
+                        sockets.remove(*handle);
+                        return false;
+                    }
+                    if watch.read_shutdown {
+                        while let Ok(len) = socket.recv(|data| (data.len(), data.len())) {
+                            if len == 0 {
+                                break;
+                            }
+                        }
+                    }
+                    tcp_readiness(socket)
+                }
+                Kind::Udp => {
+                    let socket = sockets.get::<udp::Socket>(*handle);
+                    (socket.can_recv(), socket.can_send(), false)
+                }
+                Kind::Icmp => {
+                    let socket = sockets.get::<icmp::Socket>(*handle);
+                    (socket.can_recv(), socket.can_send(), false)
+                }
+            };
+            if readable && !watch.readable {
+                watch.interest.notify(InterestType::Readable);
+            }
+            if writable && !watch.writable {
+                watch.interest.notify(InterestType::Writable);
+            }
+            if closed && !watch.closed {
+                watch.interest.notify(InterestType::Closed);
+            }
+            (watch.readable, watch.writable, watch.closed) = (readable, writable, closed);
+            true
+        });
+
+        for listener in self.listeners.values_mut() {
+            // A connection which timed out before it was established leaves
+            // its socket closed
+            for handle in listener.sockets.iter() {
+                let socket = self.sockets.get_mut::<tcp::Socket>(*handle);
+                if socket.state() == tcp::State::Closed {
+                    socket.listen(listener.endpoint).ok();
+                }
+            }
+            let readable = listener
+                .sockets
+                .iter()
+                .any(|handle| is_accepted(self.sockets.get(*handle)));
+            if readable && !listener.readable {
+                if let Some(handler) = listener.handler.as_mut() {
+                    handler.push_interest(InterestType::Readable);
+                }
+                if let Some(waker) = listener.waker.take() {
+                    waker.wake();
+                }
+            }
+            listener.readable = readable;
+        }
+    }
+}
+
+/// Whether a listening socket got a connection.
+pub(super) fn is_accepted(socket: &tcp::Socket) -> bool {
+    !matches!(
+        socket.state(),
+        tcp::State::Listen | tcp::State::SynReceived | tcp::State::Closed
+    )
+}
+
+/// Whether a connection can be read from and written to, or is closed,
+/// where a connection which failed or was shut down counts as ready so
+/// that the error or the end of the stream is found out.
+pub(super) fn tcp_readiness(socket: &tcp::Socket) -> (bool, bool, bool) {
+    let connecting = matches!(
+        socket.state(),
+        tcp::State::SynSent | tcp::State::SynReceived
+    );
+    let readable = socket.can_recv() || (!connecting && !socket.may_recv());
+    let writable = socket.can_send() || (!connecting && !socket.may_send());
+    let closed = socket.state() == tcp::State::Closed;
+    (readable, writable, closed)
+}
+
+/// The stack, which is polled whenever a socket is used, and by the
+/// wakeups of its link and its timers.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub(super) struct Stack {
+    state: Mutex<StackState>,
+    /// Whether the stack has to be polled again, which is left to whoever
+    /// holds the state when it's woken
+    woken: AtomicBool,
+    #[derivative(Debug = "ignore")]
+    clock: Arc<dyn StackClock>,
+}
+
+impl Stack {
+    pub fn new(state: StackState, clock: Arc<dyn StackClock>) -> Arc<Self> {
+        let stack = Arc::new(Self {
+            state: Mutex::new(state),
+            woken: AtomicBool::new(false),
+            clock,
+        });
+        stack.drive();
+        stack
+    }
+
+    pub fn now(&self) -> Instant {
+        let now = self.clock.now();
+        Instant::from_micros(now.as_micros() as i64)
+    }
+
+    /// Runs `f` on the state, then polls the stack so that what it did is
+    /// sent right away.
+    pub fn with<R>(self: &Arc<Self>, f: impl FnOnce(&mut StackState) -> R) -> R {
+        let ret = {
+            let mut state = self.state.lock().unwrap();
+            let ret = f(&mut state);
+            self.poll(&mut state);
+            ret
+        };
+        if self.woken.load(Ordering::SeqCst) {
+            self.drive();
+        }
+        ret
+    }
+
+    fn drive(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::SeqCst);
+        while self.woken.load(Ordering::SeqCst) {
+            let Ok(mut state) = self.state.try_lock() else {
+                return;
+            };
+            while self.woken.swap(false, Ordering::SeqCst) {
+                self.poll(&mut state);
+            }
+        }
+    }
+
+    /// Polls the interface and tells the sockets what changed, then waits
+    /// for the link to receive and for the next timer.
+    fn poll(self: &Arc<Self>, state: &mut StackState) {
+        let now = self.now();
+        let StackState {
+            iface,
+            device,
+            sockets,
+            ..
+        } = state;
+        iface.poll(now, device, sockets);
+        state.update_dhcp(now);
+        state.notify();
+
+        let waker = Waker::from(self.clone());
+        let mut cx = Context::from_waker(&waker);
+        // A link which failed stays ready without anything to receive
+        if let Poll::Ready(Ok(len)) = state.device.link.poll_read_ready(&mut cx) {
+            if len > 0 {
+                self.woken.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let Some(delay) = state.iface.poll_delay(now, &state.sockets) else {
+            state.timer = None;
+            state.timer_at = None;
+            return;
+        };
+        let delay = Duration::from(delay).max(MIN_TIMER_DELAY);
+        let at = now + delay.into();
+        if state.timer_at.map_or(true, |timer_at| at < timer_at) {
+            state.timer = Some(self.clock.sleep(delay));
+            state.timer_at = Some(at);
+        }
+        if let Some(timer) = state.timer.as_mut() {
+            if let Poll::Ready(()) = timer.as_mut().poll(&mut cx) {
+                state.timer = None;
+                state.timer_at = None;
+                self.woken.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+impl Wake for Stack {
+    fn wake(self: Arc<Self>) {
+        self.drive();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.drive();
+    }
+}
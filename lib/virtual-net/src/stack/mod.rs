@@ -0,0 +1,624 @@
+//! Networking over a user-space TCP/IP stack, which talks to a network
+//! made of raw frames, like the one of a tun or tap device or a bridged
+//! network, instead of relying on the other end to terminate the
+//! connections.
+//!
+//! # Design
+//!
+//! The stack is [smoltcp](https://docs.rs/smoltcp), whose interface sends
+//! and receives the frames over a [`VirtualRawSocket`], the link. It has no
+//! thread of its own: it's polled whenever a socket is used, when the link
+//! receives and when one of its timers is due, which it waits for with a
+//! [`StackClock`], so that it works wherever the link and the clock do.
+//!
+//! The sockets tell their handlers when they become readable or writable,
+//! once per change, like an edge-triggered poller. A connection which is
+//! dropped is closed gracefully, and forgotten once its peer closed it too.
+//!
+//! [`BridgedStackNetworking`] runs the stack over the link of a network
+//! joined with [`VirtualNetworking::bridge`], like the one of `port_bridge`.
+
+mod bridged;
+mod device;
+mod dhcp;
+mod driver;
+mod socket;
+
+use std::{
+    fmt::Debug,
+    future::{poll_fn, Future},
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::Arc,
+    task::Poll,
+    time::Duration,
+};
+
+use smoltcp::{
+    iface::{Config, Interface},
+    phy::Medium,
+    wire::{EthernetAddress, HardwareAddress, IpAddress, IpListenEndpoint},
+};
+
+pub use self::bridged::BridgedStackNetworking;
+pub use self::socket::{StackIcmpSocket, StackTcpListener, StackTcpSocket, StackUdpSocket};
+use self::{
+    device::LinkDevice,
+    driver::{Stack, StackState},
+};
+use crate::{
+    DhcpLease, IpCidr, NetworkError, Result, VirtualIcmpSocket, VirtualNetworking,
+    VirtualRawSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+};
+
+/// The time of the stack, and how it waits for its timers.
+pub trait StackClock: Debug + Send + Sync + 'static {
+    /// The time elapsed since an arbitrary point, which never goes back.
+    fn now(&self) -> Duration;
+
+    /// Waits for `duration`, waking the task which polls the returned
+    /// future once it's elapsed.
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send + Sync>>;
+}
+
+/// How the stack is attached to its link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackConfig {
+    /// The MAC address of the stack when the link carries Ethernet frames,
+    /// like a tap device, or `None` when it carries IP packets, like a tun
+    /// device
+    pub mac: Option<[u8; 6]>,
+    /// The largest IP packet sent over the link
+    pub mtu: usize,
+}
+
+impl Default for StackConfig {
+    fn default() -> Self {
+        Self {
+            mac: None,
+            mtu: 1500,
+        }
+    }
+}
+
+/// Networking over a user-space TCP/IP stack attached to a link.
+///
+/// The stack has no address until one is added with
+/// [`VirtualNetworking::ip_add`] or acquired with
+/// [`VirtualNetworking::dhcp_acquire`], nor routes beyond its networks until
+/// a gateway is set or given by DHCP. DHCP needs a link carrying Ethernet
+/// frames.
+#[derive(Debug, Clone)]
+pub struct StackNetworking {
+    stack: Arc<Stack>,
+}
+
+impl StackNetworking {
+    pub fn new(
+        link: Box<dyn VirtualRawSocket + Sync>,
+        config: StackConfig,
+        clock: Arc<dyn StackClock>,
+    ) -> Self {
+        let (medium, hardware_addr) = match config.mac {
+            Some(mac) => (
+                Medium::Ethernet,
+                HardwareAddress::Ethernet(EthernetAddress(mac)),
+            ),
+            None => (Medium::Ip, HardwareAddress::Ip),
+        };
+        let mut device = LinkDevice {
+            link,
+            medium,
+            mtu: config.mtu,
+        };
+
+        let now = clock.now();
+        let mut iface_config = Config::new(hardware_addr);
+        // The seed picks the initial sequence numbers of the connections
+        iface_config.random_seed = now.as_nanos() as u64;
+        let now = smoltcp::time::Instant::from_micros(now.as_micros() as i64);
+        let iface = Interface::new(iface_config, &mut device, now);
+
+        Self {
+            stack: Stack::new(StackState::new(iface, device), clock),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for StackNetworking {
+    async fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        self.stack.with(|state| match state.device.medium {
+            Medium::Ethernet => {
+                state.start_dhcp();
+                Ok(())
+            }
+            _ => Err(NetworkError::Unsupported),
+        })?;
+        let lease = poll_fn(|cx| {
+            let now = self.stack.now();
+            self.stack.with(|state| {
+                let lease = state.dhcp.lease(now);
+                if lease.addrs.is_empty() {
+                    state.dhcp.register_waker(cx.waker());
+                    return Poll::Pending;
+                }
+                Poll::Ready(lease)
+            })
+        })
+        .await;
+        Ok(lease.addrs.into_iter().map(|cidr| cidr.ip).collect())
+    }
+
+    async fn dhcp_lease(&self) -> Result<DhcpLease> {
+        let now = self.stack.now();
+        Ok(self.stack.with(|state| state.dhcp.lease(now)))
+    }
+
+    async fn dhcp_lease_changed(&self, serial: u64) -> Result<DhcpLease> {
+        let lease = poll_fn(|cx| {
+            let now = self.stack.now();
+            self.stack.with(|state| {
+                let lease = state.dhcp.lease(now);
+                if lease.serial == serial {
+                    state.dhcp.register_waker(cx.waker());
+                    return Poll::Pending;
+                }
+                Poll::Ready(lease)
+            })
+        })
+        .await;
+        Ok(lease)
+    }
+
+    async fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        let cidr = smoltcp::wire::IpCidr::new(ip.into(), prefix);
+        self.stack.with(|state| {
+            let mut ret = Ok(());
+            state.iface.update_ip_addrs(|addrs| {
+                if !addrs.contains(&cidr) && addrs.push(cidr).is_err() {
+                    ret = Err(NetworkError::InsufficientMemory);
+                }
+            });
+            ret
+        })
+    }
+
+    async fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        let ip = IpAddress::from(ip);
+        self.stack.with(|state| {
+            state
+                .iface
+                .update_ip_addrs(|addrs| addrs.retain(|cidr| cidr.address() != ip));
+        });
+        Ok(())
+    }
+
+    async fn ip_clear(&self) -> Result<()> {
+        self.stack
+            .with(|state| state.iface.update_ip_addrs(|addrs| addrs.clear()));
+        Ok(())
+    }
+
+    async fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        let addrs = self.stack.with(|state| {
+            state
+                .iface
+                .ip_addrs()
+                .iter()
+                .map(|cidr| IpCidr {
+                    ip: cidr.address().into(),
+                    prefix: cidr.prefix_len(),
+                })
+                .collect()
+        });
+        Ok(addrs)
+    }
+
+    async fn mac(&self) -> Result<[u8; 6]> {
+        match self.stack.with(|state| state.iface.hardware_addr()) {
+            HardwareAddress::Ethernet(mac) => Ok(mac.0),
+            _ => Err(NetworkError::Unsupported),
+        }
+    }
+
+    async fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        self.stack.with(|state| {
+            let routes = state.iface.routes_mut();
+            let added = match ip {
+                IpAddr::V4(ip) => routes.add_default_ipv4_route(ip.into()),
+                IpAddr::V6(ip) => routes.add_default_ipv6_route(ip.into()),
+            };
+            added
+                .map(|_| ())
+                .map_err(|_| NetworkError::InsufficientMemory)
+        })
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        _only_v6: bool,
+        _reuse_port: bool,
+        _reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        let listener = StackTcpListener::listen(&self.stack, addr)?;
+        Ok(Box::new(listener))
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        _reuse_port: bool,
+        _reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        let socket = StackUdpSocket::bind(&self.stack, addr)?;
+        Ok(Box::new(socket))
+    }
+
+    async fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        let socket = StackIcmpSocket::bind(&self.stack, addr)?;
+        Ok(Box::new(socket))
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        let socket = StackTcpSocket::connect(&self.stack, addr, peer).await?;
+        Ok(Box::new(socket))
+    }
+
+    /// The stack has no resolver, the names are resolved by sending queries
+    /// over UDP like any other program would.
+    async fn resolve(
+        &self,
+        _host: &str,
+        _port: Option<u16>,
+        _dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        Err(NetworkError::Unsupported)
+    }
+}
+
+/// The endpoint to listen to for `addr`, where an unspecified address
+/// listens to every address of the stack.
+fn listen_endpoint(addr: SocketAddr) -> IpListenEndpoint {
+    IpListenEndpoint {
+        addr: (!addr.ip().is_unspecified()).then(|| addr.ip().into()),
+        port: addr.port(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        mem::MaybeUninit,
+        net::Shutdown,
+        sync::Mutex,
+        task::{Context, Waker},
+        thread,
+        time::Instant,
+    };
+
+    use futures::{executor::block_on, future::join};
+
+    use super::*;
+    use crate::{
+        InterestHandler, SocketStatus, StreamSecurity, VirtualConnectedSocketExt,
+        VirtualConnectionlessSocketExt, VirtualIoSource, VirtualSocket, VirtualTcpListenerExt,
+    };
+
+    /// The frames sent to one end of a wire, which the other end receives.
+    #[derive(Debug, Default)]
+    struct Frames {
+        queue: VecDeque<Vec<u8>>,
+        waker: Option<Waker>,
+    }
+
+    /// One end of a wire between two stacks.
+    #[derive(Debug)]
+    struct WireEnd {
+        rx: Arc<Mutex<Frames>>,
+        tx: Arc<Mutex<Frames>>,
+    }
+
+    fn wire() -> (WireEnd, WireEnd) {
+        let a: Arc<Mutex<Frames>> = Arc::default();
+        let b: Arc<Mutex<Frames>> = Arc::default();
+        let end = WireEnd {
+            rx: a.clone(),
+            tx: b.clone(),
+        };
+        (end, WireEnd { rx: b, tx: a })
+    }
+
+    impl VirtualIoSource for WireEnd {
+        fn remove_handler(&mut self) {}
+
+        fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+            let mut frames = self.rx.lock().unwrap();
+            if !frames.queue.is_empty() {
+                return Poll::Ready(Ok(frames.queue.len()));
+            }
+            frames.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+
+        fn poll_write_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+            Poll::Ready(Ok(1))
+        }
+    }
+
+    impl VirtualSocket for WireEnd {
+        fn set_ttl(&mut self, _ttl: u32) -> Result<()> {
+            Ok(())
+        }
+
+        fn ttl(&self) -> Result<u32> {
+            Ok(64)
+        }
+
+        fn addr_local(&self) -> Result<SocketAddr> {
+            Err(NetworkError::Unsupported)
+        }
+
+        fn status(&self) -> Result<SocketStatus> {
+            Ok(SocketStatus::Opened)
+        }
+
+        fn set_handler(&mut self, _handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl VirtualRawSocket for WireEnd {
+        fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+            let mut frames = self.tx.lock().unwrap();
+            frames.queue.push_back(data.to_vec());
+            // The other stack is woken up from another thread, as it may
+            // be the one sending
+            if let Some(waker) = frames.waker.take() {
+                thread::spawn(move || waker.wake());
+            }
+            Ok(data.len())
+        }
+
+        fn try_flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+            let mut frames = self.rx.lock().unwrap();
+            let frame = frames.queue.pop_front().ok_or(NetworkError::WouldBlock)?;
+            for (byte, value) in buf.iter_mut().zip(&frame) {
+                byte.write(*value);
+            }
+            Ok(frame.len())
+        }
+
+        fn set_promiscuous(&mut self, _promiscuous: bool) -> Result<()> {
+            Ok(())
+        }
+
+        fn promiscuous(&self) -> Result<bool> {
+            Ok(false)
+        }
+    }
+
+    /// A clock whose timers are threads.
+    #[derive(Debug)]
+    struct ThreadClock(Instant);
+
+    impl StackClock for ThreadClock {
+        fn now(&self) -> Duration {
+            self.0.elapsed()
+        }
+
+        fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send + Sync>> {
+            let (tx, rx) = futures::channel::oneshot::channel::<()>();
+            thread::spawn(move || {
+                thread::sleep(duration);
+                tx.send(()).ok();
+            });
+            Box::pin(async move {
+                rx.await.ok();
+            })
+        }
+    }
+
+    /// Two stacks at 10.0.0.1 and 10.0.0.2 on the two ends of a wire,
+    /// carrying IP packets or Ethernet frames.
+    fn stacks(ethernet: bool) -> (StackNetworking, StackNetworking) {
+        let clock: Arc<dyn StackClock> = Arc::new(ThreadClock(Instant::now()));
+        let config = |mac: u8| StackConfig {
+            mac: ethernet.then_some([2, 0, 0, 0, 0, mac]),
+            mtu: 1500,
+        };
+        let (a, b) = wire();
+        let a = StackNetworking::new(Box::new(a), config(1), clock.clone());
+        let b = StackNetworking::new(Box::new(b), config(2), clock);
+        block_on(async {
+            a.ip_add("10.0.0.1".parse().unwrap(), 24).await.unwrap();
+            b.ip_add("10.0.0.2".parse().unwrap(), 24).await.unwrap();
+        });
+        (a, b)
+    }
+
+    fn init(buf: &[MaybeUninit<u8>]) -> Vec<u8> {
+        buf.iter()
+            .map(|byte| unsafe { byte.assume_init() })
+            .collect()
+    }
+
+    async fn send_all(socket: &mut Box<dyn VirtualTcpSocket + Sync>, mut data: &[u8]) {
+        while !data.is_empty() {
+            let sent = socket.send(data).await.unwrap();
+            data = &data[sent..];
+        }
+        socket.shutdown(Shutdown::Write).unwrap();
+    }
+
+    async fn recv_all(socket: &mut Box<dyn VirtualTcpSocket + Sync>) -> Vec<u8> {
+        let mut received = Vec::new();
+        let mut buf = vec![MaybeUninit::new(0); 8192];
+        loop {
+            match socket.recv(&mut buf).await.unwrap() {
+                0 => return received,
+                read => received.extend(init(&buf[..read])),
+            }
+        }
+    }
+
+    #[test]
+    fn tcp_connections_carry_streams() {
+        for ethernet in [false, true] {
+            let (client, server) = stacks(ethernet);
+            block_on(async {
+                let any = "0.0.0.0:0".parse().unwrap();
+                let mut listener = server
+                    .listen_tcp("0.0.0.0:80".parse().unwrap(), false, false, false)
+                    .await
+                    .unwrap();
+                let err = client
+                    .connect_tcp(any, "10.0.0.2:81".parse().unwrap())
+                    .await
+                    .unwrap_err();
+                assert_eq!(err, NetworkError::ConnectionRefused);
+
+                let mut connection = client
+                    .connect_tcp(any, "10.0.0.2:80".parse().unwrap())
+                    .await
+                    .unwrap();
+                let (mut accepted, peer) = listener.accept().await.unwrap();
+                assert_eq!(peer, connection.addr_local().unwrap());
+                assert_eq!(
+                    accepted.addr_local().unwrap(),
+                    "10.0.0.2:80".parse().unwrap()
+                );
+
+                // More than the windows hold, in both directions
+                let data: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+                let (_, received) =
+                    join(send_all(&mut connection, &data), recv_all(&mut accepted)).await;
+                assert_eq!(received, data);
+                let (_, received) =
+                    join(send_all(&mut accepted, &data), recv_all(&mut connection)).await;
+                assert_eq!(received, data);
+            });
+        }
+    }
+
+    #[test]
+    fn udp_datagrams_reach_their_socket() {
+        let (a, b) = stacks(false);
+        block_on(async {
+            let mut sender = a
+                .bind_udp("0.0.0.0:0".parse().unwrap(), false, false)
+                .await
+                .unwrap();
+            let mut receiver = b
+                .bind_udp("10.0.0.2:53".parse().unwrap(), false, false)
+                .await
+                .unwrap();
+            let from = sender.addr_local().unwrap();
+
+            sender
+                .send_to(b"hello", "10.0.0.2:53".parse().unwrap())
+                .await
+                .unwrap();
+            let mut buf = vec![MaybeUninit::new(0); 64];
+            let (read, addr) = receiver.recv_from(&mut buf).await.unwrap();
+            assert_eq!(init(&buf[..read]), b"hello");
+            assert_eq!(
+                addr,
+                SocketAddr::new("10.0.0.1".parse().unwrap(), from.port())
+            );
+
+            receiver.send_to(b"world", addr).await.unwrap();
+            let (read, addr) = sender.recv_from(&mut buf).await.unwrap();
+            assert_eq!(init(&buf[..read]), b"world");
+            assert_eq!(addr, "10.0.0.2:53".parse().unwrap());
+        });
+    }
+
+    /// A bridged network whose link is one end of a wire.
+    #[derive(Debug)]
+    struct Bridge(Mutex<Option<WireEnd>>);
+
+    #[async_trait::async_trait]
+    impl VirtualNetworking for Bridge {
+        async fn bridge(
+            &self,
+            network: &str,
+            _token: &str,
+            _security: StreamSecurity,
+        ) -> Result<()> {
+            match network {
+                "lan" => Ok(()),
+                _ => Err(NetworkError::ConnectionRefused),
+            }
+        }
+
+        async fn unbridge(&self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+            let link = self.0.lock().unwrap().take();
+            Ok(Box::new(link.ok_or(NetworkError::AddressInUse)?))
+        }
+    }
+
+    #[test]
+    fn bridging_runs_the_stack_over_the_link() {
+        let clock: Arc<dyn StackClock> = Arc::new(ThreadClock(Instant::now()));
+        let (link, peer) = wire();
+        let net = BridgedStackNetworking::new(
+            Arc::new(Bridge(Mutex::new(Some(link)))),
+            StackConfig::default(),
+            clock.clone(),
+        );
+        let peer = StackNetworking::new(Box::new(peer), StackConfig::default(), clock);
+
+        block_on(async {
+            let any = "0.0.0.0:0".parse().unwrap();
+            let err = net.ip_add("10.0.0.1".parse().unwrap(), 24).await;
+            assert_eq!(err, Err(NetworkError::Unsupported));
+            let err = net.bridge("wan", "", StreamSecurity::AnyEncyption).await;
+            assert_eq!(err, Err(NetworkError::ConnectionRefused));
+            assert!(!net.is_bridged());
+
+            net.bridge("lan", "", StreamSecurity::AnyEncyption)
+                .await
+                .unwrap();
+            assert!(net.is_bridged());
+            assert_eq!(
+                net.bind_raw().await.unwrap_err(),
+                NetworkError::AddressInUse
+            );
+            net.ip_add("10.0.0.1".parse().unwrap(), 24).await.unwrap();
+            peer.ip_add("10.0.0.2".parse().unwrap(), 24).await.unwrap();
+
+            let mut listener = peer
+                .listen_tcp("0.0.0.0:80".parse().unwrap(), false, false, false)
+                .await
+                .unwrap();
+            let mut connection = net
+                .connect_tcp(any, "10.0.0.2:80".parse().unwrap())
+                .await
+                .unwrap();
+            let (mut accepted, _) = listener.accept().await.unwrap();
+            let (_, received) =
+                join(send_all(&mut connection, b"ping"), recv_all(&mut accepted)).await;
+            assert_eq!(received, b"ping");
+
+            net.unbridge().await.unwrap();
+            assert!(!net.is_bridged());
+            let err = net.connect_tcp(any, "10.0.0.2:80".parse().unwrap()).await;
+            assert_eq!(err.unwrap_err(), NetworkError::Unsupported);
+        });
+    }
+}
@@ -0,0 +1,921 @@
+use std::{
+    future::poll_fn,
+    mem::MaybeUninit,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr},
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use smoltcp::{
+    iface::SocketHandle,
+    socket::{icmp, tcp, udp},
+    wire::{IpAddress, IpEndpoint},
+};
+use virtual_mio::{InterestHandler, InterestType};
+
+use super::{
+    driver::{is_accepted, tcp_readiness, Kind, Listener, Stack, StackState, Watch},
+    listen_endpoint,
+};
+use crate::{
+    NetworkError, Result, SocketStatus, TcpKeepalive, VirtualConnectedSocket,
+    VirtualConnectionlessSocket, VirtualIcmpSocket, VirtualIoSource, VirtualSocket,
+    VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+};
+
+/// The size of the buffers of a TCP connection, each way.
+const TCP_BUF_SIZE: usize = 64 * 1024;
+
+/// The size of the buffers of a UDP or ICMP socket, each way, and how many
+/// datagrams they hold.
+const DGRAM_BUF_SIZE: usize = 64 * 1024;
+const DGRAM_BUF_COUNT: usize = 32;
+
+/// How many connections to a listener can be established before they're
+/// accepted.
+const LISTEN_BACKLOG: usize = 4;
+
+/// How long a connection waits for its peer before it's given up.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(75);
+
+/// How often the keep-alive probes are sent when the program doesn't say.
+const DEFAULT_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(75);
+
+/// The hop limit of the packets when the program doesn't say.
+const DEFAULT_HOP_LIMIT: u8 = 64;
+
+fn tcp_socket() -> tcp::Socket<'static> {
+    tcp::Socket::new(
+        tcp::SocketBuffer::new(vec![0; TCP_BUF_SIZE]),
+        tcp::SocketBuffer::new(vec![0; TCP_BUF_SIZE]),
+    )
+}
+
+fn socket_addr(endpoint: IpEndpoint) -> SocketAddr {
+    SocketAddr::new(endpoint.addr.into(), endpoint.port)
+}
+
+/// Whether a TCP socket of the stack uses `port`.
+fn tcp_port_in_use(state: &StackState, port: u16) -> bool {
+    state.listeners.contains_key(&port)
+        || state.watches.iter().any(|(handle, watch)| {
+            watch.kind == Kind::Tcp
+                && state
+                    .sockets
+                    .get::<tcp::Socket>(*handle)
+                    .local_endpoint()
+                    .is_some_and(|endpoint| endpoint.port == port)
+        })
+}
+
+/// Fails unless `ip` is unspecified or one of the addresses of the stack.
+fn check_local_ip(state: &StackState, ip: IpAddr) -> Result<()> {
+    if ip.is_unspecified() || state.iface.has_ip_addr(ip) {
+        Ok(())
+    } else {
+        Err(NetworkError::AddressNotAvailable)
+    }
+}
+
+/// The hop limit set by `ttl`, which has to fit in the header.
+fn hop_limit(ttl: u32) -> Result<u8> {
+    u8::try_from(ttl)
+        .ok()
+        .filter(|ttl| *ttl > 0)
+        .ok_or(NetworkError::InvalidInput)
+}
+
+fn copy_to(buf: &mut [MaybeUninit<u8>], data: &[u8]) -> usize {
+    let amt = data.len().min(buf.len());
+    for (dst, src) in buf.iter_mut().zip(&data[..amt]) {
+        dst.write(*src);
+    }
+    amt
+}
+
+/// A TCP connection of the stack, which is closed once dropped.
+#[derive(Debug)]
+pub struct StackTcpSocket {
+    stack: Arc<Stack>,
+    handle: SocketHandle,
+    local: SocketAddr,
+    peer: SocketAddr,
+    /// The interval of the keep-alive probes, once they're enabled
+    keepalive_interval: Duration,
+}
+
+impl StackTcpSocket {
+    pub(super) async fn connect(
+        stack: &Arc<Stack>,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Self> {
+        let started = stack.now();
+        let handle = stack.with(|state| {
+            check_local_ip(state, addr.ip())?;
+            let port = match addr.port() {
+                0 => state
+                    .ephemeral_port(tcp_port_in_use)
+                    .ok_or(NetworkError::AddressInUse)?,
+                port if tcp_port_in_use(state, port) => return Err(NetworkError::AddressInUse),
+                port => port,
+            };
+            let mut socket = tcp_socket();
+            socket.set_timeout(Some(CONNECT_TIMEOUT.into()));
+            let local = listen_endpoint(SocketAddr::new(addr.ip(), port));
+            socket
+                .connect(state.iface.context(), peer, local)
+                .map_err(|err| match err {
+                    tcp::ConnectError::Unaddressable => NetworkError::AddressNotAvailable,
+                    tcp::ConnectError::InvalidState => NetworkError::InvalidInput,
+                })?;
+            let handle = state.sockets.add(socket);
+            state.watches.insert(handle, Watch::new(Kind::Tcp));
+            Ok(handle)
+        })?;
+        // The socket is closed if the connection fails, or if it's given up
+        let mut socket = Self {
+            stack: stack.clone(),
+            handle,
+            local: addr,
+            peer,
+            keepalive_interval: DEFAULT_KEEPALIVE_INTERVAL,
+        };
+
+        socket.local = poll_fn(|cx| {
+            socket.stack.with(|state| {
+                let tcp = state.tcp(handle);
+                match tcp.state() {
+                    tcp::State::SynSent | tcp::State::SynReceived => {
+                        state.watch(handle).interest.write_waker = Some(cx.waker().clone());
+                        Poll::Pending
+                    }
+                    tcp::State::Closed
+                        if socket.stack.now() - started >= CONNECT_TIMEOUT.into() =>
+                    {
+                        Poll::Ready(Err(NetworkError::TimedOut))
+                    }
+                    tcp::State::Closed => Poll::Ready(Err(NetworkError::ConnectionRefused)),
+                    _ => {
+                        // Once established, a connection is only given up if
+                        // it's kept alive
+                        tcp.set_timeout(None);
+                        let local = tcp.local_endpoint().ok_or(NetworkError::NotConnected);
+                        Poll::Ready(local.map(socket_addr))
+                    }
+                }
+            })
+        })
+        .await?;
+        Ok(socket)
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut tcp::Socket<'static>, &mut Watch) -> R) -> R {
+        self.stack.with(|state| {
+            let socket = state.sockets.get_mut(self.handle);
+            let watch = state
+                .watches
+                .get_mut(&self.handle)
+                .expect("the watch of a socket lives as long as its handle");
+            f(socket, watch)
+        })
+    }
+
+    fn recv(&mut self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        self.with(|socket, watch| {
+            if watch.read_shutdown {
+                return Ok(0);
+            }
+            let read = match peek {
+                true => socket.peek(buf.len()).map(|data| copy_to(buf, data)),
+                false => socket
+                    .recv(|data| {
+                        let amt = copy_to(buf, data);
+                        (amt, amt)
+                    })
+                    .map(|amt| {
+                        // What was received may wrap around the end of the
+                        // buffer of the connection
+                        let more = socket.recv(|data| {
+                            let more = copy_to(&mut buf[amt..], data);
+                            (more, more)
+                        });
+                        amt + more.unwrap_or(0)
+                    }),
+            };
+            match read {
+                Ok(0) if !buf.is_empty() => Err(NetworkError::WouldBlock),
+                Ok(amt) => Ok(amt),
+                Err(tcp::RecvError::Finished) => Ok(0),
+                Err(tcp::RecvError::InvalidState) => match socket.state() {
+                    tcp::State::Closed => Err(NetworkError::ConnectionReset),
+                    _ => Err(NetworkError::NotConnected),
+                },
+            }
+        })
+    }
+}
+
+impl Drop for StackTcpSocket {
+    /// The connection is closed in the background, unless it lingers for no
+    /// time, in which case it's reset.
+    fn drop(&mut self) {
+        self.with(|socket, watch| {
+            match watch.linger {
+                Some(linger) if linger.is_zero() => socket.abort(),
+                _ => socket.close(),
+            }
+            watch.orphaned = true;
+            watch.interest.handler.take();
+            watch.interest.read_waker.take();
+            watch.interest.write_waker.take();
+        });
+    }
+}
+
+impl VirtualIoSource for StackTcpSocket {
+    fn remove_handler(&mut self) {
+        self.with(|_, watch| watch.interest.handler.take());
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.with(|socket, watch| {
+            let (readable, _, _) = tcp_readiness(socket);
+            if readable || watch.read_shutdown {
+                return Poll::Ready(Ok(socket.recv_queue()));
+            }
+            watch.interest.read_waker = Some(cx.waker().clone());
+            Poll::Pending
+        })
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.with(|socket, watch| {
+            let (_, writable, _) = tcp_readiness(socket);
+            if writable {
+                return Poll::Ready(Ok(socket.send_capacity() - socket.send_queue()));
+            }
+            watch.interest.write_waker = Some(cx.waker().clone());
+            Poll::Pending
+        })
+    }
+}
+
+impl VirtualSocket for StackTcpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        let ttl = hop_limit(ttl)?;
+        self.with(|socket, _| socket.set_hop_limit(Some(ttl)));
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        let ttl = self.with(|socket, _| socket.hop_limit());
+        Ok(ttl.unwrap_or(DEFAULT_HOP_LIMIT).into())
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.local)
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        let status = self.with(|socket, _| match socket.state() {
+            tcp::State::SynSent | tcp::State::SynReceived => SocketStatus::Opening,
+            tcp::State::Closed | tcp::State::TimeWait => SocketStatus::Closed,
+            _ => SocketStatus::Opened,
+        });
+        Ok(status)
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.with(|_, watch| watch.set_handler(handler));
+        Ok(())
+    }
+}
+
+impl VirtualConnectedSocket for StackTcpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.with(|_, watch| watch.linger = linger);
+        Ok(())
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        Ok(self.with(|_, watch| watch.linger))
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        self.with(|socket, _| {
+            if !socket.may_send() {
+                return Err(NetworkError::BrokenPipe);
+            }
+            match socket.send_slice(data) {
+                Ok(0) if !data.is_empty() => Err(NetworkError::WouldBlock),
+                Ok(amt) => Ok(amt),
+                Err(tcp::SendError::InvalidState) => Err(NetworkError::BrokenPipe),
+            }
+        })
+    }
+
+    /// What was sent is in the buffer of the connection, which the stack
+    /// sends as fast as the peer takes it.
+    fn try_flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.shutdown(Shutdown::Both)
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        self.recv(buf, false)
+    }
+
+    fn try_peek(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        self.recv(buf, true)
+    }
+}
+
+impl VirtualTcpSocket for StackTcpSocket {
+    /// The buffers of a connection are sized once it's opened.
+    fn set_recv_buf_size(&mut self, _size: usize) -> Result<()> {
+        Ok(())
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        Ok(self.with(|socket, _| socket.recv_capacity()))
+    }
+
+    fn set_send_buf_size(&mut self, _size: usize) -> Result<()> {
+        Ok(())
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        Ok(self.with(|socket, _| socket.send_capacity()))
+    }
+
+    fn set_nodelay(&mut self, nodelay: bool) -> Result<()> {
+        self.with(|socket, _| socket.set_nagle_enabled(!nodelay));
+        Ok(())
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        Ok(self.with(|socket, _| !socket.nagle_enabled()))
+    }
+
+    fn set_keepalive(&mut self, keepalive: bool) -> Result<()> {
+        let interval = keepalive.then_some(self.keepalive_interval.into());
+        self.with(|socket, _| socket.set_keep_alive(interval));
+        Ok(())
+    }
+
+    fn keepalive(&self) -> Result<bool> {
+        Ok(self.with(|socket, _| socket.keep_alive().is_some()))
+    }
+
+    /// The probes are sent at a single interval, which is the one between
+    /// the probes if given, and the connection times out after that many
+    /// intervals whatever the retries.
+    fn set_keepalive_params(&mut self, params: TcpKeepalive) -> Result<()> {
+        let Some(interval) = params.interval.or(params.idle) else {
+            return Ok(());
+        };
+        self.keepalive_interval = interval;
+        self.with(|socket, _| {
+            if socket.keep_alive().is_some() {
+                socket.set_keep_alive(Some(interval.into()));
+            }
+        });
+        Ok(())
+    }
+
+    fn set_dontroute(&mut self, _dontroute: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn dontroute(&self) -> Result<bool> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        Ok(self.peer)
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        self.with(|socket, watch| {
+            if matches!(how, Shutdown::Read | Shutdown::Both) {
+                watch.read_shutdown = true;
+            }
+            if matches!(how, Shutdown::Write | Shutdown::Both) {
+                socket.close();
+            }
+        });
+        Ok(())
+    }
+
+    fn is_closed(&self) -> bool {
+        self.with(|socket, _| socket.state() == tcp::State::Closed)
+    }
+}
+
+/// A TCP listener of the stack, whose connections nobody accepted are
+/// dropped with it.
+#[derive(Debug)]
+pub struct StackTcpListener {
+    stack: Arc<Stack>,
+    addr: SocketAddr,
+}
+
+impl StackTcpListener {
+    pub(super) fn listen(stack: &Arc<Stack>, addr: SocketAddr) -> Result<Self> {
+        let addr = stack.with(|state| {
+            check_local_ip(state, addr.ip())?;
+            let port = match addr.port() {
+                0 => state
+                    .ephemeral_port(tcp_port_in_use)
+                    .ok_or(NetworkError::AddressInUse)?,
+                port if tcp_port_in_use(state, port) => return Err(NetworkError::AddressInUse),
+                port => port,
+            };
+            let addr = SocketAddr::new(addr.ip(), port);
+            let endpoint = listen_endpoint(addr);
+            let sockets = (0..LISTEN_BACKLOG)
+                .map(|_| {
+                    let mut socket = tcp_socket();
+                    socket
+                        .listen(endpoint)
+                        .map_err(|_| NetworkError::AddressNotAvailable)?;
+                    Ok(state.sockets.add(socket))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            state
+                .listeners
+                .insert(port, Listener::new(endpoint, sockets));
+            Ok(addr)
+        })?;
+        Ok(Self {
+            stack: stack.clone(),
+            addr,
+        })
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut StackState, &mut Listener) -> R) -> R {
+        self.stack.with(|state| {
+            let mut listener = state
+                .listeners
+                .remove(&self.addr.port())
+                .expect("a listener lives as long as its handle");
+            let ret = f(state, &mut listener);
+            state.listeners.insert(self.addr.port(), listener);
+            ret
+        })
+    }
+
+    /// How many connections wait to be accepted.
+    fn pending(state: &StackState, listener: &Listener) -> usize {
+        listener
+            .sockets
+            .iter()
+            .filter(|handle| is_accepted(state.sockets.get(**handle)))
+            .count()
+    }
+}
+
+impl Drop for StackTcpListener {
+    /// Once the sockets are gone, the stack resets the connections their
+    /// peers still make.
+    fn drop(&mut self) {
+        self.stack.with(|state| {
+            let listener = state.listeners.remove(&self.addr.port());
+            for handle in listener.into_iter().flat_map(|listener| listener.sockets) {
+                state.sockets.remove(handle);
+            }
+        });
+    }
+}
+
+impl VirtualIoSource for StackTcpListener {
+    fn remove_handler(&mut self) {
+        self.with(|_, listener| listener.handler.take());
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.with(|state, listener| match Self::pending(state, listener) {
+            0 => {
+                listener.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            pending => Poll::Ready(Ok(pending)),
+        })
+    }
+
+    fn poll_write_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        Poll::Pending
+    }
+}
+
+impl VirtualTcpListener for StackTcpListener {
+    fn try_accept(&mut self) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        let (handle, local, peer) = self.with(|state, listener| {
+            let idx = listener
+                .sockets
+                .iter()
+                .position(|handle| is_accepted(state.sockets.get(*handle)))
+                .ok_or(NetworkError::WouldBlock)?;
+
+            // The connection makes room for another one
+            let mut socket = tcp_socket();
+            socket
+                .listen(listener.endpoint)
+                .map_err(|_| NetworkError::AddressNotAvailable)?;
+            let handle = std::mem::replace(&mut listener.sockets[idx], state.sockets.add(socket));
+
+            state.watches.insert(handle, Watch::new(Kind::Tcp));
+            let socket = state.tcp(handle);
+            let endpoints = socket.local_endpoint().zip(socket.remote_endpoint());
+            let (local, peer) = endpoints.ok_or(NetworkError::ConnectionAborted)?;
+            Ok((handle, socket_addr(local), socket_addr(peer)))
+        })?;
+        let socket = StackTcpSocket {
+            stack: self.stack.clone(),
+            handle,
+            local,
+            peer,
+            keepalive_interval: DEFAULT_KEEPALIVE_INTERVAL,
+        };
+        Ok((Box::new(socket), peer))
+    }
+
+    fn set_handler(&mut self, mut handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.with(|state, listener| {
+            if Self::pending(state, listener) > 0 {
+                handler.push_interest(InterestType::Readable);
+            }
+            listener.handler = Some(handler);
+        });
+        Ok(())
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn set_ttl(&mut self, _ttl: u8) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        Err(NetworkError::Unsupported)
+    }
+}
+
+/// A UDP socket of the stack.
+#[derive(Debug)]
+pub struct StackUdpSocket {
+    stack: Arc<Stack>,
+    handle: SocketHandle,
+    addr: SocketAddr,
+}
+
+impl StackUdpSocket {
+    pub(super) fn bind(stack: &Arc<Stack>, addr: SocketAddr) -> Result<Self> {
+        let (handle, addr) = stack.with(|state| {
+            check_local_ip(state, addr.ip())?;
+            let port = match addr.port() {
+                0 => state
+                    .ephemeral_port(|state, port| state.udp_ports.contains(&port))
+                    .ok_or(NetworkError::AddressInUse)?,
+                port if state.udp_ports.contains(&port) => return Err(NetworkError::AddressInUse),
+                port => port,
+            };
+            let addr = SocketAddr::new(addr.ip(), port);
+            let buffer = || {
+                udp::PacketBuffer::new(
+                    vec![udp::PacketMetadata::EMPTY; DGRAM_BUF_COUNT],
+                    vec![0; DGRAM_BUF_SIZE],
+                )
+            };
+            let mut socket = udp::Socket::new(buffer(), buffer());
+            socket
+                .bind(listen_endpoint(addr))
+                .map_err(|_| NetworkError::AddressNotAvailable)?;
+            let handle = state.sockets.add(socket);
+            state.watches.insert(handle, Watch::new(Kind::Udp));
+            state.udp_ports.insert(port);
+            Ok((handle, addr))
+        })?;
+        Ok(Self {
+            stack: stack.clone(),
+            handle,
+            addr,
+        })
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut udp::Socket<'static>, &mut Watch) -> R) -> R {
+        self.stack.with(|state| {
+            let socket = state.sockets.get_mut(self.handle);
+            let watch = state
+                .watches
+                .get_mut(&self.handle)
+                .expect("the watch of a socket lives as long as its handle");
+            f(socket, watch)
+        })
+    }
+}
+
+impl Drop for StackUdpSocket {
+    fn drop(&mut self) {
+        self.stack.with(|state| {
+            state.sockets.remove(self.handle);
+            state.watches.remove(&self.handle);
+            state.udp_ports.remove(&self.addr.port());
+        });
+    }
+}
+
+impl VirtualIoSource for StackUdpSocket {
+    fn remove_handler(&mut self) {
+        self.with(|_, watch| watch.interest.handler.take());
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.with(|socket, watch| match socket.peek() {
+            Ok((data, _)) => Poll::Ready(Ok(data.len())),
+            Err(_) => {
+                watch.interest.read_waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.with(|socket, watch| {
+            if socket.can_send() {
+                return Poll::Ready(Ok(socket.payload_send_capacity()));
+            }
+            watch.interest.write_waker = Some(cx.waker().clone());
+            Poll::Pending
+        })
+    }
+}
+
+impl VirtualSocket for StackUdpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        let ttl = hop_limit(ttl)?;
+        self.with(|socket, _| socket.set_hop_limit(Some(ttl)));
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        let ttl = self.with(|socket, _| socket.hop_limit());
+        Ok(ttl.unwrap_or(DEFAULT_HOP_LIMIT).into())
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(SocketStatus::Opened)
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.with(|_, watch| watch.set_handler(handler));
+        Ok(())
+    }
+}
+
+impl VirtualConnectionlessSocket for StackUdpSocket {
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        self.with(|socket, _| {
+            if data.len() > socket.payload_send_capacity() {
+                return Err(NetworkError::InvalidInput);
+            }
+            match socket.send_slice(data, IpEndpoint::from(addr)) {
+                Ok(()) => Ok(data.len()),
+                Err(udp::SendError::BufferFull) => Err(NetworkError::WouldBlock),
+                Err(udp::SendError::Unaddressable) => Err(NetworkError::AddressNotAvailable),
+            }
+        })
+    }
+
+    /// What doesn't fit in the buffer is discarded.
+    fn try_recv_from(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)> {
+        self.with(|socket, _| match socket.recv() {
+            Ok((data, meta)) => Ok((copy_to(buf, data), socket_addr(meta.endpoint))),
+            Err(_) => Err(NetworkError::WouldBlock),
+        })
+    }
+
+    fn try_peek_from(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)> {
+        self.with(|socket, _| match socket.peek() {
+            Ok((data, meta)) => Ok((copy_to(buf, data), socket_addr(meta.endpoint))),
+            Err(_) => Err(NetworkError::WouldBlock),
+        })
+    }
+}
+
+impl VirtualUdpSocket for StackUdpSocket {
+    /// The datagrams sent to a broadcast address always go out.
+    fn set_broadcast(&mut self, _broadcast: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn set_multicast_loop_v4(&mut self, _val: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn set_multicast_loop_v6(&mut self, _val: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn set_multicast_ttl_v4(&mut self, _ttl: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn join_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn join_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        Ok(None)
+    }
+}
+
+/// An ICMP socket of the stack, which exchanges echo messages like the
+/// ping sockets of Linux: the identifier of the requests sent is the one of
+/// the socket, and only the replies to them are received.
+#[derive(Debug)]
+pub struct StackIcmpSocket {
+    stack: Arc<Stack>,
+    handle: SocketHandle,
+    addr: IpAddr,
+    ident: u16,
+}
+
+impl StackIcmpSocket {
+    pub(super) fn bind(stack: &Arc<Stack>, addr: IpAddr) -> Result<Self> {
+        let (handle, ident) = stack.with(|state| {
+            check_local_ip(state, addr)?;
+            let ident = state.icmp_ident().ok_or(NetworkError::AddressInUse)?;
+            let buffer = || {
+                icmp::PacketBuffer::new(
+                    vec![icmp::PacketMetadata::EMPTY; DGRAM_BUF_COUNT],
+                    vec![0; DGRAM_BUF_SIZE],
+                )
+            };
+            let mut socket = icmp::Socket::new(buffer(), buffer());
+            if socket.bind(icmp::Endpoint::Ident(ident)).is_err() {
+                state.icmp_idents.remove(&ident);
+                return Err(NetworkError::AddressNotAvailable);
+            }
+            let handle = state.sockets.add(socket);
+            state.watches.insert(handle, Watch::new(Kind::Icmp));
+            Ok((handle, ident))
+        })?;
+        Ok(Self {
+            stack: stack.clone(),
+            handle,
+            addr,
+            ident,
+        })
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut icmp::Socket<'static>, &mut Watch) -> R) -> R {
+        self.stack.with(|state| {
+            let socket = state.sockets.get_mut(self.handle);
+            let watch = state
+                .watches
+                .get_mut(&self.handle)
+                .expect("the watch of a socket lives as long as its handle");
+            f(socket, watch)
+        })
+    }
+}
+
+impl Drop for StackIcmpSocket {
+    fn drop(&mut self) {
+        self.stack.with(|state| {
+            state.sockets.remove(self.handle);
+            state.watches.remove(&self.handle);
+            state.icmp_idents.remove(&self.ident);
+        });
+    }
+}
+
+impl VirtualIoSource for StackIcmpSocket {
+    fn remove_handler(&mut self) {
+        self.with(|_, watch| watch.interest.handler.take());
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.with(|socket, watch| {
+            if socket.can_recv() {
+                return Poll::Ready(Ok(1));
+            }
+            watch.interest.read_waker = Some(cx.waker().clone());
+            Poll::Pending
+        })
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.with(|socket, watch| {
+            if socket.can_send() {
+                return Poll::Ready(Ok(DGRAM_BUF_SIZE));
+            }
+            watch.interest.write_waker = Some(cx.waker().clone());
+            Poll::Pending
+        })
+    }
+}
+
+impl VirtualSocket for StackIcmpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        let ttl = hop_limit(ttl)?;
+        self.with(|socket, _| socket.set_hop_limit(Some(ttl)));
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        let ttl = self.with(|socket, _| socket.hop_limit());
+        Ok(ttl.unwrap_or(DEFAULT_HOP_LIMIT).into())
+    }
+
+    /// The port of the socket is the identifier of its echo requests.
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(SocketAddr::new(self.addr, self.ident))
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(SocketStatus::Opened)
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.with(|_, watch| watch.set_handler(handler));
+        Ok(())
+    }
+}
+
+impl VirtualConnectionlessSocket for StackIcmpSocket {
+    /// Sends an ICMP message without its IP header, whose checksum is
+    /// computed by the stack.
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        const ECHO_REQUEST_V4: u8 = 8;
+        const ECHO_REQUEST_V6: u8 = 128;
+
+        if data.len() < 8 {
+            return Err(NetworkError::InvalidInput);
+        }
+        let mut packet = data.to_vec();
+        let echo_request = match addr {
+            SocketAddr::V4(_) => ECHO_REQUEST_V4,
+            SocketAddr::V6(_) => ECHO_REQUEST_V6,
+        };
+        if packet[0] == echo_request {
+            packet[4..6].copy_from_slice(&self.ident.to_be_bytes());
+        }
+        self.with(
+            |socket, _| match socket.send_slice(&packet, IpAddress::from(addr.ip())) {
+                Ok(()) => Ok(data.len()),
+                Err(icmp::SendError::BufferFull) => Err(NetworkError::WouldBlock),
+                Err(icmp::SendError::Unaddressable) => Err(NetworkError::AddressNotAvailable),
+            },
+        )
+    }
+
+    fn try_recv_from(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)> {
+        self.with(|socket, _| match socket.recv() {
+            Ok((data, addr)) => Ok((copy_to(buf, data), SocketAddr::new(addr.into(), 0))),
+            Err(_) => Err(NetworkError::WouldBlock),
+        })
+    }
+}
+
+impl VirtualIcmpSocket for StackIcmpSocket {}
@@ -0,0 +1,742 @@
+//! Encryption of TCP streams with TLS, which the runtime terminates on
+//! behalf of a program, so that the program speaks in the clear to a
+//! socket whose peer is reached over TLS.
+//!
+//! The session is [rustls](https://docs.rs/rustls), run over the socket it
+//! wraps: the handshake happens as the socket is first used, and a failure
+//! of the session, such as a certificate which isn't trusted, fails what
+//! the socket does next with [`NetworkError::ConnectionAborted`].
+
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    mem::MaybeUninit,
+    net::{Shutdown, SocketAddr},
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use rustls::{
+    crypto::ring::default_provider,
+    pki_types::{CertificateDer, ServerName},
+    ClientConfig, ClientConnection, RootCertStore,
+};
+use virtual_mio::InterestHandler;
+
+use crate::{
+    io_err_into_net_error, net_error_into_io_err, NetworkError, Result, SocketStatus,
+    StreamSecurity, VirtualConnectedSocket, VirtualIoSource, VirtualSocket, VirtualTcpSocket,
+};
+
+/// The most plaintext a record carries, which is as much as the socket reads
+/// ahead of the program when it peeks, and tells it can write.
+const MAX_RECORD: usize = 16384;
+
+/// Which servers the streams encrypted with TLS trust.
+#[derive(Clone)]
+pub struct TlsConfig {
+    client: Arc<ClientConfig>,
+}
+
+impl fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsConfig").finish_non_exhaustive()
+    }
+}
+
+impl TlsConfig {
+    /// Trusts the certificate authorities of the web, the ones Mozilla
+    /// trusts.
+    pub fn webpki() -> Self {
+        let mut roots = RootCertStore::empty();
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        Self::new(roots)
+    }
+
+    /// Trusts the certificates `roots` only, given in DER, such as the
+    /// authority of a private network.
+    pub fn with_roots(roots: impl IntoIterator<Item = Vec<u8>>) -> Result<Self> {
+        let mut store = RootCertStore::empty();
+        for root in roots {
+            store
+                .add(CertificateDer::from(root))
+                .map_err(|_| NetworkError::InvalidInput)?;
+        }
+        Ok(Self::new(store))
+    }
+
+    fn new(roots: RootCertStore) -> Self {
+        let client = ClientConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()
+            .expect("the default provider supports the default versions")
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        Self {
+            client: Arc::new(client),
+        }
+    }
+
+    /// Starts a session with `server_name`, a host name or an IP address,
+    /// at the level of `security`: the classic encryption, or any, is TLS
+    /// 1.2 or 1.3, while a double encryption isn't supported.
+    pub fn session(&self, server_name: &str, security: StreamSecurity) -> Result<TlsSession> {
+        match security {
+            StreamSecurity::Unencrypted => return Ok(TlsSession { conn: None }),
+            StreamSecurity::AnyEncyption | StreamSecurity::ClassicEncryption => {}
+            StreamSecurity::DoubleEncryption => return Err(NetworkError::Unsupported),
+        }
+        let server_name = ServerName::try_from(server_name.to_string())
+            .map_err(|_| NetworkError::InvalidInput)?;
+        let conn = ClientConnection::new(self.client.clone(), server_name)
+            .map_err(|_| NetworkError::InvalidInput)?;
+        Ok(TlsSession { conn: Some(conn) })
+    }
+}
+
+/// A session which didn't start yet, see [`TlsConfig::session`].
+#[derive(Debug)]
+pub struct TlsSession {
+    conn: Option<ClientConnection>,
+}
+
+impl TlsSession {
+    /// Runs the session over `socket`, a connected stream, which is left as
+    /// it is when the session is unencrypted.
+    pub fn wrap(
+        self,
+        socket: Box<dyn VirtualTcpSocket + Sync>,
+    ) -> Box<dyn VirtualTcpSocket + Sync> {
+        match self.conn {
+            Some(conn) => Box::new(TlsTcpSocket {
+                inner: socket,
+                conn,
+                peeked: Vec::new(),
+                eof: false,
+                error: None,
+            }),
+            None => socket,
+        }
+    }
+}
+
+/// Reads and writes the records of a session from and to its stream.
+struct Records<'a>(&'a mut (dyn VirtualTcpSocket + Sync));
+
+impl Read for Records<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Initialized bytes are valid uninitialized ones
+        let buf = unsafe { &mut *(buf as *mut [u8] as *mut [MaybeUninit<u8>]) };
+        self.0.try_recv(buf).map_err(net_error_into_io_err)
+    }
+}
+
+impl Write for Records<'_> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.0.try_send(data).map_err(net_error_into_io_err)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.try_flush().map_err(net_error_into_io_err)
+    }
+}
+
+/// A TCP stream encrypted with TLS, whose plaintext is what the program
+/// sends and receives.
+#[derive(Debug)]
+struct TlsTcpSocket {
+    inner: Box<dyn VirtualTcpSocket + Sync>,
+    conn: ClientConnection,
+    /// The plaintext the program peeked at, which it receives first
+    peeked: Vec<u8>,
+    /// Whether the stream under the session ended
+    eof: bool,
+    /// The failure of the session, which fails all that follows
+    error: Option<NetworkError>,
+}
+
+impl TlsTcpSocket {
+    /// Moves the records between the session and the stream, as far as the
+    /// stream allows without blocking, and returns whether any moved.
+    fn drive(&mut self) -> Result<bool> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        let mut moved = false;
+        loop {
+            let mut progress = false;
+            while self.conn.wants_write() {
+                match self.conn.write_tls(&mut Records(self.inner.as_mut())) {
+                    Ok(0) => break,
+                    Ok(_) => progress = true,
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(err) => return Err(self.fail(io_err_into_net_error(err))),
+                }
+            }
+            if !self.eof && self.conn.wants_read() {
+                match self.conn.read_tls(&mut Records(self.inner.as_mut())) {
+                    Ok(amt) => {
+                        self.eof = amt == 0;
+                        progress = true;
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                    Err(err) => return Err(self.fail(io_err_into_net_error(err))),
+                }
+                if self.conn.process_new_packets().is_err() {
+                    // The alert telling the peer why goes out if it can,
+                    // after what the session queued before it
+                    while self.conn.wants_write() {
+                        match self.conn.write_tls(&mut Records(self.inner.as_mut())) {
+                            Ok(amt) if amt > 0 => {}
+                            _ => break,
+                        }
+                    }
+                    return Err(self.fail(NetworkError::ConnectionAborted));
+                }
+            }
+            if !progress {
+                return Ok(moved);
+            }
+            moved = true;
+        }
+    }
+
+    fn fail(&mut self, err: NetworkError) -> NetworkError {
+        self.error = Some(err);
+        err
+    }
+
+    /// Reads the plaintext received so far into `buf`.
+    fn read_plaintext(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.drive()?;
+        match self.conn.reader().read(buf) {
+            Ok(amt) => Ok(amt),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Err(NetworkError::WouldBlock),
+            // The stream ended without the session being closed, which the
+            // protocol above tells from a stream that was cut, as most
+            // clients let it
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(0),
+            Err(err) => Err(io_err_into_net_error(err)),
+        }
+    }
+
+    /// Reads ahead of the program, unless it did already.
+    fn fill_peeked(&mut self) -> Result<usize> {
+        if self.peeked.is_empty() {
+            let mut buf = vec![0; MAX_RECORD];
+            let amt = self.read_plaintext(&mut buf)?;
+            buf.truncate(amt);
+            self.peeked = buf;
+        }
+        Ok(self.peeked.len())
+    }
+
+    /// Moves the records once the stream is ready, and tells whether it's
+    /// worth polling again. A stream which was ready for nothing is polled
+    /// again later, rather than right away.
+    fn poll_again(&mut self, cx: &mut Context<'_>) -> Result<bool> {
+        let moved = self.drive()?;
+        if !moved {
+            cx.waker().wake_by_ref();
+        }
+        Ok(moved)
+    }
+
+    /// Sends the end of the session, as far as the stream allows.
+    fn close_session(&mut self) {
+        self.conn.send_close_notify();
+        self.drive().ok();
+    }
+}
+
+impl VirtualIoSource for TlsTcpSocket {
+    fn remove_handler(&mut self) {
+        self.inner.remove_handler();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        loop {
+            match self.fill_peeked() {
+                Err(NetworkError::WouldBlock) => {}
+                res => return Poll::Ready(res),
+            }
+            match self.inner.poll_read_ready(cx) {
+                Poll::Ready(Ok(_)) => {}
+                res => return res,
+            }
+            match self.poll_again(cx) {
+                Ok(true) => {}
+                Ok(false) => return Poll::Pending,
+                Err(err) => return Poll::Ready(Err(err)),
+            }
+        }
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        loop {
+            if let Err(err) = self.drive() {
+                return Poll::Ready(Err(err));
+            }
+            // What's written during the handshake waits in the session
+            if !self.conn.wants_write() {
+                return Poll::Ready(Ok(MAX_RECORD));
+            }
+            match self.inner.poll_write_ready(cx) {
+                Poll::Ready(Ok(_)) => {}
+                res => return res,
+            }
+            match self.poll_again(cx) {
+                Ok(true) => {}
+                Ok(false) => return Poll::Pending,
+                Err(err) => return Poll::Ready(Err(err)),
+            }
+        }
+    }
+}
+
+impl VirtualSocket for TlsTcpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.inner.set_handler(handler)
+    }
+}
+
+impl VirtualConnectedSocket for TlsTcpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.inner.set_linger(linger)
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        self.inner.linger()
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        self.drive()?;
+        let amt = self
+            .conn
+            .writer()
+            .write(data)
+            .map_err(io_err_into_net_error)?;
+        // A failure to send it shows on the next call
+        self.drive().ok();
+        match amt {
+            0 if !data.is_empty() => Err(NetworkError::WouldBlock),
+            amt => Ok(amt),
+        }
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        self.drive()?;
+        if self.conn.wants_write() {
+            return Err(NetworkError::WouldBlock);
+        }
+        self.inner.try_flush()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.close_session();
+        self.inner.close()
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        if !self.peeked.is_empty() {
+            let amt = self.peeked.len().min(buf.len());
+            for (dst, src) in buf.iter_mut().zip(self.peeked.drain(..amt)) {
+                dst.write(src);
+            }
+            return Ok(amt);
+        }
+        buf.fill(MaybeUninit::new(0));
+        // The bytes were just initialized
+        let buf = unsafe { &mut *(buf as *mut [MaybeUninit<u8>] as *mut [u8]) };
+        self.read_plaintext(buf)
+    }
+
+    fn try_peek(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        let amt = self.fill_peeked()?.min(buf.len());
+        for (dst, src) in buf.iter_mut().zip(&self.peeked[..amt]) {
+            dst.write(*src);
+        }
+        Ok(amt)
+    }
+}
+
+impl VirtualTcpSocket for TlsTcpSocket {
+    fn set_recv_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_recv_buf_size(size)
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        self.inner.recv_buf_size()
+    }
+
+    fn set_send_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_send_buf_size(size)
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        self.inner.send_buf_size()
+    }
+
+    fn set_nodelay(&mut self, nodelay: bool) -> Result<()> {
+        self.inner.set_nodelay(nodelay)
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        self.inner.nodelay()
+    }
+
+    fn set_keepalive(&mut self, keepalive: bool) -> Result<()> {
+        self.inner.set_keepalive(keepalive)
+    }
+
+    fn keepalive(&self) -> Result<bool> {
+        self.inner.keepalive()
+    }
+
+    fn set_keepalive_params(&mut self, params: crate::TcpKeepalive) -> Result<()> {
+        self.inner.set_keepalive_params(params)
+    }
+
+    fn set_dontroute(&mut self, dontroute: bool) -> Result<()> {
+        self.inner.set_dontroute(dontroute)
+    }
+
+    fn dontroute(&self) -> Result<bool> {
+        self.inner.dontroute()
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        self.inner.addr_peer()
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        if matches!(how, Shutdown::Write | Shutdown::Both) {
+            self.close_session();
+        }
+        self.inner.shutdown(how)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+    };
+
+    use rustls::{
+        pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer},
+        ServerConfig, ServerConnection,
+    };
+
+    use super::*;
+
+    /// The bytes sent to one end of a pipe, which the other end receives.
+    type Bytes = Arc<Mutex<VecDeque<u8>>>;
+
+    /// One end of a stream between the client and the server.
+    #[derive(Debug)]
+    struct PipeEnd {
+        rx: Bytes,
+        tx: Bytes,
+    }
+
+    fn pipe() -> (PipeEnd, PipeEnd) {
+        let a: Bytes = Arc::default();
+        let b: Bytes = Arc::default();
+        let end = PipeEnd {
+            rx: a.clone(),
+            tx: b.clone(),
+        };
+        (end, PipeEnd { rx: b, tx: a })
+    }
+
+    impl Read for PipeEnd {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let mut rx = self.rx.lock().unwrap();
+            if rx.is_empty() {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            rx.read(buf)
+        }
+    }
+
+    impl Write for PipeEnd {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            self.tx.lock().unwrap().extend(data);
+            Ok(data.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl VirtualIoSource for PipeEnd {
+        fn remove_handler(&mut self) {}
+
+        fn poll_read_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+            match self.rx.lock().unwrap().len() {
+                0 => Poll::Pending,
+                len => Poll::Ready(Ok(len)),
+            }
+        }
+
+        fn poll_write_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+            Poll::Ready(Ok(MAX_RECORD))
+        }
+    }
+
+    impl VirtualSocket for PipeEnd {
+        fn set_ttl(&mut self, _ttl: u32) -> Result<()> {
+            Ok(())
+        }
+
+        fn ttl(&self) -> Result<u32> {
+            Ok(64)
+        }
+
+        fn addr_local(&self) -> Result<SocketAddr> {
+            Err(NetworkError::Unsupported)
+        }
+
+        fn status(&self) -> Result<SocketStatus> {
+            Ok(SocketStatus::Opened)
+        }
+
+        fn set_handler(&mut self, _handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl VirtualConnectedSocket for PipeEnd {
+        fn set_linger(&mut self, _linger: Option<Duration>) -> Result<()> {
+            Ok(())
+        }
+
+        fn linger(&self) -> Result<Option<Duration>> {
+            Ok(None)
+        }
+
+        fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+            self.write(data).map_err(io_err_into_net_error)
+        }
+
+        fn try_flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn close(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+            let mut rx = self.rx.lock().unwrap();
+            if rx.is_empty() {
+                return Err(NetworkError::WouldBlock);
+            }
+            let amt = rx.len().min(buf.len());
+            for (dst, src) in buf.iter_mut().zip(rx.drain(..amt)) {
+                dst.write(src);
+            }
+            Ok(amt)
+        }
+    }
+
+    impl VirtualTcpSocket for PipeEnd {
+        fn set_recv_buf_size(&mut self, _size: usize) -> Result<()> {
+            Ok(())
+        }
+
+        fn recv_buf_size(&self) -> Result<usize> {
+            Ok(MAX_RECORD)
+        }
+
+        fn set_send_buf_size(&mut self, _size: usize) -> Result<()> {
+            Ok(())
+        }
+
+        fn send_buf_size(&self) -> Result<usize> {
+            Ok(MAX_RECORD)
+        }
+
+        fn set_nodelay(&mut self, _nodelay: bool) -> Result<()> {
+            Ok(())
+        }
+
+        fn nodelay(&self) -> Result<bool> {
+            Ok(true)
+        }
+
+        fn set_keepalive(&mut self, _keepalive: bool) -> Result<()> {
+            Ok(())
+        }
+
+        fn keepalive(&self) -> Result<bool> {
+            Ok(false)
+        }
+
+        fn set_dontroute(&mut self, _dontroute: bool) -> Result<()> {
+            Ok(())
+        }
+
+        fn dontroute(&self) -> Result<bool> {
+            Ok(false)
+        }
+
+        fn addr_peer(&self) -> Result<SocketAddr> {
+            Err(NetworkError::Unsupported)
+        }
+
+        fn shutdown(&mut self, _how: Shutdown) -> Result<()> {
+            Ok(())
+        }
+
+        fn is_closed(&self) -> bool {
+            false
+        }
+    }
+
+    /// A rustls server for `localhost` on one end of a pipe, and the
+    /// certificate it presents.
+    struct Server {
+        conn: ServerConnection,
+        end: PipeEnd,
+        cert: Vec<u8>,
+    }
+
+    impl Server {
+        fn new(end: PipeEnd) -> Self {
+            let certified = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+            let cert = certified.cert.der().to_vec();
+            let key = PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der());
+            let config = ServerConfig::builder_with_provider(Arc::new(default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_no_client_auth()
+                .with_single_cert(vec![cert.clone().into()], PrivateKeyDer::Pkcs8(key))
+                .unwrap();
+            Self {
+                conn: ServerConnection::new(Arc::new(config)).unwrap(),
+                end,
+                cert,
+            }
+        }
+
+        /// Moves the records between the session and the pipe, and returns
+        /// the plaintext received so far, or why the session failed.
+        fn pump(&mut self) -> std::result::Result<Vec<u8>, rustls::Error> {
+            while self.conn.wants_write() {
+                self.conn.write_tls(&mut self.end).unwrap();
+            }
+            match self.conn.read_tls(&mut self.end) {
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                Err(err) => panic!("{err}"),
+            }
+            self.conn.process_new_packets()?;
+            while self.conn.wants_write() {
+                self.conn.write_tls(&mut self.end).unwrap();
+            }
+            let mut plaintext = Vec::new();
+            self.conn.reader().read_to_end(&mut plaintext).ok();
+            Ok(plaintext)
+        }
+    }
+
+    /// Receives what the client was sent, pumping the server meanwhile.
+    fn recv(client: &mut (dyn VirtualTcpSocket + Sync), server: &mut Server) -> Result<Vec<u8>> {
+        for _ in 0..16 {
+            let mut buf = [MaybeUninit::new(0); 64];
+            match client.try_recv(&mut buf) {
+                Ok(amt) => {
+                    return Ok(buf[..amt]
+                        .iter()
+                        .map(|b| unsafe { b.assume_init() })
+                        .collect())
+                }
+                Err(NetworkError::WouldBlock) => assert!(server.pump().unwrap().is_empty()),
+                Err(err) => return Err(err),
+            }
+        }
+        panic!("the client received nothing");
+    }
+
+    #[test]
+    fn handshake_and_traffic_with_a_trusted_server() {
+        let (client_end, server_end) = pipe();
+        let mut server = Server::new(server_end);
+        let mut client = TlsConfig::with_roots([server.cert.clone()])
+            .unwrap()
+            .session("localhost", StreamSecurity::ClassicEncryption)
+            .unwrap()
+            .wrap(Box::new(client_end));
+
+        // What's sent during the handshake waits in the session
+        assert_eq!(client.try_send(b"ping").unwrap(), 4);
+        let mut received = Vec::new();
+        for _ in 0..16 {
+            received.extend(server.pump().unwrap());
+            client.try_flush().ok();
+        }
+        assert_eq!(received, b"ping");
+        assert!(!server.conn.is_handshaking());
+
+        server.conn.writer().write_all(b"pong").unwrap();
+        assert_eq!(recv(client.as_mut(), &mut server).unwrap(), b"pong");
+
+        // The end of the session reaches the server, and the client reads
+        // the end of the stream once the server closes too
+        client.close().unwrap();
+        server.pump().unwrap();
+        server.conn.send_close_notify();
+        assert_eq!(recv(client.as_mut(), &mut server).unwrap(), b"");
+    }
+
+    #[test]
+    fn handshake_with_an_untrusted_server_aborts() {
+        let (client_end, server_end) = pipe();
+        let mut server = Server::new(server_end);
+        let other = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+        let mut client = TlsConfig::with_roots([other.cert.der().to_vec()])
+            .unwrap()
+            .session("localhost", StreamSecurity::ClassicEncryption)
+            .unwrap()
+            .wrap(Box::new(client_end));
+
+        assert_eq!(
+            recv(client.as_mut(), &mut server),
+            Err(NetworkError::ConnectionAborted)
+        );
+        // The failure sticks to the socket
+        assert_eq!(
+            client.try_send(b"ping"),
+            Err(NetworkError::ConnectionAborted)
+        );
+        // and the server is told why
+        assert!(matches!(
+            server.pump(),
+            Err(rustls::Error::AlertReceived(_))
+        ));
+    }
+}
@@ -277,11 +277,27 @@ pub mod net {
         pub expires_at: OptionTimestamp,
     }
 
+    /// The lease of the addresses acquired with DHCP, whose addresses and
+    /// DNS servers are listed apart.
+    #[derive(Debug, Copy, Clone, ValueType)]
+    #[repr(C)]
+    pub struct DhcpLease {
+        /// Changes whenever a lease is acquired, changed or lost
+        pub serial: u64,
+        /// The default gateway, unspecified when there's none
+        pub gateway: __wasi_addr_t,
+        /// The DHCP server which gave the lease, unspecified when there's none
+        pub server: __wasi_addr_t,
+        pub lease_time: OptionTimestamp,
+        pub expires_in: OptionTimestamp,
+    }
+
     pub const __WASI_SOCK_RECV_INPUT_PEEK: RiFlags = 1 << 0;
     pub const __WASI_SOCK_RECV_INPUT_WAITALL: RiFlags = 1 << 1;
     pub const __WASI_SOCK_RECV_INPUT_DATA_TRUNCATED: RiFlags = 1 << 2;
 
     pub const __WASI_SOCK_RECV_OUTPUT_DATA_TRUNCATED: RoFlags = 1 << 0;
+    pub const __WASI_SOCK_RECV_OUTPUT_CONTROL_TRUNCATED: RoFlags = 1 << 1;
 
     pub const __WASI_SHUT_RD: SdFlags = 1 << 0;
     pub const __WASI_SHUT_WR: SdFlags = 1 << 1;
@@ -1029,6 +1029,9 @@ pub enum Sockoption {
     MulticastTtlV4,
     Type,
     Proto,
+    KeepAliveIdle,
+    KeepAliveInterval,
+    KeepAliveCount,
 }
 impl core::fmt::Debug for Sockoption {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -1060,6 +1063,11 @@ impl core::fmt::Debug for Sockoption {
             Sockoption::MulticastTtlV4 => f.debug_tuple("Sockoption::MulticastTtlV4").finish(),
             Sockoption::Type => f.debug_tuple("Sockoption::Type").finish(),
             Sockoption::Proto => f.debug_tuple("Sockoption::Proto").finish(),
+            Sockoption::KeepAliveIdle => f.debug_tuple("Sockoption::KeepAliveIdle").finish(),
+            Sockoption::KeepAliveInterval => {
+                f.debug_tuple("Sockoption::KeepAliveInterval").finish()
+            }
+            Sockoption::KeepAliveCount => f.debug_tuple("Sockoption::KeepAliveCount").finish(),
         }
     }
 }
@@ -2973,6 +2981,9 @@ unsafe impl wasmer::FromToNativeWasmType for Sockoption {
             24 => Self::MulticastTtlV4,
             25 => Self::Type,
             26 => Self::Proto,
+            27 => Self::KeepAliveIdle,
+            28 => Self::KeepAliveInterval,
+            29 => Self::KeepAliveCount,
 
             q => {
                 tracing::debug!("could not serialize number {q} to enum Sockoption");
@@ -156,6 +156,9 @@ impl std::fmt::Display for Sockoption {
             Self::MulticastTtlV4 => "Sockoption::MulticastTtlV4",
             Self::Type => "Sockoption::Type",
             Self::Proto => "Sockoption::Proto",
+            Self::KeepAliveIdle => "Sockoption::KeepAliveIdle",
+            Self::KeepAliveInterval => "Sockoption::KeepAliveInterval",
+            Self::KeepAliveCount => "Sockoption::KeepAliveCount",
         };
         write!(f, "{}", s)
     }
@@ -5,7 +5,7 @@ use std::mem::MaybeUninit;
 use wasmer::{FromToNativeWasmType, MemorySize, ValueType};
 
 use super::{
-    Errno, ErrnoSignal, EventFdReadwrite, Eventtype, Fd, JoinStatusType, Signal,
+    Errno, ErrnoSignal, EventFdReadwrite, Eventtype, Fd, Filesize, JoinStatusType, Pid, Signal,
     Snapshot0SubscriptionClock, SubscriptionClock, SubscriptionFsReadwrite, Userdata,
 };
 
@@ -425,6 +425,356 @@ unsafe impl wasmer::FromToNativeWasmType for EpollCtl {
     }
 }
 
+#[doc = " Type of an advisory file lock."]
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, num_enum :: TryFromPrimitive, Hash)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub enum LockType {
+    #[doc = " Shared lock, any number of owners can hold it at once."]
+    Read,
+    #[doc = " Exclusive lock."]
+    Write,
+    #[doc = " Releases the lock."]
+    Unlock,
+    #[doc = " Unknown."]
+    Unknown,
+}
+impl core::fmt::Debug for LockType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LockType::Read => f.debug_tuple("F_RDLCK").finish(),
+            LockType::Write => f.debug_tuple("F_WRLCK").finish(),
+            LockType::Unlock => f.debug_tuple("F_UNLCK").finish(),
+            LockType::Unknown => f.debug_tuple("Unknown").finish(),
+        }
+    }
+}
+// TODO: if necessary, must be implemented in wit-bindgen
+unsafe impl ValueType for LockType {
+    #[inline]
+    fn zero_padding_bytes(&self, _bytes: &mut [MaybeUninit<u8>]) {}
+}
+
+unsafe impl wasmer::FromToNativeWasmType for LockType {
+    type Native = i32;
+
+    fn to_native(self) -> Self::Native {
+        self as i32
+    }
+
+    fn from_native(n: Self::Native) -> Self {
+        match n {
+            0 => Self::Read,
+            1 => Self::Write,
+            2 => Self::Unlock,
+
+            q => {
+                tracing::debug!("could not serialize number {q} to enum LockType");
+                Self::Unknown
+            }
+        }
+    }
+
+    fn is_from_store(&self, _store: &impl wasmer::AsStoreRef) -> bool {
+        false
+    }
+}
+
+#[doc = " A byte-range lock on a file."]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileLock {
+    #[doc = " Offset of the first locked byte."]
+    pub start: Filesize,
+    #[doc = " Number of locked bytes, zero when the lock extends to the end of the file."]
+    pub len: Filesize,
+    #[doc = " The process holding the lock."]
+    pub pid: Pid,
+    pub lock_type: LockType,
+}
+unsafe impl ValueType for FileLock {
+    #[inline]
+    fn zero_padding_bytes(&self, _bytes: &mut [MaybeUninit<u8>]) {}
+}
+
+#[doc = " The capacity and usage of a file system, like statfs(2)."]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Statfs {
+    #[doc = " Size of the blocks the other counts are in."]
+    pub block_size: u64,
+    #[doc = " Total number of blocks."]
+    pub blocks: u64,
+    #[doc = " Number of free blocks."]
+    pub blocks_free: u64,
+    #[doc = " Number of free blocks available to the process."]
+    pub blocks_available: u64,
+    #[doc = " Total number of inodes."]
+    pub files: u64,
+    #[doc = " Number of free inodes."]
+    pub files_free: u64,
+    #[doc = " Maximum length of the file names."]
+    pub name_max: u64,
+}
+unsafe impl ValueType for Statfs {
+    #[inline]
+    fn zero_padding_bytes(&self, _bytes: &mut [MaybeUninit<u8>]) {}
+}
+
+#[doc = " The mode and owner of a file."]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fileperms {
+    #[doc = " Permission bits, along with the setuid, setgid and sticky bits, like `st_mode` without the file type."]
+    pub mode: u32,
+    #[doc = " User owning the file."]
+    pub uid: u32,
+    #[doc = " Group owning the file."]
+    pub gid: u32,
+}
+unsafe impl ValueType for Fileperms {
+    #[inline]
+    fn zero_padding_bytes(&self, _bytes: &mut [MaybeUninit<u8>]) {}
+}
+
+wai_bindgen_rust::bitflags::bitflags! {
+    #[doc = " File watching events, with the values of inotify(7)."]
+    #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+    pub struct WatchMask : u32 {
+        #[doc = " File was accessed."]
+        const ACCESS = 0x0000_0001;
+        #[doc = " File was modified."]
+        const MODIFY = 0x0000_0002;
+        #[doc = " Metadata changed."]
+        const ATTRIB = 0x0000_0004;
+        #[doc = " File opened for writing was closed."]
+        const CLOSE_WRITE = 0x0000_0008;
+        #[doc = " File was moved out of the watched directory."]
+        const MOVED_FROM = 0x0000_0040;
+        #[doc = " File was moved into the watched directory."]
+        const MOVED_TO = 0x0000_0080;
+        #[doc = " File or directory created in the watched directory."]
+        const CREATE = 0x0000_0100;
+        #[doc = " File or directory deleted from the watched directory."]
+        const DELETE = 0x0000_0200;
+        #[doc = " The watched file or directory was itself deleted."]
+        const DELETE_SELF = 0x0000_0400;
+        #[doc = " The watched file or directory was itself moved."]
+        const MOVE_SELF = 0x0000_0800;
+        #[doc = " Events were dropped because the event queue overflowed."]
+        const Q_OVERFLOW = 0x0000_4000;
+        #[doc = " The watch was removed."]
+        const IGNORED = 0x0000_8000;
+        #[doc = " The subject of the event is a directory."]
+        const ISDIR = 0x4000_0000;
+    }
+}
+
+wai_bindgen_rust::bitflags::bitflags! {
+    #[doc = " Modes of `fd_fallocate`, with the values of fallocate(2)."]
+    #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+    pub struct FallocateFlags : u32 {
+        #[doc = " The size of the file doesn't change."]
+        const KEEP_SIZE = 0x01;
+        #[doc = " Deallocates the range, which then reads as zeros. Requires `KEEP_SIZE`."]
+        const PUNCH_HOLE = 0x02;
+    }
+}
+
+wai_bindgen_rust::bitflags::bitflags! {
+    #[doc = " Flags of `fd_timer_settime`, with the values of timerfd_settime(2)."]
+    #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+    pub struct TimerFlags : u32 {
+        #[doc = " The expiration is a time of the clock of the timer, rather than a delay."]
+        const ABSTIME = 0x01;
+    }
+}
+
+wai_bindgen_rust::bitflags::bitflags! {
+    #[doc = " Flags of `fd_mmap`, with the values of mmap(2)."]
+    #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+    pub struct MmapFlags : u32 {
+        #[doc = " Changes to the memory are written back to the file."]
+        const SHARED = 0x01;
+        #[doc = " Changes to the memory are private to the process."]
+        const PRIVATE = 0x02;
+    }
+}
+
+wai_bindgen_rust::bitflags::bitflags! {
+    #[doc = " Flags of `fd_msync`, with the values of msync(2)."]
+    #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+    pub struct MsyncFlags : u32 {
+        #[doc = " The changes are written back without waiting for the storage."]
+        const ASYNC = 0x01;
+        #[doc = " The other mappings of the file are updated with the changes."]
+        const INVALIDATE = 0x02;
+        #[doc = " The changes are written back and the file is flushed to the storage."]
+        const SYNC = 0x04;
+    }
+}
+
+#[doc = " Operation of an entry submitted to a ring."]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, num_enum :: TryFromPrimitive, Hash)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub enum RingOp {
+    #[doc = " Does nothing, which completes at once."]
+    Nop,
+    #[doc = " Reads into `iovs` at the cursor of `fd`, like `fd_read`."]
+    Read,
+    #[doc = " Reads into `iovs` at `offset`, like `fd_pread`."]
+    Pread,
+    #[doc = " Writes `iovs` at the cursor of `fd`, like `fd_write`."]
+    Write,
+    #[doc = " Writes `iovs` at `offset`, like `fd_pwrite`."]
+    Pwrite,
+    #[doc = " Synchronizes `fd` with the storage, like `fd_sync`."]
+    Sync,
+    #[doc = " Closes `fd`, like `fd_close`."]
+    Close,
+}
+
+#[doc = " An entry of the submission queue of a ring."]
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct RingSqe<M: MemorySize> {
+    #[doc = " The operation, one of `RingOp`."]
+    pub opcode: u8,
+    #[doc = " The file descriptor the operation is done on."]
+    pub fd: Fd,
+    #[doc = " The vectors of the reads and writes."]
+    pub iovs: M::Offset,
+    #[doc = " Number of vectors in `iovs`."]
+    pub iovs_len: M::Offset,
+    #[doc = " The offset of the positioned reads and writes."]
+    pub offset: Filesize,
+    #[doc = " Returned as is in the completion of the operation."]
+    pub user_data: u64,
+}
+impl<M> core::fmt::Debug for RingSqe<M>
+where
+    M: MemorySize,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("RingSqe")
+            .field("opcode", &self.opcode)
+            .field("fd", &self.fd)
+            .field("iovs", &self.iovs)
+            .field("iovs_len", &self.iovs_len)
+            .field("offset", &self.offset)
+            .field("user_data", &self.user_data)
+            .finish()
+    }
+}
+unsafe impl<M> ValueType for RingSqe<M>
+where
+    M: MemorySize,
+{
+    #[inline]
+    fn zero_padding_bytes(&self, _bytes: &mut [MaybeUninit<u8>]) {}
+}
+
+#[doc = " An entry of the completion queue of a ring."]
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct RingCqe {
+    #[doc = " The `user_data` of the submitted entry."]
+    pub user_data: u64,
+    #[doc = " Number of bytes read or written."]
+    pub result: u64,
+    #[doc = " The error of the operation."]
+    pub errno: Errno,
+}
+unsafe impl ValueType for RingCqe {
+    #[inline]
+    fn zero_padding_bytes(&self, _bytes: &mut [MaybeUninit<u8>]) {}
+}
+
+#[doc = " A ring shared by a process with the runtime, through which batches of operations are"]
+#[doc = " submitted to `ring_enter` and their completions reaped. The process advances `sq_tail`"]
+#[doc = " and `cq_head`, the runtime `sq_head` and `cq_tail`."]
+#[doc = ""]
+#[doc = " The runtimes which predate the ring don't provide `ring_enter`, and there is no shim"]
+#[doc = " running a ring through the other syscalls: a process which may run on them imports"]
+#[doc = " `ring_enter` weakly and makes the syscalls of the operations itself when it's missing,"]
+#[doc = " as it does for the operations which complete with `Errno::Notsup`."]
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Ring<M: MemorySize> {
+    #[doc = " Index of the next entry to be run, which only the runtime changes."]
+    pub sq_head: u32,
+    #[doc = " Index past the last submitted entry."]
+    pub sq_tail: u32,
+    #[doc = " Index of the next completion to be reaped."]
+    pub cq_head: u32,
+    #[doc = " Index past the last completion, which only the runtime changes."]
+    pub cq_tail: u32,
+    #[doc = " Number of entries of the submission queue, a power of two."]
+    pub sq_entries: u32,
+    #[doc = " Number of entries of the completion queue, a power of two."]
+    pub cq_entries: u32,
+    #[doc = " The entries of the submission queue."]
+    pub sqes: M::Offset,
+    #[doc = " The entries of the completion queue."]
+    pub cqes: M::Offset,
+}
+impl<M: MemorySize> Ring<M> {
+    #[doc = " Number of submitted entries the runtime runs, as many as the completion queue has"]
+    #[doc = " room for, or `None` when the sizes or indexes of the ring are inconsistent."]
+    pub fn runnable(&self) -> Option<u32> {
+        if !self.sq_entries.is_power_of_two() || !self.cq_entries.is_power_of_two() {
+            return None;
+        }
+        let submitted = self.sq_tail.wrapping_sub(self.sq_head);
+        let queued = self.cq_tail.wrapping_sub(self.cq_head);
+        if submitted > self.sq_entries || queued > self.cq_entries {
+            return None;
+        }
+        Some(submitted.min(self.cq_entries - queued))
+    }
+
+    #[doc = " The slot of the submission queue holding the next entry to run."]
+    pub fn sq_slot(&self) -> u32 {
+        self.sq_head & (self.sq_entries - 1)
+    }
+
+    #[doc = " The slot of the completion queue the next completion goes to."]
+    pub fn cq_slot(&self) -> u32 {
+        self.cq_tail & (self.cq_entries - 1)
+    }
+
+    #[doc = " Moves past the entry which was run and its completion."]
+    pub fn advance(&mut self) {
+        self.sq_head = self.sq_head.wrapping_add(1);
+        self.cq_tail = self.cq_tail.wrapping_add(1);
+    }
+}
+impl<M> core::fmt::Debug for Ring<M>
+where
+    M: MemorySize,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Ring")
+            .field("sq_head", &self.sq_head)
+            .field("sq_tail", &self.sq_tail)
+            .field("cq_head", &self.cq_head)
+            .field("cq_tail", &self.cq_tail)
+            .field("sq_entries", &self.sq_entries)
+            .field("cq_entries", &self.cq_entries)
+            .field("sqes", &self.sqes)
+            .field("cqes", &self.cqes)
+            .finish()
+    }
+}
+unsafe impl<M> ValueType for Ring<M>
+where
+    M: MemorySize,
+{
+    #[inline]
+    fn zero_padding_bytes(&self, _bytes: &mut [MaybeUninit<u8>]) {}
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 pub struct EpollEventCtl {
@@ -489,3 +839,82 @@ where
     #[inline]
     fn zero_padding_bytes(&self, _bytes: &mut [MaybeUninit<u8>]) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use wasmer::Memory32;
+
+    use super::*;
+
+    fn ring(sq_entries: u32, cq_entries: u32) -> Ring<Memory32> {
+        Ring {
+            sq_head: 0,
+            sq_tail: 0,
+            cq_head: 0,
+            cq_tail: 0,
+            sq_entries,
+            cq_entries,
+            sqes: 0,
+            cqes: 0,
+        }
+    }
+
+    #[test]
+    fn rings_run_what_the_completions_have_room_for() {
+        let mut ring = ring(8, 4);
+        assert_eq!(ring.runnable(), Some(0));
+        ring.sq_tail = 6;
+        assert_eq!(ring.runnable(), Some(4));
+
+        // The completions which weren't reaped hold back the submissions
+        ring.cq_tail = 3;
+        assert_eq!(ring.runnable(), Some(1));
+        ring.cq_head = 3;
+        assert_eq!(ring.runnable(), Some(4));
+    }
+
+    #[test]
+    fn inconsistent_rings_are_refused() {
+        assert_eq!(ring(6, 4).runnable(), None);
+        assert_eq!(ring(4, 0).runnable(), None);
+
+        let mut overflowing = ring(4, 4);
+        overflowing.sq_tail = 5;
+        assert_eq!(overflowing.runnable(), None);
+        overflowing.sq_tail = 0;
+        overflowing.cq_head = 1;
+        assert_eq!(overflowing.runnable(), None);
+    }
+
+    #[test]
+    fn submissions_complete_in_order_across_the_wrap() {
+        let mut ring = ring(4, 4);
+        ring.sq_head = u32::MAX - 1;
+        ring.sq_tail = u32::MAX - 1;
+        ring.cq_head = u32::MAX - 1;
+        ring.cq_tail = u32::MAX - 1;
+        let mut sqes = [0u64; 4];
+        let mut cqes = [0u64; 4];
+
+        // The process submits three entries, past the end of the indexes
+        for user_data in 1..=3 {
+            sqes[(ring.sq_tail & 3) as usize] = user_data;
+            ring.sq_tail = ring.sq_tail.wrapping_add(1);
+        }
+
+        // The runtime runs them into the completion queue
+        assert_eq!(ring.runnable(), Some(3));
+        for _ in 0..3 {
+            cqes[ring.cq_slot() as usize] = sqes[ring.sq_slot() as usize];
+            ring.advance();
+        }
+        assert_eq!((ring.sq_head, ring.cq_tail), (1, 1));
+        assert_eq!(ring.runnable(), Some(0));
+
+        // And the process reaps them in the order they were submitted
+        let reaped: Vec<_> = (0..3)
+            .map(|i| cqes[(ring.cq_head.wrapping_add(i) & 3) as usize])
+            .collect();
+        assert_eq!(reaped, [1, 2, 3]);
+    }
+}
@@ -4,7 +4,7 @@ use std::{
     path::PathBuf,
     pin::Pin,
     sync::{atomic::AtomicU64, Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
-    task::Context,
+    task::{Context, Poll},
 };
 
 use futures::Future;
@@ -90,6 +90,37 @@ pub struct EpollFd {
     pub data2: u64,
 }
 
+impl EpollFd {
+    /// The events reported for a file descriptor whose readiness is
+    /// `readiness`, the errors and the hang-ups being reported whether they
+    /// are asked for or not, and nothing once a one-shot event disarmed it.
+    pub fn reported(&self, readiness: EpollType) -> EpollType {
+        if !self.is_armed() {
+            return EpollType::empty();
+        }
+        readiness
+            & (self.events | EpollType::EPOLLERR | EpollType::EPOLLHUP)
+            & !(EpollType::EPOLLET | EpollType::EPOLLONESHOT)
+    }
+
+    /// Whether the file descriptor is reported, which stops after a one-shot
+    /// event until it's modified again.
+    pub fn is_armed(&self) -> bool {
+        self.events
+            .intersects(!(EpollType::EPOLLET | EpollType::EPOLLONESHOT))
+    }
+
+    /// Stops reporting the file descriptor, as Linux does once it reported a
+    /// one-shot event.
+    pub fn disarm(&mut self) {
+        self.events &= EpollType::EPOLLET | EpollType::EPOLLONESHOT;
+    }
+
+    pub fn is_edge_triggered(&self) -> bool {
+        self.events.contains(EpollType::EPOLLET)
+    }
+}
+
 /// Represents all the EpollInterests that have occurred
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct EpollInterest {
@@ -104,9 +135,13 @@ pub enum EpollJoinGuard {
     Join {
         join_guard: InodeValFilePollGuardJoin,
         epoll_waker: Arc<EpollJoinWaker>,
+        /// Whether events were reported since the guard was last armed
+        reported: bool,
     },
     Handler {
         fd_guard: InodeValFilePollGuard,
+        /// Whether events were reported since the readiness was last checked
+        reported: bool,
     },
 }
 impl Drop for EpollJoinGuard {
@@ -120,28 +155,64 @@ impl Drop for EpollJoinGuard {
     }
 }
 impl EpollJoinGuard {
-    pub fn is_spent(&self) -> bool {
+    /// Records that the events of the file descriptor were reported, which
+    /// takes them until the guard is re-armed.
+    pub fn mark_reported(&mut self) {
         match self {
-            Self::Join { join_guard, .. } => join_guard.is_spent(),
-            Self::Handler { .. } => false,
+            Self::Join { reported, .. } | Self::Handler { reported, .. } => *reported = true,
         }
     }
-    pub fn renew(&mut self) {
-        if let Self::Join {
-            join_guard,
-            epoll_waker,
-        } = self
-        {
-            let fd = join_guard.fd();
-            join_guard.reset();
-
-            let waker = epoll_waker.as_waker();
-            let mut cx = Context::from_waker(&waker);
-            if Pin::new(join_guard).poll(&mut cx).is_ready() {
-                tracing::trace!(fd, "join renew already woken");
-                waker.wake();
-            } else {
-                tracing::trace!(fd, "join waker reinstalled");
+
+    /// Re-arms the guard after its events were reported, so that a level
+    /// triggered file descriptor is reported again for as long as it stays
+    /// ready, while an edge-triggered one only is once it becomes ready
+    /// again.
+    ///
+    /// The sockets tell their handler when they become ready, so only their
+    /// level is checked again. The other files are polled again, a pipe
+    /// which is still ready is not reported when it's edge-triggered but is
+    /// polled again on the next wait, until the guest drained it and the
+    /// waker is installed for the data to come.
+    pub fn rearm(&mut self, fd: &EpollFd, tx: &Arc<watch::Sender<EpollInterest>>) {
+        let edge_triggered = fd.is_edge_triggered();
+        match self {
+            Self::Join {
+                join_guard,
+                epoll_waker,
+                reported,
+            } => {
+                if !std::mem::take(reported) && !join_guard.is_spent() {
+                    return;
+                }
+                if !edge_triggered {
+                    join_guard.reset();
+                }
+
+                let waker = epoll_waker.as_waker();
+                let mut cx = Context::from_waker(&waker);
+                let fd = join_guard.fd();
+                if Pin::new(&mut *join_guard).poll(&mut cx).is_pending() {
+                    tracing::trace!(fd, "join waker reinstalled");
+                } else if !edge_triggered || join_guard.is_edge_triggered() {
+                    tracing::trace!(fd, "join rearm already woken");
+                    waker.wake();
+                }
+            }
+            Self::Handler { fd_guard, reported } => {
+                if !std::mem::take(reported) || edge_triggered {
+                    return;
+                }
+
+                let waker = EpollJoinWaker::new(fd.fd, fd.events, tx.clone()).as_waker();
+                let mut cx = Context::from_waker(&waker);
+                let mut join_guard = InodeValFilePollGuardJoin::new(fd_guard.clone());
+                if let Poll::Ready(events) = Pin::new(&mut join_guard).poll(&mut cx) {
+                    tracing::trace!(fd = fd.fd, "handler still ready");
+                    tx.send_modify(|i| {
+                        i.interest
+                            .extend(events.into_iter().map(|(_, readiness)| (fd.fd, readiness)));
+                    });
+                }
             }
         }
     }
@@ -213,7 +284,9 @@ pub enum Kind {
         base_po_dir: WasiFd,
         /// The path to the symlink from the `base_po_dir`
         path_to_symlink: PathBuf,
-        /// the value of the symlink as a relative path
+        /// the value of the symlink, relative to the directory containing
+        /// the symlink unless it's absolute, in which case it's relative to
+        /// the root of the process
         relative_path: PathBuf,
     },
     Buffer {
@@ -223,3 +296,97 @@ pub enum Kind {
         inner: Arc<NotificationInner>,
     },
 }
+
+#[cfg(test)]
+mod tests {
+    use wasmer_wasix_types::{
+        types::Eventtype,
+        wasi::{Subscription, SubscriptionFsReadwrite, SubscriptionUnion},
+    };
+
+    use super::*;
+    use crate::state::{PollEvent, PollEventBuilder};
+
+    const FD: WasiFd = 3;
+
+    type Tx = Arc<watch::Sender<EpollInterest>>;
+
+    fn subscribe(
+        events: EpollType,
+        inner: &Arc<NotificationInner>,
+        tx: &Tx,
+    ) -> (EpollFd, EpollJoinGuard) {
+        let fd = EpollFd {
+            events,
+            ptr: 0,
+            fd: FD,
+            data1: 0,
+            data2: 0,
+        };
+        let fd_guard = InodeValFilePollGuard {
+            fd: FD,
+            peb: PollEventBuilder::new().add(PollEvent::PollIn).build(),
+            subscription: Subscription {
+                userdata: 0,
+                type_: Eventtype::FdRead,
+                data: SubscriptionUnion {
+                    fd_readwrite: SubscriptionFsReadwrite {
+                        file_descriptor: FD,
+                    },
+                },
+            },
+            mode: InodeValFilePollGuardMode::EventNotifications(inner.clone()),
+        };
+        let join = EpollJoinGuard::Join {
+            join_guard: InodeValFilePollGuardJoin::new(fd_guard),
+            epoll_waker: EpollJoinWaker::new(FD, events, tx.clone()),
+            reported: true,
+        };
+        (fd, join)
+    }
+
+    /// Takes the interest, as a wait does after it re-armed the guard.
+    fn wait(fd: &EpollFd, join: &mut EpollJoinGuard, tx: &Tx) -> bool {
+        join.rearm(fd, tx);
+        let mut ready = false;
+        tx.send_modify(|i| {
+            ready = i.interest.drain().any(|(_, r)| !fd.reported(r).is_empty());
+        });
+        if ready {
+            join.mark_reported();
+        }
+        ready
+    }
+
+    #[test]
+    fn level_triggered_notifications_are_reported_while_readable() {
+        let tx = Arc::new(watch::channel(EpollInterest::default()).0);
+        let inner = Arc::new(NotificationInner::new(1, false));
+        let (fd, mut join) = subscribe(EpollType::EPOLLIN, &inner, &tx);
+
+        assert!(wait(&fd, &mut join, &tx));
+        assert!(wait(&fd, &mut join, &tx));
+        inner.try_read();
+        assert!(!wait(&fd, &mut join, &tx));
+        assert!(inner.try_write(1).is_ok());
+        assert!(wait(&fd, &mut join, &tx));
+    }
+
+    #[test]
+    fn edge_triggered_notifications_are_reported_once_written() {
+        let tx = Arc::new(watch::channel(EpollInterest::default()).0);
+        let inner = Arc::new(NotificationInner::new(1, false));
+        let (mut fd, mut join) = subscribe(EpollType::EPOLLIN | EpollType::EPOLLET, &inner, &tx);
+
+        assert!(wait(&fd, &mut join, &tx));
+        assert!(!wait(&fd, &mut join, &tx));
+        assert!(inner.try_write(1).is_ok());
+        assert!(wait(&fd, &mut join, &tx));
+
+        fd.events |= EpollType::EPOLLONESHOT;
+        fd.disarm();
+        assert!(inner.try_write(1).is_ok());
+        assert!(!fd.is_armed());
+        assert!(!wait(&fd, &mut join, &tx));
+    }
+}
@@ -31,6 +31,7 @@ pub(crate) enum InodeValFilePollGuardMode {
     Pipe { pipe: Arc<RwLock<Box<VirtualPipe>>> },
 }
 
+#[derive(Clone)]
 pub struct InodeValFilePollGuard {
     pub(crate) fd: u32,
     pub(crate) peb: PollEventSet,
@@ -142,6 +143,13 @@ impl InodeValFilePollGuardJoin {
     pub fn is_spent(&self) -> bool {
         self.spent
     }
+    /// Whether the guard is only ready again once the file changed, rather
+    /// than for as long as it stays ready. The notifications are only ready
+    /// to be read again once their counter changed, while they stay writable
+    pub(crate) fn is_edge_triggered(&self) -> bool {
+        matches!(self.mode, InodeValFilePollGuardMode::EventNotifications(_))
+            && !iterate_poll_events(self.peb).any(|event| matches!(event, PollEvent::PollOut))
+    }
     pub fn reset(&mut self) {
         match &self.mode {
             InodeValFilePollGuardMode::File(_) => {}
@@ -285,7 +293,9 @@ impl Future for InodeValFilePollGuardJoin {
                     let file = Pin::new(guard.as_mut());
                     file.poll_write_ready(cx)
                 }
-                InodeValFilePollGuardMode::EventNotifications(inner) => inner.poll(waker).map(Ok),
+                InodeValFilePollGuardMode::EventNotifications(inner) => {
+                    inner.poll_write_ready(waker).map(Ok)
+                }
                 InodeValFilePollGuardMode::Socket { ref inner } => {
                     let mut guard = inner.protected.write().unwrap();
                     guard.poll_write_ready(cx)
@@ -541,6 +551,20 @@ impl VirtualFile for WasiStateFileGuard {
         }
     }
 
+    fn advise(
+        &mut self,
+        offset: u64,
+        len: u64,
+        advice: virtual_fs::FileAdvice,
+    ) -> Result<(), FsError> {
+        let mut guard = self.lock_write();
+        if let Some(file) = guard.as_mut() {
+            file.advise(offset, len, advice)
+        } else {
+            Err(FsError::IOError)
+        }
+    }
+
     fn unlink(&mut self) -> Result<(), FsError> {
         let mut guard = self.lock_write();
         if let Some(file) = guard.as_mut() {
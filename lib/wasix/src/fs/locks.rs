@@ -0,0 +1,557 @@
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    ops::Deref,
+    path::PathBuf,
+    pin::Pin,
+    sync::{atomic::AtomicU64, Arc, Mutex, Weak},
+    task::{Context, Poll, Waker},
+};
+
+use virtual_fs::FileId;
+use wasmer_wasix_types::wasi::Errno;
+
+use crate::WasiProcessId;
+
+use super::{InodeGuard, Kind, WasiFs, WasiFsRoot};
+
+/// What the locks of a file are indexed by, only files and directories can
+/// be locked
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum LockKey {
+    /// The file as identified by its file system, which its hard links
+    /// share and which stays the same when it's renamed
+    File(FileId),
+    /// The path of the file in a root file system, identified by its
+    /// address, for the directories and the files of the file systems
+    /// which can't tell their files apart
+    Path { root: usize, path: PathBuf },
+}
+
+/// The key the locks of the file of `inode` are indexed by.
+pub(crate) fn lock_key(fs: &WasiFs, inode: &InodeGuard) -> Option<LockKey> {
+    let (handle, path) = match inode.read().deref() {
+        Kind::File { handle, path, .. } => (handle.clone(), path.clone()),
+        Kind::Dir { path, .. } => (None, path.clone()),
+        _ => return None,
+    };
+    if let Some(id) = handle.and_then(|handle| handle.read().unwrap().file_id()) {
+        return Some(LockKey::File(id));
+    }
+    let root = match &fs.root_fs {
+        WasiFsRoot::Sandbox(root) => Arc::as_ptr(root) as *const () as usize,
+        WasiFsRoot::Backing(root) => Arc::as_ptr(root) as *const () as usize,
+    };
+    Some(LockKey::Path { root, path })
+}
+
+/// Kind of an advisory lock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LockKind {
+    Shared,
+    Exclusive,
+}
+
+impl LockKind {
+    fn conflicts_with(self, other: LockKind) -> bool {
+        self == LockKind::Exclusive || other == LockKind::Exclusive
+    }
+}
+
+/// A byte-range lock, which belongs to a process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RangeLock {
+    pub pid: WasiProcessId,
+    pub kind: LockKind,
+    pub start: u64,
+    /// End of the range, exclusive, `u64::MAX` when it extends to the end of
+    /// the file
+    pub end: u64,
+}
+
+impl RangeLock {
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start < end && start < self.end
+    }
+
+    fn conflicts_with(&self, other: &RangeLock) -> bool {
+        self.pid != other.pid
+            && self.overlaps(other.start, other.end)
+            && self.kind.conflicts_with(other.kind)
+    }
+}
+
+/// A whole-file lock, which belongs to an open file description and so is
+/// shared by the duplicates of a file descriptor. The open file description
+/// is identified by the offset it shares with them.
+#[derive(Debug)]
+struct OpenFileLock {
+    owner: Weak<AtomicU64>,
+    kind: LockKind,
+}
+
+/// The locks of a file along with the threads waiting for them
+#[derive(Debug, Default)]
+struct InodeLocks {
+    ranges: Vec<RangeLock>,
+    open_files: Vec<OpenFileLock>,
+    waiters: Vec<Waker>,
+}
+
+impl InodeLocks {
+    fn range_conflict(&self, lock: &RangeLock) -> Option<&RangeLock> {
+        self.ranges.iter().find(|other| other.conflicts_with(lock))
+    }
+
+    /// Removes the locks of `pid` within a range, the locks which only
+    /// partially overlap it are shrunk or split.
+    fn remove_range(&mut self, pid: WasiProcessId, start: u64, end: u64) {
+        let mut kept = Vec::with_capacity(self.ranges.len());
+        for lock in self.ranges.drain(..) {
+            if lock.pid != pid || !lock.overlaps(start, end) {
+                kept.push(lock);
+                continue;
+            }
+            if lock.start < start {
+                kept.push(RangeLock { end: start, ..lock });
+            }
+            if end < lock.end {
+                kept.push(RangeLock { start: end, ..lock });
+            }
+        }
+        self.ranges = kept;
+    }
+
+    fn open_file_conflict(&self, owner: &Arc<AtomicU64>, kind: LockKind) -> bool {
+        self.open_files.iter().any(|lock| {
+            !std::ptr::eq(lock.owner.as_ptr(), Arc::as_ptr(owner))
+                && lock.owner.strong_count() > 0
+                && lock.kind.conflicts_with(kind)
+        })
+    }
+
+    fn remove_open_file(&mut self, owner: &Arc<AtomicU64>) {
+        self.open_files
+            .retain(|lock| !std::ptr::eq(lock.owner.as_ptr(), Arc::as_ptr(owner)));
+    }
+
+    fn wait(&mut self, waker: &Waker) {
+        if !self.waiters.iter().any(|waiter| waiter.will_wake(waker)) {
+            self.waiters.push(waker.clone());
+        }
+    }
+
+    /// Wakes the waiting threads once some locks were released
+    fn wake(&mut self) {
+        for waker in self.waiters.drain(..) {
+            waker.wake();
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ranges.is_empty() && self.open_files.is_empty() && self.waiters.is_empty()
+    }
+}
+
+#[derive(Debug, Default)]
+struct FileLocksInner {
+    files: HashMap<LockKey, InodeLocks>,
+    /// The byte-range locks threads are waiting for, by waiter
+    pending: HashMap<u64, (LockKey, RangeLock)>,
+    pending_seed: u64,
+}
+
+impl FileLocksInner {
+    /// Releases the locks which can no longer be held, either because their
+    /// process exited or because their open file description was closed,
+    /// and wakes the threads waiting for them.
+    fn release(&mut self, key: &LockKey, pid: Option<WasiProcessId>) {
+        let Some(file) = self.files.get_mut(key) else {
+            return;
+        };
+        if let Some(pid) = pid {
+            file.remove_range(pid, 0, u64::MAX);
+        }
+        file.open_files.retain(|lock| lock.owner.strong_count() > 0);
+        file.wake();
+        if file.is_empty() {
+            self.files.remove(key);
+        }
+    }
+
+    /// Returns `true` if the process waiting for `lock` would end up waiting,
+    /// through the locks the blocking processes are themselves waiting for,
+    /// on a lock it holds.
+    fn would_deadlock(&self, key: &LockKey, lock: &RangeLock) -> bool {
+        let mut visited = HashSet::new();
+        let mut blockers = self.blockers(key, lock);
+        while let Some(pid) = blockers.pop() {
+            if pid == lock.pid {
+                return true;
+            }
+            if !visited.insert(pid) {
+                continue;
+            }
+            for (key, pending) in self.pending.values() {
+                if pending.pid == pid {
+                    blockers.extend(self.blockers(key, pending));
+                }
+            }
+        }
+        false
+    }
+
+    fn blockers(&self, key: &LockKey, lock: &RangeLock) -> Vec<WasiProcessId> {
+        self.files
+            .get(key)
+            .map(|file| {
+                file.ranges
+                    .iter()
+                    .filter(|other| other.conflicts_with(lock))
+                    .map(|other| other.pid)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// The advisory locks of the files, shared by all the processes of a
+/// [`WasiControlPlane`](crate::WasiControlPlane) and indexed by the
+/// [`LockKey`] of the files.
+///
+/// Byte-range locks (`fcntl`) belong to processes, they are released when
+/// the process closes any descriptor of the file or exits. Whole-file locks
+/// (`flock`) belong to open file descriptions, they are released along with
+/// the last descriptor referring to them. Like on Linux, both kinds of locks
+/// do not interact with each other.
+#[derive(Debug, Default)]
+pub(crate) struct FileLocks {
+    inner: Mutex<FileLocksInner>,
+}
+
+impl FileLocks {
+    /// Sets a byte-range lock, replacing the locks of the process within the
+    /// range, or fails with [`Errno::Again`] if another process holds a
+    /// conflicting lock.
+    pub fn try_lock_range(&self, key: &LockKey, lock: RangeLock) -> Result<(), Errno> {
+        let mut inner = self.inner.lock().unwrap();
+        let file = inner.files.entry(key.clone()).or_default();
+        if file.range_conflict(&lock).is_some() {
+            return Err(Errno::Again);
+        }
+        file.remove_range(lock.pid, lock.start, lock.end);
+        file.ranges.push(lock);
+        // Converting an exclusive lock to a shared one lets others in
+        file.wake();
+        Ok(())
+    }
+
+    /// Sets a byte-range lock, waiting for the conflicting locks to be
+    /// released. Fails with [`Errno::Deadlk`] instead of waiting forever.
+    pub fn lock_range<'a>(
+        &'a self,
+        key: &'a LockKey,
+        lock: RangeLock,
+    ) -> impl Future<Output = Result<(), Errno>> + 'a {
+        RangeLockWaiter {
+            locks: self,
+            key,
+            lock,
+            pending: None,
+        }
+    }
+
+    /// Releases the locks of a process within a range.
+    pub fn unlock_range(&self, key: &LockKey, pid: WasiProcessId, start: u64, end: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(file) = inner.files.get_mut(key) {
+            file.remove_range(pid, start, end);
+            file.wake();
+            if file.is_empty() {
+                inner.files.remove(key);
+            }
+        }
+    }
+
+    /// Returns a lock which conflicts with `lock`, if any.
+    pub fn range_conflict(&self, key: &LockKey, lock: &RangeLock) -> Option<RangeLock> {
+        let inner = self.inner.lock().unwrap();
+        inner.files.get(key)?.range_conflict(lock).copied()
+    }
+
+    /// Sets a whole-file lock on behalf of an open file description, or
+    /// fails with [`Errno::Again`] if another one holds a conflicting lock.
+    pub fn try_lock_file(
+        &self,
+        key: &LockKey,
+        owner: &Arc<AtomicU64>,
+        kind: LockKind,
+    ) -> Result<(), Errno> {
+        let mut inner = self.inner.lock().unwrap();
+        let file = inner.files.entry(key.clone()).or_default();
+        if file.open_file_conflict(owner, kind) {
+            return Err(Errno::Again);
+        }
+        file.remove_open_file(owner);
+        file.open_files.push(OpenFileLock {
+            owner: Arc::downgrade(owner),
+            kind,
+        });
+        file.wake();
+        Ok(())
+    }
+
+    /// Sets a whole-file lock, waiting for the conflicting locks to be
+    /// released.
+    pub async fn lock_file(
+        &self,
+        key: &LockKey,
+        owner: &Arc<AtomicU64>,
+        kind: LockKind,
+    ) -> Result<(), Errno> {
+        std::future::poll_fn(|cx| {
+            let mut inner = self.inner.lock().unwrap();
+            let file = inner.files.entry(key.clone()).or_default();
+            if file.open_file_conflict(owner, kind) {
+                file.wait(cx.waker());
+                return Poll::Pending;
+            }
+            file.remove_open_file(owner);
+            file.open_files.push(OpenFileLock {
+                owner: Arc::downgrade(owner),
+                kind,
+            });
+            file.wake();
+            Poll::Ready(Ok(()))
+        })
+        .await
+    }
+
+    /// Releases the whole-file lock of an open file description.
+    pub fn unlock_file(&self, key: &LockKey, owner: &Arc<AtomicU64>) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(file) = inner.files.get_mut(key) {
+            file.remove_open_file(owner);
+        }
+        inner.release(key, None);
+    }
+
+    /// Called once a process closed a descriptor of a file, which releases
+    /// its byte-range locks on the file, and the whole-file lock of the open
+    /// file description if it was its last descriptor.
+    pub fn file_closed(&self, key: &LockKey, pid: WasiProcessId) {
+        self.inner.lock().unwrap().release(key, Some(pid));
+    }
+
+    /// Called once a process exited, which releases all its locks so the
+    /// processes waiting for them are not blocked forever.
+    pub fn process_exited(&self, pid: WasiProcessId) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.pending.retain(|_, (_, lock)| lock.pid != pid);
+        let keys: Vec<_> = inner.files.keys().cloned().collect();
+        for key in keys {
+            inner.release(&key, Some(pid));
+        }
+    }
+}
+
+/// Waits for a byte-range lock, the waiter is registered while it waits so
+/// deadlocks can be detected
+struct RangeLockWaiter<'a> {
+    locks: &'a FileLocks,
+    key: &'a LockKey,
+    lock: RangeLock,
+    pending: Option<u64>,
+}
+
+impl Future for RangeLockWaiter<'_> {
+    type Output = Result<(), Errno>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let mut inner = this.locks.inner.lock().unwrap();
+        let inner = &mut *inner;
+
+        let file = inner.files.entry(this.key.clone()).or_default();
+        if file.range_conflict(&this.lock).is_none() {
+            file.remove_range(this.lock.pid, this.lock.start, this.lock.end);
+            file.ranges.push(this.lock);
+            file.wake();
+            if let Some(pending) = this.pending.take() {
+                inner.pending.remove(&pending);
+            }
+            return Poll::Ready(Ok(()));
+        }
+        file.wait(cx.waker());
+
+        if this.pending.is_none() {
+            if inner.would_deadlock(this.key, &this.lock) {
+                return Poll::Ready(Err(Errno::Deadlk));
+            }
+            inner.pending_seed += 1;
+            inner
+                .pending
+                .insert(inner.pending_seed, (this.key.clone(), this.lock));
+            this.pending = Some(inner.pending_seed);
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for RangeLockWaiter<'_> {
+    fn drop(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            self.locks.inner.lock().unwrap().pending.remove(&pending);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(path: &str) -> LockKey {
+        LockKey::Path {
+            root: 0,
+            path: PathBuf::from(path),
+        }
+    }
+
+    fn range(pid: u32, kind: LockKind, start: u64, end: u64) -> RangeLock {
+        RangeLock {
+            pid: WasiProcessId::from(pid),
+            kind,
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn range_locks_conflict_across_processes() {
+        let locks = FileLocks::default();
+        let path = &key("/db");
+
+        locks
+            .try_lock_range(path, range(1, LockKind::Shared, 0, 100))
+            .unwrap();
+        locks
+            .try_lock_range(path, range(2, LockKind::Shared, 50, 150))
+            .unwrap();
+        assert_eq!(
+            locks.try_lock_range(path, range(2, LockKind::Exclusive, 0, 10)),
+            Err(Errno::Again)
+        );
+
+        // The process converts the middle of its lock, which splits it
+        locks
+            .try_lock_range(path, range(1, LockKind::Exclusive, 10, 20))
+            .unwrap();
+        assert_eq!(
+            locks.range_conflict(path, &range(2, LockKind::Shared, 0, u64::MAX)),
+            Some(range(1, LockKind::Exclusive, 10, 20))
+        );
+
+        locks.unlock_range(path, WasiProcessId::from(1), 0, 50);
+        locks
+            .try_lock_range(path, range(2, LockKind::Exclusive, 0, 50))
+            .unwrap();
+
+        // Closing the file releases the locks of the process
+        locks.file_closed(path, WasiProcessId::from(2));
+        locks
+            .try_lock_range(path, range(1, LockKind::Exclusive, 0, u64::MAX))
+            .unwrap();
+        locks.process_exited(WasiProcessId::from(1));
+        assert!(locks.inner.lock().unwrap().files.is_empty());
+    }
+
+    #[test]
+    fn waiting_for_a_range_detects_deadlocks() {
+        let locks = FileLocks::default();
+        let (a, b) = (&key("/a"), &key("/b"));
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+
+        locks
+            .try_lock_range(a, range(1, LockKind::Exclusive, 0, 1))
+            .unwrap();
+        locks
+            .try_lock_range(b, range(2, LockKind::Exclusive, 0, 1))
+            .unwrap();
+
+        let mut waiter = Box::pin(locks.lock_range(b, range(1, LockKind::Exclusive, 0, 1)));
+        assert!(waiter.as_mut().poll(&mut cx).is_pending());
+        let mut deadlock = Box::pin(locks.lock_range(a, range(2, LockKind::Shared, 0, 1)));
+        assert_eq!(
+            deadlock.as_mut().poll(&mut cx),
+            Poll::Ready(Err(Errno::Deadlk))
+        );
+
+        // The waiter gets the lock once the other process exits
+        locks.process_exited(WasiProcessId::from(2));
+        assert_eq!(waiter.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+        assert!(locks.inner.lock().unwrap().pending.is_empty());
+    }
+
+    #[test]
+    fn file_locks_belong_to_open_files() {
+        let locks = FileLocks::default();
+        let path = &key("/pid");
+        let first = Arc::new(AtomicU64::new(0));
+        let second = Arc::new(AtomicU64::new(0));
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+
+        locks.try_lock_file(path, &first, LockKind::Shared).unwrap();
+        locks
+            .try_lock_file(path, &second, LockKind::Shared)
+            .unwrap();
+        assert_eq!(
+            locks.try_lock_file(path, &second, LockKind::Exclusive),
+            Err(Errno::Again)
+        );
+
+        let mut waiter = Box::pin(locks.lock_file(path, &second, LockKind::Exclusive));
+        assert!(waiter.as_mut().poll(&mut cx).is_pending());
+
+        // Closing the last descriptor of the open file releases its lock
+        let closed = Arc::downgrade(&first);
+        drop(first);
+        assert!(closed.upgrade().is_none());
+        locks.file_closed(path, WasiProcessId::from(1));
+        assert_eq!(waiter.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+
+        locks.unlock_file(path, &second);
+        assert!(locks.inner.lock().unwrap().files.is_empty());
+    }
+
+    #[test]
+    fn files_are_locked_whatever_their_name() {
+        let locks = FileLocks::default();
+        let file = LockKey::File(FileId { dev: 1, ino: 7 });
+        locks
+            .try_lock_range(&file, range(1, LockKind::Exclusive, 0, 1))
+            .unwrap();
+
+        // A hard link, or the file once renamed, has the same key
+        let linked = LockKey::File(FileId { dev: 1, ino: 7 });
+        assert_eq!(
+            locks.try_lock_range(&linked, range(2, LockKind::Shared, 0, 1)),
+            Err(Errno::Again)
+        );
+        // Files of other file systems don't share the locks
+        let other = LockKey::File(FileId { dev: 2, ino: 7 });
+        locks
+            .try_lock_range(&other, range(2, LockKind::Shared, 0, 1))
+            .unwrap();
+        let other_root = LockKey::Path {
+            root: 1,
+            path: PathBuf::from("/db"),
+        };
+        locks
+            .try_lock_range(&other_root, range(2, LockKind::Exclusive, 0, 1))
+            .unwrap();
+        assert_eq!(
+            locks.try_lock_range(&key("/db"), range(1, LockKind::Exclusive, 0, 1)),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,360 @@
+use std::{
+    collections::BTreeMap,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+};
+
+use virtual_fs::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, VirtualFile};
+use wasmer::MemoryView;
+use wasmer_wasix_types::wasi::Errno;
+
+use super::SharedMemory;
+use crate::{mem_error_to_wasi, utils::map_io_err};
+
+type MappedFile = Arc<RwLock<Box<dyn VirtualFile + Send + Sync + 'static>>>;
+
+/// A part of a file mapped into the memory of the process.
+#[derive(Debug, Clone)]
+pub(crate) struct Mapping {
+    /// Where the mapping starts in the memory
+    pub addr: u64,
+    pub len: u64,
+    /// Offset in the file of the first byte of the mapping
+    pub offset: u64,
+    pub file: MappedFile,
+    /// The path of the file, which the other descriptors of the file write
+    /// through
+    pub path: PathBuf,
+    /// Whether the changes to the memory are written back to the file,
+    /// rather than private to the process
+    pub shared: bool,
+    /// Whether the file was opened for writing, as the changes are only
+    /// written back to such files
+    pub writable: bool,
+    /// What the memory held when it was last synced, for the shared
+    /// mappings of shared memory objects
+    pub shadow: Option<Shadow>,
+}
+
+/// The copy a shared mapping of a shared memory object holds of what was
+/// last synced, which its changes are told apart from those of the other
+/// processes with.
+#[derive(Debug, Clone)]
+pub(crate) struct Shadow {
+    object: SharedMemory,
+    /// Where the memory of the whole mapping starts, which parts of it keep
+    addr: u64,
+    synced: Arc<Mutex<Vec<u8>>>,
+}
+
+impl Shadow {
+    pub fn new(object: SharedMemory, addr: u64, len: u64) -> Self {
+        Self {
+            object,
+            addr,
+            synced: Arc::new(Mutex::new(vec![0; len as usize])),
+        }
+    }
+}
+
+impl Mapping {
+    fn end(&self) -> u64 {
+        self.addr.saturating_add(self.len)
+    }
+
+    /// The part of the mapping within `addr..addr + len`.
+    fn slice(&self, addr: u64, len: u64) -> Option<Mapping> {
+        let start = self.addr.max(addr);
+        let end = self.end().min(addr.saturating_add(len));
+        (start < end).then(|| Mapping {
+            addr: start,
+            len: end - start,
+            offset: self.offset + (start - self.addr),
+            ..self.clone()
+        })
+    }
+
+    /// Reads the mapped part of the file into the memory, what is past the
+    /// end of the file reading as zeros.
+    #[allow(clippy::await_holding_lock)]
+    pub async fn load(&self, memory: &MemoryView<'_>) -> Result<(), Errno> {
+        let mut buf = vec![0u8; self.len as usize];
+        let mut file = self.file.write().unwrap();
+        file.seek(SeekFrom::Start(self.offset))
+            .await
+            .map_err(map_io_err)?;
+        let mut read = 0;
+        while read < buf.len() {
+            match file.read(&mut buf[read..]).await.map_err(map_io_err)? {
+                0 => break,
+                n => read += n,
+            }
+        }
+        if let Some(shadow) = self.shadow.as_ref() {
+            let start = (self.addr - shadow.addr) as usize;
+            shadow.synced.lock().unwrap()[start..start + buf.len()].copy_from_slice(&buf);
+        }
+        memory.write(self.addr, &buf).map_err(mem_error_to_wasi)
+    }
+
+    /// Writes the memory back to the mapped part of the file, unless the
+    /// mapping is private or read-only. The file doesn't grow, so what is
+    /// past its end is dropped.
+    #[allow(clippy::await_holding_lock)]
+    pub async fn store(&self, memory: &MemoryView<'_>) -> Result<(), Errno> {
+        if let Some(shadow) = self.shadow.as_ref() {
+            return self.merge(shadow, memory);
+        }
+        if !self.shared || !self.writable {
+            return Ok(());
+        }
+        let mut file = self.file.write().unwrap();
+        let len = self.len.min(file.size().saturating_sub(self.offset));
+        if len == 0 {
+            return Ok(());
+        }
+        let mut buf = vec![0u8; len as usize];
+        memory
+            .read(self.addr, &mut buf)
+            .map_err(mem_error_to_wasi)?;
+        file.seek(SeekFrom::Start(self.offset))
+            .await
+            .map_err(map_io_err)?;
+        file.write_all(&buf).await.map_err(map_io_err)
+    }
+
+    /// Merges the changes to the memory with those the other processes made
+    /// to the shared memory object.
+    fn merge(&self, shadow: &Shadow, memory: &MemoryView<'_>) -> Result<(), Errno> {
+        let mut buf = vec![0u8; self.len as usize];
+        memory
+            .read(self.addr, &mut buf)
+            .map_err(mem_error_to_wasi)?;
+        let start = (self.addr - shadow.addr) as usize;
+        let mut synced = shadow.synced.lock().unwrap();
+        let synced = &mut synced[start..start + buf.len()];
+        if !self.writable {
+            // The memory of a read-only mapping doesn't change
+            synced.copy_from_slice(&buf);
+        }
+        shadow.object.merge(self.offset, &mut buf, synced);
+        memory.write(self.addr, &buf).map_err(mem_error_to_wasi)
+    }
+
+    /// Flushes what was written back to the storage of the file.
+    #[allow(clippy::await_holding_lock)]
+    pub async fn flush(&self) -> Result<(), Errno> {
+        let mut file = self.file.write().unwrap();
+        file.flush().await.map_err(map_io_err)
+    }
+}
+
+/// Checks that `addr..addr + len` can be mapped in a memory of
+/// `memory_size` bytes, before anything is allocated for the mapping.
+pub(crate) fn check_mapped_range(addr: u64, len: u64, memory_size: u64) -> Result<(), Errno> {
+    match addr.checked_add(len) {
+        _ if len == 0 => Err(Errno::Inval),
+        Some(end) if end <= memory_size => Ok(()),
+        Some(_) => Err(Errno::Nomem),
+        None => Err(Errno::Inval),
+    }
+}
+
+/// The files mapped into the memory of the process, which never overlap.
+///
+/// The memory of the process can't be backed by the files, so the mapped
+/// parts are copied into it, and written back when they are synced or
+/// unmapped.
+#[derive(Debug, Default)]
+pub(crate) struct MemoryMappings {
+    mappings: BTreeMap<u64, Mapping>,
+}
+
+impl MemoryMappings {
+    /// Adds a mapping, returning the parts of the mappings it replaced.
+    pub fn map(&mut self, mapping: Mapping) -> Vec<Mapping> {
+        let replaced = self.unmap(mapping.addr, mapping.len);
+        self.mappings.insert(mapping.addr, mapping);
+        replaced
+    }
+
+    /// Unmaps `addr..addr + len`, returning the parts of the mappings which
+    /// were in it. What the mappings have outside of it stays mapped.
+    pub fn unmap(&mut self, addr: u64, len: u64) -> Vec<Mapping> {
+        let end = addr.saturating_add(len);
+        let overlapping = self
+            .mappings
+            .range(..end)
+            .rev()
+            .take_while(|(_, mapping)| mapping.end() > addr)
+            .map(|(start, _)| *start)
+            .collect::<Vec<_>>();
+
+        let mut unmapped = Vec::new();
+        for start in overlapping.into_iter().rev() {
+            let mapping = self.mappings.remove(&start).unwrap();
+            let before = mapping.slice(mapping.addr, addr.saturating_sub(mapping.addr));
+            let after = mapping.slice(end, u64::MAX);
+            unmapped.extend(mapping.slice(addr, len));
+            for part in before.into_iter().chain(after) {
+                self.mappings.insert(part.addr, part);
+            }
+        }
+        unmapped
+    }
+
+    /// The parts of the mappings within `addr..addr + len`.
+    pub fn mapped(&self, addr: u64, len: u64) -> Vec<Mapping> {
+        let end = addr.saturating_add(len);
+        let mut mapped = self
+            .mappings
+            .range(..end)
+            .rev()
+            .take_while(|(_, mapping)| mapping.end() > addr)
+            .filter_map(|(_, mapping)| mapping.slice(addr, len))
+            .collect::<Vec<_>>();
+        mapped.reverse();
+        mapped
+    }
+
+    /// Whether the file at `path` has shared mappings.
+    pub fn is_mapped(&self, path: &Path) -> bool {
+        self.mappings
+            .values()
+            .any(|mapping| mapping.shared && mapping.path == path)
+    }
+
+    /// The parts of the shared mappings of the file at `path` which map
+    /// `offset..offset + len` of it.
+    pub fn mapped_file(&self, path: &Path, offset: u64, len: u64) -> Vec<Mapping> {
+        let end = offset.saturating_add(len);
+        self.mappings
+            .values()
+            .filter(|mapping| mapping.shared && mapping.path == path)
+            .filter_map(|mapping| {
+                let start = offset.max(mapping.offset);
+                let end = end.min(mapping.offset + mapping.len);
+                (start < end).then(|| Mapping {
+                    addr: mapping.addr + (start - mapping.offset),
+                    len: end - start,
+                    offset: start,
+                    ..mapping.clone()
+                })
+            })
+            .collect()
+    }
+
+    /// The mappings which were made through `file`.
+    pub fn mapped_through(&self, file: &MappedFile) -> Vec<Mapping> {
+        self.mappings
+            .values()
+            .filter(|mapping| Arc::ptr_eq(&mapping.file, file))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use virtual_fs::BufferFile;
+
+    use super::*;
+
+    fn mapping(file: &MappedFile, addr: u64, len: u64, offset: u64) -> Mapping {
+        Mapping {
+            addr,
+            len,
+            offset,
+            file: file.clone(),
+            path: PathBuf::from("/data"),
+            shared: true,
+            writable: true,
+            shadow: None,
+        }
+    }
+
+    fn ranges(mappings: Vec<Mapping>) -> Vec<(u64, u64, u64)> {
+        mappings
+            .into_iter()
+            .map(|mapping| (mapping.addr, mapping.len, mapping.offset))
+            .collect()
+    }
+
+    #[test]
+    fn unmapping_splits_the_mappings() {
+        let file: MappedFile = Arc::new(RwLock::new(Box::<BufferFile>::default()));
+        let mut mappings = MemoryMappings::default();
+        assert!(mappings.map(mapping(&file, 1000, 100, 0)).is_empty());
+        assert!(mappings.map(mapping(&file, 2000, 100, 500)).is_empty());
+
+        assert_eq!(
+            ranges(mappings.unmap(1050, 1000)),
+            vec![(1050, 50, 50), (2000, 50, 500)]
+        );
+        assert_eq!(
+            ranges(mappings.mapped(0, u64::MAX)),
+            vec![(1000, 50, 0), (2050, 50, 550)]
+        );
+
+        // Mapping over a mapping replaces it
+        assert_eq!(
+            ranges(mappings.map(mapping(&file, 1020, 40, 0))),
+            vec![(1020, 30, 20)]
+        );
+        assert_eq!(
+            ranges(mappings.mapped(1010, 20)),
+            vec![(1010, 10, 10), (1020, 10, 0)]
+        );
+    }
+
+    #[test]
+    fn mapped_ranges_must_be_within_the_memory() {
+        assert_eq!(check_mapped_range(0, 65536, 65536), Ok(()));
+        assert_eq!(check_mapped_range(1000, 0, 65536), Err(Errno::Inval));
+        assert_eq!(check_mapped_range(1000, 65536, 65536), Err(Errno::Nomem));
+        assert_eq!(check_mapped_range(65536, 1, 65536), Err(Errno::Nomem));
+        assert_eq!(check_mapped_range(1000, u64::MAX, 65536), Err(Errno::Inval));
+        assert_eq!(
+            check_mapped_range(u64::MAX - 10, 20, u64::MAX),
+            Err(Errno::Inval)
+        );
+    }
+
+    #[test]
+    fn mappings_ending_past_the_address_space_are_unmapped() {
+        let file: MappedFile = Arc::new(RwLock::new(Box::<BufferFile>::default()));
+        let mut mappings = MemoryMappings::default();
+        mappings.map(mapping(&file, u64::MAX - 100, 100, 0));
+        assert_eq!(
+            ranges(mappings.mapped(u64::MAX - 50, u64::MAX)),
+            vec![(u64::MAX - 50, 50, 50)]
+        );
+        assert_eq!(
+            ranges(mappings.unmap(u64::MAX - 200, 150)),
+            vec![(u64::MAX - 100, 50, 0)]
+        );
+    }
+
+    #[test]
+    fn file_ranges_are_found_in_the_shared_mappings() {
+        let file: MappedFile = Arc::new(RwLock::new(Box::<BufferFile>::default()));
+        let mut mappings = MemoryMappings::default();
+        mappings.map(mapping(&file, 1000, 100, 200));
+        mappings.map(Mapping {
+            shared: false,
+            ..mapping(&file, 2000, 100, 200)
+        });
+
+        assert_eq!(
+            ranges(mappings.mapped_file(Path::new("/data"), 250, 100)),
+            vec![(1050, 50, 250)]
+        );
+        assert!(mappings.mapped_file(Path::new("/data"), 0, 200).is_empty());
+        assert!(mappings
+            .mapped_file(Path::new("/other"), 250, 100)
+            .is_empty());
+        assert!(mappings.is_mapped(Path::new("/data")));
+        assert_eq!(mappings.mapped_through(&file).len(), 2);
+    }
+}
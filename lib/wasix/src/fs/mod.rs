@@ -1,6 +1,11 @@
 mod fd;
 mod inode_guard;
+mod locks;
+mod mmap;
 mod notification;
+mod shm;
+mod timer;
+mod watch;
 
 use std::{
     borrow::{Borrow, Cow},
@@ -24,12 +29,14 @@ use ahash::AHashMap;
 use futures::{future::BoxFuture, Future};
 use tokio::io::AsyncWriteExt;
 use tracing::{debug, trace};
-use virtual_fs::{FileSystem, FsError, OpenOptions, VirtualFile};
+use virtual_fs::{
+    limiter::DynFsMemoryLimiter, FileSystem, FsError, OpenOptions, Permissions, VirtualFile,
+};
 use wasmer_wasix_types::{
     types::{__WASI_STDERR_FILENO, __WASI_STDIN_FILENO, __WASI_STDOUT_FILENO},
     wasi::{
         Errno, Fd as WasiFd, Fdflags, Fdstat, Filesize, Filestat, Filetype, Preopentype, Prestat,
-        PrestatEnum, Rights, Socktype,
+        PrestatEnum, Rights, Socktype, Statfs,
     },
 };
 
@@ -39,9 +46,14 @@ use serde_derive::{Deserialize, Serialize};
 pub use self::fd::{EpollFd, EpollInterest, EpollJoinGuard, Fd, InodeVal, Kind};
 pub(crate) use self::inode_guard::{
     InodeValFilePollGuard, InodeValFilePollGuardJoin, InodeValFilePollGuardMode,
-    InodeValFileReadGuard, InodeValFileWriteGuard, WasiStateFileGuard, POLL_GUARD_MAX_RET,
+    InodeValFileReadGuard, InodeValFileWriteGuard, WasiStateFileGuard,
 };
+pub(crate) use self::locks::{lock_key, FileLocks, LockKind, RangeLock};
+pub(crate) use self::mmap::{check_mapped_range, Mapping, MemoryMappings, Shadow};
 pub use self::notification::NotificationInner;
+pub(crate) use self::shm::{SharedMemory, SharedMemoryFile, SharedMemoryObjects};
+pub(crate) use self::timer::TimerFile;
+pub(crate) use self::watch::{FileWatchers, WatchFile};
 use crate::syscalls::map_io_err;
 use crate::{state::PreopenedDir, ALL_RIGHTS};
 
@@ -93,10 +105,13 @@ const STDOUT_DEFAULT_RIGHTS: Rights = {
 };
 const STDERR_DEFAULT_RIGHTS: Rights = STDOUT_DEFAULT_RIGHTS;
 
-/// A completely aribtrary "big enough" number used as the upper limit for
-/// the number of symlinks that can be traversed when resolving a path
+/// A completely aribtrary "big enough" number used as the default upper limit
+/// for the number of symlinks that can be traversed when resolving a path
 pub const MAX_SYMLINKS: u32 = 128;
 
+/// The umask of the processes, unless they change it
+pub const DEFAULT_UMASK: u32 = 0o022;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Inode(u64);
 
@@ -295,6 +310,17 @@ pub enum WasiFsRoot {
     Backing(Arc<Box<dyn FileSystem>>),
 }
 
+impl WasiFsRoot {
+    /// The memory limiter of the sandbox file system, which the `tmpfs`
+    /// mounted by the program count towards.
+    pub fn memory_limiter(&self) -> Option<DynFsMemoryLimiter> {
+        match self {
+            WasiFsRoot::Sandbox(fs) => fs.memory_limiter(),
+            WasiFsRoot::Backing(_) => None,
+        }
+    }
+}
+
 impl FileSystem for WasiFsRoot {
     fn readlink(&self, path: &Path) -> virtual_fs::Result<PathBuf> {
         match self {
@@ -350,6 +376,30 @@ impl FileSystem for WasiFsRoot {
             WasiFsRoot::Backing(fs) => fs.remove_file(path),
         }
     }
+    fn hard_link(&self, from: &Path, to: &Path) -> virtual_fs::Result<()> {
+        match self {
+            WasiFsRoot::Sandbox(fs) => fs.hard_link(from, to),
+            WasiFsRoot::Backing(fs) => fs.hard_link(from, to),
+        }
+    }
+    fn statfs(&self, path: &Path) -> virtual_fs::Result<virtual_fs::FsStats> {
+        match self {
+            WasiFsRoot::Sandbox(fs) => fs.statfs(path),
+            WasiFsRoot::Backing(fs) => fs.statfs(path),
+        }
+    }
+    fn permissions(&self, path: &Path) -> virtual_fs::Result<Permissions> {
+        match self {
+            WasiFsRoot::Sandbox(fs) => fs.permissions(path),
+            WasiFsRoot::Backing(fs) => fs.permissions(path),
+        }
+    }
+    fn set_permissions(&self, path: &Path, permissions: Permissions) -> virtual_fs::Result<()> {
+        match self {
+            WasiFsRoot::Sandbox(fs) => fs.set_permissions(path, permissions),
+            WasiFsRoot::Backing(fs) => fs.set_permissions(path, permissions),
+        }
+    }
     fn new_open_options(&self) -> OpenOptions {
         match self {
             WasiFsRoot::Sandbox(fs) => fs.new_open_options(),
@@ -367,6 +417,12 @@ impl FileSystem for WasiFsRoot {
             WasiFsRoot::Backing(f) => f.mount(name, path, fs),
         }
     }
+    fn unmount(&self, path: &Path) -> virtual_fs::Result<()> {
+        match self {
+            WasiFsRoot::Sandbox(fs) => fs.unmount(path),
+            WasiFsRoot::Backing(fs) => fs.unmount(path),
+        }
+    }
 }
 
 /// This needs to be exposed so that the multiple use-cases are able
@@ -450,6 +506,21 @@ pub struct WasiFs {
     // It should not be necessary at all.
     is_wasix: AtomicBool,
 
+    // Whether renaming a file across mounts copies it and removes the source
+    // instead of failing with `EXDEV`
+    copy_cross_device_renames: AtomicBool,
+
+    // The number of symlinks that can be traversed when resolving a path
+    // before failing with `ELOOP`
+    max_symlinks: AtomicU32,
+
+    // Whether symlinks whose target is outside of the preopened directory
+    // containing them are refused
+    confine_symlinks: AtomicBool,
+
+    // The permission bits removed from the files and directories created
+    umask: AtomicU32,
+
     // The preopens when this was initialized
     pub(crate) init_preopens: Vec<PreopenedDir>,
     // The virtual file system preopens when this was initialized
@@ -467,6 +538,49 @@ impl WasiFs {
         self.is_wasix.store(is_wasix, Ordering::SeqCst);
     }
 
+    pub fn max_symlinks(&self) -> u32 {
+        self.max_symlinks.load(Ordering::Relaxed)
+    }
+
+    /// Sets the number of symlinks that can be traversed when resolving a
+    /// path, beyond which resolving it fails with `ELOOP`.
+    pub fn set_max_symlinks(&self, max_symlinks: u32) {
+        self.max_symlinks.store(max_symlinks, Ordering::SeqCst);
+    }
+
+    pub fn confine_symlinks(&self) -> bool {
+        self.confine_symlinks.load(Ordering::Relaxed)
+    }
+
+    /// Refuses to follow the symlinks whose target is outside of the
+    /// preopened directory containing them, with `ENOTCAPABLE`, so that they
+    /// can't be used to escape it.
+    pub fn set_confine_symlinks(&self, confine: bool) {
+        self.confine_symlinks.store(confine, Ordering::SeqCst);
+    }
+
+    pub fn umask(&self) -> u32 {
+        self.umask.load(Ordering::Relaxed)
+    }
+
+    /// Sets the umask, the permission bits removed from the mode of the
+    /// files and directories created, returning the previous one.
+    pub fn set_umask(&self, umask: u32) -> u32 {
+        self.umask.swap(umask & 0o777, Ordering::SeqCst)
+    }
+
+    pub fn copy_cross_device_renames(&self) -> bool {
+        self.copy_cross_device_renames.load(Ordering::Relaxed)
+    }
+
+    /// Renaming a file to another mount fails with `EXDEV` like on POSIX
+    /// systems, unless this fallback is enabled, in which case the file is
+    /// copied and the source removed. The copy isn't atomic, only its
+    /// replacement of the target is.
+    pub fn set_copy_cross_device_renames(&self, copy: bool) {
+        self.copy_cross_device_renames.store(copy, Ordering::SeqCst);
+    }
+
     /// Forking the WasiState is used when either fork or vfork is called
     pub fn fork(&self) -> Self {
         let fd_map = self.fd_map.read().unwrap().clone();
@@ -478,6 +592,10 @@ impl WasiFs {
             freed_fds: Arc::new(RwLock::new(freed_fds)),
             current_dir: Mutex::new(self.current_dir.lock().unwrap().clone()),
             is_wasix: AtomicBool::new(self.is_wasix.load(Ordering::Acquire)),
+            copy_cross_device_renames: AtomicBool::new(self.copy_cross_device_renames()),
+            max_symlinks: AtomicU32::new(self.max_symlinks()),
+            confine_symlinks: AtomicBool::new(self.confine_symlinks()),
+            umask: AtomicU32::new(self.umask()),
             root_fs: self.root_fs.clone(),
             root_inode: self.root_inode.clone(),
             init_preopens: self.init_preopens.clone(),
@@ -576,6 +694,10 @@ impl WasiFs {
             freed_fds: Arc::new(RwLock::new(BinaryHeap::new())),
             current_dir: Mutex::new("/".to_string()),
             is_wasix: AtomicBool::new(false),
+            copy_cross_device_renames: AtomicBool::new(false),
+            max_symlinks: AtomicU32::new(MAX_SYMLINKS),
+            confine_symlinks: AtomicBool::new(false),
+            umask: AtomicU32::new(DEFAULT_UMASK),
             root_fs: fs_backing,
             root_inode,
             init_preopens: Default::default(),
@@ -860,11 +982,11 @@ impl WasiFs {
         inodes: &WasiInodes,
         mut cur_inode: InodeGuard,
         path_str: &str,
-        mut symlink_count: u32,
+        symlink_count: u32,
         follow_symlinks: bool,
     ) -> Result<InodeGuard, Errno> {
-        if symlink_count > MAX_SYMLINKS {
-            return Err(Errno::Mlink);
+        if symlink_count > self.max_symlinks() {
+            return Err(Errno::Loop);
         }
 
         let path: &Path = Path::new(path_str);
@@ -876,7 +998,7 @@ impl WasiFs {
             let last_component = i + 1 == n_components;
             // for each component traverse file structure
             // loading inodes as necessary
-            'symlink_resolution: while symlink_count < MAX_SYMLINKS {
+            'symlink_resolution: loop {
                 let processing_cur_inode = cur_inode.clone();
                 let mut guard = processing_cur_inode.write();
                 match guard.deref_mut() {
@@ -899,12 +1021,11 @@ impl WasiFs {
                             "." => continue 'path_iter,
                             _ => (),
                         }
-                        // used for full resolution of symlinks
-                        let mut loop_for_symlink = false;
                         if let Some(entry) =
                             entries.get(component.as_os_str().to_string_lossy().as_ref())
                         {
                             cur_inode = entry.clone();
+                            drop(guard);
                         } else {
                             let file = {
                                 let mut cd = path.clone();
@@ -943,14 +1064,8 @@ impl WasiFs {
                                     self.root_fs.readlink(&file).ok().ok_or(Errno::Noent)?;
                                 debug!("attempting to decompose path {:?}", link_value);
 
-                                let (pre_open_dir_fd, relative_path) = if link_value.is_relative() {
-                                    self.path_into_pre_open_and_relative_path(&file)?
-                                } else {
-                                    tracing::error!("Absolute symlinks are not yet supported");
-                                    return Err(Errno::Notsup);
-                                };
-                                loop_for_symlink = true;
-                                symlink_count += 1;
+                                let (pre_open_dir_fd, relative_path) =
+                                    self.path_into_pre_open_and_relative_path(&file)?;
                                 Kind::Symlink {
                                     base_po_dir: pre_open_dir_fd,
                                     path_to_symlink: relative_path.to_owned(),
@@ -1038,11 +1153,11 @@ impl WasiFs {
                                 }
                             }
                             cur_inode = new_inode;
+                        }
 
-                            if loop_for_symlink && follow_symlinks {
-                                debug!("Following symlink to {:?}", cur_inode);
-                                continue 'symlink_resolution;
-                            }
+                        // Only the last component may be left unresolved
+                        if follow_symlinks || !last_component {
+                            cur_inode = self.resolve_symlink(inodes, cur_inode, symlink_count)?;
                         }
                     }
                     Kind::Root { entries } => {
@@ -1073,45 +1188,11 @@ impl WasiFs {
                     | Kind::Epoll { .. } => {
                         return Err(Errno::Notdir);
                     }
-                    Kind::Symlink {
-                        base_po_dir,
-                        path_to_symlink,
-                        relative_path,
-                    } => {
-                        let new_base_dir = *base_po_dir;
-                        let new_base_inode = self.get_fd_inode(new_base_dir)?;
-
-                        // allocate to reborrow mutabily to recur
-                        let new_path = {
-                            /*if let Kind::Root { .. } = self.inodes[base_po_dir].kind {
-                                assert!(false, "symlinks should never be relative to the root");
-                            }*/
-                            let mut base = path_to_symlink.clone();
-                            // remove the symlink file itself from the path, leaving just the path from the base
-                            // to the dir containing the symlink
-                            base.pop();
-                            base.push(relative_path);
-                            base.to_string_lossy().to_string()
-                        };
-                        debug!("Following symlink recursively");
+                    Kind::Symlink { .. } => {
+                        // The component is looked up in the target of the
+                        // symlink, which is never a symlink itself
                         drop(guard);
-                        let symlink_inode = self.get_inode_at_path_inner(
-                            inodes,
-                            new_base_inode,
-                            &new_path,
-                            symlink_count + 1,
-                            follow_symlinks,
-                        )?;
-                        cur_inode = symlink_inode;
-                        // if we're at the very end and we found a file, then we're done
-                        // TODO: figure out if this should also happen for directories?
-                        let guard = cur_inode.read();
-                        if let Kind::File { .. } = guard.deref() {
-                            // check if on last step
-                            if last_component {
-                                break 'symlink_resolution;
-                            }
-                        }
+                        cur_inode = self.resolve_symlink(inodes, cur_inode, symlink_count)?;
                         continue 'symlink_resolution;
                     }
                 }
@@ -1122,6 +1203,87 @@ impl WasiFs {
         Ok(cur_inode)
     }
 
+    /// Follows `inode` to its final target if it's a symlink, with
+    /// `symlink_count` symlinks already traversed.
+    fn resolve_symlink(
+        &self,
+        inodes: &WasiInodes,
+        inode: InodeGuard,
+        symlink_count: u32,
+    ) -> Result<InodeGuard, Errno> {
+        let guard = inode.read();
+        let (base_po_dir, mut target) = match guard.deref() {
+            Kind::Symlink {
+                base_po_dir,
+                path_to_symlink,
+                relative_path,
+            } => {
+                // remove the symlink file itself from the path, leaving just
+                // the path from the base to the dir containing the symlink,
+                // unless the target is absolute and replaces it
+                let mut target = path_to_symlink.clone();
+                target.pop();
+                target.push(relative_path);
+                (*base_po_dir, target)
+            }
+            _ => {
+                drop(guard);
+                return Ok(inode);
+            }
+        };
+        drop(guard);
+
+        if self.confine_symlinks() && self.symlink_escapes_pre_open(base_po_dir, &target)? {
+            debug!(
+                ?target,
+                "Refusing to follow a symlink escaping its preopened directory"
+            );
+            return Err(Errno::Notcapable);
+        }
+
+        // Absolute targets are relative to the root of the process rather
+        // than to the preopened directory
+        let base_inode = if target.is_absolute() {
+            self.root_inode.clone()
+        } else {
+            self.get_fd_inode(base_po_dir)?
+        };
+        if target.as_os_str().is_empty() {
+            target.push(".");
+        }
+
+        debug!("Following symlink recursively");
+        self.get_inode_at_path_inner(
+            inodes,
+            base_inode,
+            &target.to_string_lossy(),
+            symlink_count + 1,
+            true,
+        )
+    }
+
+    /// Whether the target of a symlink, as resolved from the preopened
+    /// directory `base_po_dir`, lexically leaves that directory.
+    fn symlink_escapes_pre_open(&self, base_po_dir: WasiFd, target: &Path) -> Result<bool, Errno> {
+        let po_inode = self.get_fd_inode(base_po_dir)?;
+        let po_path = match po_inode.read().deref() {
+            Kind::Dir { path, .. } => path.clone(),
+            _ => PathBuf::from("/"),
+        };
+
+        let mut normalized = PathBuf::new();
+        for component in po_path.join(target).components() {
+            match component {
+                Component::ParentDir => {
+                    normalized.pop();
+                }
+                Component::CurDir => {}
+                component => normalized.push(component),
+            }
+        }
+        Ok(!normalized.starts_with(&po_path))
+    }
+
     /// Finds the preopened directory that is the "best match" for the given path and
     /// returns a path relative to this preopened directory.
     ///
@@ -1131,7 +1293,7 @@ impl WasiFs {
     /// directory, `a/b` and the relative path `c/file`.
     ///
     /// In the case of a tie, the later preopened fd is preferred.
-    fn path_into_pre_open_and_relative_path<'path>(
+    pub(crate) fn path_into_pre_open_and_relative_path<'path>(
         &self,
         path: &'path Path,
     ) -> Result<(WasiFd, &'path Path), Errno> {
@@ -1185,32 +1347,6 @@ impl WasiFs {
         }
     }
 
-    /// finds the number of directories between the fd and the inode if they're connected
-    /// expects inode to point to a directory
-    pub(crate) fn path_depth_from_fd(&self, fd: WasiFd, inode: InodeGuard) -> Result<usize, Errno> {
-        let mut counter = 0;
-        let base_inode = self.get_fd_inode(fd)?;
-        let mut cur_inode = inode;
-
-        while cur_inode.ino() != base_inode.ino() {
-            counter += 1;
-
-            let processing_cur_inode = cur_inode.clone();
-            let guard = processing_cur_inode.read();
-
-            match guard.deref() {
-                Kind::Dir { parent, .. } => {
-                    if let Some(p) = parent.upgrade() {
-                        cur_inode = p;
-                    }
-                }
-                _ => return Err(Errno::Inval),
-            }
-        }
-
-        Ok(counter)
-    }
-
     /// gets a host file from a base directory and a path
     /// this function ensures the fs remains sandboxed
     // NOTE: follow symlinks is super weird right now
@@ -1601,6 +1737,20 @@ impl WasiFs {
         Ok(idx)
     }
 
+    /// Inserts a file descriptor passed by another process, which shares its
+    /// offset with the sender's like a duplicated one.
+    pub(crate) fn receive_fd(&self, fd: Fd) -> WasiFd {
+        let idx = self.get_first_free_fd();
+        self.fd_map.write().unwrap().insert(
+            idx,
+            Fd {
+                is_stdio: false,
+                ..fd
+            },
+        );
+        idx
+    }
+
     /// Low level function to remove an inode, that is it deletes the WASI FS's
     /// knowledge of a file.
     ///
@@ -1965,6 +2115,93 @@ impl WasiFs {
         })
     }
 
+    /// Reports the capacity and usage of the file system holding a file or
+    /// directory, which is the file system mounted there when it's in a mount
+    pub fn get_statfs_for_kind(&self, kind: &Kind) -> Result<Statfs, Errno> {
+        let stats = match kind {
+            Kind::File { path, .. } | Kind::Dir { path, .. } => self.root_fs.statfs(path),
+            Kind::Root { .. } => self.root_fs.statfs(Path::new("/")),
+            _ => return Err(Errno::Inval),
+        }
+        .map_err(fs_error_into_wasi_err)?;
+        Ok(Statfs {
+            block_size: stats.block_size,
+            blocks: stats.blocks,
+            blocks_free: stats.blocks_free,
+            // No blocks are reserved for privileged processes
+            blocks_available: stats.blocks_free,
+            files: stats.files,
+            files_free: stats.files_free,
+            name_max: 255,
+        })
+    }
+
+    /// Returns the mode and owner of a file or directory
+    pub fn get_permissions_for_kind(&self, kind: &Kind) -> Result<Permissions, Errno> {
+        let path = match kind {
+            Kind::File { path, .. } | Kind::Dir { path, .. } => path.as_path(),
+            Kind::Root { .. } => Path::new("/"),
+            Kind::Symlink { .. } => {
+                let ft = virtual_fs::FileType {
+                    symlink: true,
+                    ..Default::default()
+                };
+                return Ok(Permissions::default_for(&ft));
+            }
+            // The pipes, sockets and the like are only accessible to their
+            // owner
+            _ => {
+                return Ok(Permissions {
+                    mode: 0o600,
+                    uid: 0,
+                    gid: 0,
+                })
+            }
+        };
+        self.root_fs
+            .permissions(path)
+            .map_err(fs_error_into_wasi_err)
+    }
+
+    /// Changes the mode and owner of a file or directory. Those of the
+    /// symlinks, pipes, sockets and the like can't be changed, which is
+    /// ignored.
+    pub fn set_permissions_for_kind(
+        &self,
+        kind: &Kind,
+        update: impl FnOnce(&mut Permissions),
+    ) -> Result<(), Errno> {
+        let path = match kind {
+            Kind::File { path, .. } | Kind::Dir { path, .. } => path.as_path(),
+            Kind::Root { .. } => Path::new("/"),
+            _ => return Ok(()),
+        };
+        let mut permissions = self
+            .root_fs
+            .permissions(path)
+            .map_err(fs_error_into_wasi_err)?;
+        update(&mut permissions);
+        self.root_fs
+            .set_permissions(path, permissions)
+            .map_err(fs_error_into_wasi_err)
+    }
+
+    /// Removes the bits of the umask from the mode of a file or directory
+    /// which was just created, when they aren't already removed from the
+    /// default one.
+    pub(crate) fn apply_umask(&self, path: &Path, is_dir: bool) {
+        let mode = if is_dir { 0o777 } else { 0o666 } & !self.umask();
+        let Ok(mut permissions) = self.root_fs.permissions(path) else {
+            return;
+        };
+        if permissions.mode != mode {
+            permissions.mode = mode;
+            if let Err(err) = self.root_fs.set_permissions(path, permissions) {
+                debug!(path = %path.display(), %err, "Unable to apply the umask");
+            }
+        }
+    }
+
     /// Closes an open FD, handling all details such as FD being preopen
     pub(crate) fn close_fd(&self, fd: WasiFd) -> Result<(), Errno> {
         let mut fd_map = self.fd_map.write().unwrap();
@@ -2043,6 +2280,7 @@ pub fn fs_error_from_wasi_err(err: Errno) -> FsError {
         Errno::Again => FsError::WouldBlock,
         Errno::Nospc => FsError::WriteZero,
         Errno::Notempty => FsError::DirectoryNotEmpty,
+        Errno::Xdev => FsError::CrossDevice,
         _ => FsError::UnknownError,
     }
 }
@@ -2072,8 +2310,144 @@ pub fn fs_error_into_wasi_err(fs_error: FsError) -> Errno {
         FsError::WouldBlock => Errno::Again,
         FsError::WriteZero => Errno::Nospc,
         FsError::DirectoryNotEmpty => Errno::Notempty,
-        FsError::StorageFull => Errno::Overflow,
+        FsError::StorageFull => Errno::Nospc,
+        FsError::CrossDevice => Errno::Xdev,
         FsError::Lock | FsError::UnknownError => Errno::Io,
         FsError::Unsupported => Errno::Notsup,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use virtual_fs::TmpFileSystem;
+
+    use super::*;
+
+    /// A file system with `/` and `/data` preopened, and `/secret` and
+    /// `/data/file` files.
+    fn wasi_fs() -> (WasiInodes, WasiFs) {
+        let tmp_fs = TmpFileSystem::new();
+        tmp_fs.create_dir(Path::new("/data")).unwrap();
+        for file in ["/secret", "/data/file"] {
+            tmp_fs
+                .new_open_options()
+                .write(true)
+                .create(true)
+                .open(file)
+                .unwrap();
+        }
+
+        let inodes = WasiInodes::new();
+        let vfs_preopens = ["/".to_string(), "/data".to_string()];
+        let fs = WasiFs::new_with_preopen(
+            &inodes,
+            &[],
+            &vfs_preopens,
+            WasiFsRoot::Sandbox(Arc::new(tmp_fs)),
+        )
+        .unwrap();
+        (inodes, fs)
+    }
+
+    fn data_fd(fs: &WasiFs) -> WasiFd {
+        *fs.preopen_fds.read().unwrap().last().unwrap()
+    }
+
+    fn symlink(inodes: &WasiInodes, fs: &WasiFs, name: &str, target: &str) {
+        let kind = Kind::Symlink {
+            base_po_dir: data_fd(fs),
+            path_to_symlink: PathBuf::from(name),
+            relative_path: PathBuf::from(target),
+        };
+        let inode = fs.create_inode_with_default_stat(inodes, kind, false, name.to_string().into());
+        let data = fs.get_fd_inode(data_fd(fs)).unwrap();
+        let mut guard = data.write();
+        if let Kind::Dir { entries, .. } = guard.deref_mut() {
+            entries.insert(name.to_string(), inode);
+        }
+    }
+
+    fn is_file(inode: Result<InodeGuard, Errno>) -> bool {
+        matches!(inode.unwrap().read().deref(), Kind::File { .. })
+    }
+
+    #[test]
+    fn symlink_loops_fail_with_eloop() {
+        let (inodes, fs) = wasi_fs();
+        symlink(&inodes, &fs, "a", "b");
+        symlink(&inodes, &fs, "b", "a");
+        symlink(&inodes, &fs, "c", "d");
+        symlink(&inodes, &fs, "d", "./file");
+
+        let result = fs.get_inode_at_path(&inodes, data_fd(&fs), "a", true);
+        assert_eq!(result.err(), Some(Errno::Loop));
+        assert!(is_file(fs.get_inode_at_path(
+            &inodes,
+            data_fd(&fs),
+            "c",
+            true
+        )));
+
+        fs.set_max_symlinks(1);
+        let result = fs.get_inode_at_path(&inodes, data_fd(&fs), "c", true);
+        assert_eq!(result.err(), Some(Errno::Loop));
+    }
+
+    #[test]
+    fn confined_symlinks_cant_escape_their_preopen() {
+        let (inodes, fs) = wasi_fs();
+        symlink(&inodes, &fs, "relative", "../secret");
+        symlink(&inodes, &fs, "absolute", "/secret");
+        symlink(&inodes, &fs, "inside", "/data/file");
+
+        for link in ["relative", "absolute", "inside"] {
+            assert!(is_file(fs.get_inode_at_path(
+                &inodes,
+                data_fd(&fs),
+                link,
+                true
+            )));
+        }
+
+        fs.set_confine_symlinks(true);
+        for link in ["relative", "absolute"] {
+            let result = fs.get_inode_at_path(&inodes, data_fd(&fs), link, true);
+            assert_eq!(result.err(), Some(Errno::Notcapable));
+        }
+        assert!(is_file(fs.get_inode_at_path(
+            &inodes,
+            data_fd(&fs),
+            "inside",
+            true
+        )));
+    }
+
+    #[test]
+    fn permissions_follow_the_umask_and_chmod() {
+        let (inodes, fs) = wasi_fs();
+        let file = fs
+            .get_inode_at_path(&inodes, data_fd(&fs), "file", true)
+            .unwrap();
+        let mode = |fs: &WasiFs| {
+            let guard = file.read();
+            fs.get_permissions_for_kind(guard.deref()).unwrap().mode
+        };
+        assert_eq!(mode(&fs), 0o644);
+
+        assert_eq!(fs.set_umask(0o077), DEFAULT_UMASK);
+        fs.apply_umask(Path::new("/data/file"), false);
+        assert_eq!(mode(&fs), 0o600);
+
+        {
+            let guard = file.read();
+            fs.set_permissions_for_kind(guard.deref(), |permissions| {
+                permissions.mode = 0o4755;
+                permissions.uid = 1000;
+            })
+            .unwrap();
+        }
+        assert_eq!(mode(&fs), 0o4755);
+        // The umask is inherited by the forked processes
+        assert_eq!(fs.fork().umask(), 0o077);
+    }
+}
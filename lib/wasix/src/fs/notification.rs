@@ -4,6 +4,8 @@ use std::{
     task::{Poll, Waker},
 };
 
+use wasmer_wasix_types::wasi::Errno;
+
 #[derive(Debug)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 struct NotificationState {
@@ -17,6 +19,9 @@ struct NotificationState {
     /// All the registered wakers
     #[cfg_attr(feature = "enable-serde", serde(skip))]
     wakers: VecDeque<Waker>,
+    /// The wakers of the writers waiting for the counter to be read
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    write_wakers: VecDeque<Waker>,
 }
 
 impl NotificationState {
@@ -26,6 +31,12 @@ impl NotificationState {
         }
     }
 
+    fn add_write_waker(&mut self, waker: &Waker) {
+        if !self.write_wakers.iter().any(|a| a.will_wake(waker)) {
+            self.write_wakers.push_front(waker.clone());
+        }
+    }
+
     fn wake_all(&mut self) {
         self.last_poll = u64::MAX;
         while let Some(waker) = self.wakers.pop_front() {
@@ -33,27 +44,47 @@ impl NotificationState {
         }
     }
 
-    fn inc(&mut self, val: u64) {
-        self.counter += val;
-        self.wake_all();
+    fn wake_writers(&mut self) {
+        while let Some(waker) = self.write_wakers.pop_front() {
+            waker.wake();
+        }
     }
 
-    fn dec(&mut self) -> u64 {
-        let val = self.counter;
-        if self.is_semaphore {
-            if self.counter > 0 {
-                self.counter -= 1;
-                if self.counter > 0 {
-                    self.wake_all();
-                }
+    /// Adds `val` to the counter, unless it would overflow.
+    fn inc(&mut self, val: u64) -> bool {
+        match self.counter.checked_add(val) {
+            Some(counter) if counter <= MAX_COUNTER => {
+                self.counter = counter;
+                self.wake_all();
+                true
             }
-        } else {
-            self.counter = 0;
+            _ => false,
         }
+    }
+
+    /// Reads the counter, which is decremented in semaphore mode and reset
+    /// otherwise, or returns zero when there is nothing to read.
+    fn dec(&mut self) -> u64 {
+        if self.counter == 0 {
+            return 0;
+        }
+        let val = match self.is_semaphore {
+            true => 1,
+            false => self.counter,
+        };
+        self.counter -= val;
+        if self.counter > 0 {
+            self.wake_all();
+        }
+        self.wake_writers();
         val
     }
 }
 
+/// The largest value of the counter, as on Linux. A write which would take
+/// the counter past it waits for the counter to be read.
+pub const MAX_COUNTER: u64 = u64::MAX - 1;
+
 #[derive(Debug)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 pub struct NotificationInner {
@@ -63,6 +94,33 @@ pub struct NotificationInner {
 }
 
 impl NotificationInner {
+    /// The size of the values read from and written to the counter, which
+    /// are only read and written whole.
+    pub const VALUE_SIZE: usize = std::mem::size_of::<u64>();
+
+    /// Checks that a buffer of `buf_len` bytes can hold the counter it
+    /// reads.
+    pub fn check_read_len(buf_len: u64) -> Result<(), Errno> {
+        if buf_len < Self::VALUE_SIZE as u64 {
+            return Err(Errno::Inval);
+        }
+        Ok(())
+    }
+
+    /// The value written in `buf`, which holds it whole. The largest value
+    /// can't be written, as the counter can't reach it.
+    pub fn value_of(buf: &[u8]) -> Result<u64, Errno> {
+        let val = buf
+            .get(..Self::VALUE_SIZE)
+            .and_then(|buf| buf.try_into().ok())
+            .map(u64::from_ne_bytes)
+            .ok_or(Errno::Inval)?;
+        if val == u64::MAX {
+            return Err(Errno::Inval);
+        }
+        Ok(val)
+    }
+
     pub fn new(initial_val: u64, is_semaphore: bool) -> Self {
         Self {
             state: Mutex::new(NotificationState {
@@ -70,24 +128,53 @@ impl NotificationInner {
                 last_poll: u64::MAX,
                 is_semaphore,
                 wakers: Default::default(),
+                write_wakers: Default::default(),
             }),
         }
     }
+    /// Polls for the counter changing since it was last polled, which is
+    /// only ready while there are events to read.
     pub fn poll(&self, waker: &Waker) -> Poll<usize> {
         let mut state = self.state.lock().unwrap();
         state.add_waker(waker);
 
         if state.last_poll != state.counter {
             state.last_poll = state.counter;
-            Poll::Ready(state.counter as usize)
-        } else {
-            Poll::Pending
+            if state.counter > 0 {
+                return Poll::Ready(state.counter as usize);
+            }
+        }
+        Poll::Pending
+    }
+
+    /// Polls for the counter to be writable, which it stays until it
+    /// reaches its maximum.
+    pub fn poll_write_ready(&self, waker: &Waker) -> Poll<usize> {
+        let mut state = self.state.lock().unwrap();
+        if state.counter < MAX_COUNTER {
+            return Poll::Ready((MAX_COUNTER - state.counter) as usize);
         }
+        state.add_write_waker(waker);
+        Poll::Pending
     }
 
-    pub fn write(&self, val: u64) {
+    pub fn write(&self, val: u64, waker: &Waker) -> Poll<()> {
         let mut state = self.state.lock().unwrap();
-        state.inc(val);
+        if state.inc(val) {
+            return Poll::Ready(());
+        }
+        state.add_write_waker(waker);
+        Poll::Pending
+    }
+
+    /// Writes `val` unless it would overflow the counter, which fails with
+    /// [`Errno::Again`] rather than waiting for it to be read.
+    pub fn try_write(&self, val: u64) -> Result<(), Errno> {
+        let mut state = self.state.lock().unwrap();
+        match state.inc(val) {
+            true => Ok(()),
+            false => Err(Errno::Again),
+        }
     }
 
     pub fn read(&self, waker: &Waker) -> Poll<u64> {
@@ -112,3 +199,58 @@ impl NotificationInner {
         state.last_poll = u64::MAX;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn semaphores_are_read_one_at_a_time() {
+        let inner = NotificationInner::new(3, true);
+        assert_eq!(inner.try_read(), Some(1));
+        assert_eq!(inner.try_read(), Some(1));
+        assert!(inner.try_write(2).is_ok());
+        assert_eq!(inner.try_read(), Some(1));
+        assert_eq!(inner.try_read(), Some(1));
+        assert_eq!(inner.try_read(), Some(1));
+        assert_eq!(inner.try_read(), None);
+
+        // Unlike counters, which are read whole
+        let inner = NotificationInner::new(3, false);
+        assert_eq!(inner.try_read(), Some(3));
+        assert_eq!(inner.try_read(), None);
+    }
+
+    #[test]
+    fn writes_past_the_maximum_would_block() {
+        let inner = NotificationInner::new(MAX_COUNTER - 1, false);
+        assert_eq!(inner.try_write(2), Err(Errno::Again));
+        assert_eq!(inner.try_write(u64::MAX - 1), Err(Errno::Again));
+        assert!(inner.try_write(1).is_ok());
+        assert_eq!(inner.try_write(1), Err(Errno::Again));
+
+        // until the counter is read
+        assert_eq!(inner.try_read(), Some(MAX_COUNTER));
+        assert!(inner.try_write(MAX_COUNTER).is_ok());
+    }
+
+    #[test]
+    fn values_are_read_and_written_whole() {
+        assert_eq!(NotificationInner::check_read_len(0), Err(Errno::Inval));
+        assert_eq!(NotificationInner::check_read_len(7), Err(Errno::Inval));
+        assert_eq!(NotificationInner::check_read_len(8), Ok(()));
+        assert_eq!(NotificationInner::check_read_len(64), Ok(()));
+
+        assert_eq!(NotificationInner::value_of(&[]), Err(Errno::Inval));
+        assert_eq!(NotificationInner::value_of(&[1; 7]), Err(Errno::Inval));
+        assert_eq!(NotificationInner::value_of(&5u64.to_ne_bytes()), Ok(5));
+        assert_eq!(
+            NotificationInner::value_of(&u64::MAX.to_ne_bytes()),
+            Err(Errno::Inval)
+        );
+        assert_eq!(
+            NotificationInner::value_of(&MAX_COUNTER.to_ne_bytes()),
+            Ok(MAX_COUNTER)
+        );
+    }
+}
@@ -0,0 +1,323 @@
+use std::{
+    collections::HashMap,
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex, RwLock},
+    task::{Context, Poll},
+};
+
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+use virtual_fs::{
+    limiter::{DynFsMemoryLimiter, QuotaLimiter, TrackedVec},
+    FsError, VirtualFile,
+};
+use wasmer_wasix_types::wasi::Errno;
+
+/// Longest name of a shared memory object, without its leading slash
+const NAME_MAX: usize = 255;
+
+/// How many bytes the shared memory objects hold at most altogether, past
+/// which growing them fails with `Errno::Nospc`
+const SHARED_MEMORY_QUOTA: u64 = 1 << 30;
+
+/// The contents of a shared memory object, which live as long as the object
+/// has a name, or is opened or mapped by a process.
+///
+/// The memory of a process can't be backed by an object, so the processes
+/// mapping it each have a copy which is only merged with the object when it
+/// is synced. The mappings aren't coherent: atomics and futexes don't work
+/// across processes through them, which have to sync to see each other's
+/// changes.
+#[derive(Debug, Clone)]
+pub(crate) struct SharedMemory {
+    data: Arc<RwLock<TrackedVec>>,
+}
+
+impl SharedMemory {
+    fn new(limiter: Option<DynFsMemoryLimiter>) -> Self {
+        Self {
+            data: Arc::new(RwLock::new(TrackedVec::new(limiter))),
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.data.read().unwrap().len() as u64
+    }
+
+    /// Syncs the copy a process mapped of `offset..offset + mapped.len()`
+    /// with the object. The bytes the process changed since `synced`, what
+    /// it held at the last sync, go to the object, the others are updated
+    /// with the changes of the other processes. What is past the end of the
+    /// object is left alone.
+    pub fn merge(&self, offset: u64, mapped: &mut [u8], synced: &mut [u8]) {
+        let mut data = self.data.write().unwrap();
+        let start = (offset as usize).min(data.len());
+        let len = mapped.len().min(data.len() - start);
+        let object = &mut data[start..start + len];
+        for ((byte, synced), object) in mapped.iter_mut().zip(synced.iter()).zip(object) {
+            match *byte == *synced {
+                true => *byte = *object,
+                false => *object = *byte,
+            }
+        }
+        synced[..len].copy_from_slice(&mapped[..len]);
+    }
+}
+
+/// The named shared memory objects, shared by all the processes.
+#[derive(Debug)]
+pub(crate) struct SharedMemoryObjects {
+    objects: Mutex<HashMap<String, SharedMemory>>,
+    /// What the objects hold altogether, including those which were unlinked
+    /// but are still opened
+    limiter: DynFsMemoryLimiter,
+}
+
+impl Default for SharedMemoryObjects {
+    fn default() -> Self {
+        Self::with_quota(SHARED_MEMORY_QUOTA)
+    }
+}
+
+impl SharedMemoryObjects {
+    /// Creates the objects, which hold at most `quota` bytes altogether.
+    pub fn with_quota(quota: u64) -> Self {
+        Self {
+            objects: Default::default(),
+            limiter: Arc::new(QuotaLimiter::new(quota)),
+        }
+    }
+
+    /// Opens the object named `name`, creating it empty when `create` is set
+    /// and it doesn't exist, which it must not when `exclusive` is also set.
+    pub fn open(&self, name: &str, create: bool, exclusive: bool) -> Result<SharedMemory, Errno> {
+        let name = check_name(name)?;
+        let mut objects = self.objects.lock().unwrap();
+        match objects.get(name) {
+            Some(_) if create && exclusive => Err(Errno::Exist),
+            Some(object) => Ok(object.clone()),
+            None if create => {
+                let object = SharedMemory::new(Some(self.limiter.clone()));
+                objects.insert(name.to_string(), object.clone());
+                Ok(object)
+            }
+            None => Err(Errno::Noent),
+        }
+    }
+
+    /// Removes the name of an object, whose contents stay until the
+    /// processes which opened or mapped it are done with it.
+    pub fn unlink(&self, name: &str) -> Result<(), Errno> {
+        let name = check_name(name)?;
+        match self.objects.lock().unwrap().remove(name) {
+            Some(_) => Ok(()),
+            None => Err(Errno::Noent),
+        }
+    }
+}
+
+/// Strips the leading slash of a name, which has no other.
+fn check_name(name: &str) -> Result<&str, Errno> {
+    let name = name.strip_prefix('/').ok_or(Errno::Inval)?;
+    if name.is_empty() || name.contains('/') {
+        return Err(Errno::Inval);
+    }
+    if name.len() > NAME_MAX {
+        return Err(Errno::Nametoolong);
+    }
+    Ok(name)
+}
+
+/// The file of the `shm_open` file descriptors, which reads and writes the
+/// contents of the object right away.
+#[derive(Debug)]
+pub(crate) struct SharedMemoryFile {
+    object: SharedMemory,
+    cursor: u64,
+}
+
+impl SharedMemoryFile {
+    pub fn new(object: SharedMemory) -> Self {
+        Self { object, cursor: 0 }
+    }
+
+    pub fn object(&self) -> &SharedMemory {
+        &self.object
+    }
+}
+
+impl AsyncSeek for SharedMemoryFile {
+    fn start_seek(mut self: Pin<&mut Self>, position: io::SeekFrom) -> io::Result<()> {
+        let cursor = match position {
+            io::SeekFrom::Start(offset) => Some(offset),
+            io::SeekFrom::End(offset) => self.object.len().checked_add_signed(offset),
+            io::SeekFrom::Current(offset) => self.cursor.checked_add_signed(offset),
+        };
+        self.cursor = cursor.ok_or(io::ErrorKind::InvalidInput)?;
+        Ok(())
+    }
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Poll::Ready(Ok(self.cursor))
+    }
+}
+
+impl AsyncWrite for SharedMemoryFile {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        // An object can't grow past what the memory can address
+        let end = self
+            .cursor
+            .checked_add(buf.len() as u64)
+            .and_then(|end| usize::try_from(end).ok())
+            .ok_or(FsError::StorageFull)?;
+        let start = end - buf.len();
+        let mut data = self.object.data.write().unwrap();
+        if data.len() < end {
+            data.resize(end, 0)?;
+        }
+        data[start..end].copy_from_slice(buf);
+        drop(data);
+        self.cursor += buf.len() as u64;
+        Poll::Ready(Ok(buf.len()))
+    }
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for SharedMemoryFile {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let data = self.object.data.read().unwrap();
+        let start = (self.cursor as usize).min(data.len());
+        let len = buf.remaining().min(data.len() - start);
+        buf.put_slice(&data[start..start + len]);
+        drop(data);
+        self.cursor += len as u64;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl VirtualFile for SharedMemoryFile {
+    fn last_accessed(&self) -> u64 {
+        0
+    }
+    fn last_modified(&self) -> u64 {
+        0
+    }
+    fn created_time(&self) -> u64 {
+        0
+    }
+    fn size(&self) -> u64 {
+        self.object.len()
+    }
+    fn set_len(&mut self, new_size: u64) -> virtual_fs::Result<()> {
+        let new_size = usize::try_from(new_size).map_err(|_| FsError::StorageFull)?;
+        self.object.data.write().unwrap().resize(new_size, 0)
+    }
+    fn unlink(&mut self) -> virtual_fs::Result<()> {
+        Ok(())
+    }
+    fn poll_read_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        let len = self.object.len().saturating_sub(self.cursor);
+        Poll::Ready(Ok(len as usize))
+    }
+    fn poll_write_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        Poll::Ready(Ok(8192))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use virtual_fs::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::utils::map_io_err;
+
+    #[test]
+    fn objects_live_until_they_are_unlinked() {
+        let objects = SharedMemoryObjects::default();
+        assert_eq!(
+            objects.open("/data", false, false).unwrap_err(),
+            Errno::Noent
+        );
+        assert_eq!(objects.open("data", true, false).unwrap_err(), Errno::Inval);
+        assert_eq!(objects.open("/a/b", true, false).unwrap_err(), Errno::Inval);
+
+        let object = objects.open("/data", true, true).unwrap();
+        assert_eq!(objects.open("/data", true, true).unwrap_err(), Errno::Exist);
+        let mut file = SharedMemoryFile::new(objects.open("/data", false, false).unwrap());
+        block_on(file.write_all(b"hello")).unwrap();
+        assert_eq!(object.len(), 5);
+
+        // The contents stay for those who opened the object
+        objects.unlink("/data").unwrap();
+        assert_eq!(objects.unlink("/data").unwrap_err(), Errno::Noent);
+        let mut buf = String::new();
+        block_on(file.rewind()).unwrap();
+        block_on(file.read_to_string(&mut buf)).unwrap();
+        assert_eq!(buf, "hello");
+        assert_eq!(objects.open("/data", true, true).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn objects_grow_within_their_quota() {
+        let objects = SharedMemoryObjects::with_quota(16);
+        let mut file = SharedMemoryFile::new(objects.open("/data", true, false).unwrap());
+        file.set_len(10).unwrap();
+        let mut other = SharedMemoryFile::new(objects.open("/other", true, false).unwrap());
+        assert_eq!(other.set_len(10).unwrap_err(), FsError::StorageFull);
+        other.set_len(6).unwrap();
+
+        // Neither a write past the quota nor one past the end of the address
+        // space grows the object
+        block_on(file.seek(io::SeekFrom::End(0))).unwrap();
+        let err = block_on(file.write_all(&[1; 7])).unwrap_err();
+        assert_eq!(map_io_err(err), Errno::Nospc);
+        block_on(file.seek(io::SeekFrom::Start(u64::MAX - 1))).unwrap();
+        let err = block_on(file.write_all(b"hello")).unwrap_err();
+        assert_eq!(map_io_err(err), Errno::Nospc);
+        assert_eq!(file.set_len(u64::MAX).unwrap_err(), FsError::StorageFull);
+        assert_eq!(file.size(), 10);
+
+        // Shrinking an object gives its bytes back to the others
+        file.set_len(0).unwrap();
+        other.set_len(16).unwrap();
+    }
+
+    #[test]
+    fn mappings_are_merged_with_the_changes_of_the_others() {
+        let object = SharedMemory::new(None);
+        let mut file = SharedMemoryFile::new(object.clone());
+        file.set_len(4).unwrap();
+
+        let mut first = [0u8; 4];
+        let mut first_synced = [0u8; 4];
+        let mut second = [0u8; 4];
+        let mut second_synced = [0u8; 4];
+        first[0] = 1;
+        second[3] = 2;
+        object.merge(0, &mut first, &mut first_synced);
+        object.merge(0, &mut second, &mut second_synced);
+        assert_eq!(second, [1, 0, 0, 2]);
+        object.merge(0, &mut first, &mut first_synced);
+        assert_eq!(first, [1, 0, 0, 2]);
+
+        // What is past the end of the object is left alone
+        let mut past = [7u8; 6];
+        let mut past_synced = [0u8; 6];
+        object.merge(2, &mut past, &mut past_synced);
+        assert_eq!(past, [7, 7, 7, 7, 7, 7]);
+        assert_eq!(past_synced, [7, 7, 0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,295 @@
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use futures::task::{waker, ArcWake};
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+use virtual_fs::VirtualFile;
+use wasmer_wasix_types::wasi::Snapshot0Clockid;
+
+use crate::{syscalls::platform_clock_time_get, VirtualTaskManager};
+
+/// Size of the expiration count read from a timer file
+const EXPIRATIONS_LEN: usize = 8;
+
+type Sleep = Pin<Box<dyn Future<Output = ()> + Send + Sync + 'static>>;
+
+/// When a timer expires, on the monotonic clock.
+#[derive(Debug, Default)]
+struct TimerState {
+    /// When the timer expires next, unless it's disarmed
+    deadline: Option<Duration>,
+    /// The period of the timer, which only expires once when it's zero
+    interval: Duration,
+    /// The expirations which were not read yet
+    expirations: u64,
+}
+
+impl TimerState {
+    /// Arms the timer to expire `value` from `now`, or disarms it when
+    /// `value` is `None`. The expirations which were not read are dropped.
+    fn set(&mut self, now: Duration, value: Option<Duration>, interval: Duration) {
+        self.deadline = value.map(|value| now + value);
+        self.interval = interval;
+        self.expirations = 0;
+    }
+
+    /// The time left until the timer expires, zero when it's disarmed, and
+    /// its interval.
+    fn get(&mut self, now: Duration) -> (Duration, Duration) {
+        self.expire(now);
+        let left = self
+            .deadline
+            .map(|deadline| deadline.saturating_sub(now))
+            .unwrap_or_default();
+        (left, self.interval)
+    }
+
+    /// Counts the expirations up to `now`.
+    fn expire(&mut self, now: Duration) {
+        let Some(deadline) = self.deadline.filter(|deadline| *deadline <= now) else {
+            return;
+        };
+        if self.interval.is_zero() {
+            self.expirations += 1;
+            self.deadline = None;
+            return;
+        }
+        let elapsed = (now - deadline).as_nanos();
+        let interval = self.interval.as_nanos();
+        let missed = (elapsed / interval) as u64;
+        self.expirations = self.expirations.saturating_add(missed + 1);
+        self.deadline = Some(now + Duration::from_nanos((interval - elapsed % interval) as u64));
+    }
+
+    /// Takes the expirations up to `now`.
+    fn take(&mut self, now: Duration) -> u64 {
+        self.expire(now);
+        std::mem::take(&mut self.expirations)
+    }
+}
+
+/// The tasks waiting for a timer to expire, which are all woken by the
+/// sleep of the timer.
+#[derive(Debug, Default)]
+struct TimerWakers {
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl ArcWake for TimerWakers {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        for waker in arc_self.wakers.lock().unwrap().drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+/// The file of the `fd_timer_create` file descriptors, which is readable
+/// once the timer expired. Reading it returns the number of expirations
+/// since it was last read, as a native-endian `u64`.
+pub(crate) struct TimerFile {
+    clock: Snapshot0Clockid,
+    tasks: Arc<dyn VirtualTaskManager>,
+    state: Mutex<TimerState>,
+    /// Sleeps until the deadline, while tasks wait for it
+    sleep: Mutex<Option<Sleep>>,
+    wakers: Arc<TimerWakers>,
+}
+
+impl TimerFile {
+    pub fn new(clock: Snapshot0Clockid, tasks: Arc<dyn VirtualTaskManager>) -> Self {
+        Self {
+            clock,
+            tasks,
+            state: Default::default(),
+            sleep: Default::default(),
+            wakers: Default::default(),
+        }
+    }
+
+    /// The clock the absolute expirations of the timer are given on.
+    pub fn clock(&self) -> Snapshot0Clockid {
+        self.clock
+    }
+
+    /// Arms the timer to expire in `value`, then every `interval` unless it's
+    /// zero, or disarms it when `value` is `None`.
+    pub fn set(&self, value: Option<Duration>, interval: Duration) {
+        self.state.lock().unwrap().set(now(), value, interval);
+        // The tasks waiting for the timer wait for the new deadline
+        self.sleep.lock().unwrap().take();
+        ArcWake::wake_by_ref(&self.wakers);
+    }
+
+    /// The time left until the timer expires, zero when it's disarmed, and
+    /// its interval.
+    pub fn get(&self) -> (Duration, Duration) {
+        self.state.lock().unwrap().get(now())
+    }
+
+    /// Polls for the timer to expire, returning the expirations which were
+    /// not read yet.
+    fn poll_expired(&self, cx: &mut Context<'_>, take: bool) -> Poll<u64> {
+        loop {
+            let deadline = {
+                let mut state = self.state.lock().unwrap();
+                let now = now();
+                let expirations = match take {
+                    true => state.take(now),
+                    false => {
+                        state.expire(now);
+                        state.expirations
+                    }
+                };
+                if expirations > 0 {
+                    return Poll::Ready(expirations);
+                }
+                state.deadline.map(|deadline| deadline.saturating_sub(now))
+            };
+
+            let mut wakers = self.wakers.wakers.lock().unwrap();
+            if !wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                wakers.push(cx.waker().clone());
+            }
+            drop(wakers);
+            let Some(left) = deadline else {
+                return Poll::Pending;
+            };
+
+            let mut sleep = self.sleep.lock().unwrap();
+            let waker = waker(self.wakers.clone());
+            let ready = sleep
+                .get_or_insert_with(|| self.tasks.sleep_now(left))
+                .as_mut()
+                .poll(&mut Context::from_waker(&waker))
+                .is_ready();
+            if !ready {
+                return Poll::Pending;
+            }
+            *sleep = None;
+        }
+    }
+}
+
+impl std::fmt::Debug for TimerFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TimerFile")
+            .field("clock", &self.clock)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl AsyncSeek for TimerFile {
+    fn start_seek(self: Pin<&mut Self>, _position: io::SeekFrom) -> io::Result<()> {
+        Ok(())
+    }
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Poll::Ready(Ok(0))
+    }
+}
+
+impl AsyncWrite for TimerFile {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        _buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(Err(io::ErrorKind::InvalidInput.into()))
+    }
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for TimerFile {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if buf.remaining() < EXPIRATIONS_LEN {
+            return Poll::Ready(Err(io::ErrorKind::InvalidInput.into()));
+        }
+        let expirations = std::task::ready!(self.poll_expired(cx, true));
+        buf.put_slice(&expirations.to_ne_bytes());
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl VirtualFile for TimerFile {
+    fn last_accessed(&self) -> u64 {
+        0
+    }
+    fn last_modified(&self) -> u64 {
+        0
+    }
+    fn created_time(&self) -> u64 {
+        0
+    }
+    fn size(&self) -> u64 {
+        0
+    }
+    fn set_len(&mut self, _new_size: u64) -> virtual_fs::Result<()> {
+        Err(virtual_fs::FsError::PermissionDenied)
+    }
+    fn unlink(&mut self) -> virtual_fs::Result<()> {
+        Ok(())
+    }
+    fn poll_read_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        self.poll_expired(cx, false).map(|_| Ok(EXPIRATIONS_LEN))
+    }
+    fn poll_write_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        // Timer files are never writable
+        Poll::Pending
+    }
+}
+
+/// The monotonic time.
+fn now() -> Duration {
+    let nanos = platform_clock_time_get(Snapshot0Clockid::Monotonic, 1).unwrap_or(0);
+    Duration::from_nanos(nanos as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn one_shot_timers_expire_once() {
+        let mut timer = TimerState::default();
+        timer.set(secs(10), Some(secs(5)), Duration::ZERO);
+        assert_eq!(timer.get(secs(12)), (secs(3), Duration::ZERO));
+        assert_eq!(timer.take(secs(14)), 0);
+        assert_eq!(timer.take(secs(20)), 1);
+        assert_eq!(timer.take(secs(30)), 0);
+        assert_eq!(timer.get(secs(30)), (Duration::ZERO, Duration::ZERO));
+    }
+
+    #[test]
+    fn periodic_timers_count_the_missed_expirations() {
+        let mut timer = TimerState::default();
+        timer.set(secs(10), Some(secs(1)), secs(2));
+        assert_eq!(timer.take(secs(11)), 1);
+        // Expires at 13, 15 and 17
+        assert_eq!(timer.take(secs(18)), 3);
+        assert_eq!(timer.get(secs(18)), (secs(1), secs(2)));
+
+        // Setting the timer drops what was not read
+        timer.expire(secs(25));
+        timer.set(secs(25), None, secs(2));
+        assert_eq!(timer.take(secs(40)), 0);
+    }
+}
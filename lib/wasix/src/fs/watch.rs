@@ -0,0 +1,520 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+use virtual_fs::VirtualFile;
+use wasmer_wasix_types::wasi::{Errno, WatchMask};
+
+/// Maximum number of events waiting to be read from a watch file, the
+/// events past it are dropped and reported by a single `Q_OVERFLOW`
+const MAX_QUEUED_EVENTS: usize = 16384;
+
+/// Size of the `wd`, `mask`, `cookie` and `len` fields of an event
+const EVENT_HEADER_LEN: usize = 16;
+
+/// Events reported to the watches of the file or directory itself rather
+/// than to the watches of its parent directory
+const SELF_EVENTS: WatchMask = WatchMask::ACCESS
+    .union(WatchMask::MODIFY)
+    .union(WatchMask::ATTRIB)
+    .union(WatchMask::CLOSE_WRITE);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WatchEvent {
+    wd: u32,
+    mask: WatchMask,
+    cookie: u32,
+    /// Name of the entry of the watched directory the event is about
+    name: Option<String>,
+}
+
+impl WatchEvent {
+    /// Length of the name field, which is NUL terminated and padded to keep
+    /// the following event aligned
+    fn name_len(&self) -> usize {
+        self.name
+            .as_ref()
+            .map(|name| (name.len() + 1).next_multiple_of(EVENT_HEADER_LEN))
+            .unwrap_or_default()
+    }
+
+    fn len(&self) -> usize {
+        EVENT_HEADER_LEN + self.name_len()
+    }
+
+    /// Encodes the event in the layout of `struct inotify_event`
+    fn encode(&self, buf: &mut ReadBuf<'_>) {
+        let name_len = self.name_len();
+        buf.put_slice(&self.wd.to_le_bytes());
+        buf.put_slice(&self.mask.bits().to_le_bytes());
+        buf.put_slice(&self.cookie.to_le_bytes());
+        buf.put_slice(&(name_len as u32).to_le_bytes());
+        if let Some(name) = self.name.as_ref() {
+            buf.put_slice(name.as_bytes());
+            buf.put_slice(&vec![0u8; name_len - name.len()]);
+        }
+    }
+}
+
+/// The events of the watches of a watch file, waiting to be read
+#[derive(Debug, Default)]
+pub(crate) struct WatchQueue {
+    state: Mutex<WatchQueueState>,
+}
+
+#[derive(Debug, Default)]
+struct WatchQueueState {
+    events: VecDeque<WatchEvent>,
+    wd_seed: u32,
+    wakers: VecDeque<Waker>,
+}
+
+impl WatchQueueState {
+    fn add_waker(&mut self, waker: &Waker) {
+        if !self.wakers.iter().any(|a| a.will_wake(waker)) {
+            self.wakers.push_front(waker.clone());
+        }
+    }
+}
+
+impl WatchQueue {
+    fn push(&self, event: WatchEvent) {
+        let mut state = self.state.lock().unwrap();
+        // Identical events in a row are coalesced, like inotify does
+        if state.events.back() == Some(&event) {
+            return;
+        }
+        if state.events.len() >= MAX_QUEUED_EVENTS {
+            if state
+                .events
+                .back()
+                .is_some_and(|last| last.mask == WatchMask::Q_OVERFLOW)
+            {
+                return;
+            }
+            state.events.push_back(WatchEvent {
+                wd: u32::MAX,
+                mask: WatchMask::Q_OVERFLOW,
+                cookie: 0,
+                name: None,
+            });
+        } else {
+            state.events.push_back(event);
+        }
+        while let Some(waker) = state.wakers.pop_front() {
+            waker.wake();
+        }
+    }
+
+    fn next_wd(&self) -> u32 {
+        let mut state = self.state.lock().unwrap();
+        state.wd_seed += 1;
+        state.wd_seed
+    }
+}
+
+#[derive(Debug)]
+struct Watch {
+    queue: Arc<WatchQueue>,
+    wd: u32,
+    mask: WatchMask,
+}
+
+impl Watch {
+    fn push(&self, mask: WatchMask, cookie: u32, name: Option<String>) {
+        self.queue.push(WatchEvent {
+            wd: self.wd,
+            mask,
+            cookie,
+            name,
+        });
+    }
+}
+
+#[derive(Debug, Default)]
+struct FileWatchersInner {
+    /// The watches, indexed by the path of the file or directory they watch
+    paths: HashMap<PathBuf, Vec<Watch>>,
+    cookie_seed: u32,
+}
+
+impl FileWatchersInner {
+    /// Reports an event about `path` to the watches of its parent directory
+    /// and to its own watches
+    fn notify(&mut self, path: &Path, mask: WatchMask, cookie: u32) {
+        let event = mask - WatchMask::ISDIR;
+        if let (Some(dir), Some(name)) = (path.parent(), path.file_name()) {
+            for watch in self.paths.get(dir).into_iter().flatten() {
+                if watch.mask.intersects(event) {
+                    watch.push(mask, cookie, Some(name.to_string_lossy().into_owned()));
+                }
+            }
+        }
+
+        if event.contains(WatchMask::DELETE) {
+            self.remove_path(path, WatchMask::DELETE_SELF);
+        } else if event.contains(WatchMask::MOVED_FROM) {
+            for watch in self.paths.get(path).into_iter().flatten() {
+                if watch.mask.contains(WatchMask::MOVE_SELF) {
+                    watch.push(WatchMask::MOVE_SELF, 0, None);
+                }
+            }
+        } else if event.intersects(SELF_EVENTS) {
+            for watch in self.paths.get(path).into_iter().flatten() {
+                if watch.mask.intersects(event) {
+                    watch.push(mask & (SELF_EVENTS | WatchMask::ISDIR), 0, None);
+                }
+            }
+        }
+    }
+
+    /// Removes the watches of a file or directory which no longer exists
+    fn remove_path(&mut self, path: &Path, mask: WatchMask) {
+        for watch in self.paths.remove(path).into_iter().flatten() {
+            if watch.mask.intersects(mask) {
+                watch.push(mask, 0, None);
+            }
+            watch.push(WatchMask::IGNORED, 0, None);
+        }
+    }
+
+    /// Moves the watches of a file or directory, and of all the files within
+    /// it, to the path it was renamed to
+    fn rename(&mut self, from: &Path, to: &Path) {
+        let moved: Vec<_> = self
+            .paths
+            .keys()
+            .filter(|path| path.starts_with(from))
+            .cloned()
+            .collect();
+        for path in moved {
+            if let Some(watches) = self.paths.remove(&path) {
+                let path = match path.strip_prefix(from) {
+                    Ok(rest) if !rest.as_os_str().is_empty() => to.join(rest),
+                    _ => to.to_path_buf(),
+                };
+                self.paths.entry(path).or_default().extend(watches);
+            }
+        }
+    }
+}
+
+/// Watches files and directories for changes, the changes being reported by
+/// the syscalls which make them. The watches are indexed by path, like the
+/// locks of [`FileLocks`](super::FileLocks).
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FileWatchers {
+    inner: Arc<Mutex<FileWatchersInner>>,
+}
+
+impl FileWatchers {
+    /// Whether anything is watched, which lets the syscalls skip working out
+    /// the paths of their changes
+    pub fn is_watching(&self) -> bool {
+        !self.inner.lock().unwrap().paths.is_empty()
+    }
+
+    /// Adds a watch on `path` to a watch queue and returns its descriptor,
+    /// the mask of the queue's existing watch on the path is replaced
+    pub fn add(&self, queue: &Arc<WatchQueue>, path: PathBuf, mask: WatchMask) -> u32 {
+        let mut inner = self.inner.lock().unwrap();
+        let watches = inner.paths.entry(path).or_default();
+        if let Some(watch) = watches
+            .iter_mut()
+            .find(|watch| Arc::ptr_eq(&watch.queue, queue))
+        {
+            watch.mask = mask;
+            return watch.wd;
+        }
+        let wd = queue.next_wd();
+        watches.push(Watch {
+            queue: queue.clone(),
+            wd,
+            mask,
+        });
+        wd
+    }
+
+    /// Removes a watch of a watch queue
+    pub fn remove(&self, queue: &Arc<WatchQueue>, wd: u32) -> Result<(), Errno> {
+        let mut inner = self.inner.lock().unwrap();
+        let mut removed = None;
+        inner.paths.retain(|_, watches| {
+            if let Some(index) = watches
+                .iter()
+                .position(|watch| watch.wd == wd && Arc::ptr_eq(&watch.queue, queue))
+            {
+                removed = Some(watches.remove(index));
+            }
+            !watches.is_empty()
+        });
+        let watch = removed.ok_or(Errno::Inval)?;
+        watch.push(WatchMask::IGNORED, 0, None);
+        Ok(())
+    }
+
+    /// Removes all the watches of a watch queue whose file was closed
+    fn close(&self, queue: &Arc<WatchQueue>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.paths.retain(|_, watches| {
+            watches.retain(|watch| !Arc::ptr_eq(&watch.queue, queue));
+            !watches.is_empty()
+        });
+    }
+
+    /// Reports a change to a file or directory, `mask` holds a single event
+    /// and `ISDIR` when the change is about a directory
+    pub fn notify(&self, path: &Path, mask: WatchMask) {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.paths.is_empty() {
+            inner.notify(path, mask, 0);
+        }
+    }
+
+    /// Reports the renaming of a file or directory, the watches of the
+    /// renamed file and of the file it replaced follow the rename
+    pub fn notify_rename(&self, from: &Path, to: &Path, is_dir: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.paths.is_empty() || from == to {
+            return;
+        }
+        inner.cookie_seed = inner.cookie_seed.wrapping_add(1).max(1);
+        let cookie = inner.cookie_seed;
+        let isdir = if is_dir {
+            WatchMask::ISDIR
+        } else {
+            WatchMask::empty()
+        };
+        inner.notify(from, WatchMask::MOVED_FROM | isdir, cookie);
+        inner.notify(to, WatchMask::MOVED_TO | isdir, cookie);
+        inner.remove_path(to, WatchMask::DELETE_SELF);
+        inner.rename(from, to);
+    }
+}
+
+/// The file of the `fd_watch_create` file descriptors, reading it returns
+/// the events of its watches in the layout of inotify(7)
+#[derive(Debug)]
+pub(crate) struct WatchFile {
+    watchers: FileWatchers,
+    queue: Arc<WatchQueue>,
+}
+
+impl WatchFile {
+    pub fn new(watchers: FileWatchers) -> Self {
+        Self {
+            watchers,
+            queue: Default::default(),
+        }
+    }
+
+    pub fn add(&self, path: PathBuf, mask: WatchMask) -> u32 {
+        self.watchers.add(&self.queue, path, mask)
+    }
+
+    pub fn remove(&self, wd: u32) -> Result<(), Errno> {
+        self.watchers.remove(&self.queue, wd)
+    }
+}
+
+impl Drop for WatchFile {
+    fn drop(&mut self) {
+        self.watchers.close(&self.queue);
+    }
+}
+
+impl AsyncSeek for WatchFile {
+    fn start_seek(self: Pin<&mut Self>, _position: io::SeekFrom) -> io::Result<()> {
+        Ok(())
+    }
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Poll::Ready(Ok(0))
+    }
+}
+
+impl AsyncWrite for WatchFile {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        _buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(Err(io::ErrorKind::InvalidInput.into()))
+    }
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for WatchFile {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut state = self.queue.state.lock().unwrap();
+        if state.events.is_empty() {
+            state.add_waker(cx.waker());
+            return Poll::Pending;
+        }
+        // Only whole events are read
+        let mut read = false;
+        while let Some(event) = state.events.front() {
+            if event.len() > buf.remaining() {
+                break;
+            }
+            event.encode(buf);
+            state.events.pop_front();
+            read = true;
+        }
+        if !read {
+            return Poll::Ready(Err(io::ErrorKind::InvalidInput.into()));
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl VirtualFile for WatchFile {
+    fn last_accessed(&self) -> u64 {
+        0
+    }
+    fn last_modified(&self) -> u64 {
+        0
+    }
+    fn created_time(&self) -> u64 {
+        0
+    }
+    fn size(&self) -> u64 {
+        0
+    }
+    fn set_len(&mut self, _new_size: u64) -> virtual_fs::Result<()> {
+        Err(virtual_fs::FsError::PermissionDenied)
+    }
+    fn unlink(&mut self) -> virtual_fs::Result<()> {
+        Ok(())
+    }
+    fn poll_read_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        let mut state = self.queue.state.lock().unwrap();
+        if state.events.is_empty() {
+            state.add_waker(cx.waker());
+            return Poll::Pending;
+        }
+        Poll::Ready(Ok(state.events.iter().map(WatchEvent::len).sum()))
+    }
+    fn poll_write_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        // Watch files are never writable
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::task::noop_waker_ref;
+
+    use super::*;
+
+    fn read_all(file: &mut WatchFile) -> Vec<u8> {
+        let mut data = vec![0u8; 4096];
+        let mut buf = ReadBuf::new(&mut data);
+        let mut cx = Context::from_waker(noop_waker_ref());
+        match Pin::new(file).poll_read(&mut cx, &mut buf) {
+            Poll::Ready(Ok(())) => buf.filled().to_vec(),
+            Poll::Ready(Err(err)) => panic!("read failed: {err}"),
+            Poll::Pending => Vec::new(),
+        }
+    }
+
+    fn event(wd: u32, mask: WatchMask, cookie: u32, name: Option<&str>) -> Vec<u8> {
+        let event = WatchEvent {
+            wd,
+            mask,
+            cookie,
+            name: name.map(String::from),
+        };
+        let mut data = vec![0u8; event.len()];
+        event.encode(&mut ReadBuf::new(&mut data));
+        data
+    }
+
+    #[test]
+    fn changes_are_reported_to_the_watches() {
+        let watchers = FileWatchers::default();
+        let mut file = WatchFile::new(watchers.clone());
+        let dir = file.add("/tmp".into(), WatchMask::CREATE | WatchMask::DELETE);
+        let data = file.add(
+            "/tmp/data".into(),
+            WatchMask::MODIFY | WatchMask::DELETE_SELF,
+        );
+        assert!(watchers.is_watching());
+
+        watchers.notify(Path::new("/tmp/data"), WatchMask::CREATE);
+        watchers.notify(Path::new("/tmp/data"), WatchMask::MODIFY);
+        watchers.notify(Path::new("/tmp/data"), WatchMask::MODIFY);
+        watchers.notify(Path::new("/home/data"), WatchMask::CREATE);
+        let mut expected = event(dir, WatchMask::CREATE, 0, Some("data"));
+        expected.extend(event(data, WatchMask::MODIFY, 0, None));
+        assert_eq!(read_all(&mut file), expected);
+        assert_eq!(expected.len(), 48);
+
+        // Deleting a watched file removes its watch
+        watchers.notify(Path::new("/tmp/data"), WatchMask::DELETE);
+        let mut expected = event(dir, WatchMask::DELETE, 0, Some("data"));
+        expected.extend(event(data, WatchMask::DELETE_SELF, 0, None));
+        expected.extend(event(data, WatchMask::IGNORED, 0, None));
+        assert_eq!(read_all(&mut file), expected);
+        assert_eq!(file.remove(data), Err(Errno::Inval));
+
+        file.remove(dir).unwrap();
+        assert!(!watchers.is_watching());
+        assert_eq!(read_all(&mut file), event(dir, WatchMask::IGNORED, 0, None));
+    }
+
+    #[test]
+    fn watches_follow_renames() {
+        let watchers = FileWatchers::default();
+        let mut file = WatchFile::new(watchers.clone());
+        let dir = file.add("/tmp".into(), WatchMask::MOVED_FROM | WatchMask::MOVED_TO);
+        let inner = file.add("/tmp/a/b".into(), WatchMask::ATTRIB);
+
+        watchers.notify_rename(Path::new("/tmp/a"), Path::new("/tmp/c"), true);
+        let mut expected = event(dir, WatchMask::MOVED_FROM | WatchMask::ISDIR, 1, Some("a"));
+        expected.extend(event(
+            dir,
+            WatchMask::MOVED_TO | WatchMask::ISDIR,
+            1,
+            Some("c"),
+        ));
+        assert_eq!(read_all(&mut file), expected);
+
+        watchers.notify(Path::new("/tmp/c/b"), WatchMask::ATTRIB);
+        assert_eq!(
+            read_all(&mut file),
+            event(inner, WatchMask::ATTRIB, 0, None)
+        );
+
+        // Closing the file removes its watches
+        drop(file);
+        assert!(!watchers.is_watching());
+    }
+
+    #[test]
+    fn full_queues_overflow() {
+        let watchers = FileWatchers::default();
+        let file = WatchFile::new(watchers.clone());
+        file.add("/tmp".into(), WatchMask::CREATE);
+        for i in 0..MAX_QUEUED_EVENTS + 2 {
+            watchers.notify(&Path::new("/tmp").join(i.to_string()), WatchMask::CREATE);
+        }
+        let state = file.queue.state.lock().unwrap();
+        assert_eq!(state.events.len(), MAX_QUEUED_EVENTS + 1);
+        assert_eq!(state.events.back().unwrap().mask, WatchMask::Q_OVERFLOW);
+    }
+}
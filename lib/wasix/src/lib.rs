@@ -459,6 +459,34 @@ fn wasix_exports_32(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>)
         "fd_tell" => Function::new_typed_with_env(&mut store, env, fd_tell::<Memory32>),
         "fd_write" => Function::new_typed_with_env(&mut store, env, fd_write::<Memory32>),
         "fd_pipe" => Function::new_typed_with_env(&mut store, env, fd_pipe::<Memory32>),
+        "fd_lock" => Function::new_typed_with_env(&mut store, env, fd_lock),
+        "fd_lock_get" => Function::new_typed_with_env(&mut store, env, fd_lock_get::<Memory32>),
+        "fd_flock" => Function::new_typed_with_env(&mut store, env, fd_flock),
+        "fd_watch_create" => Function::new_typed_with_env(&mut store, env, fd_watch_create::<Memory32>),
+        "fd_watch_add" => Function::new_typed_with_env(&mut store, env, fd_watch_add::<Memory32>),
+        "fd_watch_remove" => Function::new_typed_with_env(&mut store, env, fd_watch_remove),
+        "fd_timer_create" => Function::new_typed_with_env(&mut store, env, fd_timer_create::<Memory32>),
+        "fd_timer_settime" => Function::new_typed_with_env(&mut store, env, fd_timer_settime),
+        "fd_timer_gettime" => Function::new_typed_with_env(&mut store, env, fd_timer_gettime::<Memory32>),
+        "fd_mmap" => Function::new_typed_with_env(&mut store, env, fd_mmap::<Memory32>),
+        "fd_munmap" => Function::new_typed_with_env(&mut store, env, fd_munmap::<Memory32>),
+        "fd_msync" => Function::new_typed_with_env(&mut store, env, fd_msync::<Memory32>),
+        "shm_open" => Function::new_typed_with_env(&mut store, env, shm_open::<Memory32>),
+        "shm_unlink" => Function::new_typed_with_env(&mut store, env, shm_unlink::<Memory32>),
+        "ring_enter" => Function::new_typed_with_env(&mut store, env, ring_enter::<Memory32>),
+        "fd_copy_range" => Function::new_typed_with_env(&mut store, env, fd_copy_range::<Memory32>),
+        "fd_sendfile" => Function::new_typed_with_env(&mut store, env, fd_sendfile::<Memory32>),
+        "fd_fallocate" => Function::new_typed_with_env(&mut store, env, fd_fallocate),
+        "fd_statfs" => Function::new_typed_with_env(&mut store, env, fd_statfs::<Memory32>),
+        "path_statfs" => Function::new_typed_with_env(&mut store, env, path_statfs::<Memory32>),
+        "fd_permissions_get" => Function::new_typed_with_env(&mut store, env, fd_permissions_get::<Memory32>),
+        "path_permissions_get" => Function::new_typed_with_env(&mut store, env, path_permissions_get::<Memory32>),
+        "fd_chmod" => Function::new_typed_with_env(&mut store, env, fd_chmod),
+        "path_chmod" => Function::new_typed_with_env(&mut store, env, path_chmod::<Memory32>),
+        "fd_chown" => Function::new_typed_with_env(&mut store, env, fd_chown),
+        "path_chown" => Function::new_typed_with_env(&mut store, env, path_chown::<Memory32>),
+        "path_mount" => Function::new_typed_with_env(&mut store, env, path_mount::<Memory32>),
+        "path_unmount" => Function::new_typed_with_env(&mut store, env, path_unmount::<Memory32>),
         "path_create_directory" => Function::new_typed_with_env(&mut store, env, path_create_directory::<Memory32>),
         "path_filestat_get" => Function::new_typed_with_env(&mut store, env, path_filestat_get::<Memory32>),
         "path_filestat_set_times" => Function::new_typed_with_env(&mut store, env, path_filestat_set_times::<Memory32>),
@@ -477,6 +505,11 @@ fn wasix_exports_32(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>)
         "proc_raise_interval" => Function::new_typed_with_env(&mut store, env, proc_raise_interval),
         "proc_id" => Function::new_typed_with_env(&mut store, env, proc_id::<Memory32>),
         "proc_parent" => Function::new_typed_with_env(&mut store, env, proc_parent::<Memory32>),
+        "proc_getpgid" => Function::new_typed_with_env(&mut store, env, proc_getpgid::<Memory32>),
+        "proc_getrusage" => Function::new_typed_with_env(&mut store, env, proc_getrusage::<Memory32>),
+        "proc_setpgid" => Function::new_typed_with_env(&mut store, env, proc_setpgid),
+        "proc_setsid" => Function::new_typed_with_env(&mut store, env, proc_setsid::<Memory32>),
+        "proc_umask" => Function::new_typed_with_env(&mut store, env, proc_umask::<Memory32>),
         "random_get" => Function::new_typed_with_env(&mut store, env, random_get::<Memory32>),
         "tty_get" => Function::new_typed_with_env(&mut store, env, tty_get::<Memory32>),
         "tty_set" => Function::new_typed_with_env(&mut store, env, tty_set::<Memory32>),
@@ -489,17 +522,27 @@ fn wasix_exports_32(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>)
         "thread_id" => Function::new_typed_with_env(&mut store, env, thread_id::<Memory32>),
         "thread_signal" => Function::new_typed_with_env(&mut store, env, thread_signal),
         "thread_join" => Function::new_typed_with_env(&mut store, env, thread_join::<Memory32>),
+        "thread_detach" => Function::new_typed_with_env(&mut store, env, thread_detach),
         "thread_parallelism" => Function::new_typed_with_env(&mut store, env, thread_parallelism::<Memory32>),
+        "thread_set_name" => Function::new_typed_with_env(&mut store, env, thread_set_name::<Memory32>),
+        "thread_get_name" => Function::new_typed_with_env(&mut store, env, thread_get_name::<Memory32>),
         "thread_exit" => Function::new_typed_with_env(&mut store, env, thread_exit),
+        "thread_exit_hook" => Function::new_typed_with_env(&mut store, env, thread_exit_hook::<Memory32>),
         "sched_yield" => Function::new_typed_with_env(&mut store, env, sched_yield::<Memory32>),
         "stack_checkpoint" => Function::new_typed_with_env(&mut store, env, stack_checkpoint::<Memory32>),
         "stack_restore" => Function::new_typed_with_env(&mut store, env, stack_restore::<Memory32>),
         "futex_wait" => Function::new_typed_with_env(&mut store, env, futex_wait::<Memory32>),
         "futex_wake" => Function::new_typed_with_env(&mut store, env, futex_wake::<Memory32>),
         "futex_wake_all" => Function::new_typed_with_env(&mut store, env, futex_wake_all::<Memory32>),
+        "futex_wait_bitset" => Function::new_typed_with_env(&mut store, env, futex_wait_bitset::<Memory32>),
+        "futex_wake_bitset" => Function::new_typed_with_env(&mut store, env, futex_wake_bitset::<Memory32>),
+        "futex_requeue" => Function::new_typed_with_env(&mut store, env, futex_requeue::<Memory32>),
+        "futex_cmp_requeue" => Function::new_typed_with_env(&mut store, env, futex_cmp_requeue::<Memory32>),
         "port_bridge" => Function::new_typed_with_env(&mut store, env, port_bridge::<Memory32>),
         "port_unbridge" => Function::new_typed_with_env(&mut store, env, port_unbridge),
         "port_dhcp_acquire" => Function::new_typed_with_env(&mut store, env, port_dhcp_acquire),
+        "port_dhcp_lease" => Function::new_typed_with_env(&mut store, env, port_dhcp_lease::<Memory32>),
+        "port_dhcp_wait" => Function::new_typed_with_env(&mut store, env, port_dhcp_wait::<Memory32>),
         "port_addr_add" => Function::new_typed_with_env(&mut store, env, port_addr_add::<Memory32>),
         "port_addr_remove" => Function::new_typed_with_env(&mut store, env, port_addr_remove::<Memory32>),
         "port_addr_clear" => Function::new_typed_with_env(&mut store, env, port_addr_clear),
@@ -514,6 +557,7 @@ fn wasix_exports_32(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>)
         "sock_addr_local" => Function::new_typed_with_env(&mut store, env, sock_addr_local::<Memory32>),
         "sock_addr_peer" => Function::new_typed_with_env(&mut store, env, sock_addr_peer::<Memory32>),
         "sock_open" => Function::new_typed_with_env(&mut store, env, sock_open::<Memory32>),
+        "sock_pair" => Function::new_typed_with_env(&mut store, env, sock_pair::<Memory32>),
         "sock_set_opt_flag" => Function::new_typed_with_env(&mut store, env, sock_set_opt_flag),
         "sock_get_opt_flag" => Function::new_typed_with_env(&mut store, env, sock_get_opt_flag::<Memory32>),
         "sock_set_opt_time" => Function::new_typed_with_env(&mut store, env, sock_set_opt_time::<Memory32>),
@@ -525,17 +569,25 @@ fn wasix_exports_32(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>)
         "sock_join_multicast_v6" => Function::new_typed_with_env(&mut store, env, sock_join_multicast_v6::<Memory32>),
         "sock_leave_multicast_v6" => Function::new_typed_with_env(&mut store, env, sock_leave_multicast_v6::<Memory32>),
         "sock_bind" => Function::new_typed_with_env(&mut store, env, sock_bind::<Memory32>),
+        "sock_bind_unix" => Function::new_typed_with_env(&mut store, env, sock_bind_unix::<Memory32>),
         "sock_listen" => Function::new_typed_with_env(&mut store, env, sock_listen::<Memory32>),
         "sock_accept" => Function::new_typed_with_env(&mut store, env, sock_accept_v2::<Memory32>),
         "sock_accept_v2" => Function::new_typed_with_env(&mut store, env, sock_accept_v2::<Memory32>),
         "sock_connect" => Function::new_typed_with_env(&mut store, env, sock_connect::<Memory32>),
+        "sock_connect_unix" => Function::new_typed_with_env(&mut store, env, sock_connect_unix::<Memory32>),
         "sock_recv" => Function::new_typed_with_env(&mut store, env, sock_recv::<Memory32>),
         "sock_recv_from" => Function::new_typed_with_env(&mut store, env, sock_recv_from::<Memory32>),
+        "sock_recv_fds" => Function::new_typed_with_env(&mut store, env, sock_recv_fds::<Memory32>),
         "sock_send" => Function::new_typed_with_env(&mut store, env, sock_send::<Memory32>),
         "sock_send_to" => Function::new_typed_with_env(&mut store, env, sock_send_to::<Memory32>),
+        "sock_send_fds" => Function::new_typed_with_env(&mut store, env, sock_send_fds::<Memory32>),
         "sock_send_file" => Function::new_typed_with_env(&mut store, env, sock_send_file::<Memory32>),
         "sock_shutdown" => Function::new_typed_with_env(&mut store, env, sock_shutdown),
+        "sock_tls" => Function::new_typed_with_env(&mut store, env, sock_tls::<Memory32>),
         "resolve" => Function::new_typed_with_env(&mut store, env, resolve::<Memory32>),
+        "mdns_register" => Function::new_typed_with_env(&mut store, env, mdns_register::<Memory32>),
+        "mdns_unregister" => Function::new_typed_with_env(&mut store, env, mdns_unregister),
+        "mdns_browse" => Function::new_typed_with_env(&mut store, env, mdns_browse::<Memory32>),
     };
     namespace
 }
@@ -577,6 +629,34 @@ fn wasix_exports_64(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>)
         "fd_tell" => Function::new_typed_with_env(&mut store, env, fd_tell::<Memory64>),
         "fd_write" => Function::new_typed_with_env(&mut store, env, fd_write::<Memory64>),
         "fd_pipe" => Function::new_typed_with_env(&mut store, env, fd_pipe::<Memory64>),
+        "fd_lock" => Function::new_typed_with_env(&mut store, env, fd_lock),
+        "fd_lock_get" => Function::new_typed_with_env(&mut store, env, fd_lock_get::<Memory64>),
+        "fd_flock" => Function::new_typed_with_env(&mut store, env, fd_flock),
+        "fd_watch_create" => Function::new_typed_with_env(&mut store, env, fd_watch_create::<Memory64>),
+        "fd_watch_add" => Function::new_typed_with_env(&mut store, env, fd_watch_add::<Memory64>),
+        "fd_watch_remove" => Function::new_typed_with_env(&mut store, env, fd_watch_remove),
+        "fd_timer_create" => Function::new_typed_with_env(&mut store, env, fd_timer_create::<Memory64>),
+        "fd_timer_settime" => Function::new_typed_with_env(&mut store, env, fd_timer_settime),
+        "fd_timer_gettime" => Function::new_typed_with_env(&mut store, env, fd_timer_gettime::<Memory64>),
+        "fd_mmap" => Function::new_typed_with_env(&mut store, env, fd_mmap::<Memory64>),
+        "fd_munmap" => Function::new_typed_with_env(&mut store, env, fd_munmap::<Memory64>),
+        "fd_msync" => Function::new_typed_with_env(&mut store, env, fd_msync::<Memory64>),
+        "shm_open" => Function::new_typed_with_env(&mut store, env, shm_open::<Memory64>),
+        "shm_unlink" => Function::new_typed_with_env(&mut store, env, shm_unlink::<Memory64>),
+        "ring_enter" => Function::new_typed_with_env(&mut store, env, ring_enter::<Memory64>),
+        "fd_copy_range" => Function::new_typed_with_env(&mut store, env, fd_copy_range::<Memory64>),
+        "fd_sendfile" => Function::new_typed_with_env(&mut store, env, fd_sendfile::<Memory64>),
+        "fd_fallocate" => Function::new_typed_with_env(&mut store, env, fd_fallocate),
+        "fd_statfs" => Function::new_typed_with_env(&mut store, env, fd_statfs::<Memory64>),
+        "path_statfs" => Function::new_typed_with_env(&mut store, env, path_statfs::<Memory64>),
+        "fd_permissions_get" => Function::new_typed_with_env(&mut store, env, fd_permissions_get::<Memory64>),
+        "path_permissions_get" => Function::new_typed_with_env(&mut store, env, path_permissions_get::<Memory64>),
+        "fd_chmod" => Function::new_typed_with_env(&mut store, env, fd_chmod),
+        "path_chmod" => Function::new_typed_with_env(&mut store, env, path_chmod::<Memory64>),
+        "fd_chown" => Function::new_typed_with_env(&mut store, env, fd_chown),
+        "path_chown" => Function::new_typed_with_env(&mut store, env, path_chown::<Memory64>),
+        "path_mount" => Function::new_typed_with_env(&mut store, env, path_mount::<Memory64>),
+        "path_unmount" => Function::new_typed_with_env(&mut store, env, path_unmount::<Memory64>),
         "path_create_directory" => Function::new_typed_with_env(&mut store, env, path_create_directory::<Memory64>),
         "path_filestat_get" => Function::new_typed_with_env(&mut store, env, path_filestat_get::<Memory64>),
         "path_filestat_set_times" => Function::new_typed_with_env(&mut store, env, path_filestat_set_times::<Memory64>),
@@ -595,6 +675,11 @@ fn wasix_exports_64(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>)
         "proc_raise_interval" => Function::new_typed_with_env(&mut store, env, proc_raise_interval),
         "proc_id" => Function::new_typed_with_env(&mut store, env, proc_id::<Memory64>),
         "proc_parent" => Function::new_typed_with_env(&mut store, env, proc_parent::<Memory64>),
+        "proc_getpgid" => Function::new_typed_with_env(&mut store, env, proc_getpgid::<Memory64>),
+        "proc_getrusage" => Function::new_typed_with_env(&mut store, env, proc_getrusage::<Memory64>),
+        "proc_setpgid" => Function::new_typed_with_env(&mut store, env, proc_setpgid),
+        "proc_setsid" => Function::new_typed_with_env(&mut store, env, proc_setsid::<Memory64>),
+        "proc_umask" => Function::new_typed_with_env(&mut store, env, proc_umask::<Memory64>),
         "random_get" => Function::new_typed_with_env(&mut store, env, random_get::<Memory64>),
         "tty_get" => Function::new_typed_with_env(&mut store, env, tty_get::<Memory64>),
         "tty_set" => Function::new_typed_with_env(&mut store, env, tty_set::<Memory64>),
@@ -607,17 +692,27 @@ fn wasix_exports_64(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>)
         "thread_id" => Function::new_typed_with_env(&mut store, env, thread_id::<Memory64>),
         "thread_signal" => Function::new_typed_with_env(&mut store, env, thread_signal),
         "thread_join" => Function::new_typed_with_env(&mut store, env, thread_join::<Memory64>),
+        "thread_detach" => Function::new_typed_with_env(&mut store, env, thread_detach),
         "thread_parallelism" => Function::new_typed_with_env(&mut store, env, thread_parallelism::<Memory64>),
+        "thread_set_name" => Function::new_typed_with_env(&mut store, env, thread_set_name::<Memory64>),
+        "thread_get_name" => Function::new_typed_with_env(&mut store, env, thread_get_name::<Memory64>),
         "thread_exit" => Function::new_typed_with_env(&mut store, env, thread_exit),
+        "thread_exit_hook" => Function::new_typed_with_env(&mut store, env, thread_exit_hook::<Memory64>),
         "sched_yield" => Function::new_typed_with_env(&mut store, env, sched_yield::<Memory64>),
         "stack_checkpoint" => Function::new_typed_with_env(&mut store, env, stack_checkpoint::<Memory64>),
         "stack_restore" => Function::new_typed_with_env(&mut store, env, stack_restore::<Memory64>),
         "futex_wait" => Function::new_typed_with_env(&mut store, env, futex_wait::<Memory64>),
         "futex_wake" => Function::new_typed_with_env(&mut store, env, futex_wake::<Memory64>),
         "futex_wake_all" => Function::new_typed_with_env(&mut store, env, futex_wake_all::<Memory64>),
+        "futex_wait_bitset" => Function::new_typed_with_env(&mut store, env, futex_wait_bitset::<Memory64>),
+        "futex_wake_bitset" => Function::new_typed_with_env(&mut store, env, futex_wake_bitset::<Memory64>),
+        "futex_requeue" => Function::new_typed_with_env(&mut store, env, futex_requeue::<Memory64>),
+        "futex_cmp_requeue" => Function::new_typed_with_env(&mut store, env, futex_cmp_requeue::<Memory64>),
         "port_bridge" => Function::new_typed_with_env(&mut store, env, port_bridge::<Memory64>),
         "port_unbridge" => Function::new_typed_with_env(&mut store, env, port_unbridge),
         "port_dhcp_acquire" => Function::new_typed_with_env(&mut store, env, port_dhcp_acquire),
+        "port_dhcp_lease" => Function::new_typed_with_env(&mut store, env, port_dhcp_lease::<Memory64>),
+        "port_dhcp_wait" => Function::new_typed_with_env(&mut store, env, port_dhcp_wait::<Memory64>),
         "port_addr_add" => Function::new_typed_with_env(&mut store, env, port_addr_add::<Memory64>),
         "port_addr_remove" => Function::new_typed_with_env(&mut store, env, port_addr_remove::<Memory64>),
         "port_addr_clear" => Function::new_typed_with_env(&mut store, env, port_addr_clear),
@@ -632,6 +727,7 @@ fn wasix_exports_64(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>)
         "sock_addr_local" => Function::new_typed_with_env(&mut store, env, sock_addr_local::<Memory64>),
         "sock_addr_peer" => Function::new_typed_with_env(&mut store, env, sock_addr_peer::<Memory64>),
         "sock_open" => Function::new_typed_with_env(&mut store, env, sock_open::<Memory64>),
+        "sock_pair" => Function::new_typed_with_env(&mut store, env, sock_pair::<Memory64>),
         "sock_set_opt_flag" => Function::new_typed_with_env(&mut store, env, sock_set_opt_flag),
         "sock_get_opt_flag" => Function::new_typed_with_env(&mut store, env, sock_get_opt_flag::<Memory64>),
         "sock_set_opt_time" => Function::new_typed_with_env(&mut store, env, sock_set_opt_time::<Memory64>),
@@ -643,17 +739,25 @@ fn wasix_exports_64(mut store: &mut impl AsStoreMut, env: &FunctionEnv<WasiEnv>)
         "sock_join_multicast_v6" => Function::new_typed_with_env(&mut store, env, sock_join_multicast_v6::<Memory64>),
         "sock_leave_multicast_v6" => Function::new_typed_with_env(&mut store, env, sock_leave_multicast_v6::<Memory64>),
         "sock_bind" => Function::new_typed_with_env(&mut store, env, sock_bind::<Memory64>),
+        "sock_bind_unix" => Function::new_typed_with_env(&mut store, env, sock_bind_unix::<Memory64>),
         "sock_listen" => Function::new_typed_with_env(&mut store, env, sock_listen::<Memory64>),
         "sock_accept" => Function::new_typed_with_env(&mut store, env, sock_accept_v2::<Memory64>),
         "sock_accept_v2" => Function::new_typed_with_env(&mut store, env, sock_accept_v2::<Memory64>),
         "sock_connect" => Function::new_typed_with_env(&mut store, env, sock_connect::<Memory64>),
+        "sock_connect_unix" => Function::new_typed_with_env(&mut store, env, sock_connect_unix::<Memory64>),
         "sock_recv" => Function::new_typed_with_env(&mut store, env, sock_recv::<Memory64>),
         "sock_recv_from" => Function::new_typed_with_env(&mut store, env, sock_recv_from::<Memory64>),
+        "sock_recv_fds" => Function::new_typed_with_env(&mut store, env, sock_recv_fds::<Memory64>),
         "sock_send" => Function::new_typed_with_env(&mut store, env, sock_send::<Memory64>),
         "sock_send_to" => Function::new_typed_with_env(&mut store, env, sock_send_to::<Memory64>),
+        "sock_send_fds" => Function::new_typed_with_env(&mut store, env, sock_send_fds::<Memory64>),
         "sock_send_file" => Function::new_typed_with_env(&mut store, env, sock_send_file::<Memory64>),
         "sock_shutdown" => Function::new_typed_with_env(&mut store, env, sock_shutdown),
+        "sock_tls" => Function::new_typed_with_env(&mut store, env, sock_tls::<Memory64>),
         "resolve" => Function::new_typed_with_env(&mut store, env, resolve::<Memory64>),
+        "mdns_register" => Function::new_typed_with_env(&mut store, env, mdns_register::<Memory64>),
+        "mdns_unregister" => Function::new_typed_with_env(&mut store, env, mdns_unregister),
+        "mdns_browse" => Function::new_typed_with_env(&mut store, env, mdns_browse::<Memory64>),
     };
     namespace
 }
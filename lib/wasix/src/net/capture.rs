@@ -0,0 +1,1064 @@
+//! Capture of the traffic of the processes, written as pcapng to a file or
+//! handed to a callback, for instance to open it in Wireshark.
+//!
+//! The virtual networks carry streams and datagrams rather than packets, so
+//! the packets are made up from what the sockets send and receive: each
+//! connection starts with a handshake and ends with a FIN, each read or
+//! write is a segment, and each datagram a packet. The packets are IP
+//! packets without a link layer, with a comment naming the process which
+//! sent or received them. Raw sockets already carry frames of the link and
+//! aren't captured.
+
+use std::{
+    fmt,
+    io::Write,
+    mem::MaybeUninit,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr},
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use virtual_mio::InterestHandler;
+use virtual_net::{
+    DhcpLease, DynVirtualNetworking, IpCidr, IpRoute, NetworkError, Result, SocketStatus,
+    StreamSecurity, VirtualConnectedSocket, VirtualConnectionlessSocket, VirtualIcmpSocket,
+    VirtualIoSource, VirtualNetworking, VirtualRawSocket, VirtualSocket, VirtualTcpListener,
+    VirtualTcpSocket, VirtualUdpSocket,
+};
+use wasmer_wasix_types::wasi::Snapshot0Clockid;
+
+use crate::syscalls::platform_clock_time_get;
+
+/// The link type of the captured packets, raw IPv4 and IPv6.
+const LINKTYPE_RAW: u16 = 101;
+
+/// The largest payload put in a single made up packet, a larger write is
+/// split in several segments.
+const MAX_PAYLOAD: usize = 65_000;
+
+const IPPROTO_ICMP: u8 = 1;
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+const IPPROTO_ICMPV6: u8 = 58;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_RST: u8 = 0x04;
+const TCP_PSH: u8 = 0x08;
+const TCP_ACK: u8 = 0x10;
+
+/// Which packets are captured, all of them by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CaptureFilter {
+    pids: Vec<u32>,
+    ports: Vec<u16>,
+}
+
+impl CaptureFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Captures the packets of process `pid`, on top of the other processes
+    /// given, rather than the packets of all of them.
+    pub fn pid(mut self, pid: u32) -> Self {
+        self.pids.push(pid);
+        self
+    }
+
+    /// Captures the TCP and UDP packets from or to `port`, on top of the
+    /// other ports given, rather than all the packets. The ICMP packets
+    /// have no port, they're left out once a port is given.
+    pub fn port(mut self, port: u16) -> Self {
+        self.ports.push(port);
+        self
+    }
+
+    fn matches(&self, pid: u32, ports: Option<(u16, u16)>) -> bool {
+        let pid_matches = self.pids.is_empty() || self.pids.contains(&pid);
+        let port_matches = self.ports.is_empty()
+            || ports
+                .is_some_and(|(src, dst)| self.ports.contains(&src) || self.ports.contains(&dst));
+        pid_matches && port_matches
+    }
+}
+
+/// Where the capture is written.
+struct Sink {
+    writer: Box<dyn Write + Send>,
+    /// Whether writing failed, nothing is written anymore then
+    failed: bool,
+}
+
+impl Sink {
+    fn write(&mut self, data: &[u8]) {
+        if self.failed {
+            return;
+        }
+        if let Err(err) = self.writer.write_all(data) {
+            tracing::warn!(%err, "failed to write the packet capture, it's stopped");
+            self.failed = true;
+        }
+    }
+}
+
+/// Writes each chunk of the capture to a callback.
+struct CallbackWriter<F>(F);
+
+impl<F: FnMut(&[u8]) + Send> Write for CallbackWriter<F> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        (self.0)(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A capture of the traffic of the processes of a control plane, see
+/// [`ControlPlaneConfig::capture`](crate::os::task::control_plane::ControlPlaneConfig::capture).
+///
+/// The capture is a pcapng stream, whose header is written right away and
+/// each packet as soon as it's sent or received.
+#[derive(Clone)]
+pub struct PacketCapture {
+    sink: Arc<Mutex<Sink>>,
+    filter: Arc<CaptureFilter>,
+}
+
+impl fmt::Debug for PacketCapture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PacketCapture")
+            .field("filter", &self.filter)
+            .finish()
+    }
+}
+
+impl PacketCapture {
+    /// Writes the capture to `writer`, for instance a file.
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        let mut sink = Sink {
+            writer: Box::new(writer),
+            failed: false,
+        };
+        sink.write(&section_header());
+        sink.write(&interface_description());
+        Self {
+            sink: Arc::new(Mutex::new(sink)),
+            filter: Default::default(),
+        }
+    }
+
+    /// Hands the capture to `callback`, a chunk of the pcapng stream at a
+    /// time.
+    pub fn with_callback(callback: impl FnMut(&[u8]) + Send + 'static) -> Self {
+        Self::new(CallbackWriter(callback))
+    }
+
+    /// Only captures the packets `filter` matches.
+    pub fn filter(mut self, filter: CaptureFilter) -> Self {
+        self.filter = Arc::new(filter);
+        self
+    }
+
+    /// Taps `net`, recording the traffic of the sockets process `pid` opens
+    /// through it.
+    pub(crate) fn tap(&self, net: DynVirtualNetworking, pid: u32) -> DynVirtualNetworking {
+        Arc::new(CaptureNetworking {
+            inner: net,
+            tap: Tap {
+                capture: self.clone(),
+                pid,
+            },
+        })
+    }
+
+    fn record(&self, pid: u32, packet: &[u8]) {
+        let block = enhanced_packet(packet, &format!("pid {pid}"));
+        self.sink.lock().unwrap().write(&block);
+    }
+}
+
+/// A pcapng block of type `ty` holding `body`.
+fn block(ty: u32, body: &[u8]) -> Vec<u8> {
+    let len = (12 + body.len().next_multiple_of(4)) as u32;
+    let mut block = Vec::with_capacity(len as usize);
+    block.extend_from_slice(&ty.to_le_bytes());
+    block.extend_from_slice(&len.to_le_bytes());
+    block.extend_from_slice(body);
+    block.resize(len as usize - 4, 0);
+    block.extend_from_slice(&len.to_le_bytes());
+    block
+}
+
+fn section_header() -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&0x1A2B3C4Du32.to_le_bytes());
+    body.extend_from_slice(&1u16.to_le_bytes());
+    body.extend_from_slice(&0u16.to_le_bytes());
+    // The length of the section isn't known
+    body.extend_from_slice(&(-1i64).to_le_bytes());
+    block(0x0A0D0D0A, &body)
+}
+
+fn interface_description() -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+    body.extend_from_slice(&0u16.to_le_bytes());
+    // No limit on the captured length
+    body.extend_from_slice(&0u32.to_le_bytes());
+    block(1, &body)
+}
+
+/// A block of a packet, stamped with the current time in microseconds.
+fn enhanced_packet(packet: &[u8], comment: &str) -> Vec<u8> {
+    let now = platform_clock_time_get(Snapshot0Clockid::Realtime, 1_000).unwrap_or(0);
+    let micros = (now / 1_000) as u64;
+    let mut body = Vec::with_capacity(packet.len() + comment.len() + 40);
+    body.extend_from_slice(&0u32.to_le_bytes());
+    body.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+    body.extend_from_slice(&(micros as u32).to_le_bytes());
+    body.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+    body.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+    body.extend_from_slice(packet);
+    body.resize(body.len().next_multiple_of(4), 0);
+    // The comment, then the end of the options
+    body.extend_from_slice(&1u16.to_le_bytes());
+    body.extend_from_slice(&(comment.len() as u16).to_le_bytes());
+    body.extend_from_slice(comment.as_bytes());
+    body.resize(body.len().next_multiple_of(4), 0);
+    body.extend_from_slice(&[0; 4]);
+    block(6, &body)
+}
+
+/// The internet checksum of `data`, continuing from `sum`.
+fn checksum(mut sum: u32, data: &[u8]) -> u16 {
+    let mut chunks = data.chunks_exact(2);
+    for chunk in chunks.by_ref() {
+        sum += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+    }
+    if let [last] = chunks.remainder() {
+        sum += (*last as u32) << 8;
+    }
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// The addresses of a packet, where an IPv4 address is mapped to IPv6 when
+/// the other one is IPv6.
+fn packet_ips(src: IpAddr, dst: IpAddr) -> (IpAddr, IpAddr) {
+    let to_v6 = |ip: IpAddr| match ip {
+        IpAddr::V4(ip) => IpAddr::V6(ip.to_ipv6_mapped()),
+        ip => ip,
+    };
+    match (src, dst) {
+        (IpAddr::V4(_), IpAddr::V4(_)) | (IpAddr::V6(_), IpAddr::V6(_)) => (src, dst),
+        _ => (to_v6(src), to_v6(dst)),
+    }
+}
+
+/// An IP packet carrying `payload`, whose checksum at `checksum_at`, if
+/// any, is filled in over the pseudo header.
+fn ip_packet(
+    src: IpAddr,
+    dst: IpAddr,
+    proto: u8,
+    mut payload: Vec<u8>,
+    checksum_at: Option<usize>,
+) -> Vec<u8> {
+    let (src, dst) = packet_ips(src, dst);
+    let (header, pseudo) = match (src, dst) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            let mut header = vec![0x45, 0];
+            header.extend_from_slice(&((20 + payload.len()) as u16).to_be_bytes());
+            // Identification, flags and fragment offset, TTL and protocol
+            header.extend_from_slice(&[0, 0, 0x40, 0, 64, proto, 0, 0]);
+            header.extend_from_slice(&src.octets());
+            header.extend_from_slice(&dst.octets());
+            let sum = checksum(0, &header);
+            header[10..12].copy_from_slice(&sum.to_be_bytes());
+
+            let mut pseudo = Vec::with_capacity(12);
+            pseudo.extend_from_slice(&src.octets());
+            pseudo.extend_from_slice(&dst.octets());
+            pseudo.extend_from_slice(&[0, proto]);
+            pseudo.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+            (header, pseudo)
+        }
+        (IpAddr::V6(src), IpAddr::V6(dst)) => {
+            let mut header = vec![0x60, 0, 0, 0];
+            header.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+            header.extend_from_slice(&[proto, 64]);
+            header.extend_from_slice(&src.octets());
+            header.extend_from_slice(&dst.octets());
+
+            let mut pseudo = Vec::with_capacity(40);
+            pseudo.extend_from_slice(&src.octets());
+            pseudo.extend_from_slice(&dst.octets());
+            pseudo.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+            pseudo.extend_from_slice(&[0, 0, 0, proto]);
+            (header, pseudo)
+        }
+        _ => unreachable!("the addresses of a packet are of the same family"),
+    };
+    if let Some(at) = checksum_at {
+        let mut sum = 0;
+        for chunk in pseudo.chunks_exact(2) {
+            sum += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+        }
+        let sum = checksum(sum, &payload);
+        payload[at..at + 2].copy_from_slice(&sum.to_be_bytes());
+    }
+    let mut packet = header;
+    packet.extend_from_slice(&payload);
+    packet
+}
+
+fn tcp_packet(
+    src: SocketAddr,
+    dst: SocketAddr,
+    seq: u32,
+    ack: u32,
+    flags: u8,
+    data: &[u8],
+) -> Vec<u8> {
+    let mut segment = Vec::with_capacity(20 + data.len());
+    segment.extend_from_slice(&src.port().to_be_bytes());
+    segment.extend_from_slice(&dst.port().to_be_bytes());
+    segment.extend_from_slice(&seq.to_be_bytes());
+    segment.extend_from_slice(&ack.to_be_bytes());
+    segment.extend_from_slice(&[5 << 4, flags]);
+    // The window, the checksum and the urgent pointer
+    segment.extend_from_slice(&[0xFF, 0xFF, 0, 0, 0, 0]);
+    segment.extend_from_slice(data);
+    ip_packet(src.ip(), dst.ip(), IPPROTO_TCP, segment, Some(16))
+}
+
+fn udp_packet(src: SocketAddr, dst: SocketAddr, data: &[u8]) -> Vec<u8> {
+    let mut datagram = Vec::with_capacity(8 + data.len());
+    datagram.extend_from_slice(&src.port().to_be_bytes());
+    datagram.extend_from_slice(&dst.port().to_be_bytes());
+    datagram.extend_from_slice(&((8 + data.len()) as u16).to_be_bytes());
+    datagram.extend_from_slice(&[0, 0]);
+    datagram.extend_from_slice(data);
+    ip_packet(src.ip(), dst.ip(), IPPROTO_UDP, datagram, Some(6))
+}
+
+/// An ICMP packet, whose message comes with its checksum.
+fn icmp_packet(src: IpAddr, dst: IpAddr, message: &[u8]) -> Vec<u8> {
+    let (src, dst) = packet_ips(src, dst);
+    let proto = match dst {
+        IpAddr::V4(_) => IPPROTO_ICMP,
+        IpAddr::V6(_) => IPPROTO_ICMPV6,
+    };
+    ip_packet(src, dst, proto, message.to_vec(), None)
+}
+
+/// Records the packets of the sockets of a process.
+#[derive(Debug, Clone)]
+struct Tap {
+    capture: PacketCapture,
+    pid: u32,
+}
+
+impl Tap {
+    /// Records the packet made by `packet`, when the filter matches it.
+    fn record(&self, ports: Option<(u16, u16)>, packet: impl FnOnce() -> Vec<u8>) {
+        if self.capture.filter.matches(self.pid, ports) {
+            self.capture.record(self.pid, &packet());
+        }
+    }
+}
+
+/// The network of a process, whose sockets record their traffic.
+#[derive(Debug)]
+struct CaptureNetworking {
+    inner: DynVirtualNetworking,
+    tap: Tap,
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for CaptureNetworking {
+    async fn bridge(
+        &self,
+        network: &str,
+        access_token: &str,
+        security: StreamSecurity,
+    ) -> Result<()> {
+        self.inner.bridge(network, access_token, security).await
+    }
+
+    async fn unbridge(&self) -> Result<()> {
+        self.inner.unbridge().await
+    }
+
+    async fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        self.inner.dhcp_acquire().await
+    }
+
+    async fn dhcp_lease(&self) -> Result<DhcpLease> {
+        self.inner.dhcp_lease().await
+    }
+
+    async fn dhcp_lease_changed(&self, serial: u64) -> Result<DhcpLease> {
+        self.inner.dhcp_lease_changed(serial).await
+    }
+
+    async fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        self.inner.ip_add(ip, prefix).await
+    }
+
+    async fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        self.inner.ip_remove(ip).await
+    }
+
+    async fn ip_clear(&self) -> Result<()> {
+        self.inner.ip_clear().await
+    }
+
+    async fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        self.inner.ip_list().await
+    }
+
+    async fn mac(&self) -> Result<[u8; 6]> {
+        self.inner.mac().await
+    }
+
+    async fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        self.inner.gateway_set(ip).await
+    }
+
+    async fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        self.inner
+            .route_add(cidr, via_router, preferred_until, expires_at)
+            .await
+    }
+
+    async fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        self.inner.route_remove(cidr).await
+    }
+
+    async fn route_clear(&self) -> Result<()> {
+        self.inner.route_clear().await
+    }
+
+    async fn route_list(&self) -> Result<Vec<IpRoute>> {
+        self.inner.route_list().await
+    }
+
+    async fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        self.inner.bind_raw().await
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        let inner = self
+            .inner
+            .listen_tcp(addr, only_v6, reuse_port, reuse_addr)
+            .await?;
+        Ok(Box::new(CaptureTcpListener {
+            inner,
+            tap: self.tap.clone(),
+        }))
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        let inner = self.inner.bind_udp(addr, reuse_port, reuse_addr).await?;
+        Ok(Box::new(CaptureDatagramSocket {
+            inner,
+            tap: self.tap.clone(),
+            icmp: false,
+        }))
+    }
+
+    async fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        let inner = self.inner.bind_icmp(addr).await?;
+        Ok(Box::new(CaptureDatagramSocket {
+            inner,
+            tap: self.tap.clone(),
+            icmp: true,
+        }))
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        match self.inner.connect_tcp(addr, peer).await {
+            Ok(inner) => Ok(Box::new(CaptureTcpSocket::new(
+                inner,
+                self.tap.clone(),
+                true,
+            ))),
+            Err(NetworkError::ConnectionRefused) => {
+                let ports = Some((addr.port(), peer.port()));
+                self.tap
+                    .record(ports, || tcp_packet(addr, peer, 0, 0, TCP_SYN, &[]));
+                self.tap.record(ports, || {
+                    tcp_packet(peer, addr, 0, 1, TCP_RST | TCP_ACK, &[])
+                });
+                Err(NetworkError::ConnectionRefused)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        self.inner.resolve(host, port, dns_server).await
+    }
+}
+
+#[derive(Debug)]
+struct CaptureTcpListener {
+    inner: Box<dyn VirtualTcpListener + Sync>,
+    tap: Tap,
+}
+
+impl VirtualIoSource for CaptureTcpListener {
+    fn remove_handler(&mut self) {
+        self.inner.remove_handler();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl VirtualTcpListener for CaptureTcpListener {
+    fn try_accept(&mut self) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        let (inner, peer) = self.inner.try_accept()?;
+        let socket = CaptureTcpSocket::new(inner, self.tap.clone(), false);
+        Ok((Box::new(socket), peer))
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.inner.set_handler(handler)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn set_ttl(&mut self, ttl: u8) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        self.inner.ttl()
+    }
+}
+
+/// A TCP connection recording a segment for each read and write, with the
+/// sequence numbers each end reached.
+#[derive(Debug)]
+struct CaptureTcpSocket {
+    inner: Box<dyn VirtualTcpSocket + Sync>,
+    tap: Tap,
+    local: SocketAddr,
+    peer: SocketAddr,
+    seq_out: u32,
+    seq_in: u32,
+    fin_out: bool,
+    fin_in: bool,
+}
+
+impl CaptureTcpSocket {
+    /// Wraps a connection, recording its handshake, which was started by
+    /// this end when it's `outgoing`.
+    fn new(inner: Box<dyn VirtualTcpSocket + Sync>, tap: Tap, outgoing: bool) -> Self {
+        let unspecified = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+        let local = inner.addr_local().unwrap_or(unspecified);
+        let peer = inner.addr_peer().unwrap_or(unspecified);
+        let socket = Self {
+            inner,
+            tap,
+            local,
+            peer,
+            seq_out: 1,
+            seq_in: 1,
+            fin_out: false,
+            fin_in: false,
+        };
+        let (client, server) = match outgoing {
+            true => (local, peer),
+            false => (peer, local),
+        };
+        let ports = socket.ports();
+        socket
+            .tap
+            .record(ports, || tcp_packet(client, server, 0, 0, TCP_SYN, &[]));
+        socket.tap.record(ports, || {
+            tcp_packet(server, client, 0, 1, TCP_SYN | TCP_ACK, &[])
+        });
+        socket
+            .tap
+            .record(ports, || tcp_packet(client, server, 1, 1, TCP_ACK, &[]));
+        socket
+    }
+
+    fn ports(&self) -> Option<(u16, u16)> {
+        Some((self.local.port(), self.peer.port()))
+    }
+
+    fn record_sent(&mut self, data: &[u8]) {
+        for chunk in data.chunks(MAX_PAYLOAD) {
+            let (seq, ack) = (self.seq_out, self.seq_in);
+            self.tap.record(self.ports(), || {
+                tcp_packet(self.local, self.peer, seq, ack, TCP_PSH | TCP_ACK, chunk)
+            });
+            self.seq_out = self.seq_out.wrapping_add(chunk.len() as u32);
+        }
+    }
+
+    fn record_received(&mut self, data: &[u8]) {
+        for chunk in data.chunks(MAX_PAYLOAD) {
+            let (seq, ack) = (self.seq_in, self.seq_out);
+            self.tap.record(self.ports(), || {
+                tcp_packet(self.peer, self.local, seq, ack, TCP_PSH | TCP_ACK, chunk)
+            });
+            self.seq_in = self.seq_in.wrapping_add(chunk.len() as u32);
+        }
+    }
+
+    fn record_fin_out(&mut self) {
+        if !self.fin_out {
+            self.fin_out = true;
+            let (seq, ack) = (self.seq_out, self.seq_in);
+            self.tap.record(self.ports(), || {
+                tcp_packet(self.local, self.peer, seq, ack, TCP_FIN | TCP_ACK, &[])
+            });
+            self.seq_out = self.seq_out.wrapping_add(1);
+        }
+    }
+
+    fn record_fin_in(&mut self) {
+        if !self.fin_in {
+            self.fin_in = true;
+            let (seq, ack) = (self.seq_in, self.seq_out);
+            self.tap.record(self.ports(), || {
+                tcp_packet(self.peer, self.local, seq, ack, TCP_FIN | TCP_ACK, &[])
+            });
+            self.seq_in = self.seq_in.wrapping_add(1);
+        }
+    }
+}
+
+impl Drop for CaptureTcpSocket {
+    fn drop(&mut self) {
+        self.record_fin_out();
+    }
+}
+
+impl VirtualIoSource for CaptureTcpSocket {
+    fn remove_handler(&mut self) {
+        self.inner.remove_handler();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl VirtualSocket for CaptureTcpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.inner.set_handler(handler)
+    }
+}
+
+impl VirtualConnectedSocket for CaptureTcpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.inner.set_linger(linger)
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        self.inner.linger()
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        let amt = self.inner.try_send(data)?;
+        self.record_sent(&data[..amt]);
+        Ok(amt)
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        self.inner.try_flush()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.inner.close()?;
+        self.record_fin_out();
+        Ok(())
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        let amt = self.inner.try_recv(buf)?;
+        match amt {
+            0 if !buf.is_empty() => self.record_fin_in(),
+            _ => {
+                let data = &buf[..amt];
+                // The bytes up to `amt` were written by the read
+                let data = unsafe { &*(data as *const [MaybeUninit<u8>] as *const [u8]) };
+                self.record_received(data);
+            }
+        }
+        Ok(amt)
+    }
+
+    fn try_peek(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        self.inner.try_peek(buf)
+    }
+}
+
+impl VirtualTcpSocket for CaptureTcpSocket {
+    fn set_recv_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_recv_buf_size(size)
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        self.inner.recv_buf_size()
+    }
+
+    fn set_send_buf_size(&mut self, size: usize) -> Result<()> {
+        self.inner.set_send_buf_size(size)
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        self.inner.send_buf_size()
+    }
+
+    fn set_nodelay(&mut self, nodelay: bool) -> Result<()> {
+        self.inner.set_nodelay(nodelay)
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        self.inner.nodelay()
+    }
+
+    fn set_keepalive(&mut self, keepalive: bool) -> Result<()> {
+        self.inner.set_keepalive(keepalive)
+    }
+
+    fn keepalive(&self) -> Result<bool> {
+        self.inner.keepalive()
+    }
+
+    fn set_dontroute(&mut self, dontroute: bool) -> Result<()> {
+        self.inner.set_dontroute(dontroute)
+    }
+
+    fn dontroute(&self) -> Result<bool> {
+        self.inner.dontroute()
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        self.inner.addr_peer()
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        self.inner.shutdown(how)?;
+        if matches!(how, Shutdown::Write | Shutdown::Both) {
+            self.record_fin_out();
+        }
+        Ok(())
+    }
+
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+/// A UDP or ICMP socket recording a packet for each datagram.
+#[derive(Debug)]
+struct CaptureDatagramSocket<S: ?Sized> {
+    inner: Box<S>,
+    tap: Tap,
+    icmp: bool,
+}
+
+impl<S: VirtualConnectionlessSocket + Sync + ?Sized> CaptureDatagramSocket<S> {
+    fn record(&self, src: SocketAddr, dst: SocketAddr, data: &[u8]) {
+        match self.icmp {
+            true => self
+                .tap
+                .record(None, || icmp_packet(src.ip(), dst.ip(), data)),
+            false => self.tap.record(Some((src.port(), dst.port())), || {
+                udp_packet(src, dst, data)
+            }),
+        }
+    }
+
+    fn local_addr(&self) -> SocketAddr {
+        self.inner
+            .addr_local()
+            .unwrap_or_else(|_| SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0))
+    }
+}
+
+impl<S: VirtualConnectionlessSocket + Sync + ?Sized> VirtualIoSource for CaptureDatagramSocket<S> {
+    fn remove_handler(&mut self) {
+        self.inner.remove_handler();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl<S: VirtualConnectionlessSocket + Sync + ?Sized> VirtualSocket for CaptureDatagramSocket<S> {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.inner.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.inner.set_handler(handler)
+    }
+}
+
+impl<S: VirtualConnectionlessSocket + Sync + ?Sized> VirtualConnectionlessSocket
+    for CaptureDatagramSocket<S>
+{
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        let amt = self.inner.try_send_to(data, addr)?;
+        self.record(self.local_addr(), addr, &data[..amt]);
+        Ok(amt)
+    }
+
+    fn try_recv_from(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)> {
+        let (amt, from) = self.inner.try_recv_from(buf)?;
+        let data = &buf[..amt];
+        // The bytes up to `amt` were written by the read
+        let data = unsafe { &*(data as *const [MaybeUninit<u8>] as *const [u8]) };
+        self.record(from, self.local_addr(), data);
+        Ok((amt, from))
+    }
+
+    fn try_peek_from(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)> {
+        self.inner.try_peek_from(buf)
+    }
+}
+
+impl VirtualIcmpSocket for CaptureDatagramSocket<dyn VirtualIcmpSocket + Sync> {}
+
+impl VirtualUdpSocket for CaptureDatagramSocket<dyn VirtualUdpSocket + Sync> {
+    fn set_broadcast(&mut self, broadcast: bool) -> Result<()> {
+        self.inner.set_broadcast(broadcast)
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        self.inner.broadcast()
+    }
+
+    fn set_multicast_loop_v4(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v4(val)
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        self.inner.multicast_loop_v4()
+    }
+
+    fn set_multicast_loop_v6(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v6(val)
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        self.inner.multicast_loop_v6()
+    }
+
+    fn set_multicast_ttl_v4(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_multicast_ttl_v4(ttl)
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        self.inner.multicast_ttl_v4()
+    }
+
+    fn join_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.join_multicast_v4(multiaddr, iface)
+    }
+
+    fn leave_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.leave_multicast_v4(multiaddr, iface)
+    }
+
+    fn join_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.join_multicast_v6(multiaddr, iface)
+    }
+
+    fn leave_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.leave_multicast_v6(multiaddr, iface)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        self.inner.addr_peer()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+
+    use futures::executor::block_on;
+    use virtual_net::VirtualTcpListenerExt;
+
+    use super::*;
+    use crate::net::loopback::LoopbackNetworking;
+
+    /// The packets of the captured blocks, with their comments.
+    fn packets(capture: &[u8]) -> Vec<(Vec<u8>, String)> {
+        let mut packets = Vec::new();
+        let mut rest = capture;
+        while !rest.is_empty() {
+            let ty = u32::from_le_bytes(rest[..4].try_into().unwrap());
+            let len = u32::from_le_bytes(rest[4..8].try_into().unwrap()) as usize;
+            assert_eq!(rest[len - 4..len], rest[4..8]);
+            if ty == 6 {
+                let body = &rest[8..len - 4];
+                let caplen = u32::from_le_bytes(body[12..16].try_into().unwrap()) as usize;
+                let packet = body[20..20 + caplen].to_vec();
+                let options = &body[20 + caplen.next_multiple_of(4)..];
+                let comment_len = u16::from_le_bytes(options[2..4].try_into().unwrap()) as usize;
+                let comment = String::from_utf8(options[4..4 + comment_len].to_vec()).unwrap();
+                packets.push((packet, comment));
+            }
+            rest = &rest[len..];
+        }
+        packets
+    }
+
+    fn exchange(capture: &PacketCapture, port: u16) {
+        let net: DynVirtualNetworking = Arc::new(LoopbackNetworking::new());
+        let server = capture.tap(net.clone(), 1);
+        let client = capture.tap(net, 2);
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+
+        block_on(async {
+            let mut listener = server.listen_tcp(addr, false, false, false).await.unwrap();
+            let unspecified = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+            let mut socket = client.connect_tcp(unspecified, addr).await.unwrap();
+            let (mut accepted, _) = listener.accept().await.unwrap();
+
+            assert_eq!(socket.try_send(b"ping").unwrap(), 4);
+            let mut buf = [MaybeUninit::new(0); 16];
+            let amt = poll_fn(|cx| loop {
+                if accepted.poll_read_ready(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                match accepted.try_recv(&mut buf) {
+                    Err(NetworkError::WouldBlock) => {}
+                    ret => return Poll::Ready(ret.unwrap()),
+                }
+            })
+            .await;
+            assert_eq!(amt, 4);
+        });
+    }
+
+    #[test]
+    fn tcp_traffic_is_recorded_as_pcapng() {
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        let capture = PacketCapture::with_callback({
+            let recorded = recorded.clone();
+            move |data| recorded.lock().unwrap().extend_from_slice(data)
+        });
+        exchange(&capture, 80);
+
+        let recorded = recorded.lock().unwrap();
+        assert_eq!(recorded[..4], 0x0A0D0D0Au32.to_le_bytes());
+        let packets = packets(&recorded);
+        // The handshake seen by each end, the segment sent and received, and
+        // the FIN of each end dropping its socket
+        assert_eq!(packets.len(), 3 + 3 + 1 + 1 + 2);
+        let (packet, comment) = &packets[6];
+        assert_eq!(comment, "pid 2");
+        assert_eq!(packet[9], IPPROTO_TCP);
+        assert_eq!(&packet[12..16], &[127, 0, 0, 1]);
+        assert_eq!(u16::from_be_bytes([packet[22], packet[23]]), 80);
+        assert_eq!(&packet[40..], b"ping");
+        // The checksums of the IP header and of the segment add up
+        assert_eq!(checksum(0, &packet[..20]), 0);
+        let (_, comment) = &packets[7];
+        assert_eq!(comment, "pid 1");
+    }
+
+    #[test]
+    fn the_filter_picks_the_packets() {
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        let capture = PacketCapture::with_callback({
+            let recorded = recorded.clone();
+            move |data| recorded.lock().unwrap().extend_from_slice(data)
+        })
+        .filter(CaptureFilter::new().pid(1).port(80));
+        exchange(&capture, 80);
+        exchange(&capture, 81);
+
+        let packets = packets(&recorded.lock().unwrap());
+        assert_eq!(packets.len(), 3 + 1 + 1);
+        assert!(packets.iter().all(|(_, comment)| comment == "pid 1"));
+    }
+}
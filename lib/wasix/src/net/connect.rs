@@ -0,0 +1,161 @@
+//! Connections started by a non-blocking `connect`, which are established
+//! while the guest goes on running.
+//!
+//! The runtime has no executor to spawn the connection on, so it's driven by
+//! the wakeups of its own future instead. Once done, the outcome waits here
+//! until the socket next picks it up, while the handler and the wakers of
+//! the socket are told that it became writable.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Wake, Waker},
+};
+
+use derivative::Derivative;
+use virtual_mio::{InterestHandler, InterestType};
+use virtual_net::{NetworkError, VirtualTcpSocket};
+
+pub(crate) type ConnectResult = Result<Box<dyn VirtualTcpSocket + Sync>, NetworkError>;
+
+type ConnectFuture = Pin<Box<dyn Future<Output = ConnectResult> + Send + 'static>>;
+
+#[derive(Derivative, Default)]
+#[derivative(Debug)]
+struct ConnectState {
+    #[derivative(Debug = "ignore")]
+    future: Option<ConnectFuture>,
+    result: Option<ConnectResult>,
+    #[derivative(Debug = "ignore")]
+    handler: Option<Box<dyn InterestHandler + Send + Sync>>,
+    wakers: Vec<Waker>,
+}
+
+impl ConnectState {
+    fn poll(&mut self, waker: &Waker) {
+        let Some(future) = self.future.as_mut() else {
+            return;
+        };
+        let Poll::Ready(res) = future.as_mut().poll(&mut Context::from_waker(waker)) else {
+            return;
+        };
+        self.future = None;
+        // Like on Linux, a failed connection is also reported as writable
+        if let Some(handler) = self.handler.as_mut() {
+            handler.push_interest(InterestType::Writable);
+            if res.is_err() {
+                handler.push_interest(InterestType::Error);
+            }
+        }
+        self.result = Some(res);
+        for waker in self.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ConnectDriver {
+    state: Mutex<ConnectState>,
+    /// Whether the future has to be polled again, which is left to whoever
+    /// holds the state when it's woken
+    woken: AtomicBool,
+}
+
+impl ConnectDriver {
+    fn drive(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::SeqCst);
+        while self.woken.load(Ordering::SeqCst) {
+            let Ok(mut state) = self.state.try_lock() else {
+                return;
+            };
+            let waker = Waker::from(self.clone());
+            while self.woken.swap(false, Ordering::SeqCst) {
+                state.poll(&waker);
+            }
+        }
+    }
+
+    fn with_state<R>(self: &Arc<Self>, f: impl FnOnce(&mut ConnectState) -> R) -> R {
+        let ret = f(&mut self.state.lock().unwrap());
+        if self.woken.load(Ordering::SeqCst) {
+            self.drive();
+        }
+        ret
+    }
+}
+
+impl Wake for ConnectDriver {
+    fn wake(self: Arc<Self>) {
+        self.drive();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.drive();
+    }
+}
+
+/// A connection being established in the background.
+#[derive(Debug, Clone)]
+pub(crate) struct PendingConnect {
+    driver: Arc<ConnectDriver>,
+}
+
+impl PendingConnect {
+    /// Starts establishing the connection, the handler is told once it's
+    /// done.
+    pub fn start(
+        connect: impl Future<Output = ConnectResult> + Send + 'static,
+        handler: Option<Box<dyn InterestHandler + Send + Sync>>,
+    ) -> Self {
+        let driver = Arc::new(ConnectDriver {
+            state: Mutex::new(ConnectState {
+                future: Some(Box::pin(connect)),
+                handler,
+                ..Default::default()
+            }),
+            woken: AtomicBool::new(false),
+        });
+        driver.drive();
+        Self { driver }
+    }
+
+    /// The outcome of the connection with the handler to pass on to the
+    /// connected socket, once it's done.
+    pub fn try_finish(
+        &self,
+    ) -> Option<(
+        ConnectResult,
+        Option<Box<dyn InterestHandler + Send + Sync>>,
+    )> {
+        self.driver.with_state(|state| {
+            let res = state.result.take()?;
+            Some((res, state.handler.take()))
+        })
+    }
+
+    pub fn set_handler(&self, mut handler: Box<dyn InterestHandler + Send + Sync>) {
+        self.driver.with_state(|state| {
+            if state.result.is_some() {
+                handler.push_interest(InterestType::Writable);
+            }
+            state.handler = Some(handler);
+        });
+    }
+
+    pub fn remove_handler(&self) {
+        self.driver.with_state(|state| state.handler.take());
+    }
+
+    /// Wakes the task once the connection is done.
+    pub fn register_waker(&self, waker: &Waker) {
+        self.driver.with_state(|state| match state.result {
+            Some(_) => waker.wake_by_ref(),
+            None => state.wakers.push(waker.clone()),
+        });
+    }
+}
@@ -0,0 +1,315 @@
+//! Names resolved by the embedder, before the network the processes use
+//! resolves them, so that a program reaches a service by the name it knows
+//! without a DNS server answering for it.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use virtual_net::{
+    DhcpLease, DynVirtualNetworking, IpCidr, IpRoute, Result, StreamSecurity, VirtualIcmpSocket,
+    VirtualNetworking, VirtualRawSocket, VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+};
+
+/// A service offered on a port of a host, as an SRV record gives it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SrvRecord {
+    /// The hosts with the lowest priority are tried first
+    pub priority: u16,
+    /// How often the host is picked among the ones of the same priority
+    pub weight: u16,
+    pub port: u16,
+    pub target: String,
+}
+
+/// The records of a name.
+#[derive(Debug, Clone, Default)]
+struct NameRecords {
+    ips: Vec<IpAddr>,
+    srv: Vec<SrvRecord>,
+    txt: Vec<String>,
+}
+
+/// The records the embedder gives to names, see
+/// [`ControlPlaneConfig::dns`](crate::os::task::control_plane::ControlPlaneConfig::dns).
+///
+/// A name starting with `*.` is a wildcard, which gives its records to all
+/// the names below it, unless one of them, or a closer wildcard, has its
+/// own. The names are compared without their case and their trailing dot.
+/// The records can be changed while the processes run, what a process
+/// resolved before is kept for as long as its resolver caches it.
+#[derive(Debug, Clone, Default)]
+pub struct DnsRecords {
+    names: Arc<RwLock<HashMap<String, NameRecords>>>,
+}
+
+impl DnsRecords {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gives the IPv4 address `ip` to `name`, as an A record does.
+    pub fn add_a(&self, name: &str, ip: Ipv4Addr) {
+        self.add(name, |records| records.ips.push(ip.into()));
+    }
+
+    /// Gives the IPv6 address `ip` to `name`, as an AAAA record does.
+    pub fn add_aaaa(&self, name: &str, ip: Ipv6Addr) {
+        self.add(name, |records| records.ips.push(ip.into()));
+    }
+
+    /// Offers a service under `name`, such as `_http._tcp.example.com`.
+    pub fn add_srv(&self, name: &str, srv: SrvRecord) {
+        self.add(name, |records| records.srv.push(srv));
+    }
+
+    /// Attaches a text to `name`.
+    pub fn add_txt(&self, name: &str, txt: impl Into<String>) {
+        let txt = txt.into();
+        self.add(name, |records| records.txt.push(txt));
+    }
+
+    /// Removes all the records of `name`, which the network of the
+    /// processes resolves again.
+    pub fn remove(&self, name: &str) {
+        self.names.write().unwrap().remove(&normalize(name));
+    }
+
+    /// The addresses of `name`, or `None` when it has no records.
+    pub fn lookup_ip(&self, name: &str) -> Option<Vec<IpAddr>> {
+        self.find(name, |records| records.ips.clone())
+    }
+
+    /// The services offered under `name`, or `None` when it has no records.
+    pub fn lookup_srv(&self, name: &str) -> Option<Vec<SrvRecord>> {
+        self.find(name, |records| records.srv.clone())
+    }
+
+    /// The texts attached to `name`, or `None` when it has no records.
+    pub fn lookup_txt(&self, name: &str) -> Option<Vec<String>> {
+        self.find(name, |records| records.txt.clone())
+    }
+
+    /// Resolves the names of `net` from the records first.
+    pub(crate) fn serve(&self, net: DynVirtualNetworking) -> DynVirtualNetworking {
+        Arc::new(DnsNetworking {
+            inner: net,
+            records: self.clone(),
+        })
+    }
+
+    fn add(&self, name: &str, f: impl FnOnce(&mut NameRecords)) {
+        f(self
+            .names
+            .write()
+            .unwrap()
+            .entry(normalize(name))
+            .or_default());
+    }
+
+    /// Reads the records of `name`, or of the closest wildcard above it.
+    fn find<T>(&self, name: &str, f: impl FnOnce(&NameRecords) -> T) -> Option<T> {
+        let names = self.names.read().unwrap();
+        let name = normalize(name);
+        if let Some(records) = names.get(&name) {
+            return Some(f(records));
+        }
+        name.match_indices('.')
+            .find_map(|(i, _)| names.get(&format!("*{}", &name[i..])))
+            .map(f)
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// A network whose names are resolved from records first.
+#[derive(Debug)]
+struct DnsNetworking {
+    inner: DynVirtualNetworking,
+    records: DnsRecords,
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for DnsNetworking {
+    async fn bridge(
+        &self,
+        network: &str,
+        access_token: &str,
+        security: StreamSecurity,
+    ) -> Result<()> {
+        self.inner.bridge(network, access_token, security).await
+    }
+
+    async fn unbridge(&self) -> Result<()> {
+        self.inner.unbridge().await
+    }
+
+    async fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        self.inner.dhcp_acquire().await
+    }
+
+    async fn dhcp_lease(&self) -> Result<DhcpLease> {
+        self.inner.dhcp_lease().await
+    }
+
+    async fn dhcp_lease_changed(&self, serial: u64) -> Result<DhcpLease> {
+        self.inner.dhcp_lease_changed(serial).await
+    }
+
+    async fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        self.inner.ip_add(ip, prefix).await
+    }
+
+    async fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        self.inner.ip_remove(ip).await
+    }
+
+    async fn ip_clear(&self) -> Result<()> {
+        self.inner.ip_clear().await
+    }
+
+    async fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        self.inner.ip_list().await
+    }
+
+    async fn mac(&self) -> Result<[u8; 6]> {
+        self.inner.mac().await
+    }
+
+    async fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        self.inner.gateway_set(ip).await
+    }
+
+    async fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        self.inner
+            .route_add(cidr, via_router, preferred_until, expires_at)
+            .await
+    }
+
+    async fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        self.inner.route_remove(cidr).await
+    }
+
+    async fn route_clear(&self) -> Result<()> {
+        self.inner.route_clear().await
+    }
+
+    async fn route_list(&self) -> Result<Vec<IpRoute>> {
+        self.inner.route_list().await
+    }
+
+    async fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        self.inner.bind_raw().await
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        self.inner
+            .listen_tcp(addr, only_v6, reuse_port, reuse_addr)
+            .await
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        self.inner.bind_udp(addr, reuse_port, reuse_addr).await
+    }
+
+    async fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        self.inner.bind_icmp(addr).await
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        self.inner.connect_tcp(addr, peer).await
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        match self.records.lookup_ip(host) {
+            Some(ips) => Ok(ips),
+            None => self.inner.resolve(host, port, dns_server).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::net::loopback::LoopbackNetworking;
+
+    #[test]
+    fn names_are_resolved_from_the_records_first() {
+        let records = DnsRecords::new();
+        let net = records.serve(Arc::new(LoopbackNetworking::new()));
+        let api: IpAddr = "10.0.0.1".parse().unwrap();
+        let v6: IpAddr = "fd00::1".parse().unwrap();
+        let any: IpAddr = "10.0.0.2".parse().unwrap();
+        records.add_a("API.example.com.", "10.0.0.1".parse().unwrap());
+        records.add_aaaa("api.example.com", "fd00::1".parse().unwrap());
+        records.add_a("*.example.com", "10.0.0.2".parse().unwrap());
+        records.add_txt("*.internal.example.com", "nothing here");
+
+        block_on(async {
+            let resolve = |host: &'static str| net.resolve(host, None, None);
+            assert_eq!(resolve("api.example.com").await.unwrap(), vec![api, v6]);
+            assert_eq!(resolve("a.b.example.com").await.unwrap(), vec![any]);
+            // A closer wildcard takes over, even without addresses
+            assert!(resolve("db.internal.example.com").await.unwrap().is_empty());
+            // The other names are left to the network
+            assert!(resolve("example.com").await.is_err());
+            assert_eq!(
+                resolve("localhost").await.unwrap()[0],
+                IpAddr::from(Ipv4Addr::LOCALHOST)
+            );
+
+            records.remove("*.example.com");
+            assert!(resolve("a.b.example.com").await.is_err());
+        });
+
+        let srv = SrvRecord {
+            priority: 10,
+            weight: 5,
+            port: 8080,
+            target: "api.example.com".to_string(),
+        };
+        records.add_srv("_http._tcp.example.com", srv.clone());
+        assert_eq!(
+            records.lookup_srv("_http._tcp.example.com"),
+            Some(vec![srv])
+        );
+        assert_eq!(
+            records.lookup_txt("x.internal.example.com"),
+            Some(vec!["nothing here".to_string()])
+        );
+        assert_eq!(records.lookup_txt("example.org"), None);
+    }
+}
@@ -0,0 +1,730 @@
+//! Firewall and port mapping of the traffic of the processes, so that an
+//! embedder bridging them to a real network constrains what they reach and
+//! what reaches them.
+//!
+//! The rules are checked where the sockets connect, accept, send and
+//! receive, so they hold whatever network the sockets are opened on. A
+//! connection or a datagram going out against them fails with `EPERM`, as
+//! when a firewall of Linux drops it on its way out, while a connection or a
+//! datagram coming in against them is dropped without the process knowing.
+//! Raw sockets carry packets the rules don't look into, so they are refused
+//! once the firewall filters anything.
+
+use std::{
+    collections::HashMap,
+    mem::MaybeUninit,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    ops::RangeInclusive,
+    sync::{Arc, RwLock},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use virtual_mio::InterestHandler;
+use virtual_net::{
+    DhcpLease, DynVirtualNetworking, IpCidr, IpRoute, NetworkError, Result, SocketStatus,
+    StreamSecurity, VirtualConnectionlessSocket, VirtualIcmpSocket, VirtualIoSource,
+    VirtualNetworking, VirtualRawSocket, VirtualSocket, VirtualTcpListener, VirtualTcpSocket,
+    VirtualUdpSocket,
+};
+
+use super::loopback::cidr_contains;
+
+/// The port DNS servers answer on.
+const DNS_PORT: u16 = 53;
+
+/// The protocol of the traffic a rule or a port mapping applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+    Icmp,
+}
+
+/// Which way the traffic a rule applies to goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// The connections the processes make and the datagrams they send
+    Outbound,
+    /// The connections the processes accept and the datagrams they receive
+    Inbound,
+}
+
+/// What's done with the traffic a rule matches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FirewallAction {
+    #[default]
+    Allow,
+    Deny,
+}
+
+/// A rule of a [`Firewall`], which matches all the traffic until it's
+/// narrowed down.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirewallRule {
+    action: FirewallAction,
+    direction: Option<Direction>,
+    protocol: Option<Protocol>,
+    peer: Option<IpCidr>,
+    ports: Option<RangeInclusive<u16>>,
+}
+
+impl FirewallRule {
+    /// Lets the traffic the rule matches through.
+    pub fn allow() -> Self {
+        Self::new(FirewallAction::Allow)
+    }
+
+    /// Stops the traffic the rule matches.
+    pub fn deny() -> Self {
+        Self::new(FirewallAction::Deny)
+    }
+
+    fn new(action: FirewallAction) -> Self {
+        Self {
+            action,
+            direction: None,
+            protocol: None,
+            peer: None,
+            ports: None,
+        }
+    }
+
+    /// Only matches the traffic going `direction`.
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self.direction = Some(direction);
+        self
+    }
+
+    /// Only matches the traffic of `protocol`.
+    pub fn protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    /// Only matches the traffic with the hosts of `peer`, which are the
+    /// other end of it whichever way it goes.
+    pub fn peer(mut self, peer: IpCidr) -> Self {
+        self.peer = Some(peer);
+        self
+    }
+
+    /// Only matches the TCP and UDP traffic to `ports`: the ports of the
+    /// peers the processes reach, and the ones of the processes the peers
+    /// reach.
+    pub fn ports(mut self, ports: RangeInclusive<u16>) -> Self {
+        self.ports = Some(ports);
+        self
+    }
+
+    fn matches(&self, traffic: &Traffic) -> bool {
+        self.direction.map_or(true, |d| d == traffic.direction)
+            && self.protocol.map_or(true, |p| p == traffic.protocol)
+            && self
+                .peer
+                .as_ref()
+                .map_or(true, |peer| cidr_contains(peer, traffic.peer))
+            && self.ports.as_ref().map_or(true, |ports| {
+                traffic.protocol != Protocol::Icmp && ports.contains(&traffic.port)
+            })
+    }
+}
+
+/// A connection or a datagram, as the rules see it.
+#[derive(Debug)]
+struct Traffic {
+    direction: Direction,
+    protocol: Protocol,
+    peer: IpAddr,
+    /// The port it's sent to
+    port: u16,
+}
+
+impl Traffic {
+    fn new(direction: Direction, protocol: Protocol, peer: IpAddr, port: u16) -> Self {
+        Self {
+            direction,
+            protocol,
+            // A peer reached over IPv6 by its IPv4 address is that address
+            peer: match peer {
+                IpAddr::V6(ip) => ip.to_ipv4_mapped().map_or(peer, IpAddr::V4),
+                IpAddr::V4(_) => peer,
+            },
+            port,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Rules {
+    default: FirewallAction,
+    rules: Vec<FirewallRule>,
+    /// The ports of the network the ports of the processes are mapped to
+    ports: HashMap<(Protocol, u16), u16>,
+}
+
+/// The firewall of the processes of a control plane, see
+/// [`ControlPlaneConfig::firewall`](crate::os::task::control_plane::ControlPlaneConfig::firewall).
+///
+/// The first rule matching the traffic decides what's done with it, the
+/// traffic no rule matches is allowed unless the default is changed. The
+/// rules and the port mappings can be changed while the processes run, the
+/// sockets follow the rules from their next connection or datagram, while
+/// the mappings only apply to the sockets bound after they're changed.
+#[derive(Debug, Clone, Default)]
+pub struct Firewall {
+    rules: Arc<RwLock<Rules>>,
+}
+
+impl Firewall {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets what's done with the traffic no rule matches.
+    pub fn set_default(&self, action: FirewallAction) {
+        self.rules.write().unwrap().default = action;
+    }
+
+    /// Adds `rule` after the others.
+    pub fn add_rule(&self, rule: FirewallRule) {
+        self.rules.write().unwrap().rules.push(rule);
+    }
+
+    /// Removes all the rules, leaving the default.
+    pub fn clear_rules(&self) {
+        self.rules.write().unwrap().rules.clear();
+    }
+
+    /// Maps the TCP or UDP port `port` of the processes to the port
+    /// `network_port` of the network, as when the port of a container is
+    /// published: a process listening on `port` is reached on `network_port`,
+    /// while it still sees itself bound to `port`.
+    pub fn map_port(&self, protocol: Protocol, port: u16, network_port: u16) {
+        self.rules
+            .write()
+            .unwrap()
+            .ports
+            .insert((protocol, port), network_port);
+    }
+
+    /// Stops mapping the port `port` of the processes.
+    pub fn unmap_port(&self, protocol: Protocol, port: u16) {
+        self.rules.write().unwrap().ports.remove(&(protocol, port));
+    }
+
+    /// Guards `net` with the firewall.
+    pub(crate) fn guard(&self, net: DynVirtualNetworking) -> DynVirtualNetworking {
+        Arc::new(FirewallNetworking {
+            inner: net,
+            firewall: self.clone(),
+        })
+    }
+
+    fn allows(&self, traffic: Traffic) -> bool {
+        let rules = self.rules.read().unwrap();
+        let action = rules
+            .rules
+            .iter()
+            .find(|rule| rule.matches(&traffic))
+            .map_or(rules.default, |rule| rule.action);
+        action == FirewallAction::Allow
+    }
+
+    /// Fails the traffic going out, unless it's allowed.
+    fn check_outbound(&self, protocol: Protocol, peer: SocketAddr) -> Result<()> {
+        let traffic = Traffic::new(Direction::Outbound, protocol, peer.ip(), peer.port());
+        match self.allows(traffic) {
+            true => Ok(()),
+            false => Err(NetworkError::PermissionDenied),
+        }
+    }
+
+    /// Whether the rules stop anything.
+    fn filters(&self) -> bool {
+        let rules = self.rules.read().unwrap();
+        rules.default == FirewallAction::Deny || !rules.rules.is_empty()
+    }
+
+    /// The address of the network `addr` is bound to, and the port the
+    /// process sees when it's mapped.
+    fn map_addr(&self, protocol: Protocol, addr: SocketAddr) -> (SocketAddr, Option<u16>) {
+        let rules = self.rules.read().unwrap();
+        match rules.ports.get(&(protocol, addr.port())) {
+            Some(port) if addr.port() != 0 => {
+                (SocketAddr::new(addr.ip(), *port), Some(addr.port()))
+            }
+            _ => (addr, None),
+        }
+    }
+}
+
+/// A network whose traffic goes through a firewall.
+#[derive(Debug)]
+struct FirewallNetworking {
+    inner: DynVirtualNetworking,
+    firewall: Firewall,
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for FirewallNetworking {
+    async fn bridge(
+        &self,
+        network: &str,
+        access_token: &str,
+        security: StreamSecurity,
+    ) -> Result<()> {
+        self.inner.bridge(network, access_token, security).await
+    }
+
+    async fn unbridge(&self) -> Result<()> {
+        self.inner.unbridge().await
+    }
+
+    async fn dhcp_acquire(&self) -> Result<Vec<IpAddr>> {
+        self.inner.dhcp_acquire().await
+    }
+
+    async fn dhcp_lease(&self) -> Result<DhcpLease> {
+        self.inner.dhcp_lease().await
+    }
+
+    async fn dhcp_lease_changed(&self, serial: u64) -> Result<DhcpLease> {
+        self.inner.dhcp_lease_changed(serial).await
+    }
+
+    async fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        self.inner.ip_add(ip, prefix).await
+    }
+
+    async fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        self.inner.ip_remove(ip).await
+    }
+
+    async fn ip_clear(&self) -> Result<()> {
+        self.inner.ip_clear().await
+    }
+
+    async fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        self.inner.ip_list().await
+    }
+
+    async fn mac(&self) -> Result<[u8; 6]> {
+        self.inner.mac().await
+    }
+
+    async fn gateway_set(&self, ip: IpAddr) -> Result<()> {
+        self.inner.gateway_set(ip).await
+    }
+
+    async fn route_add(
+        &self,
+        cidr: IpCidr,
+        via_router: IpAddr,
+        preferred_until: Option<Duration>,
+        expires_at: Option<Duration>,
+    ) -> Result<()> {
+        self.inner
+            .route_add(cidr, via_router, preferred_until, expires_at)
+            .await
+    }
+
+    async fn route_remove(&self, cidr: IpAddr) -> Result<()> {
+        self.inner.route_remove(cidr).await
+    }
+
+    async fn route_clear(&self) -> Result<()> {
+        self.inner.route_clear().await
+    }
+
+    async fn route_list(&self) -> Result<Vec<IpRoute>> {
+        self.inner.route_list().await
+    }
+
+    async fn bind_raw(&self) -> Result<Box<dyn VirtualRawSocket + Sync>> {
+        if self.firewall.filters() {
+            return Err(NetworkError::PermissionDenied);
+        }
+        self.inner.bind_raw().await
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        let (network_addr, mapped) = self.firewall.map_addr(Protocol::Tcp, addr);
+        let inner = self
+            .inner
+            .listen_tcp(network_addr, only_v6, reuse_port, reuse_addr)
+            .await?;
+        let port = mapped.unwrap_or_else(|| inner.addr_local().map_or(0, |addr| addr.port()));
+        Ok(Box::new(FirewallTcpListener {
+            inner,
+            firewall: self.firewall.clone(),
+            port,
+            mapped: mapped.is_some(),
+        }))
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        let (network_addr, mapped) = self.firewall.map_addr(Protocol::Udp, addr);
+        let inner = self
+            .inner
+            .bind_udp(network_addr, reuse_port, reuse_addr)
+            .await?;
+        let port = mapped.unwrap_or_else(|| inner.addr_local().map_or(0, |addr| addr.port()));
+        Ok(Box::new(FirewallDatagramSocket {
+            inner,
+            firewall: self.firewall.clone(),
+            protocol: Protocol::Udp,
+            port,
+            mapped: mapped.is_some(),
+        }))
+    }
+
+    async fn bind_icmp(&self, addr: IpAddr) -> Result<Box<dyn VirtualIcmpSocket + Sync>> {
+        let inner = self.inner.bind_icmp(addr).await?;
+        Ok(Box::new(FirewallDatagramSocket {
+            inner,
+            firewall: self.firewall.clone(),
+            protocol: Protocol::Icmp,
+            port: 0,
+            mapped: false,
+        }))
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        self.firewall.check_outbound(Protocol::Tcp, peer)?;
+        self.inner.connect_tcp(addr, peer).await
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        // The server the network picks when it's not given isn't known
+        if let Some(server) = dns_server {
+            let server = SocketAddr::new(server, DNS_PORT);
+            self.firewall.check_outbound(Protocol::Udp, server)?;
+        }
+        self.inner.resolve(host, port, dns_server).await
+    }
+}
+
+/// A TCP listener accepting the connections the firewall allows, and
+/// dropping the others.
+#[derive(Debug)]
+struct FirewallTcpListener {
+    inner: Box<dyn VirtualTcpListener + Sync>,
+    firewall: Firewall,
+    /// The port the process listens on
+    port: u16,
+    /// Whether the port is mapped to another one of the network
+    mapped: bool,
+}
+
+impl VirtualIoSource for FirewallTcpListener {
+    fn remove_handler(&mut self) {
+        self.inner.remove_handler();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl VirtualTcpListener for FirewallTcpListener {
+    fn try_accept(&mut self) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        loop {
+            let (mut socket, peer) = self.inner.try_accept()?;
+            let traffic = Traffic::new(Direction::Inbound, Protocol::Tcp, peer.ip(), self.port);
+            if self.firewall.allows(traffic) {
+                return Ok((socket, peer));
+            }
+            socket.close().ok();
+        }
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.inner.set_handler(handler)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        let addr = self.inner.addr_local()?;
+        match self.mapped {
+            true => Ok(SocketAddr::new(addr.ip(), self.port)),
+            false => Ok(addr),
+        }
+    }
+
+    fn set_ttl(&mut self, ttl: u8) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        self.inner.ttl()
+    }
+}
+
+/// A UDP or ICMP socket sending the datagrams the firewall allows, and
+/// receiving them.
+#[derive(Debug)]
+struct FirewallDatagramSocket<S: ?Sized> {
+    inner: Box<S>,
+    firewall: Firewall,
+    protocol: Protocol,
+    /// The port the process is bound to
+    port: u16,
+    /// Whether the port is mapped to another one of the network
+    mapped: bool,
+}
+
+impl<S: VirtualConnectionlessSocket + Sync + ?Sized> FirewallDatagramSocket<S> {
+    fn allows_from(&self, from: SocketAddr) -> bool {
+        let traffic = Traffic::new(Direction::Inbound, self.protocol, from.ip(), self.port);
+        self.firewall.allows(traffic)
+    }
+}
+
+impl<S: VirtualConnectionlessSocket + Sync + ?Sized> VirtualIoSource for FirewallDatagramSocket<S> {
+    fn remove_handler(&mut self) {
+        self.inner.remove_handler();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.inner.poll_write_ready(cx)
+    }
+}
+
+impl<S: VirtualConnectionlessSocket + Sync + ?Sized> VirtualSocket for FirewallDatagramSocket<S> {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_ttl(ttl)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.inner.ttl()
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        let addr = self.inner.addr_local()?;
+        match self.mapped {
+            true => Ok(SocketAddr::new(addr.ip(), self.port)),
+            false => Ok(addr),
+        }
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.inner.status()
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.inner.set_handler(handler)
+    }
+}
+
+impl<S: VirtualConnectionlessSocket + Sync + ?Sized> VirtualConnectionlessSocket
+    for FirewallDatagramSocket<S>
+{
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        self.firewall.check_outbound(self.protocol, addr)?;
+        self.inner.try_send_to(data, addr)
+    }
+
+    fn try_recv_from(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)> {
+        loop {
+            let (amt, from) = self.inner.try_recv_from(buf)?;
+            if self.allows_from(from) {
+                return Ok((amt, from));
+            }
+        }
+    }
+
+    fn try_peek_from(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)> {
+        loop {
+            let (amt, from) = self.inner.try_peek_from(buf)?;
+            if self.allows_from(from) {
+                return Ok((amt, from));
+            }
+            // The datagram is dropped, to peek at the next one
+            self.inner.try_recv_from(buf)?;
+        }
+    }
+}
+
+impl VirtualIcmpSocket for FirewallDatagramSocket<dyn VirtualIcmpSocket + Sync> {}
+
+impl VirtualUdpSocket for FirewallDatagramSocket<dyn VirtualUdpSocket + Sync> {
+    fn set_broadcast(&mut self, broadcast: bool) -> Result<()> {
+        self.inner.set_broadcast(broadcast)
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        self.inner.broadcast()
+    }
+
+    fn set_multicast_loop_v4(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v4(val)
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        self.inner.multicast_loop_v4()
+    }
+
+    fn set_multicast_loop_v6(&mut self, val: bool) -> Result<()> {
+        self.inner.set_multicast_loop_v6(val)
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        self.inner.multicast_loop_v6()
+    }
+
+    fn set_multicast_ttl_v4(&mut self, ttl: u32) -> Result<()> {
+        self.inner.set_multicast_ttl_v4(ttl)
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        self.inner.multicast_ttl_v4()
+    }
+
+    fn join_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.join_multicast_v4(multiaddr, iface)
+    }
+
+    fn leave_multicast_v4(&mut self, multiaddr: Ipv4Addr, iface: Ipv4Addr) -> Result<()> {
+        self.inner.leave_multicast_v4(multiaddr, iface)
+    }
+
+    fn join_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.join_multicast_v6(multiaddr, iface)
+    }
+
+    fn leave_multicast_v6(&mut self, multiaddr: Ipv6Addr, iface: u32) -> Result<()> {
+        self.inner.leave_multicast_v6(multiaddr, iface)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        self.inner.addr_peer()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::net::loopback::LoopbackNetworking;
+
+    fn addr(addr: &str) -> SocketAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn connections_follow_the_rules_and_the_mapped_ports() {
+        let net: DynVirtualNetworking = Arc::new(LoopbackNetworking::new());
+        let firewall = Firewall::new();
+        let guarded = firewall.guard(net.clone());
+        firewall.add_rule(
+            FirewallRule::deny()
+                .direction(Direction::Outbound)
+                .protocol(Protocol::Tcp)
+                .ports(22..=22),
+        );
+        firewall.map_port(Protocol::Tcp, 80, 8080);
+
+        block_on(async {
+            let mut listener = guarded
+                .listen_tcp(addr("0.0.0.0:80"), false, false, false)
+                .await
+                .unwrap();
+            assert_eq!(listener.addr_local().unwrap().port(), 80);
+            // The port of the network is the mapped one
+            net.connect_tcp(addr("0.0.0.0:0"), addr("127.0.0.1:8080"))
+                .await
+                .unwrap();
+            listener.try_accept().unwrap();
+
+            net.listen_tcp(addr("0.0.0.0:22"), false, false, false)
+                .await
+                .unwrap();
+            assert_eq!(
+                guarded
+                    .connect_tcp(addr("0.0.0.0:0"), addr("127.0.0.1:22"))
+                    .await
+                    .unwrap_err(),
+                NetworkError::PermissionDenied
+            );
+            assert_eq!(
+                guarded.bind_raw().await.unwrap_err(),
+                NetworkError::PermissionDenied
+            );
+
+            // The connections coming in against the rules are dropped
+            firewall.add_rule(FirewallRule::deny().direction(Direction::Inbound));
+            net.connect_tcp(addr("0.0.0.0:0"), addr("127.0.0.1:8080"))
+                .await
+                .unwrap();
+            assert_eq!(listener.try_accept().unwrap_err(), NetworkError::WouldBlock);
+        });
+    }
+
+    #[test]
+    fn datagrams_follow_the_rules() {
+        let net: DynVirtualNetworking = Arc::new(LoopbackNetworking::new());
+        let firewall = Firewall::new();
+        let guarded = firewall.guard(net.clone());
+        firewall.add_rule(FirewallRule::allow().peer(IpCidr {
+            ip: "127.0.0.1".parse().unwrap(),
+            prefix: 32,
+        }));
+        firewall.set_default(FirewallAction::Deny);
+
+        block_on(async {
+            let mut socket = guarded
+                .bind_udp(addr("0.0.0.0:5000"), false, false)
+                .await
+                .unwrap();
+            let mut peer = net
+                .bind_udp(addr("127.0.0.1:5001"), false, false)
+                .await
+                .unwrap();
+            socket.try_send_to(b"ping", addr("127.0.0.1:5001")).unwrap();
+            assert_eq!(
+                socket
+                    .try_send_to(b"ping", addr("10.0.0.1:5001"))
+                    .unwrap_err(),
+                NetworkError::PermissionDenied
+            );
+            let mut buf = [MaybeUninit::new(0); 16];
+            assert_eq!(peer.try_recv_from(&mut buf).unwrap().0, 4);
+
+            firewall.clear_rules();
+            peer.try_send_to(b"pong", addr("127.0.0.1:5000")).unwrap();
+            assert_eq!(
+                socket.try_recv_from(&mut buf).unwrap_err(),
+                NetworkError::WouldBlock
+            );
+        });
+    }
+}
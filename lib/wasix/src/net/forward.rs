@@ -0,0 +1,250 @@
+//! Forwarding of TCP connections between networks, so that an embedder
+//! exposes a server of its programs on a listener of the host, or lets its
+//! programs reach a server of the host through their own network.
+//!
+//! The same functions serve both ways: the connections accepted by a
+//! listener of one network are forwarded to an address reached through the
+//! other one.
+
+use std::{
+    future::poll_fn,
+    mem::MaybeUninit,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr},
+    task::{Context, Poll},
+};
+
+use futures::{stream::FuturesUnordered, FutureExt, StreamExt};
+use virtual_net::{
+    DynVirtualNetworking, NetworkError, Result, VirtualTcpListener, VirtualTcpListenerExt,
+    VirtualTcpSocket,
+};
+
+/// How many bytes are read from a connection before they're written to the
+/// other one.
+const PUMP_BUF_SIZE: usize = 16 * 1024;
+
+/// Forwards the connections accepted by `listener` to `target`, connecting
+/// to it through `net`, until the listener fails.
+///
+/// For instance, a listener of the host on `localhost:8080` forwarded to
+/// port 80 of the network of the programs exposes their web server on the
+/// host, and a listener of their network forwarded to an address of the
+/// host exposes a server of the host to them. A connection which can't be
+/// made to `target` is closed.
+pub async fn forward_tcp(
+    mut listener: Box<dyn VirtualTcpListener + Sync>,
+    net: DynVirtualNetworking,
+    target: SocketAddr,
+) -> Result<()> {
+    let mut connections = FuturesUnordered::new();
+    loop {
+        futures::select! {
+            accepted = listener.accept().fuse() => {
+                let (socket, peer) = accepted?;
+                let net = net.clone();
+                connections.push(async move {
+                    let ret = match connect(&net, target).await {
+                        Ok(forwarded) => pipe(socket, forwarded).await,
+                        Err(err) => Err(err),
+                    };
+                    if let Err(err) = ret {
+                        tracing::debug!(%peer, %target, %err, "forwarded connection failed");
+                    }
+                });
+            }
+            () = connections.select_next_some() => {}
+        }
+    }
+}
+
+/// Connects to `target` through `net`, from any of its addresses.
+pub async fn connect(
+    net: &DynVirtualNetworking,
+    target: SocketAddr,
+) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+    let ip = match target {
+        SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    net.connect_tcp(SocketAddr::new(ip, 0), target).await
+}
+
+/// Copies what each connection receives to the other one, until both
+/// streams ended, where the end of one is passed on by shutting down the
+/// writing of the other.
+pub async fn pipe(
+    mut a: Box<dyn VirtualTcpSocket + Sync>,
+    mut b: Box<dyn VirtualTcpSocket + Sync>,
+) -> Result<()> {
+    let (mut a_to_b, mut b_to_a) = (Pump::default(), Pump::default());
+    poll_fn(|cx| {
+        let a_to_b = a_to_b.poll(a.as_mut(), b.as_mut(), cx)?;
+        let b_to_a = b_to_a.poll(b.as_mut(), a.as_mut(), cx)?;
+        match (a_to_b, b_to_a) {
+            (Poll::Ready(()), Poll::Ready(())) => Poll::Ready(Ok(())),
+            _ => Poll::Pending,
+        }
+    })
+    .await
+}
+
+/// Copies what a connection receives to another one.
+#[derive(Debug)]
+struct Pump {
+    buf: Vec<MaybeUninit<u8>>,
+    /// The bytes of `buf` which were received and not sent yet
+    start: usize,
+    end: usize,
+    done: bool,
+}
+
+impl Default for Pump {
+    fn default() -> Self {
+        Self {
+            buf: vec![MaybeUninit::new(0); PUMP_BUF_SIZE],
+            start: 0,
+            end: 0,
+            done: false,
+        }
+    }
+}
+
+impl Pump {
+    fn poll(
+        &mut self,
+        from: &mut (dyn VirtualTcpSocket + Sync),
+        to: &mut (dyn VirtualTcpSocket + Sync),
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<()>> {
+        while !self.done {
+            if self.start < self.end {
+                match to.poll_write_ready(cx) {
+                    Poll::Ready(Ok(_)) => {}
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                    Poll::Pending => return Poll::Pending,
+                }
+                // The buffer is filled with zeros when it's created, all its
+                // bytes are initialized
+                let data = &self.buf[self.start..self.end];
+                let data = unsafe { &*(data as *const [MaybeUninit<u8>] as *const [u8]) };
+                match to.try_send(data) {
+                    Ok(amt) => self.start += amt,
+                    Err(NetworkError::WouldBlock) => {}
+                    Err(err) => return Poll::Ready(Err(err)),
+                }
+                continue;
+            }
+
+            match from.poll_read_ready(cx) {
+                Poll::Ready(Ok(_)) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+            match from.try_recv(&mut self.buf) {
+                Ok(0) => {
+                    self.done = true;
+                    to.shutdown(Shutdown::Write)?;
+                }
+                Ok(amt) => (self.start, self.end) = (0, amt),
+                Err(NetworkError::WouldBlock) => {}
+                Err(err) => return Poll::Ready(Err(err)),
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use futures::{executor::block_on, future::Either};
+
+    use super::*;
+    use crate::net::loopback::LoopbackNetworking;
+
+    /// Sends all of `data`, then ends the stream.
+    async fn send_all(socket: &mut (dyn VirtualTcpSocket + Sync), data: &[u8]) {
+        let mut sent = 0;
+        poll_fn(|cx| {
+            while sent < data.len() {
+                if socket.poll_write_ready(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                sent += socket.try_send(&data[sent..]).unwrap_or(0);
+            }
+            Poll::Ready(())
+        })
+        .await;
+        socket.shutdown(Shutdown::Write).unwrap();
+    }
+
+    /// Receives until the end of the stream.
+    async fn recv_all(socket: &mut (dyn VirtualTcpSocket + Sync)) -> Vec<u8> {
+        let mut received = Vec::new();
+        poll_fn(|cx| loop {
+            if socket.poll_read_ready(cx).is_pending() {
+                return Poll::Pending;
+            }
+            let mut buf = [MaybeUninit::new(0); 1024];
+            match socket.try_recv(&mut buf) {
+                Ok(0) => return Poll::Ready(()),
+                Ok(amt) => received.extend(buf[..amt].iter().map(|b| unsafe { b.assume_init() })),
+                Err(NetworkError::WouldBlock) => {}
+                Err(err) => panic!("{err}"),
+            }
+        })
+        .await;
+        received
+    }
+
+    #[test]
+    fn connections_are_forwarded_to_the_other_network() {
+        let host: DynVirtualNetworking = Arc::new(LoopbackNetworking::new());
+        let guest: DynVirtualNetworking = Arc::new(LoopbackNetworking::new());
+        let exposed: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let server: SocketAddr = "127.0.0.1:80".parse().unwrap();
+
+        block_on(async {
+            let listener = host.listen_tcp(exposed, false, false, false).await.unwrap();
+            let mut server_listener = guest.listen_tcp(server, false, false, false).await.unwrap();
+            let forward = Box::pin(forward_tcp(listener, guest.clone(), server));
+
+            // The server sends back what it received, reversed
+            let reverse = async {
+                let (mut socket, _) = server_listener.accept().await.unwrap();
+                let mut data = recv_all(socket.as_mut()).await;
+                data.reverse();
+                send_all(socket.as_mut(), &data).await;
+            };
+            let client = async {
+                let data: Vec<u8> = (0..300_000).map(|i| i as u8).collect();
+                let mut socket = connect(&host, exposed).await.unwrap();
+                send_all(socket.as_mut(), &data).await;
+                let received = recv_all(socket.as_mut()).await;
+                assert!(received.iter().eq(data.iter().rev()));
+            };
+            let done = Box::pin(futures::future::join(reverse, client));
+            assert!(matches!(
+                futures::future::select(forward, done).await,
+                Either::Right(_)
+            ));
+
+            // What the forwarded address refuses is closed
+            drop(server_listener);
+            let forward = Box::pin(forward_tcp(
+                host.listen_tcp(exposed, false, false, false).await.unwrap(),
+                guest.clone(),
+                server,
+            ));
+            let client = Box::pin(async {
+                let mut socket = connect(&host, exposed).await.unwrap();
+                assert_eq!(recv_all(socket.as_mut()).await, b"");
+            });
+            assert!(matches!(
+                futures::future::select(forward, client).await,
+                Either::Right(_)
+            ));
+        });
+    }
+}
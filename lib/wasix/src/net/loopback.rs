@@ -0,0 +1,846 @@
+//! A network which lives in memory, shared by the processes of a control
+//! plane, so that they reach each other by IP address and port without
+//! anything leaving the host.
+//!
+//! All the processes are on the same host: the addresses added by any of
+//! them are local to all of them, on top of `127.0.0.1` and `::1`. The
+//! connections are made of the same in-memory streams as the Unix sockets,
+//! and the datagrams are queued for the socket bound to their destination,
+//! or dropped like on a real network when there is none or it's full.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    mem::MaybeUninit,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr},
+    ops::Range,
+    sync::{Arc, Mutex, Weak},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use virtual_mio::{InterestHandler, InterestType};
+use virtual_net::{
+    Interest, IpCidr, NetworkError, Result, SocketStatus, VirtualConnectedSocket,
+    VirtualConnectionlessSocket, VirtualIoSource, VirtualNetworking, VirtualSocket,
+    VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+};
+
+use super::unix::{UnixStream, DEFAULT_BUF_SIZE, MAX_QUEUED_DATAGRAMS};
+
+/// The ports given to the sockets bound to port 0.
+const EPHEMERAL_PORTS: Range<u16> = 49152..65535;
+
+/// How many connections wait to be accepted before the next ones are
+/// refused, the default `somaxconn` of Linux.
+const MAX_BACKLOG: usize = 4096;
+
+/// The TTL the sockets report until it's changed, the default of Linux.
+const DEFAULT_TTL: u32 = 64;
+
+/// The addresses of the network until some are added or removed.
+const DEFAULT_ADDRS: [IpCidr; 2] = [
+    IpCidr {
+        ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        prefix: 8,
+    },
+    IpCidr {
+        ip: IpAddr::V6(Ipv6Addr::LOCALHOST),
+        prefix: 128,
+    },
+];
+
+/// Whether `ip` is within `cidr`.
+pub(super) fn cidr_contains(cidr: &IpCidr, ip: IpAddr) -> bool {
+    match (cidr.ip, ip) {
+        (IpAddr::V4(net), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - cidr.prefix.min(32) as u32);
+            let mask = mask.unwrap_or(0);
+            u32::from(net) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(net), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - cidr.prefix.min(128) as u32);
+            let mask = mask.unwrap_or(0);
+            u128::from(net) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Whether a socket bound to `bound` receives what is sent to `ip`, where
+/// an unspecified IPv6 address also receives IPv4 unless it's `only_v6`.
+fn accepts(bound: SocketAddr, only_v6: bool, ip: IpAddr) -> bool {
+    match (bound.ip(), ip) {
+        (bound, ip) if bound == ip => true,
+        (IpAddr::V4(bound), IpAddr::V4(_)) => bound.is_unspecified(),
+        (IpAddr::V6(bound), IpAddr::V6(_)) => bound.is_unspecified(),
+        (IpAddr::V6(bound), IpAddr::V4(_)) => bound.is_unspecified() && !only_v6,
+        (IpAddr::V4(_), IpAddr::V6(_)) => false,
+    }
+}
+
+/// A socket bound to an address, which frees it once it's dropped.
+#[derive(Debug)]
+struct Binding<T> {
+    socket: Weak<Mutex<T>>,
+    only_v6: bool,
+}
+
+/// The sockets of one protocol bound to the addresses of the network.
+#[derive(Debug)]
+struct Bindings<T> {
+    sockets: HashMap<SocketAddr, Binding<T>>,
+    next_port: u16,
+}
+
+impl<T> Default for Bindings<T> {
+    fn default() -> Self {
+        Self {
+            sockets: HashMap::new(),
+            next_port: EPHEMERAL_PORTS.start,
+        }
+    }
+}
+
+impl<T> Bindings<T> {
+    fn is_bound(&self, port: u16) -> bool {
+        self.sockets.keys().any(|addr| addr.port() == port)
+    }
+
+    /// Picks a port which no socket is bound to.
+    fn ephemeral_port(&mut self) -> Result<u16> {
+        self.sockets
+            .retain(|_, binding| binding.socket.strong_count() > 0);
+        for _ in EPHEMERAL_PORTS {
+            let port = self.next_port;
+            self.next_port = match port + 1 {
+                next if EPHEMERAL_PORTS.contains(&next) => next,
+                _ => EPHEMERAL_PORTS.start,
+            };
+            if !self.is_bound(port) {
+                return Ok(port);
+            }
+        }
+        Err(NetworkError::AddressInUse)
+    }
+
+    /// Binds `socket` to `addr`, or to an ephemeral port of its address when
+    /// its port is 0, and returns the address it's bound to.
+    fn bind(
+        &mut self,
+        mut addr: SocketAddr,
+        only_v6: bool,
+        socket: &Arc<Mutex<T>>,
+    ) -> Result<SocketAddr> {
+        if addr.port() == 0 {
+            addr.set_port(self.ephemeral_port()?);
+        }
+        self.sockets
+            .retain(|_, binding| binding.socket.strong_count() > 0);
+        let in_use = self.sockets.iter().any(|(bound, binding)| {
+            bound.port() == addr.port()
+                && (accepts(*bound, binding.only_v6, addr.ip())
+                    || accepts(addr, only_v6, bound.ip()))
+        });
+        if in_use {
+            return Err(NetworkError::AddressInUse);
+        }
+        let binding = Binding {
+            socket: Arc::downgrade(socket),
+            only_v6,
+        };
+        self.sockets.insert(addr, binding);
+        Ok(addr)
+    }
+
+    /// The socket which receives what is sent to `addr`, the one bound to
+    /// it rather than to an unspecified address.
+    fn find(&self, addr: SocketAddr) -> Option<Arc<Mutex<T>>> {
+        if let Some(binding) = self.sockets.get(&addr) {
+            if let Some(socket) = binding.socket.upgrade() {
+                return Some(socket);
+            }
+        }
+        self.sockets
+            .iter()
+            .filter(|(bound, binding)| {
+                bound.port() == addr.port() && accepts(**bound, binding.only_v6, addr.ip())
+            })
+            .find_map(|(_, binding)| binding.socket.upgrade())
+    }
+}
+
+/// The addresses and the sockets of the network.
+#[derive(Debug)]
+struct Network {
+    addrs: Vec<IpCidr>,
+    listeners: Bindings<Backlog>,
+    udp_sockets: Bindings<DatagramQueue>,
+}
+
+impl Network {
+    /// Whether `ip` is an address of the host, where all the loopback
+    /// network is.
+    fn is_local(&self, ip: IpAddr) -> bool {
+        self.addrs
+            .iter()
+            .any(|cidr| cidr.ip == ip || (cidr.ip.is_loopback() && cidr_contains(cidr, ip)))
+    }
+
+    /// Checks that a socket can be bound to `ip`.
+    fn check_bindable(&self, ip: IpAddr) -> Result<()> {
+        match ip.is_unspecified() || self.is_local(ip) {
+            true => Ok(()),
+            false => Err(NetworkError::AddressNotAvailable),
+        }
+    }
+}
+
+/// A network in memory, shared by the processes of a control plane.
+///
+/// It has the addresses `127.0.0.1/8` and `::1/128` until they are changed
+/// with [`VirtualNetworking::ip_add`] and the like, and only resolves
+/// `localhost` and IP addresses.
+#[derive(Debug, Clone)]
+pub struct LoopbackNetworking {
+    network: Arc<Mutex<Network>>,
+}
+
+impl LoopbackNetworking {
+    pub fn new() -> Self {
+        Self {
+            network: Arc::new(Mutex::new(Network {
+                addrs: DEFAULT_ADDRS.to_vec(),
+                listeners: Default::default(),
+                udp_sockets: Default::default(),
+            })),
+        }
+    }
+}
+
+impl Default for LoopbackNetworking {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for LoopbackNetworking {
+    async fn ip_add(&self, ip: IpAddr, prefix: u8) -> Result<()> {
+        let mut network = self.network.lock().unwrap();
+        let cidr = IpCidr { ip, prefix };
+        if !network.addrs.contains(&cidr) {
+            network.addrs.push(cidr);
+        }
+        Ok(())
+    }
+
+    async fn ip_remove(&self, ip: IpAddr) -> Result<()> {
+        let mut network = self.network.lock().unwrap();
+        network.addrs.retain(|cidr| cidr.ip != ip);
+        Ok(())
+    }
+
+    async fn ip_clear(&self) -> Result<()> {
+        self.network.lock().unwrap().addrs.clear();
+        Ok(())
+    }
+
+    async fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        Ok(self.network.lock().unwrap().addrs.clone())
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        only_v6: bool,
+        _reuse_port: bool,
+        _reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        let mut network = self.network.lock().unwrap();
+        network.check_bindable(addr.ip())?;
+        let backlog = Arc::new(Mutex::new(Backlog {
+            pending: VecDeque::new(),
+            interest: Interest::default(),
+        }));
+        let addr = network.listeners.bind(addr, only_v6, &backlog)?;
+        Ok(Box::new(LoopbackTcpListener {
+            backlog,
+            addr,
+            ttl: DEFAULT_TTL as u8,
+        }))
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        _reuse_port: bool,
+        _reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        let mut network = self.network.lock().unwrap();
+        network.check_bindable(addr.ip())?;
+        let queue = Arc::new(Mutex::new(DatagramQueue::default()));
+        let addr = network.udp_sockets.bind(addr, false, &queue)?;
+        Ok(Box::new(LoopbackUdpSocket {
+            network: self.clone(),
+            queue,
+            addr,
+            ttl: DEFAULT_TTL,
+            broadcast: false,
+        }))
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        let mut network = self.network.lock().unwrap();
+        network.check_bindable(addr.ip())?;
+        // Nothing answers on the addresses which aren't on the host
+        if !network.is_local(peer.ip()) {
+            return Err(NetworkError::ConnectionRefused);
+        }
+        let backlog = network
+            .listeners
+            .find(peer)
+            .ok_or(NetworkError::ConnectionRefused)?;
+
+        // The connections to the host come from the address they're made to
+        let ip = match addr.ip().is_unspecified() {
+            true => peer.ip(),
+            false => addr.ip(),
+        };
+        let port = match addr.port() {
+            0 => network.listeners.ephemeral_port()?,
+            port => port,
+        };
+        let local = SocketAddr::new(ip, port);
+        drop(network);
+
+        let mut backlog = backlog.lock().unwrap();
+        if backlog.pending.len() >= MAX_BACKLOG {
+            return Err(NetworkError::ConnectionRefused);
+        }
+        let (client, server) = UnixStream::pair();
+        backlog
+            .pending
+            .push_back(LoopbackTcpSocket::new(server, peer, local));
+        backlog.interest.notify(InterestType::Readable);
+        Ok(Box::new(LoopbackTcpSocket::new(client, local, peer)))
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        _port: Option<u16>,
+        _dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        if host.eq_ignore_ascii_case("localhost") {
+            return Ok(vec![Ipv4Addr::LOCALHOST.into(), Ipv6Addr::LOCALHOST.into()]);
+        }
+        host.trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse()
+            .map(|ip| vec![ip])
+            .map_err(|_| NetworkError::AddressNotAvailable)
+    }
+}
+
+/// An end of a TCP connection of the loopback network.
+#[derive(Debug)]
+pub struct LoopbackTcpSocket {
+    stream: UnixStream,
+    addr: SocketAddr,
+    peer: SocketAddr,
+    ttl: u32,
+    nodelay: bool,
+}
+
+impl LoopbackTcpSocket {
+    fn new(stream: UnixStream, addr: SocketAddr, peer: SocketAddr) -> Self {
+        Self {
+            stream,
+            addr,
+            peer,
+            ttl: DEFAULT_TTL,
+            nodelay: false,
+        }
+    }
+}
+
+impl VirtualIoSource for LoopbackTcpSocket {
+    fn remove_handler(&mut self) {
+        self.stream.remove_handler();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.stream.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.stream.poll_write_ready(cx)
+    }
+}
+
+impl VirtualSocket for LoopbackTcpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.ttl = ttl;
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Ok(self.ttl)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.stream.status()
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.stream.set_handler(handler)
+    }
+}
+
+impl VirtualConnectedSocket for LoopbackTcpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.stream.set_linger(linger)
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        self.stream.linger()
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        self.stream.try_send(data)
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        self.stream.try_flush()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.stream.close()
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        self.stream.try_recv(buf)
+    }
+
+    fn try_peek(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        self.stream.try_peek(buf)
+    }
+}
+
+impl VirtualTcpSocket for LoopbackTcpSocket {
+    fn set_recv_buf_size(&mut self, size: usize) -> Result<()> {
+        self.stream.set_recv_buf_size(size)
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        self.stream.recv_buf_size()
+    }
+
+    fn set_send_buf_size(&mut self, size: usize) -> Result<()> {
+        self.stream.set_send_buf_size(size)
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        self.stream.send_buf_size()
+    }
+
+    /// The bytes are always sent right away, the option is only reported
+    /// back.
+    fn set_nodelay(&mut self, nodelay: bool) -> Result<()> {
+        self.nodelay = nodelay;
+        Ok(())
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        Ok(self.nodelay)
+    }
+
+    fn set_keepalive(&mut self, keepalive: bool) -> Result<()> {
+        self.stream.set_keepalive(keepalive)
+    }
+
+    fn keepalive(&self) -> Result<bool> {
+        self.stream.keepalive()
+    }
+
+    fn set_dontroute(&mut self, dontroute: bool) -> Result<()> {
+        self.stream.set_dontroute(dontroute)
+    }
+
+    fn dontroute(&self) -> Result<bool> {
+        self.stream.dontroute()
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        Ok(self.peer)
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        self.stream.shutdown(how)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.stream.is_closed()
+    }
+}
+
+/// The connections waiting to be accepted by a listener.
+#[derive(Debug)]
+struct Backlog {
+    pending: VecDeque<LoopbackTcpSocket>,
+    interest: Interest,
+}
+
+/// A TCP socket of the loopback network listening for connections, which
+/// frees its address once it's dropped.
+#[derive(Debug)]
+pub struct LoopbackTcpListener {
+    backlog: Arc<Mutex<Backlog>>,
+    addr: SocketAddr,
+    ttl: u8,
+}
+
+impl VirtualIoSource for LoopbackTcpListener {
+    fn remove_handler(&mut self) {
+        self.backlog.lock().unwrap().interest.handler.take();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut backlog = self.backlog.lock().unwrap();
+        if !backlog.pending.is_empty() {
+            return Poll::Ready(Ok(backlog.pending.len()));
+        }
+        backlog.interest.read_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    fn poll_write_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        Poll::Pending
+    }
+}
+
+impl VirtualTcpListener for LoopbackTcpListener {
+    fn try_accept(&mut self) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        let socket = self
+            .backlog
+            .lock()
+            .unwrap()
+            .pending
+            .pop_front()
+            .ok_or(NetworkError::WouldBlock)?;
+        let peer = socket.peer;
+        Ok((Box::new(socket), peer))
+    }
+
+    fn set_handler(&mut self, mut handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        let mut backlog = self.backlog.lock().unwrap();
+        if !backlog.pending.is_empty() {
+            handler.push_interest(InterestType::Readable);
+        }
+        backlog.interest.handler = Some(handler);
+        Ok(())
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn set_ttl(&mut self, ttl: u8) -> Result<()> {
+        self.ttl = ttl;
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        Ok(self.ttl)
+    }
+}
+
+/// The datagrams sent to a UDP socket which weren't received yet, with the
+/// address they were sent from.
+#[derive(Debug, Default)]
+struct DatagramQueue {
+    datagrams: VecDeque<(Vec<u8>, SocketAddr)>,
+    interest: Interest,
+}
+
+/// A UDP socket of the loopback network, which frees its address once it's
+/// dropped.
+#[derive(Debug)]
+pub struct LoopbackUdpSocket {
+    network: LoopbackNetworking,
+    queue: Arc<Mutex<DatagramQueue>>,
+    addr: SocketAddr,
+    ttl: u32,
+    broadcast: bool,
+}
+
+impl LoopbackUdpSocket {
+    fn read_front(&self, buf: &mut [MaybeUninit<u8>], pop: bool) -> Result<(usize, SocketAddr)> {
+        let mut queue = self.queue.lock().unwrap();
+        let (datagram, from) = queue.datagrams.front().ok_or(NetworkError::WouldBlock)?;
+        // Like on Linux, what doesn't fit in the buffer is discarded
+        let amt = datagram.len().min(buf.len());
+        for (dst, src) in buf.iter_mut().zip(&datagram[..amt]) {
+            dst.write(*src);
+        }
+        let from = *from;
+        if pop {
+            queue.datagrams.pop_front();
+        }
+        Ok((amt, from))
+    }
+}
+
+impl VirtualIoSource for LoopbackUdpSocket {
+    fn remove_handler(&mut self) {
+        self.queue.lock().unwrap().interest.handler.take();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut queue = self.queue.lock().unwrap();
+        if let Some((datagram, _)) = queue.datagrams.front() {
+            return Poll::Ready(Ok(datagram.len()));
+        }
+        queue.interest.read_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    fn poll_write_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        Poll::Ready(Ok(DEFAULT_BUF_SIZE))
+    }
+}
+
+impl VirtualSocket for LoopbackUdpSocket {
+    fn set_ttl(&mut self, ttl: u32) -> Result<()> {
+        self.ttl = ttl;
+        Ok(())
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Ok(self.ttl)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(SocketStatus::Opened)
+    }
+
+    fn set_handler(&mut self, mut handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        handler.push_interest(InterestType::Writable);
+        let mut queue = self.queue.lock().unwrap();
+        if !queue.datagrams.is_empty() {
+            handler.push_interest(InterestType::Readable);
+        }
+        queue.interest.handler = Some(handler);
+        Ok(())
+    }
+}
+
+impl VirtualConnectionlessSocket for LoopbackUdpSocket {
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        let receiver = {
+            let network = self.network.network.lock().unwrap();
+            match network.is_local(addr.ip()) {
+                true => network.udp_sockets.find(addr),
+                false => None,
+            }
+        };
+        let from = match self.addr.ip().is_unspecified() {
+            true => SocketAddr::new(addr.ip(), self.addr.port()),
+            false => self.addr,
+        };
+        // The datagrams nobody receives are lost
+        if let Some(receiver) = receiver {
+            let mut receiver = receiver.lock().unwrap();
+            if receiver.datagrams.len() < MAX_QUEUED_DATAGRAMS {
+                receiver.datagrams.push_back((data.to_vec(), from));
+                receiver.interest.notify(InterestType::Readable);
+            }
+        }
+        Ok(data.len())
+    }
+
+    fn try_recv_from(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)> {
+        self.read_front(buf, true)
+    }
+
+    fn try_peek_from(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)> {
+        self.read_front(buf, false)
+    }
+}
+
+impl VirtualUdpSocket for LoopbackUdpSocket {
+    fn set_broadcast(&mut self, broadcast: bool) -> Result<()> {
+        self.broadcast = broadcast;
+        Ok(())
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        Ok(self.broadcast)
+    }
+
+    fn set_multicast_loop_v4(&mut self, _val: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn set_multicast_loop_v6(&mut self, _val: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn set_multicast_ttl_v4(&mut self, _ttl: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn join_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn join_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    fn recv(socket: &mut dyn VirtualTcpSocket, len: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![MaybeUninit::new(0); len];
+        let amt = socket.try_recv(&mut buf)?;
+        Ok(buf[..amt]
+            .iter()
+            .map(|b| unsafe { b.assume_init() })
+            .collect())
+    }
+
+    #[test]
+    fn tcp_connections_reach_the_listener_of_their_address() {
+        let net = LoopbackNetworking::new();
+        let mut listener =
+            block_on(net.listen_tcp(addr("0.0.0.0:8080"), false, false, false)).unwrap();
+        assert_eq!(
+            block_on(net.listen_tcp(addr("127.0.0.1:8080"), false, false, false)).unwrap_err(),
+            NetworkError::AddressInUse
+        );
+
+        let mut client =
+            block_on(net.connect_tcp(addr("0.0.0.0:0"), addr("127.0.0.1:8080"))).unwrap();
+        let (mut server, peer) = listener.try_accept().unwrap();
+        assert_eq!(peer, client.addr_local().unwrap());
+        assert_eq!(peer.ip(), addr("127.0.0.1:0").ip());
+        assert_eq!(server.addr_local().unwrap(), addr("127.0.0.1:8080"));
+        assert_eq!(client.addr_peer().unwrap(), addr("127.0.0.1:8080"));
+
+        assert_eq!(client.try_send(b"ping").unwrap(), 4);
+        assert_eq!(recv(server.as_mut(), 16).unwrap(), b"ping");
+        server.try_send(b"pong").unwrap();
+        server.shutdown(Shutdown::Write).unwrap();
+        assert_eq!(recv(client.as_mut(), 16).unwrap(), b"pong");
+        assert_eq!(recv(client.as_mut(), 16).unwrap(), b"");
+
+        // The address is free again once the listener is gone
+        drop(listener);
+        assert_eq!(
+            block_on(net.connect_tcp(addr("0.0.0.0:0"), addr("127.0.0.1:8080"))).unwrap_err(),
+            NetworkError::ConnectionRefused
+        );
+        block_on(net.listen_tcp(addr("127.0.0.1:8080"), false, false, false)).unwrap();
+    }
+
+    #[test]
+    fn addresses_added_by_a_process_are_reachable_by_the_others() {
+        let net = LoopbackNetworking::new();
+        let other = net.clone();
+        let server_addr = addr("10.0.0.2:80");
+        assert_eq!(
+            block_on(net.listen_tcp(server_addr, false, false, false)).unwrap_err(),
+            NetworkError::AddressNotAvailable
+        );
+
+        block_on(net.ip_add(server_addr.ip(), 24)).unwrap();
+        let mut listener = block_on(net.listen_tcp(server_addr, false, false, false)).unwrap();
+        block_on(other.connect_tcp(addr("0.0.0.0:0"), server_addr)).unwrap();
+        listener.try_accept().unwrap();
+
+        // The rest of the subnet isn't on the host
+        assert_eq!(
+            block_on(other.connect_tcp(addr("0.0.0.0:0"), addr("10.0.0.3:80"))).unwrap_err(),
+            NetworkError::ConnectionRefused
+        );
+    }
+
+    #[test]
+    fn udp_datagrams_carry_their_source() {
+        let net = LoopbackNetworking::new();
+        let mut server = block_on(net.bind_udp(addr("[::]:5353"), false, false)).unwrap();
+        let mut client = block_on(net.bind_udp(addr("127.0.0.1:0"), false, false)).unwrap();
+        let client_addr = client.addr_local().unwrap();
+        assert_ne!(client_addr.port(), 0);
+
+        // An unspecified IPv6 address receives IPv4 too
+        client
+            .try_send_to(b"query", addr("127.0.0.1:5353"))
+            .unwrap();
+        let mut buf = [MaybeUninit::new(0); 16];
+        let (amt, from) = server.try_recv_from(&mut buf).unwrap();
+        assert_eq!((amt, from), (5, client_addr));
+        assert_eq!(
+            server.try_recv_from(&mut buf).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+
+        server.try_send_to(b"answer", from).unwrap();
+        let (amt, from) = client.try_recv_from(&mut buf).unwrap();
+        assert_eq!((amt, from), (6, addr("127.0.0.1:5353")));
+
+        // What is sent to a port nobody is bound to is lost
+        drop(server);
+        assert_eq!(
+            client
+                .try_send_to(b"query", addr("127.0.0.1:5353"))
+                .unwrap(),
+            5
+        );
+    }
+}
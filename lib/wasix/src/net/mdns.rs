@@ -0,0 +1,867 @@
+//! Discovery of the services of a local network over multicast DNS, as
+//! zeroconf does, and registration of the services the processes offer.
+//!
+//! The processes of a control plane find the services of each other on any
+//! network. On the networks which support multicast, they also find the
+//! services of the other hosts, which are told about the services of the
+//! processes when they're registered. The queries the other hosts send
+//! later on are answered by [`MdnsServices::respond`], which the embedder
+//! runs in the background.
+
+use std::{
+    collections::HashMap,
+    future::poll_fn,
+    mem::MaybeUninit,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{Arc, Mutex},
+    task::Poll,
+    time::Duration,
+};
+
+use virtual_net::{DynVirtualNetworking, NetworkError, VirtualUdpSocket};
+use wasmer_wasix_types::wasi::Errno;
+
+use crate::VirtualTaskManager;
+
+/// The port mDNS is spoken on.
+pub const MDNS_PORT: u16 = 5353;
+
+/// The IPv4 group the mDNS queries and responses are sent to.
+pub const MDNS_GROUP_V4: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+
+/// How long the other hosts keep the records of the services, in seconds.
+const TTL: u32 = 120;
+
+/// The largest message received, a response is split well below it.
+const MAX_MESSAGE_SIZE: usize = 9000;
+
+/// The name the types of the services are listed under.
+const SERVICE_TYPES: [&str; 3] = ["_services", "_dns-sd", "_udp"];
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+
+/// Set on the class of a question which asks for a unicast response.
+const UNICAST_RESPONSE: u16 = 0x8000;
+
+/// The flags of a response, which is always authoritative in mDNS.
+const RESPONSE_FLAGS: u16 = 0x8400;
+
+/// A service offered on the local network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MdnsService {
+    /// The name of the instance, such as `Kitchen printer`
+    pub instance: String,
+    /// The type of the service, such as `_ipp._tcp`
+    pub service_type: String,
+    /// The host offering the service, without its `.local` domain
+    pub host: String,
+    pub port: u16,
+    pub addrs: Vec<IpAddr>,
+    /// The texts attached to the service, usually `key=value` pairs
+    pub txt: Vec<String>,
+}
+
+impl MdnsService {
+    fn name(&self) -> Name {
+        std::iter::once(self.instance.clone())
+            .chain(service_name(&self.service_type))
+            .collect()
+    }
+
+    fn host_name(&self) -> Name {
+        local_name(self.host.split('.'))
+    }
+
+    fn is_instance(&self, other: &MdnsService) -> bool {
+        same_name(&self.name(), &other.name())
+    }
+
+    /// The records describing the service, which are kept for `ttl`
+    /// seconds.
+    fn records(&self, ttl: u32) -> Vec<Record> {
+        let record = |name: Name, data| Record { name, ttl, data };
+        let mut records = vec![
+            record(service_name(&self.service_type), RData::Ptr(self.name())),
+            record(
+                self.name(),
+                RData::Srv {
+                    port: self.port,
+                    target: self.host_name(),
+                },
+            ),
+            record(self.name(), RData::Txt(self.txt.clone())),
+        ];
+        records.extend(self.addrs.iter().map(|ip| {
+            record(
+                self.host_name(),
+                match ip {
+                    IpAddr::V4(ip) => RData::A(*ip),
+                    IpAddr::V6(ip) => RData::Aaaa(*ip),
+                },
+            )
+        }));
+        records
+    }
+}
+
+/// Checks a service type such as `_http._tcp`, with or without its
+/// `.local` domain, and returns it without the domain.
+pub(crate) fn parse_service_type(service_type: &str) -> Option<String> {
+    let service_type = service_type.trim_end_matches('.').to_ascii_lowercase();
+    let service_type = service_type.strip_suffix(".local").unwrap_or(&service_type);
+    let (name, proto) = service_type.split_once('.')?;
+    let name_len = name.strip_prefix('_')?.len();
+    match (1..=15).contains(&name_len) && matches!(proto, "_tcp" | "_udp") {
+        true => Some(service_type.to_string()),
+        false => None,
+    }
+}
+
+#[derive(Debug)]
+struct Registration {
+    pid: u32,
+    service: MdnsService,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    next_id: u32,
+    services: HashMap<u32, Registration>,
+}
+
+/// The services the processes of a control plane registered, see
+/// [`WasiControlPlane::mdns`](crate::os::task::control_plane::WasiControlPlane::mdns).
+///
+/// A service is registered until its process unregisters it or exits.
+#[derive(Debug, Clone, Default)]
+pub struct MdnsServices {
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl MdnsServices {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The services registered by the processes.
+    pub fn registered(&self) -> Vec<MdnsService> {
+        let registry = self.registry.lock().unwrap();
+        registry
+            .services
+            .values()
+            .map(|registration| registration.service.clone())
+            .collect()
+    }
+
+    /// Answers the queries the hosts of `net` send about the registered
+    /// services, until the network fails. It's run in the background on a
+    /// network which supports multicast, and shares the mDNS port with the
+    /// other responders of the host.
+    pub async fn respond(&self, net: DynVirtualNetworking) -> Result<(), NetworkError> {
+        let addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), MDNS_PORT);
+        let mut socket = net.bind_udp(addr, true, true).await?;
+        socket.join_multicast_v4(MDNS_GROUP_V4, Ipv4Addr::UNSPECIFIED)?;
+        loop {
+            let (data, from) = recv_from(socket.as_mut()).await?;
+            let Some(query) = Message::decode(&data).filter(|query| !query.response) else {
+                continue;
+            };
+            let Some(mut response) = self.answer(&query) else {
+                continue;
+            };
+            // A querier which isn't on the mDNS port is a plain DNS client,
+            // which expects the answer to its own query
+            let legacy = from.port() != MDNS_PORT;
+            let to = match legacy || query.questions.iter().all(|q| q.unicast) {
+                true => from,
+                false => SocketAddr::new(MDNS_GROUP_V4.into(), MDNS_PORT),
+            };
+            if legacy {
+                response.id = query.id;
+                response.questions = query.questions;
+            }
+            if let Err(err) = socket.try_send_to(&response.encode(), to) {
+                tracing::debug!(%to, %err, "mDNS response not sent");
+            }
+        }
+    }
+
+    /// Registers the service of process `pid`, offered from the addresses
+    /// of the host on `net`, and tells the other hosts about it.
+    pub(crate) async fn register(
+        &self,
+        net: &DynVirtualNetworking,
+        pid: u32,
+        mut service: MdnsService,
+    ) -> Result<u32, Errno> {
+        service.addrs = host_addrs(net).await;
+        let id = {
+            let mut registry = self.registry.lock().unwrap();
+            if registry
+                .services
+                .values()
+                .any(|registration| registration.service.is_instance(&service))
+            {
+                return Err(Errno::Exist);
+            }
+            registry.next_id += 1;
+            let id = registry.next_id;
+            registry.services.insert(
+                id,
+                Registration {
+                    pid,
+                    service: service.clone(),
+                },
+            );
+            id
+        };
+        announce(net, &service, TTL).await;
+        Ok(id)
+    }
+
+    /// Unregisters service `id` of process `pid`, and tells the other hosts
+    /// it's gone.
+    pub(crate) async fn unregister(
+        &self,
+        net: &DynVirtualNetworking,
+        pid: u32,
+        id: u32,
+    ) -> Result<(), Errno> {
+        let service = {
+            let mut registry = self.registry.lock().unwrap();
+            match registry.services.get(&id) {
+                Some(registration) if registration.pid == pid => {}
+                _ => return Err(Errno::Noent),
+            }
+            registry.services.remove(&id).unwrap().service
+        };
+        // A time to live of zero makes the other hosts forget the records
+        announce(net, &service, 0).await;
+        Ok(())
+    }
+
+    /// Unregisters the services of a process which exited.
+    pub(crate) fn unregister_process(&self, pid: u32) {
+        let mut registry = self.registry.lock().unwrap();
+        registry
+            .services
+            .retain(|_, registration| registration.pid != pid);
+    }
+
+    /// Finds the services of type `service_type`, the registered ones and,
+    /// when `net` supports multicast, the ones of the hosts which answer
+    /// within `timeout`.
+    pub(crate) async fn browse(
+        &self,
+        net: &DynVirtualNetworking,
+        tasks: &dyn VirtualTaskManager,
+        service_type: &str,
+        timeout: Duration,
+    ) -> Vec<MdnsService> {
+        let mut services: Vec<_> = self
+            .registered()
+            .into_iter()
+            .filter(|service| service.service_type == service_type)
+            .collect();
+
+        let query = Message {
+            questions: vec![Question {
+                name: service_name(service_type),
+                qtype: TYPE_PTR,
+                unicast: true,
+            }],
+            ..Default::default()
+        };
+        let group = SocketAddr::new(MDNS_GROUP_V4.into(), MDNS_PORT);
+        let addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0);
+        let mut socket = match net.bind_udp(addr, false, false).await {
+            Ok(socket) => socket,
+            Err(err) => {
+                tracing::debug!(%err, "mDNS query not sent");
+                return services;
+            }
+        };
+        if let Err(err) = socket.try_send_to(&query.encode(), group) {
+            tracing::debug!(%err, "mDNS query not sent");
+            return services;
+        }
+
+        let mut answers = Answers::default();
+        let receive = async {
+            while let Ok((data, _)) = recv_from(socket.as_mut()).await {
+                if let Some(response) = Message::decode(&data).filter(|m| m.response) {
+                    answers.add(response);
+                }
+            }
+        };
+        tokio::select! {
+            () = receive => {}
+            () = tasks.sleep_now(timeout) => {}
+        }
+
+        for service in answers.services(service_type) {
+            if !services.iter().any(|found| found.is_instance(&service)) {
+                services.push(service);
+            }
+        }
+        services
+    }
+
+    /// The response to `query`, if it asks about the registered services.
+    fn answer(&self, query: &Message) -> Option<Message> {
+        let asked = |record: &Record| query.questions.iter().any(|q| q.matches(record));
+        let mut answers = Vec::new();
+        let mut additionals = Vec::new();
+        for service in self.registered() {
+            let service_type = Record {
+                name: local_name(SERVICE_TYPES),
+                ttl: TTL,
+                data: RData::Ptr(service_name(&service.service_type)),
+            };
+            if asked(&service_type) && !answers.contains(&service_type) {
+                answers.push(service_type);
+            }
+            // The other records of a service come along with the asked ones,
+            // which spares the querier another query
+            let records = service.records(TTL);
+            if records.iter().any(asked) {
+                for record in records {
+                    match asked(&record) {
+                        true => answers.push(record),
+                        false => additionals.push(record),
+                    }
+                }
+            }
+        }
+        if answers.is_empty() {
+            return None;
+        }
+        Some(Message {
+            response: true,
+            answers,
+            additionals,
+            ..Default::default()
+        })
+    }
+}
+
+/// The records found about the services of a type.
+#[derive(Debug, Default)]
+struct Answers {
+    instances: Vec<Name>,
+    srv: HashMap<String, (u16, Name)>,
+    txt: HashMap<String, Vec<String>>,
+    addrs: HashMap<String, Vec<IpAddr>>,
+}
+
+impl Answers {
+    fn add(&mut self, response: Message) {
+        for record in response.answers.into_iter().chain(response.additionals) {
+            let key = name_key(&record.name);
+            match record.data {
+                // The instances which are gone come with a time to live of
+                // zero
+                RData::Ptr(instance) if record.ttl == 0 => {
+                    self.instances.retain(|name| !same_name(name, &instance));
+                }
+                RData::Ptr(instance) => {
+                    if !self.instances.iter().any(|name| same_name(name, &instance)) {
+                        self.instances.push(instance);
+                    }
+                }
+                RData::Srv { port, target } => {
+                    self.srv.insert(key, (port, target));
+                }
+                RData::Txt(txt) => {
+                    self.txt.insert(key, txt);
+                }
+                RData::A(ip) => self.add_addr(key, ip.into()),
+                RData::Aaaa(ip) => self.add_addr(key, ip.into()),
+                RData::Other(_) => {}
+            }
+        }
+    }
+
+    fn add_addr(&mut self, key: String, ip: IpAddr) {
+        let addrs = self.addrs.entry(key).or_default();
+        if !addrs.contains(&ip) {
+            addrs.push(ip);
+        }
+    }
+
+    /// The services of type `service_type` whose port is known.
+    fn services(&self, service_type: &str) -> Vec<MdnsService> {
+        let type_name = service_name(service_type);
+        self.instances
+            .iter()
+            .filter(|name| name.len() == type_name.len() + 1 && same_name(&name[1..], &type_name))
+            .filter_map(|name| {
+                let key = name_key(name);
+                let (port, target) = self.srv.get(&key)?;
+                let host = match target.split_last() {
+                    Some((local, host)) if local.eq_ignore_ascii_case("local") => host,
+                    _ => target,
+                };
+                Some(MdnsService {
+                    instance: name[0].clone(),
+                    service_type: service_type.to_string(),
+                    host: host.join("."),
+                    port: *port,
+                    addrs: self
+                        .addrs
+                        .get(&name_key(target))
+                        .cloned()
+                        .unwrap_or_default(),
+                    txt: self.txt.get(&key).cloned().unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Tells the hosts of `net` about `service`, whose records they keep for
+/// `ttl` seconds, when it supports multicast.
+async fn announce(net: &DynVirtualNetworking, service: &MdnsService, ttl: u32) {
+    let response = Message {
+        response: true,
+        answers: service.records(ttl),
+        ..Default::default()
+    };
+    // The hosts only trust the responses sent from the mDNS port
+    let addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), MDNS_PORT);
+    let group = SocketAddr::new(MDNS_GROUP_V4.into(), MDNS_PORT);
+    let sent = match net.bind_udp(addr, true, true).await {
+        Ok(mut socket) => socket.try_send_to(&response.encode(), group),
+        Err(err) => Err(err),
+    };
+    if let Err(err) = sent {
+        tracing::debug!(%err, "mDNS announcement not sent");
+    }
+}
+
+/// The addresses of the host on `net`, the loopback ones only when it has
+/// no others.
+async fn host_addrs(net: &DynVirtualNetworking) -> Vec<IpAddr> {
+    let ips: Vec<IpAddr> = net
+        .ip_list()
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|cidr| cidr.ip)
+        .collect();
+    match ips.iter().any(|ip| !ip.is_loopback()) {
+        true => ips.into_iter().filter(|ip| !ip.is_loopback()).collect(),
+        false => ips,
+    }
+}
+
+async fn recv_from(
+    socket: &mut (dyn VirtualUdpSocket + Sync),
+) -> Result<(Vec<u8>, SocketAddr), NetworkError> {
+    let mut buf = vec![MaybeUninit::new(0); MAX_MESSAGE_SIZE];
+    let (amt, from) = poll_fn(|cx| loop {
+        match socket.poll_read_ready(cx) {
+            Poll::Ready(Ok(_)) => {}
+            Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+            Poll::Pending => return Poll::Pending,
+        }
+        match socket.try_recv_from(&mut buf) {
+            Err(NetworkError::WouldBlock) => {}
+            ret => return Poll::Ready(ret),
+        }
+    })
+    .await?;
+    // The buffer is filled with zeros when it's created, all its bytes are
+    // initialized
+    let data = buf[..amt]
+        .iter()
+        .map(|byte| unsafe { byte.assume_init() })
+        .collect();
+    Ok((data, from))
+}
+
+/// A domain name, as its labels.
+type Name = Vec<String>;
+
+fn local_name<'a>(labels: impl IntoIterator<Item = &'a str>) -> Name {
+    labels
+        .into_iter()
+        .chain(["local"])
+        .map(String::from)
+        .collect()
+}
+
+fn service_name(service_type: &str) -> Name {
+    local_name(service_type.split('.'))
+}
+
+fn same_name(a: &[String], b: &[String]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.eq_ignore_ascii_case(b))
+}
+
+fn name_key(name: &[String]) -> String {
+    name.join(".").to_ascii_lowercase()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Ptr(Name),
+    Srv { port: u16, target: Name },
+    Txt(Vec<String>),
+    Other(u16),
+}
+
+impl RData {
+    fn rtype(&self) -> u16 {
+        match self {
+            RData::A(_) => TYPE_A,
+            RData::Aaaa(_) => TYPE_AAAA,
+            RData::Ptr(_) => TYPE_PTR,
+            RData::Srv { .. } => TYPE_SRV,
+            RData::Txt(_) => TYPE_TXT,
+            RData::Other(rtype) => *rtype,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Record {
+    name: Name,
+    ttl: u32,
+    data: RData,
+}
+
+impl Record {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        write_name(buf, &self.name);
+        buf.extend(self.data.rtype().to_be_bytes());
+        buf.extend(CLASS_IN.to_be_bytes());
+        buf.extend(self.ttl.to_be_bytes());
+        let len_at = buf.len();
+        buf.extend([0, 0]);
+        match &self.data {
+            RData::A(ip) => buf.extend(ip.octets()),
+            RData::Aaaa(ip) => buf.extend(ip.octets()),
+            RData::Ptr(name) => write_name(buf, name),
+            RData::Srv { port, target } => {
+                // Neither a priority nor a weight
+                buf.extend([0; 4]);
+                buf.extend(port.to_be_bytes());
+                write_name(buf, target);
+            }
+            // A record without texts has an empty one
+            RData::Txt(txt) if txt.is_empty() => buf.push(0),
+            RData::Txt(txt) => {
+                for txt in txt {
+                    let txt = &txt.as_bytes()[..txt.len().min(255)];
+                    buf.push(txt.len() as u8);
+                    buf.extend(txt);
+                }
+            }
+            RData::Other(_) => {}
+        }
+        let len = (buf.len() - len_at - 2) as u16;
+        buf[len_at..len_at + 2].copy_from_slice(&len.to_be_bytes());
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Question {
+    name: Name,
+    qtype: u16,
+    unicast: bool,
+}
+
+impl Question {
+    fn matches(&self, record: &Record) -> bool {
+        same_name(&self.name, &record.name)
+            && (self.qtype == TYPE_ANY || self.qtype == record.data.rtype())
+    }
+}
+
+/// A DNS message, with only what mDNS uses of it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Message {
+    id: u16,
+    response: bool,
+    questions: Vec<Question>,
+    answers: Vec<Record>,
+    additionals: Vec<Record>,
+}
+
+impl Message {
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(self.id.to_be_bytes());
+        let flags = match self.response {
+            true => RESPONSE_FLAGS,
+            false => 0,
+        };
+        buf.extend(flags.to_be_bytes());
+        for count in [
+            self.questions.len(),
+            self.answers.len(),
+            0,
+            self.additionals.len(),
+        ] {
+            buf.extend((count as u16).to_be_bytes());
+        }
+        for question in &self.questions {
+            write_name(&mut buf, &question.name);
+            buf.extend(question.qtype.to_be_bytes());
+            let class = match question.unicast {
+                true => CLASS_IN | UNICAST_RESPONSE,
+                false => CLASS_IN,
+            };
+            buf.extend(class.to_be_bytes());
+        }
+        for record in self.answers.iter().chain(&self.additionals) {
+            record.encode(&mut buf);
+        }
+        buf
+    }
+
+    /// Reads a message, the records of its authority section are taken as
+    /// additional ones.
+    fn decode(msg: &[u8]) -> Option<Self> {
+        let mut reader = Reader { msg, pos: 0 };
+        let id = reader.u16()?;
+        let flags = reader.u16()?;
+        let questions = reader.u16()?;
+        let answers = reader.u16()?;
+        let additionals = u32::from(reader.u16()?) + u32::from(reader.u16()?);
+        Some(Self {
+            id,
+            response: flags & 0x8000 != 0,
+            questions: (0..questions)
+                .map(|_| {
+                    let name = reader.name()?;
+                    let qtype = reader.u16()?;
+                    let class = reader.u16()?;
+                    Some(Question {
+                        name,
+                        qtype,
+                        unicast: class & UNICAST_RESPONSE != 0,
+                    })
+                })
+                .collect::<Option<_>>()?,
+            answers: (0..answers)
+                .map(|_| reader.record())
+                .collect::<Option<_>>()?,
+            additionals: (0..additionals)
+                .map(|_| reader.record())
+                .collect::<Option<_>>()?,
+        })
+    }
+}
+
+fn write_name(buf: &mut Vec<u8>, name: &[String]) {
+    for label in name {
+        let label = &label.as_bytes()[..label.len().min(63)];
+        buf.push(label.len() as u8);
+        buf.extend(label);
+    }
+    buf.push(0);
+}
+
+struct Reader<'a> {
+    msg: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.msg.get(self.pos..self.pos + len)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_be_bytes(self.bytes(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    /// Reads a name, following the pointers to the names it ends like.
+    fn name(&mut self) -> Option<Name> {
+        let mut name = Vec::new();
+        let mut pos = self.pos;
+        let mut jumped = false;
+        // Pointers may loop, while a name has at most 127 labels
+        for _ in 0..128 {
+            let len = *self.msg.get(pos)? as usize;
+            match len {
+                0 => {
+                    if !jumped {
+                        self.pos = pos + 1;
+                    }
+                    return Some(name);
+                }
+                len if len & 0xc0 == 0xc0 => {
+                    let target = (len & 0x3f) << 8 | *self.msg.get(pos + 1)? as usize;
+                    if !jumped {
+                        self.pos = pos + 2;
+                        jumped = true;
+                    }
+                    pos = target;
+                }
+                len if len < 64 => {
+                    let label = self.msg.get(pos + 1..pos + 1 + len)?;
+                    name.push(String::from_utf8_lossy(label).into_owned());
+                    pos += 1 + len;
+                }
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    fn record(&mut self) -> Option<Record> {
+        let name = self.name()?;
+        let rtype = self.u16()?;
+        let _class = self.u16()?;
+        let ttl = self.u32()?;
+        let len = self.u16()? as usize;
+        let end = self.pos + len;
+        let rdata = self.msg.get(self.pos..end)?;
+        let data = match rtype {
+            TYPE_A => RData::A(<[u8; 4]>::try_from(rdata).ok()?.into()),
+            TYPE_AAAA => RData::Aaaa(<[u8; 16]>::try_from(rdata).ok()?.into()),
+            TYPE_PTR => RData::Ptr(self.name()?),
+            TYPE_SRV => {
+                // The priority and the weight don't matter on a local network
+                self.bytes(4)?;
+                RData::Srv {
+                    port: self.u16()?,
+                    target: self.name()?,
+                }
+            }
+            TYPE_TXT => {
+                let mut txt = Vec::new();
+                let mut rest = rdata;
+                while let Some((&len, tail)) = rest.split_first() {
+                    let text = tail.get(..len as usize)?;
+                    if !text.is_empty() {
+                        txt.push(String::from_utf8_lossy(text).into_owned());
+                    }
+                    rest = &tail[len as usize..];
+                }
+                RData::Txt(txt)
+            }
+            rtype => RData::Other(rtype),
+        };
+        self.pos = end;
+        Some(Record { name, ttl, data })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::net::loopback::LoopbackNetworking;
+
+    #[test]
+    fn service_types_are_checked() {
+        assert_eq!(
+            parse_service_type("_HTTP._tcp.local."),
+            Some("_http._tcp".to_string())
+        );
+        assert_eq!(
+            parse_service_type("_ipp._udp"),
+            Some("_ipp._udp".to_string())
+        );
+        assert_eq!(parse_service_type("http._tcp"), None);
+        assert_eq!(parse_service_type("_http._sctp"), None);
+        assert_eq!(parse_service_type("_a-very-long-service._tcp"), None);
+    }
+
+    #[test]
+    fn registered_services_are_answered_for_and_found() {
+        let net: DynVirtualNetworking = Arc::new(LoopbackNetworking::new());
+        let services = MdnsServices::new();
+        let service = MdnsService {
+            instance: "Kitchen.printer".to_string(),
+            service_type: "_ipp._tcp".to_string(),
+            host: "wasix-1".to_string(),
+            port: 631,
+            addrs: Vec::new(),
+            txt: vec!["rp=ipp/print".to_string()],
+        };
+        let id = block_on(services.register(&net, 1, service.clone())).unwrap();
+        // The instance names are unique
+        assert_eq!(
+            block_on(services.register(&net, 2, service.clone())),
+            Err(Errno::Exist)
+        );
+
+        // A query for the type of the service is answered with the service,
+        // and the records which complete it
+        let query = Message {
+            questions: vec![Question {
+                name: service_name("_ipp._tcp"),
+                qtype: TYPE_PTR,
+                unicast: false,
+            }],
+            ..Default::default()
+        };
+        let query = Message::decode(&query.encode()).unwrap();
+        let response = services.answer(&query).unwrap();
+        assert_eq!(response.answers.len(), 1);
+        let response = Message::decode(&response.encode()).unwrap();
+
+        let mut answers = Answers::default();
+        answers.add(response);
+        let expected = MdnsService {
+            addrs: vec![Ipv4Addr::LOCALHOST.into(), Ipv6Addr::LOCALHOST.into()],
+            ..service
+        };
+        assert_eq!(answers.services("_ipp._tcp"), vec![expected]);
+        assert!(answers.services("_http._tcp").is_empty());
+
+        // Another process can't unregister it, while an exiting one does
+        assert_eq!(
+            block_on(services.unregister(&net, 2, id)),
+            Err(Errno::Noent)
+        );
+        services.unregister_process(1);
+        assert!(services.answer(&query).is_none());
+    }
+
+    #[test]
+    fn compressed_names_are_read() {
+        #[rustfmt::skip]
+        let msg = [
+            0, 0, 0x84, 0, 0, 0, 0, 1, 0, 0, 0, 0,
+            // _http._tcp.local PTR a._http._tcp.local
+            5, b'_', b'h', b't', b't', b'p', 4, b'_', b't', b'c', b'p', 5, b'l', b'o', b'c', b'a', b'l', 0,
+            0, 12, 0, 1, 0, 0, 0, 120, 0, 4,
+            1, b'a', 0xc0, 12,
+        ];
+        let message = Message::decode(&msg).unwrap();
+        assert_eq!(
+            message.answers[0].data,
+            RData::Ptr(vec![
+                "a".to_string(),
+                "_http".to_string(),
+                "_tcp".to_string(),
+                "local".to_string()
+            ])
+        );
+        // A pointer to itself isn't followed forever
+        let mut looped = msg;
+        looped[12..14].copy_from_slice(&[0xc0, 12]);
+        assert!(Message::decode(&looped).is_none());
+    }
+}
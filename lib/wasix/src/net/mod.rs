@@ -9,14 +9,27 @@ use wasmer::{MemoryView, WasmPtr};
 use wasmer_types::MemorySize;
 use wasmer_wasix_types::{
     types::{
-        OptionTag, OptionTimestamp, Route, __wasi_addr_ip4_t, __wasi_addr_ip6_t,
-        __wasi_addr_port_t, __wasi_addr_port_u, __wasi_addr_t, __wasi_addr_u, __wasi_cidr_t,
-        __wasi_cidr_u,
+        __wasi_addr_ip4_t, __wasi_addr_ip6_t, __wasi_addr_port_t, __wasi_addr_port_u,
+        __wasi_addr_t, __wasi_addr_u, __wasi_cidr_t, __wasi_cidr_u, DhcpLease, OptionTag,
+        OptionTimestamp, Route,
     },
     wasi::{Addressfamily, Errno},
 };
 
+pub mod capture;
+mod connect;
+pub mod dns;
+pub mod firewall;
+pub mod forward;
+pub mod loopback;
+pub mod mdns;
+pub mod resolver;
+pub mod shaping;
 pub mod socket;
+pub mod unix;
+
+#[cfg(feature = "tls")]
+pub use virtual_net::tls::TlsConfig;
 
 #[allow(dead_code)]
 pub(crate) fn read_ip<M: MemorySize>(
@@ -228,6 +241,22 @@ pub(crate) fn write_ip_port<M: MemorySize>(
     Ok(())
 }
 
+/// Writes the address of a Unix socket, whose path doesn't fit in the
+/// address so only its family is set.
+pub(crate) fn write_unix_addr<M: MemorySize>(
+    memory: &MemoryView,
+    ptr: WasmPtr<__wasi_addr_port_t, M>,
+) -> Result<(), Errno> {
+    let addr = __wasi_addr_port_t {
+        tag: Addressfamily::Unix,
+        _padding: 0,
+        u: __wasi_addr_port_u { octs: [0; 18] },
+    };
+    let addr_ptr = ptr.deref(memory);
+    addr_ptr.write(addr).map_err(crate::mem_error_to_wasi)?;
+    Ok(())
+}
+
 #[allow(dead_code)]
 pub(crate) fn read_route<M: MemorySize>(
     memory: &MemoryView,
@@ -370,6 +399,57 @@ pub(crate) fn write_route<M: MemorySize>(
     Ok(())
 }
 
+pub(crate) fn write_dhcp_lease<M: MemorySize>(
+    memory: &MemoryView,
+    ptr: WasmPtr<DhcpLease, M>,
+    lease: &virtual_net::DhcpLease,
+) -> Result<(), Errno> {
+    let addr = |ip: Option<IpAddr>| match ip {
+        Some(IpAddr::V4(ip)) => {
+            let o = ip.octets();
+            __wasi_addr_t {
+                tag: Addressfamily::Inet4,
+                _padding: 0,
+                u: __wasi_addr_u {
+                    octs: [o[0], o[1], o[2], o[3], 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                },
+            }
+        }
+        Some(IpAddr::V6(ip)) => __wasi_addr_t {
+            tag: Addressfamily::Inet6,
+            _padding: 0,
+            u: __wasi_addr_u { octs: ip.octets() },
+        },
+        None => __wasi_addr_t {
+            tag: Addressfamily::Unspec,
+            _padding: 0,
+            u: __wasi_addr_u { octs: [0; 16] },
+        },
+    };
+    let timestamp = |time: Option<Duration>| match time {
+        None => OptionTimestamp {
+            tag: OptionTag::None,
+            u: 0,
+        },
+        Some(u) => OptionTimestamp {
+            tag: OptionTag::Some,
+            u: u.as_nanos() as u64,
+        },
+    };
+
+    let lease = DhcpLease {
+        serial: lease.serial,
+        gateway: addr(lease.gateway),
+        server: addr(lease.server),
+        lease_time: timestamp(lease.lease_time),
+        expires_in: timestamp(lease.expires_in),
+    };
+
+    let lease_ptr = ptr.deref(memory);
+    lease_ptr.write(lease).map_err(crate::mem_error_to_wasi)?;
+    Ok(())
+}
+
 pub fn net_error_into_wasi_err(net_error: NetworkError) -> Errno {
     match net_error {
         NetworkError::InvalidFd => Errno::Badf,
@@ -0,0 +1,302 @@
+//! Resolution of the names a program looks up, configured like
+//! `/etc/resolv.conf` does, with the answers cached so that a program
+//! connecting over and over doesn't look the same name up each time.
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use virtual_net::{DynVirtualNetworking, NetworkError, Result};
+use wasmer_wasix_types::wasi::Snapshot0Clockid;
+
+use crate::syscalls::platform_clock_time_get;
+
+/// The most cached names, the oldest answers are dropped past it.
+const MAX_CACHED_NAMES: usize = 1024;
+
+/// How the names are resolved, see [`WasiEnvBuilder::resolver`](crate::WasiEnvBuilder::resolver).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolverConfig {
+    /// The DNS servers asked in turn, until one of them answers, or none to
+    /// let the network pick
+    pub nameservers: Vec<IpAddr>,
+    /// The domains the names are looked up in, such as `svc.local` which
+    /// resolves `db` as `db.svc.local`
+    pub search: Vec<String>,
+    /// How many dots a name has at least to be looked up as it is before
+    /// it's looked up in the search domains
+    pub ndots: usize,
+    /// How long the addresses of a name are kept. The networks don't tell
+    /// how long their answers hold, so this is the time to live of them all
+    pub cache_ttl: Duration,
+    /// How long a name which isn't found is remembered
+    pub negative_ttl: Duration,
+}
+
+impl Default for ResolverConfig {
+    fn default() -> Self {
+        Self {
+            nameservers: Vec::new(),
+            search: Vec::new(),
+            ndots: 1,
+            cache_ttl: Duration::from_secs(30),
+            negative_ttl: Duration::from_secs(5),
+        }
+    }
+}
+
+impl ResolverConfig {
+    /// Reads the `nameserver`, `search`, `domain` and `options ndots:`
+    /// lines of a `resolv.conf` file, ignoring the others.
+    pub fn parse(conf: &str) -> Self {
+        let mut config = Self::default();
+        for line in conf.lines() {
+            let line = line.split(['#', ';']).next().unwrap_or_default();
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("nameserver") => {
+                    if let Some(Ok(ip)) = words.next().map(str::parse) {
+                        config.nameservers.push(ip);
+                    }
+                }
+                // The last of the `search` and `domain` lines wins
+                Some("search") => config.search = words.map(String::from).collect(),
+                Some("domain") => config.search = words.take(1).map(String::from).collect(),
+                Some("options") => {
+                    for option in words {
+                        if let Some(Ok(ndots)) = option.strip_prefix("ndots:").map(str::parse) {
+                            config.ndots = ndots;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        config
+    }
+
+    /// The names `host` is looked up as, in turn.
+    fn candidates(&self, host: &str) -> Vec<String> {
+        // A name ending with a dot is looked up as it is only
+        if let Some(host) = host.strip_suffix('.') {
+            return vec![host.to_string()];
+        }
+        let searched = self.search.iter().map(|domain| format!("{host}.{domain}"));
+        match host.matches('.').count() >= self.ndots {
+            true => std::iter::once(host.to_string()).chain(searched).collect(),
+            false => searched.chain(std::iter::once(host.to_string())).collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CachedAnswer {
+    answer: Result<Vec<IpAddr>>,
+    expires_at: Duration,
+}
+
+/// Resolves the names of a process, its clones share their cache.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Resolver {
+    config: ResolverConfig,
+    cache: Arc<Mutex<HashMap<String, CachedAnswer>>>,
+}
+
+impl Resolver {
+    pub fn new(config: ResolverConfig) -> Self {
+        Self {
+            config,
+            cache: Default::default(),
+        }
+    }
+
+    /// Resolves `host` through `net`, unless it was lately.
+    pub async fn resolve(
+        &self,
+        net: &DynVirtualNetworking,
+        host: &str,
+        port: Option<u16>,
+    ) -> Result<Vec<IpAddr>> {
+        if let Ok(ip) = host.trim_start_matches('[').trim_end_matches(']').parse() {
+            return Ok(vec![ip]);
+        }
+        let key = host.to_ascii_lowercase();
+        if let Some(cached) = self.cache.lock().unwrap().get(&key) {
+            if cached.expires_at > now() {
+                return cached.answer.clone();
+            }
+        }
+
+        let answer = self.lookup(net, host, port).await;
+        let ttl = match &answer {
+            Ok(ips) if !ips.is_empty() => self.config.cache_ttl,
+            _ => self.config.negative_ttl,
+        };
+        let mut cache = self.cache.lock().unwrap();
+        let now = now();
+        cache.retain(|_, cached| cached.expires_at > now);
+        if cache.len() >= MAX_CACHED_NAMES {
+            if let Some(oldest) = cache
+                .iter()
+                .min_by_key(|(_, cached)| cached.expires_at)
+                .map(|(name, _)| name.clone())
+            {
+                cache.remove(&oldest);
+            }
+        }
+        cache.insert(
+            key,
+            CachedAnswer {
+                answer: answer.clone(),
+                expires_at: now + ttl,
+            },
+        );
+        answer
+    }
+
+    /// Looks `host` up under each of its names, asking each server in
+    /// turn, until one has addresses for it.
+    async fn lookup(
+        &self,
+        net: &DynVirtualNetworking,
+        host: &str,
+        port: Option<u16>,
+    ) -> Result<Vec<IpAddr>> {
+        let servers = match self.config.nameservers.is_empty() {
+            true => vec![None],
+            false => self.config.nameservers.iter().copied().map(Some).collect(),
+        };
+        let mut answer = Err(NetworkError::AddressNotAvailable);
+        for name in self.config.candidates(host) {
+            for server in &servers {
+                match net.resolve(&name, port, *server).await {
+                    Ok(ips) if !ips.is_empty() => return Ok(ips),
+                    // A server which knows the name without addresses for
+                    // it is as good as the others
+                    Ok(ips) => {
+                        answer = Ok(ips);
+                        break;
+                    }
+                    Err(err) => {
+                        if answer.is_err() {
+                            answer = Err(err);
+                        }
+                    }
+                }
+            }
+        }
+        answer
+    }
+}
+
+/// The time of the clock, which only has to tell when the answers are
+/// stale, so a change of the clock just refreshes them early or late.
+fn now() -> Duration {
+    let nanos = platform_clock_time_get(Snapshot0Clockid::Realtime, 1).unwrap_or(0);
+    Duration::from_nanos(nanos as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use virtual_net::VirtualNetworking;
+
+    use super::*;
+
+    /// Knows a single name on a single server, and records the lookups.
+    #[derive(Debug, Default)]
+    struct CountingNetworking {
+        lookups: Mutex<Vec<(String, Option<IpAddr>)>>,
+    }
+
+    #[async_trait::async_trait]
+    impl VirtualNetworking for CountingNetworking {
+        async fn resolve(
+            &self,
+            host: &str,
+            _port: Option<u16>,
+            dns_server: Option<IpAddr>,
+        ) -> Result<Vec<IpAddr>> {
+            self.lookups
+                .lock()
+                .unwrap()
+                .push((host.to_string(), dns_server));
+            let server: IpAddr = "10.0.0.2".parse().unwrap();
+            match host.eq_ignore_ascii_case("db.svc.local") && dns_server == Some(server) {
+                true => Ok(vec!["10.0.0.5".parse().unwrap()]),
+                false => Err(NetworkError::AddressNotAvailable),
+            }
+        }
+    }
+
+    #[test]
+    fn resolv_conf_is_parsed() {
+        let config = ResolverConfig::parse(
+            "# generated\n\
+             nameserver 10.0.0.1\n\
+             nameserver not-an-ip\n\
+             domain example.com\n\
+             search svc.local example.com ; the cluster first\n\
+             options rotate ndots:2\n",
+        );
+        assert_eq!(
+            config.nameservers,
+            vec!["10.0.0.1".parse::<IpAddr>().unwrap()]
+        );
+        assert_eq!(config.search, vec!["svc.local", "example.com"]);
+        assert_eq!(config.ndots, 2);
+
+        assert_eq!(
+            config.candidates("db"),
+            vec!["db.svc.local", "db.example.com", "db"]
+        );
+        assert_eq!(
+            config.candidates("a.b.c"),
+            vec!["a.b.c", "a.b.c.svc.local", "a.b.c.example.com"]
+        );
+        assert_eq!(config.candidates("db."), vec!["db"]);
+    }
+
+    #[test]
+    fn names_are_searched_and_cached() {
+        let net = Arc::new(CountingNetworking::default());
+        let dyn_net: DynVirtualNetworking = net.clone();
+        let resolver = Resolver::new(ResolverConfig {
+            nameservers: vec!["10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap()],
+            search: vec!["svc.local".to_string()],
+            ..Default::default()
+        });
+
+        block_on(async {
+            let ips = resolver.resolve(&dyn_net, "DB", None).await.unwrap();
+            assert_eq!(ips, vec!["10.0.0.5".parse::<IpAddr>().unwrap()]);
+            assert_eq!(
+                net.lookups.lock().unwrap().clone(),
+                vec![
+                    (
+                        "DB.svc.local".to_string(),
+                        Some("10.0.0.1".parse().unwrap())
+                    ),
+                    (
+                        "DB.svc.local".to_string(),
+                        Some("10.0.0.2".parse().unwrap())
+                    ),
+                ]
+            );
+            // The name isn't looked up again while it's cached, nor are
+            // the addresses
+            resolver.resolve(&dyn_net, "db", None).await.unwrap();
+            resolver.resolve(&dyn_net, "10.1.2.3", None).await.unwrap();
+            assert_eq!(net.lookups.lock().unwrap().len(), 2);
+
+            // Nor are the names which aren't found
+            assert!(resolver.resolve(&dyn_net, "nope", None).await.is_err());
+            let lookups = net.lookups.lock().unwrap().len();
+            assert!(resolver.resolve(&dyn_net, "nope", None).await.is_err());
+            assert_eq!(net.lookups.lock().unwrap().len(), lookups);
+        });
+    }
+}
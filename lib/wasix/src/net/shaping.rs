@@ -0,0 +1,610 @@
+//! Shaping of the traffic of the processes, to share a network fairly
+//! between them or to see how a program copes with a bad one.
+//!
+//! The traffic is shaped where the processes send and receive, so the
+//! limits hold whatever network the sockets are opened on. A blocking send
+//! or receive waits for the rate to allow it, while a non-blocking one fails
+//! with `EAGAIN` until it does.
+//!
+//! Latency and loss are applied to what the sockets receive: it's held back
+//! in a queue of the socket until it arrives, whether the socket blocks or
+//! not, and the socket is only ready to be read once it has.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    future::Future,
+    mem::MaybeUninit,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, Mutex, RwLock},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use wasmer_wasix_types::wasi::{Errno, Snapshot0Clockid};
+
+use crate::{syscalls::platform_clock_time_get, VirtualTaskManager};
+
+/// How much traffic a rate lets through at once, after the socket was
+/// idle, as the time it takes to send it at that rate.
+const BURST: Duration = Duration::from_millis(100);
+
+/// How long a segment of a stream which is lost takes to be sent again.
+const RETRANSMIT_DELAY: Duration = Duration::from_millis(200);
+
+/// The shape given to traffic, which is left as it is by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TrafficShape {
+    rate: Option<u64>,
+    latency: Duration,
+    loss: f64,
+}
+
+impl TrafficShape {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits what a socket sends, and what it receives, to `bytes_per_sec`
+    /// bytes per second each.
+    pub fn rate(mut self, bytes_per_sec: u64) -> Self {
+        self.rate = Some(bytes_per_sec.max(1));
+        self
+    }
+
+    /// Delays what a socket receives by `latency`, as well as the
+    /// connections it makes.
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Loses the given ratio of what a socket receives, between 0 and 1. A
+    /// lost datagram is never received, while a lost segment of a stream is
+    /// received after a delay, as TCP would send it again, and holds back
+    /// what follows it.
+    pub fn loss(mut self, ratio: f64) -> Self {
+        self.loss = ratio.clamp(0., 1.);
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+struct Rules {
+    default: Option<TrafficShape>,
+    processes: HashMap<u32, TrafficShape>,
+    ports: HashMap<u16, TrafficShape>,
+}
+
+/// The shapes of the traffic of the processes of a control plane, see
+/// [`ControlPlaneConfig::shaping`](crate::os::task::control_plane::ControlPlaneConfig::shaping).
+///
+/// A socket takes the shape given to its local port, or else the one of
+/// its process, or else the default one. The shapes can be changed while
+/// the processes run, the sockets take them from their next send or
+/// receive.
+#[derive(Debug, Clone, Default)]
+pub struct TrafficShaping {
+    rules: Arc<RwLock<Rules>>,
+}
+
+impl TrafficShaping {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Shapes the traffic of the sockets which aren't given another shape.
+    pub fn set_default(&self, shape: Option<TrafficShape>) {
+        self.rules.write().unwrap().default = shape;
+    }
+
+    /// Shapes the traffic of the sockets of process `pid`.
+    pub fn set_process(&self, pid: u32, shape: Option<TrafficShape>) {
+        let processes = &mut self.rules.write().unwrap().processes;
+        match shape {
+            Some(shape) => processes.insert(pid, shape),
+            None => processes.remove(&pid),
+        };
+    }
+
+    /// Shapes the traffic of the sockets bound to the local port `port`,
+    /// whichever process they belong to.
+    pub fn set_port(&self, port: u16, shape: Option<TrafficShape>) {
+        let ports = &mut self.rules.write().unwrap().ports;
+        match shape {
+            Some(shape) => ports.insert(port, shape),
+            None => ports.remove(&port),
+        };
+    }
+
+    fn shape(&self, pid: u32, port: Option<u16>) -> Option<TrafficShape> {
+        let rules = self.rules.read().unwrap();
+        port.and_then(|port| rules.ports.get(&port))
+            .or_else(|| rules.processes.get(&pid))
+            .or(rules.default.as_ref())
+            .copied()
+    }
+}
+
+/// What went through a socket recently in one direction, as a bucket of
+/// the bytes it may still pass, which goes below zero when it passed more.
+#[derive(Debug, Default)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Option<Duration>,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Duration, rate: u64) {
+        let burst = (rate as f64 * BURST.as_secs_f64()).max(1.);
+        self.tokens = match self.refilled_at {
+            Some(at) => {
+                (self.tokens + now.saturating_sub(at).as_secs_f64() * rate as f64).min(burst)
+            }
+            None => burst,
+        };
+        self.refilled_at = Some(now);
+    }
+
+    /// How long the socket waits before passing anything more.
+    fn delay(&self, rate: u64) -> Duration {
+        match self.tokens < 0. {
+            true => Duration::from_secs_f64(-self.tokens / rate as f64),
+            false => Duration::ZERO,
+        }
+    }
+}
+
+/// What a socket received, held back until it arrives.
+#[derive(Debug)]
+struct Delayed {
+    at: Duration,
+    data: Vec<u8>,
+    from: Option<SocketAddr>,
+}
+
+/// What a socket received and hasn't delivered yet, in the order it's
+/// delivered in.
+#[derive(Debug, Default)]
+struct Inbox {
+    entries: VecDeque<Delayed>,
+    /// Whether the socket is a stream, whose end is an empty entry which
+    /// stays at the back
+    stream: bool,
+    /// Whether the end of the stream was received
+    ended: bool,
+}
+
+impl Inbox {
+    /// Holds back what the socket received until `at`. What a stream
+    /// receives can't overtake what it received before.
+    fn push(&mut self, mut at: Duration, data: Vec<u8>, from: Option<SocketAddr>) {
+        if self.stream {
+            if let Some(back) = self.entries.back() {
+                at = at.max(back.at);
+            }
+            self.ended = data.is_empty();
+        }
+        self.entries.push_back(Delayed { at, data, from });
+    }
+
+    /// When the first entry arrives.
+    fn next_arrival(&self) -> Option<Duration> {
+        self.entries.front().map(|entry| entry.at)
+    }
+
+    /// How many bytes arrived by `now`, for a stream, or the length of the
+    /// datagram which did.
+    fn due(&self, now: Duration) -> Option<usize> {
+        let mut arrived = self.entries.iter().take_while(|entry| entry.at <= now);
+        match self.stream {
+            true => {
+                let first = arrived.next()?;
+                Some(first.data.len() + arrived.map(|entry| entry.data.len()).sum::<usize>())
+            }
+            false => arrived.next().map(|entry| entry.data.len()),
+        }
+    }
+
+    /// Delivers what arrived by `now` into `buf`, leaving it in the inbox
+    /// when `peek` is set. A stream delivers as much as it can, up to its
+    /// end, while a datagram which doesn't fit is truncated.
+    fn deliver(
+        &mut self,
+        now: Duration,
+        buf: &mut [MaybeUninit<u8>],
+        peek: bool,
+    ) -> Option<(usize, Option<SocketAddr>)> {
+        let front = self.entries.front()?;
+        if front.at > now {
+            return None;
+        }
+        let from = front.from;
+        if !self.stream {
+            let amt = copy(&front.data, buf);
+            if !peek {
+                self.entries.pop_front();
+            }
+            return Some((amt, from));
+        }
+
+        // The end of the stream stays to be read again
+        let mut amt = 0;
+        let mut index = 0;
+        while let Some(entry) = self.entries.get_mut(index) {
+            if entry.at > now || entry.data.is_empty() || amt == buf.len() {
+                break;
+            }
+            let copied = copy(&entry.data, &mut buf[amt..]);
+            amt += copied;
+            if peek {
+                index += 1;
+            } else if copied < entry.data.len() {
+                entry.data.drain(..copied);
+            } else {
+                self.entries.pop_front();
+            }
+        }
+        Some((amt, from))
+    }
+}
+
+/// Copies as much of `data` as fits into `buf`.
+fn copy(data: &[u8], buf: &mut [MaybeUninit<u8>]) -> usize {
+    let amt = data.len().min(buf.len());
+    for (dst, src) in buf.iter_mut().zip(&data[..amt]) {
+        dst.write(*src);
+    }
+    amt
+}
+
+/// Wakes the poller of a socket once the first entry of its inbox arrives.
+struct Timer {
+    at: Duration,
+    sleep: Pin<Box<dyn Future<Output = ()> + Send + Sync + 'static>>,
+}
+
+impl fmt::Debug for Timer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Timer").field("at", &self.at).finish()
+    }
+}
+
+#[derive(Debug)]
+struct ShaperState {
+    sent: Bucket,
+    received: Bucket,
+    inbox: Inbox,
+    timer: Option<Timer>,
+    /// The state of the generator picking what's lost
+    seed: u64,
+}
+
+/// Shapes the traffic of a socket of a process.
+///
+/// Its clones share what went through the socket, [`SocketShaper::renew`]
+/// makes one for another socket.
+#[derive(Debug, Clone)]
+pub(crate) struct SocketShaper {
+    shaping: TrafficShaping,
+    pid: u32,
+    tasks: Arc<dyn VirtualTaskManager>,
+    state: Arc<Mutex<ShaperState>>,
+}
+
+impl SocketShaper {
+    pub fn new(shaping: TrafficShaping, pid: u32, tasks: Arc<dyn VirtualTaskManager>) -> Self {
+        Self {
+            shaping,
+            pid,
+            tasks,
+            state: Arc::new(Mutex::new(ShaperState::new())),
+        }
+    }
+
+    /// A shaper for another socket of the same process.
+    pub fn renew(&self) -> Self {
+        Self::new(self.shaping.clone(), self.pid, self.tasks.clone())
+    }
+
+    /// Waits until the socket bound to `port` may send.
+    pub async fn before_send(
+        &self,
+        tasks: &dyn VirtualTaskManager,
+        port: Option<u16>,
+        nonblocking: bool,
+    ) -> Result<(), Errno> {
+        let Some(TrafficShape {
+            rate: Some(rate), ..
+        }) = self.shaping.shape(self.pid, port)
+        else {
+            return Ok(());
+        };
+        let delay = {
+            let mut state = self.state.lock().unwrap();
+            state.sent.refill(now(), rate);
+            state.sent.delay(rate)
+        };
+        if !delay.is_zero() {
+            if nonblocking {
+                return Err(Errno::Again);
+            }
+            tasks.sleep_now(delay).await;
+        }
+        Ok(())
+    }
+
+    /// Counts the bytes the socket bound to `port` sent.
+    pub fn sent(&self, port: Option<u16>, amt: usize) {
+        if let Some(TrafficShape {
+            rate: Some(rate), ..
+        }) = self.shaping.shape(self.pid, port)
+        {
+            let mut state = self.state.lock().unwrap();
+            state.sent.refill(now(), rate);
+            state.sent.tokens -= amt as f64;
+        }
+    }
+
+    /// Waits until the socket bound to `port` may receive.
+    pub async fn before_recv(
+        &self,
+        tasks: &dyn VirtualTaskManager,
+        port: Option<u16>,
+        nonblocking: bool,
+    ) -> Result<(), Errno> {
+        let Some(TrafficShape {
+            rate: Some(rate), ..
+        }) = self.shaping.shape(self.pid, port)
+        else {
+            return Ok(());
+        };
+        let delay = {
+            let mut state = self.state.lock().unwrap();
+            state.received.refill(now(), rate);
+            state.received.delay(rate)
+        };
+        if !delay.is_zero() {
+            if nonblocking {
+                return Err(Errno::Again);
+            }
+            tasks.sleep_now(delay).await;
+        }
+        Ok(())
+    }
+
+    /// Counts the bytes the socket bound to `port` received.
+    pub fn received(&self, port: Option<u16>, amt: usize) {
+        if let Some(TrafficShape {
+            rate: Some(rate), ..
+        }) = self.shaping.shape(self.pid, port)
+        {
+            let mut state = self.state.lock().unwrap();
+            state.received.refill(now(), rate);
+            state.received.tokens -= amt as f64;
+        }
+    }
+
+    /// Whether what the socket bound to `port` receives goes through its
+    /// inbox, because it's delayed or lost, or was while the inbox still
+    /// holds some.
+    pub fn delays(&self, port: Option<u16>) -> bool {
+        let delays = self
+            .shaping
+            .shape(self.pid, port)
+            .is_some_and(|shape| !shape.latency.is_zero() || shape.loss > 0.);
+        delays || !self.state.lock().unwrap().inbox.entries.is_empty()
+    }
+
+    /// Whether the end of the stream was received, after which nothing
+    /// more is.
+    pub fn ended(&self) -> bool {
+        self.state.lock().unwrap().inbox.ended
+    }
+
+    /// Holds back what the socket bound to `port` received until it
+    /// arrives, or drops it when it's a lost datagram. An empty `data` is
+    /// the end of a stream.
+    pub fn arrived(
+        &self,
+        port: Option<u16>,
+        stream: bool,
+        data: Vec<u8>,
+        from: Option<SocketAddr>,
+    ) {
+        let shape = self.shaping.shape(self.pid, port).unwrap_or_default();
+        let mut state = self.state.lock().unwrap();
+        let lost = !data.is_empty() && state.random() < shape.loss;
+        if lost && !stream {
+            return;
+        }
+        let mut at = now() + shape.latency;
+        if lost {
+            at += RETRANSMIT_DELAY;
+        }
+        state.inbox.stream = stream;
+        state.inbox.push(at, data, from);
+    }
+
+    /// Delivers what arrived into `buf`, along with where it comes from,
+    /// leaving it to be delivered again when `peek` is set.
+    pub fn deliver(
+        &self,
+        buf: &mut [MaybeUninit<u8>],
+        peek: bool,
+    ) -> Option<(usize, Option<SocketAddr>)> {
+        self.state.lock().unwrap().inbox.deliver(now(), buf, peek)
+    }
+
+    /// Polls for something to arrive, and how much did.
+    pub fn poll_arrival(&self, cx: &mut Context<'_>) -> Poll<usize> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let now = now();
+            if let Some(amt) = state.inbox.due(now) {
+                state.timer.take();
+                return Poll::Ready(amt);
+            }
+            let Some(at) = state.inbox.next_arrival() else {
+                state.timer.take();
+                return Poll::Pending;
+            };
+            if state.timer.as_ref().map(|timer| timer.at) != Some(at) {
+                let sleep = self.tasks.sleep_now(at.saturating_sub(now));
+                state.timer = Some(Timer { at, sleep });
+            }
+            let timer = state.timer.as_mut().unwrap();
+            match timer.sleep.as_mut().poll(cx) {
+                // A timer which is a little early is set again
+                Poll::Ready(()) => {
+                    state.timer.take();
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    /// The latency of the socket bound to `port`, which delays the
+    /// connections it makes.
+    pub fn connect_delay(&self, port: Option<u16>) -> Duration {
+        self.shaping
+            .shape(self.pid, port)
+            .map_or(Duration::ZERO, |shape| shape.latency)
+    }
+}
+
+impl ShaperState {
+    fn new() -> Self {
+        let mut seed = [0; 8];
+        getrandom::getrandom(&mut seed).ok();
+        Self {
+            sent: Bucket::default(),
+            received: Bucket::default(),
+            inbox: Inbox::default(),
+            timer: None,
+            // The generator never leaves zero
+            seed: u64::from_le_bytes(seed) | 1,
+        }
+    }
+
+    /// A number between 0 and 1, from a xorshift generator.
+    fn random(&mut self) -> f64 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        (self.seed >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// The monotonic time.
+fn now() -> Duration {
+    let nanos = platform_clock_time_get(Snapshot0Clockid::Monotonic, 1).unwrap_or(0);
+    Duration::from_nanos(nanos as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sockets_take_the_most_specific_shape() {
+        let shaping = TrafficShaping::new();
+        let slow = TrafficShape::new().rate(1_000);
+        let far = TrafficShape::new().latency(Duration::from_millis(50));
+        let lossy = TrafficShape::new().loss(0.5);
+        assert_eq!(shaping.shape(1, Some(80)), None);
+
+        shaping.set_default(Some(slow));
+        shaping.set_process(1, Some(far));
+        shaping.set_port(80, Some(lossy));
+        assert_eq!(shaping.shape(1, Some(80)), Some(lossy));
+        assert_eq!(shaping.shape(1, Some(81)), Some(far));
+        assert_eq!(shaping.shape(1, None), Some(far));
+        assert_eq!(shaping.shape(2, Some(81)), Some(slow));
+
+        shaping.set_port(80, None);
+        assert_eq!(shaping.shape(1, Some(80)), Some(far));
+    }
+
+    #[test]
+    fn the_rate_is_enforced_once_the_burst_is_spent() {
+        let mut bucket = Bucket::default();
+        let start = Duration::from_secs(10);
+        bucket.refill(start, 10_000);
+        // A tenth of a second of traffic passes right away
+        assert_eq!(bucket.delay(10_000), Duration::ZERO);
+        bucket.tokens -= 3_000.;
+        assert_eq!(bucket.delay(10_000).as_millis(), 200);
+
+        // Which is paid back over time
+        bucket.refill(start + Duration::from_millis(150), 10_000);
+        let delay = bucket.delay(10_000).as_secs_f64();
+        assert!((delay - 0.05).abs() < 1e-6, "{delay}");
+        bucket.refill(start + Duration::from_secs(5), 10_000);
+        assert_eq!(bucket.tokens, 1_000.);
+    }
+
+    #[test]
+    fn the_loss_ratio_is_followed() {
+        let mut state = ShaperState::new();
+        let lost = (0..10_000).filter(|_| state.random() < 0.3).count();
+        assert!((2_500..3_500).contains(&lost), "{lost}");
+    }
+
+    fn received(buf: &[MaybeUninit<u8>], amt: usize) -> Vec<u8> {
+        buf[..amt]
+            .iter()
+            .map(|byte| unsafe { byte.assume_init() })
+            .collect()
+    }
+
+    #[test]
+    fn streams_are_delivered_in_order_once_they_arrive() {
+        let mut inbox = Inbox {
+            stream: true,
+            ..Default::default()
+        };
+        let ms = Duration::from_millis;
+        inbox.push(ms(300), b"lost ".to_vec(), None);
+        // What follows a lost segment is held back by it
+        inbox.push(ms(100), b"then".to_vec(), None);
+        inbox.push(ms(100), Vec::new(), None);
+        assert!(inbox.ended);
+
+        let mut buf = [MaybeUninit::uninit(); 7];
+        assert_eq!(inbox.due(ms(200)), None);
+        assert_eq!(inbox.deliver(ms(200), &mut buf, false), None);
+        assert_eq!(inbox.due(ms(300)), Some(9));
+
+        // The bytes which don't fit stay for the next read
+        assert_eq!(inbox.deliver(ms(300), &mut buf, true), Some((7, None)));
+        assert_eq!(received(&buf, 7), b"lost th");
+        assert_eq!(inbox.deliver(ms(300), &mut buf, false), Some((7, None)));
+        assert_eq!(inbox.deliver(ms(300), &mut buf, false), Some((2, None)));
+        assert_eq!(received(&buf, 2), b"en");
+
+        // The end of the stream is read as often as it's asked for
+        assert_eq!(inbox.deliver(ms(300), &mut buf, false), Some((0, None)));
+        assert_eq!(inbox.deliver(ms(300), &mut buf, false), Some((0, None)));
+    }
+
+    #[test]
+    fn datagrams_are_delivered_whole_once_they_arrive() {
+        let mut inbox = Inbox::default();
+        let ms = Duration::from_millis;
+        let (first, second) = ("10.0.0.1:80".parse().ok(), "10.0.0.2:80".parse().ok());
+        inbox.push(ms(100), b"first".to_vec(), first);
+        inbox.push(ms(200), b"second".to_vec(), second);
+
+        let mut buf = [MaybeUninit::uninit(); 4];
+        assert_eq!(inbox.deliver(ms(50), &mut buf, false), None);
+        assert_eq!(inbox.deliver(ms(150), &mut buf, true), Some((4, first)));
+        assert_eq!(inbox.deliver(ms(150), &mut buf, false), Some((4, first)));
+        assert_eq!(received(&buf, 4), b"firs");
+        assert_eq!(inbox.deliver(ms(150), &mut buf, false), None);
+        assert_eq!(inbox.due(ms(200)), Some(6));
+        assert_eq!(inbox.deliver(ms(200), &mut buf, false), Some((4, second)));
+        assert_eq!(inbox.next_arrival(), None);
+    }
+}
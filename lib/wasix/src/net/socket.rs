@@ -2,7 +2,8 @@ use std::{
     future::Future,
     io,
     mem::MaybeUninit,
-    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr},
+    path::PathBuf,
     pin::Pin,
     sync::{Arc, RwLock},
     task::{Context, Poll},
@@ -14,13 +15,24 @@ use derivative::Derivative;
 use serde_derive::{Deserialize, Serialize};
 use virtual_mio::InterestHandler;
 use virtual_net::{
-    net_error_into_io_err, NetworkError, VirtualIcmpSocket, VirtualNetworking, VirtualRawSocket,
-    VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+    net_error_into_io_err, DynVirtualNetworking, NetworkError, TcpKeepalive, VirtualIcmpSocket,
+    VirtualNetworking, VirtualRawSocket, VirtualSocket, VirtualTcpListener, VirtualTcpSocket,
+    VirtualUdpSocket,
 };
 use wasmer_types::MemorySize;
 use wasmer_wasix_types::wasi::{Addressfamily, Errno, Rights, SockProto, Sockoption, Socktype};
 
-use crate::{net::net_error_into_wasi_err, VirtualTaskManager};
+use crate::{
+    fs::Fd,
+    net::{
+        connect::PendingConnect,
+        net_error_into_wasi_err,
+        shaping::SocketShaper,
+        unix::{UnixChannel, UnixDatagram, UnixSocketState, UnixSockets, UNIX_SOCKET_ADDR},
+    },
+    utils::map_io_err,
+    VirtualTaskManager,
+};
 
 #[derive(Debug)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
@@ -115,6 +127,9 @@ pub enum WasiSocketOption {
     MulticastTtlV4,
     Type,
     Proto,
+    KeepAliveIdle,
+    KeepAliveInterval,
+    KeepAliveCount,
 }
 
 impl From<Sockoption> for WasiSocketOption {
@@ -148,6 +163,9 @@ impl From<Sockoption> for WasiSocketOption {
             Sockoption::MulticastTtlV4 => MulticastTtlV4,
             Sockoption::Type => Type,
             Sockoption::Proto => Proto,
+            Sockoption::KeepAliveIdle => KeepAliveIdle,
+            Sockoption::KeepAliveInterval => KeepAliveInterval,
+            Sockoption::KeepAliveCount => KeepAliveCount,
         }
     }
 }
@@ -169,12 +187,82 @@ pub enum TimeType {
     ConnectTimeout,
     BindTimeout,
     Linger,
+    KeepAliveIdle,
+    KeepAliveInterval,
+}
+
+/// The time to live of the packets of a socket which doesn't say otherwise
+const DEFAULT_TTL: u32 = 64;
+/// How many keep-alive probes are sent by default, like on Linux
+const DEFAULT_KEEPALIVE_COUNT: u32 = 9;
+/// The most a socket whose shaper holds back what it receives takes from
+/// the network at once, the size of the largest datagram
+const MAX_DATAGRAM: usize = 65_536;
+
+/// The options of a socket which outlive its kind, they are kept when it's
+/// bound, listens or connects and passed on to the connections it accepts.
+/// The values of the options the socket doesn't support are also kept here,
+/// so they read back as they were set.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SocketOptions {
+    pub reuse_port: bool,
+    pub reuse_addr: bool,
+    pub no_delay: Option<bool>,
+    pub keep_alive: Option<bool>,
+    pub keepalive: TcpKeepalive,
+    pub linger: Option<Duration>,
+    pub ttl: Option<u32>,
+    pub send_buf_size: Option<usize>,
+    pub recv_buf_size: Option<usize>,
+    pub read_timeout: Option<Duration>,
+    pub write_timeout: Option<Duration>,
+    /// The shaping of the traffic of the socket, which a socket accepted or
+    /// bound, listening or connected from it starts afresh with
+    pub shaper: Option<SocketShaper>,
+}
+
+/// Ignores the options the socket doesn't support, they are emulated.
+fn emulate_unsupported(res: Result<(), NetworkError>) -> Result<(), Errno> {
+    match res {
+        Err(NetworkError::Unsupported) => Ok(()),
+        res => res.map_err(net_error_into_wasi_err),
+    }
 }
 
+/// Reads an option, or its emulated value if the socket doesn't support it.
+fn or_emulated<T>(res: Result<T, NetworkError>, emulated: T) -> Result<T, Errno> {
+    match res {
+        Err(NetworkError::Unsupported) => Ok(emulated),
+        res => res.map_err(net_error_into_wasi_err),
+    }
+}
+
+/// A connection accepted by a listening socket, with the state of a socket of
+/// the `Unix` family.
+pub type AcceptedSocket = (
+    Box<dyn VirtualTcpSocket + Sync>,
+    SocketAddr,
+    Option<UnixSocketState>,
+);
+
 #[derive(Debug)]
 //#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 pub(crate) struct InodeSocketProtected {
     pub kind: InodeSocketKind,
+    /// The paths of a socket of the `Unix` family, which has no IP address,
+    /// and the handle passing its file descriptors
+    pub unix: Option<UnixSocketState>,
+    pub options: SocketOptions,
+    /// The directions of the stream shut down on this side, which are
+    /// enforced here as well for the transports which can't shut down the
+    /// reader themselves
+    pub read_shutdown: bool,
+    pub write_shutdown: bool,
+    /// The connection started by a non-blocking `connect`
+    pub connecting: Option<PendingConnect>,
+    /// The error of the last connection which failed in the background,
+    /// reported through `SO_ERROR`
+    pub error: Option<NetworkError>,
 }
 
 #[derive(Debug)]
@@ -191,7 +279,30 @@ pub struct InodeSocket {
 
 impl InodeSocket {
     pub fn new(kind: InodeSocketKind) -> Self {
-        let protected = InodeSocketProtected { kind };
+        let unix = match &kind {
+            InodeSocketKind::PreSocket { props, .. } if props.family == Addressfamily::Unix => {
+                Some(UnixSocketState::default())
+            }
+            _ => None,
+        };
+        Self::with_unix_state(kind, unix)
+    }
+
+    /// Creates a socket of the `Unix` family.
+    pub fn new_unix(kind: InodeSocketKind, unix: UnixSocketState) -> Self {
+        Self::with_unix_state(kind, Some(unix))
+    }
+
+    fn with_unix_state(kind: InodeSocketKind, unix: Option<UnixSocketState>) -> Self {
+        let protected = InodeSocketProtected {
+            kind,
+            unix,
+            options: SocketOptions::default(),
+            read_shutdown: false,
+            write_shutdown: false,
+            connecting: None,
+            error: None,
+        };
         Self {
             inner: Arc::new(InodeSocketInner {
                 protected: RwLock::new(protected),
@@ -199,6 +310,70 @@ impl InodeSocket {
         }
     }
 
+    pub fn is_unix(&self) -> bool {
+        self.inner.protected.read().unwrap().unix.is_some()
+    }
+
+    /// The paths of the socket when it's of the `Unix` family.
+    pub fn unix_state(&self) -> Option<UnixSocketState> {
+        self.inner.protected.read().unwrap().unix.clone()
+    }
+
+    /// Whether the socket is a connected stream, whose bytes can arrive in
+    /// several pieces.
+    pub fn is_stream(&self) -> bool {
+        matches!(
+            self.inner.protected.read().unwrap().kind,
+            InodeSocketKind::TcpStream { .. }
+        )
+    }
+
+    /// Takes the error of the connection which failed in the background,
+    /// like `SO_ERROR`.
+    pub fn take_error(&self) -> Option<Errno> {
+        let mut inner = self.inner.protected.write().unwrap();
+        inner.finish_connect();
+        inner.error.take().map(net_error_into_wasi_err)
+    }
+
+    /// Takes the options of the socket this one was bound, listening or
+    /// connected from, or accepted by.
+    pub(crate) fn inherit_options(&self, parent: &InodeSocket) {
+        let options = parent.inner.protected.read().unwrap().inheritable_options();
+        self.inner.protected.write().unwrap().apply_options(options);
+    }
+
+    /// Shapes the traffic of the socket, see
+    /// [`TrafficShaping`](crate::net::shaping::TrafficShaping).
+    pub(crate) fn shape(&self, shaper: SocketShaper) {
+        self.inner.protected.write().unwrap().options.shaper = Some(shaper);
+    }
+
+    /// The shaper of the socket, along with the local port it's bound to.
+    fn shaper(&self) -> Option<(SocketShaper, Option<u16>)> {
+        let inner = self.inner.protected.read().unwrap();
+        let shaper = inner.options.shaper.clone()?;
+        Some((shaper, inner.local_port()))
+    }
+
+    /// Sends `fds` along with the next data written to a connected socket
+    /// of the `Unix` family.
+    pub(crate) fn attach_fds(&self, fds: Vec<Fd>) -> Result<(), Errno> {
+        match &self.inner.protected.read().unwrap().unix {
+            Some(unix) => unix.attach_fds(fds).map_err(net_error_into_wasi_err),
+            None => Err(Errno::Inval),
+        }
+    }
+
+    /// The file descriptors received along with the last data read from
+    /// the socket.
+    pub(crate) fn take_received_fds(&self) -> Vec<Fd> {
+        match &self.inner.protected.read().unwrap().unix {
+            Some(unix) => unix.take_received_fds(),
+            None => Vec::new(),
+        }
+    }
+
     pub fn poll_read_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
         let mut inner = self.inner.protected.write().unwrap();
         inner.poll_read_ready(cx)
@@ -221,6 +396,22 @@ impl InodeSocket {
             .flatten()
             .unwrap_or(Duration::from_secs(30));
 
+        // An ICMP socket is bound to the unspecified address when it's
+        // opened, binding it again changes its source address
+        let icmp = matches!(
+            self.inner.protected.read().unwrap().kind,
+            InodeSocketKind::Icmp(_)
+        );
+        if icmp {
+            return tokio::select! {
+                socket = net.bind_icmp(set_addr.ip()) => {
+                    let socket = socket.map_err(net_error_into_wasi_err)?;
+                    Ok(Some(InodeSocket::new(InodeSocketKind::Icmp(socket))))
+                },
+                _ = tasks.sleep_now(timeout) => Err(Errno::Timedout)
+            };
+        }
+
         let socket = {
             let mut inner = self.inner.protected.write().unwrap();
             match &mut inner.kind {
@@ -325,6 +516,191 @@ impl InodeSocket {
         }
     }
 
+    /// Binds a socket of the `Unix` family to `path`, whose file was
+    /// created by the caller. A stream socket starts receiving the
+    /// connections to the path once it listens.
+    pub(crate) fn bind_unix(
+        &self,
+        sockets: &UnixSockets,
+        path: PathBuf,
+    ) -> Result<Option<InodeSocket>, Errno> {
+        let mut inner = self.inner.protected.write().unwrap();
+        let inner = &mut *inner;
+        let (InodeSocketKind::PreSocket { props, .. }, Some(unix)) =
+            (&mut inner.kind, &mut inner.unix)
+        else {
+            return Err(Errno::Inval);
+        };
+        if unix.path.is_some() {
+            return Err(Errno::Inval);
+        }
+
+        match props.ty {
+            Socktype::Stream => {
+                unix.path = Some(path);
+                Ok(None)
+            }
+            Socktype::Dgram => {
+                let mut socket = UnixDatagram::new(None);
+                sockets
+                    .bind_datagram(&path, &socket.inbox())
+                    .map_err(net_error_into_wasi_err)?;
+                if let Some(handler) = props.handler.take() {
+                    socket
+                        .set_handler(handler)
+                        .map_err(net_error_into_wasi_err)?;
+                }
+                let unix = UnixSocketState {
+                    path: Some(path),
+                    peer_path: None,
+                    channel: Some(UnixChannel::Datagram(socket.inbox())),
+                };
+                Ok(Some(InodeSocket::new_unix(
+                    InodeSocketKind::UdpSocket {
+                        socket: Box::new(socket),
+                        peer: None,
+                    },
+                    unix,
+                )))
+            }
+            _ => Err(Errno::Notsup),
+        }
+    }
+
+    /// Listens for the connections to the path a stream socket of the `Unix`
+    /// family is bound to.
+    pub(crate) fn listen_unix(
+        &self,
+        sockets: &UnixSockets,
+        backlog: usize,
+    ) -> Result<Option<InodeSocket>, Errno> {
+        let mut inner = self.inner.protected.write().unwrap();
+        let inner = &mut *inner;
+        let (InodeSocketKind::PreSocket { props, .. }, Some(unix)) = (&mut inner.kind, &inner.unix)
+        else {
+            return Err(Errno::Inval);
+        };
+        if props.ty != Socktype::Stream {
+            return Err(Errno::Notsup);
+        }
+        let Some(path) = unix.path.as_deref() else {
+            tracing::warn!("wasi[?]::sock_listen - failed - path not bound");
+            return Err(Errno::Inval);
+        };
+
+        let mut socket = sockets
+            .listen(path, backlog)
+            .map_err(net_error_into_wasi_err)?;
+        if let Some(handler) = props.handler.take() {
+            socket
+                .set_handler(handler)
+                .map_err(net_error_into_wasi_err)?;
+        }
+        let unix = UnixSocketState {
+            channel: Some(UnixChannel::Listener(socket.clone())),
+            ..unix.clone()
+        };
+        Ok(Some(InodeSocket::new_unix(
+            InodeSocketKind::TcpListener {
+                socket: Box::new(socket),
+                accept_timeout: props.accept_timeout,
+            },
+            unix,
+        )))
+    }
+
+    /// Connects a socket of the `Unix` family to the socket bound to `path`.
+    pub(crate) fn connect_unix(
+        &self,
+        sockets: &UnixSockets,
+        path: PathBuf,
+    ) -> Result<Option<InodeSocket>, Errno> {
+        let mut inner = self.inner.protected.write().unwrap();
+        let inner = &mut *inner;
+        let Some(unix) = inner.unix.as_mut() else {
+            return Err(Errno::Inval);
+        };
+
+        match &mut inner.kind {
+            InodeSocketKind::PreSocket { props, .. } => {
+                let unix = UnixSocketState {
+                    peer_path: Some(path.clone()),
+                    ..unix.clone()
+                };
+                let handler = props.handler.take();
+                match props.ty {
+                    Socktype::Stream => {
+                        let mut socket = sockets.connect(&path).map_err(net_error_into_wasi_err)?;
+                        if let Some(size) = props.send_buf_size {
+                            socket.set_send_buf_size(size).ok();
+                        }
+                        if let Some(size) = props.recv_buf_size {
+                            socket.set_recv_buf_size(size).ok();
+                        }
+                        if let Some(handler) = handler {
+                            socket
+                                .set_handler(handler)
+                                .map_err(net_error_into_wasi_err)?;
+                        }
+                        let unix = UnixSocketState {
+                            channel: Some(UnixChannel::Stream(socket.clone())),
+                            ..unix
+                        };
+                        Ok(Some(InodeSocket::new_unix(
+                            InodeSocketKind::TcpStream {
+                                socket: Box::new(socket),
+                                write_timeout: props.write_timeout,
+                                read_timeout: props.read_timeout,
+                            },
+                            unix,
+                        )))
+                    }
+                    Socktype::Dgram => {
+                        let mut socket = UnixDatagram::new(None);
+                        sockets
+                            .connect_datagram(&mut socket, &path)
+                            .map_err(net_error_into_wasi_err)?;
+                        if let Some(handler) = handler {
+                            socket
+                                .set_handler(handler)
+                                .map_err(net_error_into_wasi_err)?;
+                        }
+                        let unix = UnixSocketState {
+                            channel: Some(UnixChannel::Datagram(socket.inbox())),
+                            ..unix
+                        };
+                        Ok(Some(InodeSocket::new_unix(
+                            InodeSocketKind::UdpSocket {
+                                socket: Box::new(socket),
+                                peer: Some(UNIX_SOCKET_ADDR),
+                            },
+                            unix,
+                        )))
+                    }
+                    _ => Err(Errno::Notsup),
+                }
+            }
+            // A bound datagram socket keeps receiving what is sent to its
+            // path, its handler is kept along with the queue
+            InodeSocketKind::UdpSocket { socket, peer } => {
+                let inbox = match &unix.channel {
+                    Some(UnixChannel::Datagram(inbox)) => Some(inbox.clone()),
+                    _ => None,
+                };
+                let mut connected = UnixDatagram::new(inbox);
+                sockets
+                    .connect_datagram(&mut connected, &path)
+                    .map_err(net_error_into_wasi_err)?;
+                *socket = Box::new(connected);
+                *peer = Some(UNIX_SOCKET_ADDR);
+                unix.peer_path = Some(path);
+                Ok(None)
+            }
+            InodeSocketKind::TcpStream { .. } => Err(Errno::Isconn),
+            _ => Err(Errno::Inval),
+        }
+    }
+
     pub async fn listen(
         &self,
         tasks: &dyn VirtualTaskManager,
@@ -426,7 +802,7 @@ impl InodeSocket {
         tasks: &dyn VirtualTaskManager,
         nonblocking: bool,
         timeout: Option<Duration>,
-    ) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr), Errno> {
+    ) -> Result<AcceptedSocket, Errno> {
         struct SocketAccepter<'a> {
             sock: &'a InodeSocket,
             nonblocking: bool,
@@ -441,16 +817,17 @@ impl InodeSocket {
             }
         }
         impl<'a> Future for SocketAccepter<'a> {
-            type Output = Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr), Errno>;
+            type Output = Result<AcceptedSocket, Errno>;
             fn poll(
                 mut self: Pin<&mut Self>,
                 cx: &mut std::task::Context<'_>,
             ) -> std::task::Poll<Self::Output> {
                 loop {
                     let mut inner = self.sock.inner.protected.write().unwrap();
-                    return match &mut inner.kind {
-                        InodeSocketKind::TcpListener { socket, .. } => match socket.try_accept() {
-                            Ok((child, addr)) => Poll::Ready(Ok((child, addr))),
+                    let InodeSocketProtected { kind, unix, .. } = &mut *inner;
+                    return match kind {
+                        InodeSocketKind::TcpListener { socket, .. } => match accept(socket, unix) {
+                            Ok(accepted) => Poll::Ready(Ok(accepted)),
                             Err(NetworkError::WouldBlock) if self.nonblocking => {
                                 Poll::Ready(Err(Errno::Again))
                             }
@@ -473,6 +850,30 @@ impl InodeSocket {
             }
         }
 
+        fn accept(
+            socket: &mut Box<dyn VirtualTcpListener + Sync>,
+            unix: &Option<UnixSocketState>,
+        ) -> Result<AcceptedSocket, NetworkError> {
+            let Some(UnixSocketState {
+                path,
+                channel: Some(UnixChannel::Listener(listener)),
+                ..
+            }) = unix
+            else {
+                let (child, addr) = socket.try_accept()?;
+                return Ok((child, addr, None));
+            };
+            // The connections accepted by a Unix socket are bound to its
+            // path, and keep a handle to pass file descriptors
+            let stream = listener.try_accept_stream()?;
+            let unix = UnixSocketState {
+                path: path.clone(),
+                peer_path: None,
+                channel: Some(UnixChannel::Stream(stream.clone())),
+            };
+            Ok((Box::new(stream), UNIX_SOCKET_ADDR, Some(unix)))
+        }
+
         let acceptor = SocketAccepter {
             sock: self,
             nonblocking,
@@ -504,10 +905,13 @@ impl InodeSocket {
         Ok(())
     }
 
+    /// Connects the socket to `peer`. On a non-blocking socket the
+    /// connection is established in the background, `Errno::Inprogress` is
+    /// returned and the socket becomes writable once it's done.
     pub async fn connect(
         &mut self,
         tasks: &dyn VirtualTaskManager,
-        net: &dyn VirtualNetworking,
+        net: &DynVirtualNetworking,
         peer: SocketAddr,
         timeout: Option<std::time::Duration>,
         nonblocking: bool,
@@ -516,10 +920,24 @@ impl InodeSocket {
         let new_read_timeout;
 
         let timeout = timeout.unwrap_or(Duration::from_secs(30));
+        let delay = self
+            .shaper()
+            .map(|(shaper, port)| shaper.connect_delay(port))
+            .filter(|delay| !delay.is_zero())
+            .map(|delay| tasks.sleep_now(delay));
 
         let handler;
         let connect = {
             let mut inner = self.inner.protected.write().unwrap();
+            inner.finish_connect();
+            if inner.connecting.is_some() {
+                return Err(Errno::Already);
+            }
+            // Like on Linux, the connection which failed in the background
+            // is reported by the next attempt
+            if let Some(err) = inner.error.take() {
+                return Err(net_error_into_wasi_err(err));
+            }
             match &mut inner.kind {
                 InodeSocketKind::PreSocket { props, addr, .. } => {
                     handler = props.handler.take();
@@ -540,7 +958,11 @@ impl InodeSocket {
                                     SocketAddr::new(ip, 0)
                                 }
                             };
-                            Box::pin(async move {
+                            let net = net.clone();
+                            async move {
+                                if let Some(delay) = delay {
+                                    delay.await;
+                                }
                                 let mut ret = net.connect_tcp(addr, peer).await?;
                                 if let Some(no_delay) = no_delay {
                                     ret.set_nodelay(no_delay).ok();
@@ -551,11 +973,9 @@ impl InodeSocket {
                                 if let Some(dont_route) = dont_route {
                                     ret.set_dontroute(dont_route).ok();
                                 }
-                                if !nonblocking {
-                                    futures::future::poll_fn(|cx| ret.poll_write_ready(cx)).await?;
-                                }
+                                futures::future::poll_fn(|cx| ret.poll_write_ready(cx)).await?;
                                 Ok(ret)
-                            })
+                            }
                         }
                         Socktype::Dgram => return Err(Errno::Inval),
                         _ => return Err(Errno::Notsup),
@@ -571,10 +991,24 @@ impl InodeSocket {
                     *peer_addr = peer;
                     return Ok(None);
                 }
+                InodeSocketKind::TcpStream { .. } => return Err(Errno::Isconn),
                 _ => return Err(Errno::Notsup),
             }
         };
 
+        if nonblocking {
+            let sleep = tasks.sleep_now(timeout);
+            let connect = async move {
+                tokio::select! {
+                    res = connect => res,
+                    _ = sleep => Err(NetworkError::TimedOut)
+                }
+            };
+            let mut inner = self.inner.protected.write().unwrap();
+            inner.connecting = Some(PendingConnect::start(connect, handler));
+            return Err(Errno::Inprogress);
+        }
+
         let mut socket = tokio::select! {
             res = connect => res.map_err(net_error_into_wasi_err)?,
             _ = tasks.sleep_now(timeout) => return Err(Errno::Timedout)
@@ -596,8 +1030,10 @@ impl InodeSocket {
     }
 
     pub fn status(&self) -> Result<WasiSocketStatus, Errno> {
-        let inner = self.inner.protected.read().unwrap();
+        let mut inner = self.inner.protected.write().unwrap();
+        inner.finish_connect();
         Ok(match &inner.kind {
+            InodeSocketKind::PreSocket { .. } if inner.error.is_some() => WasiSocketStatus::Failed,
             InodeSocketKind::PreSocket { .. } => WasiSocketStatus::Opening,
             InodeSocketKind::TcpListener { .. } => WasiSocketStatus::Opened,
             InodeSocketKind::TcpStream { .. } => WasiSocketStatus::Opened,
@@ -645,7 +1081,8 @@ impl InodeSocket {
     }
 
     pub fn addr_peer(&self) -> Result<SocketAddr, Errno> {
-        let inner = self.inner.protected.read().unwrap();
+        let mut inner = self.inner.protected.write().unwrap();
+        inner.finish_connect();
         Ok(match &inner.kind {
             InodeSocketKind::PreSocket { props, .. } => SocketAddr::new(
                 match props.family {
@@ -683,7 +1120,8 @@ impl InodeSocket {
 
     pub fn set_opt_flag(&mut self, option: WasiSocketOption, val: bool) -> Result<(), Errno> {
         let mut inner = self.inner.protected.write().unwrap();
-        match &mut inner.kind {
+        let InodeSocketProtected { kind, options, .. } = &mut *inner;
+        match kind {
             InodeSocketKind::PreSocket { props, .. }
             | InodeSocketKind::RemoteSocket { props, .. } => {
                 match option {
@@ -704,17 +1142,28 @@ impl InodeSocket {
             },
             InodeSocketKind::TcpStream { socket, .. } => match option {
                 WasiSocketOption::NoDelay => {
-                    socket.set_nodelay(val).map_err(net_error_into_wasi_err)?
+                    emulate_unsupported(socket.set_nodelay(val))?;
+                    options.no_delay = Some(val);
                 }
                 WasiSocketOption::KeepAlive => {
-                    socket.set_keepalive(val).map_err(net_error_into_wasi_err)?
+                    emulate_unsupported(socket.set_keepalive(val))?;
+                    options.keep_alive = Some(val);
                 }
                 WasiSocketOption::DontRoute => {
                     socket.set_dontroute(val).map_err(net_error_into_wasi_err)?
                 }
+                WasiSocketOption::ReusePort => options.reuse_port = val,
+                WasiSocketOption::ReuseAddr => options.reuse_addr = val,
+                _ => return Err(Errno::Inval),
+            },
+            // The options of the connections it accepts
+            InodeSocketKind::TcpListener { .. } => match option {
+                WasiSocketOption::NoDelay => options.no_delay = Some(val),
+                WasiSocketOption::KeepAlive => options.keep_alive = Some(val),
+                WasiSocketOption::ReusePort => options.reuse_port = val,
+                WasiSocketOption::ReuseAddr => options.reuse_addr = val,
                 _ => return Err(Errno::Inval),
             },
-            InodeSocketKind::TcpListener { .. } => return Err(Errno::Inval),
             InodeSocketKind::UdpSocket { socket, .. } => match option {
                 WasiSocketOption::Broadcast => {
                     socket.set_broadcast(val).map_err(net_error_into_wasi_err)?
@@ -725,6 +1174,8 @@ impl InodeSocket {
                 WasiSocketOption::MulticastLoopV6 => socket
                     .set_multicast_loop_v6(val)
                     .map_err(net_error_into_wasi_err)?,
+                WasiSocketOption::ReusePort => options.reuse_port = val,
+                WasiSocketOption::ReuseAddr => options.reuse_addr = val,
                 _ => return Err(Errno::Inval),
             },
             _ => return Err(Errno::Notsup),
@@ -734,7 +1185,8 @@ impl InodeSocket {
 
     pub fn get_opt_flag(&self, option: WasiSocketOption) -> Result<bool, Errno> {
         let mut inner = self.inner.protected.write().unwrap();
-        Ok(match &mut inner.kind {
+        let InodeSocketProtected { kind, options, .. } = &mut *inner;
+        Ok(match kind {
             InodeSocketKind::PreSocket { props, .. }
             | InodeSocketKind::RemoteSocket { props, .. } => match option {
                 WasiSocketOption::OnlyV6 => props.only_v6,
@@ -742,6 +1194,7 @@ impl InodeSocket {
                 WasiSocketOption::ReuseAddr => props.reuse_addr,
                 WasiSocketOption::NoDelay => props.no_delay.unwrap_or_default(),
                 WasiSocketOption::KeepAlive => props.keep_alive.unwrap_or_default(),
+                WasiSocketOption::DontRoute => props.dont_route.unwrap_or_default(),
                 _ => return Err(Errno::Inval),
             },
             InodeSocketKind::Raw(sock) => match option {
@@ -751,13 +1204,25 @@ impl InodeSocket {
                 _ => return Err(Errno::Inval),
             },
             InodeSocketKind::TcpStream { socket, .. } => match option {
-                WasiSocketOption::NoDelay => socket.nodelay().map_err(net_error_into_wasi_err)?,
+                WasiSocketOption::NoDelay => {
+                    or_emulated(socket.nodelay(), options.no_delay.unwrap_or_default())?
+                }
                 WasiSocketOption::KeepAlive => {
-                    socket.keepalive().map_err(net_error_into_wasi_err)?
+                    or_emulated(socket.keepalive(), options.keep_alive.unwrap_or_default())?
                 }
                 WasiSocketOption::DontRoute => {
                     socket.dontroute().map_err(net_error_into_wasi_err)?
                 }
+                WasiSocketOption::ReusePort => options.reuse_port,
+                WasiSocketOption::ReuseAddr => options.reuse_addr,
+                _ => return Err(Errno::Inval),
+            },
+            InodeSocketKind::TcpListener { .. } => match option {
+                WasiSocketOption::NoDelay => options.no_delay.unwrap_or_default(),
+                WasiSocketOption::KeepAlive => options.keep_alive.unwrap_or_default(),
+                WasiSocketOption::ReusePort => options.reuse_port,
+                WasiSocketOption::ReuseAddr => options.reuse_addr,
+                WasiSocketOption::Listening => true,
                 _ => return Err(Errno::Inval),
             },
             InodeSocketKind::UdpSocket { socket, .. } => match option {
@@ -770,6 +1235,8 @@ impl InodeSocket {
                 WasiSocketOption::MulticastLoopV6 => socket
                     .multicast_loop_v6()
                     .map_err(net_error_into_wasi_err)?,
+                WasiSocketOption::ReusePort => options.reuse_port,
+                WasiSocketOption::ReuseAddr => options.reuse_addr,
                 _ => return Err(Errno::Inval),
             },
             _ => return Err(Errno::Notsup),
@@ -778,15 +1245,18 @@ impl InodeSocket {
 
     pub fn set_send_buf_size(&mut self, size: usize) -> Result<(), Errno> {
         let mut inner = self.inner.protected.write().unwrap();
-        match &mut inner.kind {
+        let InodeSocketProtected { kind, options, .. } = &mut *inner;
+        match kind {
             InodeSocketKind::PreSocket { props, .. }
             | InodeSocketKind::RemoteSocket { props, .. } => {
                 props.send_buf_size = Some(size);
             }
             InodeSocketKind::TcpStream { socket, .. } => {
-                socket
-                    .set_send_buf_size(size)
-                    .map_err(net_error_into_wasi_err)?;
+                emulate_unsupported(socket.set_send_buf_size(size))?;
+                options.send_buf_size = Some(size);
+            }
+            InodeSocketKind::TcpListener { .. } | InodeSocketKind::UdpSocket { .. } => {
+                options.send_buf_size = Some(size);
             }
             _ => return Err(Errno::Notsup),
         }
@@ -795,29 +1265,34 @@ impl InodeSocket {
 
     pub fn send_buf_size(&self) -> Result<usize, Errno> {
         let inner = self.inner.protected.read().unwrap();
+        let emulated = inner.options.send_buf_size.unwrap_or_default();
         match &inner.kind {
             InodeSocketKind::PreSocket { props, .. }
             | InodeSocketKind::RemoteSocket { props, .. } => {
                 Ok(props.send_buf_size.unwrap_or_default())
             }
             InodeSocketKind::TcpStream { socket, .. } => {
-                socket.send_buf_size().map_err(net_error_into_wasi_err)
+                or_emulated(socket.send_buf_size(), emulated)
             }
+            InodeSocketKind::TcpListener { .. } | InodeSocketKind::UdpSocket { .. } => Ok(emulated),
             _ => Err(Errno::Notsup),
         }
     }
 
     pub fn set_recv_buf_size(&mut self, size: usize) -> Result<(), Errno> {
         let mut inner = self.inner.protected.write().unwrap();
-        match &mut inner.kind {
+        let InodeSocketProtected { kind, options, .. } = &mut *inner;
+        match kind {
             InodeSocketKind::PreSocket { props, .. }
             | InodeSocketKind::RemoteSocket { props, .. } => {
                 props.recv_buf_size = Some(size);
             }
             InodeSocketKind::TcpStream { socket, .. } => {
-                socket
-                    .set_recv_buf_size(size)
-                    .map_err(net_error_into_wasi_err)?;
+                emulate_unsupported(socket.set_recv_buf_size(size))?;
+                options.recv_buf_size = Some(size);
+            }
+            InodeSocketKind::TcpListener { .. } | InodeSocketKind::UdpSocket { .. } => {
+                options.recv_buf_size = Some(size);
             }
             _ => return Err(Errno::Notsup),
         }
@@ -826,37 +1301,68 @@ impl InodeSocket {
 
     pub fn recv_buf_size(&self) -> Result<usize, Errno> {
         let inner = self.inner.protected.read().unwrap();
+        let emulated = inner.options.recv_buf_size.unwrap_or_default();
         match &inner.kind {
             InodeSocketKind::PreSocket { props, .. }
             | InodeSocketKind::RemoteSocket { props, .. } => {
                 Ok(props.recv_buf_size.unwrap_or_default())
             }
             InodeSocketKind::TcpStream { socket, .. } => {
-                socket.recv_buf_size().map_err(net_error_into_wasi_err)
+                or_emulated(socket.recv_buf_size(), emulated)
             }
+            InodeSocketKind::TcpListener { .. } | InodeSocketKind::UdpSocket { .. } => Ok(emulated),
             _ => Err(Errno::Notsup),
         }
     }
 
-    pub fn set_linger(&mut self, linger: Option<std::time::Duration>) -> Result<(), Errno> {
+    pub fn set_linger(&self, linger: Option<std::time::Duration>) -> Result<(), Errno> {
         let mut inner = self.inner.protected.write().unwrap();
-        match &mut inner.kind {
+        let InodeSocketProtected { kind, options, .. } = &mut *inner;
+        match kind {
             InodeSocketKind::TcpStream { socket, .. } => {
-                socket.set_linger(linger).map_err(net_error_into_wasi_err)
+                emulate_unsupported(socket.set_linger(linger))?;
             }
-            InodeSocketKind::RemoteSocket { .. } => Ok(()),
-            InodeSocketKind::PreSocket { .. } => Err(Errno::Io),
-            _ => Err(Errno::Notsup),
+            InodeSocketKind::RemoteSocket { .. }
+            | InodeSocketKind::PreSocket { .. }
+            | InodeSocketKind::TcpListener { .. } => {}
+            _ => return Err(Errno::Notsup),
         }
+        options.linger = linger;
+        Ok(())
     }
 
     pub fn linger(&self) -> Result<Option<std::time::Duration>, Errno> {
         let inner = self.inner.protected.read().unwrap();
         match &inner.kind {
             InodeSocketKind::TcpStream { socket, .. } => {
-                socket.linger().map_err(net_error_into_wasi_err)
+                or_emulated(socket.linger(), inner.options.linger)
             }
-            InodeSocketKind::PreSocket { .. } => Err(Errno::Io),
+            InodeSocketKind::RemoteSocket { .. }
+            | InodeSocketKind::PreSocket { .. }
+            | InodeSocketKind::TcpListener { .. } => Ok(inner.options.linger),
+            _ => Err(Errno::Notsup),
+        }
+    }
+
+    /// Sets how many keep-alive probes are sent before the connection is
+    /// dropped, like `TCP_KEEPCNT`.
+    pub fn set_keepalive_count(&self, count: u32) -> Result<(), Errno> {
+        let mut inner = self.inner.protected.write().unwrap();
+        inner.options.keepalive.retries = Some(count);
+        inner.update_keepalive_params()
+    }
+
+    pub fn keepalive_count(&self) -> Result<u32, Errno> {
+        let inner = self.inner.protected.read().unwrap();
+        match &inner.kind {
+            InodeSocketKind::PreSocket { .. }
+            | InodeSocketKind::RemoteSocket { .. }
+            | InodeSocketKind::TcpListener { .. }
+            | InodeSocketKind::TcpStream { .. } => Ok(inner
+                .options
+                .keepalive
+                .retries
+                .unwrap_or(DEFAULT_KEEPALIVE_COUNT)),
             _ => Err(Errno::Notsup),
         }
     }
@@ -867,7 +1373,24 @@ impl InodeSocket {
         timeout: Option<std::time::Duration>,
     ) -> Result<(), Errno> {
         let mut inner = self.inner.protected.write().unwrap();
-        match &mut inner.kind {
+        match ty {
+            TimeType::Linger => {
+                drop(inner);
+                return self.set_linger(timeout);
+            }
+            TimeType::KeepAliveIdle => {
+                inner.options.keepalive.idle = timeout;
+                return inner.update_keepalive_params();
+            }
+            TimeType::KeepAliveInterval => {
+                inner.options.keepalive.interval = timeout;
+                return inner.update_keepalive_params();
+            }
+            _ => {}
+        }
+
+        let InodeSocketProtected { kind, options, .. } = &mut *inner;
+        match kind {
             InodeSocketKind::TcpStream {
                 write_timeout,
                 read_timeout,
@@ -883,6 +1406,17 @@ impl InodeSocket {
             InodeSocketKind::TcpListener { accept_timeout, .. } => {
                 match ty {
                     TimeType::AcceptTimeout => *accept_timeout = timeout,
+                    // The timeouts of the connections it accepts
+                    TimeType::WriteTimeout => options.write_timeout = timeout,
+                    TimeType::ReadTimeout => options.read_timeout = timeout,
+                    _ => return Err(Errno::Inval),
+                }
+                Ok(())
+            }
+            InodeSocketKind::UdpSocket { .. } => {
+                match ty {
+                    TimeType::WriteTimeout => options.write_timeout = timeout,
+                    TimeType::ReadTimeout => options.read_timeout = timeout,
                     _ => return Err(Errno::Inval),
                 }
                 Ok(())
@@ -903,6 +1437,24 @@ impl InodeSocket {
     }
 
     pub fn opt_time(&self, ty: TimeType) -> Result<Option<std::time::Duration>, Errno> {
+        match ty {
+            TimeType::Linger => return self.linger(),
+            TimeType::KeepAliveIdle => {
+                return Ok(self.inner.protected.read().unwrap().options.keepalive.idle)
+            }
+            TimeType::KeepAliveInterval => {
+                return Ok(self
+                    .inner
+                    .protected
+                    .read()
+                    .unwrap()
+                    .options
+                    .keepalive
+                    .interval)
+            }
+            _ => {}
+        }
+
         let inner = self.inner.protected.read().unwrap();
         match &inner.kind {
             InodeSocketKind::TcpStream {
@@ -916,6 +1468,13 @@ impl InodeSocket {
             }),
             InodeSocketKind::TcpListener { accept_timeout, .. } => Ok(match ty {
                 TimeType::AcceptTimeout => *accept_timeout,
+                TimeType::ReadTimeout => inner.options.read_timeout,
+                TimeType::WriteTimeout => inner.options.write_timeout,
+                _ => return Err(Errno::Inval),
+            }),
+            InodeSocketKind::UdpSocket { .. } => Ok(match ty {
+                TimeType::ReadTimeout => inner.options.read_timeout,
+                TimeType::WriteTimeout => inner.options.write_timeout,
                 _ => return Err(Errno::Inval),
             }),
             InodeSocketKind::PreSocket { props, .. }
@@ -932,33 +1491,32 @@ impl InodeSocket {
 
     pub fn set_ttl(&self, ttl: u32) -> Result<(), Errno> {
         let mut inner = self.inner.protected.write().unwrap();
-        match &mut inner.kind {
+        let InodeSocketProtected { kind, options, .. } = &mut *inner;
+        match kind {
             InodeSocketKind::TcpStream { socket, .. } => {
-                socket.set_ttl(ttl).map_err(net_error_into_wasi_err)
+                emulate_unsupported(socket.set_ttl(ttl))?;
             }
             InodeSocketKind::UdpSocket { socket, .. } => {
-                socket.set_ttl(ttl).map_err(net_error_into_wasi_err)
+                emulate_unsupported(socket.set_ttl(ttl))?;
             }
             InodeSocketKind::RemoteSocket { ttl: set_ttl, .. } => {
                 *set_ttl = ttl;
-                Ok(())
             }
-            InodeSocketKind::PreSocket { .. } => Err(Errno::Io),
-            _ => Err(Errno::Notsup),
+            InodeSocketKind::PreSocket { .. } | InodeSocketKind::TcpListener { .. } => {}
+            _ => return Err(Errno::Notsup),
         }
+        options.ttl = Some(ttl);
+        Ok(())
     }
 
     pub fn ttl(&self) -> Result<u32, Errno> {
         let inner = self.inner.protected.read().unwrap();
+        let emulated = inner.options.ttl.unwrap_or(DEFAULT_TTL);
         match &inner.kind {
-            InodeSocketKind::TcpStream { socket, .. } => {
-                socket.ttl().map_err(net_error_into_wasi_err)
-            }
-            InodeSocketKind::UdpSocket { socket, .. } => {
-                socket.ttl().map_err(net_error_into_wasi_err)
-            }
+            InodeSocketKind::TcpStream { socket, .. } => or_emulated(socket.ttl(), emulated),
+            InodeSocketKind::UdpSocket { socket, .. } => or_emulated(socket.ttl(), emulated),
             InodeSocketKind::RemoteSocket { ttl, .. } => Ok(*ttl),
-            InodeSocketKind::PreSocket { .. } => Err(Errno::Io),
+            InodeSocketKind::PreSocket { .. } | InodeSocketKind::TcpListener { .. } => Ok(emulated),
             _ => Err(Errno::Notsup),
         }
     }
@@ -1070,7 +1628,18 @@ impl InodeSocket {
             ) -> Poll<Self::Output> {
                 loop {
                     let mut inner = self.inner.protected.write().unwrap();
+                    if inner.write_shutdown {
+                        return Poll::Ready(Err(Errno::Pipe));
+                    }
+                    inner.finish_connect();
+                    if let Some(err) = inner.error.take() {
+                        return Poll::Ready(Err(net_error_into_wasi_err(err)));
+                    }
+                    let connecting = inner.connecting.is_some();
                     let res = match &mut inner.kind {
+                        InodeSocketKind::PreSocket { .. } if connecting => {
+                            Err(NetworkError::WouldBlock)
+                        }
                         InodeSocketKind::Raw(socket) => socket.try_send(self.data),
                         InodeSocketKind::TcpStream { socket, .. } => socket.try_send(self.data),
                         InodeSocketKind::UdpSocket { socket, peer } => {
@@ -1111,20 +1680,29 @@ impl InodeSocket {
             }
         }
 
+        let shaper = self.shaper();
+        if let Some((shaper, port)) = &shaper {
+            shaper.before_send(tasks, *port, nonblocking).await?;
+        }
+
         let poller = SocketSender {
             inner: &self.inner,
             data: buf,
             nonblocking,
             handler_registered: false,
         };
-        if let Some(timeout) = timeout {
+        let ret = if let Some(timeout) = timeout {
             tokio::select! {
                 res = poller => res,
                 _ = tasks.sleep_now(timeout) => Err(Errno::Timedout)
             }
         } else {
             poller.await
+        };
+        if let (Ok(amt), Some((shaper, port))) = (&ret, shaper) {
+            shaper.sent(port, *amt);
         }
+        ret
     }
 
     pub async fn send_to<M: MemorySize>(
@@ -1194,6 +1772,11 @@ impl InodeSocket {
             }
         }
 
+        let shaper = self.shaper();
+        if let Some((shaper, port)) = &shaper {
+            shaper.before_send(tasks, *port, nonblocking).await?;
+        }
+
         let poller = SocketSender {
             inner: &self.inner,
             data: buf,
@@ -1201,27 +1784,35 @@ impl InodeSocket {
             nonblocking,
             handler_registered: false,
         };
-        if let Some(timeout) = timeout {
+        let ret = if let Some(timeout) = timeout {
             tokio::select! {
                 res = poller => res,
                 _ = tasks.sleep_now(timeout) => Err(Errno::Timedout)
             }
         } else {
             poller.await
+        };
+        if let (Ok(amt), Some((shaper, port))) = (&ret, shaper) {
+            shaper.sent(port, *amt);
         }
+        ret
     }
 
+    /// Receives from the socket, leaving the data to be received again when
+    /// `peek` is set.
     pub async fn recv(
         &self,
         tasks: &dyn VirtualTaskManager,
         buf: &mut [MaybeUninit<u8>],
         timeout: Option<Duration>,
         nonblocking: bool,
+        peek: bool,
     ) -> Result<usize, Errno> {
         struct SocketReceiver<'a, 'b> {
             inner: &'a InodeSocketInner,
             data: &'b mut [MaybeUninit<u8>],
             nonblocking: bool,
+            peek: bool,
             handler_registered: bool,
         }
         impl<'a, 'b> Drop for SocketReceiver<'a, 'b> {
@@ -1239,22 +1830,46 @@ impl InodeSocket {
                 cx: &mut std::task::Context<'_>,
             ) -> Poll<Self::Output> {
                 loop {
+                    let peek = self.peek;
                     let mut inner = self.inner.protected.write().unwrap();
+                    // Like on Linux, the reads after the reader was shut down
+                    // find the end of the stream
+                    if inner.read_shutdown {
+                        return Poll::Ready(Ok(0));
+                    }
+                    inner.finish_connect();
+                    if let Some(err) = inner.error.take() {
+                        return Poll::Ready(Err(net_error_into_wasi_err(err)));
+                    }
+                    let connecting = inner.connecting.is_some();
                     let res = match &mut inner.kind {
+                        InodeSocketKind::PreSocket { .. } if connecting => {
+                            Err(NetworkError::WouldBlock)
+                        }
+                        InodeSocketKind::Raw(_) if peek => Err(NetworkError::Unsupported),
                         InodeSocketKind::Raw(socket) => socket.try_recv(self.data),
+                        InodeSocketKind::TcpStream { socket, .. } if peek => {
+                            socket.try_peek(self.data)
+                        }
                         InodeSocketKind::TcpStream { socket, .. } => socket.try_recv(self.data),
                         InodeSocketKind::UdpSocket { socket, peer } => {
-                            if let Some(peer) = peer {
-                                match socket.try_recv_from(self.data) {
-                                    Ok((amt, addr)) if addr == *peer => Ok(amt),
-                                    Ok(_) => Err(NetworkError::WouldBlock),
-                                    Err(err) => Err(err),
-                                }
-                            } else {
-                                match socket.try_recv_from(self.data) {
-                                    Ok((amt, _)) => Ok(amt),
-                                    Err(err) => Err(err),
+                            let res = match peek {
+                                true => socket.try_peek_from(self.data),
+                                false => socket.try_recv_from(self.data),
+                            };
+                            match (res, peer) {
+                                (Ok((amt, addr)), Some(peer)) if addr == *peer => Ok(amt),
+                                (Ok(_), Some(_)) => {
+                                    // The datagrams of other peers are dropped,
+                                    // even when they are only peeked at
+                                    if peek {
+                                        socket.try_recv_from(self.data).ok();
+                                        continue;
+                                    }
+                                    Err(NetworkError::WouldBlock)
                                 }
+                                (Ok((amt, _)), None) => Ok(amt),
+                                (Err(err), _) => Err(err),
                             }
                         }
                         InodeSocketKind::RemoteSocket { is_dead, .. } => {
@@ -1289,33 +1904,63 @@ impl InodeSocket {
             }
         }
 
-        let poller = SocketReceiver {
-            inner: &self.inner,
-            data: buf,
-            nonblocking,
-            handler_registered: false,
-        };
-        if let Some(timeout) = timeout {
-            tokio::select! {
-                res = poller => res,
-                _ = tasks.sleep_now(timeout) => Err(Errno::Timedout)
+        let shaper = self.shaper();
+        if let Some((shaper, port)) = &shaper {
+            shaper.before_recv(tasks, *port, nonblocking).await?;
+        }
+
+        let delaying = self.inner.protected.read().unwrap().delaying_shaper();
+        let ret = if let Some((shaper, port)) = delaying {
+            let receiver = self.recv_delayed(&shaper, port, buf, nonblocking, peek);
+            if let Some(timeout) = timeout {
+                tokio::select! {
+                    res = receiver => res,
+                    _ = tasks.sleep_now(timeout) => Err(Errno::Timedout)
+                }
+            } else {
+                receiver.await
             }
+            .map(|(amt, _)| amt)
         } else {
-            poller.await
+            let poller = SocketReceiver {
+                inner: &self.inner,
+                data: buf,
+                nonblocking,
+                peek,
+                handler_registered: false,
+            };
+            if let Some(timeout) = timeout {
+                tokio::select! {
+                    res = poller => res,
+                    _ = tasks.sleep_now(timeout) => Err(Errno::Timedout)
+                }
+            } else {
+                poller.await
+            }
+        };
+        if let (Ok(amt), Some((shaper, port))) = (&ret, shaper) {
+            if !peek {
+                shaper.received(port, *amt);
+            }
         }
+        ret
     }
 
+    /// Receives from the socket along with the address of the sender,
+    /// leaving the datagram to be received again when `peek` is set.
     pub async fn recv_from(
         &self,
         tasks: &dyn VirtualTaskManager,
         buf: &mut [MaybeUninit<u8>],
         timeout: Option<Duration>,
         nonblocking: bool,
+        peek: bool,
     ) -> Result<(usize, SocketAddr), Errno> {
         struct SocketReceiver<'a, 'b> {
             inner: &'a InodeSocketInner,
             data: &'b mut [MaybeUninit<u8>],
             nonblocking: bool,
+            peek: bool,
             handler_registered: bool,
         }
         impl<'a, 'b> Drop for SocketReceiver<'a, 'b> {
@@ -1335,7 +1980,13 @@ impl InodeSocket {
                 let mut inner = self.inner.protected.write().unwrap();
                 loop {
                     let res = match &mut inner.kind {
+                        InodeSocketKind::Icmp(socket) if self.peek => {
+                            socket.try_peek_from(self.data)
+                        }
                         InodeSocketKind::Icmp(socket) => socket.try_recv_from(self.data),
+                        InodeSocketKind::UdpSocket { socket, .. } if self.peek => {
+                            socket.try_peek_from(self.data)
+                        }
                         InodeSocketKind::UdpSocket { socket, .. } => {
                             socket.try_recv_from(self.data)
                         }
@@ -1371,35 +2022,132 @@ impl InodeSocket {
             }
         }
 
-        let poller = SocketReceiver {
-            inner: &self.inner,
-            data: buf,
-            nonblocking,
-            handler_registered: false,
-        };
-        if let Some(timeout) = timeout {
-            tokio::select! {
-                res = poller => res,
-                _ = tasks.sleep_now(timeout) => Err(Errno::Timedout)
+        let shaper = self.shaper();
+        if let Some((shaper, port)) = &shaper {
+            shaper.before_recv(tasks, *port, nonblocking).await?;
+        }
+
+        let delaying = self.inner.protected.read().unwrap().delaying_shaper();
+        let ret = if let Some((shaper, port)) = delaying {
+            let receiver = self.recv_delayed(&shaper, port, buf, nonblocking, peek);
+            if let Some(timeout) = timeout {
+                tokio::select! {
+                    res = receiver => res,
+                    _ = tasks.sleep_now(timeout) => Err(Errno::Timedout)
+                }
+            } else {
+                receiver.await
             }
+            .and_then(|(amt, from)| Ok((amt, from.ok_or(Errno::Notsup)?)))
         } else {
-            poller.await
+            let poller = SocketReceiver {
+                inner: &self.inner,
+                data: buf,
+                nonblocking,
+                peek,
+                handler_registered: false,
+            };
+            if let Some(timeout) = timeout {
+                tokio::select! {
+                    res = poller => res,
+                    _ = tasks.sleep_now(timeout) => Err(Errno::Timedout)
+                }
+            } else {
+                poller.await
+            }
+        };
+        if let (Ok((amt, _)), Some((shaper, port))) = (&ret, shaper) {
+            if !peek {
+                shaper.received(port, *amt);
+            }
         }
+        ret
+    }
+
+    /// Receives what the shaper of the socket held back once it arrives,
+    /// along with where it comes from.
+    async fn recv_delayed(
+        &self,
+        shaper: &SocketShaper,
+        port: Option<u16>,
+        buf: &mut [MaybeUninit<u8>],
+        nonblocking: bool,
+        peek: bool,
+    ) -> Result<(usize, Option<SocketAddr>), Errno> {
+        futures::future::poll_fn(|cx| loop {
+            let mut inner = self.inner.protected.write().unwrap();
+            // Like on Linux, the reads after the reader was shut down find
+            // the end of the stream
+            if inner.read_shutdown {
+                return Poll::Ready(Ok((0, None)));
+            }
+            inner.finish_connect();
+            if let Some(err) = inner.error.take() {
+                return Poll::Ready(Err(net_error_into_wasi_err(err)));
+            }
+            if let Err(err) = inner.pull_delayed(shaper, port) {
+                return Poll::Ready(Err(net_error_into_wasi_err(err)));
+            }
+            if let Some(res) = shaper.deliver(buf, peek) {
+                return Poll::Ready(Ok(res));
+            }
+            if nonblocking {
+                return Poll::Ready(Err(Errno::Again));
+            }
+            match inner.poll_delayed(cx, shaper, port) {
+                Poll::Ready(Ok(_)) => continue,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(map_io_err(err))),
+                Poll::Pending => return Poll::Pending,
+            }
+        })
+        .await
     }
 
-    pub fn shutdown(&mut self, how: std::net::Shutdown) -> Result<(), Errno> {
+    pub fn shutdown(&mut self, how: Shutdown) -> Result<(), Errno> {
         let mut inner = self.inner.protected.write().unwrap();
         match &mut inner.kind {
             InodeSocketKind::TcpStream { socket, .. } => {
-                socket.shutdown(how).map_err(net_error_into_wasi_err)?;
+                // Shutting down the writer sends the end of the stream to the
+                // peer, which only the transport can do, while the reader is
+                // shut down here by ignoring what arrives
+                let res = match socket.shutdown(how) {
+                    Err(NetworkError::Unsupported) => match how {
+                        Shutdown::Read => Ok(()),
+                        Shutdown::Write => Err(NetworkError::Unsupported),
+                        Shutdown::Both => socket.shutdown(Shutdown::Write),
+                    },
+                    res => res,
+                };
+                res.map_err(net_error_into_wasi_err)?;
             }
             InodeSocketKind::RemoteSocket { .. } => return Ok(()),
             InodeSocketKind::PreSocket { .. } => return Err(Errno::Notconn),
             _ => return Err(Errno::Notsup),
         }
+        if matches!(how, Shutdown::Read | Shutdown::Both) {
+            inner.read_shutdown = true;
+        }
+        if matches!(how, Shutdown::Write | Shutdown::Both) {
+            inner.write_shutdown = true;
+        }
         Ok(())
     }
 
+    /// Encrypts the stream of the socket with `session`, from what it
+    /// sends and receives next.
+    #[cfg(feature = "tls")]
+    pub fn secure(&mut self, session: virtual_net::tls::TlsSession) -> Result<(), Errno> {
+        let mut inner = self.inner.protected.write().unwrap();
+        match &mut inner.kind {
+            InodeSocketKind::TcpStream { socket, .. } => {
+                replace_with::replace_with_or_abort(socket, |socket| session.wrap(socket));
+                Ok(())
+            }
+            InodeSocketKind::PreSocket { .. } => Err(Errno::Notconn),
+            _ => Err(Errno::Notsup),
+        }
+    }
+
     pub async fn can_write(&self) -> bool {
         if let Ok(mut guard) = self.inner.protected.try_write() {
             #[allow(clippy::match_like_matches_macro)]
@@ -1417,7 +2165,122 @@ impl InodeSocket {
 }
 
 impl InodeSocketProtected {
+    /// Picks up the outcome of the connection started by a non-blocking
+    /// `connect`, once it's done.
+    fn finish_connect(&mut self) {
+        let Some((res, handler)) = self
+            .connecting
+            .as_ref()
+            .and_then(PendingConnect::try_finish)
+        else {
+            return;
+        };
+        self.connecting = None;
+        match res {
+            Ok(socket) => {
+                let options = self.inheritable_options();
+                let (write_timeout, read_timeout) = match &self.kind {
+                    InodeSocketKind::PreSocket { props, .. } => {
+                        (props.write_timeout, props.read_timeout)
+                    }
+                    _ => (None, None),
+                };
+                self.kind = InodeSocketKind::TcpStream {
+                    socket,
+                    write_timeout,
+                    read_timeout,
+                };
+                self.apply_options(options);
+                if let Some(handler) = handler {
+                    self.set_handler(handler).ok();
+                }
+            }
+            Err(err) => {
+                if let InodeSocketKind::PreSocket { props, .. } = &mut self.kind {
+                    props.handler = handler;
+                }
+                self.error = Some(err);
+            }
+        }
+    }
+
+    fn inheritable_options(&self) -> SocketOptions {
+        let mut options = self.options.clone();
+        options.shaper = options.shaper.as_ref().map(SocketShaper::renew);
+        if let InodeSocketKind::PreSocket { props, .. }
+        | InodeSocketKind::RemoteSocket { props, .. } = &self.kind
+        {
+            options.reuse_port = props.reuse_port;
+            options.reuse_addr = props.reuse_addr;
+            options.no_delay = props.no_delay;
+            options.keep_alive = props.keep_alive;
+            options.send_buf_size = props.send_buf_size;
+            options.recv_buf_size = props.recv_buf_size;
+            options.read_timeout = props.read_timeout;
+            options.write_timeout = props.write_timeout;
+        }
+        options
+    }
+
+    /// Applies the options of another socket, the ones the socket doesn't
+    /// support are only kept.
+    fn apply_options(&mut self, options: SocketOptions) {
+        match &mut self.kind {
+            InodeSocketKind::TcpStream {
+                socket,
+                read_timeout,
+                write_timeout,
+            } => {
+                if let Some(no_delay) = options.no_delay {
+                    socket.set_nodelay(no_delay).ok();
+                }
+                if let Some(keep_alive) = options.keep_alive {
+                    socket.set_keepalive(keep_alive).ok();
+                }
+                if options.keepalive != TcpKeepalive::default() {
+                    socket.set_keepalive_params(options.keepalive).ok();
+                }
+                if options.linger.is_some() {
+                    socket.set_linger(options.linger).ok();
+                }
+                if let Some(ttl) = options.ttl {
+                    socket.set_ttl(ttl).ok();
+                }
+                if let Some(size) = options.send_buf_size {
+                    socket.set_send_buf_size(size).ok();
+                }
+                if let Some(size) = options.recv_buf_size {
+                    socket.set_recv_buf_size(size).ok();
+                }
+                *read_timeout = read_timeout.or(options.read_timeout);
+                *write_timeout = write_timeout.or(options.write_timeout);
+            }
+            InodeSocketKind::UdpSocket { socket, .. } => {
+                if let Some(ttl) = options.ttl {
+                    socket.set_ttl(ttl).ok();
+                }
+            }
+            _ => {}
+        }
+        self.options = options;
+    }
+
+    fn update_keepalive_params(&mut self) -> Result<(), Errno> {
+        match &mut self.kind {
+            InodeSocketKind::TcpStream { socket, .. } => {
+                emulate_unsupported(socket.set_keepalive_params(self.options.keepalive))
+            }
+            InodeSocketKind::PreSocket { .. }
+            | InodeSocketKind::RemoteSocket { .. }
+            | InodeSocketKind::TcpListener { .. } => Ok(()),
+            _ => Err(Errno::Notsup),
+        }
+    }
+
     pub fn remove_handler(&mut self) {
+        if let Some(connecting) = &self.connecting {
+            connecting.remove_handler();
+        }
         match &mut self.kind {
             InodeSocketKind::TcpListener { socket, .. } => socket.remove_handler(),
             InodeSocketKind::TcpStream { socket, .. } => socket.remove_handler(),
@@ -1434,6 +2297,15 @@ impl InodeSocketProtected {
     }
 
     pub fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        if self.read_shutdown {
+            return Poll::Ready(Ok(0));
+        }
+        if let Some(res) = self.poll_connect(cx) {
+            return res;
+        }
+        if let Some((shaper, port)) = self.delaying_shaper() {
+            return self.poll_delayed(cx, &shaper, port);
+        }
         match &mut self.kind {
             InodeSocketKind::TcpListener { socket, .. } => socket.poll_read_ready(cx),
             InodeSocketKind::TcpStream { socket, .. } => socket.poll_read_ready(cx),
@@ -1450,6 +2322,12 @@ impl InodeSocketProtected {
     }
 
     pub fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        if self.write_shutdown {
+            return Poll::Ready(Err(net_error_into_io_err(NetworkError::BrokenPipe)));
+        }
+        if let Some(res) = self.poll_connect(cx) {
+            return res;
+        }
         match &mut self.kind {
             InodeSocketKind::TcpListener { socket, .. } => socket.poll_write_ready(cx),
             InodeSocketKind::TcpStream { socket, .. } => socket.poll_write_ready(cx),
@@ -1465,10 +2343,137 @@ impl InodeSocketProtected {
         .map_err(net_error_into_io_err)
     }
 
+    /// Waits for the connection started by a non-blocking `connect`, its
+    /// failure is reported as an error until it's taken.
+    fn poll_connect(&mut self, cx: &mut Context<'_>) -> Option<Poll<io::Result<usize>>> {
+        self.finish_connect();
+        if let Some(connecting) = &self.connecting {
+            connecting.register_waker(cx.waker());
+            return Some(Poll::Pending);
+        }
+        self.error
+            .map(|err| Poll::Ready(Err(net_error_into_io_err(err))))
+    }
+
+    /// The local port of the socket, which picks the shape of its traffic.
+    fn local_port(&self) -> Option<u16> {
+        let addr = match &self.kind {
+            InodeSocketKind::PreSocket { addr, .. } => *addr,
+            InodeSocketKind::Icmp(socket) => socket.addr_local().ok(),
+            InodeSocketKind::TcpListener { socket, .. } => socket.addr_local().ok(),
+            InodeSocketKind::TcpStream { socket, .. } => socket.addr_local().ok(),
+            InodeSocketKind::UdpSocket { socket, .. } => socket.addr_local().ok(),
+            InodeSocketKind::RemoteSocket { local_addr, .. } => Some(*local_addr),
+            InodeSocketKind::Raw(_) => None,
+        };
+        addr.map(|addr| addr.port())
+    }
+
+    /// The shaper of a socket which receives, when it holds back what the
+    /// socket receives until it arrives.
+    fn delaying_shaper(&self) -> Option<(SocketShaper, Option<u16>)> {
+        let receives = matches!(
+            self.kind,
+            InodeSocketKind::TcpStream { .. }
+                | InodeSocketKind::UdpSocket { .. }
+                | InodeSocketKind::Raw(_)
+                | InodeSocketKind::Icmp(_)
+        );
+        let shaper = self.options.shaper.as_ref().filter(|_| receives)?;
+        let port = self.local_port();
+        shaper.delays(port).then(|| (shaper.clone(), port))
+    }
+
+    /// Hands what the socket received to its shaper, which holds it back
+    /// until it arrives. The datagrams of the peers a socket isn't
+    /// connected to are dropped.
+    fn pull_delayed(
+        &mut self,
+        shaper: &SocketShaper,
+        port: Option<u16>,
+    ) -> Result<(), NetworkError> {
+        if self.read_shutdown || shaper.ended() {
+            return Ok(());
+        }
+        let mut buf = vec![MaybeUninit::uninit(); MAX_DATAGRAM];
+        loop {
+            let res = match &mut self.kind {
+                InodeSocketKind::TcpStream { socket, .. } => {
+                    socket.try_recv(&mut buf).map(|amt| (amt, None))
+                }
+                InodeSocketKind::UdpSocket { socket, peer } => {
+                    match (socket.try_recv_from(&mut buf), peer) {
+                        (Ok((_, addr)), Some(peer)) if addr != *peer => continue,
+                        (res, _) => res.map(|(amt, addr)| (amt, Some(addr))),
+                    }
+                }
+                InodeSocketKind::Icmp(socket) => socket
+                    .try_recv_from(&mut buf)
+                    .map(|(amt, addr)| (amt, Some(addr))),
+                InodeSocketKind::Raw(socket) => socket.try_recv(&mut buf).map(|amt| (amt, None)),
+                _ => return Ok(()),
+            };
+            let stream = matches!(self.kind, InodeSocketKind::TcpStream { .. });
+            match res {
+                Ok((amt, from)) => {
+                    // SAFETY: the socket initialized the bytes it received
+                    let data = buf[..amt]
+                        .iter()
+                        .map(|byte| unsafe { byte.assume_init() })
+                        .collect();
+                    shaper.arrived(port, stream, data, from);
+                    if stream && amt == 0 {
+                        return Ok(());
+                    }
+                }
+                Err(NetworkError::WouldBlock) => return Ok(()),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Polls for what the shaper of the socket held back to arrive, taking
+    /// what the socket receives meanwhile.
+    fn poll_delayed(
+        &mut self,
+        cx: &mut Context<'_>,
+        shaper: &SocketShaper,
+        port: Option<u16>,
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            if let Err(err) = self.pull_delayed(shaper, port) {
+                return Poll::Ready(Err(net_error_into_io_err(err)));
+            }
+            if let Poll::Ready(amt) = shaper.poll_arrival(cx) {
+                return Poll::Ready(Ok(amt));
+            }
+            if shaper.ended() {
+                return Poll::Pending;
+            }
+            let ready = match &mut self.kind {
+                InodeSocketKind::TcpStream { socket, .. } => socket.poll_read_ready(cx),
+                InodeSocketKind::UdpSocket { socket, .. } => socket.poll_read_ready(cx),
+                InodeSocketKind::Raw(socket) => socket.poll_read_ready(cx),
+                InodeSocketKind::Icmp(socket) => socket.poll_read_ready(cx),
+                _ => return Poll::Pending,
+            };
+            match ready {
+                // More was received, which is taken on the next round
+                Poll::Ready(Ok(_)) => continue,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(net_error_into_io_err(err))),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
     pub fn set_handler(
         &mut self,
         handler: Box<dyn InterestHandler + Send + Sync>,
     ) -> virtual_net::Result<()> {
+        if let Some(connecting) = &self.connecting {
+            connecting.set_handler(handler);
+            return Ok(());
+        }
         match &mut self.kind {
             InodeSocketKind::TcpListener { socket, .. } => socket.set_handler(handler),
             InodeSocketKind::TcpStream { socket, .. } => socket.set_handler(handler),
@@ -1517,3 +2522,146 @@ pub(crate) fn all_socket_rights() -> Rights {
         .union(Rights::SOCK_RECV_FROM)
         .union(Rights::SOCK_SEND_TO)
 }
+
+#[cfg(test)]
+mod tests {
+    use virtual_net::VirtualConnectedSocket;
+
+    use super::*;
+    use crate::net::{connect::ConnectResult, unix::UnixStream};
+
+    fn pre_socket() -> InodeSocket {
+        InodeSocket::new(InodeSocketKind::PreSocket {
+            props: SocketProperties {
+                family: Addressfamily::Unix,
+                ty: Socktype::Stream,
+                pt: SockProto::Ip,
+                only_v6: false,
+                reuse_port: false,
+                reuse_addr: false,
+                no_delay: None,
+                keep_alive: None,
+                dont_route: None,
+                send_buf_size: None,
+                recv_buf_size: None,
+                write_timeout: None,
+                read_timeout: None,
+                accept_timeout: None,
+                connect_timeout: None,
+                handler: None,
+            },
+            addr: None,
+        })
+    }
+
+    #[test]
+    fn options_are_kept_when_connecting_and_emulated() {
+        let mut socket = pre_socket();
+        socket
+            .set_opt_flag(WasiSocketOption::ReusePort, true)
+            .unwrap();
+        socket
+            .set_opt_flag(WasiSocketOption::NoDelay, true)
+            .unwrap();
+        socket.set_ttl(32).unwrap();
+        socket.set_keepalive_count(3).unwrap();
+        let timeout = Some(Duration::from_secs(5));
+        socket.set_opt_time(TimeType::ReadTimeout, timeout).unwrap();
+        socket
+            .set_opt_time(TimeType::KeepAliveIdle, timeout)
+            .unwrap();
+
+        // The Unix streams support neither TCP_NODELAY nor IP_TTL
+        let (stream, _) = UnixStream::pair();
+        let mut connected = InodeSocket::new(InodeSocketKind::TcpStream {
+            socket: Box::new(stream),
+            write_timeout: None,
+            read_timeout: None,
+        });
+        connected.inherit_options(&socket);
+
+        assert!(connected.get_opt_flag(WasiSocketOption::ReusePort).unwrap());
+        assert!(connected.get_opt_flag(WasiSocketOption::NoDelay).unwrap());
+        connected
+            .set_opt_flag(WasiSocketOption::NoDelay, false)
+            .unwrap();
+        assert!(!connected.get_opt_flag(WasiSocketOption::NoDelay).unwrap());
+        assert_eq!(connected.ttl().unwrap(), 32);
+        assert_eq!(connected.keepalive_count().unwrap(), 3);
+        assert_eq!(connected.opt_time(TimeType::ReadTimeout).unwrap(), timeout);
+        assert_eq!(
+            connected.opt_time(TimeType::KeepAliveIdle).unwrap(),
+            timeout
+        );
+
+        connected.set_opt_time(TimeType::Linger, timeout).unwrap();
+        assert_eq!(connected.opt_time(TimeType::Linger).unwrap(), timeout);
+    }
+
+    #[test]
+    fn connections_are_established_in_the_background() {
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        let socket = pre_socket();
+        let (tx, rx) = futures::channel::oneshot::channel::<ConnectResult>();
+        socket.inner.protected.write().unwrap().connecting = Some(PendingConnect::start(
+            async move { rx.await.unwrap_or(Err(NetworkError::ConnectionAborted)) },
+            None,
+        ));
+
+        let mut inner = socket.inner.protected.write().unwrap();
+        assert!(inner.poll_write_ready(&mut cx).is_pending());
+        drop(inner);
+        assert!(matches!(socket.status(), Ok(WasiSocketStatus::Opening)));
+
+        let (stream, _peer) = UnixStream::pair();
+        tx.send(Ok(Box::new(stream))).ok();
+        let mut inner = socket.inner.protected.write().unwrap();
+        assert!(matches!(
+            inner.poll_write_ready(&mut cx),
+            Poll::Ready(Ok(_))
+        ));
+        assert!(matches!(inner.kind, InodeSocketKind::TcpStream { .. }));
+        drop(inner);
+        assert_eq!(socket.take_error(), None);
+
+        // The failure is reported once through `SO_ERROR`
+        let socket = pre_socket();
+        let (tx, rx) = futures::channel::oneshot::channel::<ConnectResult>();
+        socket.inner.protected.write().unwrap().connecting = Some(PendingConnect::start(
+            async move { rx.await.unwrap_or(Err(NetworkError::ConnectionAborted)) },
+            None,
+        ));
+        tx.send(Err(NetworkError::ConnectionRefused)).ok();
+        assert!(matches!(socket.status(), Ok(WasiSocketStatus::Failed)));
+        assert_eq!(socket.take_error(), Some(Errno::Connrefused));
+        assert_eq!(socket.take_error(), None);
+    }
+
+    #[test]
+    fn half_closed_streams_keep_reading() {
+        let (stream, mut peer) = UnixStream::pair();
+        let mut socket = InodeSocket::new(InodeSocketKind::TcpStream {
+            socket: Box::new(stream),
+            write_timeout: None,
+            read_timeout: None,
+        });
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        let mut buf = [MaybeUninit::new(0); 16];
+
+        // The peer finds the end of the stream and can still answer
+        socket.shutdown(Shutdown::Write).unwrap();
+        assert_eq!(peer.try_recv(&mut buf).unwrap(), 0);
+        peer.try_send(b"pong").unwrap();
+        let mut inner = socket.inner.protected.write().unwrap();
+        assert!(matches!(inner.poll_read_ready(&mut cx), Poll::Ready(Ok(4))));
+        assert!(matches!(
+            inner.poll_write_ready(&mut cx),
+            Poll::Ready(Err(_))
+        ));
+        drop(inner);
+
+        socket.shutdown(Shutdown::Read).unwrap();
+        let mut inner = socket.inner.protected.write().unwrap();
+        assert!(matches!(inner.poll_read_ready(&mut cx), Poll::Ready(Ok(0))));
+    }
+}
@@ -0,0 +1,1035 @@
+//! Unix domain sockets, which live in memory and are found through the paths
+//! they are bound to in the file system rather than through the network.
+//!
+//! They implement the traits of `virtual_net` so that they are stored, read,
+//! written and polled like the other sockets. A socket also keeps a clone of
+//! itself in its [`UnixSocketState`], through which file descriptors are
+//! passed along with the data.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    mem::MaybeUninit,
+    net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, SocketAddrV4},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, Weak},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use virtual_mio::{InterestHandler, InterestType};
+use virtual_net::{
+    Interest, NetworkError, Result, SocketStatus, VirtualConnectedSocket,
+    VirtualConnectionlessSocket, VirtualIoSource, VirtualSocket, VirtualTcpListener,
+    VirtualTcpSocket, VirtualUdpSocket,
+};
+
+use crate::fs::Fd;
+
+/// Unix sockets have no IP address, this one is reported wherever an
+/// address is expected.
+pub const UNIX_SOCKET_ADDR: SocketAddr =
+    SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0));
+
+/// The default size of the buffer of each direction of a stream, the
+/// default `SO_SNDBUF` of Linux.
+pub(super) const DEFAULT_BUF_SIZE: usize = 212_992;
+
+/// How many datagrams a socket queues before its senders have to wait, the
+/// default `max_dgram_qlen` of Linux.
+pub(super) const MAX_QUEUED_DATAGRAMS: usize = 512;
+
+/// The state of an end of a stream.
+#[derive(Debug)]
+struct StreamEnd {
+    /// The bytes written by the other end which weren't read yet
+    buffer: VecDeque<u8>,
+    /// How many bytes `buffer` holds at most
+    capacity: usize,
+    /// Whether the other end won't write anymore
+    eof: bool,
+    read_shutdown: bool,
+    write_shutdown: bool,
+    /// How many bytes were ever written to `buffer`, and read from it
+    written: u64,
+    read: u64,
+    /// The file descriptors sent along with the bytes in `buffer`, by the
+    /// position in the stream of the first of them
+    fds: VecDeque<(u64, Vec<Fd>)>,
+    /// The file descriptors this end sends along with its next bytes
+    outgoing_fds: Vec<Fd>,
+    /// The file descriptors received along with the last bytes read
+    received_fds: Vec<Fd>,
+    linger: Option<Duration>,
+    keepalive: bool,
+    dontroute: bool,
+    interest: Interest,
+}
+
+impl Default for StreamEnd {
+    fn default() -> Self {
+        Self {
+            buffer: VecDeque::new(),
+            capacity: DEFAULT_BUF_SIZE,
+            eof: false,
+            read_shutdown: false,
+            write_shutdown: false,
+            written: 0,
+            read: 0,
+            fds: VecDeque::new(),
+            outgoing_fds: Vec::new(),
+            received_fds: Vec::new(),
+            linger: None,
+            keepalive: false,
+            dontroute: false,
+            interest: Interest::default(),
+        }
+    }
+}
+
+impl StreamEnd {
+    fn is_readable(&self) -> bool {
+        !self.buffer.is_empty() || self.eof || self.read_shutdown
+    }
+
+    fn free(&self) -> usize {
+        self.capacity.saturating_sub(self.buffer.len())
+    }
+}
+
+/// An end of a stream, which is closed once all its handles are dropped.
+#[derive(Debug)]
+struct StreamSide {
+    ends: Arc<Mutex<[StreamEnd; 2]>>,
+    side: usize,
+}
+
+impl Drop for StreamSide {
+    fn drop(&mut self) {
+        shutdown_ends(&self.ends, self.side, true, true);
+    }
+}
+
+fn shutdown_ends(ends: &Mutex<[StreamEnd; 2]>, side: usize, read: bool, write: bool) {
+    let mut ends = ends.lock().unwrap();
+    let peer = 1 - side;
+    if read && !ends[side].read_shutdown {
+        let end = &mut ends[side];
+        end.read_shutdown = true;
+        end.read = end.written;
+        end.buffer.clear();
+        end.fds.clear();
+        end.interest.notify(InterestType::Readable);
+        ends[peer].interest.notify(InterestType::Writable);
+    }
+    if write && !ends[side].write_shutdown {
+        ends[side].write_shutdown = true;
+        ends[peer].eof = true;
+        ends[peer].interest.notify(InterestType::Readable);
+        ends[side].interest.notify(InterestType::Writable);
+    }
+    if ends[side].read_shutdown && ends[side].write_shutdown {
+        ends[peer].interest.notify(InterestType::Closed);
+    }
+}
+
+/// An end of a connected Unix stream socket.
+#[derive(Debug, Clone)]
+pub struct UnixStream {
+    inner: Arc<StreamSide>,
+}
+
+impl UnixStream {
+    /// Creates both ends of a stream, like `socketpair`.
+    pub fn pair() -> (Self, Self) {
+        let ends = Arc::new(Mutex::new(Default::default()));
+        let end = |side| Self {
+            inner: Arc::new(StreamSide {
+                ends: Arc::clone(&ends),
+                side,
+            }),
+        };
+        (end(0), end(1))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, [StreamEnd; 2]> {
+        self.inner.ends.lock().unwrap()
+    }
+
+    fn side(&self) -> usize {
+        self.inner.side
+    }
+
+    fn peer(&self) -> usize {
+        1 - self.inner.side
+    }
+
+    fn shutdown_ends(&self, read: bool, write: bool) {
+        shutdown_ends(&self.inner.ends, self.side(), read, write);
+    }
+
+    /// Sends `fds` along with the next bytes written.
+    pub(crate) fn attach_fds(&self, fds: Vec<Fd>) {
+        self.lock()[self.side()].outgoing_fds = fds;
+    }
+
+    /// The file descriptors received along with the bytes of the last read.
+    pub(crate) fn take_received_fds(&self) -> Vec<Fd> {
+        std::mem::take(&mut self.lock()[self.side()].received_fds)
+    }
+}
+
+impl VirtualIoSource for UnixStream {
+    fn remove_handler(&mut self) {
+        self.lock()[self.side()].interest.handler.take();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut ends = self.lock();
+        let end = &mut ends[self.side()];
+        if end.is_readable() {
+            return Poll::Ready(Ok(end.buffer.len()));
+        }
+        end.interest.read_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut ends = self.lock();
+        if ends[self.side()].write_shutdown || ends[self.peer()].read_shutdown {
+            return Poll::Ready(Err(NetworkError::BrokenPipe));
+        }
+        let free = ends[self.peer()].free();
+        if free > 0 {
+            return Poll::Ready(Ok(free));
+        }
+        ends[self.side()].interest.write_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl VirtualSocket for UnixStream {
+    fn set_ttl(&mut self, _ttl: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(UNIX_SOCKET_ADDR)
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        let ends = self.lock();
+        Ok(
+            match ends[self.side()].eof && ends[self.side()].write_shutdown {
+                true => SocketStatus::Closed,
+                false => SocketStatus::Opened,
+            },
+        )
+    }
+
+    fn set_handler(&mut self, mut handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        let mut ends = self.lock();
+        if ends[self.side()].is_readable() {
+            handler.push_interest(InterestType::Readable);
+        }
+        if ends[self.peer()].free() > 0 {
+            handler.push_interest(InterestType::Writable);
+        }
+        ends[self.side()].interest.handler = Some(handler);
+        Ok(())
+    }
+}
+
+impl VirtualConnectedSocket for UnixStream {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.lock()[self.side()].linger = linger;
+        Ok(())
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        Ok(self.lock()[self.side()].linger)
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        let mut ends = self.lock();
+        if ends[self.side()].write_shutdown {
+            return Err(NetworkError::BrokenPipe);
+        }
+        if ends[self.peer()].read_shutdown {
+            return Err(NetworkError::BrokenPipe);
+        }
+        let amt = ends[self.peer()].free().min(data.len());
+        if amt == 0 && !data.is_empty() {
+            return Err(NetworkError::WouldBlock);
+        }
+        // The file descriptors travel with the first byte sent
+        let fds = match amt {
+            0 => Vec::new(),
+            _ => std::mem::take(&mut ends[self.side()].outgoing_fds),
+        };
+        let peer = &mut ends[self.peer()];
+        if !fds.is_empty() {
+            peer.fds.push_back((peer.written, fds));
+        }
+        peer.buffer.extend(&data[..amt]);
+        peer.written += amt as u64;
+        peer.interest.notify(InterestType::Readable);
+        Ok(amt)
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.shutdown_ends(true, true);
+        Ok(())
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        let mut ends = self.lock();
+        let end = &mut ends[self.side()];
+        if end.buffer.is_empty() {
+            return match end.eof || end.read_shutdown {
+                true => Ok(0),
+                false => Err(NetworkError::WouldBlock),
+            };
+        }
+        // A read stops before the bytes sent with other file descriptors
+        end.received_fds = match end.fds.front() {
+            Some((pos, _)) if *pos == end.read => end.fds.pop_front().unwrap().1,
+            _ => Vec::new(),
+        };
+        let mut amt = end.buffer.len().min(buf.len());
+        if let Some((pos, _)) = end.fds.front() {
+            amt = amt.min((pos - end.read) as usize);
+        }
+        for (dst, src) in buf.iter_mut().zip(end.buffer.drain(..amt)) {
+            dst.write(src);
+        }
+        end.read += amt as u64;
+        ends[self.peer()].interest.notify(InterestType::Writable);
+        Ok(amt)
+    }
+
+    fn try_peek(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        let ends = self.lock();
+        let end = &ends[self.side()];
+        if end.buffer.is_empty() {
+            return match end.eof || end.read_shutdown {
+                true => Ok(0),
+                false => Err(NetworkError::WouldBlock),
+            };
+        }
+        // The peeked bytes are the ones the next read returns, which stops
+        // before the bytes sent with other file descriptors
+        let mut amt = end.buffer.len().min(buf.len());
+        if let Some((pos, _)) = end.fds.iter().find(|(pos, _)| *pos > end.read) {
+            amt = amt.min((pos - end.read) as usize);
+        }
+        for (dst, src) in buf.iter_mut().zip(end.buffer.range(..amt)) {
+            dst.write(*src);
+        }
+        Ok(amt)
+    }
+}
+
+impl VirtualTcpSocket for UnixStream {
+    fn set_recv_buf_size(&mut self, size: usize) -> Result<()> {
+        self.lock()[self.side()].capacity = size;
+        Ok(())
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        Ok(self.lock()[self.side()].capacity)
+    }
+
+    fn set_send_buf_size(&mut self, size: usize) -> Result<()> {
+        self.lock()[self.peer()].capacity = size;
+        Ok(())
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        Ok(self.lock()[self.peer()].capacity)
+    }
+
+    fn set_nodelay(&mut self, _nodelay: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn set_keepalive(&mut self, keepalive: bool) -> Result<()> {
+        self.lock()[self.side()].keepalive = keepalive;
+        Ok(())
+    }
+
+    fn keepalive(&self) -> Result<bool> {
+        Ok(self.lock()[self.side()].keepalive)
+    }
+
+    fn set_dontroute(&mut self, dontroute: bool) -> Result<()> {
+        self.lock()[self.side()].dontroute = dontroute;
+        Ok(())
+    }
+
+    fn dontroute(&self) -> Result<bool> {
+        Ok(self.lock()[self.side()].dontroute)
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        Ok(UNIX_SOCKET_ADDR)
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        match how {
+            Shutdown::Read => self.shutdown_ends(true, false),
+            Shutdown::Write => self.shutdown_ends(false, true),
+            Shutdown::Both => self.shutdown_ends(true, true),
+        }
+        Ok(())
+    }
+
+    fn is_closed(&self) -> bool {
+        let ends = self.lock();
+        ends[self.side()].eof && ends[self.side()].buffer.is_empty()
+    }
+}
+
+/// The connections waiting to be accepted by a listener.
+#[derive(Debug)]
+struct Backlog {
+    pending: VecDeque<UnixStream>,
+    max: usize,
+    interest: Interest,
+}
+
+/// A Unix stream socket listening for connections.
+#[derive(Debug, Clone)]
+pub struct UnixListener {
+    backlog: Arc<Mutex<Backlog>>,
+}
+
+impl UnixListener {
+    /// Accepts a connection, as the stream whose clones can pass file
+    /// descriptors.
+    pub(crate) fn try_accept_stream(&self) -> Result<UnixStream> {
+        self.backlog
+            .lock()
+            .unwrap()
+            .pending
+            .pop_front()
+            .ok_or(NetworkError::WouldBlock)
+    }
+}
+
+impl VirtualIoSource for UnixListener {
+    fn remove_handler(&mut self) {
+        self.backlog.lock().unwrap().interest.handler.take();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut backlog = self.backlog.lock().unwrap();
+        if !backlog.pending.is_empty() {
+            return Poll::Ready(Ok(backlog.pending.len()));
+        }
+        backlog.interest.read_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    fn poll_write_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        Poll::Pending
+    }
+}
+
+impl VirtualTcpListener for UnixListener {
+    fn try_accept(&mut self) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        let stream = self.try_accept_stream()?;
+        Ok((Box::new(stream), UNIX_SOCKET_ADDR))
+    }
+
+    fn set_handler(&mut self, mut handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        let mut backlog = self.backlog.lock().unwrap();
+        if !backlog.pending.is_empty() {
+            handler.push_interest(InterestType::Readable);
+        }
+        backlog.interest.handler = Some(handler);
+        Ok(())
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(UNIX_SOCKET_ADDR)
+    }
+
+    fn set_ttl(&mut self, _ttl: u8) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        Err(NetworkError::Unsupported)
+    }
+}
+
+/// The datagrams sent to a socket which weren't received yet.
+#[derive(Debug, Default)]
+struct DatagramQueue {
+    /// The datagrams with the file descriptors sent along with them
+    datagrams: VecDeque<(Vec<u8>, Vec<Fd>)>,
+    /// The file descriptors sent along with the next datagram of the socket
+    outgoing_fds: Vec<Fd>,
+    /// The file descriptors received along with the last datagram
+    received_fds: Vec<Fd>,
+    /// The queues of the sockets which found this one full, they are told
+    /// when there is room again
+    blocked_senders: Vec<Weak<Mutex<DatagramQueue>>>,
+    interest: Interest,
+}
+
+/// The queue a Unix datagram socket receives from, which outlives the
+/// [`UnixDatagram`] when it's connected after being bound.
+#[derive(Debug, Clone, Default)]
+pub struct UnixInbox(Arc<Mutex<DatagramQueue>>);
+
+impl UnixInbox {
+    /// Sends `fds` along with the next datagram of the socket.
+    pub(crate) fn attach_fds(&self, fds: Vec<Fd>) {
+        self.0.lock().unwrap().outgoing_fds = fds;
+    }
+
+    /// The file descriptors received along with the last datagram.
+    pub(crate) fn take_received_fds(&self) -> Vec<Fd> {
+        std::mem::take(&mut self.0.lock().unwrap().received_fds)
+    }
+}
+
+/// A Unix datagram socket, which sends to the socket it's connected to.
+#[derive(Debug)]
+pub struct UnixDatagram {
+    inbox: UnixInbox,
+    peer: Option<Weak<Mutex<DatagramQueue>>>,
+}
+
+impl UnixDatagram {
+    /// Creates a socket receiving from `inbox`, or from a new queue.
+    pub fn new(inbox: Option<UnixInbox>) -> Self {
+        Self {
+            inbox: inbox.unwrap_or_default(),
+            peer: None,
+        }
+    }
+
+    /// Creates two sockets connected to each other, like `socketpair`.
+    pub fn pair() -> (Self, Self) {
+        let (mut a, mut b) = (Self::new(None), Self::new(None));
+        a.peer = Some(Arc::downgrade(&b.inbox.0));
+        b.peer = Some(Arc::downgrade(&a.inbox.0));
+        (a, b)
+    }
+
+    pub fn inbox(&self) -> UnixInbox {
+        self.inbox.clone()
+    }
+
+    /// The queue of the socket this one is connected to.
+    fn peer_queue(&self) -> Result<Arc<Mutex<DatagramQueue>>> {
+        self.peer
+            .as_ref()
+            .ok_or(NetworkError::NotConnected)?
+            .upgrade()
+            .ok_or(NetworkError::ConnectionRefused)
+    }
+
+    fn peer_has_room(&self) -> bool {
+        self.peer_queue()
+            .map(|queue| queue.lock().unwrap().datagrams.len() < MAX_QUEUED_DATAGRAMS)
+            .unwrap_or(false)
+    }
+}
+
+impl VirtualIoSource for UnixDatagram {
+    fn remove_handler(&mut self) {
+        self.inbox.0.lock().unwrap().interest.handler.take();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut queue = self.inbox.0.lock().unwrap();
+        if let Some((datagram, _)) = queue.datagrams.front() {
+            return Poll::Ready(Ok(datagram.len()));
+        }
+        queue.interest.read_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let peer = match self.peer_queue() {
+            Ok(peer) => peer,
+            Err(err) => return Poll::Ready(Err(err)),
+        };
+        {
+            let mut peer = peer.lock().unwrap();
+            if peer.datagrams.len() < MAX_QUEUED_DATAGRAMS {
+                return Poll::Ready(Ok(DEFAULT_BUF_SIZE));
+            }
+            peer.blocked_senders.push(Arc::downgrade(&self.inbox.0));
+        }
+        self.inbox.0.lock().unwrap().interest.write_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl VirtualSocket for UnixDatagram {
+    fn set_ttl(&mut self, _ttl: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(UNIX_SOCKET_ADDR)
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        Ok(SocketStatus::Opened)
+    }
+
+    fn set_handler(&mut self, mut handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        if self.peer_has_room() {
+            handler.push_interest(InterestType::Writable);
+        }
+        let mut queue = self.inbox.0.lock().unwrap();
+        if !queue.datagrams.is_empty() {
+            handler.push_interest(InterestType::Readable);
+        }
+        queue.interest.handler = Some(handler);
+        Ok(())
+    }
+}
+
+impl VirtualConnectionlessSocket for UnixDatagram {
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        // The datagrams can only be sent to the peer, the addresses of the
+        // Unix sockets don't fit in a `SocketAddr`
+        if addr != UNIX_SOCKET_ADDR {
+            return Err(NetworkError::AddressNotAvailable);
+        }
+        let peer = self.peer_queue()?;
+        // Both queues are never locked at once, as their sockets may be
+        // sending to each other
+        let fds = std::mem::take(&mut self.inbox.0.lock().unwrap().outgoing_fds);
+        {
+            let mut peer = peer.lock().unwrap();
+            if peer.datagrams.len() < MAX_QUEUED_DATAGRAMS {
+                peer.datagrams.push_back((data.to_vec(), fds));
+                peer.interest.notify(InterestType::Readable);
+                return Ok(data.len());
+            }
+            peer.blocked_senders.push(Arc::downgrade(&self.inbox.0));
+        }
+        self.inbox.0.lock().unwrap().outgoing_fds = fds;
+        Err(NetworkError::WouldBlock)
+    }
+
+    fn try_recv_from(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)> {
+        let (datagram, blocked_senders) = {
+            let mut queue = self.inbox.0.lock().unwrap();
+            let (datagram, fds) = queue
+                .datagrams
+                .pop_front()
+                .ok_or(NetworkError::WouldBlock)?;
+            queue.received_fds = fds;
+            (datagram, std::mem::take(&mut queue.blocked_senders))
+        };
+        for sender in blocked_senders.iter().filter_map(Weak::upgrade) {
+            sender
+                .lock()
+                .unwrap()
+                .interest
+                .notify(InterestType::Writable);
+        }
+
+        // Like on Linux, what doesn't fit in the buffer is discarded
+        let amt = datagram.len().min(buf.len());
+        for (dst, src) in buf.iter_mut().zip(&datagram[..amt]) {
+            dst.write(*src);
+        }
+        Ok((amt, UNIX_SOCKET_ADDR))
+    }
+
+    fn try_peek_from(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)> {
+        let queue = self.inbox.0.lock().unwrap();
+        let (datagram, _) = queue.datagrams.front().ok_or(NetworkError::WouldBlock)?;
+        let amt = datagram.len().min(buf.len());
+        for (dst, src) in buf.iter_mut().zip(&datagram[..amt]) {
+            dst.write(*src);
+        }
+        Ok((amt, UNIX_SOCKET_ADDR))
+    }
+}
+
+impl VirtualUdpSocket for UnixDatagram {
+    fn set_broadcast(&mut self, _broadcast: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn set_multicast_loop_v4(&mut self, _val: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn set_multicast_loop_v6(&mut self, _val: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn set_multicast_ttl_v4(&mut self, _ttl: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn join_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn join_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        Ok(self.peer.as_ref().map(|_| UNIX_SOCKET_ADDR))
+    }
+}
+
+/// The state of a socket of the `Unix` family beside its kind.
+#[derive(Debug, Clone, Default)]
+pub struct UnixSocketState {
+    /// The path the socket is bound to
+    pub path: Option<PathBuf>,
+    /// The path of the socket it's connected to
+    pub peer_path: Option<PathBuf>,
+    /// The socket itself once it's bound, listening or connected, which
+    /// passes the file descriptors
+    pub channel: Option<UnixChannel>,
+}
+
+impl UnixSocketState {
+    /// Sends `fds` along with the next data written to the socket.
+    pub(crate) fn attach_fds(&self, fds: Vec<Fd>) -> Result<()> {
+        match &self.channel {
+            Some(UnixChannel::Stream(stream)) => stream.attach_fds(fds),
+            Some(UnixChannel::Datagram(inbox)) => inbox.attach_fds(fds),
+            Some(UnixChannel::Listener(_)) | None => return Err(NetworkError::NotConnected),
+        }
+        Ok(())
+    }
+
+    /// The file descriptors received along with the last data read from
+    /// the socket.
+    pub(crate) fn take_received_fds(&self) -> Vec<Fd> {
+        match &self.channel {
+            Some(UnixChannel::Stream(stream)) => stream.take_received_fds(),
+            Some(UnixChannel::Datagram(inbox)) => inbox.take_received_fds(),
+            Some(UnixChannel::Listener(_)) | None => Vec::new(),
+        }
+    }
+}
+
+/// A handle on a Unix socket beside the one stored in its kind.
+#[derive(Debug, Clone)]
+pub enum UnixChannel {
+    Listener(UnixListener),
+    Stream(UnixStream),
+    /// The queue of a datagram socket, kept when it's connected after
+    /// being bound
+    Datagram(UnixInbox),
+}
+
+/// What a Unix socket bound to a path is.
+#[derive(Debug)]
+enum UnixEndpoint {
+    Listener(Weak<Mutex<Backlog>>),
+    Datagram(Weak<Mutex<DatagramQueue>>),
+}
+
+impl UnixEndpoint {
+    fn is_alive(&self) -> bool {
+        match self {
+            UnixEndpoint::Listener(backlog) => backlog.strong_count() > 0,
+            UnixEndpoint::Datagram(queue) => queue.strong_count() > 0,
+        }
+    }
+}
+
+/// The Unix sockets which listen or receive on a path, shared by all the
+/// processes.
+#[derive(Debug, Default)]
+pub(crate) struct UnixSockets {
+    endpoints: Mutex<HashMap<PathBuf, UnixEndpoint>>,
+}
+
+impl UnixSockets {
+    fn register(&self, path: &Path, endpoint: UnixEndpoint) -> Result<()> {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        endpoints.retain(|_, endpoint| endpoint.is_alive());
+        if endpoints.contains_key(path) {
+            return Err(NetworkError::AddressInUse);
+        }
+        endpoints.insert(path.to_owned(), endpoint);
+        Ok(())
+    }
+
+    /// Starts listening for the connections to `path`.
+    pub fn listen(&self, path: &Path, backlog: usize) -> Result<UnixListener> {
+        let backlog = Arc::new(Mutex::new(Backlog {
+            pending: VecDeque::new(),
+            max: backlog.max(1),
+            interest: Interest::default(),
+        }));
+        self.register(path, UnixEndpoint::Listener(Arc::downgrade(&backlog)))?;
+        Ok(UnixListener { backlog })
+    }
+
+    /// Makes the datagrams sent to `path` go to `inbox`.
+    pub fn bind_datagram(&self, path: &Path, inbox: &UnixInbox) -> Result<()> {
+        self.register(path, UnixEndpoint::Datagram(Arc::downgrade(&inbox.0)))
+    }
+
+    /// Connects to the stream socket listening on `path`.
+    pub fn connect(&self, path: &Path) -> Result<UnixStream> {
+        let backlog = match self.endpoints.lock().unwrap().get(path) {
+            Some(UnixEndpoint::Listener(backlog)) => backlog.upgrade(),
+            Some(UnixEndpoint::Datagram(_)) => return Err(NetworkError::Unsupported),
+            None => None,
+        };
+        let backlog = backlog.ok_or(NetworkError::ConnectionRefused)?;
+        let mut backlog = backlog.lock().unwrap();
+        if backlog.pending.len() >= backlog.max {
+            return Err(NetworkError::WouldBlock);
+        }
+        let (client, server) = UnixStream::pair();
+        backlog.pending.push_back(server);
+        backlog.interest.notify(InterestType::Readable);
+        Ok(client)
+    }
+
+    /// Connects `socket` to the datagram socket bound to `path`.
+    pub fn connect_datagram(&self, socket: &mut UnixDatagram, path: &Path) -> Result<()> {
+        let queue = match self.endpoints.lock().unwrap().get(path) {
+            Some(UnixEndpoint::Datagram(queue)) if queue.strong_count() > 0 => queue.clone(),
+            Some(UnixEndpoint::Listener(_)) => return Err(NetworkError::Unsupported),
+            _ => return Err(NetworkError::ConnectionRefused),
+        };
+        socket.peer = Some(queue);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicU64, RwLock};
+
+    use wasmer_wasix_types::wasi::{Fdflags, Filestat, Rights};
+
+    use super::*;
+    use crate::fs::{InodeVal, Kind, WasiInodes};
+
+    fn buffer_fd(inodes: &WasiInodes) -> Fd {
+        let inode = inodes.add_inode_val(InodeVal {
+            stat: RwLock::new(Filestat::default()),
+            is_preopened: false,
+            name: "buffer".into(),
+            kind: RwLock::new(Kind::Buffer { buffer: Vec::new() }),
+        });
+        Fd {
+            rights: Rights::all(),
+            rights_inheriting: Rights::all(),
+            flags: Fdflags::empty(),
+            offset: Arc::new(AtomicU64::new(0)),
+            open_flags: 0,
+            inode,
+            is_stdio: false,
+        }
+    }
+
+    fn recv(socket: &mut dyn VirtualTcpSocket, len: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![MaybeUninit::new(0); len];
+        let amt = socket.try_recv(&mut buf)?;
+        Ok(buf[..amt]
+            .iter()
+            .map(|b| unsafe { b.assume_init() })
+            .collect())
+    }
+
+    #[test]
+    fn streams_are_connected_through_their_path() {
+        let sockets = UnixSockets::default();
+        let path = Path::new("/tmp/server.sock");
+        let mut listener = sockets.listen(path, 8).unwrap();
+        assert_eq!(
+            sockets.listen(path, 8).unwrap_err(),
+            NetworkError::AddressInUse
+        );
+
+        let mut client = sockets.connect(path).unwrap();
+        let (mut server, _) = listener.try_accept().unwrap();
+        assert_eq!(listener.try_accept().unwrap_err(), NetworkError::WouldBlock);
+
+        assert_eq!(client.try_send(b"ping").unwrap(), 4);
+        assert_eq!(recv(server.as_mut(), 16).unwrap(), b"ping");
+        assert_eq!(
+            recv(server.as_mut(), 16).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+
+        // The other end reads what was sent before the end of the stream
+        server.try_send(b"pong").unwrap();
+        server.shutdown(Shutdown::Write).unwrap();
+        assert_eq!(recv(&mut client, 16).unwrap(), b"pong");
+        assert_eq!(recv(&mut client, 16).unwrap(), b"");
+
+        drop(server);
+        assert_eq!(
+            client.try_send(b"ping").unwrap_err(),
+            NetworkError::BrokenPipe
+        );
+
+        // The path is free again once the listener is gone
+        drop(listener);
+        assert_eq!(
+            sockets.connect(path).unwrap_err(),
+            NetworkError::ConnectionRefused
+        );
+        sockets.listen(path, 8).unwrap();
+    }
+
+    #[test]
+    fn full_streams_block_the_writer() {
+        let (mut a, mut b) = UnixStream::pair();
+        a.set_send_buf_size(4).unwrap();
+
+        assert_eq!(a.try_send(b"abcdef").unwrap(), 4);
+        assert_eq!(a.try_send(b"ef").unwrap_err(), NetworkError::WouldBlock);
+        assert_eq!(recv(&mut b, 2).unwrap(), b"ab");
+        assert_eq!(a.try_send(b"ef").unwrap(), 2);
+        assert_eq!(recv(&mut b, 16).unwrap(), b"cdef");
+    }
+
+    #[test]
+    fn fds_are_received_with_the_bytes_sent_along() {
+        let inodes = WasiInodes::new();
+        let fd = buffer_fd(&inodes);
+        let ino = fd.inode.ino();
+
+        let (mut a, mut b) = UnixStream::pair();
+        a.try_send(b"ab").unwrap();
+        a.attach_fds(vec![fd.clone()]);
+        a.try_send(b"cd").unwrap();
+        a.try_send(b"ef").unwrap();
+
+        // The reads stop before the bytes the descriptors came with
+        assert_eq!(recv(&mut b, 16).unwrap(), b"ab");
+        assert!(b.take_received_fds().is_empty());
+        assert_eq!(recv(&mut b, 16).unwrap(), b"cdef");
+        let fds = b.take_received_fds();
+        assert_eq!(fds.len(), 1);
+        assert_eq!(fds[0].inode.ino(), ino);
+
+        let (mut a, mut b) = UnixDatagram::pair();
+        a.inbox().attach_fds(vec![fd]);
+        a.try_send_to(b"first", UNIX_SOCKET_ADDR).unwrap();
+        a.try_send_to(b"second", UNIX_SOCKET_ADDR).unwrap();
+
+        let mut buf = [MaybeUninit::new(0); 16];
+        b.try_recv_from(&mut buf).unwrap();
+        assert_eq!(b.inbox().take_received_fds().len(), 1);
+        b.try_recv_from(&mut buf).unwrap();
+        assert!(b.inbox().take_received_fds().is_empty());
+    }
+
+    #[test]
+    fn peeking_leaves_the_data_to_be_read() {
+        let inodes = WasiInodes::new();
+        let (mut a, mut b) = UnixStream::pair();
+        a.try_send(b"ab").unwrap();
+        a.attach_fds(vec![buffer_fd(&inodes)]);
+        a.try_send(b"cd").unwrap();
+
+        let mut buf = [MaybeUninit::new(0); 16];
+        assert_eq!(b.try_peek(&mut buf).unwrap(), 2);
+        assert_eq!(b.try_peek(&mut buf[..1]).unwrap(), 1);
+        assert_eq!(recv(&mut b, 16).unwrap(), b"ab");
+        assert_eq!(b.try_peek(&mut buf).unwrap(), 2);
+        assert!(b.take_received_fds().is_empty());
+        assert_eq!(recv(&mut b, 16).unwrap(), b"cd");
+        assert_eq!(b.take_received_fds().len(), 1);
+        assert_eq!(b.try_peek(&mut buf).unwrap_err(), NetworkError::WouldBlock);
+
+        let (mut a, mut b) = UnixDatagram::pair();
+        a.try_send_to(b"first", UNIX_SOCKET_ADDR).unwrap();
+        assert_eq!(b.try_peek_from(&mut buf).unwrap().0, 5);
+        assert_eq!(b.try_recv_from(&mut buf).unwrap().0, 5);
+        assert_eq!(
+            b.try_peek_from(&mut buf).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+    }
+
+    #[test]
+    fn datagrams_keep_their_boundaries() {
+        let sockets = UnixSockets::default();
+        let path = Path::new("/tmp/dgram.sock");
+        let mut server = UnixDatagram::new(None);
+        sockets.bind_datagram(path, &server.inbox()).unwrap();
+
+        let mut client = UnixDatagram::new(None);
+        assert_eq!(
+            client.try_send_to(b"a", UNIX_SOCKET_ADDR).unwrap_err(),
+            NetworkError::NotConnected
+        );
+        sockets.connect_datagram(&mut client, path).unwrap();
+        client.try_send_to(b"first", UNIX_SOCKET_ADDR).unwrap();
+        client.try_send_to(b"second", UNIX_SOCKET_ADDR).unwrap();
+
+        let mut buf = [MaybeUninit::new(0); 3];
+        assert_eq!(server.try_recv_from(&mut buf).unwrap().0, 3);
+        let mut buf = [MaybeUninit::new(0); 16];
+        assert_eq!(server.try_recv_from(&mut buf).unwrap().0, 6);
+        assert_eq!(
+            server.try_recv_from(&mut buf).unwrap_err(),
+            NetworkError::WouldBlock
+        );
+
+        drop(server);
+        assert_eq!(
+            client.try_send_to(b"third", UNIX_SOCKET_ADDR).unwrap_err(),
+            NetworkError::ConnectionRefused
+        );
+    }
+}
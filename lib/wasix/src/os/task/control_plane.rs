@@ -1,12 +1,36 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet, VecDeque},
+    future::Future,
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc, RwLock,
     },
 };
 
-use crate::{WasiProcess, WasiProcessId};
+use futures::Stream;
+use tokio::sync::broadcast;
+use virtual_net::DynVirtualNetworking;
+use wasmer_types::ModuleHash;
+use wasmer_wasix_types::{
+    types::Signal,
+    wasi::{Errno, ExitCode},
+};
+
+use crate::{
+    fs::{FileLocks, FileWatchers, SharedMemoryObjects},
+    net::{
+        capture::PacketCapture, dns::DnsRecords, firewall::Firewall, loopback::LoopbackNetworking,
+        mdns::MdnsServices, shaping::TrafficShaping, unix::UnixSockets,
+    },
+    VirtualTaskManager, WasiProcess, WasiProcessId,
+};
+
+use super::{
+    process::{LockableWasiProcessInner, WasiProcessState},
+    supervisor::{RestartStrategy, Supervisor},
+    task_group::{TaskGroup, TaskGroupLimits},
+    thread::WasiThreadId,
+};
 
 #[derive(Debug, Clone)]
 pub struct WasiControlPlane {
@@ -36,11 +60,32 @@ impl WasiControlPlaneHandle {
 }
 
 #[derive(Debug, Clone)]
-pub struct ControlPlaneConfig {}
+pub struct ControlPlaneConfig {
+    /// Whether the processes share a network in memory, where they reach
+    /// each other by IP address and port, instead of using the one of their
+    /// runtime
+    pub loopback_network: bool,
+    /// The records the names are resolved from, before the network of the
+    /// processes resolves them
+    pub dns: Option<DnsRecords>,
+    /// Where the traffic of the processes is recorded, if anywhere
+    pub capture: Option<PacketCapture>,
+    /// How the traffic of the processes is shaped, if at all
+    pub shaping: Option<TrafficShaping>,
+    /// The rules the traffic of the processes goes through, and the ports
+    /// of the network their ports are mapped to, if any
+    pub firewall: Option<Firewall>,
+}
 
 impl ControlPlaneConfig {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            loopback_network: false,
+            dns: None,
+            capture: None,
+            shaping: None,
+            firewall: None,
+        }
     }
 }
 
@@ -50,32 +95,181 @@ impl Default for ControlPlaneConfig {
     }
 }
 
+/// A change in the lifecycle of a process or thread, see
+/// [`WasiControlPlane::subscribe`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlPlaneEvent {
+    /// A process was created, `ppid` is set for child processes.
+    ProcessSpawned {
+        pid: WasiProcessId,
+        ppid: Option<WasiProcessId>,
+    },
+    /// A thread was started within a process.
+    ThreadSpawned {
+        pid: WasiProcessId,
+        tid: WasiThreadId,
+    },
+    /// A process terminated.
+    ProcessExited {
+        pid: WasiProcessId,
+        exit_code: ExitCode,
+    },
+    /// A signal was delivered to the threads of a process.
+    SignalDelivered { pid: WasiProcessId, signal: Signal },
+    /// A supervised process was restarted, see [`WasiControlPlane::supervise`].
+    ProcessRestarted {
+        /// The process which terminated
+        previous: WasiProcessId,
+        /// The process which replaces it
+        pid: WasiProcessId,
+        /// Number of times the process has been restarted so far
+        restarts: u32,
+    },
+}
+
+/// A snapshot of a process, see [`WasiControlPlane::processes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessInfo {
+    pub pid: WasiProcessId,
+    /// The parent process, if it is still running
+    pub ppid: Option<WasiProcessId>,
+    /// Hash of the module the process is running, once instantiated
+    pub module_hash: Option<ModuleHash>,
+    /// Number of threads running in the process
+    pub thread_count: u32,
+    /// Size of the memory of the process in bytes
+    pub memory_usage: u64,
+    pub state: WasiProcessState,
+    /// The threads running in the process, sorted by ID
+    pub threads: Vec<ThreadInfo>,
+}
+
+/// A snapshot of a thread, see [`ProcessInfo::threads`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadInfo {
+    pub tid: WasiThreadId,
+    /// Name given to the thread with `thread_set_name`
+    pub name: Option<String>,
+}
+
+impl ProcessInfo {
+    fn new(process: &WasiProcess) -> Self {
+        let ppid = process.ppid();
+        Self {
+            pid: process.pid(),
+            ppid: (ppid.raw() != 0).then_some(ppid),
+            module_hash: process.module_hash(),
+            thread_count: process.active_threads(),
+            memory_usage: process.memory_usage(),
+            state: process.state(),
+            threads: process
+                .threads()
+                .iter()
+                .map(|thread| ThreadInfo {
+                    tid: thread.tid(),
+                    name: thread.name(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Number of events a subscriber can fall behind before it misses some.
+const EVENT_CAPACITY: usize = 1024;
+
 #[derive(Debug)]
 struct State {
     /// Total number of active tasks (threads) across all processes.
     task_count: Arc<AtomicUsize>,
 
+    /// Publishes the lifecycle events to subscribers.
+    events: broadcast::Sender<ControlPlaneEvent>,
+
+    /// The advisory locks of the files, shared by all the processes.
+    file_locks: FileLocks,
+
+    /// The watches on files and directories, shared by all the processes.
+    file_watchers: FileWatchers,
+
+    /// The Unix sockets bound to paths, shared by all the processes.
+    unix_sockets: UnixSockets,
+
+    /// The named shared memory objects, shared by all the processes.
+    shared_memory: SharedMemoryObjects,
+
+    /// The network shared by all the processes, when they don't use the
+    /// one of their runtime.
+    network: Option<DynVirtualNetworking>,
+
+    /// The records the names are resolved from first.
+    dns: Option<DnsRecords>,
+
+    /// Where the traffic of the processes is recorded, if anywhere.
+    capture: Option<PacketCapture>,
+
+    /// How the traffic of the processes is shaped, if at all.
+    shaping: Option<TrafficShaping>,
+
+    /// The rules the traffic of the processes goes through, if any.
+    firewall: Option<Firewall>,
+
+    /// The services the processes registered over mDNS.
+    mdns: MdnsServices,
+
     /// Mutable state.
     mutable: RwLock<MutableState>,
 }
 
+/// The largest process or thread ID, guests see them as a signed `pid_t`.
+const MAX_ID: u32 = i32::MAX as u32;
+
+/// Number of released IDs that are held back before they are handed out
+/// again, so a new process is not mistaken for one that just terminated.
+const ID_REUSE_DELAY: usize = 1024;
+
 #[derive(Debug)]
 struct MutableState {
     /// Seed used to generate process ID's
     process_seed: u32,
+    /// The process and thread IDs that are currently handed out
+    used_ids: HashSet<u32>,
+    /// Released IDs that can be reused, oldest first
+    free_ids: VecDeque<u32>,
     /// The processes running on this machine
     processes: HashMap<WasiProcessId, WasiProcess>,
-    // TODO: keep a queue of terminated process ids for id reuse.
+    /// The task groups, by name
+    task_groups: HashMap<String, TaskGroup>,
 }
 
 impl WasiControlPlane {
     pub fn new() -> Self {
+        Self::new_with_config(ControlPlaneConfig::default())
+    }
+
+    pub fn new_with_config(config: ControlPlaneConfig) -> Self {
+        let network = config
+            .loopback_network
+            .then(|| Arc::new(LoopbackNetworking::new()) as DynVirtualNetworking);
         Self {
             state: Arc::new(State {
                 task_count: Arc::new(AtomicUsize::new(0)),
+                events: broadcast::channel(EVENT_CAPACITY).0,
+                file_locks: Default::default(),
+                file_watchers: Default::default(),
+                unix_sockets: Default::default(),
+                shared_memory: Default::default(),
+                network,
+                dns: config.dns,
+                capture: config.capture,
+                shaping: config.shaping,
+                firewall: config.firewall,
+                mdns: Default::default(),
                 mutable: RwLock::new(MutableState {
                     process_seed: 0,
+                    used_ids: Default::default(),
+                    free_ids: Default::default(),
                     processes: Default::default(),
+                    task_groups: Default::default(),
                 }),
             }),
         }
@@ -88,17 +282,136 @@ impl WasiControlPlane {
     /// Register a new task.
     ///
     // Currently just increments the task counter.
-    pub(crate) fn register_task(&self) -> Result<TaskCountGuard, ControlPlaneError> {
+    pub(crate) fn register_task(
+        &self,
+        task_group: Option<&TaskGroup>,
+    ) -> Result<TaskCountGuard, ControlPlaneError> {
+        let group = task_group.map(TaskGroup::register_task).transpose()?;
         self.state.task_count.fetch_add(1, Ordering::SeqCst);
-        Ok(TaskCountGuard(self.state.task_count.clone()))
+        Ok(TaskCountGuard {
+            total: self.state.task_count.clone(),
+            group,
+        })
+    }
+
+    /// Subscribes to the lifecycle events of all the processes and threads.
+    ///
+    /// A subscriber that falls behind by more than [`EVENT_CAPACITY`] events
+    /// misses the oldest ones. The stream ends when the control plane is
+    /// dropped.
+    pub fn subscribe(&self) -> impl Stream<Item = ControlPlaneEvent> + Send + 'static {
+        let rx = self.state.events.subscribe();
+        futures::stream::unfold(rx, |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => return Some((event, rx)),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        tracing::debug!(missed, "control plane subscriber fell behind");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    /// Publishes an event to the subscribers.
+    pub(crate) fn emit(&self, event: ControlPlaneEvent) {
+        // Sending only fails when there are no subscribers.
+        self.state.events.send(event).ok();
+    }
+
+    /// The advisory locks the processes hold on files.
+    pub(crate) fn file_locks(&self) -> &FileLocks {
+        &self.state.file_locks
+    }
+
+    /// The watches the processes have on files and directories.
+    pub(crate) fn file_watchers(&self) -> &FileWatchers {
+        &self.state.file_watchers
+    }
+
+    /// The Unix sockets the processes bound to paths.
+    pub(crate) fn unix_sockets(&self) -> &UnixSockets {
+        &self.state.unix_sockets
+    }
+
+    /// The shared memory objects the processes named.
+    pub(crate) fn shared_memory(&self) -> &SharedMemoryObjects {
+        &self.state.shared_memory
+    }
+
+    /// The network the processes share, when they don't use the one of
+    /// their runtime, see [`ControlPlaneConfig::loopback_network`].
+    pub fn network(&self) -> Option<&DynVirtualNetworking> {
+        self.state.network.as_ref()
+    }
+
+    /// The records the names are resolved from first, see
+    /// [`ControlPlaneConfig::dns`].
+    pub fn dns(&self) -> Option<&DnsRecords> {
+        self.state.dns.as_ref()
+    }
+
+    /// Where the traffic of the processes is recorded, see
+    /// [`ControlPlaneConfig::capture`].
+    pub fn capture(&self) -> Option<&PacketCapture> {
+        self.state.capture.as_ref()
+    }
+
+    /// How the traffic of the processes is shaped, see
+    /// [`ControlPlaneConfig::shaping`].
+    pub fn shaping(&self) -> Option<&TrafficShaping> {
+        self.state.shaping.as_ref()
+    }
+
+    /// The rules the traffic of the processes goes through, see
+    /// [`ControlPlaneConfig::firewall`].
+    pub fn firewall(&self) -> Option<&Firewall> {
+        self.state.firewall.as_ref()
+    }
+
+    /// The services the processes registered over mDNS, which the embedder
+    /// answers the queries of the network for with [`MdnsServices::respond`].
+    pub fn mdns(&self) -> &MdnsServices {
+        &self.state.mdns
     }
 
     /// Creates a new process
-    // FIXME: De-register terminated processes!
-    // Currently they just accumulate.
     pub fn new_process(&self) -> Result<WasiProcess, ControlPlaneError> {
+        let proc = self.register_process(None)?;
+        self.emit(ControlPlaneEvent::ProcessSpawned {
+            pid: proc.pid(),
+            ppid: None,
+        });
+        Ok(proc)
+    }
+
+    /// Creates a new process which belongs to a task group, it shares the
+    /// limits of the group with the other processes of the group.
+    pub fn new_process_in_group(
+        &self,
+        group: &TaskGroup,
+    ) -> Result<WasiProcess, ControlPlaneError> {
+        if let Some(max) = group.limits().max_memory {
+            if group.memory_usage() >= max {
+                return Err(ControlPlaneError::MemoryLimitReached { max });
+            }
+        }
+        let proc = self.register_process(Some(group.clone()))?;
+        self.emit(ControlPlaneEvent::ProcessSpawned {
+            pid: proc.pid(),
+            ppid: None,
+        });
+        Ok(proc)
+    }
+
+    fn register_process(
+        &self,
+        task_group: Option<TaskGroup>,
+    ) -> Result<WasiProcess, ControlPlaneError> {
         // Create the process first to do all the allocations before locking.
         let mut proc = WasiProcess::new(WasiProcessId::from(0), self.handle());
+        proc.task_group = task_group;
 
         let mut mutable = self.state.mutable.write().unwrap();
 
@@ -108,6 +421,57 @@ impl WasiControlPlane {
         Ok(proc)
     }
 
+    /// Creates a new process which is a child of `parent`.
+    ///
+    /// Once the child terminates it stays around as a zombie until the parent
+    /// joins on it.
+    pub fn new_child_process(
+        &self,
+        parent: &WasiProcess,
+    ) -> Result<WasiProcess, ControlPlaneError> {
+        let proc = self.register_process(parent.task_group.clone())?;
+        proc.set_parent(parent);
+        self.emit(ControlPlaneEvent::ProcessSpawned {
+            pid: proc.pid(),
+            ppid: Some(parent.pid()),
+        });
+        Ok(proc)
+    }
+
+    /// Returns `true` if this exact process is still known to the control plane.
+    pub(crate) fn is_registered(
+        &self,
+        pid: WasiProcessId,
+        inner: &LockableWasiProcessInner,
+    ) -> bool {
+        let mutable = self.state.mutable.read().unwrap();
+        mutable
+            .processes
+            .get(&pid)
+            .is_some_and(|p| Arc::ptr_eq(&p.inner, inner))
+    }
+
+    /// Removes a terminated process so it can no longer be looked up, its
+    /// ID is released for reuse.
+    pub(crate) fn remove_process(&self, pid: WasiProcessId, inner: &LockableWasiProcessInner) {
+        let mut mutable = self.state.mutable.write().unwrap();
+        if let Some(p) = mutable.processes.get(&pid) {
+            // The ID might already belong to another process.
+            if Arc::ptr_eq(&p.inner, inner) {
+                mutable.processes.remove(&pid);
+                mutable.release_id(pid.raw());
+                self.state.mdns.unregister_process(pid.raw());
+            }
+        }
+    }
+
+    /// Releases an ID handed out by [`WasiControlPlane::generate_id`] so it
+    /// can eventually be reused.
+    pub(crate) fn release_id(&self, id: u32) {
+        let mut mutable = self.state.mutable.write().unwrap();
+        mutable.release_id(id);
+    }
+
     /// Generates a new process ID
     pub fn generate_id(&self) -> Result<WasiProcessId, ControlPlaneError> {
         let mut mutable = self.state.mutable.write().unwrap();
@@ -124,19 +488,120 @@ impl WasiControlPlane {
             .get(&pid)
             .cloned()
     }
+
+    /// Supervises a process spawned by `spawn`, which is called again to
+    /// restart the process according to `strategy`.
+    ///
+    /// Nothing happens until [`Supervisor::run`] is awaited.
+    pub fn supervise<F, Fut, E>(
+        &self,
+        strategy: RestartStrategy,
+        tasks: Arc<dyn VirtualTaskManager>,
+        spawn: F,
+    ) -> Supervisor<F>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<WasiProcess, E>>,
+    {
+        Supervisor::new(self.clone(), strategy, tasks, spawn)
+    }
+
+    /// Creates a named task group, whose processes share the given limits.
+    pub fn create_task_group(
+        &self,
+        name: impl Into<String>,
+        limits: TaskGroupLimits,
+    ) -> Result<TaskGroup, ControlPlaneError> {
+        let name = name.into();
+        let mut mutable = self.state.mutable.write().unwrap();
+        if mutable.task_groups.contains_key(&name) {
+            return Err(ControlPlaneError::TaskGroupExists(name));
+        }
+        let group = TaskGroup::new(name.clone(), limits, self.handle());
+        mutable.task_groups.insert(name, group.clone());
+        Ok(group)
+    }
+
+    /// Gets a task group by its name
+    pub fn task_group(&self, name: &str) -> Option<TaskGroup> {
+        let mutable = self.state.mutable.read().unwrap();
+        mutable.task_groups.get(name).cloned()
+    }
+
+    /// Gets all the processes which belong to a task group
+    pub fn task_group_processes(&self, group: &TaskGroup) -> Vec<WasiProcess> {
+        let mutable = self.state.mutable.read().unwrap();
+        mutable
+            .processes
+            .values()
+            .filter(|process| process.task_group.as_ref().is_some_and(|g| g.ptr_eq(group)))
+            .cloned()
+            .collect()
+    }
+
+    /// Takes a snapshot of all the processes, including the terminated ones
+    /// which have not been reaped yet, ordered by their ID.
+    pub fn processes(&self) -> Vec<ProcessInfo> {
+        let processes: Vec<_> = {
+            let mutable = self.state.mutable.read().unwrap();
+            mutable.processes.values().cloned().collect()
+        };
+        let mut infos: Vec<_> = processes.iter().map(ProcessInfo::new).collect();
+        infos.sort_by_key(|info| info.pid);
+        infos
+    }
+
+    /// Gets all the processes which belong to a process group
+    pub fn process_group(&self, pgid: WasiProcessId) -> Vec<WasiProcess> {
+        // The processes are cloned first so their locks are not taken while
+        // the control plane is locked.
+        let processes: Vec<_> = {
+            let mutable = self.state.mutable.read().unwrap();
+            mutable.processes.values().cloned().collect()
+        };
+        processes
+            .into_iter()
+            .filter(|process| process.pgid() == pgid)
+            .collect()
+    }
+
+    /// Sends a signal to every process in a process group
+    pub fn signal_process_group(&self, pgid: WasiProcessId, signal: Signal) -> Result<(), Errno> {
+        let group = self.process_group(pgid);
+        if group.is_empty() {
+            return Err(Errno::Srch);
+        }
+        for process in group {
+            process.signal_process(signal);
+        }
+        Ok(())
+    }
 }
 
 impl MutableState {
     fn next_process_id(&mut self) -> Result<WasiProcessId, ControlPlaneError> {
-        // TODO: reuse terminated ids, handle wrap-around, ...
-        let id = self.process_seed.checked_add(1).ok_or({
-            ControlPlaneError::TaskLimitReached {
-                max: u32::MAX as usize,
-            }
+        let id = if self.free_ids.len() > ID_REUSE_DELAY {
+            self.free_ids.pop_front()
+        } else if self.process_seed < MAX_ID {
+            self.process_seed += 1;
+            Some(self.process_seed)
+        } else {
+            // Every ID has been handed out once, so wrap around and reuse
+            // released ones even if they were only just released.
+            self.free_ids.pop_front()
+        };
+        let id = id.ok_or(ControlPlaneError::TaskLimitReached {
+            max: MAX_ID as usize,
         })?;
-        self.process_seed = id;
+        self.used_ids.insert(id);
         Ok(WasiProcessId::from(id))
     }
+
+    fn release_id(&mut self, id: u32) {
+        if self.used_ids.remove(&id) {
+            self.free_ids.push_back(id);
+        }
+    }
 }
 
 impl Default for WasiControlPlane {
@@ -147,11 +612,18 @@ impl Default for WasiControlPlane {
 
 /// Guard that ensures the [`WasiControlPlane`] task counter is decremented when dropped.
 #[derive(Debug)]
-pub struct TaskCountGuard(Arc<AtomicUsize>);
+pub struct TaskCountGuard {
+    total: Arc<AtomicUsize>,
+    /// The counter of the task group of the process, if any
+    group: Option<Arc<AtomicUsize>>,
+}
 
 impl Drop for TaskCountGuard {
     fn drop(&mut self) {
-        self.0.fetch_sub(1, Ordering::SeqCst);
+        self.total.fetch_sub(1, Ordering::SeqCst);
+        if let Some(group) = &self.group {
+            group.fetch_sub(1, Ordering::SeqCst);
+        }
     }
 }
 
@@ -163,4 +635,256 @@ pub enum ControlPlaneError {
         /// The maximum number of tasks.
         max: usize,
     },
+    /// The memory limit of the task group has been reached.
+    #[error("The memory limit of the task group has been reached ({max} bytes)")]
+    MemoryLimitReached {
+        /// The maximum combined memory in bytes.
+        max: u64,
+    },
+    /// A task group with the same name already exists.
+    #[error("A task group named \"{0}\" already exists")]
+    TaskGroupExists(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use wasmer_wasix_types::wasix::ThreadStartType;
+
+    use crate::os::task::process::WasiProcessWait;
+
+    use super::*;
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn zombie_children_are_reaped_on_join() {
+        let plane = WasiControlPlane::new();
+        let mut parent = plane.new_process().unwrap();
+        let _parent_thread = parent.new_thread(ThreadStartType::MainThread).unwrap();
+        let child = plane.new_child_process(&parent).unwrap();
+        let child_thread = child.new_thread(ThreadStartType::MainThread).unwrap();
+        assert_eq!(child.ppid(), parent.pid());
+        assert!(!child.is_zombie());
+
+        drop(child_thread);
+        assert!(child.is_zombie());
+        assert!(plane.get_process(child.pid()).is_some());
+
+        let (pid, code) = parent.join_any_child().await.unwrap().unwrap();
+        assert_eq!(pid, child.pid());
+        assert_eq!(code.raw(), 0);
+        assert!(!child.is_zombie());
+        assert!(plane.get_process(child.pid()).is_none());
+        assert!(parent.lock().children.is_empty());
+        assert_eq!(parent.try_join_any_child(), Err(Errno::Child));
+    }
+
+    #[test]
+    fn parents_receive_sigchld() {
+        let plane = WasiControlPlane::new();
+        let parent = plane.new_process().unwrap();
+        let parent_thread = parent.new_thread(ThreadStartType::MainThread).unwrap();
+        let child = plane.new_child_process(&parent).unwrap();
+        let child_thread = child.new_thread(ThreadStartType::MainThread).unwrap();
+        assert!(!parent_thread.has_signal(&[Signal::Sigchld]));
+
+        // Waiting on the children must not divert the signal to them
+        let _wait = WasiProcessWait::new(&parent);
+        drop(child_thread);
+        assert!(parent_thread.has_signal(&[Signal::Sigchld]));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn subscribers_see_the_lifecycle_events() {
+        let plane = WasiControlPlane::new();
+        let events = plane.subscribe();
+
+        let parent = plane.new_process().unwrap();
+        let parent_thread = parent.new_thread(ThreadStartType::MainThread).unwrap();
+        let child = plane.new_child_process(&parent).unwrap();
+        drop(child.new_thread(ThreadStartType::MainThread).unwrap());
+        drop(parent_thread);
+        drop(plane);
+
+        let (pid, cpid) = (parent.pid(), child.pid());
+        let exit_code = ExitCode::from(Errno::Success);
+        let events: Vec<_> = events.collect().await;
+        assert_eq!(
+            events,
+            [
+                ControlPlaneEvent::ProcessSpawned { pid, ppid: None },
+                ControlPlaneEvent::ThreadSpawned {
+                    pid,
+                    tid: pid.raw().into()
+                },
+                ControlPlaneEvent::ProcessSpawned {
+                    pid: cpid,
+                    ppid: Some(pid)
+                },
+                ControlPlaneEvent::ThreadSpawned {
+                    pid: cpid,
+                    tid: cpid.raw().into()
+                },
+                ControlPlaneEvent::ProcessExited {
+                    pid: cpid,
+                    exit_code
+                },
+                ControlPlaneEvent::SignalDelivered {
+                    pid,
+                    signal: Signal::Sigchld
+                },
+                ControlPlaneEvent::ProcessExited { pid, exit_code },
+            ]
+        );
+    }
+
+    #[test]
+    fn processes_can_be_enumerated() {
+        let plane = WasiControlPlane::new();
+        let parent = plane.new_process().unwrap();
+        let main = parent.new_thread(ThreadStartType::MainThread).unwrap();
+        main.set_status_running();
+        let thread = parent
+            .new_thread(ThreadStartType::ThreadSpawn { start_ptr: 0 })
+            .unwrap();
+        let child = plane.new_child_process(&parent).unwrap();
+        drop(child.new_thread(ThreadStartType::MainThread).unwrap());
+
+        thread.as_thread().set_name("worker");
+
+        let info = |pid, ppid, thread_count, state, threads| ProcessInfo {
+            pid,
+            ppid,
+            module_hash: None,
+            thread_count,
+            memory_usage: 0,
+            state,
+            threads,
+        };
+        let threads = vec![
+            ThreadInfo {
+                tid: main.id(),
+                name: None,
+            },
+            ThreadInfo {
+                tid: thread.id(),
+                name: Some("worker".to_string()),
+            },
+        ];
+        assert_eq!(
+            plane.processes(),
+            [
+                info(parent.pid(), None, 2, WasiProcessState::Running, threads),
+                info(
+                    child.pid(),
+                    Some(parent.pid()),
+                    0,
+                    WasiProcessState::Zombie,
+                    Vec::new()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn orphans_are_reaped_when_they_terminate() {
+        let plane = WasiControlPlane::new();
+        let parent = plane.new_process().unwrap();
+        let parent_thread = parent.new_thread(ThreadStartType::MainThread).unwrap();
+        let zombie = plane.new_child_process(&parent).unwrap();
+        drop(zombie.new_thread(ThreadStartType::MainThread).unwrap());
+        let orphan = plane.new_child_process(&parent).unwrap();
+        let orphan_thread = orphan.new_thread(ThreadStartType::MainThread).unwrap();
+        assert!(plane.get_process(zombie.pid()).is_some());
+
+        // The parent has no parent of its own, so nobody is left to reap it.
+        drop(parent_thread);
+        assert!(plane.get_process(parent.pid()).is_none());
+        assert!(plane.get_process(zombie.pid()).is_none());
+        assert_eq!(orphan.ppid(), WasiProcessId::from(0));
+
+        drop(orphan_thread);
+        assert!(plane.get_process(orphan.pid()).is_none());
+    }
+
+    #[test]
+    fn released_ids_are_reused_after_a_delay() {
+        let plane = WasiControlPlane::new();
+        let first = plane.generate_id().unwrap();
+        plane.release_id(first.raw());
+
+        let ids: Vec<_> = (0..ID_REUSE_DELAY)
+            .map(|_| plane.generate_id().unwrap())
+            .collect();
+        assert!(!ids.contains(&first));
+        for id in ids {
+            plane.release_id(id.raw());
+        }
+        assert_eq!(plane.generate_id().unwrap(), first);
+    }
+
+    #[test]
+    fn ids_wrap_around_once_exhausted() {
+        let plane = WasiControlPlane::new();
+        plane.state.mutable.write().unwrap().process_seed = MAX_ID - 1;
+
+        let process = plane.new_process().unwrap();
+        assert_eq!(process.pid().raw(), MAX_ID);
+        assert_eq!(
+            plane.generate_id(),
+            Err(ControlPlaneError::TaskLimitReached {
+                max: MAX_ID as usize
+            })
+        );
+
+        // Reaping the process makes its ID available again.
+        drop(process.new_thread(ThreadStartType::MainThread).unwrap());
+        assert_eq!(plane.generate_id().unwrap().raw(), MAX_ID);
+    }
+
+    #[test]
+    fn thread_ids_are_released() {
+        let plane = WasiControlPlane::new();
+        plane.state.mutable.write().unwrap().process_seed = MAX_ID - 2;
+
+        let process = plane.new_process().unwrap();
+        let _main = process.new_thread(ThreadStartType::MainThread).unwrap();
+        let thread = process
+            .new_thread(ThreadStartType::ThreadSpawn { start_ptr: 0 })
+            .unwrap();
+        assert_eq!(thread.id().raw(), MAX_ID);
+
+        drop(thread);
+        assert_eq!(plane.generate_id().unwrap().raw(), MAX_ID);
+    }
+
+    #[test]
+    fn signals_reach_the_whole_process_group() {
+        let plane = WasiControlPlane::new();
+        let shell = plane.new_process().unwrap();
+        let job = plane.new_child_process(&shell).unwrap();
+        let other = plane.new_child_process(&shell).unwrap();
+        assert_eq!(job.sid(), shell.pid());
+        assert_eq!(job.pgid(), shell.pid());
+
+        // Session leaders stay in their process group
+        assert_eq!(shell.set_pgid(job.pid()), Err(Errno::Perm));
+        job.set_pgid(job.pid()).unwrap();
+        other.set_pgid(job.pid()).unwrap();
+        assert_eq!(plane.process_group(shell.pid()).len(), 1);
+
+        let job_thread = job.new_thread(ThreadStartType::MainThread).unwrap();
+        let other_thread = other.new_thread(ThreadStartType::MainThread).unwrap();
+        let shell_thread = shell.new_thread(ThreadStartType::MainThread).unwrap();
+        plane
+            .signal_process_group(job.pid(), Signal::Sigtstp)
+            .unwrap();
+        assert!(job_thread.has_signal(&[Signal::Sigtstp]));
+        assert!(other_thread.has_signal(&[Signal::Sigtstp]));
+        assert!(!shell_thread.has_signal(&[Signal::Sigtstp]));
+
+        // A group leader can not start a new session
+        assert_eq!(job.new_session(), Err(Errno::Perm));
+        assert_eq!(other.new_session(), Ok(other.pid()));
+        assert_eq!(other.pgid(), other.pid());
+    }
 }
@@ -0,0 +1,203 @@
+use std::fmt;
+
+use wasmer::{MemoryView, Module, RuntimeError};
+use wasmer_wasix_types::wasi::Errno;
+
+use crate::mem_error_to_wasi;
+
+use super::{
+    process::{ProcessSnapshot, WasiProcessId},
+    thread::WasiThreadId,
+};
+
+/// A post-mortem dump of a thread which trapped, in the
+/// [wasm-coredump](https://github.com/WebAssembly/tool-conventions/blob/main/Coredump.md)
+/// format understood by external debuggers.
+///
+/// The JS engine only exposes the functions and offsets of the call stack,
+/// so the locals and the operand stack of the frames are left empty, and so
+/// are the globals of the instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coredump {
+    /// The process the thread belongs to
+    pub pid: WasiProcessId,
+    /// The thread which trapped
+    pub tid: WasiThreadId,
+    /// The coredump, a WebAssembly module holding the memory of the process
+    /// and the `core*` custom sections
+    pub data: Vec<u8>,
+}
+
+/// Receives the coredumps of the threads which trapped, see
+/// [`Runtime::coredump_sink`](crate::Runtime::coredump_sink).
+pub trait CoredumpSink: fmt::Debug {
+    fn write(&self, coredump: Coredump);
+}
+
+impl Coredump {
+    /// Dumps the call stack of a thread which trapped with `error`, along
+    /// with the memory of its process.
+    pub fn capture(
+        pid: WasiProcessId,
+        tid: WasiThreadId,
+        module: &Module,
+        view: &MemoryView,
+        error: &RuntimeError,
+    ) -> Result<Self, Errno> {
+        let memory = ProcessSnapshot::capture(view, None).map_err(mem_error_to_wasi)?;
+        let frames = error.trace().iter().map(|frame| {
+            // Frames are only located within the module, but coredumps locate
+            // them within their function
+            let offset = module
+                .function_offset(frame.func_index())
+                .map(|start| (frame.module_offset() as u32).saturating_sub(start))
+                .unwrap_or_default();
+            (frame.func_index(), offset)
+        });
+        let data = encode(
+            module.name().unwrap_or("main"),
+            &format!("thread-{tid}"),
+            frames,
+            &memory,
+        );
+        Ok(Self { pid, tid, data })
+    }
+}
+
+const HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+const PAGE_SIZE: u64 = 64 * 1024;
+
+/// Encodes a coredump with a single instance of the module, whose memory
+/// holds the captured regions.
+fn encode(
+    name: &str,
+    thread: &str,
+    frames: impl ExactSizeIterator<Item = (u32, u32)>,
+    memory: &ProcessSnapshot,
+) -> Vec<u8> {
+    let mut out = HEADER.to_vec();
+
+    let mut core = vec![0x00];
+    write_name(&mut core, name);
+    write_section(&mut out, 0, Some("core"), &core);
+
+    let mut modules = vec![0x01, 0x00];
+    write_name(&mut modules, name);
+    write_section(&mut out, 0, Some("coremodules"), &modules);
+
+    // One instance of module 0 with memory 0 and no globals
+    let instances = [0x01, 0x00, 0x00, 0x01, 0x00, 0x00];
+    write_section(&mut out, 0, Some("coreinstances"), &instances);
+
+    let mut stack = vec![0x00];
+    write_name(&mut stack, thread);
+    write_u32(&mut stack, frames.len() as u32);
+    for (func_index, offset) in frames {
+        // Frame of instance 0 without locals nor operand stack
+        stack.extend([0x00, 0x00]);
+        write_u32(&mut stack, func_index);
+        write_u32(&mut stack, offset);
+        stack.extend([0x00, 0x00]);
+    }
+    write_section(&mut out, 0, Some("corestack"), &stack);
+
+    let mut memories = vec![0x01, 0x00];
+    write_u32(&mut memories, memory.memory_size.div_ceil(PAGE_SIZE) as u32);
+    write_section(&mut out, 5, None, &memories);
+
+    let mut data = Vec::new();
+    write_u32(&mut data, memory.regions.len() as u32);
+    for (region, bytes) in memory.regions.iter() {
+        // Active segment of memory 0 at `i32.const start`
+        data.extend([0x00, 0x41]);
+        write_i32(&mut data, region.start as u32 as i32);
+        data.push(0x0b);
+        write_u32(&mut data, bytes.len() as u32);
+        data.extend_from_slice(bytes);
+    }
+    write_section(&mut out, 11, None, &data);
+
+    out
+}
+
+fn write_section(out: &mut Vec<u8>, id: u8, custom_name: Option<&str>, contents: &[u8]) {
+    let mut section = Vec::new();
+    if let Some(name) = custom_name {
+        write_name(&mut section, name);
+    }
+    section.extend_from_slice(contents);
+
+    out.push(id);
+    write_u32(out, section.len() as u32);
+    out.extend(section);
+}
+
+fn write_name(out: &mut Vec<u8>, name: &str) {
+    write_u32(out, name.len() as u32);
+    out.extend_from_slice(name.as_bytes());
+}
+
+fn write_u32(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_i32(out: &mut Vec<u8>, mut value: i32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coredumps_are_encoded() {
+        let mut memory = ProcessSnapshot::default();
+        memory.memory_size = 2 * PAGE_SIZE;
+        memory.regions.push(((64..68).into(), vec![1, 2, 3, 4]));
+
+        let data = encode("app", "thread-1", [(3, 200)].into_iter(), &memory);
+        let mut expected = HEADER.to_vec();
+        expected.extend([0, 10, 4, b'c', b'o', b'r', b'e', 0, 3, b'a', b'p', b'p']);
+        expected.extend([0, 18, 11]);
+        expected.extend(b"coremodules");
+        expected.extend([1, 0, 3, b'a', b'p', b'p']);
+        expected.extend([0, 20, 13]);
+        expected.extend(b"coreinstances");
+        expected.extend([1, 0, 0, 1, 0, 0]);
+        expected.extend([0, 28, 9]);
+        expected.extend(b"corestack");
+        expected.extend([0, 8]);
+        expected.extend(b"thread-1");
+        expected.extend([1, 0, 0, 3, 0xc8, 0x01, 0, 0]);
+        expected.extend([5, 3, 1, 0, 2]);
+        expected.extend([11, 11, 1, 0, 0x41, 0xc0, 0x00, 0x0b, 4, 1, 2, 3, 4]);
+        assert_eq!(data, expected);
+    }
+
+    #[test]
+    fn leb128() {
+        let mut out = Vec::new();
+        write_u32(&mut out, 624485);
+        assert_eq!(out, [0xe5, 0x8e, 0x26]);
+
+        out.clear();
+        write_i32(&mut out, -123456);
+        assert_eq!(out, [0xc0, 0xbb, 0x78]);
+    }
+}
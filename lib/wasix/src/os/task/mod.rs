@@ -1,10 +1,14 @@
 //! OS task management for processes and threads.
 
 pub mod control_plane;
+pub mod coredump;
 pub mod process;
 pub mod signal;
+pub mod supervisor;
+pub mod task_group;
 mod task_join_handle;
 pub mod thread;
+pub mod usage;
 
 #[allow(unused_imports)]
 pub(crate) use process::WasiProcessInner;
@@ -1,16 +1,20 @@
 use crate::WasiRuntimeError;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     convert::TryInto,
+    future::Future,
     ops::Range,
     sync::{
-        atomic::{AtomicU32, Ordering},
-        Arc, Condvar, Mutex, MutexGuard, RwLock, Weak,
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc, Condvar, Mutex, MutexGuard, Weak,
     },
     time::Duration,
 };
+use tokio::sync::{oneshot, watch};
 use tracing::trace;
+use wasmer::{AsStoreRef, Memory, MemoryAccessError, MemoryView};
+use wasmer_types::ModuleHash;
 use wasmer_wasix_types::{
     types::Signal,
     wasi::{Errno, ExitCode, Snapshot0Clockid},
@@ -23,9 +27,11 @@ use crate::{
 };
 
 use super::{
-    control_plane::{ControlPlaneError, WasiControlPlaneHandle},
+    control_plane::{ControlPlaneError, ControlPlaneEvent, WasiControlPlaneHandle},
     signal::{SignalDeliveryError, SignalHandlerAbi},
+    task_group::TaskGroup,
     task_join_handle::OwnedTaskStatus,
+    usage::ResourceUsage,
     TaskStatus,
 };
 
@@ -83,8 +89,6 @@ pub type LockableWasiProcessInner = Arc<(Mutex<WasiProcessInner>, Condvar)>;
 pub struct WasiProcess {
     /// Unique ID of this process
     pub(crate) pid: WasiProcessId,
-    /// List of all the children spawned from this thread
-    pub(crate) parent: Option<Weak<RwLock<WasiProcessInner>>>,
     /// The inner protected region of the process with a conditional
     /// variable that is used for coordination such as snapshots.
     pub(crate) inner: LockableWasiProcessInner,
@@ -96,6 +100,25 @@ pub struct WasiProcess {
     pub(crate) finished: Arc<OwnedTaskStatus>,
     /// Number of threads waiting for children to exit
     pub(crate) waiting: Arc<AtomicU32>,
+    /// Size of the memory of the process in bytes, as last reported
+    pub(crate) memory_size: Arc<AtomicU64>,
+    /// The task group whose limits apply to this process
+    pub(crate) task_group: Option<TaskGroup>,
+}
+
+/// The run state of a process, see [`WasiProcess::state`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WasiProcessState {
+    /// The main thread has not started running yet
+    Pending,
+    /// The process is running
+    Running,
+    /// The process is running but was stopped, e.g. with `SIGSTOP`
+    Stopped,
+    /// The process terminated but has not been reaped by its parent yet
+    Zombie,
+    /// The process terminated and was reaped
+    Exited,
 }
 
 /// Represents a freeze of all threads to perform some action
@@ -107,6 +130,12 @@ pub enum WasiProcessCheckpoint {
     /// No checkpoint will take place and the process
     /// should just execute as per normal
     Execute,
+    /// The threads halt at their next safe point so that a snapshot of the
+    /// memory can be taken, see [`WasiProcess::snapshot`]
+    Snapshot {
+        /// Only the pages changed since the previous snapshot are captured
+        incremental: bool,
+    },
 }
 
 #[repr(C)]
@@ -132,11 +161,89 @@ impl Into<Range<u64>> for MemorySnapshotRegion {
     }
 }
 
+/// A snapshot of the memory of a process, see [`WasiProcess::snapshot`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcessSnapshot {
+    /// Size of the memory in bytes
+    pub memory_size: u64,
+    /// Whether the regions only hold the pages which changed since the
+    /// previous snapshot, instead of the whole memory
+    pub incremental: bool,
+    /// The regions of the memory with their contents. Zeroed regions are
+    /// left out, unless they changed since the previous snapshot.
+    pub regions: Vec<(MemorySnapshotRegion, Vec<u8>)>,
+    /// Hashes of the pages of the memory, to find the pages which changed in
+    /// the next incremental snapshot
+    page_hashes: Vec<u64>,
+}
+
+impl ProcessSnapshot {
+    /// Granularity at which changed and zeroed memory is tracked
+    const PAGE_SIZE: usize = 64 * 1024;
+
+    /// Captures the contents of a memory.
+    ///
+    /// When the hashes of the pages of the `previous` snapshot are given,
+    /// only the pages which changed since then are captured. The memory of
+    /// a JS engine can't be watched for writes, so every page is still read
+    /// and hashed, but unchanged pages aren't copied.
+    pub(crate) fn capture(
+        view: &MemoryView,
+        previous: Option<&[u64]>,
+    ) -> Result<Self, MemoryAccessError> {
+        let memory_size = view.data_size();
+        let mut snapshot = Self {
+            memory_size,
+            incremental: previous.is_some(),
+            ..Default::default()
+        };
+        let mut page = vec![0; Self::PAGE_SIZE];
+        let mut offset = 0;
+        while offset < memory_size {
+            let len = (memory_size - offset).min(Self::PAGE_SIZE as u64) as usize;
+            view.read(offset, &mut page[..len])?;
+            let index = snapshot.page_hashes.len();
+            let previous = previous.and_then(|previous| previous.get(index).copied());
+            snapshot.push_page(offset, &page[..len], previous);
+            offset += len as u64;
+        }
+        Ok(snapshot)
+    }
+
+    /// Adds a page of memory if it changed since the previous snapshot,
+    /// merging it with the previous region when they are contiguous.
+    ///
+    /// Pages which weren't part of the previous snapshot count as zeroed.
+    fn push_page(&mut self, offset: u64, page: &[u8], previous: Option<u64>) {
+        let hash = xxhash_rust::xxh64::xxh64(page, 0);
+        self.page_hashes.push(hash);
+        let changed = match previous {
+            Some(previous) => previous != hash,
+            None => page.iter().any(|byte| *byte != 0),
+        };
+        if !changed {
+            return;
+        }
+        let end = offset + page.len() as u64;
+        match self.regions.last_mut() {
+            Some((region, data)) if region.end == offset => {
+                region.end = end;
+                data.extend_from_slice(page);
+            }
+            _ => self.regions.push(((offset..end).into(), page.to_vec())),
+        }
+    }
+}
+
 // TODO: fields should be private and only accessed via methods.
 #[derive(Debug)]
 pub struct WasiProcessInner {
     /// Unique ID of this process
     pub pid: WasiProcessId,
+    /// ID of the process group this process belongs to
+    pub pgid: WasiProcessId,
+    /// ID of the session this process belongs to
+    pub sid: WasiProcessId,
     /// Number of threads waiting for children to exit
     pub(crate) waiting: Arc<AtomicU32>,
     /// The threads that make up this process
@@ -145,8 +252,53 @@ pub struct WasiProcessInner {
     pub thread_count: u32,
     /// Signals that will be triggered at specific intervals
     pub signal_intervals: HashMap<Signal, WasiSignalInterval>,
-    /// List of all the children spawned from this thread
+    /// The process that spawned this process, cleared when the parent
+    /// terminates and its children are orphaned
+    pub(crate) parent: Option<Weak<(Mutex<WasiProcessInner>, Condvar)>>,
+    /// Hash of the module the process is running
+    pub(crate) module_hash: Option<ModuleHash>,
+    /// List of all the children spawned from this thread, including the
+    /// ones which terminated but have not been reaped yet (zombies)
     pub children: Vec<WasiProcess>,
+    /// Resources used by the threads which are gone
+    pub(crate) exited_usage: ResourceUsage,
+    /// Resources used by the children which have been reaped
+    pub(crate) children_usage: ResourceUsage,
+    /// Whether the process is stopped, its threads then park at the next
+    /// safe point until it is continued
+    pub(crate) stopped: watch::Sender<bool>,
+    /// The checkpoint the threads coordinate on with the `Condvar`
+    pub(crate) checkpoint: WasiProcessCheckpoint,
+    /// Number of threads halted for the current checkpoint
+    pub(crate) halted: usize,
+    /// Where the snapshot is sent once it has been taken
+    pub(crate) snapshot_tx: Option<oneshot::Sender<Result<ProcessSnapshot, Errno>>>,
+    /// Hashes of the pages of the memory in the last snapshot
+    pub(crate) page_hashes: Option<Vec<u64>>,
+    /// Exit codes of the joinable threads which exited but have not been
+    /// joined yet, oldest first
+    pub(crate) exited_threads: VecDeque<(WasiThreadId, ExitCode)>,
+}
+
+/// Number of exit codes of joinable threads a process keeps, so a process
+/// which never joins its threads does not keep them all.
+const MAX_EXITED_THREADS: usize = 1024;
+
+impl WasiProcessInner {
+    /// Keeps the exit code of a joinable thread until it is joined
+    pub(crate) fn add_exited_thread(&mut self, tid: WasiThreadId, exit_code: ExitCode) {
+        if self.exited_threads.len() >= MAX_EXITED_THREADS {
+            self.exited_threads.pop_front();
+        }
+        self.exited_threads.push_back((tid, exit_code));
+    }
+
+    fn take_exited_thread(&mut self, tid: WasiThreadId) -> Option<ExitCode> {
+        let index = self.exited_threads.iter().position(|(id, _)| *id == tid)?;
+        self.exited_threads
+            .remove(index)
+            .map(|(_, exit_code)| exit_code)
+    }
 }
 
 // TODO: why do we need this, how is it used?
@@ -175,21 +327,33 @@ impl WasiProcess {
         let inner = Arc::new((
             Mutex::new(WasiProcessInner {
                 pid,
+                pgid: pid,
+                sid: pid,
                 threads: Default::default(),
                 thread_count: Default::default(),
                 signal_intervals: Default::default(),
+                parent: None,
+                module_hash: None,
                 children: Default::default(),
+                exited_usage: Default::default(),
+                children_usage: Default::default(),
+                stopped: watch::channel(false).0,
+                checkpoint: WasiProcessCheckpoint::Execute,
+                halted: 0,
+                snapshot_tx: None,
+                page_hashes: None,
+                exited_threads: Default::default(),
                 waiting: waiting.clone(),
             }),
             Condvar::new(),
         ));
 
         #[derive(Debug)]
-        struct SignalHandler(LockableWasiProcessInner);
+        struct SignalHandler(LockableWasiProcessInner, WasiControlPlaneHandle);
         impl SignalHandlerAbi for SignalHandler {
             fn signal(&self, signal: u8) -> Result<(), SignalDeliveryError> {
                 if let Ok(signal) = signal.try_into() {
-                    signal_process_internal(&self.0, signal);
+                    signal_process_internal(&self.0, &self.1, signal);
                     Ok(())
                 } else {
                     Err(SignalDeliveryError)
@@ -197,21 +361,52 @@ impl WasiProcess {
             }
         }
 
+        let finished = OwnedTaskStatus::new(TaskStatus::Pending)
+            .with_signal_handler(Arc::new(SignalHandler(inner.clone(), plane.clone())));
+
+        // Terminated processes are reaped by their parent, or right away
+        // when there is none.
+        let weak = Arc::downgrade(&inner);
+        let handle = plane.clone();
+        finished.set_termination_hook(Box::new(move |res| {
+            if let Some(inner) = weak.upgrade() {
+                process_exited(&inner, &handle, exit_code_of(res.clone()));
+            }
+        }));
+
         WasiProcess {
             pid,
-            parent: None,
             compute: plane,
-            inner: inner.clone(),
-            finished: Arc::new(
-                OwnedTaskStatus::new(TaskStatus::Pending)
-                    .with_signal_handler(Arc::new(SignalHandler(inner))),
-            ),
+            inner,
+            finished: Arc::new(finished),
             waiting,
+            memory_size: Default::default(),
+            task_group: None,
         }
     }
 
+    /// Sets the ID of the process, which also makes it the leader of a new
+    /// process group and session.
     pub(super) fn set_pid(&mut self, pid: WasiProcessId) {
         self.pid = pid;
+        let mut inner = self.inner.0.lock().unwrap();
+        inner.pid = pid;
+        inner.pgid = pid;
+        inner.sid = pid;
+    }
+
+    /// Makes this process a child of `parent`, it joins the process group
+    /// and session of the parent.
+    pub(super) fn set_parent(&self, parent: &WasiProcess) {
+        let (pgid, sid) = {
+            let mut parent_inner = parent.inner.0.lock().unwrap();
+            parent_inner.children.push(self.clone());
+            (parent_inner.pgid, parent_inner.sid)
+        };
+        let mut inner = self.inner.0.lock().unwrap();
+        inner.parent = Some(Arc::downgrade(&parent.inner));
+        inner.pgid = pgid;
+        inner.sid = sid;
     }
 
     /// Gets the process ID of this process
@@ -221,14 +416,136 @@ impl WasiProcess {
 
     /// Gets the process ID of the parent process
     pub fn ppid(&self) -> WasiProcessId {
-        self.parent
-            .iter()
-            .filter_map(|parent| parent.upgrade())
-            .map(|parent| parent.read().unwrap().pid)
-            .next()
+        let parent = self.inner.0.lock().unwrap().parent.clone();
+        parent
+            .and_then(|parent| parent.upgrade())
+            .map(|parent| parent.0.lock().unwrap().pid)
             .unwrap_or(WasiProcessId(0))
     }
 
+    /// Gets the ID of the process group this process belongs to
+    pub fn pgid(&self) -> WasiProcessId {
+        self.inner.0.lock().unwrap().pgid
+    }
+
+    /// Gets the ID of the session this process belongs to
+    pub fn sid(&self) -> WasiProcessId {
+        self.inner.0.lock().unwrap().sid
+    }
+
+    /// Moves the process into another process group of the same session.
+    ///
+    /// A new process group is created when `pgid` is the ID of this process.
+    pub fn set_pgid(&self, pgid: WasiProcessId) -> Result<(), Errno> {
+        let sid = self.sid();
+        if sid == self.pid {
+            // Session leaders can not leave their process group
+            return Err(Errno::Perm);
+        }
+        if pgid != self.pid {
+            let plane = self.compute.upgrade().ok_or(Errno::Srch)?;
+            let group = plane.process_group(pgid);
+            if !group.iter().any(|process| process.sid() == sid) {
+                return Err(Errno::Perm);
+            }
+        }
+        self.inner.0.lock().unwrap().pgid = pgid;
+        Ok(())
+    }
+
+    /// Makes the process the leader of a new session and process group,
+    /// returning the ID of the new session.
+    ///
+    /// This fails if the process already leads a process group.
+    pub fn new_session(&self) -> Result<WasiProcessId, Errno> {
+        let mut inner = self.inner.0.lock().unwrap();
+        if inner.pgid == self.pid {
+            return Err(Errno::Perm);
+        }
+        inner.pgid = self.pid;
+        inner.sid = self.pid;
+        Ok(self.pid)
+    }
+
+    /// Gets the hash of the module the process is running, once it has been
+    /// instantiated.
+    pub fn module_hash(&self) -> Option<ModuleHash> {
+        self.inner.0.lock().unwrap().module_hash
+    }
+
+    /// Gets the size of the memory of the process in bytes.
+    ///
+    /// Growth performed by the guest is only picked up the next time a view
+    /// of the memory is created.
+    pub fn memory_usage(&self) -> u64 {
+        self.memory_size.load(Ordering::Relaxed)
+    }
+
+    /// Records the module the process is running and keeps track of the size
+    /// of its memory.
+    pub(crate) fn set_module(&self, hash: ModuleHash, memory: &Memory, store: &impl AsStoreRef) {
+        self.inner.0.lock().unwrap().module_hash = Some(hash);
+        self.memory_size
+            .store(memory.view(store).data_size(), Ordering::Relaxed);
+
+        let memory_size = Arc::downgrade(&self.memory_size);
+        let task_group = self.task_group.clone();
+        let inner = Arc::downgrade(&self.inner);
+        let plane = self.compute.clone();
+        memory.on_grow(store, move |pages| {
+            if let Some(memory_size) = memory_size.upgrade() {
+                memory_size.store(pages.bytes().0 as u64, Ordering::Relaxed);
+            }
+            // Much like an out-of-memory killer, the process which pushes its
+            // task group over the memory limit is killed.
+            if task_group
+                .as_ref()
+                .is_some_and(|g| g.is_over_memory_limit())
+            {
+                if let Some(inner) = inner.upgrade() {
+                    signal_process_internal(&inner, &plane, Signal::Sigkill);
+                }
+            }
+        });
+    }
+
+    /// Gets the task group whose limits apply to this process
+    pub fn task_group(&self) -> Option<&TaskGroup> {
+        self.task_group.as_ref()
+    }
+
+    /// Gets the resources used by the threads of the process so far
+    pub fn usage(&self) -> ResourceUsage {
+        usage_of(&self.inner.0.lock().unwrap())
+    }
+
+    /// Gets the resources used by the children of the process which have
+    /// terminated and been reaped, including their own reaped children
+    pub fn children_usage(&self) -> ResourceUsage {
+        self.inner.0.lock().unwrap().children_usage
+    }
+
+    /// Gets the run state of the process
+    pub fn state(&self) -> WasiProcessState {
+        match self.finished.status() {
+            TaskStatus::Pending => WasiProcessState::Pending,
+            TaskStatus::Running if self.is_paused() => WasiProcessState::Stopped,
+            TaskStatus::Running => WasiProcessState::Running,
+            TaskStatus::Finished(_) if self.is_zombie() => WasiProcessState::Zombie,
+            TaskStatus::Finished(_) => WasiProcessState::Exited,
+        }
+    }
+
+    /// Returns `true` if the process terminated but has not been reaped by
+    /// its parent yet.
+    pub fn is_zombie(&self) -> bool {
+        self.try_join().is_some()
+            && self
+                .compute
+                .upgrade()
+                .is_some_and(|plane| plane.is_registered(self.pid, &self.inner))
+    }
+
     /// Gains access to the process internals
     // TODO: Make this private, all inner access should be exposed with methods.
     pub fn lock(&self) -> MutexGuard<'_, WasiProcessInner> {
@@ -264,7 +581,7 @@ impl WasiProcess {
         tid: WasiThreadId,
     ) -> Result<WasiThreadHandle, ControlPlaneError> {
         let control_plane = self.compute.must_upgrade();
-        let task_count_guard = control_plane.register_task()?;
+        let task_count_guard = control_plane.register_task(self.task_group.as_ref())?;
 
         let is_main = matches!(start, ThreadStartType::MainThread);
 
@@ -276,12 +593,23 @@ impl WasiProcess {
             Arc::new(OwnedTaskStatus::default())
         };
 
-        // Insert the thread into the pool
+        // Insert the thread into the pool, the exit code of a thread which
+        // had the same ID before is lost
         let ctrl = WasiThread::new(self.pid(), tid, is_main, finished, task_count_guard, start);
+        inner.take_exited_thread(tid);
         inner.threads.insert(tid, ctrl.clone());
         inner.thread_count += 1;
+        drop(inner);
 
-        Ok(WasiThreadHandle::new(ctrl, &self.inner))
+        control_plane.emit(ControlPlaneEvent::ThreadSpawned {
+            pid: self.pid(),
+            tid,
+        });
+        Ok(WasiThreadHandle::new(
+            ctrl,
+            &self.inner,
+            self.compute.clone(),
+        ))
     }
 
     /// Gets a reference to a particular thread
@@ -290,6 +618,21 @@ impl WasiProcess {
         inner.threads.get(tid).cloned()
     }
 
+    /// Takes the exit code of a joinable thread which exited, once it is
+    /// joined or detached
+    pub fn take_exited_thread(&self, tid: &WasiThreadId) -> Option<ExitCode> {
+        let mut inner = self.inner.0.lock().unwrap();
+        inner.take_exited_thread(*tid)
+    }
+
+    /// Gets all the threads of the process, sorted by ID
+    pub fn threads(&self) -> Vec<WasiThread> {
+        let inner = self.inner.0.lock().unwrap();
+        let mut threads: Vec<_> = inner.threads.values().cloned().collect();
+        threads.sort_by_key(|thread| thread.tid());
+        threads
+    }
+
     /// Signals a particular thread in the process
     pub fn signal_thread(&self, tid: &WasiThreadId, signal: Signal) {
         // Sometimes we will signal the process rather than the thread hence this libc hardcoded value
@@ -317,7 +660,139 @@ impl WasiProcess {
 
     /// Signals all the threads in this process
     pub fn signal_process(&self, signal: Signal) {
-        signal_process_internal(&self.inner, signal);
+        signal_process_internal(&self.inner, &self.compute, signal);
+    }
+
+    /// Stops the process, like `SIGSTOP` does.
+    ///
+    /// Its threads park the next time they reach a safe point, which is when
+    /// they make a syscall, until the process is resumed.
+    pub fn pause(&self) {
+        set_stopped(&self.inner.0.lock().unwrap(), true);
+    }
+
+    /// Resumes a stopped process, like `SIGCONT` does but without delivering
+    /// a signal to the threads.
+    pub fn resume(&self) {
+        set_stopped(&self.inner.0.lock().unwrap(), false);
+    }
+
+    /// Returns `true` if the process is stopped
+    pub fn is_paused(&self) -> bool {
+        *self.inner.0.lock().unwrap().stopped.borrow()
+    }
+
+    /// Waits until the process is no longer stopped.
+    pub(crate) async fn wait_while_paused(&self) {
+        let mut stopped = self.inner.0.lock().unwrap().stopped.subscribe();
+        stopped.wait_for(|stopped| !*stopped).await.ok();
+    }
+
+    /// Takes a snapshot of the whole memory of the process.
+    ///
+    /// The threads halt the next time they reach a safe point, which is when
+    /// they make a syscall. The last one to halt captures the memory, then
+    /// all of them resume. Threads blocked in a syscall hold the snapshot up
+    /// until they return, so it is abandoned with [`Errno::Timedout`] once
+    /// `timeout` resolves.
+    pub async fn snapshot<F>(&self, timeout: F) -> Result<ProcessSnapshot, Errno>
+    where
+        F: Future<Output = ()>,
+    {
+        self.take_snapshot(false, timeout).await
+    }
+
+    /// Takes a snapshot of the memory of the process which only holds the
+    /// pages that changed since the previous snapshot, like
+    /// [`WasiProcess::snapshot`] otherwise.
+    ///
+    /// The first snapshot of a process always holds the whole memory.
+    pub async fn snapshot_incremental<F>(&self, timeout: F) -> Result<ProcessSnapshot, Errno>
+    where
+        F: Future<Output = ()>,
+    {
+        self.take_snapshot(true, timeout).await
+    }
+
+    async fn take_snapshot<F>(
+        &self,
+        incremental: bool,
+        timeout: F,
+    ) -> Result<ProcessSnapshot, Errno>
+    where
+        F: Future<Output = ()>,
+    {
+        let snapshot = {
+            let mut inner = self.inner.0.lock().unwrap();
+            if inner.checkpoint != WasiProcessCheckpoint::Execute {
+                return Err(Errno::Busy);
+            }
+            let (tx, rx) = oneshot::channel();
+            inner.checkpoint = WasiProcessCheckpoint::Snapshot { incremental };
+            inner.snapshot_tx = Some(tx);
+            rx
+        };
+
+        let res = tokio::select! {
+            snapshot = snapshot => snapshot.unwrap_or(Err(Errno::Canceled)),
+            _ = timeout => {
+                tracing::debug!(pid = %self.pid, "threads did not halt in time, abandoning the snapshot");
+                Err(Errno::Timedout)
+            }
+        };
+
+        // Only snapshots which made it to the caller are a base for the next
+        // incremental one
+        if let Ok(snapshot) = &res {
+            self.inner.0.lock().unwrap().page_hashes = Some(snapshot.page_hashes.clone());
+        }
+
+        // Release the threads which already halted
+        end_checkpoint(&self.inner);
+        res
+    }
+
+    /// Returns the checkpoint the threads of the process should halt for
+    pub fn checkpoint(&self) -> WasiProcessCheckpoint {
+        self.inner.0.lock().unwrap().checkpoint
+    }
+
+    /// Halts the calling thread, which is at a safe point, until the current
+    /// checkpoint is over.
+    ///
+    /// The last thread to halt takes the snapshot with `capture`, which is
+    /// given the page hashes of the previous snapshot for incremental ones,
+    /// and releases the others.
+    pub(crate) fn halt_for_checkpoint<F>(&self, capture: F)
+    where
+        F: FnOnce(Option<&[u64]>) -> Result<ProcessSnapshot, Errno>,
+    {
+        let mut inner = self.inner.0.lock().unwrap();
+        if inner.checkpoint == WasiProcessCheckpoint::Execute {
+            return;
+        }
+        inner.halted += 1;
+        if inner.halted < inner.threads.len() {
+            let _inner = self
+                .inner
+                .1
+                .wait_while(inner, |inner| {
+                    inner.checkpoint != WasiProcessCheckpoint::Execute
+                })
+                .unwrap();
+            return;
+        }
+
+        let tx = inner.snapshot_tx.take();
+        let previous = match inner.checkpoint {
+            WasiProcessCheckpoint::Snapshot { incremental: true } => inner.page_hashes.clone(),
+            _ => None,
+        };
+        drop(inner);
+        if let Some(tx) = tx {
+            tx.send(capture(previous.as_deref())).ok();
+        }
+        end_checkpoint(&self.inner);
     }
 
     /// Signals one of the threads every interval
@@ -350,17 +825,32 @@ impl WasiProcess {
         inner.thread_count
     }
 
-    /// Waits until the process is finished.
+    /// Waits until the process is finished, then reaps it.
     pub async fn join(&self) -> Result<ExitCode, Arc<WasiRuntimeError>> {
         let _guard = WasiProcessWait::new(self);
-        self.finished.await_termination().await
+        let res = self.finished.await_termination().await;
+        self.reap();
+        res
     }
 
     /// Attempts to join on the process
+    ///
+    /// Unlike [`WasiProcess::join`] this does not reap the process.
     pub fn try_join(&self) -> Option<Result<ExitCode, Arc<WasiRuntimeError>>> {
         self.finished.status().into_finished()
     }
 
+    /// Reaps the process if it is finished, without waiting for it.
+    pub fn try_reap(&self) -> Option<Result<ExitCode, Arc<WasiRuntimeError>>> {
+        let res = self.try_join()?;
+        self.reap();
+        Some(res)
+    }
+
+    fn reap(&self) {
+        reap_process(&self.inner, &self.compute);
+    }
+
     /// Waits for all the children to be finished
     pub async fn join_children(&mut self) -> Option<Result<ExitCode, Arc<WasiRuntimeError>>> {
         let _guard = WasiProcessWait::new(self);
@@ -371,25 +861,13 @@ impl WasiProcess {
         if children.is_empty() {
             return None;
         }
-        let mut waits = Vec::new();
-        for child in children {
-            if let Some(process) = self.compute.must_upgrade().get_process(child.pid) {
-                let inner = self.inner.clone();
-                waits.push(async move {
-                    let join = process.join().await;
-                    let mut inner = inner.0.lock().unwrap();
-                    inner.children.retain(|a| a.pid != child.pid);
-                    join
-                })
-            }
-        }
-        futures::future::join_all(waits.into_iter())
-            .await
+        let waits = children
             .into_iter()
-            .next()
+            .map(|child| async move { child.join().await });
+        futures::future::join_all(waits).await.into_iter().next()
     }
 
-    /// Waits for any of the children to finished
+    /// Waits for any of the children to finished, then reaps it
     pub async fn join_any_child(&mut self) -> Result<Option<(WasiProcessId, ExitCode)>, Errno> {
         let _guard = WasiProcessWait::new(self);
         let children: Vec<_> = {
@@ -400,41 +878,177 @@ impl WasiProcess {
             return Err(Errno::Child);
         }
 
-        let mut waits = Vec::new();
-        for child in children {
-            if let Some(process) = self.compute.must_upgrade().get_process(child.pid) {
-                let inner = self.inner.clone();
-                waits.push(async move {
-                    let join = process.join().await;
-                    let mut inner = inner.0.lock().unwrap();
-                    inner.children.retain(|a| a.pid != child.pid);
-                    (child, join)
-                })
+        let waits = children.into_iter().map(|child| {
+            Box::pin(async move {
+                let res = child.join().await;
+                (child.pid, res)
+            })
+        });
+        let ((pid, res), _, _) = futures::future::select_all(waits).await;
+
+        Ok(Some((pid, exit_code_of(res))))
+    }
+
+    /// Reaps any of the children which already finished, without waiting.
+    ///
+    /// Returns `Ok(None)` if none of the children finished yet.
+    pub fn try_join_any_child(&self) -> Result<Option<(WasiProcessId, ExitCode)>, Errno> {
+        let children: Vec<_> = {
+            let inner = self.inner.0.lock().unwrap();
+            inner.children.clone()
+        };
+        if children.is_empty() {
+            return Err(Errno::Child);
+        }
+
+        Ok(children
+            .iter()
+            .find_map(|child| child.try_reap().map(|res| (child.pid, exit_code_of(res)))))
+    }
+
+    /// Terminates the process and all its threads.
+    ///
+    /// The other threads are sent a `SIGKILL` and given until `timeout`
+    /// resolves to exit, after which they are forced to finish. The main
+    /// thread goes last as it finishes the process, which must not be
+    /// observed as terminated while its threads may still use the memory.
+    pub async fn terminate<F>(&self, exit_code: ExitCode, timeout: F)
+    where
+        F: Future<Output = ()>,
+    {
+        let threads: Vec<_> = {
+            let guard = self.inner.0.lock().unwrap();
+            // Stopped threads have to be woken up so they can exit
+            set_stopped(&guard, false);
+            guard
+                .threads
+                .values()
+                .filter(|thread| !thread.is_main())
+                .cloned()
+                .collect()
+        };
+        for thread in threads.iter() {
+            thread.signal(Signal::Sigkill);
+        }
+
+        let exited = futures::future::join_all(threads.iter().map(|thread| {
+            let mut handle = thread.join_handle();
+            async move {
+                handle.wait_finished().await.ok();
+            }
+        }));
+        tokio::select! {
+            _ = exited => {}
+            _ = timeout => {
+                tracing::debug!(pid = %self.pid, "threads did not exit in time, forcing them to finish");
             }
         }
-        let (child, res) = futures::future::select_all(waits.into_iter().map(Box::pin))
-            .await
-            .0;
 
-        let code =
-            res.unwrap_or_else(|e| e.as_exit_code().unwrap_or_else(|| Errno::Canceled.into()));
+        // Threads that are still running will exit on their next syscall
+        for thread in threads {
+            thread.set_status_finished(Ok(exit_code));
+        }
+        self.finished.set_finished(Ok(exit_code));
+    }
+}
 
-        Ok(Some((child.pid, code)))
+/// Removes a finished process from its parent and from the control plane,
+/// which releases everything it still holds on to.
+fn reap_process(process: &LockableWasiProcessInner, plane: &WasiControlPlaneHandle) {
+    let (pid, parent, usage) = {
+        let mut inner = process.0.lock().unwrap();
+        let usage = usage_of(&inner) + inner.children_usage;
+        (inner.pid, inner.parent.take(), usage)
+    };
+    if let Some(parent) = parent.and_then(|parent| parent.upgrade()) {
+        let mut parent = parent.0.lock().unwrap();
+        parent
+            .children
+            .retain(|child| !Arc::ptr_eq(&child.inner, process));
+        parent.children_usage += usage;
+    }
+    if let Some(plane) = plane.upgrade() {
+        plane.remove_process(pid, process);
     }
+}
 
-    /// Terminate the process and all its threads
-    pub fn terminate(&self, exit_code: ExitCode) {
-        // FIXME: this is wrong, threads might still be running!
-        // Need special logic for the main thread.
-        let guard = self.inner.0.lock().unwrap();
-        for thread in guard.threads.values() {
-            thread.set_status_finished(Ok(exit_code))
+fn usage_of(inner: &WasiProcessInner) -> ResourceUsage {
+    inner.exited_usage + inner.threads.values().map(WasiThread::usage).sum()
+}
+
+/// Called once a process finished, its children become orphans and are
+/// reaped as soon as they finish.
+///
+/// The parent is sent a `SIGCHLD` so it can reap the process, or the process
+/// is reaped right away when there is no parent that could join on it.
+fn process_exited(
+    process: &LockableWasiProcessInner,
+    plane: &WasiControlPlaneHandle,
+    exit_code: ExitCode,
+) {
+    let (pid, parent, children) = {
+        let mut inner = process.0.lock().unwrap();
+        set_stopped(&inner, false);
+        (
+            inner.pid,
+            inner.parent.clone(),
+            std::mem::take(&mut inner.children),
+        )
+    };
+    if let Some(plane) = plane.upgrade() {
+        plane.file_locks().process_exited(pid);
+        plane.emit(ControlPlaneEvent::ProcessExited { pid, exit_code });
+    }
+    for child in children {
+        child.inner.0.lock().unwrap().parent = None;
+        child.try_reap();
+    }
+    match parent.and_then(|parent| parent.upgrade()) {
+        Some(parent) => {
+            // Delivered to the threads directly, a parent waiting on its
+            // children would otherwise forward the signal to them.
+            let parent = parent.0.lock().unwrap();
+            for thread in parent.threads.values() {
+                thread.signal(Signal::Sigchld);
+            }
+            if let Some(plane) = plane.upgrade() {
+                plane.emit(ControlPlaneEvent::SignalDelivered {
+                    pid: parent.pid,
+                    signal: Signal::Sigchld,
+                });
+            }
         }
+        None => reap_process(process, plane),
     }
 }
 
+/// Converts the result of a process into the exit code reported to its parent
+fn exit_code_of(res: Result<ExitCode, Arc<WasiRuntimeError>>) -> ExitCode {
+    res.unwrap_or_else(|e| e.as_exit_code().unwrap_or_else(|| Errno::Canceled.into()))
+}
+
+fn end_checkpoint(process: &LockableWasiProcessInner) {
+    let mut inner = process.0.lock().unwrap();
+    inner.checkpoint = WasiProcessCheckpoint::Execute;
+    inner.halted = 0;
+    inner.snapshot_tx = None;
+    process.1.notify_all();
+}
+
+fn set_stopped(inner: &WasiProcessInner, stopped: bool) {
+    inner.stopped.send_if_modified(|current| {
+        let modified = *current != stopped;
+        *current = stopped;
+        modified
+    });
+}
+
 /// Signals all the threads in this process
-fn signal_process_internal(process: &LockableWasiProcessInner, signal: Signal) {
+fn signal_process_internal(
+    process: &LockableWasiProcessInner,
+    plane: &WasiControlPlaneHandle,
+    signal: Signal,
+) {
     #[allow(unused_mut)]
     let mut guard = process.0.lock().unwrap();
     let pid = guard.pid;
@@ -453,9 +1067,23 @@ fn signal_process_internal(process: &LockableWasiProcessInner, signal: Signal) {
         }
     }
 
+    // Stopping and continuing applies to the whole process. `SIGSTOP` can't
+    // be caught so it never reaches the threads, and `SIGKILL` has to wake
+    // stopped threads up for them to exit.
+    match signal {
+        Signal::Sigstop => set_stopped(&guard, true),
+        Signal::Sigcont | Signal::Sigkill => set_stopped(&guard, false),
+        _ => {}
+    }
+
     // Otherwise just send the signal to all the threads
-    for thread in guard.threads.values() {
-        thread.signal(signal);
+    if signal != Signal::Sigstop {
+        for thread in guard.threads.values() {
+            thread.signal(signal);
+        }
+    }
+    if let Some(plane) = plane.upgrade() {
+        plane.emit(ControlPlaneEvent::SignalDelivered { pid, signal });
     }
 }
 
@@ -469,3 +1097,213 @@ impl SignalHandlerAbi for WasiProcess {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::os::task::{control_plane::WasiControlPlane, thread::ThreadExitHook};
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn terminate_waits_for_the_threads_to_exit() {
+        let plane = WasiControlPlane::new();
+        let process = plane.new_process().unwrap();
+        let _main = process.new_thread(ThreadStartType::MainThread).unwrap();
+        let thread = process
+            .new_thread(ThreadStartType::ThreadSpawn { start_ptr: 0 })
+            .unwrap();
+        let exit_code = ExitCode::from(Errno::Intr);
+
+        let exit = async {
+            tokio::task::yield_now().await;
+            assert!(thread.has_signal(&[Signal::Sigkill]));
+            assert!(process.try_join().is_none());
+            drop(thread);
+        };
+        tokio::join!(
+            process.terminate(exit_code, futures::future::pending()),
+            exit
+        );
+        assert_eq!(process.try_join().unwrap().unwrap(), exit_code);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn terminate_forces_threads_to_finish_after_the_timeout() {
+        let plane = WasiControlPlane::new();
+        let process = plane.new_process().unwrap();
+        let _main = process.new_thread(ThreadStartType::MainThread).unwrap();
+        let thread = process
+            .new_thread(ThreadStartType::ThreadSpawn { start_ptr: 0 })
+            .unwrap();
+        let exit_code = ExitCode::from(Errno::Intr);
+
+        process.terminate(exit_code, async {}).await;
+        assert_eq!(thread.try_join().unwrap().unwrap(), exit_code);
+        assert_eq!(process.try_join().unwrap().unwrap(), exit_code);
+    }
+
+    #[test]
+    fn exit_codes_are_kept_until_joined() {
+        let plane = WasiControlPlane::new();
+        let process = plane.new_process().unwrap();
+        let _main = process.new_thread(ThreadStartType::MainThread).unwrap();
+        let start = ThreadStartType::ThreadSpawn { start_ptr: 0 };
+        let joinable = process.new_thread(start).unwrap();
+        let detached = process.new_thread(start).unwrap();
+        assert!(detached.as_thread().detach());
+        assert!(!detached.as_thread().detach());
+
+        let exit_code = ExitCode::from(Errno::Io);
+        let (joinable_tid, detached_tid) = (joinable.id(), detached.id());
+        joinable.as_thread().set_status_finished(Ok(exit_code));
+        drop(joinable);
+        drop(detached);
+
+        assert_eq!(process.take_exited_thread(&detached_tid), None);
+        assert_eq!(process.take_exited_thread(&joinable_tid), Some(exit_code));
+        assert_eq!(process.take_exited_thread(&joinable_tid), None);
+    }
+
+    #[test]
+    fn exit_hooks_are_called_the_last_registered_first() {
+        let plane = WasiControlPlane::new();
+        let process = plane.new_process().unwrap();
+        let main = process.new_thread(ThreadStartType::MainThread).unwrap();
+        let thread = main.as_thread();
+        for func in 1..=3 {
+            thread.add_exit_hook(ThreadExitHook { func, arg: 0 });
+        }
+
+        let funcs: Vec<_> = thread
+            .take_exit_hooks()
+            .iter()
+            .map(|hook| hook.func)
+            .collect();
+        assert_eq!(funcs, [3, 2, 1]);
+        assert!(thread.take_exit_hooks().is_empty());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn snapshots_are_taken_by_the_last_thread_to_halt() {
+        let plane = WasiControlPlane::new();
+        let process = plane.new_process().unwrap();
+        let _main = process.new_thread(ThreadStartType::MainThread).unwrap();
+
+        let mut expected = ProcessSnapshot {
+            memory_size: 3 * ProcessSnapshot::PAGE_SIZE as u64,
+            ..Default::default()
+        };
+        let zeroed = vec![0; ProcessSnapshot::PAGE_SIZE];
+        let mut page = zeroed.clone();
+        page[1] = 1;
+        expected.push_page(0, &zeroed, None);
+        expected.push_page(ProcessSnapshot::PAGE_SIZE as u64, &page, None);
+        expected.push_page(2 * ProcessSnapshot::PAGE_SIZE as u64, &page, None);
+        // Zeroed pages are left out and contiguous ones are merged
+        assert_eq!(expected.regions.len(), 1);
+        assert_eq!(
+            expected.regions[0].0,
+            MemorySnapshotRegion::from(
+                ProcessSnapshot::PAGE_SIZE as u64..3 * ProcessSnapshot::PAGE_SIZE as u64
+            )
+        );
+
+        let halt = async {
+            tokio::task::yield_now().await;
+            assert_eq!(
+                process.checkpoint(),
+                WasiProcessCheckpoint::Snapshot { incremental: false }
+            );
+            process.halt_for_checkpoint(|previous| {
+                assert_eq!(previous, None);
+                Ok(expected.clone())
+            });
+        };
+        let (snapshot, ()) = tokio::join!(process.snapshot(futures::future::pending()), halt);
+        assert_eq!(snapshot.unwrap(), expected);
+        assert_eq!(process.checkpoint(), WasiProcessCheckpoint::Execute);
+
+        // Incremental snapshots only hold the pages which changed, even when
+        // they have been zeroed
+        let halt = async {
+            tokio::task::yield_now().await;
+            process.halt_for_checkpoint(|previous| {
+                let previous = previous.unwrap();
+                let mut snapshot = ProcessSnapshot {
+                    incremental: true,
+                    ..Default::default()
+                };
+                snapshot.push_page(0, &zeroed, previous.first().copied());
+                snapshot.push_page(
+                    ProcessSnapshot::PAGE_SIZE as u64,
+                    &page,
+                    previous.get(1).copied(),
+                );
+                snapshot.push_page(
+                    2 * ProcessSnapshot::PAGE_SIZE as u64,
+                    &zeroed,
+                    previous.get(2).copied(),
+                );
+                snapshot.push_page(
+                    3 * ProcessSnapshot::PAGE_SIZE as u64,
+                    &zeroed,
+                    previous.get(3).copied(),
+                );
+                Ok(snapshot)
+            });
+        };
+        let (snapshot, ()) = tokio::join!(
+            process.snapshot_incremental(futures::future::pending()),
+            halt
+        );
+        let snapshot = snapshot.unwrap();
+        assert_eq!(snapshot.regions.len(), 1);
+        assert_eq!(
+            snapshot.regions[0],
+            (
+                MemorySnapshotRegion::from(
+                    2 * ProcessSnapshot::PAGE_SIZE as u64..3 * ProcessSnapshot::PAGE_SIZE as u64
+                ),
+                zeroed.clone()
+            )
+        );
+
+        // Threads which never reach a safe point hold the snapshot up
+        assert_eq!(process.snapshot(async {}).await, Err(Errno::Timedout));
+        assert_eq!(process.checkpoint(), WasiProcessCheckpoint::Execute);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn stopped_processes_park_until_continued() {
+        let plane = WasiControlPlane::new();
+        let process = plane.new_process().unwrap();
+        let main = process.new_thread(ThreadStartType::MainThread).unwrap();
+        main.set_status_running();
+
+        process.signal_process(Signal::Sigstop);
+        assert!(process.is_paused());
+        assert_eq!(process.state(), WasiProcessState::Stopped);
+        // SIGSTOP can't be caught
+        assert!(!main.has_signal(&[Signal::Sigstop]));
+
+        let continued = std::cell::Cell::new(false);
+        let cont = async {
+            tokio::task::yield_now().await;
+            continued.set(true);
+            process.signal_process(Signal::Sigcont);
+        };
+        tokio::join!(
+            async {
+                process.wait_while_paused().await;
+                assert!(continued.get());
+            },
+            cont
+        );
+        assert_eq!(process.state(), WasiProcessState::Running);
+        assert!(main.has_signal(&[Signal::Sigcont]));
+
+        // Killing a stopped process wakes its threads up so they can exit
+        process.pause();
+        process.signal_process(Signal::Sigkill);
+        assert!(!process.is_paused());
+    }
+}
@@ -0,0 +1,164 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use wasmer_wasix_types::wasi::ExitCode;
+
+use crate::{VirtualTaskManager, WasiProcess, WasiRuntimeError};
+
+use super::control_plane::{ControlPlaneEvent, WasiControlPlane};
+
+/// When a supervised process is restarted after it terminated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RestartPolicy {
+    /// The process is never restarted
+    #[default]
+    Never,
+    /// The process is restarted when it exits with a non-zero exit code or
+    /// a runtime error
+    OnFailure,
+    /// The process is always restarted
+    Always,
+}
+
+/// How a [`Supervisor`] restarts its process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestartStrategy {
+    pub policy: RestartPolicy,
+    /// Delay before the first restart, it is doubled after every restart
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between two restarts
+    pub max_backoff: Duration,
+    /// Maximum number of restarts before the supervisor gives up
+    pub max_restarts: Option<u32>,
+}
+
+impl Default for RestartStrategy {
+    fn default() -> Self {
+        Self {
+            policy: RestartPolicy::Never,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+            max_restarts: None,
+        }
+    }
+}
+
+impl RestartStrategy {
+    fn should_restart(&self, res: &Result<ExitCode, Arc<WasiRuntimeError>>, restarts: u32) -> bool {
+        if self.max_restarts.is_some_and(|max| restarts >= max) {
+            return false;
+        }
+        match self.policy {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure => !matches!(res, Ok(code) if code.is_success()),
+            RestartPolicy::Always => true,
+        }
+    }
+
+    fn backoff(&self, restarts: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(1 << restarts.min(31))
+            .min(self.max_backoff)
+    }
+}
+
+/// Keeps a process running according to a [`RestartStrategy`], see
+/// [`WasiControlPlane::supervise`].
+///
+/// Every restart is published as a [`ControlPlaneEvent::ProcessRestarted`].
+pub struct Supervisor<F> {
+    plane: WasiControlPlane,
+    strategy: RestartStrategy,
+    tasks: Arc<dyn VirtualTaskManager>,
+    spawn: F,
+}
+
+impl<F, Fut, E> Supervisor<F>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<WasiProcess, E>>,
+{
+    pub(super) fn new(
+        plane: WasiControlPlane,
+        strategy: RestartStrategy,
+        tasks: Arc<dyn VirtualTaskManager>,
+        spawn: F,
+    ) -> Self {
+        Self {
+            plane,
+            strategy,
+            tasks,
+            spawn,
+        }
+    }
+
+    /// Spawns the process and restarts it whenever it terminates, as long as
+    /// the strategy allows it.
+    ///
+    /// Resolves with the result of the last run of the process, or with the
+    /// error of the spawn function if it fails.
+    pub async fn run(mut self) -> Result<Result<ExitCode, Arc<WasiRuntimeError>>, E> {
+        let mut process = (self.spawn)().await?;
+        let mut restarts = 0;
+        loop {
+            let res = process.join().await;
+            if !self.strategy.should_restart(&res, restarts) {
+                return Ok(res);
+            }
+
+            let delay = self.strategy.backoff(restarts);
+            tracing::debug!(pid = %process.pid(), ?delay, "restarting the supervised process");
+            if !delay.is_zero() {
+                self.tasks.sleep_now(delay).await;
+            }
+
+            let previous = process.pid();
+            process = (self.spawn)().await?;
+            restarts += 1;
+            self.plane.emit(ControlPlaneEvent::ProcessRestarted {
+                previous,
+                pid: process.pid(),
+                restarts,
+            });
+        }
+    }
+}
+
+impl<F> std::fmt::Debug for Supervisor<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Supervisor")
+            .field("strategy", &self.strategy)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasmer_wasix_types::wasi::Errno;
+
+    use super::*;
+
+    #[test]
+    fn restart_strategy() {
+        let strategy = RestartStrategy {
+            policy: RestartPolicy::OnFailure,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(350),
+            max_restarts: Some(3),
+        };
+        assert!(!strategy.should_restart(&Ok(ExitCode::from(Errno::Success)), 0));
+        assert!(strategy.should_restart(&Ok(ExitCode::from(Errno::Io)), 0));
+        assert!(!strategy.should_restart(&Ok(ExitCode::from(Errno::Io)), 3));
+
+        assert_eq!(strategy.backoff(0), Duration::from_millis(100));
+        assert_eq!(strategy.backoff(1), Duration::from_millis(200));
+        assert_eq!(strategy.backoff(2), Duration::from_millis(350));
+        assert_eq!(strategy.backoff(u32::MAX), Duration::from_millis(350));
+
+        let always = RestartStrategy {
+            policy: RestartPolicy::Always,
+            ..strategy
+        };
+        assert!(always.should_restart(&Ok(ExitCode::from(Errno::Success)), 0));
+        assert!(!RestartStrategy::default().should_restart(&Ok(ExitCode::from(Errno::Io)), 0));
+    }
+}
@@ -0,0 +1,146 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use super::control_plane::{ControlPlaneError, WasiControlPlaneHandle};
+
+/// Limits shared by all the processes of a [`TaskGroup`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TaskGroupLimits {
+    /// Maximum number of threads across all the processes of the group
+    pub max_task_count: Option<usize>,
+    /// Maximum combined size of the memories of the processes in bytes
+    pub max_memory: Option<u64>,
+}
+
+/// A named set of processes sharing the same limits, such as all the
+/// processes of one tenant.
+///
+/// Groups are created with [`WasiControlPlane::create_task_group`], and
+/// child processes belong to the group of their parent.
+///
+/// [`WasiControlPlane::create_task_group`]: super::control_plane::WasiControlPlane::create_task_group
+#[derive(Debug, Clone)]
+pub struct TaskGroup {
+    inner: Arc<TaskGroupInner>,
+}
+
+#[derive(Debug)]
+struct TaskGroupInner {
+    name: String,
+    limits: TaskGroupLimits,
+    /// Number of threads running in the processes of the group
+    task_count: Arc<AtomicUsize>,
+    plane: WasiControlPlaneHandle,
+}
+
+impl TaskGroup {
+    pub(super) fn new(
+        name: String,
+        limits: TaskGroupLimits,
+        plane: WasiControlPlaneHandle,
+    ) -> Self {
+        Self {
+            inner: Arc::new(TaskGroupInner {
+                name,
+                limits,
+                task_count: Default::default(),
+                plane,
+            }),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    pub fn limits(&self) -> &TaskGroupLimits {
+        &self.inner.limits
+    }
+
+    /// Number of threads running in the processes of the group
+    pub fn task_count(&self) -> usize {
+        self.inner.task_count.load(Ordering::SeqCst)
+    }
+
+    /// Combined size of the memories of the running processes of the group
+    pub fn memory_usage(&self) -> u64 {
+        let Some(plane) = self.inner.plane.upgrade() else {
+            return 0;
+        };
+        plane
+            .task_group_processes(self)
+            .iter()
+            .filter(|process| process.try_join().is_none())
+            .map(|process| process.memory_usage())
+            .sum()
+    }
+
+    /// Returns `true` if the combined memory of the group exceeds its limit
+    pub(crate) fn is_over_memory_limit(&self) -> bool {
+        self.inner
+            .limits
+            .max_memory
+            .is_some_and(|max| self.memory_usage() > max)
+    }
+
+    /// Counts a new thread against the limit of the group
+    pub(super) fn register_task(&self) -> Result<Arc<AtomicUsize>, ControlPlaneError> {
+        let count = self.inner.task_count.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Some(max) = self.inner.limits.max_task_count {
+            if count > max {
+                self.inner.task_count.fetch_sub(1, Ordering::SeqCst);
+                return Err(ControlPlaneError::TaskLimitReached { max });
+            }
+        }
+        Ok(self.inner.task_count.clone())
+    }
+
+    pub(crate) fn ptr_eq(&self, other: &TaskGroup) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasmer_wasix_types::wasix::ThreadStartType;
+
+    use super::*;
+    use crate::os::task::control_plane::WasiControlPlane;
+
+    #[test]
+    fn task_limits_are_shared_by_the_group() {
+        let plane = WasiControlPlane::new();
+        let limits = TaskGroupLimits {
+            max_task_count: Some(2),
+            ..Default::default()
+        };
+        let group = plane.create_task_group("tenant", limits.clone()).unwrap();
+        assert_eq!(
+            plane.create_task_group("tenant", limits).unwrap_err(),
+            ControlPlaneError::TaskGroupExists("tenant".to_string())
+        );
+
+        let parent = plane.new_process_in_group(&group).unwrap();
+        let _main = parent.new_thread(ThreadStartType::MainThread).unwrap();
+        let child = plane.new_child_process(&parent).unwrap();
+        assert!(child.task_group().unwrap().ptr_eq(&group));
+        let child_main = child.new_thread(ThreadStartType::MainThread).unwrap();
+        assert_eq!(group.task_count(), 2);
+        assert_eq!(
+            child
+                .new_thread(ThreadStartType::ThreadSpawn { start_ptr: 0 })
+                .unwrap_err(),
+            ControlPlaneError::TaskLimitReached { max: 2 }
+        );
+
+        // Processes outside of the group are not affected
+        let other = plane.new_process().unwrap();
+        let _other_main = other.new_thread(ThreadStartType::MainThread).unwrap();
+
+        drop(child_main);
+        assert_eq!(group.task_count(), 1);
+        assert_eq!(plane.task_group("tenant").unwrap().name(), "tenant");
+    }
+}
@@ -1,9 +1,11 @@
 use std::{
     pin::Pin,
-    sync::Arc,
+    sync::{Arc, Mutex},
     task::{Context, Poll},
 };
 
+use derivative::Derivative;
+
 use wasmer_wasix_types::wasi::{Errno, ExitCode};
 
 use crate::WasiRuntimeError;
@@ -70,7 +72,8 @@ pub trait VirtualTaskHandle: std::fmt::Debug + Send + Sync + 'static {
 }
 
 /// A handle that allows awaiting the termination of a task, and retrieving its exit code.
-#[derive(Debug)]
+#[derive(Derivative)]
+#[derivative(Debug)]
 pub struct OwnedTaskStatus {
     // The signal handler that can be invoked for this owned task
     signal_handler: Arc<DynSignalHandlerAbi>,
@@ -80,8 +83,15 @@ pub struct OwnedTaskStatus {
     // where the previously sent values are lost.
     #[allow(dead_code)]
     watch_rx: tokio::sync::watch::Receiver<TaskStatus>,
+
+    /// Invoked once when the task finishes.
+    #[derivative(Debug = "ignore")]
+    termination_hook: Mutex<Option<TerminationHook>>,
 }
 
+/// A callback invoked with the result of a task once it finishes.
+pub type TerminationHook = Box<dyn FnOnce(&Result<ExitCode, Arc<WasiRuntimeError>>) + Send>;
+
 impl OwnedTaskStatus {
     pub fn new(status: TaskStatus) -> Self {
         let (tx, rx) = tokio::sync::watch::channel(status);
@@ -89,6 +99,7 @@ impl OwnedTaskStatus {
             signal_handler: default_signal_handler(),
             watch_tx: tx,
             watch_rx: rx,
+            termination_hook: Mutex::new(None),
         }
     }
 
@@ -103,6 +114,17 @@ impl OwnedTaskStatus {
         self
     }
 
+    /// Sets a callback which is invoked once the task finishes.
+    ///
+    /// The callback is invoked immediately if the task already finished.
+    pub fn set_termination_hook(&self, hook: TerminationHook) {
+        if let TaskStatus::Finished(res) = self.status() {
+            hook(&res);
+        } else {
+            *self.termination_hook.lock().unwrap() = Some(hook);
+        }
+    }
+
     pub fn new_finished_with_code(code: ExitCode) -> Self {
         Self::new(TaskStatus::Finished(Ok(code)))
     }
@@ -129,11 +151,20 @@ impl OwnedTaskStatus {
                 }
             }
         };
-        self.watch_tx.send_modify(move |old| {
+        let mut finished = None;
+        self.watch_tx.send_modify(|old| {
             if !old.is_finished() {
-                *old = TaskStatus::Finished(inner);
+                *old = TaskStatus::Finished(inner.clone());
+                finished = Some(inner);
             }
         });
+
+        if let Some(res) = finished {
+            let hook = self.termination_hook.lock().unwrap().take();
+            if let Some(hook) = hook {
+                hook(&res);
+            }
+        }
     }
 
     pub fn status(&self) -> TaskStatus {
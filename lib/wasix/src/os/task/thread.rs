@@ -1,11 +1,14 @@
 use serde::{Deserialize, Serialize};
 use std::{
-    sync::{Arc, Condvar, Mutex, Weak},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, Weak,
+    },
     task::Waker,
 };
 use wasm_bindgen::{JsCast, JsValue};
 
-use wasmer::{ExportError, InstantiationError, MemoryError};
+use wasmer::{AsStoreMut, ExportError, InstantiationError, MemoryError};
 use wasmer_wasix_types::{
     types::Signal,
     wasi::{Errno, ExitCode},
@@ -18,8 +21,9 @@ use crate::{
 };
 
 use super::{
-    control_plane::TaskCountGuard,
+    control_plane::{TaskCountGuard, WasiControlPlaneHandle},
     task_join_handle::{OwnedTaskStatus, TaskJoinHandle},
+    usage::{ResourceUsage, ThreadUsage},
 };
 
 /// Represents the ID of a WASI thread
@@ -118,6 +122,15 @@ impl Drop for WasiThreadRunGuard {
     }
 }
 
+/// A guest function called with `arg` when the thread exits, such as the
+/// destructor of a thread-specific value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadExitHook {
+    /// Index of the function in the indirect function table
+    pub func: u32,
+    pub arg: u64,
+}
+
 #[derive(Debug)]
 struct WasiThreadState {
     is_main: bool,
@@ -125,6 +138,16 @@ struct WasiThreadState {
     id: WasiThreadId,
     signals: Mutex<(Vec<Signal>, Vec<Waker>)>,
     status: Arc<OwnedTaskStatus>,
+    usage: ThreadUsage,
+    /// Name given to the thread with `thread_set_name`
+    name: Mutex<Option<String>>,
+    /// Span the thread runs in, it records the name of the thread
+    span: tracing::Span,
+    /// Detached threads can not be joined, their exit code is dropped as
+    /// soon as they exit
+    detached: AtomicBool,
+    /// Hooks registered with `thread_exit_hook`
+    exit_hooks: Mutex<Vec<ThreadExitHook>>,
 
     // Registers the task termination with the ControlPlane on drop.
     // Never accessed, since it's a drop guard.
@@ -147,6 +170,17 @@ impl WasiThread {
                 id,
                 status,
                 signals: Mutex::new((Vec::new(), Vec::new())),
+                usage: Default::default(),
+                name: Mutex::new(None),
+                span: tracing::debug_span!(
+                    parent: None,
+                    "thread",
+                    %pid,
+                    %id,
+                    name = tracing::field::Empty
+                ),
+                detached: AtomicBool::new(false),
+                exit_hooks: Mutex::new(Vec::new()),
                 _task_count_guard: guard,
             }),
             start,
@@ -168,6 +202,52 @@ impl WasiThread {
         self.state.is_main
     }
 
+    /// Returns the name given to the thread, if any
+    pub fn name(&self) -> Option<String> {
+        self.state.name.lock().unwrap().clone()
+    }
+
+    /// Names the thread, the name is recorded on its [`span`](Self::span)
+    /// and shown by [`WasiControlPlane::processes`].
+    ///
+    /// [`WasiControlPlane::processes`]: super::control_plane::WasiControlPlane::processes
+    pub fn set_name(&self, name: impl Into<String>) {
+        let name = name.into();
+        self.state.span.record("name", name.as_str());
+        *self.state.name.lock().unwrap() = Some(name);
+    }
+
+    /// The span the thread runs in
+    pub fn span(&self) -> tracing::Span {
+        self.state.span.clone()
+    }
+
+    /// Returns true if the thread was detached
+    pub fn is_detached(&self) -> bool {
+        self.state.detached.load(Ordering::SeqCst)
+    }
+
+    /// Detaches the thread, its exit code is then dropped as soon as it
+    /// exits rather than kept until it is joined.
+    ///
+    /// Returns `false` if the thread was already detached.
+    pub fn detach(&self) -> bool {
+        !self.state.detached.swap(true, Ordering::SeqCst)
+    }
+
+    /// Registers a hook to be called when the thread exits
+    pub fn add_exit_hook(&self, hook: ThreadExitHook) {
+        self.state.exit_hooks.lock().unwrap().push(hook);
+    }
+
+    /// Takes the exit hooks registered so far, in the order they must be
+    /// called (the last registered first)
+    pub(crate) fn take_exit_hooks(&self) -> Vec<ThreadExitHook> {
+        let mut hooks = std::mem::take(&mut *self.state.exit_hooks.lock().unwrap());
+        hooks.reverse();
+        hooks
+    }
+
     /// Get a join handle to watch the task status.
     pub fn join_handle(&self) -> TaskJoinHandle {
         self.state.status.handle()
@@ -200,9 +280,21 @@ impl WasiThread {
     /// Marks the thread as finished (which will cause anyone that
     /// joined on it to wake up)
     pub fn set_status_finished(&self, res: Result<ExitCode, WasiRuntimeError>) {
+        self.state.usage.finish();
         self.state.status.set_finished(res.map_err(Arc::new));
     }
 
+    /// Starts accounting for the resources used by the thread, which is
+    /// about to run in `store` on the current worker.
+    pub(crate) fn start_accounting(&self, store: &mut impl AsStoreMut) {
+        self.state.usage.start(store);
+    }
+
+    /// Gets the resources used by the thread so far
+    pub fn usage(&self) -> ResourceUsage {
+        self.state.usage.usage()
+    }
+
     /// Waits until the thread is finished or the timeout is reached
     pub async fn join(&self) -> Result<ExitCode, Arc<WasiRuntimeError>> {
         self.state.status.await_termination().await
@@ -296,6 +388,7 @@ impl WasiThread {
 pub struct WasiThreadHandleProtected {
     thread: WasiThread,
     inner: Weak<(Mutex<WasiProcessInner>, Condvar)>,
+    plane: WasiControlPlaneHandle,
 }
 
 #[derive(Debug, Clone)]
@@ -307,11 +400,13 @@ impl WasiThreadHandle {
     pub(crate) fn new(
         thread: WasiThread,
         inner: &Arc<(Mutex<WasiProcessInner>, Condvar)>,
+        plane: WasiControlPlaneHandle,
     ) -> WasiThreadHandle {
         Self {
             protected: Arc::new(WasiThreadHandleProtected {
                 thread,
                 inner: Arc::downgrade(inner),
+                plane,
             }),
         }
     }
@@ -329,11 +424,38 @@ impl Drop for WasiThreadHandleProtected {
     fn drop(&mut self) {
         let id = self.thread.tid();
         if let Some(inner) = Weak::upgrade(&self.inner) {
-            let mut inner = inner.0.lock().unwrap();
-            if let Some(ctrl) = inner.threads.remove(&id) {
+            let ctrl = {
+                let mut inner = inner.0.lock().unwrap();
+                inner.thread_count -= 1;
+                let ctrl = inner.threads.remove(&id);
+                if let Some(ctrl) = &ctrl {
+                    ctrl.state.usage.finish();
+                    inner.exited_usage += ctrl.usage();
+
+                    // The exit code of joinable threads is kept until they
+                    // are joined
+                    if !ctrl.is_main() && !ctrl.is_detached() {
+                        let exit_code = ctrl
+                            .try_join()
+                            .and_then(|res| res.ok())
+                            .unwrap_or_else(|| Errno::Success.into());
+                        inner.add_exited_thread(id, exit_code);
+                    }
+                }
+                ctrl
+            };
+            // Finishing the main thread runs the exit logic of the process,
+            // which needs the process lock.
+            if let Some(ctrl) = ctrl {
                 ctrl.set_status_finished(Ok(Errno::Success.into()));
             }
-            inner.thread_count -= 1;
+        }
+        // The main thread shares its ID with the process, which is
+        // released once the process is reaped.
+        if !self.thread.is_main() {
+            if let Some(plane) = self.plane.upgrade() {
+                plane.release_id(id.raw());
+            }
         }
     }
 }
@@ -0,0 +1,128 @@
+use std::{
+    ops::{Add, AddAssign},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use utils::GlobalScope;
+use virtual_mio::InlineWaker;
+use wasmer::{AsStoreMut, HostCallCounter};
+
+/// Resources used by a thread, or by all the threads of a process.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// Time spent running, excluding the time spent blocked in syscalls
+    pub cpu_time: Duration,
+    /// Number of syscalls made
+    pub syscalls: u64,
+}
+
+impl Add for ResourceUsage {
+    type Output = ResourceUsage;
+
+    fn add(self, rhs: ResourceUsage) -> ResourceUsage {
+        ResourceUsage {
+            cpu_time: self.cpu_time + rhs.cpu_time,
+            syscalls: self.syscalls + rhs.syscalls,
+        }
+    }
+}
+
+impl AddAssign for ResourceUsage {
+    fn add_assign(&mut self, rhs: ResourceUsage) {
+        *self = *self + rhs;
+    }
+}
+
+impl std::iter::Sum for ResourceUsage {
+    fn sum<I: Iterator<Item = ResourceUsage>>(iter: I) -> ResourceUsage {
+        iter.fold(ResourceUsage::default(), Add::add)
+    }
+}
+
+/// Accounts for the resources used by a thread.
+///
+/// Threads are only accounted for once they start running. The CPU time is
+/// the time since then, minus the time the thread spent waiting in
+/// [`InlineWaker::block_on`], which is where blocking syscalls wait.
+#[derive(Debug, Default)]
+pub(super) struct ThreadUsage {
+    syscalls: HostCallCounter,
+    /// Time spent waiting in nanoseconds
+    wait_time: Arc<AtomicU64>,
+    running: Mutex<Running>,
+}
+
+#[derive(Debug, Default)]
+struct Running {
+    /// When the thread started running in milliseconds
+    since: Option<f64>,
+    /// The usage of the thread, frozen once it finished
+    finished: Option<ResourceUsage>,
+}
+
+impl ThreadUsage {
+    /// Starts accounting for the thread, which is about to run in `store` on
+    /// the current worker.
+    pub(super) fn start(&self, store: &mut impl AsStoreMut) {
+        store
+            .as_store_mut()
+            .set_host_call_counter(self.syscalls.clone());
+        InlineWaker::track_wait_time(Some(self.wait_time.clone()));
+
+        let mut running = self.running.lock().unwrap();
+        if running.since.is_none() {
+            running.since = Some(GlobalScope::current().now());
+        }
+    }
+
+    /// Stops accounting for the thread once it finished.
+    pub(super) fn finish(&self) {
+        let mut running = self.running.lock().unwrap();
+        if running.finished.is_none() {
+            running.finished = Some(self.usage_of(&running));
+        }
+    }
+
+    pub(super) fn usage(&self) -> ResourceUsage {
+        let running = self.running.lock().unwrap();
+        running.finished.unwrap_or_else(|| self.usage_of(&running))
+    }
+
+    fn usage_of(&self, running: &Running) -> ResourceUsage {
+        let Some(since) = running.since else {
+            return ResourceUsage::default();
+        };
+        let elapsed =
+            Duration::from_secs_f64((GlobalScope::current().now() - since).max(0.) / 1000.);
+        let waited = Duration::from_nanos(self.wait_time.load(Ordering::Relaxed));
+        ResourceUsage {
+            cpu_time: elapsed.saturating_sub(waited),
+            syscalls: self.syscalls.count(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_adds_up() {
+        let usage = ResourceUsage {
+            cpu_time: Duration::from_millis(5),
+            syscalls: 3,
+        };
+        let total: ResourceUsage = [usage, usage, ResourceUsage::default()].into_iter().sum();
+        assert_eq!(total.cpu_time, Duration::from_millis(10));
+        assert_eq!(total.syscalls, 6);
+
+        // Threads which never ran did not use anything
+        let thread = ThreadUsage::default();
+        thread.finish();
+        assert_eq!(thread.usage(), ResourceUsage::default());
+    }
+}
@@ -8,11 +8,13 @@ use wasmer_types::ModuleHash;
 use std::{fmt, sync::Arc};
 
 use futures::future::LocalBoxFuture;
+use virtual_fs::FileSystem;
 use virtual_net::DynVirtualNetworking;
 use wasmer::{Module, RuntimeError};
 use wasmer_wasix_types::wasi::ExitCode;
 
 use crate::{
+    os::task::coredump::CoredumpSink,
     runtime::module_cache::{ModuleCache, ThreadLocalCache},
     SpawnError,
 };
@@ -72,6 +74,28 @@ where
     /// Callback thats invokes whenever the instance is tainted, tainting can occur
     /// for multiple reasons however the most common is a panic within the process
     fn on_taint(&self, _reason: TaintReason) {}
+
+    /// Where the coredumps of the threads which trap are written.
+    ///
+    /// Coredumps are only captured when a sink is returned, as they hold a
+    /// copy of the whole memory of the process.
+    fn coredump_sink(&self) -> Option<Arc<dyn CoredumpSink + Send + Sync>> {
+        None
+    }
+
+    /// Provides the file systems which processes mount with `path_mount`,
+    /// other than `tmpfs` which is always available.
+    ///
+    /// `fstype` is the kind of file system, such as the volumes of packages
+    /// or the backends of the host, and `source` is which one of them.
+    fn mount_source(
+        &self,
+        fstype: &str,
+        source: &str,
+    ) -> Option<Box<dyn FileSystem + Send + Sync>> {
+        let _ = (fstype, source);
+        None
+    }
 }
 
 pub type DynRuntime = dyn Runtime + Send + Sync;
@@ -0,0 +1,119 @@
+use wasmer::{Engine, Module};
+
+use crate::runtime::module_cache::{CacheError, ModuleCache};
+use wasmer_types::ModuleHash;
+
+/// [`FallbackCache`] is a combinator for the [`ModuleCache`] trait that enables
+/// the chaining of two caching strategies together, typically via
+/// [`ModuleCache::with_fallback()`].
+///
+/// All operations are attempted using primary cache first, and if that fails,
+/// falls back to using the fallback cache. When a module is only found in the
+/// fallback cache, it is copied into the primary cache so subsequent loads
+/// are faster.
+#[derive(Debug, Clone)]
+pub struct FallbackCache<Primary, Fallback> {
+    primary: Primary,
+    fallback: Fallback,
+}
+
+impl<Primary, Fallback> FallbackCache<Primary, Fallback> {
+    pub(crate) fn new(primary: Primary, fallback: Fallback) -> Self {
+        FallbackCache { primary, fallback }
+    }
+
+    pub fn primary(&self) -> &Primary {
+        &self.primary
+    }
+
+    pub fn fallback(&self) -> &Fallback {
+        &self.fallback
+    }
+
+    pub fn into_inner(self) -> (Primary, Fallback) {
+        let FallbackCache { primary, fallback } = self;
+        (primary, fallback)
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl<Primary, Fallback> ModuleCache for FallbackCache<Primary, Fallback>
+where
+    Primary: ModuleCache,
+    Fallback: ModuleCache,
+{
+    async fn load(&self, key: ModuleHash, engine: &Engine) -> Result<Module, CacheError> {
+        let primary_error = match self.primary.load(key, engine).await {
+            Ok(m) => return Ok(m),
+            Err(e) => e,
+        };
+
+        if let Ok(m) = self.fallback.load(key, engine).await {
+            if let Err(e) = self.primary.save(key, engine, &m).await {
+                tracing::warn!(
+                    %key,
+                    error = &e as &dyn std::error::Error,
+                    "Unable to save a module to the primary cache",
+                );
+            }
+
+            return Ok(m);
+        }
+
+        Err(primary_error)
+    }
+
+    async fn save(
+        &self,
+        key: ModuleHash,
+        engine: &Engine,
+        module: &Module,
+    ) -> Result<(), CacheError> {
+        futures::try_join!(
+            self.primary.save(key, engine, module),
+            self.fallback.save(key, engine, module)
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::module_cache::ThreadLocalCache;
+
+    const ADD_WAT: &[u8] = br#"(
+        module
+            (func
+                (export "add")
+                (param $x i64)
+                (param $y i64)
+                (result i64)
+                (i64.add (local.get $x) (local.get $y)))
+        )"#;
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn load_from_primary() {
+        let engine = Engine::default();
+        let module = Module::new(ADD_WAT).await.unwrap();
+        let key = ModuleHash::xxhash_from_bytes([0; 8]);
+        let primary = ThreadLocalCache::default();
+        primary.save(key, &engine, &module).await.unwrap();
+        let cache = FallbackCache::new(primary, ThreadLocalCache::default());
+
+        let got = cache.load(key, &engine).await.unwrap();
+
+        assert_eq!(module, got);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn missing_modules_are_not_found() {
+        let engine = Engine::default();
+        let key = ModuleHash::xxhash_from_bytes([0xfe; 8]);
+        let cache = FallbackCache::new(ThreadLocalCache::default(), ThreadLocalCache::default());
+
+        let err = cache.load(key, &engine).await.unwrap_err();
+
+        assert!(matches!(err, CacheError::NotFound));
+    }
+}
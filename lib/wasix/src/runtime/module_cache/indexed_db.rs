@@ -0,0 +1,245 @@
+use js_sys::{Object, Promise, Reflect, Uint8Array, WebAssembly};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use wasmer::{Engine, Module};
+use web_sys::{IdbDatabase, IdbRequest, IdbTransactionMode};
+
+use crate::runtime::module_cache::{CacheError, ModuleCache};
+use wasmer_types::ModuleHash;
+
+const OBJECT_STORE: &str = "modules";
+const MODULE_KEY: &str = "module";
+const BYTES_KEY: &str = "bytes";
+
+/// A cache that persists modules in the browser's IndexedDB, so that they
+/// survive page reloads.
+///
+/// Where the browser allows storing compiled `WebAssembly.Module` objects in
+/// IndexedDB they are stored alongside the original bytes and loading them
+/// skips compilation entirely. Otherwise only the bytes are stored and the
+/// module is recompiled (without being downloaded again) when loaded.
+#[derive(Debug, Clone)]
+pub struct IndexedDbCache {
+    db_name: String,
+}
+
+impl IndexedDbCache {
+    /// The name of the database used by [`IndexedDbCache::default()`].
+    pub const DEFAULT_DB_NAME: &'static str = "wasmer-module-cache";
+
+    /// Create a cache that stores modules in the database with the given
+    /// name.
+    pub fn new(db_name: impl Into<String>) -> Self {
+        IndexedDbCache {
+            db_name: db_name.into(),
+        }
+    }
+
+    fn key(key: ModuleHash, deterministic_id: &str) -> JsValue {
+        format!("{deterministic_id}-{key}").into()
+    }
+
+    async fn open(&self) -> Result<IdbDatabase, CacheError> {
+        let factory = utils::GlobalScope::current()
+            .indexed_db()
+            .ok_or_else(|| IndexedDbError("IndexedDB is not available".into()))?;
+        let request = factory
+            .open_with_u32(&self.db_name, 1)
+            .map_err(IndexedDbError::from_js)?;
+
+        // The upgrade only happens when the database is first created.
+        let on_upgrade_needed = Closure::<dyn FnMut(JsValue)>::new({
+            let request = request.clone();
+            move |_| {
+                if let Ok(db) = request.result() {
+                    let db: IdbDatabase = db.unchecked_into();
+                    if let Err(e) = db.create_object_store(OBJECT_STORE) {
+                        tracing::warn!(error = ?e, "Unable to create the module cache store");
+                    }
+                }
+            }
+        });
+        request.set_onupgradeneeded(Some(on_upgrade_needed.as_ref().unchecked_ref()));
+        let db = wait_for(&request).await;
+        request.set_onupgradeneeded(None);
+
+        Ok(db?.unchecked_into())
+    }
+
+    async fn get(&self, key: JsValue) -> Result<JsValue, CacheError> {
+        let db = self.open().await?;
+        let request = db
+            .transaction_with_str_and_mode(OBJECT_STORE, IdbTransactionMode::Readonly)
+            .and_then(|tx| tx.object_store(OBJECT_STORE))
+            .and_then(|store| store.get(&key))
+            .map_err(IndexedDbError::from_js)?;
+        let result = wait_for(&request).await;
+        db.close();
+        result
+    }
+
+    async fn put(&self, key: JsValue, entry: &Object) -> Result<(), CacheError> {
+        let db = self.open().await?;
+        let request = db
+            .transaction_with_str_and_mode(OBJECT_STORE, IdbTransactionMode::Readwrite)
+            .and_then(|tx| tx.object_store(OBJECT_STORE))
+            .and_then(|store| store.put_with_key(entry, &key))
+            .map_err(IndexedDbError::from_js);
+        let result = match request {
+            Ok(request) => wait_for(&request).await.map(|_| ()),
+            Err(e) => Err(e.into()),
+        };
+        db.close();
+        result
+    }
+}
+
+impl Default for IndexedDbCache {
+    fn default() -> Self {
+        IndexedDbCache::new(IndexedDbCache::DEFAULT_DB_NAME)
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl ModuleCache for IndexedDbCache {
+    #[tracing::instrument(level = "trace", skip_all, fields(%key))]
+    async fn load(&self, key: ModuleHash, engine: &Engine) -> Result<Module, CacheError> {
+        let entry = self.get(Self::key(key, engine.deterministic_id())).await?;
+        if entry.is_undefined() {
+            return Err(CacheError::NotFound);
+        }
+
+        let bytes = Reflect::get(&entry, &BYTES_KEY.into())
+            .ok()
+            .and_then(|bytes| bytes.dyn_into::<Uint8Array>().ok())
+            .ok_or(CacheError::NotFound)?
+            .to_vec();
+        let module = Reflect::get(&entry, &MODULE_KEY.into())
+            .ok()
+            .and_then(|module| module.dyn_into::<WebAssembly::Module>().ok());
+
+        tracing::debug!(compiled = module.is_some(), "Cache hit!");
+        match module {
+            Some(module) => Ok(Module::from_module_and_binary(module, &bytes)),
+            None => Module::from_binary(&bytes)
+                .await
+                .map_err(|e| CacheError::Deserialize(wasmer::DeserializeError::Compiler(e))),
+        }
+    }
+
+    #[tracing::instrument(level = "trace", skip_all, fields(%key))]
+    async fn save(
+        &self,
+        key: ModuleHash,
+        engine: &Engine,
+        module: &Module,
+    ) -> Result<(), CacheError> {
+        let key = Self::key(key, engine.deterministic_id());
+        let bytes = module.serialize();
+
+        let entry = Object::new();
+        Reflect::set(&entry, &BYTES_KEY.into(), &Uint8Array::from(&bytes[..]))
+            .map_err(IndexedDbError::from_js)?;
+        Reflect::set(&entry, &MODULE_KEY.into(), &JsValue::from(module.clone()))
+            .map_err(IndexedDbError::from_js)?;
+
+        if let Err(e) = self.put(key.clone(), &entry).await {
+            // Most browsers refuse to serialize compiled modules, so fall back
+            // to only storing the bytes.
+            tracing::debug!(
+                error = &e as &dyn std::error::Error,
+                "Unable to store the compiled module"
+            );
+            Reflect::delete_property(&entry, &MODULE_KEY.into())
+                .map_err(IndexedDbError::from_js)?;
+            self.put(key, &entry).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Wait for an IndexedDB request to complete, returning its result.
+async fn wait_for(request: &IdbRequest) -> Result<JsValue, CacheError> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        request.set_onsuccess(Some(&resolve));
+        request.set_onerror(Some(&reject));
+    });
+    let outcome = JsFuture::from(promise).await;
+    request.set_onsuccess(None);
+    request.set_onerror(None);
+
+    match outcome {
+        Ok(_) => request
+            .result()
+            .map_err(|e| IndexedDbError::from_js(e).into()),
+        Err(_) => {
+            let message = match request.error() {
+                Ok(Some(e)) => e.message(),
+                _ => "The IndexedDB request failed".to_string(),
+            };
+            Err(IndexedDbError(message).into())
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+struct IndexedDbError(String);
+
+impl IndexedDbError {
+    fn from_js(value: JsValue) -> Self {
+        IndexedDbError(utils::js_error(value).to_string())
+    }
+}
+
+impl From<IndexedDbError> for CacheError {
+    fn from(e: IndexedDbError) -> Self {
+        CacheError::other(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+
+    const ADD_WAT: &[u8] = br#"(
+        module
+            (func
+                (export "add")
+                (param $x i64)
+                (param $y i64)
+                (result i64)
+                (i64.add (local.get $x) (local.get $y)))
+        )"#;
+
+    #[wasm_bindgen_test]
+    async fn round_trip_via_cache() {
+        let engine = Engine::default();
+        let module = Module::new(ADD_WAT).await.unwrap();
+        let cache = IndexedDbCache::new("wasmer-module-cache-test");
+        let key = ModuleHash::xxhash_from_bytes([0; 8]);
+
+        cache.save(key, &engine, &module).await.unwrap();
+        let round_tripped = cache.load(key, &engine).await.unwrap();
+
+        let exports: Vec<_> = round_tripped
+            .exports()
+            .map(|export| export.name().to_string())
+            .collect();
+        assert_eq!(exports, ["add"]);
+    }
+
+    #[wasm_bindgen_test]
+    async fn missing_modules_are_not_found() {
+        let engine = Engine::default();
+        let cache = IndexedDbCache::new("wasmer-module-cache-test");
+        let key = ModuleHash::xxhash_from_bytes([0xff; 8]);
+
+        let err = cache.load(key, &engine).await.unwrap_err();
+
+        assert!(matches!(err, CacheError::NotFound));
+    }
+}
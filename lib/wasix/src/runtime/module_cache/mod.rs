@@ -2,7 +2,8 @@
 //!
 //! The core of this module is the [`ModuleCache`] trait, which is designed to
 //! be implemented by different cache storage strategies, such as in-memory
-//! caches [`ThreadLocalCache`]). Implementing custom caching
+//! caches ([`ThreadLocalCache`]) or caches persisted in the browser
+//! ([`IndexedDbCache`]). Implementing custom caching
 //! strategies allows you to optimize for your specific use case.
 //!
 //! ## Assumptions and Requirements
@@ -51,7 +52,24 @@ use wasmer_types::ModuleHash;
 /// be called more often than [`ModuleCache::save()`] and optimise
 /// their caching strategy accordingly.
 ///
-#[async_trait::async_trait]
+/// ## Threading
+///
+/// Cache operations may need to await JavaScript promises, so the futures
+/// they return are not required to be [`Send`].
+///
+/// ## Migrating from `Send` futures
+///
+/// The futures of the trait used to be [`Send`], which is a breaking change
+/// for both sides of it:
+///
+/// - Implementations must use `#[async_trait::async_trait(?Send)]` rather
+///   than `#[async_trait::async_trait]`, as the signatures don't match
+///   otherwise. They may then hold JavaScript values across `.await`s.
+/// - Callers can no longer move the futures to another thread, such as with
+///   `tokio::spawn()`. They are awaited where they are created, or spawned
+///   on the current thread with `wasm_bindgen_futures::spawn_local()`.
+///
+#[async_trait::async_trait(?Send)]
 pub trait ModuleCache: Debug {
     /// Load a module based on its hash.
     async fn load(&self, key: ModuleHash, engine: &Engine) -> Result<Module, CacheError>;
@@ -70,9 +88,31 @@ pub trait ModuleCache: Debug {
         engine: &Engine,
         module: &Module,
     ) -> Result<(), CacheError>;
+
+    /// Chain a second [`ModuleCache`] that will be used as a fallback if
+    /// lookups on the primary cache fail.
+    ///
+    /// The general assumption is that each subsequent cache in the chain will
+    /// be significantly slower than the previous one.
+    ///
+    /// ```rust
+    /// use wasmer_wasix::runtime::module_cache::{
+    ///     ModuleCache, ThreadLocalCache, IndexedDbCache,
+    /// };
+    ///
+    /// let cache = ThreadLocalCache::default()
+    ///     .with_fallback(IndexedDbCache::default());
+    /// ```
+    fn with_fallback<C>(self, other: C) -> FallbackCache<Self, C>
+    where
+        Self: Sized,
+        C: ModuleCache,
+    {
+        FallbackCache::new(self, other)
+    }
 }
 
-#[async_trait::async_trait]
+#[async_trait::async_trait(?Send)]
 impl<D, C> ModuleCache for D
 where
     D: Deref<Target = C> + Debug + Send + Sync,
@@ -125,8 +165,13 @@ impl CacheError {
     }
 }
 
+mod fallback;
+mod indexed_db;
 mod thread_local;
-pub use thread_local::ThreadLocalCache;
+
+pub use self::{
+    fallback::FallbackCache, indexed_db::IndexedDbCache, thread_local::ThreadLocalCache,
+};
 
 #[cfg(test)]
 mod tests {
@@ -27,7 +27,7 @@ impl ThreadLocalCache {
     }
 }
 
-#[async_trait::async_trait]
+#[async_trait::async_trait(?Send)]
 impl ModuleCache for ThreadLocalCache {
     #[tracing::instrument(level = "trace", skip_all, fields(%key))]
     async fn load(&self, key: ModuleHash, engine: &Engine) -> Result<Module, CacheError> {
@@ -5,12 +5,12 @@ use derivative::Derivative;
 use futures::future::LocalBoxFuture;
 use futures::Future;
 use wasm_bindgen::JsValue;
-use wasmer::{Memory, MemoryType, Module, Store, StoreMut, StoreRef};
+use wasmer::{AsStoreRef, Memory, MemoryType, Module, Store, StoreMut, StoreRef};
 
 use crate::os::task::thread::WasiThreadError;
 use crate::{StoreSnapshot, WasiEnv, WasiFunctionEnv};
 
-pub use virtual_mio::InlineWaker;
+pub use virtual_mio::{InlineSleep, InlineWaker};
 
 #[derive(Debug)]
 pub enum SpawnMemoryType<'a> {
@@ -81,6 +81,111 @@ impl<'a, 'b> TaskWasm<'a, 'b> {
     }
 }
 
+/// How the memory of a [`SpawnWasm`] is created on the thread running it.
+#[derive(Debug, Clone)]
+pub enum SpawnWasmMemory {
+    /// The module does not import a memory
+    CreateMemory,
+    /// A new memory of this type is created
+    CreateMemoryOfType(MemoryType),
+    /// The memory of this type transferred to the thread is shared
+    ShareMemory(MemoryType),
+}
+
+/// A [`TaskWasm`] which no longer borrows the store of the thread which
+/// spawned it, so it can be sent to the thread which runs it.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct SpawnWasm {
+    #[derivative(Debug = "ignore")]
+    run: Box<TaskWasmRun>,
+    memory: SpawnWasmMemory,
+    env: WasiEnv,
+    store_snapshot: Option<StoreSnapshot>,
+}
+
+impl<'a, 'b> From<TaskWasm<'a, 'b>> for SpawnWasm {
+    fn from(task: TaskWasm<'a, 'b>) -> Self {
+        let TaskWasm {
+            run,
+            env,
+            globals,
+            spawn_type,
+            ..
+        } = task;
+
+        let memory = match spawn_type {
+            SpawnMemoryType::CreateMemory => SpawnWasmMemory::CreateMemory,
+            SpawnMemoryType::CreateMemoryOfType(ty) => SpawnWasmMemory::CreateMemoryOfType(ty),
+            SpawnMemoryType::CopyMemory(memory, store)
+            | SpawnMemoryType::ShareMemory(memory, store) => {
+                SpawnWasmMemory::ShareMemory(memory.ty(&store))
+            }
+        };
+
+        Self {
+            run,
+            memory,
+            env,
+            store_snapshot: globals.cloned(),
+        }
+    }
+}
+
+impl SpawnWasm {
+    /// The environment of the thread.
+    pub fn env(&self) -> &WasiEnv {
+        &self.env
+    }
+
+    /// Runs the task on the current thread, blocking until it has completed.
+    ///
+    /// `module` and `memory` are the module and memory of the process, as
+    /// transferred to this thread.
+    pub async fn execute(
+        self,
+        module: Module,
+        memory: Memory,
+        wbg_js_module: Option<JsValue>,
+    ) -> Result<(), WasiThreadError> {
+        let Self {
+            run,
+            memory: memory_type,
+            env,
+            store_snapshot,
+        } = self;
+
+        // A temporary store holds the transferred memory until it is shared
+        // with the store of the thread
+        let temp_store = env.runtime().new_store();
+        let spawn_type = match memory_type {
+            SpawnWasmMemory::CreateMemory => SpawnMemoryType::CreateMemory,
+            SpawnWasmMemory::CreateMemoryOfType(ty) => SpawnMemoryType::CreateMemoryOfType(ty),
+            SpawnWasmMemory::ShareMemory(ty) => {
+                assert_eq!(ty, memory.ty(&temp_store));
+                SpawnMemoryType::ShareMemory(memory, temp_store.as_store_ref())
+            }
+        };
+
+        let (ctx, store) = WasiFunctionEnv::new_creating_store(
+            module,
+            env,
+            store_snapshot.as_ref(),
+            spawn_type,
+            wbg_js_module.clone(),
+        )
+        .await?;
+
+        run(TaskWasmRunProperties {
+            ctx,
+            store,
+            wbg_js_module,
+        })
+        .await;
+        Ok(())
+    }
+}
+
 /// Data for spawning the scheduler.
 #[derive(Debug)]
 pub struct SchedulerSpawn {
@@ -90,27 +195,62 @@ pub struct SchedulerSpawn {
     pub memory: Memory,
     /// wasm-bindgen generated module name.
     pub wbg_js_module_name: String,
-    /// Number of workers to pre-start.
+    /// How the pool of workers running the threads is sized.
+    pub worker_pool: WorkerPoolConfig,
+}
+
+/// How a task manager backed by a pool of workers sizes the pool.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkerPoolConfig {
+    /// Number of workers started ahead of time, so threads don't have to
+    /// wait for a worker to start
     pub prestarted_workers: usize,
+    /// Maximum number of threads running at the same time, the threads
+    /// spawned beyond it are queued until a running thread exits
+    pub max_concurrency: Option<usize>,
+    /// Pre-started workers which stay idle for longer than this are
+    /// terminated, the pool is warmed up again as threads are spawned
+    pub idle_timeout: Option<Duration>,
+}
+
+/// The state of a pool of workers, see [`VirtualTaskManager::pool_metrics`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WorkerPoolMetrics {
+    /// Threads waiting for a worker because the pool is at its maximum
+    /// concurrency
+    pub queued_tasks: usize,
+    /// Workers running a thread
+    pub active_workers: usize,
+    /// Pre-started workers waiting for a thread
+    pub idle_workers: usize,
 }
 
-/// A task executor backed by a thread pool.
+/// The executor used by the WASIX runtime to sleep and to run threads.
 ///
-/// ## Thread Safety
+/// Embedders provide it through [`Runtime::task_manager`], so the runtime
+/// can be plugged into a custom thread pool or browser scheduler. The
+/// browser runtime implements it with a pool of web workers.
 ///
-/// Due to [#4158], it is possible to pass non-thread safe objects across
-/// threads by capturing them in the task passed to
-/// [`VirtualTaskManager::task_shared()`] or
-/// [`VirtualTaskManager::task_dedicated()`].
+/// ## Implementing a task manager
 ///
-/// If your task needs access to a [`wasmer::Module`], [`wasmer::Memory`], or
-/// [`wasmer::Instance`], it should explicitly transfer the objects using
-/// either [`VirtualTaskManager::task_wasm()`] when in syscall context or
-/// [`VirtualTaskManager::spawn_with_module()`] for higher level code.
+/// - [`VirtualTaskManager::init`] is called once the main instance exists,
+///   with the module and memory every thread needs.
+/// - [`VirtualTaskManager::sleep_now`] is awaited by blocking syscalls with
+///   [`InlineWaker::block_on`], so its future should arm the timeout of the
+///   [`InlineWaker`] like [`InlineSleep`] does, rather than relying on an
+///   event loop which is blocked at that point.
+/// - [`VirtualTaskManager::task_wasm`] must run the task on another thread
+///   which is allowed to block. The task borrows the store of the calling
+///   thread, so it is first turned into a [`SpawnWasm`] with
+///   `SpawnWasm::from`. Once the module and memory were transferred to
+///   the new thread, [`SpawnWasm::execute`] runs it there.
 ///
-/// [#4158]: https://github.com/wasmerio/wasmer/issues/4158
+/// Task managers which can't run threads return
+/// [`WasiThreadError::Unsupported`] from `task_wasm`.
+///
+/// [`Runtime::task_manager`]: crate::Runtime::task_manager
 pub trait VirtualTaskManager: std::fmt::Debug + Send + Sync + 'static {
-    /// Initializes the task manager.
+    /// Initializes the task manager, before the process starts running.
     fn init(&self, scheduler_spawn: SchedulerSpawn) -> LocalBoxFuture<()>;
 
     /// Build a new Webassembly memory.
@@ -182,6 +322,12 @@ pub trait VirtualTaskManager: std::fmt::Debug + Send + Sync + 'static {
 
     /// Returns the amount of parallelism that is possible on this platform.
     fn thread_parallelism(&self) -> Result<usize, WasiThreadError>;
+
+    /// Returns the state of the pool of workers running the threads, if the
+    /// task manager has one.
+    fn pool_metrics(&self) -> Option<WorkerPoolMetrics> {
+        None
+    }
 }
 
 impl<D, T> VirtualTaskManager for D
@@ -215,4 +361,8 @@ where
     fn thread_parallelism(&self) -> Result<usize, WasiThreadError> {
         (**self).thread_parallelism()
     }
+
+    fn pool_metrics(&self) -> Option<WorkerPoolMetrics> {
+        (**self).pool_metrics()
+    }
 }
@@ -14,7 +14,9 @@ use wasmer::{
 
 use crate::{
     fs::{WasiFs, WasiFsRoot, WasiInodes},
+    net::resolver::{Resolver, ResolverConfig},
     os::task::control_plane::{ControlPlaneError, WasiControlPlane},
+    runtime::task_manager::WorkerPoolConfig,
     state::WasiState,
     syscalls::types::{__WASI_STDERR_FILENO, __WASI_STDIN_FILENO, __WASI_STDOUT_FILENO},
     Runtime, WasiEnv, WasiFunctionEnv, WasiRuntimeError,
@@ -55,20 +57,35 @@ pub struct WasiEnvBuilder {
     pub(super) stderr: Option<Box<dyn VirtualFile + Send + Sync + 'static>>,
     pub(super) stdin: Option<Box<dyn VirtualFile + Send + Sync + 'static>>,
     pub(super) fs: Option<WasiFsRoot>,
+    /// Whether renames across mounts fall back to copying the file.
+    pub(super) copy_cross_device_renames: bool,
+    /// The number of symlinks which can be traversed when resolving a path.
+    pub(super) max_symlinks: Option<u32>,
+    /// Whether symlinks can't lead out of their preopened directory.
+    pub(super) confine_symlinks: bool,
+    /// Whether raw and ICMP sockets can be opened.
+    pub(super) raw_sockets: bool,
+    /// How the names the program looks up are resolved.
+    pub(super) resolver: Option<ResolverConfig>,
+    /// Which servers the streams the program encrypts trust.
+    #[cfg(feature = "tls")]
+    pub(super) tls: Option<crate::net::TlsConfig>,
     pub(super) runtime: Option<Arc<dyn crate::Runtime + Send + Sync + 'static>>,
+    /// The control plane the process is created in.
+    pub(super) control_plane: Option<WasiControlPlane>,
     pub(super) current_dir: Option<PathBuf>,
     pub(super) additional_imports: Imports,
     /// Name of wasm-bindgen generated JavaScript module.
     pub(super) wbg_js_module_name: Option<String>,
-    /// Number of thread workers to pre-start.
-    pub(super) prestarted_workers: Option<usize>,
+    /// How the pool of thread workers is sized.
+    pub(super) worker_pool: Option<WorkerPoolConfig>,
 }
 
 impl std::fmt::Debug for WasiEnvBuilder {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         // TODO: update this when stable
-        f.debug_struct("WasiEnvBuilder")
-            .field("args", &self.args)
+        let mut s = f.debug_struct("WasiEnvBuilder");
+        s.field("args", &self.args)
             .field("envs", &self.envs)
             .field("preopens", &self.preopens)
             .field("setup_fs_fn exists", &self.setup_fs_fn.is_some())
@@ -76,9 +93,17 @@ impl std::fmt::Debug for WasiEnvBuilder {
             .field("stderr_override exists", &self.stderr.is_some())
             .field("stdin_override exists", &self.stdin.is_some())
             .field("runtime_override_exists", &self.runtime.is_some())
+            .field("control_plane", &self.control_plane)
+            .field("copy_cross_device_renames", &self.copy_cross_device_renames)
+            .field("max_symlinks", &self.max_symlinks)
+            .field("confine_symlinks", &self.confine_symlinks)
+            .field("raw_sockets", &self.raw_sockets)
+            .field("resolver", &self.resolver)
             .field("wbg_js_module_name", &self.wbg_js_module_name)
-            .field("prestarted_workers", &self.prestarted_workers)
-            .finish()
+            .field("worker_pool", &self.worker_pool);
+        #[cfg(feature = "tls")]
+        s.field("tls", &self.tls);
+        s.finish()
     }
 }
 
@@ -480,10 +505,114 @@ impl WasiEnvBuilder {
     ///
     /// This is usually used in case a custom `virtual_fs::FileSystem` is needed.
     pub fn sandbox_fs(mut self, fs: TmpFileSystem) -> Self {
+        self.set_sandbox_fs(fs);
+        self
+    }
+
+    /// Sets a new sandbox FileSystem to be used with this WASI instance, the
+    /// `tmpfs` the program mounts share its memory limiter.
+    pub fn set_sandbox_fs(&mut self, fs: TmpFileSystem) {
         self.fs = Some(WasiFsRoot::Sandbox(Arc::new(fs)));
+    }
+
+    /// Sets whether renaming a file to another mount copies it and removes
+    /// the source instead of failing with `EXDEV`, for programs which don't
+    /// handle that error.
+    pub fn copy_cross_device_renames(mut self, copy: bool) -> Self {
+        self.set_copy_cross_device_renames(copy);
+        self
+    }
+
+    /// Sets whether renaming a file to another mount copies it and removes
+    /// the source instead of failing with `EXDEV`.
+    pub fn set_copy_cross_device_renames(&mut self, copy: bool) {
+        self.copy_cross_device_renames = copy;
+    }
+
+    /// Sets the number of symlinks which can be traversed when resolving a
+    /// path before failing with `ELOOP`, [`crate::fs::MAX_SYMLINKS`] by
+    /// default.
+    pub fn max_symlinks(mut self, max_symlinks: u32) -> Self {
+        self.set_max_symlinks(max_symlinks);
+        self
+    }
+
+    /// Sets the number of symlinks which can be traversed when resolving a
+    /// path before failing with `ELOOP`.
+    pub fn set_max_symlinks(&mut self, max_symlinks: u32) {
+        self.max_symlinks = Some(max_symlinks);
+    }
+
+    /// Sets whether following a symlink whose target is outside of the
+    /// preopened directory containing it fails, for sandboxes where symlinks
+    /// shouldn't give access to the rest of the file system.
+    pub fn confine_symlinks(mut self, confine: bool) -> Self {
+        self.set_confine_symlinks(confine);
+        self
+    }
+
+    /// Sets whether following a symlink whose target is outside of the
+    /// preopened directory containing it fails.
+    pub fn set_confine_symlinks(&mut self, confine: bool) {
+        self.confine_symlinks = confine;
+    }
+
+    /// Sets whether the program can open raw sockets and the ICMP sockets
+    /// `ping` uses, on the networks supporting them. They are refused with
+    /// `EPERM` and `EACCES` otherwise, like for a program without
+    /// `CAP_NET_RAW` on Linux.
+    pub fn raw_sockets(mut self, allow: bool) -> Self {
+        self.set_raw_sockets(allow);
         self
     }
 
+    /// Sets whether the program can open raw sockets and ICMP sockets.
+    pub fn set_raw_sockets(&mut self, allow: bool) {
+        self.raw_sockets = allow;
+    }
+
+    /// Sets how the names the program looks up are resolved, for instance
+    /// from a `resolv.conf` file with [`ResolverConfig::parse`]. They are
+    /// resolved by the network as they are, and cached for a while, by
+    /// default.
+    pub fn resolver(mut self, config: ResolverConfig) -> Self {
+        self.set_resolver(config);
+        self
+    }
+
+    /// Sets how the names the program looks up are resolved.
+    pub fn set_resolver(&mut self, config: ResolverConfig) {
+        self.resolver = Some(config);
+    }
+
+    /// Sets which servers the streams the program encrypts with `sock_tls`
+    /// trust, the certificate authorities of the web by default.
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, config: crate::net::TlsConfig) -> Self {
+        self.set_tls(config);
+        self
+    }
+
+    /// Sets which servers the streams the program encrypts trust.
+    #[cfg(feature = "tls")]
+    pub fn set_tls(&mut self, config: crate::net::TlsConfig) {
+        self.tls = Some(config);
+    }
+
+    /// Creates the process in `control_plane`, whose processes share their
+    /// Unix sockets, file locks and, when it has one, their network, rather
+    /// than in a control plane of its own.
+    pub fn control_plane(mut self, control_plane: WasiControlPlane) -> Self {
+        self.set_control_plane(control_plane);
+        self
+    }
+
+    /// Creates the process in `control_plane` rather than in a control plane
+    /// of its own.
+    pub fn set_control_plane(&mut self, control_plane: WasiControlPlane) {
+        self.control_plane = Some(control_plane);
+    }
+
     /// Configure the WASI filesystem before running.
     // TODO: improve ergonomics on this function
     pub fn setup_fs(mut self, setup_fs_fn: SetupFsFn) -> Self {
@@ -560,7 +689,35 @@ impl WasiEnvBuilder {
 
     /// Sets the number of web workers to pre-start for executing threads.
     pub fn set_prestarted_workers(&mut self, prestarted_workers: usize) {
-        self.prestarted_workers = Some(prestarted_workers);
+        self.get_worker_pool_mut().prestarted_workers = prestarted_workers;
+    }
+
+    /// Sets how the pool of web workers executing threads is sized.
+    pub fn set_worker_pool(&mut self, worker_pool: WorkerPoolConfig) {
+        self.worker_pool = Some(worker_pool);
+    }
+
+    /// Get a mutable reference to the configuration of the pool of web
+    /// workers executing threads.
+    pub fn get_worker_pool_mut(&mut self) -> &mut WorkerPoolConfig {
+        self.worker_pool
+            .get_or_insert_with(Self::default_worker_pool)
+    }
+
+    /// When the events loops of the workers aren't independent, workers can't
+    /// be started while the UI thread is blocked, so they are pre-started.
+    fn default_worker_pool() -> WorkerPoolConfig {
+        let prestarted_workers = match GlobalScope::current()
+            .navigator()
+            .has_independent_event_loops()
+        {
+            true => 0,
+            false => 8,
+        };
+        WorkerPoolConfig {
+            prestarted_workers,
+            ..Default::default()
+        }
     }
 
     /// Consumes the [`WasiEnvBuilder`] and produces a [`WasiEnvInit`], which
@@ -688,6 +845,12 @@ impl WasiEnvBuilder {
                     .map_err(WasiStateCreationError::FileSystemError)?;
             }
 
+            wasi_fs.set_copy_cross_device_renames(self.copy_cross_device_renames);
+            if let Some(max_symlinks) = self.max_symlinks {
+                wasi_fs.set_max_symlinks(max_symlinks);
+            }
+            wasi_fs.set_confine_symlinks(self.confine_symlinks);
+
             if let Some(f) = &self.setup_fs_fn {
                 f(&inodes, &mut wasi_fs).map_err(WasiStateCreationError::WasiFsSetupError)?;
             }
@@ -710,25 +873,22 @@ impl WasiEnvBuilder {
             args: self.args.clone(),
             preopen: self.vfs_preopens.clone(),
             futexs: Default::default(),
+            mmaps: Default::default(),
             clock_offset: Default::default(),
             envs: std::sync::Mutex::new(conv_env_vars(self.envs)),
+            raw_sockets: self.raw_sockets,
+            resolver: Resolver::new(self.resolver.unwrap_or_default()),
+            #[cfg(feature = "tls")]
+            tls: self.tls,
         };
 
         let runtime = self.runtime.unwrap_or_else(|| {
                 panic!("this build does not support a default runtime - specify one with WasiEnvBuilder::runtime()");
         });
 
-        let control_plane = WasiControlPlane::new();
+        let control_plane = self.control_plane.unwrap_or_default();
 
-        let prestarted_workers = self.prestarted_workers.unwrap_or_else(|| {
-            match GlobalScope::current()
-                .navigator()
-                .has_independent_event_loops()
-            {
-                true => 0,
-                false => 8,
-            }
-        });
+        let worker_pool = self.worker_pool.unwrap_or_else(Self::default_worker_pool);
 
         let init = WasiEnvInit {
             state,
@@ -742,7 +902,7 @@ impl WasiEnvBuilder {
             wbg_js_module_name: self
                 .wbg_js_module_name
                 .ok_or(WasiStateCreationError::WbgJsModuleNameMissing)?,
-            prestarted_workers,
+            worker_pool,
         };
 
         Ok(init)
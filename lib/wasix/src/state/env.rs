@@ -7,20 +7,23 @@ use virtual_fs::{FsError, VirtualFile};
 use virtual_net::DynVirtualNetworking;
 use wasmer::{
     AsStoreMut, AsStoreRef, FunctionEnvMut, Imports, ImportsObj, Instance, Memory, MemoryType,
-    MemoryView, Module, TypedFunction, Value,
+    MemoryView, Module, RuntimeError, TypedFunction, Value,
 };
+use wasmer_types::ModuleHash;
 use wasmer_wasix_types::{
     types::Signal,
     wasi::{Errno, ExitCode, Snapshot0Clockid},
     wasix::ThreadStartType,
 };
 
-use crate::runtime::task_manager::SchedulerSpawn;
+use crate::runtime::task_manager::{SchedulerSpawn, WorkerPoolConfig};
 use crate::{
     fs::{WasiFsRoot, WasiInodes},
-    import_object_for_all_wasi_versions,
+    import_object_for_all_wasi_versions, mem_error_to_wasi,
+    net::shaping::SocketShaper,
     os::task::{
-        process::{WasiProcess, WasiProcessId},
+        coredump::Coredump,
+        process::{ProcessSnapshot, WasiProcess, WasiProcessCheckpoint, WasiProcessId},
         thread::{WasiThread, WasiThreadHandle, WasiThreadId},
     },
     runtime::{task_manager::InlineWaker, SpawnMemoryType},
@@ -137,7 +140,8 @@ pub struct WasiEnvInit {
     /// Name of wasm-bindgen generated JavaScript module.
     pub wbg_js_module_name: String,
 
-    pub prestarted_workers: usize,
+    /// How the pool of workers running the threads is sized.
+    pub worker_pool: WorkerPoolConfig,
 }
 
 impl WasiEnvInit {
@@ -154,12 +158,17 @@ impl WasiEnvInit {
                 inodes,
                 fs,
                 futexs: Default::default(),
+                mmaps: Default::default(),
                 clock_offset: std::sync::Mutex::new(
                     self.state.clock_offset.lock().unwrap().clone(),
                 ),
                 args: self.state.args.clone(),
                 envs: std::sync::Mutex::new(self.state.envs.lock().unwrap().deref().clone()),
                 preopen: self.state.preopen.clone(),
+                raw_sockets: self.state.raw_sockets,
+                resolver: self.state.resolver.clone(),
+                #[cfg(feature = "tls")]
+                tls: self.state.tls.clone(),
             },
             runtime: self.runtime.clone(),
             control_plane: self.control_plane.clone(),
@@ -169,7 +178,7 @@ impl WasiEnvInit {
             call_initialize: self.call_initialize,
             additional_imports: self.additional_imports.clone(),
             wbg_js_module_name: self.wbg_js_module_name.clone(),
-            prestarted_workers: self.prestarted_workers,
+            worker_pool: self.worker_pool.clone(),
         }
     }
 }
@@ -299,10 +308,11 @@ impl WasiEnv {
 
         let additional_imports = init.additional_imports.clone();
         let wbg_js_module_name = init.wbg_js_module_name.clone();
-        let prestarted_workers = init.prestarted_workers;
+        let worker_pool = init.worker_pool.clone();
 
         let env = Self::from_init(init)?;
         let pid = env.process.pid();
+        let process = env.process.clone();
 
         let mut store = store.as_store_mut();
 
@@ -376,6 +386,14 @@ impl WasiEnv {
             return Err(err.into());
         }
 
+        let module_hash = module
+            .info()
+            .hash()
+            .unwrap_or_else(|| ModuleHash::xxhash(module.serialize()));
+        if let Some(memory) = func_env.data(&store).try_memory_clone() {
+            process.set_module(module_hash, &memory, &store);
+        }
+
         // Set number of processors.
         if let Ok(nprocessors) = instance.exports.get_global("wasi_nprocessors").cloned() {
             match tasks.thread_parallelism() {
@@ -393,7 +411,7 @@ impl WasiEnv {
             module,
             memory: func_env.data(&store).try_memory_clone().unwrap(),
             wbg_js_module_name,
-            prestarted_workers,
+            worker_pool,
         };
         func_env.data(&store).tasks().init(scheduler_spawn).await;
 
@@ -402,6 +420,9 @@ impl WasiEnv {
             if let Ok(initialize) = instance.exports.get_function("_initialize") {
                 tracing::debug!("calling WASI _initialize");
                 if let Err(err) = crate::run_wasi_func_start(initialize, &mut store) {
+                    if let WasiRuntimeError::Runtime(err) = &err {
+                        func_env.data(&store).write_coredump(&store, err);
+                    }
                     func_env
                         .data(&store)
                         .blocking_on_exit(Some(Errno::Noexec.into()));
@@ -413,6 +434,26 @@ impl WasiEnv {
         Ok((instance, func_env))
     }
 
+    /// Writes a coredump of the current thread, which trapped with `error`,
+    /// to the sink of the runtime if it has one.
+    ///
+    /// This is done for the threads spawned by the process. Embedders which
+    /// call the exports of the instance themselves should call it when those
+    /// calls trap.
+    pub fn write_coredump(&self, store: &impl AsStoreRef, error: &RuntimeError) {
+        let Some(sink) = self.runtime.coredump_sink() else {
+            return;
+        };
+        let Some(inner) = self.try_inner() else {
+            return;
+        };
+        let view = inner.memory_view(store);
+        match Coredump::capture(self.pid(), self.tid(), inner.module(), &view, error) {
+            Ok(coredump) => sink.write(coredump),
+            Err(err) => tracing::warn!("cannot capture the coredump of the thread: {err}"),
+        }
+    }
+
     /// Returns a copy of the current runtime implementation for this environment
     pub fn runtime(&self) -> &(dyn Runtime + Send + Sync) {
         self.runtime.deref()
@@ -442,9 +483,24 @@ impl WasiEnv {
 
     /// Porcesses any signals that are batched up or any forced exit codes
     pub fn process_signals_and_exit(ctx: &mut FunctionEnvMut<'_, Self>) -> WasiResult<bool> {
+        let env = ctx.data();
+
+        // The threads of a stopped process park here until it is continued
+        if env.process.is_paused() {
+            tracing::trace!(pid=%env.pid(), tid=%env.tid(), "thread parked, the process is stopped");
+            InlineWaker::block_on(env.process.wait_while_paused());
+        }
+
+        // The threads halt here while a checkpoint of the process is taken
+        if env.process.checkpoint() != WasiProcessCheckpoint::Execute {
+            env.process.halt_for_checkpoint(|previous| {
+                let memory = unsafe { env.memory_view(ctx) };
+                ProcessSnapshot::capture(&memory, previous).map_err(mem_error_to_wasi)
+            });
+        }
+
         // If a signal handler has never been set then we need to handle signals
         // differently
-        let env = ctx.data();
         let inner = env
             .try_inner()
             .ok_or_else(|| WasiError::Exit(Errno::Fault.into()))?;
@@ -602,9 +658,35 @@ impl WasiEnv {
         None
     }
 
-    /// Accesses the virtual networking implementation
-    pub fn net(&self) -> &DynVirtualNetworking {
-        self.runtime.networking()
+    /// Accesses the virtual networking implementation, which is the network
+    /// shared by the control plane when it has one, whose traffic goes
+    /// through the firewall of the control plane, whose names are resolved
+    /// from the records of the control plane first, and whose traffic is
+    /// recorded when the control plane captures it
+    pub fn net(&self) -> DynVirtualNetworking {
+        let mut net = self
+            .control_plane
+            .network()
+            .unwrap_or_else(|| self.runtime.networking())
+            .clone();
+        if let Some(firewall) = self.control_plane.firewall() {
+            net = firewall.guard(net);
+        }
+        if let Some(dns) = self.control_plane.dns() {
+            net = dns.serve(net);
+        }
+        match self.control_plane.capture() {
+            Some(capture) => capture.tap(net, self.pid().raw()),
+            None => net,
+        }
+    }
+
+    /// The shaper of a socket the process opens, when the control plane
+    /// shapes the traffic of the processes
+    pub(crate) fn socket_shaper(&self) -> Option<SocketShaper> {
+        self.control_plane.shaping().map(|shaping| {
+            SocketShaper::new(shaping.clone(), self.pid().raw(), self.tasks().clone())
+        })
     }
 
     /// Providers safe access to the initialized part of WasiEnv
@@ -747,6 +829,7 @@ impl WasiEnv {
     #[allow(clippy::await_holding_lock)]
     pub fn on_exit(&self, exit_code: Option<ExitCode>) -> BoxFuture<'static, ()> {
         const CLEANUP_TIMEOUT: Duration = Duration::from_secs(10);
+        const THREAD_EXIT_TIMEOUT: Duration = Duration::from_secs(5);
 
         // If this is the main thread then also close all the files
         if self.thread.is_main() {
@@ -755,6 +838,7 @@ impl WasiEnv {
             let pid = self.pid();
 
             let timeout = self.tasks().sleep_now(CLEANUP_TIMEOUT);
+            let tasks = self.tasks().clone();
             let state = self.state.clone();
             Box::pin(async move {
                 if !disable_fs_cleanup {
@@ -776,7 +860,9 @@ impl WasiEnv {
 
                 // Terminate the process
                 let exit_code = exit_code.unwrap_or_else(|| Errno::Canceled.into());
-                process.terminate(exit_code);
+                process
+                    .terminate(exit_code, tasks.sleep_now(THREAD_EXIT_TIMEOUT))
+                    .await;
             })
         } else {
             Box::pin(async {})
@@ -5,7 +5,7 @@ use tracing::trace;
 use wasm_bindgen::{JsCast, JsValue};
 use wasmer::{
     AsStoreMut, AsStoreRef, ExportError, FunctionEnv, Imports, ImportsObj, Instance, Memory,
-    Module, Store, Value,
+    Module, Store, Type, Value,
 };
 use wasmer_wasix_types::wasi::ExitCode;
 
@@ -212,6 +212,9 @@ impl WasiFunctionEnv {
 
         let new_inner = WasiInstanceHandles::new(memory, store, instance);
 
+        let thread = self.data(store).thread.clone();
+        thread.start_accounting(store);
+
         let env = self.data_mut(store);
         env.set_inner(new_inner);
         env.state.fs.set_is_wasix(is_wasix_module);
@@ -253,7 +256,56 @@ impl WasiFunctionEnv {
             self.data(store).tid()
         );
 
+        self.run_exit_hooks(store);
+
         // Cleans up all the open files (if this is the main thread)
         self.data(store).blocking_on_exit(exit_code);
     }
+
+    /// Calls the exit hooks registered by the thread, the last registered
+    /// first. Hooks registered while they run (for instance destructors
+    /// setting their value again) are called as well, for a bounded number
+    /// of rounds.
+    fn run_exit_hooks(&self, store: &mut impl AsStoreMut) {
+        /// As `PTHREAD_DESTRUCTOR_ITERATIONS`
+        const EXIT_HOOK_ROUNDS: usize = 4;
+
+        let env = self.data(store);
+        let thread = env.thread.clone();
+        let table = env.try_inner().and_then(|inner| {
+            inner
+                .instance
+                .exports
+                .get_table("__indirect_function_table")
+                .ok()
+                .cloned()
+        });
+
+        for _ in 0..EXIT_HOOK_ROUNDS {
+            let hooks = thread.take_exit_hooks();
+            if hooks.is_empty() {
+                return;
+            }
+            let Some(table) = table.as_ref() else {
+                tracing::debug!(
+                    tid = %thread.tid(),
+                    "the module does not export its function table, skipping the exit hooks"
+                );
+                return;
+            };
+            for hook in hooks {
+                let Some(Value::FuncRef(Some(func))) = table.get(store, hook.func) else {
+                    tracing::debug!(tid = %thread.tid(), func = hook.func, "invalid exit hook");
+                    continue;
+                };
+                let arg = match func.ty(store).params() {
+                    [Type::I64] => Value::I64(hook.arg as i64),
+                    _ => Value::I32(hook.arg as i32),
+                };
+                if let Err(err) = func.call(store, &[arg]) {
+                    tracing::debug!(tid = %thread.tid(), func = hook.func, "exit hook failed: {err}");
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,198 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    task::Waker,
+};
+
+#[cfg(feature = "enable-serde")]
+use serde::{Deserialize, Serialize};
+
+/// Bitset of the waiters which are woken by any wake, see
+/// [`WasiFutexState::wake`].
+pub const FUTEX_BITSET_MATCH_ANY: u32 = u32::MAX;
+
+/// Represents a futex which will make threads wait for completion in a more
+/// CPU efficient manner
+#[derive(Debug, Default)]
+pub struct WasiFutex {
+    /// The threads waiting on the futex, in the order they started waiting
+    pub(crate) wakers: BTreeMap<u64, FutexWaiter>,
+}
+
+/// A thread waiting on a futex
+#[derive(Debug)]
+pub(crate) struct FutexWaiter {
+    pub waker: Option<Waker>,
+    /// Only the wakes whose bitset intersects this one wake the thread
+    pub bitset: u32,
+}
+
+/// Stores the state of the futexes
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub(crate) struct WasiFutexState {
+    pub poller_seed: u64,
+    pub futexes: HashMap<u64, WasiFutex>,
+    /// The futex each poller waits on, which changes when it is requeued
+    pub pollers: HashMap<u64, u64>,
+}
+
+impl WasiFutexState {
+    /// Registers a new poller waiting on a futex and returns its index
+    pub fn add_poller(&mut self, futex_idx: u64, bitset: u32) -> u64 {
+        self.poller_seed += 1;
+        let poller_idx = self.poller_seed;
+        self.futexes.entry(futex_idx).or_default().wakers.insert(
+            poller_idx,
+            FutexWaiter {
+                waker: None,
+                bitset,
+            },
+        );
+        self.pollers.insert(poller_idx, futex_idx);
+        poller_idx
+    }
+
+    /// Sets the waker of a poller, returns `false` if the poller was already
+    /// woken
+    pub fn register_waker(&mut self, poller_idx: u64, waker: &Waker) -> bool {
+        let Some(futex_idx) = self.pollers.get(&poller_idx) else {
+            return false;
+        };
+        match self
+            .futexes
+            .get_mut(futex_idx)
+            .and_then(|futex| futex.wakers.get_mut(&poller_idx))
+        {
+            Some(waiter) => {
+                waiter.waker.replace(waker.clone());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Unregisters a poller which stopped waiting
+    pub fn remove_poller(&mut self, poller_idx: u64) {
+        if let Some(futex_idx) = self.pollers.remove(&poller_idx) {
+            if let Some(waiter) = self.take_waiter(futex_idx, poller_idx) {
+                if let Some(waker) = waiter.waker {
+                    waker.wake();
+                }
+            }
+        }
+    }
+
+    /// Wakes up to `count` of the pollers waiting on a futex whose bitset
+    /// intersects `bitset`, oldest first, and returns how many were woken
+    pub fn wake(&mut self, futex_idx: u64, count: usize, bitset: u32) -> usize {
+        let Some(futex) = self.futexes.get(&futex_idx) else {
+            return 0;
+        };
+        let woken: Vec<_> = futex
+            .wakers
+            .iter()
+            .filter(|(_, waiter)| waiter.bitset & bitset != 0)
+            .map(|(poller_idx, _)| *poller_idx)
+            .take(count)
+            .collect();
+        for poller_idx in woken.iter() {
+            self.pollers.remove(poller_idx);
+            if let Some(waker) = self
+                .take_waiter(futex_idx, *poller_idx)
+                .and_then(|waiter| waiter.waker)
+            {
+                waker.wake();
+            }
+        }
+        woken.len()
+    }
+
+    /// Wakes up to `wake_count` of the pollers waiting on a futex and moves
+    /// up to `requeue_count` of the others to the `target` futex, without
+    /// waking them. Returns how many pollers were woken or requeued.
+    pub fn requeue(
+        &mut self,
+        futex_idx: u64,
+        wake_count: usize,
+        target: u64,
+        requeue_count: usize,
+    ) -> usize {
+        let woken = self.wake(futex_idx, wake_count, FUTEX_BITSET_MATCH_ANY);
+        if futex_idx == target {
+            return woken;
+        }
+        let Some(futex) = self.futexes.get(&futex_idx) else {
+            return woken;
+        };
+        let requeued: Vec<_> = futex.wakers.keys().copied().take(requeue_count).collect();
+        for poller_idx in requeued.iter() {
+            if let Some(waiter) = self.take_waiter(futex_idx, *poller_idx) {
+                self.futexes
+                    .entry(target)
+                    .or_default()
+                    .wakers
+                    .insert(*poller_idx, waiter);
+                self.pollers.insert(*poller_idx, target);
+            }
+        }
+        woken + requeued.len()
+    }
+
+    /// Removes a waiter from a futex, and the futex once nobody waits on it
+    fn take_waiter(&mut self, futex_idx: u64, poller_idx: u64) -> Option<FutexWaiter> {
+        let futex = self.futexes.get_mut(&futex_idx)?;
+        let waiter = futex.wakers.remove(&poller_idx);
+        if futex.wakers.is_empty() {
+            self.futexes.remove(&futex_idx);
+        }
+        waiter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wakes_match_the_bitsets() {
+        let mut state = WasiFutexState::default();
+        let any = state.add_poller(8, FUTEX_BITSET_MATCH_ANY);
+        let odd = state.add_poller(8, 0b01);
+        let even = state.add_poller(8, 0b10);
+
+        assert_eq!(state.wake(8, usize::MAX, 0b10), 2);
+        assert!(!state.register_waker(any, futures::task::noop_waker_ref()));
+        assert!(!state.register_waker(even, futures::task::noop_waker_ref()));
+        assert!(state.register_waker(odd, futures::task::noop_waker_ref()));
+
+        assert_eq!(state.wake(16, 1, FUTEX_BITSET_MATCH_ANY), 0);
+        assert_eq!(state.wake(8, 1, FUTEX_BITSET_MATCH_ANY), 1);
+        assert!(state.futexes.is_empty());
+        assert!(state.pollers.is_empty());
+    }
+
+    #[test]
+    fn requeued_pollers_wait_on_the_target() {
+        let mut state = WasiFutexState::default();
+        let pollers: Vec<_> = (0..4)
+            .map(|_| state.add_poller(8, FUTEX_BITSET_MATCH_ANY))
+            .collect();
+        let waiting = state.add_poller(16, FUTEX_BITSET_MATCH_ANY);
+
+        // The oldest poller is woken, the next two move to the target
+        assert_eq!(state.requeue(8, 1, 16, 2), 3);
+        assert!(!state.register_waker(pollers[0], futures::task::noop_waker_ref()));
+        assert_eq!(state.pollers[&pollers[1]], 16);
+        assert_eq!(state.pollers[&pollers[2]], 16);
+        assert_eq!(state.pollers[&pollers[3]], 8);
+
+        // They are woken in the order they started waiting
+        assert_eq!(state.wake(16, 2, FUTEX_BITSET_MATCH_ANY), 2);
+        assert!(!state.register_waker(pollers[1], futures::task::noop_waker_ref()));
+        assert!(state.register_waker(waiting, futures::task::noop_waker_ref()));
+
+        state.remove_poller(pollers[2]);
+        state.remove_poller(pollers[3]);
+        assert_eq!(state.futexes.keys().collect::<Vec<_>>(), [&16]);
+    }
+}
@@ -18,32 +18,35 @@
 mod builder;
 mod env;
 mod func_env;
+mod futex;
 mod handles;
 mod types;
 
 use std::{
-    collections::{BTreeMap, HashMap},
-    path::Path,
-    sync::Mutex,
-    task::Waker,
-    time::Duration,
+    collections::HashMap, ffi::OsString, path::Path, sync::Mutex, task::Waker, time::Duration,
 };
 
-use virtual_fs::{FileOpener, FileSystem, FsError, OpenOptions, VirtualFile};
+use virtual_fs::{
+    AsyncReadExt, AsyncWriteExt, FileOpener, FileSystem, FsError, OpenOptions, VirtualFile,
+};
 use wasmer_wasix_types::wasi::{Errno, Fd as WasiFd, Rights, Snapshot0Clockid};
 
 #[cfg(feature = "enable-serde")]
 use serde::{Deserialize, Serialize};
 
+pub(crate) use self::futex::WasiFutexState;
 pub use self::{
     builder::*,
     env::{WasiEnv, WasiEnvInit, WasiInstanceHandles},
     func_env::WasiFunctionEnv,
+    futex::FUTEX_BITSET_MATCH_ANY,
     types::*,
 };
-pub use crate::fs::InodeGuard;
 use crate::{
-    fs::{fs_error_into_wasi_err, WasiFs, WasiFsRoot, WasiInodes, WasiStateFileGuard},
+    fs::{
+        fs_error_into_wasi_err, MemoryMappings, WasiFs, WasiFsRoot, WasiInodes, WasiStateFileGuard,
+    },
+    net::resolver::Resolver,
     syscalls::types::*,
     utils::WasiParkingLot,
 };
@@ -68,13 +71,6 @@ impl FileOpener for WasiStateOpener {
     }
 }
 
-/// Represents a futex which will make threads wait for completion in a more
-/// CPU efficient manner
-#[derive(Debug, Default)]
-pub struct WasiFutex {
-    pub(crate) wakers: BTreeMap<u64, Option<Waker>>,
-}
-
 /// Structure that holds the state of BUS calls to this process and from
 /// this process. BUS calls are the equivalent of RPC's with support
 /// for all the major serializers
@@ -108,14 +104,6 @@ impl WasiBusState {
     }
 }
 
-/// Stores the state of the futexes
-#[derive(Debug, Default)]
-#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
-pub(crate) struct WasiFutexState {
-    pub poller_seed: u64,
-    pub futexes: HashMap<u64, WasiFutex>,
-}
-
 /// Top level data type containing all* the state with which WASI can
 /// interact.
 ///
@@ -128,9 +116,22 @@ pub(crate) struct WasiState {
     pub fs: WasiFs,
     pub inodes: WasiInodes,
     pub futexs: Mutex<WasiFutexState>,
+    /// The files mapped into the memory of the process
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    pub mmaps: Mutex<MemoryMappings>,
     pub clock_offset: Mutex<HashMap<Snapshot0Clockid, i64>>,
     pub args: Vec<String>,
     pub envs: Mutex<Vec<Vec<u8>>>,
+    /// Whether raw and ICMP sockets can be opened
+    pub raw_sockets: bool,
+    /// Resolves the names the program looks up
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    pub resolver: Resolver,
+    /// Which servers the streams the program encrypts trust, the
+    /// certificate authorities of the web when it's not set
+    #[cfg(feature = "tls")]
+    #[cfg_attr(feature = "enable-serde", serde(skip))]
+    pub tls: Option<crate::net::TlsConfig>,
 
     // TODO: should not be here, since this requires active work to resolve.
     // State should only hold active runtime state that can be reproducibly re-created.
@@ -168,17 +169,69 @@ impl WasiState {
         from: P,
         to: Q,
     ) -> Result<(), Errno> {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        match self.fs.root_fs.rename(from, to).await {
+            Err(FsError::CrossDevice) if self.fs.copy_cross_device_renames() => self
+                .fs_copy_across_devices(from, to)
+                .await
+                .map_err(fs_error_into_wasi_err),
+            result => result.map_err(fs_error_into_wasi_err),
+        }
+    }
+
+    /// Moves a file to another mount by copying it next to the target and
+    /// renaming the copy over the target, so that readers of the target never
+    /// see a partial copy, before removing the source.
+    async fn fs_copy_across_devices(&self, from: &Path, to: &Path) -> Result<(), FsError> {
+        let root_fs = &self.fs.root_fs;
+        if !root_fs.metadata(from)?.is_file() {
+            return Err(FsError::CrossDevice);
+        }
+
+        let mut copy_name = OsString::from(".");
+        copy_name.push(to.file_name().ok_or(FsError::InvalidInput)?);
+        copy_name.push(".rename");
+        let copy_path = to.with_file_name(copy_name);
+
+        let mut data = Vec::new();
+        let mut source = root_fs.new_open_options().read(true).open(from)?;
+        source.read_to_end(&mut data).await?;
+        let result = async {
+            let mut copy = root_fs
+                .new_open_options()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&copy_path)?;
+            copy.write_all(&data).await?;
+            copy.flush().await?;
+            drop(copy);
+            root_fs.rename(&copy_path, to).await
+        }
+        .await;
+        if let Err(err) = result {
+            root_fs.remove_file(&copy_path).ok();
+            return Err(err);
+        }
+
+        root_fs.remove_file(from)
+    }
+
+    pub(crate) fn fs_remove_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Errno> {
         self.fs
             .root_fs
-            .rename(from.as_ref(), to.as_ref())
-            .await
+            .remove_file(path.as_ref())
             .map_err(fs_error_into_wasi_err)
     }
 
-    pub(crate) fn fs_remove_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Errno> {
+    pub(crate) fn fs_hard_link<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        from: P,
+        to: Q,
+    ) -> Result<(), Errno> {
         self.fs
             .root_fs
-            .remove_file(path.as_ref())
+            .hard_link(from.as_ref(), to.as_ref())
             .map_err(fs_error_into_wasi_err)
     }
 
@@ -67,7 +67,7 @@ pub(crate) use crate::{
         socket::{InodeSocket, InodeSocketKind},
         write_ip_port,
     },
-    state::{InodeGuard, PollEvent, PollEventBuilder, WasiState},
+    state::{PollEvent, PollEventBuilder, WasiState, FUTEX_BITSET_MATCH_ANY},
     utils::{self, map_io_err},
     VirtualTaskManager, WasiEnv, WasiError, WasiFunctionEnv,
 };
@@ -400,13 +400,14 @@ where
             drop(guard);
 
             // Start the work using the socket
-            let work = actor(socket, fd_entry.flags);
+            let work = actor(socket.clone(), fd_entry.flags);
 
             // Block on the work and process it
             let res = InlineWaker::block_on(work);
             let new_socket = res?;
 
             if let Some(mut new_socket) = new_socket {
+                new_socket.inherit_options(&socket);
                 let mut guard = inode.write();
                 match guard.deref_mut() {
                     Kind::Socket { socket, .. } => {
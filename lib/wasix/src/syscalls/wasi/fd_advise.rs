@@ -1,5 +1,6 @@
 use super::*;
 use crate::syscalls::*;
+use virtual_fs::FileAdvice;
 
 /// ### `fd_advise()`
 /// Advise the system about how a file will be used
@@ -29,21 +30,42 @@ pub(crate) fn fd_advise_internal(
     fd: WasiFd,
     offset: Filesize,
     len: Filesize,
-    _advice: Advice,
+    advice: Advice,
 ) -> Result<(), Errno> {
-    // Instead of unconditionally returning OK.  This barebones implementation
-    // only performs basic fd and rights checks.
-
     let env = ctx.data();
     let (_, state) = unsafe { env.get_memory_and_wasi_state(&ctx, 0) };
     let fd_entry = state.fs.get_fd(fd)?;
-    let _inode = fd_entry.inode;
+    let inode = fd_entry.inode;
 
     if !fd_entry.rights.contains(Rights::FD_ADVISE) {
         return Err(Errno::Access);
     }
 
     let _end = offset.checked_add(len).ok_or(Errno::Inval)?;
+    let advice = match advice {
+        Advice::Normal => FileAdvice::Normal,
+        Advice::Sequential => FileAdvice::Sequential,
+        Advice::Random => FileAdvice::Random,
+        Advice::Willneed => FileAdvice::WillNeed,
+        Advice::Dontneed => FileAdvice::DontNeed,
+        Advice::Noreuse => FileAdvice::NoReuse,
+        Advice::Unknown => return Err(Errno::Inval),
+    };
 
-    Ok(())
+    // The advice is forwarded to the file system, which may prefetch or drop
+    // cached data, and is otherwise ignored
+    let guard = inode.read();
+    match guard.deref() {
+        Kind::File {
+            handle: Some(handle),
+            ..
+        } => {
+            let mut handle = handle.write().unwrap();
+            handle
+                .advise(offset, len, advice)
+                .map_err(fs_error_into_wasi_err)
+        }
+        Kind::Socket { .. } | Kind::Pipe { .. } => Err(Errno::Spipe),
+        _ => Ok(()),
+    }
 }
@@ -36,13 +36,17 @@ pub(crate) fn fd_allocate_internal(
         return Err(Errno::Access);
     }
     let new_size = offset.checked_add(len).ok_or(Errno::Inval)?;
-    {
+    // The file only ever grows
+    let new_size = {
         let mut guard = inode.write();
         match guard.deref_mut() {
             Kind::File { handle, .. } => {
                 if let Some(handle) = handle {
                     let mut handle = handle.write().unwrap();
-                    handle.set_len(new_size).map_err(fs_error_into_wasi_err)?;
+                    handle
+                        .allocate(offset, len)
+                        .map_err(fs_error_into_wasi_err)?;
+                    handle.size()
                 } else {
                     return Err(Errno::Badf);
                 }
@@ -50,13 +54,16 @@ pub(crate) fn fd_allocate_internal(
             Kind::Socket { .. } => return Err(Errno::Badf),
             Kind::Pipe { .. } => return Err(Errno::Badf),
             Kind::Buffer { buffer } => {
-                buffer.resize(new_size as usize, 0);
+                if new_size as usize > buffer.len() {
+                    buffer.resize(new_size as usize, 0);
+                }
+                buffer.len() as u64
             }
             Kind::Symlink { .. } => return Err(Errno::Badf),
             Kind::EventNotifications { .. } | Kind::Epoll { .. } => return Err(Errno::Badf),
             Kind::Dir { .. } | Kind::Root { .. } => return Err(Errno::Isdir),
         }
-    }
+    };
     inode.stat.write().unwrap().st_size = new_size;
     debug!(%new_size);
 
@@ -1,5 +1,5 @@
 use super::*;
-use crate::syscalls::*;
+use crate::{fs::lock_key, syscalls::*};
 
 /// ### `fd_close()`
 /// Close an open file descriptor
@@ -29,8 +29,32 @@ pub fn fd_close(mut ctx: FunctionEnvMut<'_, WasiEnv>, fd: WasiFd) -> Result<Errn
     }
 
     let env = ctx.data();
-    let (_, state) = unsafe { env.get_memory_and_wasi_state(&ctx, 0) };
+    let (memory, state) = unsafe { env.get_memory_and_wasi_state(&ctx, 0) };
+    let fd_entry = state.fs.get_fd(fd).ok();
+    let key = fd_entry
+        .as_ref()
+        .and_then(|fd| lock_key(&state.fs, &fd.inode));
+    let mapped = match fd_entry.as_ref().map(|fd| fd.inode.read()).as_deref() {
+        Some(Kind::File {
+            handle: Some(handle),
+            ..
+        }) => state.mmaps.lock().unwrap().mapped_through(handle),
+        _ => Vec::new(),
+    };
     wasi_try_ok!(state.fs.close_fd(fd));
 
+    // The changes to the mappings made through the descriptor are written
+    // back, the mappings themselves staying
+    for mapping in mapped {
+        if let Err(err) = block_on(mapping.store(&memory)) {
+            tracing::debug!(%fd, %err, "could not write back a mapping of the file");
+        }
+    }
+
+    // Closing any descriptor of a file releases the locks of the process on it
+    if let Some(key) = key {
+        env.control_plane.file_locks().file_closed(&key, env.pid());
+    }
+
     Ok(Errno::Success)
 }
@@ -1,5 +1,6 @@
 use super::*;
 use crate::syscalls::*;
+use wasmer_wasix_types::wasi::WatchMask;
 
 /// ### `fd_filestat_set_size()`
 /// Change the size of an open file, zeroing out any new bytes
@@ -35,10 +36,13 @@ pub(crate) fn fd_filestat_set_size_internal(
     {
         let mut guard = inode.write();
         match guard.deref_mut() {
-            Kind::File { handle, .. } => {
+            Kind::File { handle, path, .. } => {
                 if let Some(handle) = handle {
                     let mut handle = handle.write().unwrap();
                     handle.set_len(st_size).map_err(fs_error_into_wasi_err)?;
+                    env.control_plane
+                        .file_watchers()
+                        .notify(path, WatchMask::MODIFY);
                 } else {
                     return Err(Errno::Badf);
                 }
@@ -229,6 +229,7 @@ pub(crate) fn fd_read_internal<M: MemorySize>(
                                         buf.as_mut_uninit(),
                                         Some(timeout),
                                         nonblocking,
+                                        false,
                                     )
                                     .await?;
                                 total_read += local_read;
@@ -311,6 +312,17 @@ pub(crate) fn fd_read_internal<M: MemorySize>(
                     return Ok(Err(Errno::Isdir));
                 }
                 Kind::EventNotifications { inner } => {
+                    // The counter is only read whole
+                    let memory = unsafe { env.memory_view(ctx) };
+                    let iovs_arr = wasi_try_mem_ok_ok!(iovs.slice(&memory, iovs_len));
+                    let iovs_arr = wasi_try_mem_ok_ok!(iovs_arr.access());
+                    let buf_len = iovs_arr
+                        .iter()
+                        .map(|iov| iov.buf_len.into())
+                        .fold(0u64, u64::saturating_add);
+                    wasi_try_ok_ok!(NotificationInner::check_read_len(buf_len));
+                    drop(iovs_arr);
+
                     // Create a poller
                     struct NotifyPoller {
                         inner: Arc<NotificationInner>,
@@ -1,5 +1,5 @@
 use super::*;
-use crate::syscalls::*;
+use crate::{fs::Inode, syscalls::*};
 
 /// ### `fd_readdir()`
 /// Read data from directory specified by file descriptor
@@ -33,18 +33,21 @@ pub fn fd_readdir<M: MemorySize>(
     let buf_arr = wasi_try_mem!(buf.slice(&memory, buf_len));
     let bufused_ref = bufused.deref(&memory);
     let working_dir = wasi_try!(state.fs.get_fd(fd));
-    let mut cur_cookie = cookie;
     let mut buf_idx = 0usize;
 
-    let entries: Vec<(String, Filetype, u64)> = {
+    // The entries are ordered by their cookie, which is derived from their
+    // name rather than from their position, so that the entries added or
+    // removed while the directory is being read don't make the others be
+    // skipped or returned twice.
+    let mut entries: Vec<(Dircookie, String, Filetype, u64)> = {
         let guard = working_dir.inode.read();
         match guard.deref() {
-            Kind::Dir { path, entries, .. } => {
+            Kind::Dir {
+                path,
+                entries,
+                parent,
+            } => {
                 trace!("reading dir {:?}", path);
-                // TODO: refactor this code
-                // we need to support multiple calls,
-                // simple and obviously correct implementation for now:
-                // maintain consistent order via lexacographic sorting
                 let fs_info = wasi_try!(wasi_try!(state.fs_read_dir(path))
                     .collect::<Result<Vec<_>, _>>()
                     .map_err(fs_error_into_wasi_err));
@@ -56,39 +59,44 @@ pub fn fd_readdir<M: MemorySize>(
                         let filetype = virtual_file_type_to_wasi_file_type(
                             entry.file_type().map_err(fs_error_into_wasi_err)?,
                         );
-                        Ok((
-                            filename, filetype, 0, // TODO: inode
-                        ))
+                        // The inodes which aren't loaded yet will be numbered
+                        // after their path
+                        let ino = match entries.get(&filename) {
+                            Some(inode) => inode.stat.read().unwrap().st_ino,
+                            None => Inode::from_path(&entry.path.to_string_lossy()).as_u64(),
+                        };
+                        Ok((dir_cookie(&filename), filename, filetype, ino))
                     })
-                    .collect::<Result<Vec<(String, Filetype, u64)>, _>>());
+                    .collect::<Result<Vec<_>, _>>());
                 entry_vec.extend(entries.iter().filter(|(_, inode)| inode.is_preopened).map(
                     |(_name, inode)| {
                         let stat = inode.stat.read().unwrap();
-                        (inode.name.to_string(), stat.st_filetype, stat.st_ino)
+                        let name = inode.name.to_string();
+                        (dir_cookie(&name), name, stat.st_filetype, stat.st_ino)
                     },
                 ));
                 // adding . and .. special folders
-                // TODO: inode
-                entry_vec.push((".".to_string(), Filetype::Directory, 0));
-                entry_vec.push(("..".to_string(), Filetype::Directory, 0));
-                entry_vec.sort_by(|a, b| a.0.cmp(&b.0));
+                let ino = working_dir.inode.stat.read().unwrap().st_ino;
+                let parent_ino = parent
+                    .upgrade()
+                    .map_or(ino, |parent| parent.stat.read().unwrap().st_ino);
+                entry_vec.push((DOT_COOKIE, ".".to_string(), Filetype::Directory, ino));
+                entry_vec.push((
+                    DOT_DOT_COOKIE,
+                    "..".to_string(),
+                    Filetype::Directory,
+                    parent_ino,
+                ));
                 entry_vec
             }
             Kind::Root { entries } => {
                 trace!("reading root");
-                let sorted_entries = {
-                    let mut entry_vec: Vec<(String, InodeGuard)> = entries
-                        .iter()
-                        .map(|(a, b)| (a.clone(), b.clone()))
-                        .collect();
-                    entry_vec.sort_by(|a, b| a.0.cmp(&b.0));
-                    entry_vec
-                };
-                sorted_entries
-                    .into_iter()
-                    .map(|(_name, inode)| {
+                entries
+                    .values()
+                    .map(|inode| {
                         let stat = inode.stat.read().unwrap();
-                        (format!("/{}", inode.name), stat.st_filetype, stat.st_ino)
+                        let name = format!("/{}", inode.name);
+                        (dir_cookie(&name), name, stat.st_filetype, stat.st_ino)
                     })
                     .collect()
             }
@@ -101,13 +109,15 @@ pub fn fd_readdir<M: MemorySize>(
             | Kind::Epoll { .. } => return Errno::Notdir,
         }
     };
+    entries.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
 
-    for (entry_path_str, wasi_file_type, ino) in entries.iter().skip(cookie as usize) {
-        cur_cookie += 1;
+    for (entry_cookie, entry_path_str, wasi_file_type, ino) in
+        entries.iter().filter(|entry| entry.0 > cookie)
+    {
         let namlen = entry_path_str.len();
         trace!("returning dirent for {}", entry_path_str);
         let dirent = Dirent {
-            d_next: cur_cookie,
+            d_next: *entry_cookie,
             d_ino: *ino,
             d_namlen: namlen as u32,
             d_type: *wasi_file_type,
@@ -139,3 +149,14 @@ pub fn fd_readdir<M: MemorySize>(
     wasi_try_mem!(bufused_ref.write(buf_idx));
     Errno::Success
 }
+
+/// The cookies of `.` and `..`, which come first.
+const DOT_COOKIE: Dircookie = 1;
+const DOT_DOT_COOKIE: Dircookie = 2;
+
+/// The cookie of the directory entry called `name`, a hash of its name which
+/// doesn't depend on the other entries. It's also the cookie from which
+/// reading resumes after that entry.
+fn dir_cookie(name: &str) -> Dircookie {
+    xxhash_rust::xxh64::xxh64(name.as_bytes(), 0).max(DOT_DOT_COOKIE + 1)
+}
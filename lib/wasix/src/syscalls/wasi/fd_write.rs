@@ -1,5 +1,6 @@
 use super::*;
-use crate::{net::socket::TimeType, syscalls::*};
+use crate::{fs::NotificationInner, net::socket::TimeType, syscalls::*};
+use wasmer_wasix_types::wasi::WatchMask;
 
 /// ### `fd_write()`
 /// Write data to the file descriptor
@@ -132,9 +133,17 @@ pub(crate) fn fd_write_internal<M: MemorySize>(
             let (memory, _) = unsafe { env.get_memory_and_wasi_state(&ctx, 0) };
             let mut guard = fd_entry.inode.write();
             match guard.deref_mut() {
-                Kind::File { handle, .. } => {
+                Kind::File { handle, path, .. } => {
                     if let Some(handle) = handle {
                         let handle = handle.clone();
+                        let watchers = env.control_plane.file_watchers();
+                        let modified = if !is_stdio && watchers.is_watching() {
+                            Some(path.clone())
+                        } else {
+                            None
+                        };
+                        let mapped = (!is_stdio && state.mmaps.lock().unwrap().is_mapped(path))
+                            .then(|| path.clone());
                         drop(guard);
 
                         let res = block_on_with_timeout(
@@ -181,7 +190,7 @@ pub(crate) fn fd_write_internal<M: MemorySize>(
                                         }
                                     }
                                     FdWriteSource::Buffer(data) => {
-                                        handle.write_all(data).await?;
+                                        handle.write_all(data).await.map_err(map_io_err)?;
                                         written += data.len();
                                     }
                                 }
@@ -196,6 +205,20 @@ pub(crate) fn fd_write_internal<M: MemorySize>(
                             Errno::Timedout => Errno::Again,
                             a => a,
                         }));
+                        if let Some(path) = modified.filter(|_| written > 0) {
+                            watchers.notify(&path, WatchMask::MODIFY);
+                        }
+                        // The shared mappings of the file show what was written
+                        if let Some(path) = mapped.filter(|_| written > 0) {
+                            let remapped = state.mmaps.lock().unwrap().mapped_file(
+                                &path,
+                                offset,
+                                written as u64,
+                            );
+                            for mapping in remapped {
+                                wasi_try_ok_ok!(block_on(mapping.load(&memory)));
+                            }
+                        }
 
                         (written, true, true)
                     } else {
@@ -294,8 +317,9 @@ pub(crate) fn fd_write_internal<M: MemorySize>(
                     return Ok(Err(Errno::Isdir));
                 }
                 Kind::EventNotifications { inner } => {
-                    let mut written = 0usize;
-
+                    // Only the first value is written, as on Linux
+                    let mut val = [0u8; NotificationInner::VALUE_SIZE];
+                    let mut filled = 0;
                     match &data {
                         FdWriteSource::Iovs { iovs, iovs_len } => {
                             let iovs_arr = wasi_try_ok_ok!(iovs
@@ -304,48 +328,36 @@ pub(crate) fn fd_write_internal<M: MemorySize>(
                             let iovs_arr =
                                 wasi_try_ok_ok!(iovs_arr.access().map_err(mem_error_to_wasi));
                             for iovs in iovs_arr.iter() {
-                                let buf_len: usize = wasi_try_ok_ok!(iovs
-                                    .buf_len
-                                    .try_into()
-                                    .map_err(|_| Errno::Inval));
-                                let will_be_written = buf_len;
-
-                                let val_cnt = buf_len / std::mem::size_of::<u64>();
-                                let val_cnt: M::Offset =
-                                    wasi_try_ok_ok!(val_cnt.try_into().map_err(|_| Errno::Inval));
-
-                                let vals = wasi_try_ok_ok!(WasmPtr::<u64, M>::new(iovs.buf)
-                                    .slice(&memory, val_cnt as M::Offset)
-                                    .map_err(mem_error_to_wasi));
-                                let vals =
-                                    wasi_try_ok_ok!(vals.access().map_err(mem_error_to_wasi));
-                                for val in vals.iter() {
-                                    inner.write(*val);
+                                if filled == val.len() {
+                                    break;
                                 }
-
-                                written += will_be_written;
+                                let buf = wasi_try_ok_ok!(WasmPtr::<u8, M>::new(iovs.buf)
+                                    .slice(&memory, iovs.buf_len)
+                                    .map_err(mem_error_to_wasi));
+                                let buf = wasi_try_ok_ok!(buf.access().map_err(mem_error_to_wasi));
+                                let len = buf.len().min(val.len() - filled);
+                                val[filled..filled + len].copy_from_slice(&buf.as_ref()[..len]);
+                                filled += len;
                             }
                         }
                         FdWriteSource::Buffer(data) => {
-                            let cnt = data.len() / std::mem::size_of::<u64>();
-                            for n in 0..cnt {
-                                let start = n * std::mem::size_of::<u64>();
-                                let data = [
-                                    data[start],
-                                    data[start + 1],
-                                    data[start + 2],
-                                    data[start + 3],
-                                    data[start + 4],
-                                    data[start + 5],
-                                    data[start + 6],
-                                    data[start + 7],
-                                ];
-                                inner.write(u64::from_ne_bytes(data));
-                            }
+                            filled = data.len().min(val.len());
+                            val[..filled].copy_from_slice(&data[..filled]);
                         }
                     }
+                    let val = wasi_try_ok_ok!(NotificationInner::value_of(&val[..filled]));
 
-                    (written, false, true)
+                    // A value which would overflow the counter waits for it
+                    // to be read
+                    let inner = inner.clone();
+                    drop(guard);
+                    if fd_flags.contains(Fdflags::NONBLOCK) {
+                        wasi_try_ok_ok!(inner.try_write(val));
+                    } else {
+                        block_on(futures::future::poll_fn(|cx| inner.write(val, cx.waker())));
+                    }
+
+                    (filled, false, true)
                 }
                 Kind::Symlink { .. } | Kind::Epoll { .. } => return Ok(Err(Errno::Inval)),
                 Kind::Buffer { buffer } => {
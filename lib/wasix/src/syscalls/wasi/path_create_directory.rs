@@ -1,5 +1,6 @@
 use super::*;
 use crate::syscalls::*;
+use wasmer_wasix_types::wasi::WatchMask;
 
 /// ### `path_create_directory()`
 /// Create directory at a path
@@ -111,6 +112,10 @@ pub(crate) fn path_create_directory_internal(
                     } else {
                         created_directory = true;
                         state.fs_create_dir(&adjusted_path)?;
+                        state.fs.apply_umask(&adjusted_path, true);
+                        env.control_plane
+                            .file_watchers()
+                            .notify(&adjusted_path, WatchMask::CREATE | WatchMask::ISDIR);
                     }
                     let kind = Kind::Dir {
                         parent: cur_dir_inode.downgrade(),
@@ -1,5 +1,6 @@
 use super::*;
 use crate::syscalls::*;
+use wasmer_wasix_types::wasi::WatchMask;
 
 /// ### `path_link()`
 /// Create a hard link
@@ -94,17 +95,50 @@ pub(crate) fn path_link_internal(
             .fs
             .get_parent_inode_at_path(inodes, new_fd, &target_path_arg, false)?;
 
-    if source_inode.stat.write().unwrap().st_nlink == Linkcount::max_value() {
+    // Only files can have several names
+    let source_path = match source_inode.read().deref() {
+        Kind::File { path, .. } => path.clone(),
+        Kind::Dir { .. } | Kind::Root { .. } => return Err(Errno::Perm),
+        _ => return Err(Errno::Notsup),
+    };
+    let stat = *source_inode.stat.read().unwrap();
+    if stat.st_nlink == Linkcount::MAX {
         return Err(Errno::Mlink);
     }
     {
         let mut guard = target_parent_inode.write();
         match guard.deref_mut() {
-            Kind::Dir { entries, .. } => {
+            Kind::Dir { entries, path, .. } => {
                 if entries.contains_key(&new_entry_name) {
                     return Err(Errno::Exist);
                 }
-                entries.insert(new_entry_name, source_inode.clone());
+                let mut target_path = path.clone();
+                target_path.push(&new_entry_name);
+                state.fs_hard_link(&source_path, &target_path)?;
+                let watchers = env.control_plane.file_watchers();
+                watchers.notify(&target_path, WatchMask::CREATE);
+                watchers.notify(&source_path, WatchMask::ATTRIB);
+
+                // Every name gets its own inode, pointing at the path of that
+                // name, which shares the serial number and the link count
+                let kind = Kind::File {
+                    handle: None,
+                    path: target_path,
+                    fd: None,
+                };
+                let stat = Filestat {
+                    st_nlink: stat.st_nlink + 1,
+                    ..stat
+                };
+                let target_inode = state.fs.create_inode_with_stat(
+                    inodes,
+                    kind,
+                    false,
+                    new_entry_name.clone().into(),
+                    stat,
+                );
+                target_inode.stat.write().unwrap().st_ino = stat.st_ino;
+                entries.insert(new_entry_name, target_inode);
             }
             Kind::Root { .. } => return Err(Errno::Inval),
             Kind::File { .. }
@@ -1,5 +1,6 @@
 use super::*;
 use crate::syscalls::*;
+use wasmer_wasix_types::wasi::WatchMask;
 
 /// ### `path_open()`
 /// Open file located at the given path
@@ -346,6 +347,10 @@ pub(crate) fn path_open_internal(
                     }
                 }
             };
+            state.fs.apply_umask(&new_file_host_path, false);
+            env.control_plane
+                .file_watchers()
+                .notify(&new_file_host_path, WatchMask::CREATE);
 
             let new_inode = {
                 let kind = Kind::File {
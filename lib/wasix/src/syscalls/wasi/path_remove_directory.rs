@@ -1,5 +1,6 @@
 use super::*;
 use crate::syscalls::*;
+use wasmer_wasix_types::wasi::WatchMask;
 
 /// Returns Errno::Notemtpy if directory is not empty
 #[instrument(level = "trace", skip_all, fields(%fd, path = field::Empty), ret)]
@@ -150,6 +151,9 @@ pub(crate) fn path_remove_directory_internal(
             if let Err(e) = state.fs_remove_dir(&child_path) {
                 tracing::warn!(path = ?child_path, error = ?e, "failed to remove directory");
             }
+            env.control_plane
+                .file_watchers()
+                .notify(child_path, WatchMask::DELETE | WatchMask::ISDIR);
         }
 
         drop(parent)
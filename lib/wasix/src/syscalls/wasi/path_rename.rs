@@ -80,14 +80,10 @@ pub fn path_rename_internal(
         Path::new(target_path),
         true
     ));
-    let mut need_create = true;
     let host_adjusted_target_path = {
         let guard = target_parent_inode.read();
         match guard.deref() {
-            Kind::Dir { entries, path, .. } => {
-                if entries.contains_key(&target_entry_name) {
-                    need_create = false;
-                }
+            Kind::Dir { path, .. } => {
                 let mut out_path = path.clone();
                 out_path.push(std::path::Path::new(&target_entry_name));
                 out_path
@@ -105,10 +101,10 @@ pub fn path_rename_internal(
     };
 
     let source_entry = {
-        let mut guard = source_parent_inode.write();
-        match guard.deref_mut() {
+        let guard = source_parent_inode.read();
+        match guard.deref() {
             Kind::Dir { entries, .. } => {
-                wasi_try_ok!(entries.remove(&source_entry_name).ok_or(Errno::Noent))
+                wasi_try_ok!(entries.get(&source_entry_name).cloned().ok_or(Errno::Noent))
             }
             Kind::Root { .. } => return Ok(Errno::Notcapable),
             Kind::Socket { .. }
@@ -124,90 +120,71 @@ pub fn path_rename_internal(
         }
     };
 
-    {
-        let mut guard = source_entry.write();
-        match guard.deref_mut() {
-            Kind::File { ref path, .. } => {
-                let result = {
-                    let path_clone = path.clone();
-                    drop(guard);
-                    let state = state;
-                    let host_adjusted_target_path = host_adjusted_target_path.clone();
-                    block_on(async move {
-                        state
-                            .fs_rename(path_clone, &host_adjusted_target_path)
-                            .await
-                    })
-                };
-                // if the above operation failed we have to revert the previous change and then fail
-                if let Err(e) = result {
-                    let mut guard = source_parent_inode.write();
-                    if let Kind::Dir { entries, .. } = guard.deref_mut() {
-                        entries.insert(source_entry_name, source_entry);
-                        return Ok(e);
-                    }
-                } else {
-                    {
-                        let mut guard = source_entry.write();
-                        if let Kind::File { ref mut path, .. } = guard.deref_mut() {
-                            *path = host_adjusted_target_path;
-                        } else {
-                            unreachable!()
-                        }
-                    }
-                }
-            }
-            Kind::Dir { ref path, .. } => {
-                let cloned_path = path.clone();
-                let res = {
-                    let state = state;
-                    let host_adjusted_target_path = host_adjusted_target_path.clone();
-                    block_on(async move {
-                        state
-                            .fs_rename(cloned_path, &host_adjusted_target_path)
-                            .await
-                    })
-                };
-                if let Err(e) = res {
-                    return Ok(e);
-                }
-                {
-                    drop(guard);
-                    let mut guard = source_entry.write();
-                    if let Kind::Dir { path, .. } = guard.deref_mut() {
-                        *path = host_adjusted_target_path;
-                    }
-                }
-            }
-            Kind::Buffer { .. } => {}
-            Kind::Symlink { .. } => {}
-            Kind::Socket { .. } => {}
-            Kind::Pipe { .. } => {}
-            Kind::Epoll { .. } => {}
-            Kind::EventNotifications { .. } => {}
+    // The entry is renamed in the file system first, the inodes are only
+    // updated once it succeeded so that there's nothing to revert.
+    let source_host_path = {
+        let guard = source_entry.read();
+        match guard.deref() {
+            Kind::File { path, .. } => Some((path.clone(), false)),
+            Kind::Dir { path, .. } => Some((path.clone(), true)),
             Kind::Root { .. } => unreachable!("The root can not be moved"),
+            Kind::Buffer { .. }
+            | Kind::Symlink { .. }
+            | Kind::Socket { .. }
+            | Kind::Pipe { .. }
+            | Kind::Epoll { .. }
+            | Kind::EventNotifications { .. } => None,
+        }
+    };
+    if let Some((source_host_path, _)) = &source_host_path {
+        let source_host_path = source_host_path.clone();
+        let target_host_path = host_adjusted_target_path.clone();
+        wasi_try_ok!(block_on(async move {
+            state.fs_rename(source_host_path, &target_host_path).await
+        }));
+
+        let mut guard = source_entry.write();
+        if let Kind::File { path, .. } | Kind::Dir { path, .. } = guard.deref_mut() {
+            *path = host_adjusted_target_path.clone();
         }
     }
 
-    let source_size = source_entry.stat.read().unwrap().st_size;
+    // The source entry is moved to the target, replacing the entry which may
+    // already be there, while both directories are locked so that the target
+    // never appears to be missing. The locks are taken in the order of the
+    // inodes to avoid deadlocking with a concurrent rename.
+    {
+        let same_parent = source_parent_inode.ino() == target_parent_inode.ino();
+        let (mut source_guard, mut target_guard) = if same_parent {
+            (source_parent_inode.write(), None)
+        } else if source_parent_inode.ino() < target_parent_inode.ino() {
+            let source_guard = source_parent_inode.write();
+            (source_guard, Some(target_parent_inode.write()))
+        } else {
+            let target_guard = target_parent_inode.write();
+            (source_parent_inode.write(), Some(target_guard))
+        };
 
-    if need_create {
-        let mut guard = target_parent_inode.write();
-        if let Kind::Dir { entries, .. } = guard.deref_mut() {
-            let result = entries.insert(target_entry_name, source_entry);
-            assert!(
-                result.is_none(),
-                "fatal error: race condition on filesystem detected or internal logic error"
-            );
+        if let Kind::Dir { entries, .. } = source_guard.deref_mut() {
+            // Unless a concurrent rename already moved it
+            if entries
+                .get(&source_entry_name)
+                .is_some_and(|entry| entry.ino() == source_entry.ino())
+            {
+                entries.remove(&source_entry_name);
+            }
+        }
+        let target_guard = target_guard.as_mut().unwrap_or(&mut source_guard);
+        if let Kind::Dir { entries, .. } = target_guard.deref_mut() {
+            entries.insert(target_entry_name, source_entry);
         }
     }
 
-    // The target entry is created, one way or the other
-    let target_inode =
-        wasi_try_ok!(state
-            .fs
-            .get_inode_at_path(inodes, target_fd, target_path, true));
-    target_inode.stat.write().unwrap().st_size = source_size;
+    if let Some((from, is_dir)) = source_host_path {
+        env.control_plane
+            .file_watchers()
+            .notify_rename(&from, &host_adjusted_target_path, is_dir);
+    }
 
     Ok(Errno::Success)
 }
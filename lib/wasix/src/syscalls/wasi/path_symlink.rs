@@ -1,5 +1,6 @@
 use super::*;
 use crate::syscalls::*;
+use wasmer_wasix_types::wasi::WatchMask;
 
 /// ### `path_symlink()`
 /// Create a symlink
@@ -25,11 +26,12 @@ pub fn path_symlink<M: MemorySize>(
 ) -> Result<Errno, WasiError> {
     let env = ctx.data();
     let (memory, _state, _inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
-    let mut old_path_str = get_input_str_ok!(&memory, old_path, old_path_len);
+    // The target is stored as is, it's relative to the symlink rather than
+    // to the current directory
+    let old_path_str = get_input_str_ok!(&memory, old_path, old_path_len);
     Span::current().record("old_path", old_path_str.as_str());
     let mut new_path_str = get_input_str_ok!(&memory, new_path, new_path_len);
     Span::current().record("new_path", new_path_str.as_str());
-    old_path_str = ctx.data().state.fs.relative_path_to_absolute(old_path_str);
     new_path_str = ctx.data().state.fs.relative_path_to_absolute(new_path_str);
 
     wasi_try_ok!(path_symlink_internal(
@@ -56,19 +58,6 @@ pub fn path_symlink_internal(
         return Err(Errno::Access);
     }
 
-    // get the depth of the parent + 1 (UNDER INVESTIGATION HMMMMMMMM THINK FISH ^ THINK FISH)
-    let old_path_path = std::path::Path::new(old_path);
-    let (source_inode, _) = state
-        .fs
-        .get_parent_inode_at_path(inodes, fd, old_path_path, true)?;
-    let depth = state.fs.path_depth_from_fd(fd, source_inode);
-
-    // depth == -1 means folder is not relative. See issue #3233.
-    let depth = match depth {
-        Ok(depth) => depth as i32 - 1,
-        Err(_) => -1,
-    };
-
     let new_path_path = std::path::Path::new(new_path);
     let (target_parent_inode, entry_name) =
         state
@@ -76,13 +65,14 @@ pub fn path_symlink_internal(
             .get_parent_inode_at_path(inodes, fd, new_path_path, true)?;
 
     // short circuit if anything is wrong, before we create an inode
-    {
+    let symlink_host_path = {
         let guard = target_parent_inode.read();
         match guard.deref() {
-            Kind::Dir { entries, .. } => {
+            Kind::Dir { entries, path, .. } => {
                 if entries.contains_key(&entry_name) {
                     return Err(Errno::Exist);
                 }
+                path.join(&entry_name)
             }
             Kind::Root { .. } => return Err(Errno::Notcapable),
             Kind::Socket { .. }
@@ -93,34 +83,43 @@ pub fn path_symlink_internal(
                 unreachable!("get_parent_inode_at_path returned something other than a Dir or Root")
             }
         }
-    }
-
-    let source_path = std::path::Path::new(old_path);
-    let mut relative_path = std::path::PathBuf::new();
-    for _ in 0..depth {
-        relative_path.push("..");
-    }
-    relative_path.push(source_path);
+    };
 
+    // The symlink is relative to the preopened directory containing it,
+    // which outlives `fd`, and its target is kept as is, so relative to the
+    // directory of the symlink, like on POSIX systems.
+    let (base_po_dir, path_to_symlink) = state
+        .fs
+        .path_into_pre_open_and_relative_path(&symlink_host_path)?;
     let kind = Kind::Symlink {
-        base_po_dir: fd,
-        path_to_symlink: std::path::PathBuf::from(new_path),
-        relative_path,
+        base_po_dir,
+        path_to_symlink: path_to_symlink.to_owned(),
+        relative_path: std::path::PathBuf::from(old_path),
     };
     let new_inode =
         state
             .fs
             .create_inode_with_default_stat(inodes, kind, false, entry_name.clone().into());
 
+    let watchers = env.control_plane.file_watchers();
+    let mut created = None;
     {
         let mut guard = target_parent_inode.write();
         if let Kind::Dir {
-            ref mut entries, ..
+            ref mut entries,
+            path,
+            ..
         } = guard.deref_mut()
         {
+            if watchers.is_watching() {
+                created = Some(path.join(&entry_name));
+            }
             entries.insert(entry_name, new_inode);
         }
     }
+    if let Some(created) = created {
+        watchers.notify(&created, WatchMask::CREATE);
+    }
 
     Ok(())
 }
@@ -1,8 +1,9 @@
 use super::*;
 use crate::syscalls::*;
+use wasmer_wasix_types::wasi::WatchMask;
 
 /// ### `path_unlink_file()`
-/// Unlink a file, deleting if the number of hardlinks is 1
+/// Unlink a file, deleting it along with its last name
 /// Inputs:
 /// - `Fd fd`
 ///     The base file descriptor from which the path is understood
@@ -53,13 +54,20 @@ pub(crate) fn path_unlink_file_internal(
         false
     ));
 
+    let watchers = env.control_plane.file_watchers();
+    let mut deleted = None;
     let removed_inode = {
         let mut guard = parent_inode.write();
         match guard.deref_mut() {
             Kind::Dir {
-                ref mut entries, ..
+                ref mut entries,
+                path,
+                ..
             } => {
                 let removed_inode = wasi_try_ok!(entries.remove(&childs_name).ok_or(Errno::Inval));
+                if watchers.is_watching() {
+                    deleted = Some(path.join(&childs_name));
+                }
                 // TODO: make this a debug assert in the future
                 assert!(inode.ino() == removed_inode.ino());
                 debug_assert!(inode.stat.read().unwrap().st_nlink > 0);
@@ -77,7 +85,15 @@ pub(crate) fn path_unlink_file_internal(
         guard.st_nlink -= 1;
         guard.st_nlink
     };
-    if st_nlink == 0 {
+    if st_nlink > 0 {
+        // The file has other names, only this one is removed
+        let guard = removed_inode.read();
+        if let Kind::File { path, .. } = guard.deref() {
+            let path = path.clone();
+            drop(guard);
+            wasi_try_ok!(state.fs_remove_file(path));
+        }
+    } else {
         {
             let guard = removed_inode.read();
             match guard.deref() {
@@ -102,6 +118,9 @@ pub(crate) fn path_unlink_file_internal(
             }
         }
     }
+    if let Some(deleted) = deleted {
+        watchers.notify(&deleted, WatchMask::DELETE);
+    }
 
     Ok(Errno::Success)
 }
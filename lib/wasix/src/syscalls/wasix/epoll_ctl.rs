@@ -266,7 +266,10 @@ pub(super) fn register_epoll_waker(
             inner.set_handler(handler).map_err(net_error_into_io_err)?;
             drop(inner);
 
-            ret.push(EpollJoinGuard::Handler { fd_guard })
+            ret.push(EpollJoinGuard::Handler {
+                fd_guard,
+                reported: false,
+            })
         }
         _ => {
             // Otherwise we fall back on the regular polling guard
@@ -284,6 +287,7 @@ pub(super) fn register_epoll_waker(
             ret.push(EpollJoinGuard::Join {
                 join_guard,
                 epoll_waker,
+                reported: false,
             });
         }
     }
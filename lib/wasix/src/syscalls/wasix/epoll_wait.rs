@@ -1,10 +1,9 @@
+use std::collections::BTreeMap;
+
 use wasmer_wasix_types::wasi::{EpollData, EpollEvent, EpollType};
 
 use super::*;
-use crate::{
-    fs::{EpollFd, POLL_GUARD_MAX_RET},
-    syscalls::*,
-};
+use crate::{fs::EpollFd, syscalls::*};
 
 const TIMEOUT_FOREVER: u64 = u64::MAX;
 
@@ -20,6 +19,9 @@ pub fn epoll_wait<'a, M: MemorySize + 'static>(
     ret_nevents: WasmPtr<M::Offset, M>,
 ) -> Result<Errno, WasiError> {
     wasi_try_ok!(WasiEnv::process_signals_and_exit(&mut ctx)?);
+    if maxevents <= 0 {
+        return Ok(Errno::Inval);
+    }
 
     if timeout == TIMEOUT_FOREVER {
         tracing::trace!(maxevents, epfd, "waiting forever on wakers");
@@ -53,50 +55,76 @@ pub fn epoll_wait<'a, M: MemorySize + 'static>(
                 // We wait for our turn then read the next event from the
                 let mut rx = rx.lock().await;
 
+                // The file descriptors reported before are re-armed, so
+                // that the ones which are still ready are reported again
+                // unless they are edge-triggered
+                {
+                    let mut guard = subscriptions.lock().unwrap();
+                    for (fd, joins) in guard.values_mut() {
+                        if !fd.is_armed() {
+                            continue;
+                        }
+                        for join in joins {
+                            join.rearm(fd, &tx);
+                        }
+                    }
+                }
+
                 // We first extract all the interest that has been registered
-                // and cycle through it
-                let mut removed = Vec::new();
+                // and merge it by file descriptor, as they are reported once
                 let interest: Vec<_> = rx
                     .borrow_and_update()
                     .interest
                     .clone()
                     .into_iter()
                     .collect();
+                let mut readiness = BTreeMap::<WasiFd, EpollType>::new();
+                for (fd, ready) in interest.iter() {
+                    *readiness.entry(*fd).or_insert_with(EpollType::empty) |= *ready;
+                }
+
+                let mut seen = Vec::new();
                 {
                     let mut guard = subscriptions.lock().unwrap();
-                    for (fd, readiness) in interest {
-                        removed.push((fd, readiness));
+                    for (fd, ready) in readiness {
+                        if ret.len() >= maxevents as usize {
+                            break;
+                        }
+                        seen.push(fd);
 
                         // Get the data for this fd
                         let (fd, joins) = match guard.get_mut(&fd) {
                             Some(a) => a,
                             None => {
-                                tracing::debug!(fd, readiness=?readiness, "orphaned interest");
+                                tracing::debug!(fd, readiness=?ready, "orphaned interest");
                                 continue;
                             }
                         };
-
-                        // We have to renew any joins that have now been spent
-                        for join in joins {
-                            if join.is_spent() {
-                                join.renew();
-                            }
+                        let events = fd.reported(ready);
+                        if events.is_empty() {
+                            continue;
                         }
 
-                        // Record the event
-                        ret.push((fd.clone(), readiness));
-                        if ret.len() + POLL_GUARD_MAX_RET >= (maxevents as usize) {
-                            break;
+                        // Record the event, which disarms a one-shot file
+                        // descriptor until it's modified
+                        ret.push((fd.clone(), events));
+                        if fd.events.contains(EpollType::EPOLLONESHOT) {
+                            fd.disarm();
+                        }
+                        for join in joins {
+                            join.mark_reported();
                         }
                     }
                 }
 
                 // Remove anything that was signaled
-                if !removed.is_empty() {
+                if !seen.is_empty() {
                     // Now update the notification system
                     tx.send_modify(|i| {
-                        for (fd, readiness) in removed {
-                            i.interest.remove(&(fd, readiness));
+                        for interest in interest {
+                            if seen.binary_search(&interest.0).is_ok() {
+                                i.interest.remove(&interest);
+                            }
                         }
                     });
                 }
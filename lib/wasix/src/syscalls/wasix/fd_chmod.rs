@@ -0,0 +1,54 @@
+use super::*;
+use crate::{fs::InodeGuard, syscalls::*};
+use virtual_fs::Permissions;
+use wasmer_wasix_types::wasi::WatchMask;
+
+/// ### `fd_chmod()`
+/// Changes the permission bits of an open file or directory, like `fchmod`
+///
+/// ## Parameters
+///
+/// * `fd` - The file descriptor of the file or directory
+/// * `mode` - The permission bits, along with the setuid, setgid and sticky
+///   bits
+#[instrument(level = "trace", skip_all, fields(%fd, mode = format_args!("{mode:o}")), ret)]
+pub fn fd_chmod(ctx: FunctionEnvMut<'_, WasiEnv>, fd: WasiFd, mode: u32) -> Errno {
+    let env = ctx.data();
+
+    let fd_entry = wasi_try!(env.state.fs.get_fd(fd));
+    if !fd_entry.rights.contains(Rights::FD_FILESTAT_SET_TIMES) {
+        return Errno::Access;
+    }
+    wasi_try!(set_permissions_internal(
+        env,
+        &fd_entry.inode,
+        |permissions| {
+            permissions.mode = mode & 0o7777;
+        }
+    ));
+
+    Errno::Success
+}
+
+/// Changes the mode or owner of a file or directory, and notifies its
+/// watchers
+pub(crate) fn set_permissions_internal(
+    env: &WasiEnv,
+    inode: &InodeGuard,
+    update: impl FnOnce(&mut Permissions),
+) -> Result<(), Errno> {
+    let path = {
+        let guard = inode.read();
+        env.state
+            .fs
+            .set_permissions_for_kind(guard.deref(), update)?;
+        match guard.deref() {
+            Kind::File { path, .. } | Kind::Dir { path, .. } => path.clone(),
+            _ => return Ok(()),
+        }
+    };
+    env.control_plane
+        .file_watchers()
+        .notify(&path, WatchMask::ATTRIB);
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `fd_chown()`
+/// Changes the owner of an open file or directory, like `fchown`
+///
+/// ## Parameters
+///
+/// * `fd` - The file descriptor of the file or directory
+/// * `uid` - The user owning the file, unchanged when it's `u32::MAX`
+/// * `gid` - The group owning the file, unchanged when it's `u32::MAX`
+#[instrument(level = "trace", skip_all, fields(%fd, %uid, %gid), ret)]
+pub fn fd_chown(ctx: FunctionEnvMut<'_, WasiEnv>, fd: WasiFd, uid: u32, gid: u32) -> Errno {
+    let env = ctx.data();
+
+    let fd_entry = wasi_try!(env.state.fs.get_fd(fd));
+    if !fd_entry.rights.contains(Rights::FD_FILESTAT_SET_TIMES) {
+        return Errno::Access;
+    }
+    wasi_try!(set_permissions_internal(
+        env,
+        &fd_entry.inode,
+        |permissions| { chown_permissions(permissions, uid, gid) }
+    ));
+
+    Errno::Success
+}
+
+/// Changes the owner in `permissions`, leaving the user or group unchanged
+/// when it's `u32::MAX` (`-1`)
+pub(crate) fn chown_permissions(permissions: &mut virtual_fs::Permissions, uid: u32, gid: u32) {
+    if uid != u32::MAX {
+        permissions.uid = uid;
+    }
+    if gid != u32::MAX {
+        permissions.gid = gid;
+    }
+}
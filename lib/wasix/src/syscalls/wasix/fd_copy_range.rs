@@ -0,0 +1,213 @@
+use std::{
+    io::SeekFrom,
+    path::PathBuf,
+    pin::Pin,
+    sync::RwLock,
+    task::{ready, Context, Poll},
+};
+
+use futures::future::poll_fn;
+use virtual_fs::{ReadBuf, VirtualFile};
+use wasmer_wasix_types::wasi::WatchMask;
+
+use super::*;
+use crate::{fs::InodeGuard, syscalls::*};
+
+type FileHandle = Arc<RwLock<Box<dyn VirtualFile + Send + Sync + 'static>>>;
+
+/// ### `fd_copy_range()`
+/// Copies a range of a file into another file without going through the
+/// memory of the process, like `copy_file_range`
+///
+/// ## Parameters
+///
+/// * `fd_in` - Descriptor of the file to copy from
+/// * `off_in` - Offset to copy from, which is advanced by the copy. When it
+///   is null the offset of `fd_in` is used and advanced instead.
+/// * `fd_out` - Descriptor of the file to copy to
+/// * `off_out` - Offset to copy to, like `off_in`
+/// * `len` - Number of bytes to copy
+///
+/// ## Return
+///
+/// Number of bytes copied, which is less than `len` when the end of the
+/// file to copy from was reached
+#[instrument(level = "trace", skip_all, fields(%fd_in, %fd_out, %len, copied = field::Empty), ret)]
+pub fn fd_copy_range<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd_in: WasiFd,
+    off_in: WasmPtr<Filesize, M>,
+    fd_out: WasiFd,
+    off_out: WasmPtr<Filesize, M>,
+    len: Filesize,
+    ret_copied: WasmPtr<Filesize, M>,
+) -> Result<Errno, WasiError> {
+    wasi_try_ok!(WasiEnv::process_signals_and_exit(&mut ctx)?);
+
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    let in_offset = match off_in.is_null() {
+        true => None,
+        false => Some(wasi_try_mem_ok!(off_in.read(&memory))),
+    };
+    let out_offset = match off_out.is_null() {
+        true => None,
+        false => Some(wasi_try_mem_ok!(off_out.read(&memory))),
+    };
+
+    let copied = wasi_try_ok!(fd_copy_range_internal(
+        &mut ctx, fd_in, in_offset, fd_out, out_offset, len
+    )?);
+    Span::current().record("copied", copied);
+
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    if let Some(offset) = in_offset {
+        wasi_try_mem_ok!(off_in.write(&memory, offset + copied));
+    }
+    if let Some(offset) = out_offset {
+        wasi_try_mem_ok!(off_out.write(&memory, offset + copied));
+    }
+    wasi_try_mem_ok!(ret_copied.write(&memory, copied));
+
+    Ok(Errno::Success)
+}
+
+/// Copies a range of a file into another file, the offsets of the
+/// descriptors are used and advanced when no offset is given
+pub(crate) fn fd_copy_range_internal(
+    ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+    fd_in: WasiFd,
+    in_offset: Option<Filesize>,
+    fd_out: WasiFd,
+    out_offset: Option<Filesize>,
+    len: Filesize,
+) -> Result<Result<Filesize, Errno>, WasiError> {
+    let env = ctx.data();
+    let in_entry = wasi_try_ok_ok!(env.state.fs.get_fd(fd_in));
+    let out_entry = wasi_try_ok_ok!(env.state.fs.get_fd(fd_out));
+    if !in_entry.rights.contains(Rights::FD_READ)
+        || !out_entry.rights.contains(Rights::FD_WRITE)
+        || out_entry.flags.contains(Fdflags::APPEND)
+    {
+        return Ok(Err(Errno::Badf));
+    }
+    let (src, _) = wasi_try_ok_ok!(file_handle(&in_entry.inode));
+    let (dst, dst_path) = wasi_try_ok_ok!(file_handle(&out_entry.inode));
+
+    let src_offset = in_offset.unwrap_or_else(|| in_entry.offset.load(Ordering::Acquire));
+    let offset = out_offset.unwrap_or_else(|| out_entry.offset.load(Ordering::Acquire));
+    wasi_try_ok_ok!(src_offset.checked_add(len).ok_or(Errno::Overflow));
+    wasi_try_ok_ok!(offset.checked_add(len).ok_or(Errno::Overflow));
+
+    // The ranges of a file copied onto itself must not overlap
+    if Arc::ptr_eq(&src, &dst) && src_offset < offset + len && offset < src_offset + len {
+        return Ok(Err(Errno::Inval));
+    }
+
+    let copied = wasi_try_ok_ok!(block_on_with_signals(ctx, None, async move {
+        copy_range(&src, src_offset, &dst, offset, len).await
+    })?);
+
+    if in_offset.is_none() {
+        in_entry.offset.fetch_add(copied, Ordering::AcqRel);
+    }
+    if out_offset.is_none() {
+        out_entry.offset.fetch_add(copied, Ordering::AcqRel);
+    }
+    if copied > 0 {
+        let mut stat = out_entry.inode.stat.write().unwrap();
+        stat.st_size = stat.st_size.max(offset + copied);
+        drop(stat);
+        ctx.data()
+            .control_plane
+            .file_watchers()
+            .notify(&dst_path, WatchMask::MODIFY);
+    }
+
+    Ok(Ok(copied))
+}
+
+/// The open file of a descriptor and its path, only files can be copied
+fn file_handle(inode: &InodeGuard) -> Result<(FileHandle, PathBuf), Errno> {
+    match inode.read().deref() {
+        Kind::File {
+            handle: Some(handle),
+            path,
+            ..
+        } => Ok((handle.clone(), path.clone())),
+        Kind::Dir { .. } | Kind::Root { .. } => Err(Errno::Isdir),
+        _ => Err(Errno::Inval),
+    }
+}
+
+/// Copies a range of a file into another file through a bounded buffer
+///
+/// Each file is only locked while it is polled, so that no lock is held
+/// while the copy waits and a file can be copied onto itself
+async fn copy_range(
+    src: &FileHandle,
+    src_offset: u64,
+    dst: &FileHandle,
+    offset: u64,
+    len: u64,
+) -> Result<u64, Errno> {
+    let mut buf = vec![0u8; len.min(64 * 1024) as usize];
+    let mut copied = 0u64;
+    while copied < len {
+        let chunk = (len - copied).min(buf.len() as u64) as usize;
+        let mut read_buf = ReadBuf::new(&mut buf[..chunk]);
+        poll_at(src, src_offset + copied, |file, cx| {
+            file.poll_read(cx, &mut read_buf)
+        })
+        .await?;
+        let read = read_buf.filled().len();
+        if read == 0 {
+            break;
+        }
+        if copied == 0 {
+            // Files can't be sought past their end, so they are grown up to
+            // the offset first
+            let mut file = dst.write().map_err(|_| Errno::Fault)?;
+            if file.size() < offset {
+                file.set_len(offset).map_err(fs_error_into_wasi_err)?;
+            }
+        }
+        let mut written = 0;
+        while written < read {
+            let data = &buf[written..read];
+            let at = offset + copied + written as u64;
+            match poll_at(dst, at, |file, cx| file.poll_write(cx, data)).await? {
+                0 => return Err(Errno::Io),
+                n => written += n,
+            }
+        }
+        copied += read as u64;
+    }
+    Ok(copied)
+}
+
+/// Polls an operation on a file at an offset until it completes, the file
+/// is locked and sought to the offset again every time it is polled since
+/// others may use it meanwhile
+async fn poll_at<T>(
+    file: &FileHandle,
+    offset: u64,
+    mut op: impl FnMut(
+        Pin<&mut (dyn VirtualFile + Send + Sync)>,
+        &mut Context<'_>,
+    ) -> Poll<std::io::Result<T>>,
+) -> Result<T, Errno> {
+    poll_fn(|cx| {
+        let mut file = file.write().map_err(|_| Errno::Fault)?;
+        let mut file = Pin::new(file.as_mut());
+        // Operations left pending by another user complete first
+        ready!(file.as_mut().poll_complete(cx)).map_err(map_io_err)?;
+        file.as_mut()
+            .start_seek(SeekFrom::Start(offset))
+            .map_err(map_io_err)?;
+        ready!(file.as_mut().poll_complete(cx)).map_err(map_io_err)?;
+        op(file, cx).map_err(map_io_err)
+    })
+    .await
+}
@@ -0,0 +1,102 @@
+use wasmer_wasix_types::wasi::{FallocateFlags, WatchMask};
+
+use super::*;
+use crate::syscalls::*;
+
+/// ### `fd_fallocate()`
+/// Manipulates the space allocated to a file, like `fallocate`
+///
+/// ## Parameters
+///
+/// * `fd` - The file descriptor of the file
+/// * `flags` - The mode, see [`FallocateFlags`]. Without flags the range is
+///   allocated and the file grows to cover it, with `KEEP_SIZE` the size of
+///   the file doesn't change and with `KEEP_SIZE | PUNCH_HOLE` the range is
+///   deallocated and reads as zeros.
+/// * `offset` - The start of the range
+/// * `len` - The length of the range
+#[instrument(level = "trace", skip_all, fields(%fd, %flags, %offset, %len), ret)]
+pub fn fd_fallocate(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    flags: u32,
+    offset: Filesize,
+    len: Filesize,
+) -> Result<Errno, WasiError> {
+    let flags = wasi_try_ok!(FallocateFlags::from_bits(flags).ok_or(Errno::Inval));
+    if len == 0 {
+        return Ok(Errno::Inval);
+    }
+    if flags.contains(FallocateFlags::PUNCH_HOLE) && !flags.contains(FallocateFlags::KEEP_SIZE) {
+        return Ok(Errno::Notsup);
+    }
+
+    if !flags.contains(FallocateFlags::KEEP_SIZE) {
+        wasi_try_ok!(fd_allocate_internal(&mut ctx, fd, offset, len));
+        return Ok(Errno::Success);
+    }
+
+    wasi_try_ok!(fd_fallocate_keep_size(
+        &mut ctx,
+        fd,
+        offset,
+        len,
+        flags.contains(FallocateFlags::PUNCH_HOLE)
+    ));
+    Ok(Errno::Success)
+}
+
+/// Allocates or deallocates a range of a file without changing its size
+fn fd_fallocate_keep_size(
+    ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    offset: Filesize,
+    len: Filesize,
+    punch_hole: bool,
+) -> Result<(), Errno> {
+    let env = ctx.data();
+    let (_, state) = unsafe { env.get_memory_and_wasi_state(&ctx, 0) };
+    let fd_entry = state.fs.get_fd(fd)?;
+    let inode = fd_entry.inode;
+
+    let rights = match punch_hole {
+        true => Rights::FD_WRITE,
+        false => Rights::FD_ALLOCATE,
+    };
+    if !fd_entry.rights.contains(rights) {
+        return Err(Errno::Access);
+    }
+    offset.checked_add(len).ok_or(Errno::Fbig)?;
+
+    let mut guard = inode.write();
+    match guard.deref_mut() {
+        Kind::File { handle, path, .. } => {
+            let handle = handle.as_ref().ok_or(Errno::Badf)?;
+            // The in-memory file systems have no space to reserve, the
+            // allocated range of the file already reads as zeros
+            if punch_hole {
+                let mut handle = handle.write().unwrap();
+                handle
+                    .punch_hole(offset, len)
+                    .map_err(fs_error_into_wasi_err)?;
+                env.control_plane
+                    .file_watchers()
+                    .notify(path, WatchMask::MODIFY);
+            }
+        }
+        Kind::Buffer { buffer } => {
+            if punch_hole {
+                let end = (offset + len).min(buffer.len() as u64) as usize;
+                if let Some(range) = buffer.get_mut(offset as usize..end) {
+                    range.fill(0);
+                }
+            }
+        }
+        Kind::Socket { .. } | Kind::Pipe { .. } => return Err(Errno::Spipe),
+        Kind::Symlink { .. } => return Err(Errno::Badf),
+        Kind::EventNotifications { .. } | Kind::Epoll { .. } => return Err(Errno::Badf),
+        Kind::Dir { .. } | Kind::Root { .. } => return Err(Errno::Isdir),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use super::*;
+use crate::{
+    fs::{lock_key, LockKind},
+    syscalls::*,
+};
+use wasmer_wasix_types::wasi::LockType;
+
+/// ### `fd_flock()`
+/// Sets or releases an advisory lock on a whole file, like `flock`. The
+/// lock belongs to the open file description, so it is shared with the
+/// duplicates of the descriptor and released along with the last of them.
+///
+/// ## Parameters
+///
+/// * `fd` - Descriptor of the file
+/// * `lock_type` - `Read` for a shared lock, `Write` for an exclusive lock,
+///   or `Unlock` to release it
+/// * `wait` - Whether to wait for the conflicting locks to be released,
+///   otherwise EAGAIN is returned
+#[instrument(level = "trace", skip_all, fields(%fd, ?lock_type, wait = wait == Bool::True), ret)]
+pub fn fd_flock(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    lock_type: LockType,
+    wait: Bool,
+) -> Result<Errno, WasiError> {
+    wasi_try_ok!(WasiEnv::process_signals_and_exit(&mut ctx)?);
+
+    let env = ctx.data();
+    let fd_entry = wasi_try_ok!(env.state.fs.get_fd(fd));
+    let key = wasi_try_ok!(lock_key(&env.state.fs, &fd_entry.inode).ok_or(Errno::Inval));
+    let owner = fd_entry.offset.clone();
+    let kind = match lock_type {
+        LockType::Read => LockKind::Shared,
+        LockType::Write => LockKind::Exclusive,
+        LockType::Unlock => {
+            env.control_plane.file_locks().unlock_file(&key, &owner);
+            return Ok(Errno::Success);
+        }
+        LockType::Unknown => return Ok(Errno::Inval),
+    };
+
+    if wait == Bool::False {
+        wasi_try_ok!(env
+            .control_plane
+            .file_locks()
+            .try_lock_file(&key, &owner, kind));
+        return Ok(Errno::Success);
+    }
+    let plane = env.control_plane.clone();
+    wasi_try_ok!(block_on_with_signals(&mut ctx, None, async move {
+        plane.file_locks().lock_file(&key, &owner, kind).await
+    })?);
+
+    Ok(Errno::Success)
+}
@@ -0,0 +1,77 @@
+use super::*;
+use crate::{
+    fs::{lock_key, LockKind, RangeLock},
+    syscalls::*,
+};
+use wasmer_wasix_types::wasi::LockType;
+
+/// ### `fd_lock()`
+/// Sets or releases an advisory byte-range lock on a file, like `fcntl`
+/// with `F_SETLK` and `F_SETLKW`. The locks belong to the process, they are
+/// released when it closes any descriptor of the file or exits.
+///
+/// ## Parameters
+///
+/// * `fd` - Descriptor of the file, it must be readable to set a read lock
+///   and writable to set a write lock
+/// * `lock_type` - Type of the lock, or `Unlock` to release the range
+/// * `start` - Offset of the first byte of the range
+/// * `len` - Length of the range, zero to extend it to the end of the file
+/// * `wait` - Whether to wait for the conflicting locks to be released,
+///   otherwise EAGAIN is returned
+///
+/// Returns EDEADLK when waiting would block forever
+#[instrument(level = "trace", skip_all, fields(%fd, ?lock_type, %start, %len, wait = wait == Bool::True), ret)]
+pub fn fd_lock(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    lock_type: LockType,
+    start: Filesize,
+    len: Filesize,
+    wait: Bool,
+) -> Result<Errno, WasiError> {
+    wasi_try_ok!(WasiEnv::process_signals_and_exit(&mut ctx)?);
+
+    let env = ctx.data();
+    let fd_entry = wasi_try_ok!(env.state.fs.get_fd(fd));
+    let key = wasi_try_ok!(lock_key(&env.state.fs, &fd_entry.inode).ok_or(Errno::Inval));
+    let end = wasi_try_ok!(lock_range_end(start, len));
+    let kind = match lock_type {
+        LockType::Read if fd_entry.rights.contains(Rights::FD_READ) => LockKind::Shared,
+        LockType::Write if fd_entry.rights.contains(Rights::FD_WRITE) => LockKind::Exclusive,
+        LockType::Read | LockType::Write => return Ok(Errno::Badf),
+        LockType::Unlock => {
+            env.control_plane
+                .file_locks()
+                .unlock_range(&key, env.pid(), start, end);
+            return Ok(Errno::Success);
+        }
+        LockType::Unknown => return Ok(Errno::Inval),
+    };
+    let lock = RangeLock {
+        pid: env.pid(),
+        kind,
+        start,
+        end,
+    };
+
+    if wait == Bool::False {
+        wasi_try_ok!(env.control_plane.file_locks().try_lock_range(&key, lock));
+        return Ok(Errno::Success);
+    }
+    let plane = env.control_plane.clone();
+    wasi_try_ok!(block_on_with_signals(&mut ctx, None, async move {
+        plane.file_locks().lock_range(&key, lock).await
+    })?);
+
+    Ok(Errno::Success)
+}
+
+/// End of a locked range, exclusive, `u64::MAX` for the ranges which extend
+/// to the end of the file
+pub(super) fn lock_range_end(start: Filesize, len: Filesize) -> Result<u64, Errno> {
+    match len {
+        0 => Ok(u64::MAX),
+        len => start.checked_add(len).ok_or(Errno::Overflow),
+    }
+}
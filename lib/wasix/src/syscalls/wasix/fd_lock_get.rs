@@ -0,0 +1,66 @@
+use super::*;
+use crate::{
+    fs::{lock_key, LockKind, RangeLock},
+    syscalls::*,
+};
+use wasmer_wasix_types::wasi::{FileLock, LockType};
+
+/// ### `fd_lock_get()`
+/// Tests whether a byte-range lock could be set on a file, like `fcntl`
+/// with `F_GETLK`
+///
+/// ## Parameters
+///
+/// * `fd` - Descriptor of the file
+/// * `lock` - The lock to test, it is overwritten with a lock of another
+///   process which prevents it from being set, or its type is set to
+///   `Unlock` when there is none
+#[instrument(level = "trace", skip_all, fields(%fd), ret)]
+pub fn fd_lock_get<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    lock: WasmPtr<FileLock, M>,
+) -> Errno {
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    let fd_entry = wasi_try!(env.state.fs.get_fd(fd));
+    let key = wasi_try!(lock_key(&env.state.fs, &fd_entry.inode).ok_or(Errno::Inval));
+
+    let mut file_lock = wasi_try_mem!(lock.read(&memory));
+    let kind = match file_lock.lock_type {
+        LockType::Read => LockKind::Shared,
+        LockType::Write => LockKind::Exclusive,
+        LockType::Unlock | LockType::Unknown => return Errno::Inval,
+    };
+    let request = RangeLock {
+        pid: env.pid(),
+        kind,
+        start: file_lock.start,
+        end: wasi_try!(lock_range_end(file_lock.start, file_lock.len)),
+    };
+
+    match env
+        .control_plane
+        .file_locks()
+        .range_conflict(&key, &request)
+    {
+        Some(conflict) => {
+            file_lock = FileLock {
+                start: conflict.start,
+                len: match conflict.end {
+                    u64::MAX => 0,
+                    end => end - conflict.start,
+                },
+                pid: conflict.pid.raw(),
+                lock_type: match conflict.kind {
+                    LockKind::Shared => LockType::Read,
+                    LockKind::Exclusive => LockType::Write,
+                },
+            };
+        }
+        None => file_lock.lock_type = LockType::Unlock,
+    }
+    wasi_try_mem!(lock.write(&memory, file_lock));
+
+    Errno::Success
+}
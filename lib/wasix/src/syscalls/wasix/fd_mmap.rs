@@ -0,0 +1,96 @@
+use wasmer_wasix_types::wasi::MmapFlags;
+
+use super::*;
+use crate::{
+    fs::{check_mapped_range, Mapping, Shadow, SharedMemoryFile},
+    syscalls::*,
+};
+
+/// ### `fd_mmap()`
+/// Maps a part of a file into the memory, like `mmap`.
+///
+/// The memory can't be backed by the file, so the process reserves the
+/// mapped range beforehand and the part of the file is copied into it. The
+/// changes to a shared mapping reach the file once they are synced with
+/// `fd_msync` or unmapped, while what is written to the file shows in its
+/// shared mappings right away.
+///
+/// ## Parameters
+///
+/// * `fd` - The file to map, which must be opened for reading
+/// * `addr` - Where the mapping starts in the memory, what was mapped there
+///   being unmapped. The mapping must end within the memory
+/// * `len` - Number of bytes mapped, those past the end of the file reading
+///   as zeros
+/// * `flags` - Either `SHARED` or `PRIVATE`
+/// * `offset` - Offset of the mapped part in the file
+#[instrument(level = "trace", skip_all, fields(%fd, %addr, %len, %flags, %offset), ret)]
+pub fn fd_mmap<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    addr: M::Offset,
+    len: M::Offset,
+    flags: u32,
+    offset: Filesize,
+) -> Result<Errno, WasiError> {
+    wasi_try_ok!(WasiEnv::process_signals_and_exit(&mut ctx)?);
+
+    let flags = wasi_try_ok!(MmapFlags::from_bits(flags).ok_or(Errno::Inval));
+    let shared = match (
+        flags.contains(MmapFlags::SHARED),
+        flags.contains(MmapFlags::PRIVATE),
+    ) {
+        (true, false) => true,
+        (false, true) => false,
+        _ => return Ok(Errno::Inval),
+    };
+    let (addr, len): (u64, u64) = (addr.into(), len.into());
+    if offset.checked_add(len).is_none() {
+        return Ok(Errno::Inval);
+    }
+
+    let env = ctx.data();
+    let (memory, state) = unsafe { env.get_memory_and_wasi_state(&ctx, 0) };
+    // The range is checked before the copies of the mapping are allocated
+    wasi_try_ok!(check_mapped_range(addr, len, memory.data_size()));
+    let fd_entry = wasi_try_ok!(state.fs.get_fd(fd));
+    if !fd_entry.rights.contains(Rights::FD_READ) {
+        return Ok(Errno::Access);
+    }
+    let (file, path) = match fd_entry.inode.read().deref() {
+        Kind::File {
+            handle: Some(handle),
+            path,
+            ..
+        } => (handle.clone(), path.clone()),
+        _ => return Ok(Errno::Access),
+    };
+    // The processes sharing a mapping of a shared memory object each have
+    // their copy, which are merged when they are synced
+    let object = (**file.read().unwrap())
+        .upcast_any_ref()
+        .downcast_ref::<SharedMemoryFile>()
+        .map(|file| file.object().clone());
+    let mapping = Mapping {
+        addr,
+        len,
+        offset,
+        file,
+        path,
+        shared,
+        writable: fd_entry.rights.contains(Rights::FD_WRITE),
+        shadow: object
+            .filter(|_| shared)
+            .map(|object| Shadow::new(object, addr, len)),
+    };
+
+    // The changes to what was mapped there are written back first
+    let replaced = state.mmaps.lock().unwrap().mapped(addr, len);
+    for part in replaced {
+        wasi_try_ok!(block_on(part.store(&memory)));
+    }
+    wasi_try_ok!(block_on(mapping.load(&memory)));
+    state.mmaps.lock().unwrap().map(mapping);
+
+    Ok(Errno::Success)
+}
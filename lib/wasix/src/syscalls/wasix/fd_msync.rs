@@ -0,0 +1,57 @@
+use wasmer_wasix_types::wasi::MsyncFlags;
+
+use super::*;
+use crate::syscalls::*;
+
+/// ### `fd_msync()`
+/// Writes the changes to the shared mappings in a range of the memory back
+/// to their files, like `msync`.
+///
+/// ## Parameters
+///
+/// * `addr` - Where the range starts in the memory
+/// * `len` - Number of bytes in the range, which must all be mapped
+/// * `flags` - With `SYNC`, the files are also flushed to their storage,
+///   which can't be asked along with `ASYNC`. With `INVALIDATE`, the other
+///   shared mappings of the files are updated with the changes
+#[instrument(level = "trace", skip_all, fields(%addr, %len, %flags), ret)]
+pub fn fd_msync<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    addr: M::Offset,
+    len: M::Offset,
+    flags: u32,
+) -> Result<Errno, WasiError> {
+    wasi_try_ok!(WasiEnv::process_signals_and_exit(&mut ctx)?);
+
+    let flags = wasi_try_ok!(MsyncFlags::from_bits(flags).ok_or(Errno::Inval));
+    if flags.contains(MsyncFlags::ASYNC | MsyncFlags::SYNC) {
+        return Ok(Errno::Inval);
+    }
+    let (addr, len): (u64, u64) = (addr.into(), len.into());
+
+    let env = ctx.data();
+    let (memory, state) = unsafe { env.get_memory_and_wasi_state(&ctx, 0) };
+    let mapped = state.mmaps.lock().unwrap().mapped(addr, len);
+    if mapped.iter().map(|part| part.len).sum::<u64>() < len {
+        return Ok(Errno::Nomem);
+    }
+
+    for part in mapped {
+        wasi_try_ok!(block_on(part.store(&memory)));
+        if flags.contains(MsyncFlags::INVALIDATE) {
+            let others = state
+                .mmaps
+                .lock()
+                .unwrap()
+                .mapped_file(&part.path, part.offset, part.len);
+            for other in others.into_iter().filter(|other| other.addr != part.addr) {
+                wasi_try_ok!(block_on(other.load(&memory)));
+            }
+        }
+        if flags.contains(MsyncFlags::SYNC) {
+            wasi_try_ok!(block_on(part.flush()));
+        }
+    }
+
+    Ok(Errno::Success)
+}
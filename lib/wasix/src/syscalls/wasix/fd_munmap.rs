@@ -0,0 +1,34 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `fd_munmap()`
+/// Unmaps the files mapped into a range of the memory, like `munmap`. The
+/// changes to the shared mappings are written back to their files.
+///
+/// ## Parameters
+///
+/// * `addr` - Where the range starts in the memory
+/// * `len` - Number of bytes in the range, which may also hold parts which
+///   aren't mapped
+#[instrument(level = "trace", skip_all, fields(%addr, %len), ret)]
+pub fn fd_munmap<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    addr: M::Offset,
+    len: M::Offset,
+) -> Result<Errno, WasiError> {
+    wasi_try_ok!(WasiEnv::process_signals_and_exit(&mut ctx)?);
+
+    let (addr, len): (u64, u64) = (addr.into(), len.into());
+    if len == 0 {
+        return Ok(Errno::Inval);
+    }
+
+    let env = ctx.data();
+    let (memory, state) = unsafe { env.get_memory_and_wasi_state(&ctx, 0) };
+    let unmapped = state.mmaps.lock().unwrap().unmap(addr, len);
+    for part in unmapped {
+        wasi_try_ok!(block_on(part.store(&memory)));
+    }
+
+    Ok(Errno::Success)
+}
@@ -0,0 +1,38 @@
+use super::*;
+use crate::syscalls::*;
+use wasmer_wasix_types::wasi::Fileperms;
+
+/// ### `fd_permissions_get()`
+/// Returns the mode and owner of an open file or directory
+///
+/// ## Parameters
+///
+/// * `fd` - The file descriptor of the file or directory
+/// * `buf` - Where the permissions of the file are written
+#[instrument(level = "trace", skip_all, fields(%fd), ret)]
+pub fn fd_permissions_get<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    buf: WasmPtr<Fileperms, M>,
+) -> Errno {
+    let env = ctx.data();
+    let (memory, state) = unsafe { env.get_memory_and_wasi_state(&ctx, 0) };
+
+    let fd_entry = wasi_try!(state.fs.get_fd(fd));
+    if !fd_entry.rights.contains(Rights::FD_FILESTAT_GET) {
+        return Errno::Access;
+    }
+    let permissions = {
+        let guard = fd_entry.inode.read();
+        wasi_try!(state.fs.get_permissions_for_kind(guard.deref()))
+    };
+
+    let perms = Fileperms {
+        mode: permissions.mode,
+        uid: permissions.uid,
+        gid: permissions.gid,
+    };
+    wasi_try_mem!(buf.write(&memory, perms));
+
+    Errno::Success
+}
@@ -0,0 +1,70 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `fd_sendfile()`
+/// Sends data from a file to another descriptor without going through the
+/// memory of the process, like `sendfile`. Sockets are fed straight from
+/// the file, files are copied like with `fd_copy_range`.
+///
+/// ## Parameters
+///
+/// * `out_fd` - Descriptor of the socket or file to send to
+/// * `in_fd` - Descriptor of the file to send from
+/// * `offset` - Offset to send from, which is advanced by the number of bytes
+///   sent. When it is null the offset of `in_fd` is used and advanced instead.
+/// * `count` - Number of bytes to send
+///
+/// ## Return
+///
+/// Number of bytes sent.
+#[instrument(level = "trace", skip_all, fields(%out_fd, %in_fd, %count, nsent = field::Empty), ret)]
+pub fn fd_sendfile<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    out_fd: WasiFd,
+    in_fd: WasiFd,
+    offset: WasmPtr<Filesize, M>,
+    count: Filesize,
+    ret_sent: WasmPtr<Filesize, M>,
+) -> Result<Errno, WasiError> {
+    wasi_try_ok!(WasiEnv::process_signals_and_exit(&mut ctx)?);
+
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    let in_offset = match offset.is_null() {
+        true => None,
+        false => Some(wasi_try_mem_ok!(offset.read(&memory))),
+    };
+
+    let in_entry = wasi_try_ok!(env.state.fs.get_fd(in_fd));
+    let out_entry = wasi_try_ok!(env.state.fs.get_fd(out_fd));
+    let to_socket = matches!(out_entry.inode.read().deref(), Kind::Socket { .. });
+    let sent = if to_socket {
+        let fd_offset = in_entry.offset.load(Ordering::Acquire);
+        let sent = wasi_try_ok!(sock_send_file_internal(
+            &mut ctx,
+            out_fd,
+            in_fd,
+            in_offset.unwrap_or(fd_offset),
+            count
+        )?);
+        // The offset of the descriptor is left alone when one is given
+        if in_offset.is_some() {
+            in_entry.offset.store(fd_offset, Ordering::Release);
+        }
+        sent
+    } else {
+        wasi_try_ok!(fd_copy_range_internal(
+            &mut ctx, in_fd, in_offset, out_fd, None, count
+        )?)
+    };
+    Span::current().record("nsent", sent);
+
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    if let Some(in_offset) = in_offset {
+        wasi_try_mem_ok!(offset.write(&memory, in_offset + sent));
+    }
+    wasi_try_mem_ok!(ret_sent.write(&memory, sent));
+
+    Ok(Errno::Success)
+}
@@ -0,0 +1,34 @@
+use super::*;
+use crate::syscalls::*;
+use wasmer_wasix_types::wasi::Statfs;
+
+/// ### `fd_statfs()`
+/// Reports the capacity and usage of the file system holding an open file
+/// or directory, like `fstatfs`
+///
+/// ## Parameters
+///
+/// * `fd` - The file descriptor of the file or directory
+/// * `buf` - Where the statistics of the file system are written
+#[instrument(level = "trace", skip_all, fields(%fd), ret)]
+pub fn fd_statfs<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    buf: WasmPtr<Statfs, M>,
+) -> Errno {
+    let env = ctx.data();
+    let (memory, state) = unsafe { env.get_memory_and_wasi_state(&ctx, 0) };
+
+    let fd_entry = wasi_try!(state.fs.get_fd(fd));
+    if !fd_entry.rights.contains(Rights::FD_FILESTAT_GET) {
+        return Errno::Access;
+    }
+    let stats = {
+        let guard = fd_entry.inode.read();
+        wasi_try!(state.fs.get_statfs_for_kind(guard.deref()))
+    };
+
+    wasi_try_mem!(buf.write(&memory, stats));
+
+    Errno::Success
+}
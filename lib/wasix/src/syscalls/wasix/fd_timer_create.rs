@@ -0,0 +1,55 @@
+use std::{path::PathBuf, sync::RwLock};
+
+use super::*;
+use crate::{fs::TimerFile, syscalls::*};
+
+/// ### `fd_timer_create()`
+/// Creates a file handle for a timer, like `timerfd_create`. The timer is
+/// disarmed until `fd_timer_settime` arms it. Reading the file handle
+/// returns the number of times the timer expired since it was last read, as
+/// a `u64`, it can be polled for the timer to expire.
+///
+/// ## Parameters
+///
+/// * `clock_id` - The clock the absolute expirations are given on, either
+///   `Realtime` or `Monotonic`
+/// * `flags` - Only `NONBLOCK` is supported
+/// * `ret_fd` - The new file handle
+#[instrument(level = "trace", skip_all, fields(?clock_id, ?flags, ret_fd = field::Empty), ret)]
+pub fn fd_timer_create<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    clock_id: Snapshot0Clockid,
+    flags: Fdflags,
+    ret_fd: WasmPtr<WasiFd, M>,
+) -> Errno {
+    if !(flags - Fdflags::NONBLOCK).is_empty() {
+        return Errno::Inval;
+    }
+    if !matches!(
+        clock_id,
+        Snapshot0Clockid::Realtime | Snapshot0Clockid::Monotonic
+    ) {
+        return Errno::Inval;
+    }
+
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+
+    let file = TimerFile::new(clock_id, env.tasks().clone());
+    let kind = Kind::File {
+        handle: Some(Arc::new(RwLock::new(Box::new(file)))),
+        path: PathBuf::new(),
+        fd: None,
+    };
+    let inode =
+        state
+            .fs
+            .create_inode_with_default_stat(inodes, kind, false, "timer".to_string().into());
+    let rights = Rights::FD_READ | Rights::POLL_FD_READWRITE | Rights::FD_FDSTAT_SET_FLAGS;
+    let fd = wasi_try!(state.fs.create_fd(rights, rights, flags, 0, inode));
+
+    Span::current().record("ret_fd", fd);
+    wasi_try_mem!(ret_fd.write(&memory, fd));
+
+    Errno::Success
+}
@@ -0,0 +1,40 @@
+use super::*;
+use crate::{fs::TimerFile, syscalls::*};
+
+/// ### `fd_timer_gettime()`
+/// Reads when a timer expires next, like `timerfd_gettime`.
+///
+/// ## Parameters
+///
+/// * `fd` - File handle created by `fd_timer_create`
+/// * `ret_value` - The time left until the timer expires, in nanoseconds,
+///   zero when it's disarmed
+/// * `ret_interval` - The period of the timer, in nanoseconds
+#[instrument(level = "trace", skip_all, fields(%fd), ret)]
+pub fn fd_timer_gettime<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    ret_value: WasmPtr<Timestamp, M>,
+    ret_interval: WasmPtr<Timestamp, M>,
+) -> Errno {
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    let fd_entry = wasi_try!(env.state.fs.get_fd(fd));
+    let handle = match fd_entry.inode.read().deref() {
+        Kind::File {
+            handle: Some(handle),
+            ..
+        } => handle.clone(),
+        _ => return Errno::Inval,
+    };
+    let handle = wasi_try!(handle.read().map_err(|_| Errno::Fault));
+    let Some(file) = (**handle).upcast_any_ref().downcast_ref::<TimerFile>() else {
+        return Errno::Inval;
+    };
+
+    let (value, interval) = file.get();
+    wasi_try_mem!(ret_value.write(&memory, value.as_nanos() as Timestamp));
+    wasi_try_mem!(ret_interval.write(&memory, interval.as_nanos() as Timestamp));
+
+    Errno::Success
+}
@@ -0,0 +1,62 @@
+use wasmer_wasix_types::wasi::TimerFlags;
+
+use super::*;
+use crate::{fs::TimerFile, syscalls::*};
+
+/// ### `fd_timer_settime()`
+/// Arms or disarms a timer, like `timerfd_settime`. The expirations which
+/// were not read are dropped.
+///
+/// ## Parameters
+///
+/// * `fd` - File handle created by `fd_timer_create`
+/// * `flags` - With `ABSTIME`, `value` is a time of the clock of the timer
+///   rather than a delay, a time which passed already expires the timer
+///   right away
+/// * `value` - When the timer first expires, in nanoseconds. Zero disarms
+///   the timer
+/// * `interval` - The period of the timer after it first expired, in
+///   nanoseconds. Zero expires the timer only once
+#[instrument(level = "trace", skip_all, fields(%fd, %flags, %value, %interval), ret)]
+pub fn fd_timer_settime(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    flags: u32,
+    value: Timestamp,
+    interval: Timestamp,
+) -> Errno {
+    let flags = wasi_try!(TimerFlags::from_bits(flags).ok_or(Errno::Inval));
+
+    let env = ctx.data();
+    let fd_entry = wasi_try!(env.state.fs.get_fd(fd));
+    let handle = match fd_entry.inode.read().deref() {
+        Kind::File {
+            handle: Some(handle),
+            ..
+        } => handle.clone(),
+        _ => return Errno::Inval,
+    };
+    let handle = wasi_try!(handle.read().map_err(|_| Errno::Fault));
+    let Some(file) = (**handle).upcast_any_ref().downcast_ref::<TimerFile>() else {
+        return Errno::Inval;
+    };
+
+    let value = match value {
+        0 => None,
+        value if flags.contains(TimerFlags::ABSTIME) => {
+            let clock_id = file.clock();
+            let mut now = wasi_try!(platform_clock_time_get(clock_id, 1));
+            if let Some(offset) = env.state.clock_offset.lock().unwrap().get(&clock_id) {
+                now += *offset;
+            }
+            Some(value.saturating_sub(now as Timestamp))
+        }
+        value => Some(value),
+    };
+    file.set(
+        value.map(Duration::from_nanos),
+        Duration::from_nanos(interval),
+    );
+
+    Errno::Success
+}
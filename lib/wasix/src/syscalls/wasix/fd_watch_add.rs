@@ -0,0 +1,72 @@
+use super::*;
+use crate::{fs::WatchFile, syscalls::*};
+use wasmer_wasix_types::wasi::WatchMask;
+
+/// ### `fd_watch_add()`
+/// Watches a file or directory for changes, like `inotify_add_watch`. When
+/// the file handle already watches it, the events of the watch are replaced.
+///
+/// ## Parameters
+///
+/// * `fd` - File handle created by `fd_watch_create`
+/// * `dir_fd` - The directory that `path` is relative to
+/// * `flags` - Flags to control how `path` is understood
+/// * `path` - Path of the file or directory to watch
+/// * `mask` - The events to watch for, with the values of inotify(7)
+/// * `ret_wd` - Descriptor of the watch, which is reported with its events
+#[instrument(level = "trace", skip_all, fields(%fd, %dir_fd, path = field::Empty, %mask, ret_wd = field::Empty), ret)]
+pub fn fd_watch_add<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    dir_fd: WasiFd,
+    flags: LookupFlags,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+    mask: u32,
+    ret_wd: WasmPtr<u32, M>,
+) -> Errno {
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+
+    let mut path_string = get_input_str!(&memory, path, path_len);
+    Span::current().record("path", path_string.as_str());
+    if path_string.starts_with("./") {
+        path_string = state.fs.relative_path_to_absolute(path_string);
+    }
+
+    let mask = WatchMask::from_bits_truncate(mask)
+        - (WatchMask::Q_OVERFLOW | WatchMask::IGNORED | WatchMask::ISDIR);
+    if mask.is_empty() {
+        return Errno::Inval;
+    }
+
+    let watched = wasi_try!(state.fs.get_inode_at_path(
+        inodes,
+        dir_fd,
+        &path_string,
+        flags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0,
+    ));
+    let watched_path = match watched.read().deref() {
+        Kind::File { path, .. } | Kind::Dir { path, .. } => path.clone(),
+        _ => return Errno::Notsup,
+    };
+
+    let fd_entry = wasi_try!(state.fs.get_fd(fd));
+    let handle = match fd_entry.inode.read().deref() {
+        Kind::File {
+            handle: Some(handle),
+            ..
+        } => handle.clone(),
+        _ => return Errno::Inval,
+    };
+    let handle = wasi_try!(handle.read().map_err(|_| Errno::Fault));
+    let Some(file) = (**handle).upcast_any_ref().downcast_ref::<WatchFile>() else {
+        return Errno::Inval;
+    };
+    let wd = file.add(watched_path, mask);
+
+    Span::current().record("ret_wd", wd);
+    wasi_try_mem!(ret_wd.write(&memory, wd));
+
+    Errno::Success
+}
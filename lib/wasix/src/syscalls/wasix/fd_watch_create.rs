@@ -0,0 +1,45 @@
+use std::{path::PathBuf, sync::RwLock};
+
+use super::*;
+use crate::{fs::WatchFile, syscalls::*};
+
+/// ### `fd_watch_create()`
+/// Creates a file handle for watching files and directories, like
+/// `inotify_init1`. Reading it returns the events of its watches in the
+/// layout of `struct inotify_event`, it can be polled for the events.
+///
+/// ## Parameters
+///
+/// * `flags` - Only `NONBLOCK` is supported
+/// * `ret_fd` - The new file handle
+#[instrument(level = "trace", skip_all, fields(?flags, ret_fd = field::Empty), ret)]
+pub fn fd_watch_create<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    flags: Fdflags,
+    ret_fd: WasmPtr<WasiFd, M>,
+) -> Errno {
+    if !(flags - Fdflags::NONBLOCK).is_empty() {
+        return Errno::Inval;
+    }
+
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+
+    let file = WatchFile::new(env.control_plane.file_watchers().clone());
+    let kind = Kind::File {
+        handle: Some(Arc::new(RwLock::new(Box::new(file)))),
+        path: PathBuf::new(),
+        fd: None,
+    };
+    let inode =
+        state
+            .fs
+            .create_inode_with_default_stat(inodes, kind, false, "watch".to_string().into());
+    let rights = Rights::FD_READ | Rights::POLL_FD_READWRITE | Rights::FD_FDSTAT_SET_FLAGS;
+    let fd = wasi_try!(state.fs.create_fd(rights, rights, flags, 0, inode));
+
+    Span::current().record("ret_fd", fd);
+    wasi_try_mem!(ret_fd.write(&memory, fd));
+
+    Errno::Success
+}
@@ -0,0 +1,30 @@
+use super::*;
+use crate::{fs::WatchFile, syscalls::*};
+
+/// ### `fd_watch_remove()`
+/// Removes a watch added by `fd_watch_add`, like `inotify_rm_watch`. An
+/// `IGNORED` event is reported for the watch.
+///
+/// ## Parameters
+///
+/// * `fd` - File handle created by `fd_watch_create`
+/// * `wd` - Descriptor of the watch
+#[instrument(level = "trace", skip_all, fields(%fd, %wd), ret)]
+pub fn fd_watch_remove(ctx: FunctionEnvMut<'_, WasiEnv>, fd: WasiFd, wd: u32) -> Errno {
+    let env = ctx.data();
+    let fd_entry = wasi_try!(env.state.fs.get_fd(fd));
+    let handle = match fd_entry.inode.read().deref() {
+        Kind::File {
+            handle: Some(handle),
+            ..
+        } => handle.clone(),
+        _ => return Errno::Inval,
+    };
+    let handle = wasi_try!(handle.read().map_err(|_| Errno::Fault));
+    let Some(file) = (**handle).upcast_any_ref().downcast_ref::<WatchFile>() else {
+        return Errno::Inval;
+    };
+    wasi_try!(file.remove(wd));
+
+    Errno::Success
+}
@@ -0,0 +1,106 @@
+use super::*;
+use crate::syscalls::*;
+
+/// Wake up to `wake_count` threads blocked on this futex and move up to
+/// `requeue_count` of the others onto the `target` futex, without waking
+/// them. Condition variables use it to wake a single waiter and hand the
+/// others over to their mutex, rather than waking them all at once.
+///
+/// ## Parameters
+///
+/// * `futex` - Memory location that holds a futex that others may be waiting on
+/// * `wake_count` - Maximum number of threads to wake
+/// * `target` - Futex the remaining threads are moved to
+/// * `requeue_count` - Maximum number of threads to move
+///
+/// ## Return
+///
+/// The number of threads which were woken or moved
+#[instrument(level = "trace", skip_all, fields(futex_idx = field::Empty, target_idx = field::Empty, %wake_count, %requeue_count, count = field::Empty), ret)]
+pub fn futex_requeue<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    futex_ptr: WasmPtr<u32, M>,
+    wake_count: u32,
+    target_ptr: WasmPtr<u32, M>,
+    requeue_count: u32,
+    ret_count: WasmPtr<u32, M>,
+) -> Errno {
+    futex_requeue_internal(
+        ctx,
+        futex_ptr,
+        None,
+        wake_count,
+        target_ptr,
+        requeue_count,
+        ret_count,
+    )
+}
+
+/// As futex_requeue, but only if the futex still holds the expected value.
+/// Returns with EAGAIN if the futex doesn't hold the expected value.
+///
+/// ## Parameters
+///
+/// * `futex` - Memory location that holds a futex that others may be waiting on
+/// * `expected` - Expected value that should be currently held at the memory location
+/// * `wake_count` - Maximum number of threads to wake
+/// * `target` - Futex the remaining threads are moved to
+/// * `requeue_count` - Maximum number of threads to move
+///
+/// ## Return
+///
+/// The number of threads which were woken or moved
+#[instrument(level = "trace", skip_all, fields(futex_idx = field::Empty, target_idx = field::Empty, %expected, %wake_count, %requeue_count, count = field::Empty), ret)]
+pub fn futex_cmp_requeue<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    futex_ptr: WasmPtr<u32, M>,
+    expected: u32,
+    wake_count: u32,
+    target_ptr: WasmPtr<u32, M>,
+    requeue_count: u32,
+    ret_count: WasmPtr<u32, M>,
+) -> Errno {
+    futex_requeue_internal(
+        ctx,
+        futex_ptr,
+        Some(expected),
+        wake_count,
+        target_ptr,
+        requeue_count,
+        ret_count,
+    )
+}
+
+fn futex_requeue_internal<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    futex_ptr: WasmPtr<u32, M>,
+    expected: Option<u32>,
+    wake_count: u32,
+    target_ptr: WasmPtr<u32, M>,
+    requeue_count: u32,
+    ret_count: WasmPtr<u32, M>,
+) -> Errno {
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+
+    let pointer: u64 = futex_ptr.offset().into();
+    let target: u64 = target_ptr.offset().into();
+    Span::current().record("futex_idx", pointer);
+    Span::current().record("target_idx", target);
+
+    let count = {
+        // The value is checked while the futexes are locked, so no thread
+        // can start waiting in between
+        let mut guard = env.state.futexs.lock().unwrap();
+        if let Some(expected) = expected {
+            if wasi_try_mem!(futex_ptr.read(&memory)) != expected {
+                return Errno::Again;
+            }
+        }
+        guard.requeue(pointer, wake_count as usize, target, requeue_count as usize)
+    };
+    Span::current().record("count", count);
+
+    wasi_try_mem!(ret_count.write(&memory, count as u32));
+    Errno::Success
+}
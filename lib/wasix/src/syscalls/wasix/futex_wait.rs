@@ -5,30 +5,18 @@ use crate::syscalls::*;
 struct FutexPoller {
     state: Arc<WasiState>,
     poller_idx: u64,
-    futex_idx: u64,
-    #[allow(dead_code)]
-    expected: u32,
     timeout: Option<Pin<Box<dyn Future<Output = ()> + Send + Sync + 'static>>>,
 }
 
 impl Future for FutexPoller {
     type Output = bool;
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<bool> {
+        // If the poller is no longer registered then it was woken by a
+        // wake call
         let mut guard = self.state.futexs.lock().unwrap();
-
-        // If the futex itself is no longer registered then it was likely
-        // woken by a wake call
-        let futex = match guard.futexes.get_mut(&self.futex_idx) {
-            Some(f) => f,
-            None => return Poll::Ready(true),
-        };
-        let waker = match futex.wakers.get_mut(&self.poller_idx) {
-            Some(w) => w,
-            None => return Poll::Ready(true),
-        };
-
-        // Register the waker
-        waker.replace(cx.waker().clone());
+        if !guard.register_waker(self.poller_idx, cx.waker()) {
+            return Poll::Ready(true);
+        }
 
         // Check for timeout
         drop(guard);
@@ -48,17 +36,7 @@ impl Future for FutexPoller {
 impl Drop for FutexPoller {
     fn drop(&mut self) {
         let mut guard = self.state.futexs.lock().unwrap();
-
-        let mut should_remove = false;
-        if let Some(futex) = guard.futexes.get_mut(&self.futex_idx) {
-            if let Some(Some(waker)) = futex.wakers.remove(&self.poller_idx) {
-                waker.wake();
-            }
-            should_remove = futex.wakers.is_empty();
-        }
-        if should_remove {
-            guard.futexes.remove(&self.futex_idx);
-        }
+        guard.remove_poller(self.poller_idx);
     }
 }
 
@@ -103,6 +81,27 @@ pub(super) fn futex_wait_internal<M: MemorySize + 'static>(
     };
     Span::current().record("timeout", &format!("{:?}", timeout));
 
+    futex_wait_for(
+        &ctx,
+        futex_ptr,
+        expected,
+        FUTEX_BITSET_MATCH_ANY,
+        timeout,
+        ret_woken,
+    )
+}
+
+/// Waits on a futex until it is woken by a wake whose bitset intersects
+/// `bitset`, or until the timeout elapses
+pub(super) fn futex_wait_for<M: MemorySize + 'static>(
+    ctx: &FunctionEnvMut<'_, WasiEnv>,
+    futex_ptr: WasmPtr<u32, M>,
+    expected: u32,
+    bitset: u32,
+    timeout: Option<Duration>,
+    ret_woken: WasmPtr<Bool, M>,
+) -> Result<Errno, WasiError> {
+    let env = ctx.data();
     let futex_idx: u64 = wasi_try_ok!(futex_ptr.offset().try_into().map_err(|_| Errno::Overflow));
     Span::current().record("futex_idx", futex_idx);
 
@@ -112,30 +111,28 @@ pub(super) fn futex_wait_internal<M: MemorySize + 'static>(
     // removed whenever the wake call is invoked (which could
     // be before the poller is polled).
     let poller = {
-        let mut guard = env.state.futexs.lock().unwrap();
-        guard.poller_seed += 1;
-        let poller_idx = guard.poller_seed;
+        // We insert the futex before we check the condition variable to avoid
+        // certain race conditions
+        let poller_idx = env
+            .state
+            .futexs
+            .lock()
+            .unwrap()
+            .add_poller(futex_idx, bitset);
 
         // Create the timeout if one exists
         let timeout = timeout.map(|timeout| env.tasks().sleep_now(timeout));
 
-        // We insert the futex before we check the condition variable to avoid
-        // certain race conditions
-        let futex = guard.futexes.entry(futex_idx).or_default();
-        futex.wakers.insert(poller_idx, Default::default());
-
         Span::current().record("poller_idx", poller_idx);
         FutexPoller {
             state: env.state.clone(),
             poller_idx,
-            futex_idx,
-            expected,
             timeout,
         }
     };
 
     // We check if the expected value has changed
-    let memory = unsafe { env.memory_view(&ctx) };
+    let memory = unsafe { env.memory_view(ctx) };
     let val = wasi_try_mem_ok!(futex_ptr.read(&memory));
     if val != expected {
         // We have been triggered so do not go into a wait
@@ -149,8 +146,9 @@ pub(super) fn futex_wait_internal<M: MemorySize + 'static>(
 
     tracing::trace!("wait on {futex_idx}");
     let res = block_on(Box::pin(poller));
+    Span::current().record("woken", res);
 
-    let memory = unsafe { env.memory_view(&ctx) };
+    let memory = unsafe { env.memory_view(ctx) };
     if res {
         wasi_try_mem_ok!(ret_woken.write(&memory, Bool::True));
     } else {
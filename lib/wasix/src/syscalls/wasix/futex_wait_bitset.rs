@@ -0,0 +1,56 @@
+use super::*;
+use crate::syscalls::*;
+
+/// Wait for a futex_wake_bitset operation whose bitset intersects `bitset`
+/// to wake us, or until an absolute deadline is reached.
+/// Returns with EINVAL if the futex doesn't hold the expected value.
+/// Returns false on timeout, and true in all other cases.
+///
+/// ## Parameters
+///
+/// * `futex` - Memory location that holds the value that will be checked
+/// * `expected` - Expected value that should be currently held at the memory location
+/// * `bitset` - Only the wakes whose bitset intersects this one wake the thread, must not be zero
+/// * `clock_id` - Clock the deadline is measured on, either `Realtime` or `Monotonic`
+/// * `deadline` - Time of the clock at which the wait times out
+#[instrument(level = "trace", skip_all, fields(futex_idx = field::Empty, poller_idx = field::Empty, %expected, %bitset, ?clock_id, timeout = field::Empty, woken = field::Empty))]
+pub fn futex_wait_bitset<M: MemorySize + 'static>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    futex_ptr: WasmPtr<u32, M>,
+    expected: u32,
+    bitset: u32,
+    clock_id: Snapshot0Clockid,
+    deadline: WasmPtr<OptionTimestamp, M>,
+    ret_woken: WasmPtr<Bool, M>,
+) -> Result<Errno, WasiError> {
+    wasi_try_ok!(WasiEnv::process_signals_and_exit(&mut ctx)?);
+    if bitset == 0 {
+        return Ok(Errno::Inval);
+    }
+
+    // The deadline is turned into the time left until it is reached
+    let env = ctx.data();
+    let deadline = {
+        let memory = unsafe { env.memory_view(&ctx) };
+        wasi_try_mem_ok!(deadline.read(&memory))
+    };
+    let timeout = match deadline.tag {
+        OptionTag::Some => {
+            if !matches!(
+                clock_id,
+                Snapshot0Clockid::Realtime | Snapshot0Clockid::Monotonic
+            ) {
+                return Ok(Errno::Inval);
+            }
+            let mut now = wasi_try_ok!(platform_clock_time_get(clock_id, 1));
+            if let Some(offset) = env.state.clock_offset.lock().unwrap().get(&clock_id) {
+                now += *offset;
+            }
+            Some(Duration::from_nanos(deadline.u.saturating_sub(now as u64)))
+        }
+        _ => None,
+    };
+    Span::current().record("timeout", format!("{:?}", timeout));
+
+    futex_wait_for(&ctx, futex_ptr, expected, bitset, timeout, ret_woken)
+}
@@ -23,22 +23,12 @@ pub fn futex_wake<M: MemorySize>(
 
     let woken = {
         let mut guard = state.futexs.lock().unwrap();
-        if let Some(futex) = guard.futexes.get_mut(&pointer) {
-            let first = futex.wakers.keys().copied().next();
-            if let Some(id) = first {
-                if let Some(Some(w)) = futex.wakers.remove(&id) {
-                    w.wake();
-                }
-            }
-            if futex.wakers.is_empty() {
-                guard.futexes.remove(&pointer);
-            }
+        if guard.wake(pointer, 1, FUTEX_BITSET_MATCH_ANY) > 0 {
             tracing::trace!("wake(hit) on {pointer}");
-            true
         } else {
             tracing::trace!("wake(miss) on {pointer}");
-            true
         }
+        true
     };
     Span::current().record("woken", woken);
 
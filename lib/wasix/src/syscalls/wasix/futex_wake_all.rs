@@ -21,18 +21,12 @@ pub fn futex_wake_all<M: MemorySize>(
 
     let woken = {
         let mut guard = state.futexs.lock().unwrap();
-        if let Some(futex) = guard.futexes.remove(&pointer) {
-            for waker in futex.wakers {
-                if let Some(waker) = waker.1 {
-                    waker.wake();
-                }
-            }
+        if guard.wake(pointer, usize::MAX, FUTEX_BITSET_MATCH_ANY) > 0 {
             tracing::trace!("wake_all (hit) on {pointer}");
-            true
         } else {
             tracing::trace!("wake_all (miss) on {pointer}");
-            true
         }
+        true
     };
     Span::current().record("woken", woken);
 
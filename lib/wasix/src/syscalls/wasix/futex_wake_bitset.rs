@@ -0,0 +1,45 @@
+use super::*;
+use crate::syscalls::*;
+
+/// Wake up to `count` threads blocked on futex_wait_bitset on this futex
+/// whose bitset intersects `bitset`, threads blocked on futex_wait match
+/// any bitset.
+///
+/// ## Parameters
+///
+/// * `futex` - Memory location that holds a futex that others may be waiting on
+/// * `count` - Maximum number of threads to wake
+/// * `bitset` - Bitset the waiting threads must intersect, must not be zero
+///
+/// ## Return
+///
+/// The number of threads which were woken
+#[instrument(level = "trace", skip_all, fields(futex_idx = field::Empty, %count, %bitset, woken = field::Empty), ret)]
+pub fn futex_wake_bitset<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    futex_ptr: WasmPtr<u32, M>,
+    count: u32,
+    bitset: u32,
+    ret_woken: WasmPtr<u32, M>,
+) -> Errno {
+    if bitset == 0 {
+        return Errno::Inval;
+    }
+
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+
+    let pointer: u64 = futex_ptr.offset().into();
+    Span::current().record("futex_idx", pointer);
+
+    let woken = env
+        .state
+        .futexs
+        .lock()
+        .unwrap()
+        .wake(pointer, count as usize, bitset);
+    Span::current().record("woken", woken);
+
+    wasi_try_mem!(ret_woken.write(&memory, woken as u32));
+    Errno::Success
+}
@@ -0,0 +1,81 @@
+use super::*;
+use crate::{net::mdns::parse_service_type, syscalls::*};
+
+/// ### `mdns_browse()`
+/// Finds the services of a type, the ones registered by the processes and,
+/// on networks which support multicast, the ones of the hosts of the local
+/// network which answer within the timeout.
+///
+/// The services are written one per line, with fields separated by tabs:
+/// the name of the service, its host, its port, its addresses separated by
+/// commas, then each of its texts. The tabs and line feeds within the names
+/// and texts are replaced by spaces.
+///
+/// If the services don't fit in the buffer then nothing is written, the
+/// size they need is returned and this function returns ERANGE.
+///
+/// ## Parameters
+///
+/// * `service_type` - The type of the services, such as `_http._tcp`
+/// * `timeout` - How long the hosts have to answer, in nanoseconds
+/// * `buf` - The buffer where the services will be written
+/// * `ret_len` - The number of bytes written
+#[instrument(level = "trace", skip_all, fields(service_type = field::Empty, %timeout, ret_len = field::Empty), ret)]
+pub fn mdns_browse<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    service_type: WasmPtr<u8, M>,
+    service_type_len: M::Offset,
+    timeout: Timestamp,
+    buf: WasmPtr<u8, M>,
+    buf_len: M::Offset,
+    ret_len: WasmPtr<M::Offset, M>,
+) -> Result<Errno, WasiError> {
+    let mut env = ctx.data();
+    let service_type = {
+        let memory = unsafe { env.memory_view(&ctx) };
+        get_input_str_ok!(&memory, service_type, service_type_len)
+    };
+    Span::current().record("service_type", service_type.as_str());
+    let service_type = wasi_try_ok!(parse_service_type(&service_type).ok_or(Errno::Inval));
+
+    let net = env.net();
+    let tasks = env.tasks().clone();
+    let services = env.control_plane.mdns().clone();
+    let found = wasi_try_ok!(block_on_with_signals(&mut ctx, None, async move {
+        let timeout = Duration::from_nanos(timeout);
+        Ok(services
+            .browse(&net, tasks.as_ref(), &service_type, timeout)
+            .await)
+    })?);
+    env = ctx.data();
+
+    let field = |text: &str| text.replace(['\t', '\n'], " ");
+    let mut out = String::new();
+    for service in found {
+        let addrs: Vec<_> = service.addrs.iter().map(ToString::to_string).collect();
+        out.push_str(&field(&service.instance));
+        out.push('\t');
+        out.push_str(&field(&service.host));
+        out.push('\t');
+        out.push_str(&service.port.to_string());
+        out.push('\t');
+        out.push_str(&addrs.join(","));
+        for txt in &service.txt {
+            out.push('\t');
+            out.push_str(&field(txt));
+        }
+        out.push('\n');
+    }
+
+    Span::current().record("ret_len", out.len());
+    let memory = unsafe { env.memory_view(&ctx) };
+    wasi_try_mem_ok!(ret_len.write(&memory, wasi_try_ok!(to_offset::<M>(out.len()))));
+    let buf_len: u64 = buf_len.into();
+    if out.len() as u64 > buf_len {
+        return Ok(Errno::Range);
+    }
+    let buf = wasi_try_mem_ok!(buf.slice(&memory, wasi_try_ok!(to_offset::<M>(out.len()))));
+    wasi_try_mem_ok!(buf.write_slice(out.as_bytes()));
+
+    Ok(Errno::Success)
+}
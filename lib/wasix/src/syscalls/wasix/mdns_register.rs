@@ -0,0 +1,81 @@
+use super::*;
+use crate::{
+    net::mdns::{parse_service_type, MdnsService},
+    syscalls::*,
+};
+
+/// ### `mdns_register()`
+/// Registers a service of the process, which the other processes and, on
+/// networks which support multicast, the other hosts of the local network
+/// find with `mdns_browse`. The service is registered until it's
+/// unregistered with `mdns_unregister` or the process exits.
+///
+/// ## Parameters
+///
+/// * `service_type` - The type of the service, such as `_http._tcp`
+/// * `instance` - The name of the service, unique among the services of
+///   its type, at most 63 bytes long
+/// * `port` - The port the service is offered on
+/// * `txt` - The texts attached to the service, usually `key=value` pairs,
+///   separated by tabs, each at most 255 bytes long
+/// * `ret_id` - Identifier of the registration
+#[instrument(level = "trace", skip_all, fields(service_type = field::Empty, instance = field::Empty, %port, ret_id = field::Empty), ret)]
+pub fn mdns_register<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    service_type: WasmPtr<u8, M>,
+    service_type_len: M::Offset,
+    instance: WasmPtr<u8, M>,
+    instance_len: M::Offset,
+    port: u16,
+    txt: WasmPtr<u8, M>,
+    txt_len: M::Offset,
+    ret_id: WasmPtr<u32, M>,
+) -> Result<Errno, WasiError> {
+    let mut env = ctx.data();
+    let (service_type, instance, txt) = {
+        let memory = unsafe { env.memory_view(&ctx) };
+        (
+            get_input_str_ok!(&memory, service_type, service_type_len),
+            get_input_str_ok!(&memory, instance, instance_len),
+            get_input_str_ok!(&memory, txt, txt_len),
+        )
+    };
+    Span::current()
+        .record("service_type", service_type.as_str())
+        .record("instance", instance.as_str());
+
+    let service_type = wasi_try_ok!(parse_service_type(&service_type).ok_or(Errno::Inval));
+    if instance.is_empty() || instance.len() > 63 {
+        return Ok(Errno::Inval);
+    }
+    let txt: Vec<String> = txt
+        .split('\t')
+        .filter(|txt| !txt.is_empty())
+        .map(String::from)
+        .collect();
+    if txt.iter().any(|txt| txt.len() > 255) {
+        return Ok(Errno::Inval);
+    }
+
+    let pid = env.pid().raw();
+    let service = MdnsService {
+        instance,
+        service_type,
+        host: format!("wasix-{pid}"),
+        port,
+        addrs: Vec::new(),
+        txt,
+    };
+    let net = env.net();
+    let services = env.control_plane.mdns().clone();
+    let id = wasi_try_ok!(block_on_with_signals(&mut ctx, None, async move {
+        services.register(&net, pid, service).await
+    })?);
+    env = ctx.data();
+
+    Span::current().record("ret_id", id);
+    let memory = unsafe { env.memory_view(&ctx) };
+    wasi_try_mem_ok!(ret_id.write(&memory, id));
+
+    Ok(Errno::Success)
+}
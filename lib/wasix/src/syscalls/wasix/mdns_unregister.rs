@@ -0,0 +1,22 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `mdns_unregister()`
+/// Unregisters a service registered by the process with `mdns_register`,
+/// the other hosts of the local network are told it's gone.
+///
+/// ## Parameters
+///
+/// * `id` - Identifier of the registration
+#[instrument(level = "trace", skip_all, fields(%id), ret)]
+pub fn mdns_unregister(mut ctx: FunctionEnvMut<'_, WasiEnv>, id: u32) -> Result<Errno, WasiError> {
+    let env = ctx.data();
+    let pid = env.pid().raw();
+    let net = env.net();
+    let services = env.control_plane.mdns().clone();
+    wasi_try_ok!(block_on_with_signals(&mut ctx, None, async move {
+        services.unregister(&net, pid, id).await
+    })?);
+
+    Ok(Errno::Success)
+}
@@ -3,17 +3,50 @@ mod chdir;
 mod epoll_create;
 mod epoll_ctl;
 mod epoll_wait;
+mod fd_chmod;
+mod fd_chown;
+mod fd_copy_range;
+mod fd_fallocate;
+mod fd_flock;
+mod fd_lock;
+mod fd_lock_get;
+mod fd_mmap;
+mod fd_msync;
+mod fd_munmap;
+mod fd_permissions_get;
 mod fd_pipe;
+mod fd_sendfile;
+mod fd_statfs;
+mod fd_timer_create;
+mod fd_timer_gettime;
+mod fd_timer_settime;
+mod fd_watch_add;
+mod fd_watch_create;
+mod fd_watch_remove;
+mod futex_requeue;
 mod futex_wait;
+mod futex_wait_bitset;
 mod futex_wake;
 mod futex_wake_all;
+mod futex_wake_bitset;
 mod getcwd;
+mod mdns_browse;
+mod mdns_register;
+mod mdns_unregister;
+mod path_chmod;
+mod path_chown;
+mod path_mount;
+mod path_permissions_get;
+mod path_statfs;
+mod path_unmount;
 mod port_addr_add;
 mod port_addr_clear;
 mod port_addr_list;
 mod port_addr_remove;
 mod port_bridge;
 mod port_dhcp_acquire;
+mod port_dhcp_lease;
+mod port_dhcp_wait;
 mod port_gateway_set;
 mod port_mac;
 mod port_route_add;
@@ -21,17 +54,27 @@ mod port_route_clear;
 mod port_route_list;
 mod port_route_remove;
 mod port_unbridge;
+mod proc_getpgid;
+mod proc_getrusage;
 mod proc_id;
 mod proc_join;
 mod proc_parent;
+mod proc_setpgid;
+mod proc_setsid;
 mod proc_signal;
+mod proc_umask;
 mod resolve;
+mod ring_enter;
 mod sched_yield;
+mod shm_open;
+mod shm_unlink;
 mod sock_accept;
 mod sock_addr_local;
 mod sock_addr_peer;
 mod sock_bind;
+mod sock_bind_unix;
 mod sock_connect;
+mod sock_connect_unix;
 mod sock_get_opt_flag;
 mod sock_get_opt_size;
 mod sock_get_opt_time;
@@ -41,9 +84,12 @@ mod sock_leave_multicast_v4;
 mod sock_leave_multicast_v6;
 mod sock_listen;
 mod sock_open;
+mod sock_pair;
 mod sock_recv;
+mod sock_recv_fds;
 mod sock_recv_from;
 mod sock_send;
+mod sock_send_fds;
 mod sock_send_file;
 mod sock_send_to;
 mod sock_set_opt_flag;
@@ -51,12 +97,17 @@ mod sock_set_opt_size;
 mod sock_set_opt_time;
 mod sock_shutdown;
 mod sock_status;
+mod sock_tls;
 mod stack_checkpoint;
 mod stack_restore;
+mod thread_detach;
 mod thread_exit;
+mod thread_exit_hook;
+mod thread_get_name;
 mod thread_id;
 mod thread_join;
 mod thread_parallelism;
+mod thread_set_name;
 mod thread_signal;
 mod thread_sleep;
 mod thread_spawn;
@@ -68,17 +119,50 @@ pub use chdir::*;
 pub use epoll_create::*;
 pub use epoll_ctl::*;
 pub use epoll_wait::*;
+pub use fd_chmod::*;
+pub use fd_chown::*;
+pub use fd_copy_range::*;
+pub use fd_fallocate::*;
+pub use fd_flock::*;
+pub use fd_lock::*;
+pub use fd_lock_get::*;
+pub use fd_mmap::*;
+pub use fd_msync::*;
+pub use fd_munmap::*;
+pub use fd_permissions_get::*;
 pub use fd_pipe::*;
+pub use fd_sendfile::*;
+pub use fd_statfs::*;
+pub use fd_timer_create::*;
+pub use fd_timer_gettime::*;
+pub use fd_timer_settime::*;
+pub use fd_watch_add::*;
+pub use fd_watch_create::*;
+pub use fd_watch_remove::*;
+pub use futex_requeue::*;
 pub use futex_wait::*;
+pub use futex_wait_bitset::*;
 pub use futex_wake::*;
 pub use futex_wake_all::*;
+pub use futex_wake_bitset::*;
 pub use getcwd::*;
+pub use mdns_browse::*;
+pub use mdns_register::*;
+pub use mdns_unregister::*;
+pub use path_chmod::*;
+pub use path_chown::*;
+pub use path_mount::*;
+pub use path_permissions_get::*;
+pub use path_statfs::*;
+pub use path_unmount::*;
 pub use port_addr_add::*;
 pub use port_addr_clear::*;
 pub use port_addr_list::*;
 pub use port_addr_remove::*;
 pub use port_bridge::*;
 pub use port_dhcp_acquire::*;
+pub use port_dhcp_lease::*;
+pub use port_dhcp_wait::*;
 pub use port_gateway_set::*;
 pub use port_mac::*;
 pub use port_route_add::*;
@@ -86,17 +170,27 @@ pub use port_route_clear::*;
 pub use port_route_list::*;
 pub use port_route_remove::*;
 pub use port_unbridge::*;
+pub use proc_getpgid::*;
+pub use proc_getrusage::*;
 pub use proc_id::*;
 pub use proc_join::*;
 pub use proc_parent::*;
+pub use proc_setpgid::*;
+pub use proc_setsid::*;
 pub use proc_signal::*;
+pub use proc_umask::*;
 pub use resolve::*;
+pub use ring_enter::*;
 pub use sched_yield::*;
+pub use shm_open::*;
+pub use shm_unlink::*;
 pub use sock_accept::*;
 pub use sock_addr_local::*;
 pub use sock_addr_peer::*;
 pub use sock_bind::*;
+pub use sock_bind_unix::*;
 pub use sock_connect::*;
+pub use sock_connect_unix::*;
 pub use sock_get_opt_flag::*;
 pub use sock_get_opt_size::*;
 pub use sock_get_opt_time::*;
@@ -106,9 +200,12 @@ pub use sock_leave_multicast_v4::*;
 pub use sock_leave_multicast_v6::*;
 pub use sock_listen::*;
 pub use sock_open::*;
+pub use sock_pair::*;
 pub use sock_recv::*;
+pub use sock_recv_fds::*;
 pub use sock_recv_from::*;
 pub use sock_send::*;
+pub use sock_send_fds::*;
 pub use sock_send_file::*;
 pub use sock_send_to::*;
 pub use sock_set_opt_flag::*;
@@ -116,12 +213,17 @@ pub use sock_set_opt_size::*;
 pub use sock_set_opt_time::*;
 pub use sock_shutdown::*;
 pub use sock_status::*;
+pub use sock_tls::*;
 pub use stack_checkpoint::*;
 pub use stack_restore::*;
+pub use thread_detach::*;
 pub use thread_exit::*;
+pub use thread_exit_hook::*;
+pub use thread_get_name::*;
 pub use thread_id::*;
 pub use thread_join::*;
 pub use thread_parallelism::*;
+pub use thread_set_name::*;
 pub use thread_signal::*;
 pub use thread_sleep::*;
 pub use thread_spawn::*;
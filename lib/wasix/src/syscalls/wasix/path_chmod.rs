@@ -0,0 +1,47 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `path_chmod()`
+/// Changes the permission bits of a file or directory, like `fchmodat`
+///
+/// ## Parameters
+///
+/// * `fd` - The directory that `path` is relative to
+/// * `flags` - Whether symbolic links are followed
+/// * `path` - Path of the file or directory
+/// * `mode` - The permission bits, along with the setuid, setgid and sticky
+///   bits
+#[instrument(level = "trace", skip_all, fields(%fd, path = field::Empty, mode = format_args!("{mode:o}")), ret)]
+pub fn path_chmod<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    flags: LookupFlags,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+    mode: u32,
+) -> Errno {
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+
+    let mut path_string = get_input_str!(&memory, path, path_len);
+    Span::current().record("path", path_string.as_str());
+    if path_string.starts_with("./") {
+        path_string = state.fs.relative_path_to_absolute(path_string);
+    }
+
+    let root_dir = wasi_try!(state.fs.get_fd(fd));
+    if !root_dir.rights.contains(Rights::PATH_FILESTAT_SET_TIMES) {
+        return Errno::Access;
+    }
+    let inode = wasi_try!(state.fs.get_inode_at_path(
+        inodes,
+        fd,
+        &path_string,
+        flags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0,
+    ));
+    wasi_try!(set_permissions_internal(env, &inode, |permissions| {
+        permissions.mode = mode & 0o7777;
+    }));
+
+    Errno::Success
+}
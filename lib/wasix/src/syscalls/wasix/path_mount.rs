@@ -0,0 +1,148 @@
+use virtual_fs::{limiter::FsQuota, ArchiveFileSystem, AsyncReadExt, FileSystem, TmpFileSystem};
+
+use super::*;
+use crate::syscalls::*;
+
+/// ### `path_mount()`
+/// Mounts a file system at a directory, like `mount`. The directory must be
+/// empty, it's restored when the file system is unmounted.
+///
+/// ## Parameters
+///
+/// * `fd` - The directory that `path` is relative to
+/// * `path` - Path of the directory to mount the file system at
+/// * `fstype` - The kind of file system, `tmpfs` for an empty in-memory file
+///   system, `zip` or `webc` for the contents of an archive, the other kinds
+///   are provided by the runtime
+/// * `source` - Which file system of that kind to mount: for `tmpfs` its
+///   comma-separated `size` and `nr_inodes` limits and `casefold` flag, like
+///   the options of tmpfs(5), and for archives the path of the archive, which
+///   is mounted read-only
+#[instrument(level = "trace", skip_all, fields(%fd, path = field::Empty, fstype = field::Empty, source = field::Empty), ret)]
+pub fn path_mount<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+    fstype: WasmPtr<u8, M>,
+    fstype_len: M::Offset,
+    source: WasmPtr<u8, M>,
+    source_len: M::Offset,
+) -> Errno {
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+
+    let mut path_string = get_input_str!(&memory, path, path_len);
+    let fstype = get_input_str!(&memory, fstype, fstype_len);
+    let source = get_input_str!(&memory, source, source_len);
+    Span::current()
+        .record("path", path_string.as_str())
+        .record("fstype", fstype.as_str())
+        .record("source", source.as_str());
+    if path_string.starts_with("./") {
+        path_string = state.fs.relative_path_to_absolute(path_string);
+    }
+
+    let base_dir = wasi_try!(state.fs.get_fd(fd));
+    if !base_dir
+        .rights
+        .contains(Rights::PATH_CREATE_DIRECTORY | Rights::PATH_REMOVE_DIRECTORY)
+    {
+        return Errno::Access;
+    }
+
+    let mounted: Box<dyn FileSystem + Send + Sync> = match fstype.as_str() {
+        "tmpfs" => {
+            let fs = TmpFileSystem::new();
+            if let Some(limiter) = state.fs.root_fs.memory_limiter() {
+                fs.set_memory_limiter(limiter);
+            }
+            let (quota, casefold) = wasi_try!(tmpfs_options(&source));
+            fs.set_quota(quota);
+            fs.set_case_insensitive(casefold);
+            Box::new(fs)
+        }
+        "zip" | "webc" => {
+            let archive = wasi_try!(read_archive(state, source));
+            let fs =
+                wasi_try!(ArchiveFileSystem::from_bytes(archive).map_err(fs_error_into_wasi_err));
+            Box::new(fs)
+        }
+        _ => match env.runtime().mount_source(&fstype, &source) {
+            Some(fs) => fs,
+            None => return Errno::Nodev,
+        },
+    };
+
+    let inode = wasi_try!(state.fs.get_inode_at_path(inodes, fd, &path_string, true));
+    let mut guard = inode.write();
+    let (mount_point, entries) = match guard.deref_mut() {
+        Kind::Dir { path, entries, .. } => (path.clone(), entries),
+        Kind::Root { .. } => return Errno::Busy,
+        _ => return Errno::Notdir,
+    };
+
+    // The mount point takes the place of the directory
+    wasi_try!(state.fs_remove_dir(&mount_point));
+    if let Err(err) = state.fs.root_fs.mount(fstype, &mount_point, mounted) {
+        if let Err(err) = state.fs_create_dir(&mount_point) {
+            tracing::warn!(path = ?mount_point, error = ?err, "failed to restore the mount point");
+        }
+        return fs_error_into_wasi_err(err);
+    }
+    // The entries of the directory are now looked up in the mounted file system
+    entries.clear();
+
+    Errno::Success
+}
+
+/// Reads the archive at `path`, which is relative to the current directory
+/// if it starts with `./`.
+fn read_archive(state: &WasiState, path: String) -> Result<Vec<u8>, Errno> {
+    let path = state.fs.relative_path_to_absolute(path);
+    let mut file = state
+        .fs
+        .root_fs
+        .new_open_options()
+        .read(true)
+        .open(&path)
+        .map_err(fs_error_into_wasi_err)?;
+
+    let mut archive = Vec::new();
+    block_on(file.read_to_end(&mut archive)).map_err(map_io_err)?;
+    Ok(archive)
+}
+
+/// Parses the options of a `tmpfs`: the `size` and `nr_inodes` limits, which
+/// take a `k`, `m` or `g` suffix and are unlimited when zero, and whether the
+/// lookups are case-insensitive with `casefold`.
+fn tmpfs_options(options: &str) -> Result<(FsQuota, bool), Errno> {
+    let mut quota = FsQuota::default();
+    let mut casefold = false;
+    for option in options.split(',').filter(|option| !option.is_empty()) {
+        // Names are folded with Unicode lowercasing whatever the encoding
+        // requested, e.g. `casefold=utf8`
+        if option == "casefold" || option.starts_with("casefold=") {
+            casefold = true;
+            continue;
+        }
+        let (key, value) = option.split_once('=').ok_or(Errno::Inval)?;
+        let (digits, multiplier) = match value.as_bytes().last() {
+            Some(b'k' | b'K') => (&value[..value.len() - 1], 1 << 10),
+            Some(b'm' | b'M') => (&value[..value.len() - 1], 1 << 20),
+            Some(b'g' | b'G') => (&value[..value.len() - 1], 1 << 30),
+            _ => (value, 1),
+        };
+        let value = digits
+            .parse::<u64>()
+            .ok()
+            .and_then(|value| value.checked_mul(multiplier))
+            .ok_or(Errno::Inval)?;
+        match key {
+            "size" => quota.bytes = (value > 0).then_some(value),
+            "nr_inodes" => quota.inodes = (value > 0).then_some(value),
+            _ => return Err(Errno::Inval),
+        }
+    }
+    Ok((quota, casefold))
+}
@@ -0,0 +1,55 @@
+use super::*;
+use crate::syscalls::*;
+use wasmer_wasix_types::wasi::Fileperms;
+
+/// ### `path_permissions_get()`
+/// Returns the mode and owner of a file or directory
+///
+/// ## Parameters
+///
+/// * `fd` - The directory that `path` is relative to
+/// * `flags` - Whether symbolic links are followed
+/// * `path` - Path of the file or directory
+/// * `buf` - Where the permissions of the file are written
+#[instrument(level = "trace", skip_all, fields(%fd, path = field::Empty), ret)]
+pub fn path_permissions_get<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    flags: LookupFlags,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+    buf: WasmPtr<Fileperms, M>,
+) -> Errno {
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+
+    let mut path_string = get_input_str!(&memory, path, path_len);
+    Span::current().record("path", path_string.as_str());
+    if path_string.starts_with("./") {
+        path_string = state.fs.relative_path_to_absolute(path_string);
+    }
+
+    let root_dir = wasi_try!(state.fs.get_fd(fd));
+    if !root_dir.rights.contains(Rights::PATH_FILESTAT_GET) {
+        return Errno::Access;
+    }
+    let inode = wasi_try!(state.fs.get_inode_at_path(
+        inodes,
+        fd,
+        &path_string,
+        flags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0,
+    ));
+    let permissions = {
+        let guard = inode.read();
+        wasi_try!(state.fs.get_permissions_for_kind(guard.deref()))
+    };
+
+    let perms = Fileperms {
+        mode: permissions.mode,
+        uid: permissions.uid,
+        gid: permissions.gid,
+    };
+    wasi_try_mem!(buf.write(&memory, perms));
+
+    Errno::Success
+}
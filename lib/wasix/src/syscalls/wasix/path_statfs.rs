@@ -0,0 +1,44 @@
+use super::*;
+use crate::syscalls::*;
+use wasmer_wasix_types::wasi::Statfs;
+
+/// ### `path_statfs()`
+/// Reports the capacity and usage of the file system holding a file or
+/// directory, like `statfs`. Symbolic links are followed.
+///
+/// ## Parameters
+///
+/// * `fd` - The directory that `path` is relative to
+/// * `path` - Path of the file or directory
+/// * `buf` - Where the statistics of the file system are written
+#[instrument(level = "trace", skip_all, fields(%fd, path = field::Empty), ret)]
+pub fn path_statfs<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+    buf: WasmPtr<Statfs, M>,
+) -> Errno {
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+
+    let mut path_string = get_input_str!(&memory, path, path_len);
+    Span::current().record("path", path_string.as_str());
+    if path_string.starts_with("./") {
+        path_string = state.fs.relative_path_to_absolute(path_string);
+    }
+
+    let root_dir = wasi_try!(state.fs.get_fd(fd));
+    if !root_dir.rights.contains(Rights::PATH_FILESTAT_GET) {
+        return Errno::Access;
+    }
+    let inode = wasi_try!(state.fs.get_inode_at_path(inodes, fd, &path_string, true));
+    let stats = {
+        let guard = inode.read();
+        wasi_try!(state.fs.get_statfs_for_kind(guard.deref()))
+    };
+
+    wasi_try_mem!(buf.write(&memory, stats));
+
+    Errno::Success
+}
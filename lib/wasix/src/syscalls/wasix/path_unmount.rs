@@ -0,0 +1,56 @@
+use virtual_fs::FileSystem;
+
+use super::*;
+use crate::syscalls::*;
+
+/// ### `path_unmount()`
+/// Unmounts the file system mounted at a directory, like `umount`. The
+/// directory is then empty again.
+///
+/// ## Parameters
+///
+/// * `fd` - The directory that `path` is relative to
+/// * `path` - Path of the directory the file system is mounted at
+#[instrument(level = "trace", skip_all, fields(%fd, path = field::Empty), ret)]
+pub fn path_unmount<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    fd: WasiFd,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+) -> Errno {
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+
+    let mut path_string = get_input_str!(&memory, path, path_len);
+    Span::current().record("path", path_string.as_str());
+    if path_string.starts_with("./") {
+        path_string = state.fs.relative_path_to_absolute(path_string);
+    }
+
+    let base_dir = wasi_try!(state.fs.get_fd(fd));
+    if !base_dir
+        .rights
+        .contains(Rights::PATH_CREATE_DIRECTORY | Rights::PATH_REMOVE_DIRECTORY)
+    {
+        return Errno::Access;
+    }
+
+    let inode = wasi_try!(state.fs.get_inode_at_path(inodes, fd, &path_string, true));
+    let mut guard = inode.write();
+    let (mount_point, entries) = match guard.deref_mut() {
+        Kind::Dir { path, entries, .. } => (path.clone(), entries),
+        Kind::Root { .. } => return Errno::Busy,
+        _ => return Errno::Notdir,
+    };
+
+    wasi_try!(state
+        .fs
+        .root_fs
+        .unmount(&mount_point)
+        .map_err(fs_error_into_wasi_err));
+    wasi_try!(state.fs_create_dir(&mount_point));
+    // The entries belonged to the mounted file system
+    entries.clear();
+
+    Errno::Success
+}
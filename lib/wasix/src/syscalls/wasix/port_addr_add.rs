@@ -30,7 +30,7 @@ pub(crate) fn port_addr_add_internal(
     cidr: IpCidr,
 ) -> Result<Result<(), Errno>, WasiError> {
     let env = ctx.data();
-    let net = env.net().clone();
+    let net = env.net();
     wasi_try_ok_ok!(block_on_with_signals(ctx, None, async {
         net.ip_add(cidr.ip, cidr.prefix)
             .await
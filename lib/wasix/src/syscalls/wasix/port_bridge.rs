@@ -51,7 +51,7 @@ pub(crate) fn port_bridge_internal(
 ) -> Result<Result<(), Errno>, WasiError> {
     let env = ctx.data();
 
-    let net = env.net().clone();
+    let net = env.net();
     wasi_try_ok_ok!(block_on_with_signals(ctx, None, async move {
         net.bridge(network, token, security)
             .await
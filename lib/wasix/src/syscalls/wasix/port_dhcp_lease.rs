@@ -0,0 +1,73 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `port_dhcp_lease()`
+/// Returns the lease of the addresses acquired with `port_dhcp_acquire`.
+/// If a buffer is not big enough then its count will be filled with the
+/// size needed and EOVERFLOW will be returned
+///
+/// ## Parameters
+///
+/// * `lease` - Where the lease will be stored
+/// * `addrs` - The buffer where the addresses given by the lease will be
+///   stored, there are none once the lease is lost
+/// * `naddrs` - The size of the `addrs` buffer, then the number of addresses
+/// * `dns_servers` - The buffer where the DNS servers will be stored
+/// * `ndns_servers` - The size of the `dns_servers` buffer, then the number
+///   of DNS servers
+#[instrument(level = "trace", skip_all, fields(serial = field::Empty, naddrs = field::Empty, ndns_servers = field::Empty), ret)]
+pub fn port_dhcp_lease<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    lease_ptr: WasmPtr<DhcpLease, M>,
+    addrs_ptr: WasmPtr<__wasi_cidr_t, M>,
+    naddrs_ptr: WasmPtr<M::Offset, M>,
+    dns_ptr: WasmPtr<__wasi_addr_t, M>,
+    ndns_ptr: WasmPtr<M::Offset, M>,
+) -> Result<Errno, WasiError> {
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    let max_addrs: u64 = wasi_try_mem_ok!(naddrs_ptr.read(&memory)).into();
+    let max_dns: u64 = wasi_try_mem_ok!(ndns_ptr.read(&memory)).into();
+
+    let net = env.net();
+    let lease = wasi_try_ok!(block_on_with_signals(&mut ctx, None, async {
+        net.dhcp_lease().await.map_err(net_error_into_wasi_err)
+    })?);
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    Span::current()
+        .record("serial", lease.serial)
+        .record("naddrs", lease.addrs.len())
+        .record("ndns_servers", lease.dns_servers.len());
+
+    wasi_try_ok!(crate::net::write_dhcp_lease(&memory, lease_ptr, &lease));
+
+    let addrs_len: M::Offset =
+        wasi_try_ok!(lease.addrs.len().try_into().map_err(|_| Errno::Overflow));
+    wasi_try_mem_ok!(naddrs_ptr.write(&memory, addrs_len));
+    let dns_len: M::Offset = wasi_try_ok!(lease
+        .dns_servers
+        .len()
+        .try_into()
+        .map_err(|_| Errno::Overflow));
+    wasi_try_mem_ok!(ndns_ptr.write(&memory, dns_len));
+    if lease.addrs.len() as u64 > max_addrs || lease.dns_servers.len() as u64 > max_dns {
+        return Ok(Errno::Overflow);
+    }
+
+    let ref_addrs = wasi_try_mem_ok!(
+        addrs_ptr.slice(&memory, wasi_try_ok!(to_offset::<M>(max_addrs as usize)))
+    );
+    for (n, cidr) in lease.addrs.iter().enumerate() {
+        let nip = ref_addrs.index(n as u64);
+        let _ = crate::net::write_cidr(&memory, nip.as_ptr::<M>(), *cidr);
+    }
+    let ref_dns =
+        wasi_try_mem_ok!(dns_ptr.slice(&memory, wasi_try_ok!(to_offset::<M>(max_dns as usize))));
+    for (n, ip) in lease.dns_servers.iter().enumerate() {
+        let nip = ref_dns.index(n as u64);
+        let _ = crate::net::write_ip(&memory, nip.as_ptr::<M>(), *ip);
+    }
+
+    Ok(Errno::Success)
+}
@@ -0,0 +1,42 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `port_dhcp_wait()`
+/// Waits until the DHCP lease changes, because it was acquired, renewed
+/// with other settings or lost, then `port_dhcp_lease` returns the new one.
+/// If the timeout elapses first then ETIMEDOUT is returned
+///
+/// ## Parameters
+///
+/// * `serial` - The serial of the lease known so far
+/// * `timeout` - How long to wait for, forever when there's none
+/// * `ret_serial` - The serial of the new lease
+#[instrument(level = "trace", skip_all, fields(%serial, ret_serial = field::Empty), ret)]
+pub fn port_dhcp_wait<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    serial: u64,
+    timeout: WasmPtr<OptionTimestamp, M>,
+    ret_serial: WasmPtr<u64, M>,
+) -> Result<Errno, WasiError> {
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    let timeout = wasi_try_mem_ok!(timeout.read(&memory));
+    let timeout = match timeout.tag {
+        OptionTag::Some => Some(Duration::from_nanos(timeout.u)),
+        _ => None,
+    };
+
+    let net = env.net();
+    let lease = wasi_try_ok!(block_on_with_signals(&mut ctx, timeout, async move {
+        net.dhcp_lease_changed(serial)
+            .await
+            .map_err(net_error_into_wasi_err)
+    })?);
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+
+    Span::current().record("ret_serial", lease.serial);
+    wasi_try_mem_ok!(ret_serial.write(&memory, lease.serial));
+
+    Ok(Errno::Success)
+}
@@ -28,7 +28,7 @@ pub(crate) fn port_gateway_set_internal(
     ip: IpAddr,
 ) -> Result<Result<(), Errno>, WasiError> {
     let env = ctx.data();
-    let net = env.net().clone();
+    let net = env.net();
     wasi_try_ok_ok!(block_on_with_signals(ctx, None, async {
         net.gateway_set(ip).await.map_err(net_error_into_wasi_err)
     })?);
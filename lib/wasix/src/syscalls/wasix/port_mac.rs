@@ -10,7 +10,7 @@ pub fn port_mac<M: MemorySize>(
 ) -> Result<Errno, WasiError> {
     let env = ctx.data();
 
-    let net = env.net().clone();
+    let net = env.net();
     let mac = wasi_try_ok!(block_on_with_signals(&mut ctx, None, async {
         net.mac().await.map_err(net_error_into_wasi_err)
     })?);
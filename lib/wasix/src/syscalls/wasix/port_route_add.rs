@@ -52,7 +52,7 @@ pub(crate) fn port_route_add_internal(
     expires_at: Option<Duration>,
 ) -> Result<Result<(), Errno>, WasiError> {
     let env = ctx.data();
-    let net = env.net().clone();
+    let net = env.net();
     wasi_try_ok_ok!(block_on_with_signals(ctx, None, async {
         net.route_add(cidr, via_router, preferred_until, expires_at)
             .await
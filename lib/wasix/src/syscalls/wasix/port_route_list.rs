@@ -26,7 +26,7 @@ pub fn port_route_list<M: MemorySize>(
     let _ref_routes =
         wasi_try_mem_ok!(routes_ptr.slice(&memory, wasi_try_ok!(to_offset::<M>(max_routes))));
 
-    let net = env.net().clone();
+    let net = env.net();
     let routes = wasi_try_ok!(block_on_with_signals(&mut ctx, None, async {
         net.route_list().await.map_err(net_error_into_wasi_err)
     })?);
@@ -0,0 +1,42 @@
+use super::*;
+use crate::syscalls::*;
+
+/// Resources used by the calling process
+pub const RUSAGE_SELF: i32 = 0;
+/// Resources used by the children of the calling process which have
+/// terminated and been reaped
+pub const RUSAGE_CHILDREN: i32 = -1;
+/// Resources used by the calling thread
+pub const RUSAGE_THREAD: i32 = 1;
+
+/// ### `proc_getrusage()`
+/// Returns the resources used by the current process, its children or the
+/// current thread
+///
+/// ## Parameters
+///
+/// * `who` - One of `RUSAGE_SELF`, `RUSAGE_CHILDREN` or `RUSAGE_THREAD`
+///
+/// ## Return
+///
+/// The CPU time in nanoseconds and the number of syscalls made
+#[instrument(level = "trace", skip_all, fields(%who), ret)]
+pub fn proc_getrusage<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    who: i32,
+    ret_cputime: WasmPtr<Timestamp, M>,
+    ret_syscalls: WasmPtr<u64, M>,
+) -> Errno {
+    let env = ctx.data();
+    let usage = match who {
+        RUSAGE_SELF => env.process.usage(),
+        RUSAGE_CHILDREN => env.process.children_usage(),
+        RUSAGE_THREAD => env.thread.usage(),
+        _ => return Errno::Inval,
+    };
+
+    let memory = unsafe { env.memory_view(&ctx) };
+    wasi_try_mem!(ret_cputime.write(&memory, usage.cpu_time.as_nanos() as Timestamp));
+    wasi_try_mem!(ret_syscalls.write(&memory, usage.syscalls));
+    Errno::Success
+}
@@ -100,6 +100,17 @@ pub(super) fn proc_join_internal<M: MemorySize + 'static>(
 
     // If the ID is maximum then it means wait for any of the children
     let pid = match option_pid {
+        None if flags.contains(JoinFlags::NON_BLOCKING) => {
+            let res = match ctx.data().process.try_join_any_child() {
+                Ok(Some((pid, exit_code))) => {
+                    trace!(ret_id = pid.raw(), exit_code = exit_code.raw());
+                    JoinStatusResult::ExitNormal(pid, exit_code)
+                }
+                Ok(None) => JoinStatusResult::Nothing,
+                Err(err) => JoinStatusResult::Err(err),
+            };
+            return ret_result(ctx, res);
+        }
         None => {
             let mut process = ctx.data_mut().process.clone();
 
@@ -131,18 +142,11 @@ pub(super) fn proc_join_internal<M: MemorySize + 'static>(
     // Otherwise we wait for the specific PID
     let pid: WasiProcessId = pid.into();
 
-    // Waiting for a process that is an explicit child will join it
-    // meaning it will no longer be a sub-process of the main process
+    // Joining a process that is an explicit child reaps it, meaning it
+    // will no longer be a sub-process of the main process
     let mut process = {
-        let mut inner = ctx.data().process.lock();
-        let process = inner
-            .children
-            .iter()
-            .filter(|c| c.pid == pid)
-            .map(Clone::clone)
-            .next();
-        inner.children.retain(|c| c.pid != pid);
-        process
+        let inner = ctx.data().process.lock();
+        inner.children.iter().find(|c| c.pid == pid).cloned()
     };
 
     // Otherwise it could be the case that we are waiting for a process
@@ -162,7 +166,7 @@ pub(super) fn proc_join_internal<M: MemorySize + 'static>(
         ));
 
         if flags.contains(JoinFlags::NON_BLOCKING) {
-            if let Some(status) = process.try_join() {
+            if let Some(status) = process.try_reap() {
                 let exit_code = status.unwrap_or_else(|_| Errno::Child.into());
                 ret_result(ctx, JoinStatusResult::ExitNormal(pid, exit_code))
             } else {
@@ -0,0 +1,36 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `proc_setpgid()`
+/// Moves a process into another process group of the same session
+///
+/// ## Parameters
+///
+/// * `pid` - Handle of the current process or one of its children, zero
+///   for the current process
+/// * `pgid` - Process group to join, zero to create a new process group
+///   led by the process
+#[instrument(level = "trace", skip_all, fields(%pid, %pgid), ret)]
+pub fn proc_setpgid(ctx: FunctionEnvMut<'_, WasiEnv>, pid: Pid, pgid: Pid) -> Errno {
+    let env = ctx.data();
+    let process = if pid == 0 || WasiProcessId::from(pid) == env.process.pid() {
+        env.process.clone()
+    } else {
+        let pid = WasiProcessId::from(pid);
+        let inner = env.process.lock();
+        wasi_try!(inner
+            .children
+            .iter()
+            .find(|child| child.pid() == pid)
+            .cloned()
+            .ok_or(Errno::Srch))
+    };
+
+    let pgid = if pgid == 0 {
+        process.pid()
+    } else {
+        WasiProcessId::from(pgid)
+    };
+    wasi_try!(process.set_pgid(pgid));
+    Errno::Success
+}
@@ -0,0 +1,20 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `proc_setsid()`
+/// Makes the current process the leader of a new session and process group
+///
+/// Fails with `Errno::Perm` if the process already leads a process group.
+#[instrument(level = "trace", skip_all, fields(sid = field::Empty), ret)]
+pub fn proc_setsid<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    ret_sid: WasmPtr<Pid, M>,
+) -> Errno {
+    let env = ctx.data();
+    let sid = wasi_try!(env.process.new_session());
+    Span::current().record("sid", sid.raw());
+
+    let memory = unsafe { env.memory_view(&ctx) };
+    wasi_try_mem!(ret_sid.write(&memory, sid.raw() as Pid));
+    Errno::Success
+}
@@ -6,7 +6,9 @@ use crate::syscalls::*;
 ///
 /// ## Parameters
 ///
-/// * `pid` - Handle of the child process to wait on
+/// * `pid` - Handle of the child process to wait on, when negative (as a
+///   signed `pid_t`) the signal is sent to every process in the process
+///   group `-pid`, and zero sends it to the process group of this process
 /// * `sig` - Signal to send the child process
 #[instrument(level = "trace", skip_all, fields(%pid, ?sig), ret)]
 pub fn proc_signal<M: MemorySize>(
@@ -14,12 +16,23 @@ pub fn proc_signal<M: MemorySize>(
     pid: Pid,
     sig: Signal,
 ) -> Result<Errno, WasiError> {
-    let process = {
-        let pid: WasiProcessId = pid.into();
-        ctx.data().control_plane.get_process(pid)
-    };
-    if let Some(process) = process {
-        process.signal_process(sig);
+    let pid = pid as i32;
+    if pid > 0 {
+        let process = {
+            let pid: WasiProcessId = pid.into();
+            ctx.data().control_plane.get_process(pid)
+        };
+        if let Some(process) = process {
+            process.signal_process(sig);
+        }
+    } else {
+        let env = ctx.data();
+        let pgid = if pid == 0 {
+            env.process.pgid()
+        } else {
+            WasiProcessId::from(pid.unsigned_abs())
+        };
+        wasi_try_ok!(env.control_plane.signal_process_group(pgid, sig));
     }
 
     wasi_try_ok!(WasiEnv::process_signals_and_exit(&mut ctx)?);
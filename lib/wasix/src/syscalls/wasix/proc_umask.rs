@@ -0,0 +1,24 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `proc_umask()`
+/// Sets the umask of the current process, the permission bits removed from
+/// the mode of the files and directories it creates, like `umask`
+///
+/// ## Parameters
+///
+/// * `mask` - The new umask, of which only the permission bits are kept
+/// * `ret_old` - Where the previous umask is written
+#[instrument(level = "trace", skip_all, fields(mask = format_args!("{mask:o}")), ret)]
+pub fn proc_umask<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    mask: u32,
+    ret_old: WasmPtr<u32, M>,
+) -> Errno {
+    let env = ctx.data();
+    let old = env.state.fs.set_umask(mask);
+
+    let memory = unsafe { env.memory_view(&ctx) };
+    wasi_try_mem!(ret_old.write(&memory, old));
+    Errno::Success
+}
@@ -39,9 +39,11 @@ pub fn resolve<M: MemorySize>(
 
     let port = if port > 0 { Some(port) } else { None };
 
-    let net = env.net().clone();
+    let net = env.net();
+    let resolver = env.state.resolver.clone();
     let found_ips = wasi_try_ok!(block_on_with_signals(&mut ctx, None, async move {
-        net.resolve(host_str.as_str(), port, None)
+        resolver
+            .resolve(&net, host_str.as_str(), port)
             .await
             .map_err(net_error_into_wasi_err)
     })?);
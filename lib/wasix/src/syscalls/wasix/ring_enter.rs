@@ -0,0 +1,128 @@
+use super::*;
+use crate::syscalls::*;
+use wasmer_wasix_types::wasi::{Ring, RingCqe, RingOp, RingSqe};
+
+/// ### `ring_enter()`
+/// Runs the operations submitted to a ring and queues their completions,
+/// so a batch of reads and writes crosses into the runtime only once.
+///
+/// The entries between `sq_head` and `sq_tail` are run in order, for as long
+/// as the completion queue has room, and each of them completes with its own
+/// error. Operations this runtime doesn't know complete with `Errno::Notsup`,
+/// upon which the process falls back to the syscall of the operation. The
+/// runtime has no shim for the processes running on runtimes without
+/// `ring_enter`, see [`Ring`].
+///
+/// ## Parameters
+///
+/// * `ring` - The ring shared by the process, whose `sq_head` and `cq_tail`
+///   are advanced past the entries which were run
+/// * `ret_submitted` - Number of entries which were run
+#[instrument(level = "trace", skip_all, fields(submitted = field::Empty), ret)]
+pub fn ring_enter<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    ring: WasmPtr<Ring<M>, M>,
+    ret_submitted: WasmPtr<u32, M>,
+) -> Result<Errno, WasiError> {
+    wasi_try_ok!(WasiEnv::process_signals_and_exit(&mut ctx)?);
+
+    let mut header = {
+        let env = ctx.data();
+        let memory = unsafe { env.memory_view(&ctx) };
+        wasi_try_mem_ok!(ring.read(&memory))
+    };
+    let Some(count) = header.runnable() else {
+        return Ok(Errno::Inval);
+    };
+
+    let sqes = WasmPtr::<RingSqe<M>, M>::new(header.sqes);
+    let cqes = WasmPtr::<RingCqe, M>::new(header.cqes);
+    for _ in 0..count {
+        let sqe = {
+            let env = ctx.data();
+            let memory = unsafe { env.memory_view(&ctx) };
+            let sqes = wasi_try_mem_ok!(sqes.slice(&memory, header.sq_entries.into()));
+            wasi_try_mem_ok!(sqes.index(header.sq_slot() as u64).read())
+        };
+
+        let (result, errno) = match ring_op::<M>(&mut ctx, &sqe)? {
+            Ok(len) => (len as u64, Errno::Success),
+            Err(err) => (0, err),
+        };
+        let cqe = RingCqe {
+            user_data: sqe.user_data,
+            result,
+            errno,
+        };
+
+        // The memory may have grown while the operation ran
+        let env = ctx.data();
+        let memory = unsafe { env.memory_view(&ctx) };
+        let cqes = wasi_try_mem_ok!(cqes.slice(&memory, header.cq_entries.into()));
+        wasi_try_mem_ok!(cqes.index(header.cq_slot() as u64).write(cqe));
+        header.advance();
+    }
+
+    // Only the indexes the runtime owns are written, as the process may be
+    // submitting and reaping from other threads meanwhile
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    let indexes = ring.cast::<u32>();
+    wasi_try_mem_ok!(indexes.write(&memory, header.sq_head));
+    let cq_tail_ptr = wasi_try_mem_ok!(indexes.add_offset(3u32.into()));
+    wasi_try_mem_ok!(cq_tail_ptr.write(&memory, header.cq_tail));
+    Span::current().record("submitted", count);
+    wasi_try_mem_ok!(ret_submitted.write(&memory, count));
+
+    Ok(Errno::Success)
+}
+
+/// Runs a submitted entry, returning the number of bytes it read or wrote.
+fn ring_op<M: MemorySize>(
+    ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+    sqe: &RingSqe<M>,
+) -> WasiResult<usize> {
+    let Ok(op) = RingOp::try_from(sqe.opcode) else {
+        return Ok(Err(Errno::Notsup));
+    };
+    let cursor = |ctx: &FunctionEnvMut<'_, WasiEnv>| {
+        let fd_entry = ctx.data().state.fs.get_fd(sqe.fd)?;
+        Ok::<_, Errno>(fd_entry.offset.load(Ordering::Acquire))
+    };
+
+    match op {
+        RingOp::Nop => Ok(Ok(0)),
+        RingOp::Read => {
+            let offset = wasi_try_ok_ok!(cursor(ctx));
+            let iovs = WasmPtr::new(sqe.iovs);
+            fd_read_internal::<M>(ctx, sqe.fd, iovs, sqe.iovs_len, offset as usize, true)
+        }
+        RingOp::Pread => {
+            let iovs = WasmPtr::new(sqe.iovs);
+            fd_read_internal::<M>(ctx, sqe.fd, iovs, sqe.iovs_len, sqe.offset as usize, false)
+        }
+        RingOp::Write => {
+            let offset = wasi_try_ok_ok!(cursor(ctx));
+            let data = FdWriteSource::Iovs {
+                iovs: WasmPtr::new(sqe.iovs),
+                iovs_len: sqe.iovs_len,
+            };
+            fd_write_internal::<M>(ctx, sqe.fd, data, offset, true)
+        }
+        RingOp::Pwrite => {
+            let data = FdWriteSource::Iovs {
+                iovs: WasmPtr::new(sqe.iovs),
+                iovs_len: sqe.iovs_len,
+            };
+            fd_write_internal::<M>(ctx, sqe.fd, data, sqe.offset, false)
+        }
+        RingOp::Sync => match fd_sync(ctx.as_mut(), sqe.fd)? {
+            Errno::Success => Ok(Ok(0)),
+            err => Ok(Err(err)),
+        },
+        RingOp::Close => match fd_close(ctx.as_mut(), sqe.fd)? {
+            Errno::Success => Ok(Ok(0)),
+            err => Ok(Err(err)),
+        },
+    }
+}
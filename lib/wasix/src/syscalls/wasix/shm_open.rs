@@ -0,0 +1,90 @@
+use std::{path::PathBuf, sync::RwLock};
+
+use virtual_fs::VirtualFile;
+
+use super::*;
+use crate::{fs::SharedMemoryFile, syscalls::*};
+
+/// ### `shm_open()`
+/// Opens a shared memory object, like `shm_open`. The objects are shared by
+/// all the processes of the control plane, and named until `shm_unlink`.
+///
+/// What is written through the file handle is shared right away, while each
+/// process mapping the object with `fd_mmap` has its own copy. The copies of
+/// the shared mappings are merged with the object when they are synced with
+/// `fd_msync`, the bytes a process changed since then going to the object
+/// and the others getting the changes of the other processes. The mappings
+/// are thus not coherent, and can't carry atomics or futexes between the
+/// processes.
+///
+/// The objects hold at most 1 GiB altogether, growing them past it failing
+/// with `Errno::Nospc`.
+///
+/// ## Parameters
+///
+/// * `name` - Name of the object, a slash followed by up to 255 characters
+///   which aren't slashes
+/// * `o_flags` - `CREATE` creates the object empty when it doesn't exist,
+///   which it must not with `EXCL`. `TRUNC` empties it
+/// * `fs_rights` - `FD_READ`, along with `FD_WRITE` when the object is
+///   written or resized
+/// * `fd_flags` - Flags of the file handle
+/// * `ret_fd` - The new file handle
+#[instrument(level = "trace", skip_all, fields(name = field::Empty, ?o_flags, ret_fd = field::Empty), ret)]
+pub fn shm_open<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    name: WasmPtr<u8, M>,
+    name_len: M::Offset,
+    o_flags: Oflags,
+    fs_rights: Rights,
+    fd_flags: Fdflags,
+    ret_fd: WasmPtr<WasiFd, M>,
+) -> Errno {
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+    let name = get_input_str!(&memory, name, name_len);
+    Span::current().record("name", name.as_str());
+
+    if o_flags.contains(Oflags::DIRECTORY) || !fs_rights.contains(Rights::FD_READ) {
+        return Errno::Inval;
+    }
+    let writable = fs_rights.contains(Rights::FD_WRITE);
+    if o_flags.contains(Oflags::TRUNC) && !writable {
+        return Errno::Access;
+    }
+
+    let object = wasi_try!(env.control_plane.shared_memory().open(
+        &name,
+        o_flags.contains(Oflags::CREATE),
+        o_flags.contains(Oflags::EXCL),
+    ));
+    let mut file = SharedMemoryFile::new(object);
+    if o_flags.contains(Oflags::TRUNC) {
+        wasi_try!(file.set_len(0).map_err(fs_error_into_wasi_err));
+    }
+
+    let kind = Kind::File {
+        handle: Some(Arc::new(RwLock::new(Box::new(file)))),
+        path: PathBuf::from(format!("/dev/shm{name}")),
+        fd: None,
+    };
+    let inode = state
+        .fs
+        .create_inode_with_default_stat(inodes, kind, false, name.into());
+    let mut rights = Rights::FD_READ
+        | Rights::FD_SEEK
+        | Rights::FD_TELL
+        | Rights::FD_FILESTAT_GET
+        | Rights::FD_FDSTAT_SET_FLAGS
+        | Rights::POLL_FD_READWRITE;
+    if writable {
+        rights |=
+            Rights::FD_WRITE | Rights::FD_FILESTAT_SET_SIZE | Rights::FD_SYNC | Rights::FD_DATASYNC;
+    }
+    let fd = wasi_try!(state.fs.create_fd(rights, rights, fd_flags, 0, inode));
+
+    Span::current().record("ret_fd", fd);
+    wasi_try_mem!(ret_fd.write(&memory, fd));
+
+    Errno::Success
+}
@@ -0,0 +1,26 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `shm_unlink()`
+/// Removes the name of a shared memory object, like `shm_unlink`. Its
+/// contents stay until the processes which opened or mapped it are done
+/// with it.
+///
+/// ## Parameters
+///
+/// * `name` - Name the object was opened with
+#[instrument(level = "trace", skip_all, fields(name = field::Empty), ret)]
+pub fn shm_unlink<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    name: WasmPtr<u8, M>,
+    name_len: M::Offset,
+) -> Errno {
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    let name = get_input_str!(&memory, name, name_len);
+    Span::current().record("name", name.as_str());
+
+    wasi_try!(env.control_plane.shared_memory().unlink(&name));
+
+    Errno::Success
+}
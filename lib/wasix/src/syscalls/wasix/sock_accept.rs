@@ -63,6 +63,13 @@ pub fn sock_accept_v2<M: MemorySize>(
 ) -> Result<Errno, WasiError> {
     wasi_try_ok!(WasiEnv::process_signals_and_exit(&mut ctx)?);
 
+    let is_unix = wasi_try_ok!(__sock_actor(
+        &mut ctx,
+        sock,
+        Rights::empty(),
+        |socket, _| { Ok(socket.is_unix()) }
+    ));
+
     let env = ctx.data();
     let (_memory, _state, _) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
 
@@ -79,12 +86,16 @@ pub fn sock_accept_v2<M: MemorySize>(
     let env = ctx.data();
     let (memory, _state, _) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
     wasi_try_mem_ok!(ro_fd.write(&memory, fd));
-    wasi_try_ok!(crate::net::write_ip_port(
-        &memory,
-        ro_addr,
-        peer_addr.ip(),
-        peer_addr.port()
-    ));
+    if is_unix {
+        wasi_try_ok!(crate::net::write_unix_addr(&memory, ro_addr));
+    } else {
+        wasi_try_ok!(crate::net::write_ip_port(
+            &memory,
+            ro_addr,
+            peer_addr.ip(),
+            peer_addr.port()
+        ));
+    }
 
     Ok(Errno::Success)
 }
@@ -100,7 +111,7 @@ pub(crate) fn sock_accept_internal(
     let inodes = &state.inodes;
 
     let tasks = env.tasks().clone();
-    let (child, local_addr, peer_addr, fd_flags) = wasi_try_ok_ok!(block_on_sock(
+    let (child, local_addr, peer_addr, fd_flags, unix, listener) = wasi_try_ok_ok!(block_on_sock(
         env,
         sock,
         Rights::SOCK_ACCEPT,
@@ -118,17 +129,21 @@ pub(crate) fn sock_accept_internal(
             socket
                 .accept(tasks.deref(), nonblocking, Some(timeout))
                 .await
-                .map(|a| (a.0, local_addr, a.1, fd_flags))
+                .map(|a| (a.0, local_addr, a.1, fd_flags, a.2, socket.clone()))
         },
     ));
 
-    let kind = Kind::Socket {
-        socket: InodeSocket::new(InodeSocketKind::TcpStream {
-            socket: child,
-            write_timeout: None,
-            read_timeout: None,
-        }),
+    let kind = InodeSocketKind::TcpStream {
+        socket: child,
+        write_timeout: None,
+        read_timeout: None,
+    };
+    let socket = match unix {
+        Some(unix) => InodeSocket::new_unix(kind, unix),
+        None => InodeSocket::new(kind),
     };
+    socket.inherit_options(&listener);
+    let kind = Kind::Socket { socket };
     let inode = state
         .fs
         .create_inode_with_default_stat(inodes, kind, false, "socket".into());
@@ -7,7 +7,7 @@ use crate::syscalls::*;
 /// Note: This is similar to `getpeername` in POSIX
 ///
 /// When successful, the contents of the output buffer consist of an IP address,
-/// either IP4 or IP6.
+/// either IP4 or IP6, or only the family of a Unix socket.
 ///
 /// ## Parameters
 ///
@@ -22,17 +22,24 @@ pub fn sock_addr_peer<M: MemorySize>(
         &mut ctx,
         sock,
         Rights::empty(),
-        |socket, _| socket.addr_peer()
+        |socket, _| match socket.is_unix() {
+            true => Ok(None),
+            false => socket.addr_peer().map(Some),
+        }
     ));
     Span::current().record("addr", &format!("{:?}", addr));
 
     let env = ctx.data();
     let memory = unsafe { env.memory_view(&ctx) };
-    wasi_try!(crate::net::write_ip_port(
-        &memory,
-        ro_addr,
-        addr.ip(),
-        addr.port()
-    ));
+    match addr {
+        Some(addr) => wasi_try!(crate::net::write_ip_port(
+            &memory,
+            ro_addr,
+            addr.ip(),
+            addr.port()
+        )),
+        // The path of a Unix socket doesn't fit in the address
+        None => wasi_try!(crate::net::write_unix_addr(&memory, ro_addr)),
+    }
     Errno::Success
 }
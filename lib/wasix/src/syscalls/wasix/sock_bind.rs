@@ -33,7 +33,7 @@ pub(crate) fn sock_bind_internal(
     addr: SocketAddr,
 ) -> Result<Result<(), Errno>, WasiError> {
     let env = ctx.data();
-    let net = env.net().clone();
+    let net = env.net();
 
     let tasks = ctx.data().tasks().clone();
     wasi_try_ok_ok!(__sock_upgrade(
@@ -0,0 +1,80 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `sock_bind_unix()`
+/// Binds a socket of the `Unix` family to a path, where a file is created
+/// that the other sockets connect through.
+///
+/// Note: This is similar to `bind` in POSIX with a `sockaddr_un`
+///
+/// ## Parameters
+///
+/// * `sock` - File descriptor of the socket to be bound
+/// * `fd` - The directory that `path` is relative to
+/// * `path` - Path of the socket file, which must not exist
+#[instrument(level = "trace", skip_all, fields(%sock, %fd, path = field::Empty), ret)]
+pub fn sock_bind_unix<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    fd: WasiFd,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+) -> Result<Errno, WasiError> {
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+
+    let mut path_string = get_input_str_ok!(&memory, path, path_len);
+    Span::current().record("path", path_string.as_str());
+    if path_string.starts_with("./") {
+        path_string = state.fs.relative_path_to_absolute(path_string);
+    }
+
+    let root_dir = wasi_try_ok!(state.fs.get_fd(fd));
+    if !root_dir.rights.contains(Rights::PATH_CREATE_FILE) {
+        return Ok(Errno::Access);
+    }
+
+    // The socket file is created in its directory like any other file
+    let path = Path::new(&path_string);
+    let Some(name) = path.file_name() else {
+        return Ok(Errno::Inval);
+    };
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_string_lossy(),
+        _ => ".".into(),
+    };
+    let dir = wasi_try_ok!(state.fs.get_inode_at_path(inodes, fd, &parent, true));
+    let socket_path = match dir.read().deref() {
+        Kind::Dir { path, .. } => path.join(name),
+        _ => return Ok(Errno::Notdir),
+    };
+
+    // Like on Linux, a socket isn't bound over an existing file
+    let created = state
+        .fs
+        .root_fs
+        .new_open_options()
+        .write(true)
+        .create_new(true)
+        .open(&socket_path);
+    match created {
+        Ok(_) => state.fs.apply_umask(&socket_path, false),
+        Err(FsError::AlreadyExists) => return Ok(Errno::Addrinuse),
+        Err(err) => return Ok(fs_error_into_wasi_err(err)),
+    }
+
+    let control_plane = env.control_plane.clone();
+    let path = socket_path.clone();
+    let res = __sock_upgrade(
+        &mut ctx,
+        sock,
+        Rights::SOCK_BIND,
+        move |socket, _| async move { socket.bind_unix(control_plane.unix_sockets(), path) },
+    );
+    if let Err(err) = res {
+        ctx.data().state.fs.root_fs.remove_file(&socket_path).ok();
+        return Ok(err);
+    }
+
+    Ok(Errno::Success)
+}
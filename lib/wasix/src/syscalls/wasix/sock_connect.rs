@@ -7,6 +7,10 @@ use crate::syscalls::*;
 /// Polling the socket handle will wait for data to arrive or for
 /// the socket status to change which can be queried via 'sock_status'
 ///
+/// On a non-blocking socket the connection is established in the
+/// background: `Errno::Inprogress` is returned, the socket becomes writable
+/// once it's done and its failure is then read with `Sockoption::LastError`
+///
 /// Note: This is similar to `connect` in POSIX
 ///
 /// ## Parameters
@@ -36,7 +40,7 @@ pub(crate) fn sock_connect_internal(
     addr: SocketAddr,
 ) -> Result<Result<(), Errno>, WasiError> {
     let env = ctx.data();
-    let net = env.net().clone();
+    let net = env.net();
     let tasks = ctx.data().tasks().clone();
     wasi_try_ok_ok!(__sock_upgrade(
         ctx,
@@ -46,7 +50,7 @@ pub(crate) fn sock_connect_internal(
             socket
                 .connect(
                     tasks.deref(),
-                    net.deref(),
+                    &net,
                     addr,
                     None,
                     flags.contains(Fdflags::NONBLOCK),
@@ -0,0 +1,51 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `sock_connect_unix()`
+/// Connects a socket of the `Unix` family to the socket bound to a path.
+///
+/// A stream socket is queued until the listening socket accepts it, a
+/// datagram socket sends to the bound socket from then on.
+///
+/// Note: This is similar to `connect` in POSIX with a `sockaddr_un`
+///
+/// ## Parameters
+///
+/// * `sock` - File descriptor of the socket to connect
+/// * `fd` - The directory that `path` is relative to
+/// * `path` - Path of the file of the socket to connect to
+#[instrument(level = "trace", skip_all, fields(%sock, %fd, path = field::Empty), ret)]
+pub fn sock_connect_unix<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    fd: WasiFd,
+    path: WasmPtr<u8, M>,
+    path_len: M::Offset,
+) -> Result<Errno, WasiError> {
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+
+    let mut path_string = get_input_str_ok!(&memory, path, path_len);
+    Span::current().record("path", path_string.as_str());
+    if path_string.starts_with("./") {
+        path_string = state.fs.relative_path_to_absolute(path_string);
+    }
+
+    // The sockets are found through their file, which is gone once it's
+    // removed even if the socket is still bound
+    let inode = wasi_try_ok!(state.fs.get_inode_at_path(inodes, fd, &path_string, true));
+    let socket_path = match inode.read().deref() {
+        Kind::File { path, .. } => path.clone(),
+        _ => return Ok(Errno::Connrefused),
+    };
+
+    let control_plane = env.control_plane.clone();
+    wasi_try_ok!(__sock_upgrade(
+        &mut ctx,
+        sock,
+        Rights::SOCK_CONNECT,
+        move |socket, _| async move { socket.connect_unix(control_plane.unix_sockets(), socket_path) }
+    ));
+
+    Ok(Errno::Success)
+}
@@ -27,6 +27,8 @@ pub fn sock_get_opt_size<M: MemorySize>(
             Sockoption::MulticastTtlV4 => {
                 socket.multicast_ttl_v4().map(|a| a as Filesize)
             }
+            Sockoption::KeepAliveCount => socket.keepalive_count().map(|a| a as Filesize),
+            Sockoption::LastError => Ok(socket.take_error().map_or(0, |err| err as Filesize)),
             _ => Err(Errno::Inval),
         }
     ));
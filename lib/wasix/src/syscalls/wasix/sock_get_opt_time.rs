@@ -21,6 +21,8 @@ pub fn sock_get_opt_time<M: MemorySize>(
         Sockoption::ConnectTimeout => TimeType::ConnectTimeout,
         Sockoption::AcceptTimeout => TimeType::AcceptTimeout,
         Sockoption::Linger => TimeType::Linger,
+        Sockoption::KeepAliveIdle => TimeType::KeepAliveIdle,
+        Sockoption::KeepAliveInterval => TimeType::KeepAliveInterval,
         _ => return Errno::Inval,
     };
 
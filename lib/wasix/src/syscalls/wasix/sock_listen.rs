@@ -32,13 +32,19 @@ pub(crate) fn sock_listen_internal(
     backlog: usize,
 ) -> Result<Result<(), Errno>, WasiError> {
     let env = ctx.data();
-    let net = env.net().clone();
+    let net = env.net();
     let tasks = ctx.data().tasks().clone();
+    let control_plane = env.control_plane.clone();
     wasi_try_ok_ok!(__sock_upgrade(
         ctx,
         sock,
         Rights::SOCK_LISTEN,
-        |socket, _| async move { socket.listen(tasks.deref(), net.deref(), backlog).await }
+        |socket, _| async move {
+            if socket.is_unix() {
+                return socket.listen_unix(control_plane.unix_sockets(), backlog);
+            }
+            socket.listen(tasks.deref(), net.deref(), backlog).await
+        }
     ));
 
     Ok(Ok(()))
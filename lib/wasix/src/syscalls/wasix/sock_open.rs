@@ -40,6 +40,9 @@ pub fn sock_open<M: MemorySize>(
                 return Ok(Errno::Notsup);
             }
         }
+        SockProto::Icmp | SockProto::Icmpv6 if ty == Socktype::Stream => {
+            return Ok(Errno::Notsup);
+        }
         _ => {}
     }
 
@@ -62,7 +65,33 @@ pub(crate) fn sock_open_internal(
     let env = ctx.data();
     let (_memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
 
+    let icmp = ty == Socktype::Dgram && matches!(pt, SockProto::Icmp | SockProto::Icmpv6);
     let kind = match ty {
+        // Raw sockets and the ICMP sockets `ping` uses reach below the
+        // transport layer, which the environment has to allow
+        _ if icmp || ty == Socktype::Raw => {
+            if !state.raw_sockets {
+                return Ok(Err(match ty {
+                    Socktype::Raw => Errno::Perm,
+                    _ => Errno::Access,
+                }));
+            }
+            let unspecified = match af {
+                Addressfamily::Inet4 => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                Addressfamily::Inet6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+                _ => return Ok(Err(Errno::Notsup)),
+            };
+            let net = env.net();
+            let kind = match icmp {
+                true => {
+                    InlineWaker::block_on(net.bind_icmp(unspecified)).map(InodeSocketKind::Icmp)
+                }
+                false => InlineWaker::block_on(net.bind_raw()).map(InodeSocketKind::Raw),
+            };
+            Kind::Socket {
+                socket: InodeSocket::new(wasi_try_ok_ok!(kind.map_err(net_error_into_wasi_err))),
+            }
+        }
         Socktype::Stream | Socktype::Dgram => Kind::Socket {
             socket: InodeSocket::new(InodeSocketKind::PreSocket {
                 props: SocketProperties {
@@ -88,6 +117,12 @@ pub(crate) fn sock_open_internal(
         },
         _ => return Ok(Err(Errno::Notsup)),
     };
+    // The sockets of the `Unix` family don't reach a network
+    if let (Kind::Socket { socket }, Some(shaper)) = (&kind, env.socket_shaper()) {
+        if af != Addressfamily::Unix {
+            socket.shape(shaper);
+        }
+    }
 
     let inode =
         state
@@ -0,0 +1,97 @@
+use super::*;
+use crate::{
+    net::unix::{UnixChannel, UnixDatagram, UnixSocketState, UnixStream, UNIX_SOCKET_ADDR},
+    syscalls::*,
+};
+
+/// ### `sock_pair()`
+/// Creates two sockets of the `Unix` family which are connected to each
+/// other.
+///
+/// Note: This is similar to `socketpair` in POSIX
+///
+/// ## Parameters
+///
+/// * `af` - Address family, only `Unix` is supported
+/// * `socktype` - Socket type, either datagram or stream
+/// * `sock_proto` - Socket protocol
+///
+/// ## Return
+///
+/// The file descriptors of both sockets.
+#[instrument(level = "trace", skip_all, fields(?af, ?ty, ?pt, sock1 = field::Empty, sock2 = field::Empty), ret)]
+pub fn sock_pair<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    af: Addressfamily,
+    ty: Socktype,
+    pt: SockProto,
+    ro_sock1: WasmPtr<WasiFd, M>,
+    ro_sock2: WasmPtr<WasiFd, M>,
+) -> Result<Errno, WasiError> {
+    if af != Addressfamily::Unix {
+        return Ok(Errno::Afnosupport);
+    }
+    if pt != SockProto::Ip {
+        return Ok(Errno::Protonosupport);
+    }
+
+    let (socket1, socket2) = match ty {
+        Socktype::Stream => {
+            let stream = |socket: UnixStream| {
+                let unix = UnixSocketState {
+                    channel: Some(UnixChannel::Stream(socket.clone())),
+                    ..Default::default()
+                };
+                let kind = InodeSocketKind::TcpStream {
+                    socket: Box::new(socket),
+                    write_timeout: None,
+                    read_timeout: None,
+                };
+                InodeSocket::new_unix(kind, unix)
+            };
+            let (socket1, socket2) = UnixStream::pair();
+            (stream(socket1), stream(socket2))
+        }
+        Socktype::Dgram => {
+            let datagram = |socket: UnixDatagram| {
+                let unix = UnixSocketState {
+                    channel: Some(UnixChannel::Datagram(socket.inbox())),
+                    ..Default::default()
+                };
+                let kind = InodeSocketKind::UdpSocket {
+                    socket: Box::new(socket),
+                    peer: Some(UNIX_SOCKET_ADDR),
+                };
+                InodeSocket::new_unix(kind, unix)
+            };
+            let (socket1, socket2) = UnixDatagram::pair();
+            (datagram(socket1), datagram(socket2))
+        }
+        _ => return Ok(Errno::Notsup),
+    };
+
+    let env = ctx.data();
+    let (memory, state, inodes) = unsafe { env.get_memory_and_wasi_state_and_inodes(&ctx, 0) };
+    let rights = Rights::all_socket();
+    let create_fd = |socket| {
+        let inode = state.fs.create_inode_with_default_stat(
+            inodes,
+            Kind::Socket { socket },
+            false,
+            "socket".to_string().into(),
+        );
+        state
+            .fs
+            .create_fd(rights, rights, Fdflags::empty(), 0, inode)
+    };
+    let sock1 = wasi_try_ok!(create_fd(socket1));
+    let sock2 = wasi_try_ok!(create_fd(socket2));
+    Span::current()
+        .record("sock1", sock1)
+        .record("sock2", sock2);
+
+    wasi_try_mem_ok!(ro_sock1.write(&memory, sock1));
+    wasi_try_mem_ok!(ro_sock2.write(&memory, sock2));
+
+    Ok(Errno::Success)
+}
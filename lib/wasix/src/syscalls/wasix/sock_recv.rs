@@ -1,3 +1,5 @@
+use std::mem::MaybeUninit;
+
 use super::*;
 use crate::{net::socket::TimeType, syscalls::*};
 
@@ -98,49 +100,85 @@ pub(super) fn sock_recv_internal<M: MemorySize>(
     let env = ctx.data();
     let memory = unsafe { env.memory_view(ctx) };
 
-    let _peek = (ri_flags & __WASI_SOCK_RECV_INPUT_PEEK) != 0;
+    let peek = (ri_flags & __WASI_SOCK_RECV_INPUT_PEEK) != 0;
+    let waitall = (ri_flags & __WASI_SOCK_RECV_INPUT_WAITALL) != 0;
 
     let data = wasi_try_ok_ok!(block_on_sock(
         env,
         sock,
         Rights::SOCK_RECV,
         |socket, fd| async move {
+            let nonblocking = fd.flags.contains(Fdflags::NONBLOCK);
+            let timeout = socket
+                .opt_time(TimeType::ReadTimeout)
+                .ok()
+                .flatten()
+                .unwrap_or(Duration::from_secs(30));
+
             let iovs_arr = ri_data
                 .slice(&memory, ri_data_len)
                 .map_err(mem_error_to_wasi)?;
-            let iovs_arr = iovs_arr.access().map_err(mem_error_to_wasi)?;
 
+            // Peeking into each buffer in turn would return the same bytes
+            // every time, so they are peeked at once and then spread out
+            if peek {
+                let mut max_size = 0usize;
+                for iovs in iovs_arr.access().map_err(mem_error_to_wasi)?.iter() {
+                    let buf_len: usize = iovs.buf_len.try_into().map_err(|_| Errno::Overflow)?;
+                    max_size += buf_len;
+                }
+                let mut buf = vec![MaybeUninit::uninit(); max_size];
+                let amt = socket
+                    .recv(
+                        env.tasks().deref(),
+                        &mut buf,
+                        Some(timeout),
+                        nonblocking,
+                        true,
+                    )
+                    .await?;
+                let buf: &[MaybeUninit<u8>] = &buf[..amt];
+                let buf: &[u8] = unsafe { std::mem::transmute(buf) };
+                return copy_from_slice(buf, &memory, iovs_arr);
+            }
+
+            // Waiting for all the bytes only makes sense for streams, as a
+            // datagram is received at once
+            let waitall = waitall && socket.is_stream();
+
+            let iovs_arr = iovs_arr.access().map_err(mem_error_to_wasi)?;
             let mut total_read = 0;
-            for iovs in iovs_arr.iter() {
+            'iovs: for iovs in iovs_arr.iter() {
                 let mut buf = WasmPtr::<u8, M>::new(iovs.buf)
                     .slice(&memory, iovs.buf_len)
                     .map_err(mem_error_to_wasi)?
                     .access()
                     .map_err(mem_error_to_wasi)?;
+                let buf = buf.as_mut_uninit();
 
-                let nonblocking = fd.flags.contains(Fdflags::NONBLOCK);
-                let timeout = socket
-                    .opt_time(TimeType::ReadTimeout)
-                    .ok()
-                    .flatten()
-                    .unwrap_or(Duration::from_secs(30));
-
-                let local_read = match socket
-                    .recv(
-                        env.tasks().deref(),
-                        buf.as_mut_uninit(),
-                        Some(timeout),
-                        nonblocking,
-                    )
-                    .await
-                {
-                    Ok(s) => s,
-                    Err(_) if total_read > 0 => break,
-                    Err(err) => return Err(err),
-                };
-                total_read += local_read;
-                if local_read != buf.len() {
-                    break;
+                let mut local_read = 0;
+                while local_read < buf.len() {
+                    let amt = match socket
+                        .recv(
+                            env.tasks().deref(),
+                            &mut buf[local_read..],
+                            Some(timeout),
+                            nonblocking,
+                            false,
+                        )
+                        .await
+                    {
+                        Ok(s) => s,
+                        // What was already received is returned when the
+                        // rest doesn't arrive in time
+                        Err(_) if total_read > 0 => break 'iovs,
+                        Err(err) => return Err(err),
+                    };
+                    total_read += amt;
+                    local_read += amt;
+                    if amt == 0 || (!waitall && local_read != buf.len()) {
+                        break 'iovs;
+                    }
                 }
             }
             Ok(total_read)
@@ -0,0 +1,122 @@
+use std::mem::MaybeUninit;
+
+use super::*;
+use crate::{net::socket::TimeType, syscalls::*};
+
+/// ### `sock_recv_fds()`
+/// Receive a message from a socket of the `Unix` family along with the file
+/// descriptors sent with it, which are added to the process.
+/// Note: This is similar to `recvmsg` in POSIX with `SCM_RIGHTS` ancillary
+/// data.
+///
+/// ## Parameters
+///
+/// * `ri_data` - List of scatter/gather vectors to which to store data.
+/// * `ri_flags` - Message flags.
+/// * `ri_fds` - Buffer where the received file descriptors are stored, the
+///   ones which don't fit are closed
+///
+/// ## Return
+///
+/// Number of bytes stored in ri_data, number of file descriptors stored in
+/// ri_fds and message flags.
+#[instrument(level = "trace", skip_all, fields(%sock, nread = field::Empty, nfds = field::Empty), ret)]
+pub fn sock_recv_fds<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    ri_data: WasmPtr<__wasi_iovec_t<M>, M>,
+    ri_data_len: M::Offset,
+    ri_flags: RiFlags,
+    ri_fds: WasmPtr<WasiFd, M>,
+    ri_fds_len: M::Offset,
+    ro_data_len: WasmPtr<M::Offset, M>,
+    ro_fds_len: WasmPtr<M::Offset, M>,
+    ro_flags: WasmPtr<RoFlags, M>,
+) -> Result<Errno, WasiError> {
+    wasi_try_ok!(WasiEnv::process_signals_and_exit(&mut ctx)?);
+
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+
+    let iovs = wasi_try_mem_ok!(ri_data.slice(&memory, ri_data_len));
+    let iovs = wasi_try_mem_ok!(iovs.read_to_vec());
+    let max_size = iovs.iter().map(|iov| iov.buf_len.into()).sum::<u64>();
+
+    let peek = (ri_flags & __WASI_SOCK_RECV_INPUT_PEEK) != 0;
+
+    // The data is received at once, along with the file descriptors sent
+    // with it
+    let (data, fds) = wasi_try_ok!(block_on_sock(
+        env,
+        sock,
+        Rights::SOCK_RECV,
+        |socket, fd| async move {
+            let nonblocking = fd.flags.contains(Fdflags::NONBLOCK);
+            let timeout = socket
+                .opt_time(TimeType::ReadTimeout)
+                .ok()
+                .flatten()
+                .unwrap_or(Duration::from_secs(30));
+
+            let mut buf = vec![MaybeUninit::uninit(); max_size as usize];
+            let amt = socket
+                .recv(
+                    env.tasks().deref(),
+                    &mut buf,
+                    Some(timeout),
+                    nonblocking,
+                    peek,
+                )
+                .await?;
+            let data = buf[..amt]
+                .iter()
+                .map(|b| unsafe { b.assume_init() })
+                .collect::<Vec<_>>();
+            // The file descriptors are only received along with the bytes
+            // they were sent with, not when peeking at them
+            let fds = match peek {
+                true => Vec::new(),
+                false => socket.take_received_fds(),
+            };
+            Ok((data, fds))
+        }
+    ));
+
+    let mut remaining = &data[..];
+    for iov in iovs {
+        if remaining.is_empty() {
+            break;
+        }
+        let amt = remaining.len().min(iov.buf_len.into() as usize);
+        let buf = wasi_try_mem_ok!(
+            WasmPtr::<u8, M>::new(iov.buf).slice(&memory, wasi_try_ok!(to_offset::<M>(amt)))
+        );
+        wasi_try_mem_ok!(buf.write_slice(&remaining[..amt]));
+        remaining = &remaining[amt..];
+    }
+
+    // Like on Linux, the file descriptors which don't fit are closed
+    let mut flags = 0;
+    let max_fds = ri_fds_len.into() as usize;
+    if fds.len() > max_fds {
+        flags |= __WASI_SOCK_RECV_OUTPUT_CONTROL_TRUNCATED;
+    }
+    let fds = fds
+        .into_iter()
+        .take(max_fds)
+        .map(|fd| env.state.fs.receive_fd(fd))
+        .collect::<Vec<_>>();
+    let out_fds = wasi_try_mem_ok!(ri_fds.slice(&memory, wasi_try_ok!(to_offset::<M>(fds.len()))));
+    wasi_try_mem_ok!(out_fds.write_slice(&fds));
+
+    Span::current().record("nread", data.len());
+    Span::current().record("nfds", fds.len());
+
+    let data_len: M::Offset = wasi_try_ok!(to_offset::<M>(data.len()));
+    let fds_len: M::Offset = wasi_try_ok!(to_offset::<M>(fds.len()));
+    wasi_try_mem_ok!(ro_data_len.write(&memory, data_len));
+    wasi_try_mem_ok!(ro_fds_len.write(&memory, fds_len));
+    wasi_try_mem_ok!(ro_flags.write(&memory, flags));
+
+    Ok(Errno::Success)
+}
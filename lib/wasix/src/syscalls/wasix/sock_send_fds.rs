@@ -0,0 +1,84 @@
+use std::borrow::Cow;
+
+use super::*;
+use crate::syscalls::*;
+
+/// ### `sock_send_fds()`
+/// Send a message on a socket of the `Unix` family along with file
+/// descriptors, which are duplicated into the process receiving them.
+/// Note: This is similar to `sendmsg` in POSIX with `SCM_RIGHTS` ancillary
+/// data.
+///
+/// ## Parameters
+///
+/// * `si_data` - List of scatter/gather vectors to which to retrieve data
+/// * `si_flags` - Message flags.
+/// * `fds` - The file descriptors to send
+///
+/// ## Return
+///
+/// Number of bytes transmitted.
+#[instrument(level = "trace", skip_all, fields(%sock, nsent = field::Empty), ret)]
+pub fn sock_send_fds<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    si_data: WasmPtr<__wasi_ciovec_t<M>, M>,
+    si_data_len: M::Offset,
+    si_flags: SiFlags,
+    fds: WasmPtr<WasiFd, M>,
+    fds_len: M::Offset,
+    ret_data_len: WasmPtr<M::Offset, M>,
+) -> Result<Errno, WasiError> {
+    wasi_try_ok!(WasiEnv::process_signals_and_exit(&mut ctx)?);
+
+    let (data, fds) = {
+        let env = ctx.data();
+        let memory = unsafe { env.memory_view(&ctx) };
+
+        let fds = wasi_try_mem_ok!(fds.slice(&memory, fds_len));
+        let fds = wasi_try_mem_ok!(fds.read_to_vec());
+        let fds = wasi_try_ok!(fds
+            .into_iter()
+            .map(|fd| env.state.fs.get_fd(fd))
+            .collect::<Result<Vec<_>, _>>());
+
+        // The data is sent at once so the file descriptors go along with
+        // all of it
+        let iovs = wasi_try_mem_ok!(si_data.slice(&memory, si_data_len));
+        let mut data = Vec::new();
+        for iov in wasi_try_mem_ok!(iovs.read_to_vec()) {
+            let buf = wasi_try_mem_ok!(WasmPtr::<u8, M>::new(iov.buf).slice(&memory, iov.buf_len));
+            data.extend(wasi_try_mem_ok!(buf.read_to_vec()));
+        }
+        (data, fds)
+    };
+
+    wasi_try_ok!(__sock_actor(
+        &mut ctx,
+        sock,
+        Rights::SOCK_SEND,
+        |socket, _| socket.attach_fds(fds)
+    ));
+    let res = sock_send_internal::<M>(
+        &ctx,
+        sock,
+        FdWriteSource::Buffer(Cow::Owned(data)),
+        si_flags,
+    )?;
+    // The file descriptors are dropped when nothing was sent
+    __sock_actor(&mut ctx, sock, Rights::SOCK_SEND, |socket, _| {
+        socket.attach_fds(Vec::new())
+    })
+    .ok();
+    let bytes_written = wasi_try_ok!(res);
+
+    Span::current().record("nsent", bytes_written);
+
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    let bytes_written: M::Offset =
+        wasi_try_ok!(bytes_written.try_into().map_err(|_| Errno::Overflow));
+    wasi_try_mem_ok!(ret_data_len.write(&memory, bytes_written));
+
+    Ok(Errno::Success)
+}
@@ -144,6 +144,7 @@ pub(crate) fn sock_send_file_internal(
                                                 &mut buf,
                                                 Some(read_timeout),
                                                 false,
+                                                false,
                                             )
                                             .await
                                             .map(|amt| {
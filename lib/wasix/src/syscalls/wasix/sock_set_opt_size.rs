@@ -37,6 +37,7 @@ pub(crate) fn sock_set_opt_size_internal(
             Sockoption::SendBufSize => socket.set_send_buf_size(size as usize),
             Sockoption::Ttl => socket.set_ttl(size as u32),
             Sockoption::MulticastTtlV4 => socket.set_multicast_ttl_v4(size as u32),
+            Sockoption::KeepAliveCount => socket.set_keepalive_count(size as u32),
             _ => Err(Errno::Inval),
         }
     ));
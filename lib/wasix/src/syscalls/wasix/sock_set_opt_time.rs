@@ -31,6 +31,8 @@ pub fn sock_set_opt_time<M: MemorySize>(
         Sockoption::ConnectTimeout => TimeType::ConnectTimeout,
         Sockoption::AcceptTimeout => TimeType::AcceptTimeout,
         Sockoption::Linger => TimeType::Linger,
+        Sockoption::KeepAliveIdle => TimeType::KeepAliveIdle,
+        Sockoption::KeepAliveInterval => TimeType::KeepAliveInterval,
         _ => return Ok(Errno::Inval),
     };
 
@@ -0,0 +1,87 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `sock_tls()`
+/// Encrypts the stream of a connected socket with TLS, which the runtime
+/// terminates: the program sends and receives the plaintext, while the peer
+/// gets it encrypted. The handshake happens as the socket is first used, a
+/// failure of it fails the sends and receives with `ECONNABORTED`.
+///
+/// ## Parameters
+///
+/// * `fd` - Socket connected to the peer
+/// * `server_name` - Name, or IP address, the certificate of the peer is checked against
+/// * `security` - Level of encryption of the stream, which is left as it is when unencrypted
+#[instrument(level = "trace", skip_all, fields(%sock, server_name = field::Empty, ?security), ret)]
+pub fn sock_tls<M: MemorySize>(
+    mut ctx: FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    server_name: WasmPtr<u8, M>,
+    server_name_len: M::Offset,
+    security: Streamsecurity,
+) -> Result<Errno, WasiError> {
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+
+    let server_name = get_input_str_ok!(&memory, server_name, server_name_len);
+    Span::current().record("server_name", server_name.as_str());
+
+    let security = match security {
+        Streamsecurity::Unencrypted => StreamSecurity::Unencrypted,
+        Streamsecurity::AnyEncryption => StreamSecurity::AnyEncyption,
+        Streamsecurity::ClassicEncryption => StreamSecurity::ClassicEncryption,
+        Streamsecurity::DoubleEncryption => StreamSecurity::DoubleEncryption,
+        _ => return Ok(Errno::Inval),
+    };
+
+    wasi_try_ok!(sock_tls_internal(
+        &mut ctx,
+        sock,
+        server_name.as_str(),
+        security
+    )?);
+
+    Ok(Errno::Success)
+}
+
+#[cfg(feature = "tls")]
+pub(crate) fn sock_tls_internal(
+    ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+    sock: WasiFd,
+    server_name: &str,
+    security: StreamSecurity,
+) -> Result<Result<(), Errno>, WasiError> {
+    let env = ctx.data();
+
+    let tls = env
+        .state
+        .tls
+        .clone()
+        .unwrap_or_else(crate::net::TlsConfig::webpki);
+    let session = wasi_try_ok_ok!(tls
+        .session(server_name, security)
+        .map_err(net_error_into_wasi_err));
+
+    wasi_try_ok_ok!(__sock_actor_mut(
+        ctx,
+        sock,
+        Rights::SOCK_SEND | Rights::SOCK_RECV,
+        |mut socket, _| socket.secure(session)
+    ));
+
+    Ok(Ok(()))
+}
+
+/// The runtime was built without TLS, so no stream is encrypted.
+#[cfg(not(feature = "tls"))]
+pub(crate) fn sock_tls_internal(
+    _ctx: &mut FunctionEnvMut<'_, WasiEnv>,
+    _sock: WasiFd,
+    _server_name: &str,
+    security: StreamSecurity,
+) -> Result<Result<(), Errno>, WasiError> {
+    match security {
+        StreamSecurity::Unencrypted => Ok(Ok(())),
+        _ => Ok(Err(Errno::Notsup)),
+    }
+}
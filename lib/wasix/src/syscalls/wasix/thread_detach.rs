@@ -0,0 +1,26 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `thread_detach()`
+/// Detaches a thread of the current process, it can then no longer be
+/// joined and its exit code is dropped as soon as it exits
+///
+/// ## Parameters
+///
+/// * `tid` - Handle of the thread to detach
+///
+/// Returns EINVAL if the thread was already detached
+#[instrument(level = "trace", skip_all, fields(%tid), ret)]
+pub fn thread_detach(ctx: FunctionEnvMut<'_, WasiEnv>, tid: Tid) -> Errno {
+    let env = ctx.data();
+    let tid: WasiThreadId = tid.into();
+    match env.process.get_thread(&tid) {
+        Some(thread) if thread.detach() => Errno::Success,
+        Some(_) => Errno::Inval,
+        // Threads which already exited are reaped
+        None => match env.process.take_exited_thread(&tid) {
+            Some(_) => Errno::Success,
+            None => Errno::Srch,
+        },
+    }
+}
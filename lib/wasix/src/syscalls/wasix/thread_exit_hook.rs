@@ -0,0 +1,27 @@
+use super::*;
+use crate::{os::task::thread::ThreadExitHook, syscalls::*};
+
+/// ### `thread_exit_hook()`
+/// Registers a function to be called with `arg` when the current thread
+/// exits, such as the destructor of a thread-specific value. The hooks are
+/// called the last registered first, and the hooks they register are
+/// called as well for a few more rounds.
+///
+/// The module must export its function table as `__indirect_function_table`
+///
+/// ## Parameters
+///
+/// * `func` - Index of the function in the function table, it takes a pointer sized argument
+/// * `arg` - Argument the function is called with
+#[instrument(level = "trace", skip_all, fields(%func, %arg), ret)]
+pub fn thread_exit_hook<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    func: u32,
+    arg: M::Offset,
+) -> Errno {
+    ctx.data().thread.add_exit_hook(ThreadExitHook {
+        func,
+        arg: arg.into(),
+    });
+    Errno::Success
+}
@@ -0,0 +1,38 @@
+use super::*;
+use crate::syscalls::*;
+
+/// ### `thread_get_name()`
+/// Returns the name of a thread of the current process, which is empty if
+/// the thread was never named
+/// If the name exceeds the size of the buffer then this function
+/// will return ERANGE
+///
+/// ## Parameters
+///
+/// * `tid` - Thread whose name is returned
+/// * `name` - Buffer the name is written to
+/// * `name_len` - Size of the buffer, it is set to the length of the name
+#[instrument(level = "trace", skip_all, fields(%tid, name = field::Empty), ret)]
+pub fn thread_get_name<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    tid: Tid,
+    name: WasmPtr<u8, M>,
+    name_len: WasmPtr<M::Offset, M>,
+) -> Errno {
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+
+    let thread = wasi_try!(env.process.get_thread(&tid.into()).ok_or(Errno::Srch));
+    let thread_name = thread.name().unwrap_or_default();
+    Span::current().record("name", thread_name.as_str());
+
+    let max_name_len: u64 = wasi_try_mem!(name_len.read(&memory)).into();
+    wasi_try_mem!(name_len.write(&memory, wasi_try!(to_offset::<M>(thread_name.len()))));
+    if thread_name.len() as u64 > max_name_len {
+        return Errno::Range;
+    }
+
+    let name = wasi_try_mem!(name.slice(&memory, wasi_try!(to_offset::<M>(thread_name.len()))));
+    wasi_try_mem!(name.write_slice(thread_name.as_bytes()));
+    Errno::Success
+}
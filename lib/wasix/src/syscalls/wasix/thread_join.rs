@@ -7,6 +7,8 @@ use crate::syscalls::*;
 /// ## Parameters
 ///
 /// * `tid` - Handle of the thread to wait on
+///
+/// Returns EINVAL if the thread was detached
 //#[instrument(level = "trace", skip_all, fields(%join_tid), ret)]
 pub fn thread_join<M: MemorySize + 'static>(
     ctx: FunctionEnvMut<'_, WasiEnv>,
@@ -25,6 +27,9 @@ pub(super) fn thread_join_internal<M: MemorySize + 'static>(
     let tid: WasiThreadId = join_tid.into();
     let other_thread = env.process.get_thread(&tid);
     if let Some(other_thread) = other_thread {
+        if other_thread.is_detached() {
+            return Ok(Errno::Inval);
+        }
         let exit_code = block_on(async move {
             other_thread
                 .join()
@@ -36,6 +41,8 @@ pub(super) fn thread_join_internal<M: MemorySize + 'static>(
                 .unwrap_or_else(|a| a)
         });
         Ok(exit_code.into())
+    } else if let Some(exit_code) = env.process.take_exited_thread(&tid) {
+        Ok(exit_code.into())
     } else {
         Ok(Errno::Success)
     }
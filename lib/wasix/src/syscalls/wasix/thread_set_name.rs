@@ -0,0 +1,36 @@
+use super::*;
+use crate::syscalls::*;
+
+/// Longest name a thread can be given, in bytes (as `TASK_COMM_LEN` on
+/// Linux, without the nul terminator)
+pub const THREAD_NAME_MAX: usize = 15;
+
+/// ### `thread_set_name()`
+/// Names a thread of the current process, the name shows up in the traces
+/// and in the process listing of the control plane
+///
+/// ## Parameters
+///
+/// * `tid` - Thread to be named
+/// * `name` - Name of the thread, at most `THREAD_NAME_MAX` bytes
+///
+/// If the name is too long then this function will return ERANGE
+#[instrument(level = "trace", skip_all, fields(%tid, name = field::Empty), ret)]
+pub fn thread_set_name<M: MemorySize>(
+    ctx: FunctionEnvMut<'_, WasiEnv>,
+    tid: Tid,
+    name: WasmPtr<u8, M>,
+    name_len: M::Offset,
+) -> Errno {
+    let env = ctx.data();
+    let memory = unsafe { env.memory_view(&ctx) };
+    let name = wasi_try_mem!(name.read_utf8_string(&memory, name_len));
+    Span::current().record("name", name.as_str());
+    if name.len() > THREAD_NAME_MAX {
+        return Errno::Range;
+    }
+
+    let thread = wasi_try!(env.process.get_thread(&tid.into()).ok_or(Errno::Srch));
+    thread.set_name(name);
+    Errno::Success
+}
@@ -11,6 +11,7 @@ use crate::{
 
 use future::FutureExt;
 use tokio::sync::oneshot;
+use tracing::Instrument;
 use wasmer_wasix_types::wasi::{ThreadActions, ThreadStart};
 
 /// ### `thread_spawn()`
@@ -118,12 +119,15 @@ pub fn thread_spawn_internal_using_layout<M: MemorySize>(
 
     // Now spawn a thread
     trace!("threading: spawning background thread");
+    // The thread runs in its own span, which records its name once it is set
+    let span = thread_env.thread.span();
     let run = move |props: TaskWasmRunProperties| {
         async move {
             if let Err(err) = execute_module(props.ctx, props.store).await {
                 tracing::warn!("Starting thread failed: {err}");
             }
         }
+        .instrument(span)
         .boxed_local()
     };
     tasks
@@ -194,9 +198,11 @@ async fn call_module<M: MemorySize>(
     }
 
     let mut ret = Errno::Success;
+    let mut exit_code = None;
     if let Err(err) = call_ret {
         match err.downcast::<WasiError>() {
             Ok(WasiError::Exit(code)) => {
+                exit_code = Some(code);
                 ret = if code.is_success() {
                     Errno::Success
                 } else {
@@ -215,6 +221,7 @@ async fn call_module<M: MemorySize>(
             }
             Err(err) => {
                 debug!("failed with runtime error: {}", err);
+                env.data(&store).write_coredump(&store, &err);
                 env.data(&store)
                     .runtime
                     .on_taint(TaintReason::RuntimeError(err));
@@ -227,7 +234,11 @@ async fn call_module<M: MemorySize>(
     // Clean up the environment
     env.on_exit(&mut store, Some(ret.into()));
 
-    // Frees the handle so that it closes
+    // Records the exit code for the thread joining this one, then frees the
+    // handle so that it closes
+    thread_handle
+        .as_thread()
+        .set_status_finished(Ok(exit_code.unwrap_or_else(|| ret.into())));
     drop(thread_handle);
     Ok(ret as Pid)
 }
@@ -1,4 +1,5 @@
 use chrono::prelude::*;
+use utils::GlobalScope;
 use wasmer::WasmRef;
 
 use crate::syscalls::types::wasi::{Errno, Snapshot0Clockid, Timestamp};
@@ -8,19 +9,28 @@ pub fn platform_clock_res_get(
     _resolution: WasmRef<Timestamp>,
 ) -> Result<i64, Errno> {
     let t_out = match clock_id {
-        Snapshot0Clockid::Monotonic => 10_000_000,
-        Snapshot0Clockid::Realtime => 1,
-        Snapshot0Clockid::ProcessCputimeId => 1,
-        Snapshot0Clockid::ThreadCputimeId => 1,
+        // The wall clock comes from `Date.now()`, which has a resolution of
+        // one millisecond
+        Snapshot0Clockid::Realtime => 1_000_000,
+        Snapshot0Clockid::Monotonic
+        | Snapshot0Clockid::ProcessCputimeId
+        | Snapshot0Clockid::ThreadCputimeId => {
+            GlobalScope::current().timer_resolution().as_nanos() as i64
+        }
         _ => return Err(Errno::Inval),
     };
     Ok(t_out)
 }
 
 pub fn platform_clock_time_get(
-    _clock_id: Snapshot0Clockid,
+    clock_id: Snapshot0Clockid,
     _precision: Timestamp,
 ) -> Result<i64, Errno> {
+    // The monotonic clock uses the high resolution timer of the browser
+    if clock_id == Snapshot0Clockid::Monotonic {
+        return Ok((GlobalScope::current().now() * 1_000_000.) as i64);
+    }
+
     Local::now()
         .timestamp_nanos_opt()
         .map(|ts| ts as i64)
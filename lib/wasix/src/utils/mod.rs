@@ -11,6 +11,7 @@ pub(crate) use owned_mutex_guard::{
 
 use std::collections::BTreeSet;
 
+use virtual_fs::FsError;
 use wasmer::Module;
 use wasmer_wasix_types::wasi::Errno;
 
@@ -32,7 +33,12 @@ pub fn is_wasix_module(module: &Module) -> bool {
 }
 
 pub fn map_io_err(err: std::io::Error) -> Errno {
-    From::<std::io::Error>::from(err)
+    // The file systems report running out of space with an `FsError`
+    // carried by the `io::Error`
+    match err.get_ref().and_then(|err| err.downcast_ref::<FsError>()) {
+        Some(err) => crate::fs::fs_error_into_wasi_err(*err),
+        None => From::<std::io::Error>::from(err),
+    }
 }
 
 /// The version of WASI. This is determined by the imports namespace
@@ -0,0 +1,363 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    path::Path,
+};
+
+use async_trait::async_trait;
+use js_sys::{Function, Promise, Reflect, Uint8Array};
+use serde::{Deserialize, Serialize};
+use virtual_fs::{DirEntry, FileType, FsError, Metadata, OpenOptionsConfig, Result};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+use super::FsCallbacks;
+
+/// The [`FsCallbacks`] of a `DirectoryCallbacks` object, whose optional
+/// methods are looked up when they're called.
+#[derive(Debug)]
+pub(crate) struct JsCallbacks {
+    callbacks: js_sys::Object,
+    /// The values returned by `open`, or the paths of the files when there
+    /// is no `open` callback, by file number
+    files: RefCell<HashMap<u64, JsValue>>,
+    next_fh: Cell<u64>,
+}
+
+impl JsCallbacks {
+    pub fn new(callbacks: js_sys::Object) -> Self {
+        JsCallbacks {
+            callbacks,
+            files: RefCell::new(HashMap::new()),
+            next_fh: Cell::new(1),
+        }
+    }
+
+    fn callback(&self, name: &str) -> Option<Function> {
+        Reflect::get(&self.callbacks, &JsValue::from_str(name))
+            .ok()?
+            .dyn_into()
+            .ok()
+    }
+
+    /// Calls the callback `name`, awaiting the promise it returns if any, or
+    /// returns `None` if it isn't defined.
+    async fn call(&self, name: &str, args: &[JsValue]) -> Option<Result<JsValue>> {
+        let callback = self.callback(name)?;
+        let args: js_sys::Array = args.iter().collect();
+        let result = match callback.apply(&self.callbacks, &args) {
+            Ok(value) => JsFuture::from(Promise::resolve(&value))
+                .await
+                .map_err(fs_error),
+            Err(err) => Err(fs_error(err)),
+        };
+        Some(result)
+    }
+
+    /// Calls a callback modifying the file system, which is read-only without
+    /// it.
+    async fn call_mut(&self, name: &str, args: &[JsValue]) -> Result<JsValue> {
+        self.call(name, args)
+            .await
+            .unwrap_or(Err(FsError::PermissionDenied))
+    }
+
+    /// Calls a callback which must be defined.
+    async fn call_required(&self, name: &str, args: &[JsValue]) -> Result<JsValue> {
+        self.call(name, args).await.unwrap_or_else(|| {
+            tracing::warn!(callback = name, "Missing directory callback");
+            Err(FsError::Unsupported)
+        })
+    }
+
+    fn file(&self, fh: u64) -> Result<JsValue> {
+        self.files
+            .borrow()
+            .get(&fh)
+            .cloned()
+            .ok_or(FsError::InvalidFd)
+    }
+}
+
+#[async_trait(?Send)]
+impl FsCallbacks for JsCallbacks {
+    async fn metadata(&self, path: &Path) -> Result<Metadata> {
+        let value = self.call_required("metadata", &[js_path(path)?]).await?;
+        if value.is_null() || value.is_undefined() {
+            return Err(FsError::EntryNotFound);
+        }
+        let metadata: JsMetadata = from_value(value)?;
+        Ok(metadata.into())
+    }
+
+    async fn read_dir(&self, path: &Path) -> Result<Vec<DirEntry>> {
+        let value = self.call_required("readDir", &[js_path(path)?]).await?;
+        let entries: Vec<JsDirEntry> = from_value(value)?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| DirEntry {
+                path: path.join(&entry.name),
+                metadata: Ok(entry.metadata().into()),
+            })
+            .collect())
+    }
+
+    async fn open(&self, path: &Path, conf: &OpenOptionsConfig) -> Result<u64> {
+        if conf.would_mutate() && self.callback("write").is_none() {
+            return Err(FsError::PermissionDenied);
+        }
+
+        let options = serde_wasm_bindgen::to_value(&JsOpenOptions::from(conf))
+            .map_err(|_| FsError::InvalidInput)?;
+        let file = match self.call("open", &[js_path(path)?, options]).await {
+            Some(file) => file?,
+            // Without `open`, only the existing files can be opened, and
+            // they're identified by their path
+            None => {
+                match self.metadata(path).await {
+                    Ok(metadata) if metadata.is_dir() => return Err(FsError::NotAFile),
+                    Ok(_) if conf.create_new => return Err(FsError::AlreadyExists),
+                    Ok(_) => {}
+                    Err(FsError::EntryNotFound) if conf.create || conf.create_new => {
+                        return Err(FsError::PermissionDenied)
+                    }
+                    Err(err) => return Err(err),
+                }
+                let file = js_path(path)?;
+                if conf.write && conf.truncate {
+                    self.call_mut("truncate", &[file.clone(), JsValue::from(0)])
+                        .await?;
+                }
+                file
+            }
+        };
+
+        let fh = self.next_fh.get();
+        self.next_fh.set(fh + 1);
+        self.files.borrow_mut().insert(fh, file);
+        Ok(fh)
+    }
+
+    async fn read(&self, fh: u64, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let args = [self.file(fh)?, JsValue::from(offset as f64), JsValue::from(len)];
+        let value = self.call_required("read", &args).await?;
+        let mut data = match value.as_string() {
+            Some(text) => text.into_bytes(),
+            None if value.is_instance_of::<Uint8Array>()
+                || value.is_instance_of::<js_sys::ArrayBuffer>() =>
+            {
+                Uint8Array::new(&value).to_vec()
+            }
+            None => {
+                tracing::warn!(?value, "The read callback returned no bytes");
+                return Err(FsError::InvalidData);
+            }
+        };
+        data.truncate(len);
+        Ok(data)
+    }
+
+    async fn write(&self, fh: u64, offset: u64, data: &[u8]) -> Result<usize> {
+        let args = [
+            self.file(fh)?,
+            JsValue::from(offset as f64),
+            Uint8Array::from(data).into(),
+        ];
+        let written = self.call_mut("write", &args).await?;
+        // Everything is written unless the callback says otherwise
+        Ok(written.as_f64().map_or(data.len(), |written| written as usize))
+    }
+
+    async fn set_len(&self, fh: u64, len: u64) -> Result<()> {
+        let args = [self.file(fh)?, JsValue::from(len as f64)];
+        self.call_mut("truncate", &args).await?;
+        Ok(())
+    }
+
+    async fn flush(&self, fh: u64) -> Result<()> {
+        let file = self.file(fh)?;
+        self.call("flush", &[file]).await.transpose()?;
+        Ok(())
+    }
+
+    async fn close(&self, fh: u64) {
+        let Some(file) = self.files.borrow_mut().remove(&fh) else {
+            return;
+        };
+        if let Some(Err(err)) = self.call("close", &[file]).await {
+            tracing::debug!(fh, %err, "Unable to close a file");
+        }
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        self.call_mut("createDir", &[js_path(path)?]).await?;
+        Ok(())
+    }
+
+    async fn remove_dir(&self, path: &Path) -> Result<()> {
+        self.call_mut("removeDir", &[js_path(path)?]).await?;
+        Ok(())
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        self.call_mut("removeFile", &[js_path(path)?]).await?;
+        Ok(())
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.call_mut("rename", &[js_path(from)?, js_path(to)?])
+            .await?;
+        Ok(())
+    }
+}
+
+/// The `EntryMetadata` returned by the callbacks.
+#[derive(Debug, Clone, Deserialize)]
+struct JsMetadata {
+    #[serde(rename = "type")]
+    ty: JsEntryType,
+    #[serde(default)]
+    size: u64,
+    /// In milliseconds since the epoch, like `Date.now()`
+    modified: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum JsEntryType {
+    File,
+    Dir,
+}
+
+impl From<JsMetadata> for Metadata {
+    fn from(metadata: JsMetadata) -> Self {
+        let modified = metadata
+            .modified
+            .map_or(0, |modified| (modified * 1_000_000.) as u64);
+        Metadata {
+            ft: match metadata.ty {
+                JsEntryType::File => FileType::new_file(),
+                JsEntryType::Dir => FileType::new_dir(),
+            },
+            accessed: modified,
+            created: modified,
+            modified,
+            len: metadata.size,
+        }
+    }
+}
+
+/// An entry returned by the `readDir` callback, its `EntryMetadata` along
+/// with its name.
+#[derive(Debug, Clone, Deserialize)]
+struct JsDirEntry {
+    name: String,
+    #[serde(rename = "type")]
+    ty: JsEntryType,
+    #[serde(default)]
+    size: u64,
+    modified: Option<f64>,
+}
+
+impl JsDirEntry {
+    fn metadata(&self) -> JsMetadata {
+        JsMetadata {
+            ty: self.ty,
+            size: self.size,
+            modified: self.modified,
+        }
+    }
+}
+
+/// The `OpenFileOptions` passed to the `open` callback.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsOpenOptions {
+    read: bool,
+    write: bool,
+    create: bool,
+    create_new: bool,
+    truncate: bool,
+    append: bool,
+}
+
+impl From<&OpenOptionsConfig> for JsOpenOptions {
+    fn from(conf: &OpenOptionsConfig) -> Self {
+        JsOpenOptions {
+            read: conf.read,
+            write: conf.write,
+            create: conf.create,
+            create_new: conf.create_new,
+            truncate: conf.truncate,
+            append: conf.append,
+        }
+    }
+}
+
+fn js_path(path: &Path) -> Result<JsValue> {
+    let path = path.to_str().ok_or(FsError::InvalidInput)?;
+    Ok(JsValue::from_str(path))
+}
+
+fn from_value<T: serde::de::DeserializeOwned>(value: JsValue) -> Result<T> {
+    serde_wasm_bindgen::from_value(value).map_err(|err| {
+        tracing::warn!(%err, "Invalid value returned by a directory callback");
+        FsError::InvalidData
+    })
+}
+
+/// Converts the errors thrown by the callbacks, whose `code` can be the name
+/// of a POSIX error like the errors of Node.js, e.g. `"ENOENT"`.
+fn fs_error(err: JsValue) -> FsError {
+    let code = Reflect::get(&err, &JsValue::from_str("code"))
+        .ok()
+        .and_then(|code| code.as_string());
+
+    match code.as_deref() {
+        Some("ENOENT") => FsError::EntryNotFound,
+        Some("EEXIST") => FsError::AlreadyExists,
+        Some("ENOTDIR") => FsError::BaseNotDirectory,
+        Some("EISDIR") => FsError::NotAFile,
+        Some("ENOTEMPTY") => FsError::DirectoryNotEmpty,
+        Some("EACCES" | "EPERM" | "EROFS") => FsError::PermissionDenied,
+        Some("EINVAL") => FsError::InvalidInput,
+        Some("EBADF") => FsError::InvalidFd,
+        Some("ENOSPC") => FsError::StorageFull,
+        Some("EAGAIN") => FsError::WouldBlock,
+        _ => {
+            tracing::warn!(error = &*utils::js_error(err), "A directory callback failed");
+            FsError::IOError
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+
+    #[wasm_bindgen_test]
+    fn errors_are_converted_by_their_code() {
+        let err = js_sys::Error::new("missing");
+        Reflect::set(&err, &"code".into(), &"ENOENT".into()).unwrap();
+        assert_eq!(fs_error(err.into()), FsError::EntryNotFound);
+
+        assert_eq!(fs_error(JsValue::from_str("oops")), FsError::IOError);
+    }
+
+    #[wasm_bindgen_test]
+    fn dir_entries_are_deserialized() {
+        let entries = js_sys::JSON::parse(
+            r#"[{"name": "a.txt", "type": "file", "size": 3, "modified": 2}, {"name": "b", "type": "dir"}]"#,
+        )
+        .unwrap();
+
+        let entries: Vec<JsDirEntry> = from_value(entries).unwrap();
+
+        let a = Metadata::from(entries[0].metadata());
+        assert!(a.is_file());
+        assert_eq!((a.len, a.modified), (3, 2_000_000));
+        assert!(Metadata::from(entries[1].metadata()).is_dir());
+    }
+}
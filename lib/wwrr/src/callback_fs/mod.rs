@@ -0,0 +1,475 @@
+//! A file system whose operations are implemented by the embedder.
+//!
+//! # Design
+//!
+//! JavaScript callbacks can only be called on the thread they were created
+//! on, usually the main thread, while instances use their file systems from
+//! web workers.
+//!
+//! The [`FsCallbacks`] are therefore driven by a task spawned on the thread
+//! which created the [`CallbackFileSystem`], while the file system and its
+//! files are cheap handles which send it [`CallbackFsMsg`]s and block until
+//! it replies, like the [`crate::opfs::OpfsFileSystem`] does with its worker.
+//! Blocking the thread driving the callbacks would deadlock it, so the calls
+//! from that thread fail with [`FsError::WouldBlock`].
+
+mod js;
+
+use std::{
+    io::{self, SeekFrom},
+    path::{Path, PathBuf},
+    pin::Pin,
+    rc::Rc,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{Context, Poll},
+};
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use tokio::{
+    io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf},
+    sync::{mpsc, oneshot},
+};
+use virtual_fs::{
+    DirEntry, FileOpener, FileSystem, FsError, Metadata, OpenOptions, OpenOptionsConfig, ReadDir,
+    Result, VirtualFile,
+};
+use wasmer_wasix::runtime::task_manager::InlineWaker;
+
+pub(crate) use self::js::JsCallbacks;
+
+/// The operations of a [`CallbackFileSystem`], implemented by the embedder.
+///
+/// They're called on the thread which created the file system, possibly
+/// concurrently. The opened files are identified by the number returned by
+/// [`FsCallbacks::open`], and the operations modifying the file system fail
+/// with [`FsError::PermissionDenied`] unless they're implemented.
+#[async_trait(?Send)]
+pub trait FsCallbacks: std::fmt::Debug {
+    async fn metadata(&self, path: &Path) -> Result<Metadata>;
+
+    async fn read_dir(&self, path: &Path) -> Result<Vec<DirEntry>>;
+
+    /// Opens a file, returning the number identifying it until it's closed.
+    async fn open(&self, path: &Path, conf: &OpenOptionsConfig) -> Result<u64>;
+
+    async fn read(&self, fh: u64, offset: u64, len: usize) -> Result<Vec<u8>>;
+
+    /// Writes to a file at `offset`, returning the number of bytes written.
+    async fn write(&self, _fh: u64, _offset: u64, _data: &[u8]) -> Result<usize> {
+        Err(FsError::PermissionDenied)
+    }
+
+    async fn set_len(&self, _fh: u64, _len: u64) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    async fn flush(&self, _fh: u64) -> Result<()> {
+        Ok(())
+    }
+
+    /// Closes a file, when its last handle is dropped.
+    async fn close(&self, _fh: u64) {}
+
+    async fn create_dir(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    async fn remove_dir(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    async fn remove_file(&self, _path: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    async fn rename(&self, _from: &Path, _to: &Path) -> Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+}
+
+/// The channel a [`CallbackFsMsg`] is replied through.
+type Reply<T> = oneshot::Sender<Result<T>>;
+
+/// Messages sent from the [`CallbackFileSystem`] handles to the task driving
+/// the [`FsCallbacks`].
+#[derive(Debug)]
+pub(crate) enum CallbackFsMsg {
+    Metadata {
+        path: PathBuf,
+        reply: Reply<Metadata>,
+    },
+    ReadDir {
+        path: PathBuf,
+        reply: Reply<Vec<DirEntry>>,
+    },
+    CreateDir {
+        path: PathBuf,
+        reply: Reply<()>,
+    },
+    RemoveDir {
+        path: PathBuf,
+        reply: Reply<()>,
+    },
+    RemoveFile {
+        path: PathBuf,
+        reply: Reply<()>,
+    },
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+        reply: Reply<()>,
+    },
+    Open {
+        path: PathBuf,
+        conf: OpenOptionsConfig,
+        reply: Reply<u64>,
+    },
+    Read {
+        fh: u64,
+        offset: u64,
+        len: usize,
+        reply: Reply<Vec<u8>>,
+    },
+    Write {
+        fh: u64,
+        offset: u64,
+        data: Vec<u8>,
+        reply: Reply<usize>,
+    },
+    SetLen {
+        fh: u64,
+        len: u64,
+        reply: Reply<()>,
+    },
+    Flush {
+        fh: u64,
+        reply: Reply<()>,
+    },
+    Close {
+        fh: u64,
+    },
+}
+
+/// A [`FileSystem`] whose operations are implemented by [`FsCallbacks`].
+#[derive(Debug, Clone)]
+pub struct CallbackFileSystem {
+    msg_tx: mpsc::UnboundedSender<CallbackFsMsg>,
+    /// The thread driving the callbacks, see [`thread_id`]
+    thread: u64,
+}
+
+impl CallbackFileSystem {
+    /// Creates a file system driving `callbacks` on the current thread until
+    /// every handle to it is dropped.
+    pub fn new(callbacks: Rc<dyn FsCallbacks>) -> Self {
+        let (msg_tx, msg_rx) = mpsc::unbounded_channel();
+        wasm_bindgen_futures::spawn_local(serve(callbacks, msg_rx));
+
+        Self {
+            msg_tx,
+            thread: thread_id(),
+        }
+    }
+
+    /// Sends a message to the task driving the callbacks and blocks until it
+    /// replies.
+    fn call<T>(&self, msg: impl FnOnce(Reply<T>) -> CallbackFsMsg) -> Result<T> {
+        if thread_id() == self.thread {
+            tracing::warn!("Directory callbacks can't be awaited on the thread calling them");
+            return Err(FsError::WouldBlock);
+        }
+
+        let (reply, rx) = oneshot::channel();
+        self.msg_tx
+            .send(msg(reply))
+            .map_err(|_| FsError::NoDevice)?;
+        InlineWaker::block_on(rx).map_err(|_| FsError::NoDevice)?
+    }
+}
+
+/// Drives the callbacks until every handle to the file system is dropped,
+/// the callbacks of each message running concurrently.
+async fn serve(callbacks: Rc<dyn FsCallbacks>, mut msg_rx: mpsc::UnboundedReceiver<CallbackFsMsg>) {
+    while let Some(msg) = msg_rx.recv().await {
+        let callbacks = callbacks.clone();
+        wasm_bindgen_futures::spawn_local(async move { execute(&*callbacks, msg).await });
+    }
+    tracing::debug!("Directory callbacks released");
+}
+
+async fn execute(callbacks: &dyn FsCallbacks, msg: CallbackFsMsg) {
+    match msg {
+        CallbackFsMsg::Metadata { path, reply } => {
+            let _ = reply.send(callbacks.metadata(&path).await);
+        }
+        CallbackFsMsg::ReadDir { path, reply } => {
+            let _ = reply.send(callbacks.read_dir(&path).await);
+        }
+        CallbackFsMsg::CreateDir { path, reply } => {
+            let _ = reply.send(callbacks.create_dir(&path).await);
+        }
+        CallbackFsMsg::RemoveDir { path, reply } => {
+            let _ = reply.send(callbacks.remove_dir(&path).await);
+        }
+        CallbackFsMsg::RemoveFile { path, reply } => {
+            let _ = reply.send(callbacks.remove_file(&path).await);
+        }
+        CallbackFsMsg::Rename { from, to, reply } => {
+            let _ = reply.send(callbacks.rename(&from, &to).await);
+        }
+        CallbackFsMsg::Open { path, conf, reply } => {
+            let _ = reply.send(callbacks.open(&path, &conf).await);
+        }
+        CallbackFsMsg::Read {
+            fh,
+            offset,
+            len,
+            reply,
+        } => {
+            let _ = reply.send(callbacks.read(fh, offset, len).await);
+        }
+        CallbackFsMsg::Write {
+            fh,
+            offset,
+            data,
+            reply,
+        } => {
+            let _ = reply.send(callbacks.write(fh, offset, &data).await);
+        }
+        CallbackFsMsg::SetLen { fh, len, reply } => {
+            let _ = reply.send(callbacks.set_len(fh, len).await);
+        }
+        CallbackFsMsg::Flush { fh, reply } => {
+            let _ = reply.send(callbacks.flush(fh).await);
+        }
+        CallbackFsMsg::Close { fh } => callbacks.close(fh).await,
+    }
+}
+
+/// A number identifying the current thread.
+fn thread_id() -> u64 {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+    thread_local! {
+        static ID: u64 = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    }
+    ID.with(|id| *id)
+}
+
+impl FileSystem for CallbackFileSystem {
+    fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        // The callbacks have no symbolic links
+        self.metadata(path)?;
+        Err(FsError::InvalidInput)
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<ReadDir> {
+        let path = path.to_path_buf();
+        let entries = self.call(|reply| CallbackFsMsg::ReadDir { path, reply })?;
+        Ok(ReadDir::new(entries))
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        let path = path.to_path_buf();
+        self.call(|reply| CallbackFsMsg::CreateDir { path, reply })
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        let path = path.to_path_buf();
+        self.call(|reply| CallbackFsMsg::RemoveDir { path, reply })
+    }
+
+    fn rename<'a>(&'a self, from: &'a Path, to: &'a Path) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let (from, to) = (from.to_path_buf(), to.to_path_buf());
+            self.call(|reply| CallbackFsMsg::Rename { from, to, reply })
+        })
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        let path = path.to_path_buf();
+        self.call(|reply| CallbackFsMsg::Metadata { path, reply })
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        self.metadata(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        let path = path.to_path_buf();
+        self.call(|reply| CallbackFsMsg::RemoveFile { path, reply })
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(self)
+    }
+
+    fn mount(
+        &self,
+        _name: String,
+        _path: &Path,
+        _fs: Box<dyn FileSystem + Send + Sync>,
+    ) -> Result<()> {
+        Err(FsError::Unsupported)
+    }
+}
+
+impl FileOpener for CallbackFileSystem {
+    fn open(
+        &self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        let fh = self.call(|reply| CallbackFsMsg::Open {
+            path: path.to_path_buf(),
+            conf: conf.clone(),
+            reply,
+        })?;
+
+        Ok(Box::new(CallbackFile {
+            fs: self.clone(),
+            fh,
+            path: path.to_path_buf(),
+            cursor: 0,
+            append: conf.append,
+        }))
+    }
+}
+
+/// A file opened in a [`CallbackFileSystem`].
+#[derive(Debug)]
+struct CallbackFile {
+    fs: CallbackFileSystem,
+    fh: u64,
+    path: PathBuf,
+    cursor: u64,
+    append: bool,
+}
+
+impl CallbackFile {
+    fn metadata(&self) -> Metadata {
+        self.fs.metadata(&self.path).unwrap_or_default()
+    }
+}
+
+impl VirtualFile for CallbackFile {
+    fn last_accessed(&self) -> u64 {
+        self.metadata().accessed
+    }
+
+    fn last_modified(&self) -> u64 {
+        self.metadata().modified
+    }
+
+    fn created_time(&self) -> u64 {
+        self.metadata().created
+    }
+
+    fn size(&self) -> u64 {
+        self.metadata().len
+    }
+
+    fn set_len(&mut self, new_size: u64) -> Result<()> {
+        let fh = self.fh;
+        self.fs.call(|reply| CallbackFsMsg::SetLen {
+            fh,
+            len: new_size,
+            reply,
+        })
+    }
+
+    fn unlink(&mut self) -> Result<()> {
+        self.fs.remove_file(&self.path)
+    }
+
+    fn poll_read_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        let remaining = self.size().saturating_sub(self.cursor);
+        Poll::Ready(Ok(remaining.try_into().unwrap_or(usize::MAX)))
+    }
+
+    fn poll_write_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        Poll::Ready(Ok(8192))
+    }
+}
+
+impl AsyncRead for CallbackFile {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let (fh, offset, len) = (self.fh, self.cursor, buf.remaining());
+        let mut data = self.fs.call(|reply| CallbackFsMsg::Read {
+            fh,
+            offset,
+            len,
+            reply,
+        })?;
+
+        data.truncate(len);
+        buf.put_slice(&data);
+        self.cursor += data.len() as u64;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for CallbackFile {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let fh = self.fh;
+        let offset = match self.append {
+            true => self.size(),
+            false => self.cursor,
+        };
+        let written = self.fs.call(|reply| CallbackFsMsg::Write {
+            fh,
+            offset,
+            data: buf.to_vec(),
+            reply,
+        })?;
+
+        let written = written.min(buf.len());
+        self.cursor = offset + written as u64;
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let fh = self.fh;
+        self.fs.call(|reply| CallbackFsMsg::Flush { fh, reply })?;
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl AsyncSeek for CallbackFile {
+    fn start_seek(mut self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        let cursor = match position {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.size().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.cursor.checked_add_signed(offset),
+        };
+        self.cursor = cursor.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Poll::Ready(Ok(self.cursor))
+    }
+}
+
+impl Drop for CallbackFile {
+    fn drop(&mut self) {
+        let _ = self.fs.msg_tx.send(CallbackFsMsg::Close { fh: self.fh });
+    }
+}
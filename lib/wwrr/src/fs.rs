@@ -1,5 +1,6 @@
 use std::{
     path::{Path, PathBuf},
+    rc::Rc,
     sync::Arc,
 };
 
@@ -7,10 +8,17 @@ use anyhow::Context;
 use js_sys::Reflect;
 use tracing::Instrument;
 use utils::{Error, StringOrBytes};
-use virtual_fs::{AsyncReadExt, AsyncWriteExt, FileSystem, FileType};
+use virtual_fs::{ArchiveFileSystem, AsyncReadExt, AsyncWriteExt, FileSystem, FileType};
 use wasm_bindgen::{prelude::wasm_bindgen, JsCast, JsValue};
 use wasmer_wasix::runtime::task_manager::InlineWaker;
 
+use crate::{
+    callback_fs::{CallbackFileSystem, JsCallbacks},
+    http_fs::{HttpFileSystem, HttpFsConfig},
+    indexed_db::IndexedDbFileSystem,
+    opfs::OpfsFileSystem,
+};
+
 /// A directory that can be mounted inside a WASIX instance.
 #[derive(Debug, Clone)]
 #[wasm_bindgen]
@@ -23,13 +31,82 @@ impl Directory {
     pub fn new(init: Option<DirectoryInit>) -> Result<Directory, Error> {
         match init {
             Some(init) => {
-                let fs = init.initialize()?;
+                let fs = init.initialize(false)?;
                 Ok(Directory(fs))
             }
             None => Ok(Directory::default()),
         }
     }
 
+    /// Create an in-memory {@link Directory} whose lookups ignore the case
+    /// of names, like on Windows, while keeping names as they were created.
+    #[wasm_bindgen(js_name = "caseInsensitive")]
+    pub fn case_insensitive(init: Option<DirectoryInit>) -> Result<Directory, Error> {
+        match init {
+            Some(init) => Ok(Directory(init.initialize(true)?)),
+            None => {
+                let fs = virtual_fs::mem_fs::FileSystem::default();
+                fs.set_case_insensitive(true);
+                Ok(Directory(Arc::new(fs)))
+            }
+        }
+    }
+
+    /// Open a directory of the browser's Origin Private File System, which
+    /// persists across page loads, creating it if it doesn't exist.
+    ///
+    /// The root of the OPFS is opened if no name is provided.
+    pub fn opfs(name: Option<String>) -> Result<Directory, Error> {
+        let fs = OpfsFileSystem::open(name.as_deref().unwrap_or_default())?;
+        Ok(Directory(Arc::new(fs)))
+    }
+
+    /// Open a directory stored in an IndexedDB database, which persists
+    /// across page loads, for when the Origin Private File System isn't
+    /// available.
+    ///
+    /// The directory is loaded into memory, and changes are written back to
+    /// the database shortly after they're made.
+    #[wasm_bindgen(js_name = "indexedDb")]
+    pub async fn indexed_db(name: Option<String>) -> Result<Directory, Error> {
+        let name = name.as_deref().unwrap_or("wasmer-fs");
+        let fs = IndexedDbFileSystem::open(name).await?;
+        Ok(Directory(Arc::new(fs)))
+    }
+
+    /// Create a read-only directory whose files are fetched over HTTP the
+    /// first time they're read.
+    ///
+    /// Files are fetched in chunks with range requests, and the chunks most
+    /// recently read are kept in memory.
+    pub fn http(init: HttpDirectoryInit) -> Result<Directory, Error> {
+        let config: HttpFsConfig =
+            serde_wasm_bindgen::from_value(init.into()).map_err(Error::js)?;
+        let fs = HttpFileSystem::new(config)?;
+        Ok(Directory(Arc::new(fs)))
+    }
+
+    /// Create a read-only directory with the contents of a `.zip` file or of
+    /// the volumes of a `.webc` package.
+    #[wasm_bindgen(js_name = "fromArchive")]
+    pub fn from_archive(archive: js_sys::Uint8Array) -> Result<Directory, Error> {
+        let fs = ArchiveFileSystem::from_bytes(archive.to_vec())
+            .context("Unable to read the archive")?;
+        Ok(Directory(Arc::new(fs)))
+    }
+
+    /// Create a directory whose contents are provided by callbacks, for
+    /// instance to expose the state of the application to instances.
+    ///
+    /// The callbacks are called on the current thread, so the directory is
+    /// meant to be mounted in instances: its methods fail when called from
+    /// this thread since they would wait for the callbacks.
+    #[wasm_bindgen(js_name = "fromCallbacks")]
+    pub fn from_callbacks(callbacks: DirectoryCallbacks) -> Directory {
+        let callbacks = JsCallbacks::new(callbacks.unchecked_into());
+        Directory(Arc::new(CallbackFileSystem::new(Rc::new(callbacks))))
+    }
+
     /// Read the contents of a directory.
     #[wasm_bindgen(js_name = "readDir")]
     pub async fn read_dir(&self, mut path: String) -> Result<ListOfDirEntry, Error> {
@@ -37,6 +114,11 @@ impl Directory {
             path.insert(0, '/');
         }
 
+        let entries = match self.as_opfs() {
+            Some(opfs) => opfs.read_dir_async(path.as_ref()).await?,
+            None => FileSystem::read_dir(self, path.as_ref())?.collect::<Result<_, _>>()?,
+        };
+
         let contents = js_sys::Array::new();
 
         let ty = JsValue::from_str("type");
@@ -45,9 +127,7 @@ impl Directory {
         let unknown = JsValue::from_str("unknown");
         let name = JsValue::from_str("name");
 
-        for entry in FileSystem::read_dir(self, path.as_ref())? {
-            let entry = entry?;
-
+        for entry in entries {
             let entry_name = entry.file_name().to_string_lossy().to_string();
             let entry_type = match entry.file_type() {
                 Ok(FileType { dir: true, .. }) => &dir,
@@ -74,13 +154,17 @@ impl Directory {
             path.insert(0, '/');
         }
 
+        let contents = contents.as_bytes();
+        if let Some(opfs) = self.as_opfs() {
+            opfs.write_file_async(path.as_ref(), contents).await?;
+            return Ok(());
+        }
+
         let mut f = self
             .new_open_options()
             .write(true)
             .create(true)
             .open(&path)?;
-
-        let contents = contents.as_bytes();
         f.write_all(&contents).await?;
 
         Ok(())
@@ -112,7 +196,10 @@ impl Directory {
             path.insert(0, '/');
         }
 
-        FileSystem::create_dir(self, path.as_ref())?;
+        match self.as_opfs() {
+            Some(opfs) => opfs.create_dir_async(path.as_ref()).await?,
+            None => FileSystem::create_dir(self, path.as_ref())?,
+        }
 
         Ok(())
     }
@@ -124,7 +211,10 @@ impl Directory {
             path.insert(0, '/');
         }
 
-        FileSystem::remove_dir(self, path.as_ref())?;
+        match self.as_opfs() {
+            Some(opfs) => opfs.remove_dir_async(path.as_ref()).await?,
+            None => FileSystem::remove_dir(self, path.as_ref())?,
+        }
 
         Ok(())
     }
@@ -136,18 +226,31 @@ impl Directory {
             path.insert(0, '/');
         }
 
-        FileSystem::remove_file(self, path.as_ref())?;
+        match self.as_opfs() {
+            Some(opfs) => opfs.remove_file_async(path.as_ref()).await?,
+            None => FileSystem::remove_file(self, path.as_ref())?,
+        }
 
         Ok(())
     }
 }
 
 impl Directory {
+    /// The OPFS directory this is, whose JS API doesn't block since it may
+    /// be called from the main thread.
+    fn as_opfs(&self) -> Option<&OpfsFileSystem> {
+        (*self.0).upcast_any_ref().downcast_ref()
+    }
+
     async fn _read_file(&self, mut path: String) -> Result<Vec<u8>, Error> {
         if !path.starts_with('/') {
             path.insert(0, '/');
         }
 
+        if let Some(opfs) = self.as_opfs() {
+            return Ok(opfs.read_file_async(path.as_ref()).await?);
+        }
+
         let mut f = self.new_open_options().read(true).open(&path)?;
         let mut buffer = Vec::with_capacity(f.size() as usize);
         f.read_to_end(&mut buffer).await?;
@@ -197,6 +300,30 @@ impl FileSystem for Directory {
         self.0.remove_file(path)
     }
 
+    #[tracing::instrument(level = "trace", skip(self))]
+    fn hard_link(&self, from: &Path, to: &Path) -> virtual_fs::Result<()> {
+        self.0.hard_link(from, to)
+    }
+
+    #[tracing::instrument(level = "trace", skip(self))]
+    fn statfs(&self, path: &Path) -> virtual_fs::Result<virtual_fs::FsStats> {
+        self.0.statfs(path)
+    }
+
+    #[tracing::instrument(level = "trace", skip(self))]
+    fn permissions(&self, path: &Path) -> virtual_fs::Result<virtual_fs::Permissions> {
+        self.0.permissions(path)
+    }
+
+    #[tracing::instrument(level = "trace", skip(self))]
+    fn set_permissions(
+        &self,
+        path: &Path,
+        permissions: virtual_fs::Permissions,
+    ) -> virtual_fs::Result<()> {
+        self.0.set_permissions(path, permissions)
+    }
+
     fn new_open_options(&self) -> virtual_fs::OpenOptions {
         virtual_fs::OpenOptions::new(self)
     }
@@ -220,6 +347,11 @@ impl FileSystem for Directory {
     ) -> virtual_fs::Result<()> {
         self.0.mount(name, path, fs)
     }
+
+    #[tracing::instrument(level = "trace", skip(self))]
+    fn unmount(&self, path: &Path) -> virtual_fs::Result<()> {
+        self.0.unmount(path)
+    }
 }
 
 impl virtual_fs::FileOpener for Directory {
@@ -272,10 +404,96 @@ extern "C" {
     pub type DirectoryInit;
 }
 
+#[wasm_bindgen(typescript_custom_section)]
+const HTTP_DIRECTORY_INIT_TYPE_DEF: &'static str = r#"
+/**
+ * The files of a directory created with {@link Directory.http}.
+ */
+export type HttpDirectoryInit = {
+    /**
+     * The URL the paths of the files are relative to, which should end with
+     * a `"/"`.
+     */
+    baseUrl: string;
+    /** The size of every file in bytes, by its path. */
+    files: Record<string, number>;
+    /** The maximum number of bytes kept in memory, 64 MiB by default. */
+    cacheSize?: number;
+};
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "HttpDirectoryInit", extends = js_sys::Object)]
+    #[derive(Debug, Clone, PartialEq)]
+    pub type HttpDirectoryInit;
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const DIRECTORY_CALLBACKS_TYPE_DEF: &'static str = r#"
+/**
+ * The type, size, and last modification time in milliseconds since the epoch
+ * of an entry in a directory created with {@link Directory.fromCallbacks}.
+ */
+export type EntryMetadata = {
+    type: "file" | "dir";
+    size?: number;
+    modified?: number;
+};
+
+/** How a file of a {@link DirectoryCallbacks} is opened. */
+export type OpenFileOptions = {
+    read: boolean;
+    write: boolean;
+    create: boolean;
+    createNew: boolean;
+    truncate: boolean;
+    append: boolean;
+};
+
+/**
+ * The callbacks providing the contents of a directory created with
+ * {@link Directory.fromCallbacks}, which can return promises.
+ *
+ * Paths are absolute within the directory. Errors can be reported by
+ * throwing an object whose `code` is the name of a POSIX error, like
+ * `"ENOENT"`, and the directory is read-only without the optional callbacks
+ * modifying it.
+ */
+export interface DirectoryCallbacks {
+    /** The metadata of an entry, or `null` if it doesn't exist. */
+    metadata(path: string): EntryMetadata | null | Promise<EntryMetadata | null>;
+    readDir(path: string): (EntryMetadata & { name: string })[] | Promise<(EntryMetadata & { name: string })[]>;
+    /**
+     * Opens a file, returning a value identifying it which is passed to the
+     * other file callbacks. The existing files are opened and identified by
+     * their path if it isn't provided.
+     */
+    open?(path: string, options: OpenFileOptions): unknown;
+    read(file: any, offset: number, length: number): Uint8Array | string | Promise<Uint8Array | string>;
+    /** Writes to a file, returning the number of bytes written if not all. */
+    write?(file: any, offset: number, data: Uint8Array): number | void | Promise<number | void>;
+    truncate?(file: any, length: number): void | Promise<void>;
+    flush?(file: any): void | Promise<void>;
+    close?(file: any): void | Promise<void>;
+    createDir?(path: string): void | Promise<void>;
+    removeDir?(path: string): void | Promise<void>;
+    removeFile?(path: string): void | Promise<void>;
+    rename?(from: string, to: string): void | Promise<void>;
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "DirectoryCallbacks", extends = js_sys::Object)]
+    #[derive(Debug, Clone, PartialEq)]
+    pub type DirectoryCallbacks;
+}
+
 impl DirectoryInit {
-    fn initialize(&self) -> Result<Arc<dyn FileSystem>, Error> {
+    fn initialize(&self, case_insensitive: bool) -> Result<Arc<dyn FileSystem>, Error> {
         if let Some(record) = self.dyn_ref::<js_sys::Object>() {
-            let fs = in_memory_filesystem(record)?;
+            let fs = in_memory_filesystem(record, case_insensitive)?;
             Ok(Arc::new(fs))
         } else {
             unreachable!()
@@ -285,8 +503,14 @@ impl DirectoryInit {
 
 /// Construct an in-memory [`FileSystem`] based on an object mapping paths to
 /// their contents (`Record<string, string | Uint8Array>`).
-fn in_memory_filesystem(record: &js_sys::Object) -> Result<virtual_fs::mem_fs::FileSystem, Error> {
+fn in_memory_filesystem(
+    record: &js_sys::Object,
+    case_insensitive: bool,
+) -> Result<virtual_fs::mem_fs::FileSystem, Error> {
     let fs = virtual_fs::mem_fs::FileSystem::default();
+    // Set before adding the files, so that paths only differing by their
+    // case end up in the same directories
+    fs.set_case_insensitive(case_insensitive);
 
     for (key, contents) in utils::object_entries(record)? {
         let mut path = String::from(key);
@@ -0,0 +1,131 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use bytes::Bytes;
+
+/// Identifies a chunk by the URL of its file and its index in the file.
+pub(crate) type ChunkKey = (Arc<str>, u64);
+
+/// The chunks of files most recently read, evicting the least recently used
+/// ones once they take more than `capacity` bytes.
+#[derive(Debug)]
+pub(crate) struct ChunkCache {
+    capacity: u64,
+    size: u64,
+    /// Incremented every time a chunk is used
+    tick: u64,
+    chunks: HashMap<ChunkKey, (Bytes, u64)>,
+    /// The chunks by when they were last used
+    by_use: BTreeMap<u64, ChunkKey>,
+}
+
+impl ChunkCache {
+    pub fn new(capacity: u64) -> Self {
+        ChunkCache {
+            capacity,
+            size: 0,
+            tick: 0,
+            chunks: HashMap::new(),
+            by_use: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, key: &ChunkKey) -> Option<Bytes> {
+        let (data, used) = self.chunks.get_mut(key)?;
+        self.tick += 1;
+        let key = self.by_use.remove(used)?;
+        *used = self.tick;
+        self.by_use.insert(self.tick, key);
+        Some(data.clone())
+    }
+
+    /// Whether a chunk is cached, without counting as a use.
+    pub fn contains(&self, key: &ChunkKey) -> bool {
+        self.chunks.contains_key(key)
+    }
+
+    /// Drops a chunk, e.g. because it won't be read again soon.
+    pub fn remove(&mut self, key: &ChunkKey) {
+        if let Some((data, used)) = self.chunks.remove(key) {
+            self.by_use.remove(&used);
+            self.size -= data.len() as u64;
+        }
+    }
+
+    pub fn insert(&mut self, key: ChunkKey, data: Bytes) {
+        let len = data.len() as u64;
+        if len > self.capacity {
+            return;
+        }
+
+        self.tick += 1;
+        if let Some((previous, used)) = self.chunks.insert(key.clone(), (data, self.tick)) {
+            self.by_use.remove(&used);
+            self.size -= previous.len() as u64;
+        }
+        self.by_use.insert(self.tick, key);
+        self.size += len;
+
+        while self.size > self.capacity {
+            let Some((_, key)) = self.by_use.pop_first() else {
+                break;
+            };
+            if let Some((data, _)) = self.chunks.remove(&key) {
+                self.size -= data.len() as u64;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+
+    fn key(url: &str, index: u64) -> ChunkKey {
+        (Arc::from(url), index)
+    }
+
+    #[wasm_bindgen_test]
+    fn evicts_the_least_recently_used_chunks() {
+        let mut cache = ChunkCache::new(8);
+        cache.insert(key("a", 0), Bytes::from_static(b"0123"));
+        cache.insert(key("a", 1), Bytes::from_static(b"4567"));
+        assert!(cache.get(&key("a", 0)).is_some());
+
+        cache.insert(key("b", 0), Bytes::from_static(b"89"));
+
+        assert_eq!(cache.get(&key("a", 0)), Some(Bytes::from_static(b"0123")));
+        assert_eq!(cache.get(&key("a", 1)), None);
+        assert_eq!(cache.get(&key("b", 0)), Some(Bytes::from_static(b"89")));
+        assert_eq!(cache.size, 6);
+    }
+
+    #[wasm_bindgen_test]
+    fn removed_chunks_free_their_space() {
+        let mut cache = ChunkCache::new(8);
+        cache.insert(key("a", 0), Bytes::from_static(b"0123"));
+        cache.insert(key("a", 1), Bytes::from_static(b"4567"));
+
+        cache.remove(&key("a", 0));
+        cache.insert(key("b", 0), Bytes::from_static(b"89"));
+
+        assert_eq!(cache.get(&key("a", 0)), None);
+        assert_eq!(cache.get(&key("a", 1)), Some(Bytes::from_static(b"4567")));
+        assert_eq!(cache.size, 6);
+    }
+
+    #[wasm_bindgen_test]
+    fn chunks_larger_than_the_cache_are_not_kept() {
+        let mut cache = ChunkCache::new(2);
+        cache.insert(key("a", 0), Bytes::from_static(b"01"));
+
+        cache.insert(key("a", 1), Bytes::from_static(b"234"));
+
+        assert_eq!(cache.get(&key("a", 0)), Some(Bytes::from_static(b"01")));
+        assert_eq!(cache.get(&key("a", 1)), None);
+    }
+}
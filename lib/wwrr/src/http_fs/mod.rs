@@ -0,0 +1,463 @@
+//! A read-only file system whose files are fetched over HTTP the first time
+//! they're read, so that huge trees of assets don't have to be downloaded
+//! before a program starts.
+//!
+//! # Design
+//!
+//! The directories and the size of every file are known upfront, so only
+//! reading the contents of a file goes over the network. Files are fetched
+//! in chunks of [`CHUNK_SIZE`] bytes with range requests by a dedicated web
+//! worker running an [`HttpFsState`], which keeps the chunks most recently
+//! read in a [`ChunkCache`]. The [`HttpFileSystem`] and its files send it
+//! [`HttpFsMsg`]s and block until it replies, like the files of the
+//! [`crate::opfs`] file system.
+//!
+//! The advice given about files is followed: the files read sequentially
+//! fetch the [`READ_AHEAD`] bytes after the ones read along with them, and
+//! the ranges which will or won't be needed are prefetched or dropped from
+//! the cache.
+//!
+//! [`HttpFsState`]: worker::HttpFsState
+//! [`ChunkCache`]: cache::ChunkCache
+
+mod cache;
+mod worker;
+
+use std::{
+    collections::BTreeMap,
+    io::{self, SeekFrom},
+    path::{Component, Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf},
+    sync::{mpsc, oneshot},
+};
+use utils::Error;
+use virtual_fs::{
+    DirEntry, FileAdvice, FileOpener, FileSystem, FileType, FsError, FsStats, Metadata,
+    OpenOptions, OpenOptionsConfig, ReadDir, VirtualFile,
+};
+use wasmer_wasix::runtime::task_manager::InlineWaker;
+
+use self::worker::HttpFsInit;
+use crate::tasks::{init_message_http_fs, WORKER_URL};
+
+/// The size of the chunks files are fetched and cached in.
+const CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// The number of bytes fetched after the ones read from the files advised
+/// to be read sequentially.
+const READ_AHEAD: u64 = 4 * CHUNK_SIZE;
+
+/// The number of bytes cached by default.
+const DEFAULT_CACHE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// The block size reported by [`FileSystem::statfs`].
+const BLOCK_SIZE: u64 = 4096;
+
+/// The options of an [`HttpFileSystem`], as passed to
+/// `Directory.http()`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HttpFsConfig {
+    /// The URL the paths of the files are relative to.
+    pub base_url: String,
+    /// The size of every file in bytes, by its path.
+    pub files: BTreeMap<String, u64>,
+    /// The maximum number of bytes cached.
+    #[serde(default)]
+    pub cache_size: Option<u64>,
+}
+
+/// Messages sent from the [`HttpFileSystem`] files to the worker fetching
+/// their contents.
+#[derive(Debug)]
+pub(crate) enum HttpFsMsg {
+    /// Reads up to `len` bytes at `offset` in the file at `url`, which is
+    /// `size` bytes long.
+    Read {
+        url: Arc<str>,
+        size: u64,
+        offset: u64,
+        len: usize,
+        /// The number of bytes after the ones read to fetch with them
+        read_ahead: u64,
+        reply: oneshot::Sender<virtual_fs::Result<Bytes>>,
+    },
+    /// Fetches `len` bytes at `offset` in the file at `url` in the
+    /// background.
+    Prefetch {
+        url: Arc<str>,
+        size: u64,
+        offset: u64,
+        len: u64,
+    },
+    /// Drops the cached chunks within `len` bytes at `offset` in the file at
+    /// `url`.
+    Evict {
+        url: Arc<str>,
+        offset: u64,
+        len: u64,
+    },
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Dir,
+    File { url: Arc<str>, size: u64 },
+}
+
+impl Node {
+    fn metadata(&self) -> Metadata {
+        match self {
+            Node::Dir => Metadata {
+                ft: FileType::new_dir(),
+                ..Default::default()
+            },
+            Node::File { size, .. } => Metadata {
+                ft: FileType::new_file(),
+                len: *size,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// A read-only [`FileSystem`] whose files are fetched over HTTP when they're
+/// read.
+#[derive(Debug, Clone)]
+pub struct HttpFileSystem {
+    nodes: Arc<BTreeMap<PathBuf, Node>>,
+    msg_tx: mpsc::UnboundedSender<HttpFsMsg>,
+}
+
+impl HttpFileSystem {
+    /// Creates the file system, resolving the URL of every file against the
+    /// base URL, and spawns the web worker fetching their contents.
+    pub(crate) fn new(config: HttpFsConfig) -> Result<Self, Error> {
+        let mut nodes = BTreeMap::from([(PathBuf::from("/"), Node::Dir)]);
+        for (path, size) in &config.files {
+            let url = web_sys::Url::new_with_base(path.trim_start_matches('/'), &config.base_url)
+                .map_err(Error::js)?;
+            let path = normalize(Path::new(path))
+                .ok_or_else(|| anyhow::anyhow!("\"{path}\" is not a valid path for a file"))?;
+
+            for ancestor in path.ancestors().skip(1) {
+                if let Some(Node::File { .. }) = nodes.insert(ancestor.to_path_buf(), Node::Dir) {
+                    return Err(anyhow::anyhow!("\"{}\" is a file", ancestor.display()).into());
+                }
+            }
+            let file = Node::File {
+                url: url.href().into(),
+                size: *size,
+            };
+            if nodes.insert(path.clone(), file).is_some() {
+                return Err(anyhow::anyhow!("\"{}\" is a directory", path.display()).into());
+            }
+        }
+
+        let wo = web_sys::WorkerOptions::new();
+        wo.set_name("http-fs");
+        wo.set_type(web_sys::WorkerType::Module);
+        let worker = web_sys::Worker::new_with_options(&WORKER_URL, &wo).map_err(Error::js)?;
+        worker
+            .post_message(&init_message_http_fs())
+            .map_err(Error::js)?;
+
+        let (msg_tx, msg_rx) = mpsc::unbounded_channel();
+        let init = HttpFsInit {
+            msg_rx,
+            cache_size: config.cache_size.unwrap_or(DEFAULT_CACHE_SIZE),
+        };
+        worker.post_message(&init.into_js()?).map_err(Error::js)?;
+
+        Ok(Self {
+            nodes: Arc::new(nodes),
+            msg_tx,
+        })
+    }
+
+    fn node(&self, path: &Path) -> virtual_fs::Result<&Node> {
+        let path = normalize(path).ok_or(FsError::InvalidInput)?;
+        self.nodes.get(&path).ok_or(FsError::EntryNotFound)
+    }
+}
+
+/// Makes `path` absolute and removes its `.` and `..` components, or returns
+/// `None` if it goes above the root.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+        }
+    }
+    Some(normalized)
+}
+
+impl FileSystem for HttpFileSystem {
+    fn readlink(&self, path: &Path) -> virtual_fs::Result<PathBuf> {
+        self.node(path)?;
+        Err(FsError::InvalidInput)
+    }
+
+    fn read_dir(&self, path: &Path) -> virtual_fs::Result<ReadDir> {
+        let Node::Dir = self.node(path)? else {
+            return Err(FsError::BaseNotDirectory);
+        };
+
+        let path = normalize(path).ok_or(FsError::InvalidInput)?;
+        let entries = self
+            .nodes
+            .range(path.clone()..)
+            .skip(1)
+            .take_while(|(child, _)| child.starts_with(&path))
+            .filter(|(child, _)| child.parent() == Some(path.as_path()))
+            .map(|(child, node)| DirEntry {
+                path: child.clone(),
+                metadata: Ok(node.metadata()),
+            })
+            .collect();
+        Ok(ReadDir::new(entries))
+    }
+
+    fn create_dir(&self, _path: &Path) -> virtual_fs::Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn remove_dir(&self, _path: &Path) -> virtual_fs::Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn rename<'a>(
+        &'a self,
+        _from: &'a Path,
+        _to: &'a Path,
+    ) -> BoxFuture<'a, virtual_fs::Result<()>> {
+        Box::pin(async { Err(FsError::PermissionDenied) })
+    }
+
+    fn metadata(&self, path: &Path) -> virtual_fs::Result<Metadata> {
+        self.node(path).map(Node::metadata)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> virtual_fs::Result<Metadata> {
+        self.metadata(path)
+    }
+
+    fn remove_file(&self, _path: &Path) -> virtual_fs::Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn statfs(&self, path: &Path) -> virtual_fs::Result<FsStats> {
+        self.node(path)?;
+        let blocks = self
+            .nodes
+            .values()
+            .map(|node| match node {
+                Node::File { size, .. } => size.div_ceil(BLOCK_SIZE),
+                Node::Dir => 0,
+            })
+            .sum();
+
+        Ok(FsStats {
+            block_size: BLOCK_SIZE,
+            blocks,
+            blocks_free: 0,
+            files: self.nodes.len() as u64,
+            files_free: 0,
+        })
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(self)
+    }
+
+    fn mount(
+        &self,
+        _name: String,
+        _path: &Path,
+        _fs: Box<dyn FileSystem + Send + Sync>,
+    ) -> virtual_fs::Result<()> {
+        Err(FsError::Unsupported)
+    }
+}
+
+impl FileOpener for HttpFileSystem {
+    fn open(
+        &self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> virtual_fs::Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        if conf.would_mutate() {
+            return Err(FsError::PermissionDenied);
+        }
+
+        match self.node(path)? {
+            Node::File { url, size } => Ok(Box::new(HttpFile {
+                fs: self.clone(),
+                url: url.clone(),
+                size: *size,
+                cursor: 0,
+                sequential: false,
+            })),
+            Node::Dir => Err(FsError::NotAFile),
+        }
+    }
+}
+
+/// A file opened in an [`HttpFileSystem`].
+#[derive(Debug)]
+struct HttpFile {
+    fs: HttpFileSystem,
+    url: Arc<str>,
+    size: u64,
+    cursor: u64,
+    /// Whether the file is advised to be read sequentially
+    sequential: bool,
+}
+
+impl VirtualFile for HttpFile {
+    fn last_accessed(&self) -> u64 {
+        0
+    }
+
+    fn last_modified(&self) -> u64 {
+        0
+    }
+
+    fn created_time(&self) -> u64 {
+        0
+    }
+
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn set_len(&mut self, _new_size: u64) -> virtual_fs::Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn unlink(&mut self) -> virtual_fs::Result<()> {
+        Err(FsError::PermissionDenied)
+    }
+
+    fn advise(&mut self, offset: u64, len: u64, advice: FileAdvice) -> virtual_fs::Result<()> {
+        let len = match len {
+            0 => self.size.saturating_sub(offset),
+            len => len,
+        };
+        let msg = match advice {
+            FileAdvice::Sequential => {
+                self.sequential = true;
+                return Ok(());
+            }
+            FileAdvice::Normal | FileAdvice::Random => {
+                self.sequential = false;
+                return Ok(());
+            }
+            FileAdvice::NoReuse => return Ok(()),
+            FileAdvice::WillNeed => HttpFsMsg::Prefetch {
+                url: self.url.clone(),
+                size: self.size,
+                offset,
+                len,
+            },
+            FileAdvice::DontNeed => HttpFsMsg::Evict {
+                url: self.url.clone(),
+                offset,
+                len,
+            },
+        };
+        self.fs.msg_tx.send(msg).map_err(|_| FsError::NoDevice)
+    }
+
+    fn poll_read_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        let remaining = self.size.saturating_sub(self.cursor);
+        Poll::Ready(Ok(remaining.try_into().unwrap_or(usize::MAX)))
+    }
+
+    fn poll_write_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        Poll::Ready(Err(io::ErrorKind::PermissionDenied.into()))
+    }
+}
+
+impl AsyncRead for HttpFile {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.cursor >= self.size || buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        let (reply, rx) = oneshot::channel();
+        let msg = HttpFsMsg::Read {
+            url: self.url.clone(),
+            size: self.size,
+            offset: self.cursor,
+            len: buf.remaining(),
+            read_ahead: if self.sequential { READ_AHEAD } else { 0 },
+            reply,
+        };
+        self.fs.msg_tx.send(msg).map_err(|_| FsError::NoDevice)?;
+        let data = InlineWaker::block_on(rx).map_err(|_| FsError::NoDevice)??;
+
+        buf.put_slice(&data);
+        self.cursor += data.len() as u64;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for HttpFile {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        _buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(Err(io::ErrorKind::PermissionDenied.into()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncSeek for HttpFile {
+    fn start_seek(mut self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        let cursor = match position {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.cursor.checked_add_signed(offset),
+        };
+        self.cursor = cursor.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Poll::Ready(Ok(self.cursor))
+    }
+}
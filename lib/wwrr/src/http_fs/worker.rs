@@ -0,0 +1,276 @@
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+
+use bytes::{Bytes, BytesMut};
+use js_sys::Uint8Array;
+use tokio::sync::mpsc;
+use utils::Error;
+use virtual_fs::{FsError, Result};
+use wasm_bindgen::{prelude::wasm_bindgen, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{DedicatedWorkerGlobalScope, Headers, Request, RequestInit, Response, WorkerGlobalScope};
+
+use super::{cache::ChunkCache, HttpFsMsg, CHUNK_SIZE};
+use crate::tasks::{Deserializer, Serializer};
+
+/// Initialization message of the worker fetching the files, sent as a web
+/// worker message.
+#[derive(Debug)]
+pub(crate) struct HttpFsInit {
+    /// Message receiver.
+    pub msg_rx: mpsc::UnboundedReceiver<HttpFsMsg>,
+    /// The maximum number of bytes cached.
+    pub cache_size: u64,
+}
+
+impl HttpFsInit {
+    pub(crate) fn into_js(self) -> std::result::Result<JsValue, Error> {
+        let Self { msg_rx, cache_size } = self;
+
+        Serializer::new(consts::TYPE_INIT)
+            .boxed(consts::MSG_RX, msg_rx)
+            .set(consts::CACHE_SIZE, cache_size as f64)
+            .finish()
+    }
+
+    pub(crate) unsafe fn try_from_js(value: JsValue) -> std::result::Result<Self, Error> {
+        let de = Deserializer::new(value);
+        if de.ty()? != consts::TYPE_INIT {
+            return Err(anyhow::anyhow!("invalid HTTP file system init message type").into());
+        }
+
+        Ok(Self {
+            msg_rx: de.boxed(consts::MSG_RX)?,
+            cache_size: de.serde(consts::CACHE_SIZE)?,
+        })
+    }
+}
+
+mod consts {
+    pub const TYPE_INIT: &str = "init-http-fs";
+    pub const MSG_RX: &str = "msg-rx";
+    pub const CACHE_SIZE: &str = "cache-size";
+}
+
+/// The worker fetching the files.
+#[wasm_bindgen(skip_typescript)]
+struct HttpFsWorker {}
+
+#[wasm_bindgen]
+impl HttpFsWorker {
+    /// Preinitializes the worker.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Handles the init message and starts serving the file system.
+    #[wasm_bindgen]
+    pub fn handle(&mut self, msg: JsValue) -> std::result::Result<(), utils::Error> {
+        let init = unsafe { HttpFsInit::try_from_js(msg) }?;
+        wasm_bindgen_futures::spawn_local(HttpFsState::run(init));
+        Ok(())
+    }
+}
+
+/// The state of the worker fetching the files.
+#[derive(Debug)]
+pub(crate) struct HttpFsState {
+    cache: RefCell<ChunkCache>,
+}
+
+impl HttpFsState {
+    /// Serves the file system on this web worker until every handle to it
+    /// is dropped.
+    async fn run(init: HttpFsInit) {
+        let HttpFsInit {
+            mut msg_rx,
+            cache_size,
+        } = init;
+        let state = Rc::new(HttpFsState {
+            cache: RefCell::new(ChunkCache::new(cache_size)),
+        });
+
+        while let Some(msg) = msg_rx.recv().await {
+            match msg {
+                HttpFsMsg::Read {
+                    url,
+                    size,
+                    offset,
+                    len,
+                    read_ahead,
+                    reply,
+                } => {
+                    // Reads are served concurrently, so that a slow download
+                    // doesn't hold up the reads of cached chunks
+                    let state = state.clone();
+                    wasm_bindgen_futures::spawn_local(async move {
+                        let data = state.read(url, size, offset, len, read_ahead).await;
+                        let _ = reply.send(data);
+                    });
+                }
+                HttpFsMsg::Prefetch {
+                    url,
+                    size,
+                    offset,
+                    len,
+                } => {
+                    let state = state.clone();
+                    wasm_bindgen_futures::spawn_local(async move {
+                        state.prefetch(url, size, offset, len).await;
+                    });
+                }
+                HttpFsMsg::Evict { url, offset, len } => state.evict(url, offset, len),
+            }
+        }
+
+        tracing::debug!("HTTP file system worker exiting");
+        let scope: DedicatedWorkerGlobalScope = js_sys::global().unchecked_into();
+        scope.close();
+    }
+
+    /// Reads from the cached chunks, fetching the missing ones with a single
+    /// range request. When chunks have to be fetched, the ones within the
+    /// `read_ahead` bytes after the ones read are fetched with them.
+    async fn read(
+        &self,
+        url: Arc<str>,
+        size: u64,
+        offset: u64,
+        len: usize,
+        read_ahead: u64,
+    ) -> Result<Bytes> {
+        let end = size.min(offset.saturating_add(len as u64));
+        if offset >= end {
+            return Ok(Bytes::new());
+        }
+
+        let first = offset / CHUNK_SIZE;
+        let mut last = (end - 1) / CHUNK_SIZE;
+        let cached = {
+            let cache = self.cache.borrow();
+            (first..=last).all(|index| cache.contains(&(url.clone(), index)))
+        };
+        if read_ahead > 0 && !cached {
+            last = (size.min(end.saturating_add(read_ahead)) - 1) / CHUNK_SIZE;
+        }
+        let chunks = self.chunks(&url, size, first, last).await?;
+
+        let mut data = BytesMut::with_capacity((end - offset) as usize);
+        for (index, chunk) in (first..).zip(chunks) {
+            let chunk_start = index * CHUNK_SIZE;
+            if chunk_start >= end {
+                break;
+            }
+            let from = offset.saturating_sub(chunk_start) as usize;
+            let to = chunk.len().min((end - chunk_start) as usize);
+            data.extend_from_slice(&chunk[from..to]);
+        }
+        Ok(data.freeze())
+    }
+
+    /// Fetches the chunks within `len` bytes at `offset` which aren't cached
+    /// yet.
+    async fn prefetch(&self, url: Arc<str>, size: u64, offset: u64, len: u64) {
+        let end = size.min(offset.saturating_add(len));
+        if offset < end {
+            let _ = self
+                .chunks(&url, size, offset / CHUNK_SIZE, (end - 1) / CHUNK_SIZE)
+                .await;
+        }
+    }
+
+    /// Returns the chunks from `first` to `last`, fetching the missing ones
+    /// with a single range request.
+    async fn chunks(&self, url: &Arc<str>, size: u64, first: u64, last: u64) -> Result<Vec<Bytes>> {
+        let mut chunks: Vec<Option<Bytes>> = {
+            let mut cache = self.cache.borrow_mut();
+            (first..=last)
+                .map(|index| cache.get(&(url.clone(), index)))
+                .collect()
+        };
+
+        let missing = chunks.iter().position(Option::is_none);
+        let last_missing = chunks.iter().rposition(Option::is_none);
+        if let (Some(missing), Some(last_missing)) = (missing, last_missing) {
+            let start = (first + missing as u64) * CHUNK_SIZE;
+            let stop = size.min((first + last_missing as u64 + 1) * CHUNK_SIZE);
+            let data = fetch(url, start, stop).await?;
+
+            let mut cache = self.cache.borrow_mut();
+            let fetched = data.chunks(CHUNK_SIZE as usize);
+            for (i, (chunk, data)) in chunks[missing..=last_missing]
+                .iter_mut()
+                .zip(fetched)
+                .enumerate()
+            {
+                let data = Bytes::copy_from_slice(data);
+                cache.insert((url.clone(), first + (missing + i) as u64), data.clone());
+                *chunk = Some(data);
+            }
+        }
+
+        chunks
+            .into_iter()
+            .map(|chunk| chunk.ok_or(FsError::UnexpectedEof))
+            .collect()
+    }
+
+    /// Drops the chunks entirely within `len` bytes at `offset`, the ones
+    /// partially in the range may still be needed.
+    fn evict(&self, url: Arc<str>, offset: u64, len: u64) {
+        let first = offset.div_ceil(CHUNK_SIZE);
+        let end = offset.saturating_add(len) / CHUNK_SIZE;
+        let mut cache = self.cache.borrow_mut();
+        for index in first..end {
+            cache.remove(&(url.clone(), index));
+        }
+    }
+}
+
+/// Fetches the bytes from `start` to `end` of the file at `url`.
+async fn fetch(url: &str, start: u64, end: u64) -> Result<Bytes> {
+    let network_error = |e: JsValue| {
+        tracing::warn!(url, error = &*utils::js_error(e), "Unable to fetch a file");
+        FsError::IOError
+    };
+
+    let headers = Headers::new().map_err(network_error)?;
+    headers
+        .set("Range", &format!("bytes={start}-{}", end - 1))
+        .map_err(network_error)?;
+    let init = RequestInit::new();
+    init.set_method("GET");
+    init.set_headers(&headers);
+    let request = Request::new_with_str_and_init(url, &init).map_err(network_error)?;
+
+    let scope: WorkerGlobalScope = js_sys::global().unchecked_into();
+    let response: Response = JsFuture::from(scope.fetch_with_request(&request))
+        .await
+        .map_err(network_error)?
+        .unchecked_into();
+
+    // Servers which don't support range requests send the whole file
+    let range = match response.status() {
+        206 => 0..end - start,
+        200 => start..end,
+        404 | 410 => return Err(FsError::EntryNotFound),
+        401 | 403 => return Err(FsError::PermissionDenied),
+        status => {
+            tracing::warn!(url, status, "Unable to fetch a file");
+            return Err(FsError::IOError);
+        }
+    };
+    let body = response.array_buffer().map_err(network_error)?;
+    let body = Uint8Array::new(&JsFuture::from(body).await.map_err(network_error)?);
+
+    if u64::from(body.length()) < range.end {
+        tracing::warn!(
+            url,
+            len = body.length(),
+            "The file is shorter than expected"
+        );
+        return Err(FsError::UnexpectedEof);
+    }
+    let data = body.subarray(range.start as u32, range.end as u32).to_vec();
+    Ok(data.into())
+}
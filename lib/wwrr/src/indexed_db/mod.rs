@@ -0,0 +1,553 @@
+//! A file system persisted in the browser's IndexedDB, for the browsers and
+//! contexts where the Origin Private File System isn't available.
+//!
+//! # Design
+//!
+//! IndexedDB can only be accessed through callbacks, so the whole file system
+//! is loaded into an in-memory [`mem_fs::FileSystem`] when it's opened, which
+//! then serves every operation synchronously. The database is owned by a
+//! dedicated web worker running an [`IndexedDbState`], which the
+//! [`IndexedDbFileSystem`] handles send [`IndexedDbMsg`]s to:
+//!
+//! - Changes to the tree of directories and files are appended to a journal
+//!   as they happen, so that the tree stored in the database is always
+//!   consistent, even when a directory holding many files is renamed. The
+//!   journal is folded into a snapshot of the tree whenever the file system
+//!   is opened.
+//! - The contents of the files are written back by the worker a moment after
+//!   they're modified, or as soon as they're flushed. They're stored under a
+//!   stable id rather than their path, so renaming doesn't move them.
+//!
+//! [`IndexedDbState`]: worker::IndexedDbState
+
+mod worker;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, SeekFrom},
+    path::{Component, Path, PathBuf},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf},
+    sync::{mpsc, oneshot},
+};
+use utils::Error;
+use virtual_fs::{
+    mem_fs, FileOpener, FileSystem, FsError, FsStats, Metadata, OpenOptions, OpenOptionsConfig,
+    ReadDir, VirtualFile,
+};
+use wasmer_wasix::runtime::task_manager::InlineWaker;
+
+use self::worker::IndexedDbInit;
+use crate::tasks::{init_message_indexed_db, WORKER_URL};
+
+/// A change to the tree of directories and files, paths are relative to the
+/// root of the file system.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "kebab-case")]
+pub(crate) enum JournalEntry {
+    CreateDir { path: String },
+    CreateFile { path: String, id: u64 },
+    /// Removes a directory or a file, along with what the directory holds.
+    Remove { path: String },
+    /// Renames a directory or a file, replacing what is at `to`.
+    Rename { from: String, to: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum Node {
+    Dir,
+    /// A file whose contents are stored under `id`.
+    File { id: u64 },
+}
+
+/// The directories and files of the file system, by their path.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Tree {
+    nodes: BTreeMap<String, Node>,
+    /// The id of the next file created.
+    next_id: u64,
+}
+
+impl Tree {
+    /// Applies a change, returning the ids of the files it removed.
+    fn apply(&mut self, entry: &JournalEntry) -> Vec<u64> {
+        match entry {
+            JournalEntry::CreateDir { path } => {
+                self.nodes.insert(path.clone(), Node::Dir);
+                Vec::new()
+            }
+            JournalEntry::CreateFile { path, id } => {
+                self.nodes.insert(path.clone(), Node::File { id: *id });
+                self.next_id = self.next_id.max(id + 1);
+                Vec::new()
+            }
+            JournalEntry::Remove { path } => file_ids(self.remove(path)),
+            JournalEntry::Rename { from, to } => {
+                let replaced = self.remove(to);
+                for (path, node) in self.remove(from) {
+                    let path = format!("{to}{}", &path[from.len()..]);
+                    self.nodes.insert(path, node);
+                }
+                file_ids(replaced)
+            }
+        }
+    }
+
+    /// Removes `path` and everything under it.
+    fn remove(&mut self, path: &str) -> Vec<(String, Node)> {
+        let prefix = format!("{path}/");
+        let paths: Vec<_> = self
+            .nodes
+            .keys()
+            .filter(|key| *key == path || key.starts_with(&prefix))
+            .cloned()
+            .collect();
+
+        paths
+            .into_iter()
+            .filter_map(|path| self.nodes.remove_entry(&path))
+            .collect()
+    }
+
+    /// The path of the file whose contents are stored under `id`.
+    fn path_of(&self, id: u64) -> Option<&str> {
+        self.nodes
+            .iter()
+            .find(|(_, node)| **node == Node::File { id })
+            .map(|(path, _)| path.as_str())
+    }
+}
+
+fn file_ids(nodes: Vec<(String, Node)>) -> Vec<u64> {
+    nodes
+        .into_iter()
+        .filter_map(|(_, node)| match node {
+            Node::File { id } => Some(id),
+            Node::Dir => None,
+        })
+        .collect()
+}
+
+/// The path of `path` relative to the root of the file system, which
+/// identifies it in the [`Tree`].
+fn tree_path(path: &Path) -> virtual_fs::Result<String> {
+    let names = path
+        .components()
+        .filter_map(|component| match component {
+            Component::RootDir | Component::CurDir => None,
+            Component::Normal(name) => Some(name.to_str()),
+            Component::ParentDir | Component::Prefix(_) => Some(None),
+        })
+        .map(|name| name.ok_or(FsError::InvalidInput))
+        .collect::<virtual_fs::Result<Vec<_>>>()?;
+    Ok(names.join("/"))
+}
+
+/// Messages sent from the [`IndexedDbFileSystem`] handles to the worker
+/// owning the database.
+#[derive(Debug)]
+pub(crate) enum IndexedDbMsg {
+    /// Appends to the journal, and drops the contents of the removed files.
+    Journal {
+        entry: JournalEntry,
+        removed: Vec<u64>,
+    },
+    /// The contents of a file were modified and must be written back.
+    Dirty { id: u64 },
+    /// Writes the contents of a file back now.
+    Flush {
+        id: u64,
+        reply: oneshot::Sender<virtual_fs::Result<()>>,
+    },
+}
+
+/// A [`FileSystem`] stored in an IndexedDB database, which persists across
+/// page loads.
+#[derive(Debug, Clone)]
+pub struct IndexedDbFileSystem {
+    cache: mem_fs::FileSystem,
+    tree: Arc<Mutex<Tree>>,
+    msg_tx: mpsc::UnboundedSender<IndexedDbMsg>,
+}
+
+impl IndexedDbFileSystem {
+    /// Opens the file system stored in the database `name`, which is created
+    /// if needed, and loads it.
+    ///
+    /// Opening the same database again returns the same file system, since
+    /// each of them caches the whole database.
+    pub async fn open(name: &str) -> Result<Self, Error> {
+        static OPENED: once_cell::sync::Lazy<Mutex<HashMap<String, IndexedDbFileSystem>>> =
+            once_cell::sync::Lazy::new(Mutex::default);
+
+        if let Some(fs) = OPENED.lock().unwrap().get(name) {
+            if !fs.msg_tx.is_closed() {
+                return Ok(fs.clone());
+            }
+        }
+
+        let fs = Self::spawn(name).await?;
+        OPENED
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), fs.clone());
+        Ok(fs)
+    }
+
+    /// Spawns the web worker owning the database and waits for it to load
+    /// the file system.
+    async fn spawn(name: &str) -> Result<Self, Error> {
+        let wo = web_sys::WorkerOptions::new();
+        wo.set_name("indexed-db");
+        wo.set_type(web_sys::WorkerType::Module);
+        let worker = web_sys::Worker::new_with_options(&WORKER_URL, &wo).map_err(Error::js)?;
+        worker
+            .post_message(&init_message_indexed_db())
+            .map_err(Error::js)?;
+
+        let cache = mem_fs::FileSystem::default();
+        let tree = Arc::new(Mutex::new(Tree::default()));
+        let (msg_tx, msg_rx) = mpsc::unbounded_channel();
+        let (ready_tx, ready_rx) = oneshot::channel();
+        let init = IndexedDbInit {
+            msg_rx,
+            ready_tx,
+            name: name.to_string(),
+            cache: cache.clone(),
+            tree: tree.clone(),
+        };
+        worker.post_message(&init.into_js()?).map_err(Error::js)?;
+
+        ready_rx
+            .await
+            .map_err(|_| anyhow::anyhow!("the IndexedDB worker exited"))?
+            .map_err(anyhow::Error::msg)?;
+
+        Ok(Self {
+            cache,
+            tree,
+            msg_tx,
+        })
+    }
+
+    /// Applies a change to the tree and journals it, the tree must be locked
+    /// while the cache is changed so that the journal is in the same order.
+    fn journal(&self, tree: &mut Tree, entry: JournalEntry) {
+        let removed = tree.apply(&entry);
+        let _ = self.msg_tx.send(IndexedDbMsg::Journal { entry, removed });
+    }
+
+    fn remove(&self, path: &Path, remove: impl FnOnce(&Path) -> virtual_fs::Result<()>) -> virtual_fs::Result<()> {
+        let entry = JournalEntry::Remove {
+            path: tree_path(path)?,
+        };
+        let mut tree = self.tree.lock().unwrap();
+        remove(path)?;
+        self.journal(&mut tree, entry);
+        Ok(())
+    }
+}
+
+impl FileSystem for IndexedDbFileSystem {
+    fn readlink(&self, path: &Path) -> virtual_fs::Result<PathBuf> {
+        self.cache.readlink(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> virtual_fs::Result<ReadDir> {
+        self.cache.read_dir(path)
+    }
+
+    fn create_dir(&self, path: &Path) -> virtual_fs::Result<()> {
+        let entry = JournalEntry::CreateDir {
+            path: tree_path(path)?,
+        };
+        let mut tree = self.tree.lock().unwrap();
+        self.cache.create_dir(path)?;
+        self.journal(&mut tree, entry);
+        Ok(())
+    }
+
+    fn remove_dir(&self, path: &Path) -> virtual_fs::Result<()> {
+        self.remove(path, |path| self.cache.remove_dir(path))
+    }
+
+    fn rename<'a>(
+        &'a self,
+        from: &'a Path,
+        to: &'a Path,
+    ) -> BoxFuture<'a, virtual_fs::Result<()>> {
+        Box::pin(async move {
+            let entry = JournalEntry::Rename {
+                from: tree_path(from)?,
+                to: tree_path(to)?,
+            };
+            // Renaming in memory doesn't wait, so the tree stays locked
+            // without holding the lock across an await point
+            let mut tree = self.tree.lock().unwrap();
+            InlineWaker::block_on(self.cache.rename(from, to))?;
+            self.journal(&mut tree, entry);
+            Ok(())
+        })
+    }
+
+    fn metadata(&self, path: &Path) -> virtual_fs::Result<Metadata> {
+        self.cache.metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> virtual_fs::Result<Metadata> {
+        self.cache.symlink_metadata(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> virtual_fs::Result<()> {
+        self.remove(path, |path| self.cache.remove_file(path))
+    }
+
+    fn statfs(&self, path: &Path) -> virtual_fs::Result<FsStats> {
+        self.cache.statfs(path)
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(self)
+    }
+
+    fn mount(
+        &self,
+        _name: String,
+        _path: &Path,
+        _fs: Box<dyn FileSystem + Send + Sync>,
+    ) -> virtual_fs::Result<()> {
+        Err(FsError::Unsupported)
+    }
+}
+
+impl FileOpener for IndexedDbFileSystem {
+    fn open(
+        &self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> virtual_fs::Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        let tree_path = tree_path(path)?;
+        let mut tree = self.tree.lock().unwrap();
+        let file = self
+            .cache
+            .new_open_options()
+            .options(conf.clone())
+            .open(path)?;
+
+        let id = match tree.nodes.get(&tree_path) {
+            Some(Node::File { id }) => {
+                let id = *id;
+                if conf.truncate && (conf.write || conf.append) {
+                    let _ = self.msg_tx.send(IndexedDbMsg::Dirty { id });
+                }
+                id
+            }
+            Some(Node::Dir) => return Err(FsError::NotAFile),
+            None => {
+                let id = tree.next_id;
+                self.journal(
+                    &mut tree,
+                    JournalEntry::CreateFile {
+                        path: tree_path,
+                        id,
+                    },
+                );
+                id
+            }
+        };
+
+        Ok(Box::new(IndexedDbFile {
+            fs: self.clone(),
+            id,
+            inner: file,
+        }))
+    }
+}
+
+/// A file opened in an [`IndexedDbFileSystem`], which tells the worker when
+/// its contents must be written back.
+#[derive(Debug)]
+struct IndexedDbFile {
+    fs: IndexedDbFileSystem,
+    id: u64,
+    inner: Box<dyn VirtualFile + Send + Sync + 'static>,
+}
+
+impl IndexedDbFile {
+    fn modified<T>(&self, result: T) -> T {
+        let _ = self.fs.msg_tx.send(IndexedDbMsg::Dirty { id: self.id });
+        result
+    }
+}
+
+impl VirtualFile for IndexedDbFile {
+    fn last_accessed(&self) -> u64 {
+        self.inner.last_accessed()
+    }
+
+    fn last_modified(&self) -> u64 {
+        self.inner.last_modified()
+    }
+
+    fn created_time(&self) -> u64 {
+        self.inner.created_time()
+    }
+
+    fn set_times(&mut self, atime: Option<u64>, mtime: Option<u64>) -> virtual_fs::Result<()> {
+        self.inner.set_times(atime, mtime)
+    }
+
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+
+    fn set_len(&mut self, new_size: u64) -> virtual_fs::Result<()> {
+        let result = self.inner.set_len(new_size);
+        self.modified(result)
+    }
+
+    fn allocate(&mut self, offset: u64, len: u64) -> virtual_fs::Result<()> {
+        let result = self.inner.allocate(offset, len);
+        self.modified(result)
+    }
+
+    fn punch_hole(&mut self, offset: u64, len: u64) -> virtual_fs::Result<()> {
+        let result = self.inner.punch_hole(offset, len);
+        self.modified(result)
+    }
+
+    fn unlink(&mut self) -> virtual_fs::Result<()> {
+        let mut tree = self.fs.tree.lock().unwrap();
+        self.inner.unlink()?;
+        if let Some(path) = tree.path_of(self.id) {
+            let path = path.to_string();
+            self.fs.journal(&mut tree, JournalEntry::Remove { path });
+        }
+        Ok(())
+    }
+
+    fn poll_read_ready(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(self.inner.as_mut()).poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(self.inner.as_mut()).poll_write_ready(cx)
+    }
+}
+
+impl AsyncRead for IndexedDbFile {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(self.inner.as_mut()).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for IndexedDbFile {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match Pin::new(self.inner.as_mut()).poll_write(cx, buf) {
+            Poll::Ready(result) => Poll::Ready(self.modified(result)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let Poll::Ready(Err(err)) = Pin::new(self.inner.as_mut()).poll_flush(cx) {
+            return Poll::Ready(Err(err));
+        }
+
+        // Flushing makes the contents durable, like fsync(2)
+        let (reply, rx) = oneshot::channel();
+        let msg = IndexedDbMsg::Flush { id: self.id, reply };
+        if self.fs.msg_tx.send(msg).is_ok() {
+            InlineWaker::block_on(rx).map_err(|_| FsError::NoDevice)??;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(self.inner.as_mut()).poll_shutdown(cx)
+    }
+}
+
+impl AsyncSeek for IndexedDbFile {
+    fn start_seek(mut self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        Pin::new(self.inner.as_mut()).start_seek(position)
+    }
+
+    fn poll_complete(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Pin::new(self.inner.as_mut()).poll_complete(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+
+    fn replay(entries: &[JournalEntry]) -> (Tree, Vec<u64>) {
+        let mut tree = Tree::default();
+        let removed = entries.iter().flat_map(|entry| tree.apply(entry)).collect();
+        (tree, removed)
+    }
+
+    #[wasm_bindgen_test]
+    fn renaming_a_directory_moves_its_files() {
+        let (tree, removed) = replay(&[
+            JournalEntry::CreateDir { path: "a".into() },
+            JournalEntry::CreateFile {
+                path: "a/file".into(),
+                id: 0,
+            },
+            JournalEntry::CreateFile {
+                path: "b".into(),
+                id: 1,
+            },
+            JournalEntry::Remove { path: "b".into() },
+            JournalEntry::CreateDir { path: "b".into() },
+            JournalEntry::Rename {
+                from: "a".into(),
+                to: "b".into(),
+            },
+        ]);
+
+        assert_eq!(removed, [1]);
+        assert_eq!(tree.next_id, 2);
+        assert_eq!(tree.path_of(0), Some("b/file"));
+        let paths: Vec<_> = tree.nodes.keys().map(String::as_str).collect();
+        assert_eq!(paths, ["b", "b/file"]);
+    }
+
+    #[wasm_bindgen_test]
+    fn journal_entries_round_trip() {
+        let entry = JournalEntry::Rename {
+            from: "a".into(),
+            to: "b".into(),
+        };
+
+        let value = serde_wasm_bindgen::to_value(&entry).unwrap();
+        let round_tripped: JournalEntry = serde_wasm_bindgen::from_value(value).unwrap();
+
+        assert_eq!(round_tripped, entry);
+    }
+}
@@ -0,0 +1,426 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+use futures::future::Either;
+use js_sys::{Array, Promise, Uint8Array};
+use tokio::sync::{mpsc, oneshot};
+use utils::{Error, GlobalScope};
+use virtual_fs::{mem_fs, AsyncReadExt, AsyncWriteExt, FileSystem, FsError};
+use wasm_bindgen::{closure::Closure, prelude::wasm_bindgen, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    DedicatedWorkerGlobalScope, IdbDatabase, IdbObjectStoreParameters, IdbRequest,
+    IdbTransaction, IdbTransactionMode,
+};
+
+use super::{IndexedDbMsg, JournalEntry, Node, Tree};
+use crate::tasks::{Deserializer, Serializer};
+
+/// The store holding the snapshot of the [`Tree`].
+const TREE_STORE: &str = "tree";
+const TREE_KEY: &str = "tree";
+/// The store holding the [`JournalEntry`]s since the snapshot.
+const JOURNAL_STORE: &str = "journal";
+/// The store holding the contents of the files, by their id.
+const CONTENTS_STORE: &str = "contents";
+
+/// How long the contents of a modified file are kept in memory before being
+/// written back.
+const WRITE_BACK_DELAY_MS: f64 = 1000.;
+
+/// Initialization message of the worker owning the database, sent as a web
+/// worker message.
+#[derive(Debug)]
+pub(crate) struct IndexedDbInit {
+    /// Message receiver.
+    pub msg_rx: mpsc::UnboundedReceiver<IndexedDbMsg>,
+    /// Replied to once the file system is loaded.
+    pub ready_tx: oneshot::Sender<Result<(), String>>,
+    /// The name of the database.
+    pub name: String,
+    /// The cache the file system is loaded into.
+    pub cache: mem_fs::FileSystem,
+    /// The tree the file system is loaded into.
+    pub tree: Arc<Mutex<Tree>>,
+}
+
+impl IndexedDbInit {
+    pub(crate) fn into_js(self) -> Result<JsValue, Error> {
+        let Self {
+            msg_rx,
+            ready_tx,
+            name,
+            cache,
+            tree,
+        } = self;
+
+        Serializer::new(consts::TYPE_INIT)
+            .boxed(consts::MSG_RX, msg_rx)
+            .boxed(consts::READY_TX, ready_tx)
+            .set(consts::NAME, name)
+            .boxed(consts::CACHE, cache)
+            .boxed(consts::TREE, tree)
+            .finish()
+    }
+
+    pub(crate) unsafe fn try_from_js(value: JsValue) -> Result<Self, Error> {
+        let de = Deserializer::new(value);
+        if de.ty()? != consts::TYPE_INIT {
+            return Err(anyhow::anyhow!("invalid IndexedDB init message type").into());
+        }
+
+        Ok(Self {
+            msg_rx: de.boxed(consts::MSG_RX)?,
+            ready_tx: de.boxed(consts::READY_TX)?,
+            name: de.string(consts::NAME)?,
+            cache: de.boxed(consts::CACHE)?,
+            tree: de.boxed(consts::TREE)?,
+        })
+    }
+}
+
+mod consts {
+    pub const TYPE_INIT: &str = "init-indexed-db";
+    pub const MSG_RX: &str = "msg-rx";
+    pub const READY_TX: &str = "ready-tx";
+    pub const NAME: &str = "name";
+    pub const CACHE: &str = "cache";
+    pub const TREE: &str = "tree";
+}
+
+/// The worker owning the database.
+#[wasm_bindgen(skip_typescript)]
+struct IndexedDbWorker {}
+
+#[wasm_bindgen]
+impl IndexedDbWorker {
+    /// Preinitializes the worker.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Handles the init message and starts loading the file system.
+    #[wasm_bindgen]
+    pub fn handle(&mut self, msg: JsValue) -> Result<(), utils::Error> {
+        let init = unsafe { IndexedDbInit::try_from_js(msg) }?;
+        wasm_bindgen_futures::spawn_local(IndexedDbState::run(init));
+        Ok(())
+    }
+}
+
+/// The state of the worker owning the database.
+#[derive(Debug)]
+pub(crate) struct IndexedDbState {
+    db: IdbDatabase,
+    cache: mem_fs::FileSystem,
+    tree: Arc<Mutex<Tree>>,
+    /// The ids of the files whose contents must be written back.
+    dirty: HashSet<u64>,
+}
+
+impl IndexedDbState {
+    /// Loads the file system, then keeps the database up to date until every
+    /// handle to the file system is dropped.
+    async fn run(init: IndexedDbInit) {
+        let IndexedDbInit {
+            mut msg_rx,
+            ready_tx,
+            name,
+            cache,
+            tree,
+        } = init;
+
+        match Self::load(&name, cache, tree).await {
+            Ok(mut state) => {
+                let _ = ready_tx.send(Ok(()));
+                state.serve(&mut msg_rx).await;
+                state.db.close();
+            }
+            Err(err) => {
+                tracing::error!(%name, error = &*err, "Unable to load the IndexedDB file system");
+                let _ = ready_tx.send(Err(format!("{err:#}")));
+            }
+        }
+
+        tracing::debug!(%name, "IndexedDB worker exiting");
+        let scope: DedicatedWorkerGlobalScope = js_sys::global().unchecked_into();
+        scope.close();
+    }
+
+    /// Loads the snapshot of the tree and the journal since then into the
+    /// cache, then folds the journal into a new snapshot.
+    async fn load(
+        name: &str,
+        cache: mem_fs::FileSystem,
+        tree: Arc<Mutex<Tree>>,
+    ) -> anyhow::Result<Self> {
+        let db = open(name).await?;
+
+        let tx = transaction(&db, &[TREE_STORE, JOURNAL_STORE, CONTENTS_STORE], false)?;
+        let snapshot = tx
+            .object_store(TREE_STORE)
+            .and_then(|store| store.get(&TREE_KEY.into()))
+            .map_err(utils::js_error)?;
+        let journal = tx
+            .object_store(JOURNAL_STORE)
+            .and_then(|store| store.get_all())
+            .map_err(utils::js_error)?;
+        let (ids, contents) = tx
+            .object_store(CONTENTS_STORE)
+            .and_then(|store| Ok((store.get_all_keys()?, store.get_all()?)))
+            .map_err(utils::js_error)?;
+
+        let snapshot = wait_for(&snapshot).await?;
+        let mut loaded: Tree = match snapshot.is_undefined() {
+            true => Tree::default(),
+            false => serde_wasm_bindgen::from_value(snapshot).map_err(|e| utils::js_error(e.into()))?,
+        };
+        let journal: Array = wait_for(&journal).await?.unchecked_into();
+        for entry in journal.iter() {
+            let entry: JournalEntry = serde_wasm_bindgen::from_value(entry).map_err(|e| utils::js_error(e.into()))?;
+            loaded.apply(&entry);
+        }
+
+        let ids: Array = wait_for(&ids).await?.unchecked_into();
+        let contents: Array = wait_for(&contents).await?.unchecked_into();
+        let mut contents: HashMap<u64, Uint8Array> = ids
+            .iter()
+            .zip(contents.iter())
+            .filter_map(|(id, data)| Some((id.as_f64()? as u64, data.dyn_into().ok()?)))
+            .collect();
+
+        for (path, node) in &loaded.nodes {
+            let path = Path::new("/").join(path);
+            match node {
+                Node::Dir => cache.create_dir(&path)?,
+                Node::File { id } => {
+                    let data = contents.remove(id).map(|data| data.to_vec());
+                    let mut file = cache
+                        .new_open_options()
+                        .write(true)
+                        .create_new(true)
+                        .open(&path)?;
+                    file.write_all(data.as_deref().unwrap_or_default()).await?;
+                }
+            }
+        }
+
+        // The contents left belong to files removed before being written
+        // back
+        let tx = transaction(&db, &[TREE_STORE, JOURNAL_STORE, CONTENTS_STORE], true)?;
+        let snapshot = serde_wasm_bindgen::to_value(&loaded).map_err(|e| utils::js_error(e.into()))?;
+        tx.object_store(TREE_STORE)
+            .and_then(|store| store.put_with_key(&snapshot, &TREE_KEY.into()))
+            .and_then(|_| tx.object_store(JOURNAL_STORE)?.clear())
+            .map_err(utils::js_error)?;
+        let store = tx.object_store(CONTENTS_STORE).map_err(utils::js_error)?;
+        for id in contents.keys() {
+            store.delete(&content_key(*id)).map_err(utils::js_error)?;
+        }
+        commit(&tx).await?;
+
+        tracing::debug!(%name, nodes = loaded.nodes.len(), "Loaded the IndexedDB file system");
+        *tree.lock().unwrap() = loaded;
+
+        Ok(Self {
+            db,
+            cache,
+            tree,
+            dirty: HashSet::new(),
+        })
+    }
+
+    /// Handles the messages, writing the modified files back once they've
+    /// been kept in memory for long enough.
+    async fn serve(&mut self, msg_rx: &mut mpsc::UnboundedReceiver<IndexedDbMsg>) {
+        let scope = GlobalScope::current();
+        let mut deadline: Option<f64> = None;
+
+        loop {
+            let msg = match deadline {
+                None => msg_rx.recv().await,
+                Some(at) => {
+                    let millis = (at - scope.now()).max(0.) as i32;
+                    let timeout = JsFuture::from(scope.sleep(millis));
+                    match futures::future::select(Box::pin(msg_rx.recv()), timeout).await {
+                        Either::Left((msg, _)) => msg,
+                        Either::Right(_) => {
+                            self.write_back_dirty().await;
+                            deadline = None;
+                            continue;
+                        }
+                    }
+                }
+            };
+            let Some(msg) = msg else {
+                break;
+            };
+
+            match msg {
+                IndexedDbMsg::Journal { entry, removed } => {
+                    if let Err(err) = self.journal(&entry, removed).await {
+                        tracing::error!(?entry, error = &*err, "Unable to journal a change");
+                    }
+                }
+                IndexedDbMsg::Dirty { id } => {
+                    self.dirty.insert(id);
+                    deadline.get_or_insert(scope.now() + WRITE_BACK_DELAY_MS);
+                }
+                IndexedDbMsg::Flush { id, reply } => {
+                    let result = match self.dirty.remove(&id) {
+                        true => self.write_back(id).await,
+                        false => Ok(()),
+                    };
+                    let _ = reply.send(result.map_err(|err| {
+                        tracing::error!(id, error = &*err, "Unable to write a file back");
+                        FsError::IOError
+                    }));
+                }
+            }
+            if self.dirty.is_empty() {
+                deadline = None;
+            }
+        }
+
+        self.write_back_dirty().await;
+    }
+
+    async fn journal(&mut self, entry: &JournalEntry, removed: Vec<u64>) -> anyhow::Result<()> {
+        let entry = serde_wasm_bindgen::to_value(entry).map_err(|e| utils::js_error(e.into()))?;
+
+        let tx = transaction(&self.db, &[JOURNAL_STORE, CONTENTS_STORE], true)?;
+        tx.object_store(JOURNAL_STORE)
+            .and_then(|store| store.add(&entry))
+            .map_err(utils::js_error)?;
+        let store = tx.object_store(CONTENTS_STORE).map_err(utils::js_error)?;
+        for id in removed {
+            self.dirty.remove(&id);
+            store.delete(&content_key(id)).map_err(utils::js_error)?;
+        }
+        commit(&tx).await
+    }
+
+    async fn write_back_dirty(&mut self) {
+        for id in std::mem::take(&mut self.dirty) {
+            if let Err(err) = self.write_back(id).await {
+                tracing::error!(id, error = &*err, "Unable to write a file back");
+            }
+        }
+    }
+
+    async fn write_back(&self, id: u64) -> anyhow::Result<()> {
+        let path = self.tree.lock().unwrap().path_of(id).map(String::from);
+        // The file was removed in the meantime
+        let Some(path) = path else {
+            return Ok(());
+        };
+
+        let mut file = self
+            .cache
+            .new_open_options()
+            .read(true)
+            .open(Path::new("/").join(&path))?;
+        let mut data = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut data).await?;
+
+        let tx = transaction(&self.db, &[CONTENTS_STORE], true)?;
+        tx.object_store(CONTENTS_STORE)
+            .and_then(|store| store.put_with_key(&Uint8Array::from(&data[..]), &content_key(id)))
+            .map_err(utils::js_error)?;
+        commit(&tx)
+            .await
+            .with_context(|| format!("Unable to write \"{path}\" back"))
+    }
+}
+
+fn content_key(id: u64) -> JsValue {
+    JsValue::from_f64(id as f64)
+}
+
+/// Opens the database, creating its stores when it's first created.
+async fn open(name: &str) -> anyhow::Result<IdbDatabase> {
+    let factory = GlobalScope::current()
+        .indexed_db()
+        .context("IndexedDB is not available")?;
+    let request = factory.open_with_u32(name, 1).map_err(utils::js_error)?;
+
+    let on_upgrade_needed = Closure::<dyn FnMut(JsValue)>::new({
+        let request = request.clone();
+        move |_| {
+            let Ok(db) = request.result() else {
+                return;
+            };
+            let db: IdbDatabase = db.unchecked_into();
+
+            let journal = IdbObjectStoreParameters::new();
+            journal.set_auto_increment(true);
+            let created = db
+                .create_object_store(TREE_STORE)
+                .and_then(|_| db.create_object_store_with_optional_parameters(JOURNAL_STORE, &journal))
+                .and_then(|_| db.create_object_store(CONTENTS_STORE));
+            if let Err(e) = created {
+                tracing::warn!(error = ?e, "Unable to create the file system stores");
+            }
+        }
+    });
+    request.set_onupgradeneeded(Some(on_upgrade_needed.as_ref().unchecked_ref()));
+    let db = wait_for(&request).await;
+    request.set_onupgradeneeded(None);
+
+    Ok(db?.unchecked_into())
+}
+
+fn transaction(db: &IdbDatabase, stores: &[&str], write: bool) -> anyhow::Result<IdbTransaction> {
+    let stores: Array = stores.iter().map(|store| JsValue::from_str(store)).collect();
+    let mode = match write {
+        true => IdbTransactionMode::Readwrite,
+        false => IdbTransactionMode::Readonly,
+    };
+    db.transaction_with_str_sequence_and_mode(&stores, mode)
+        .map_err(utils::js_error)
+}
+
+/// Wait for an IndexedDB request to complete, returning its result.
+async fn wait_for(request: &IdbRequest) -> anyhow::Result<JsValue> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        request.set_onsuccess(Some(&resolve));
+        request.set_onerror(Some(&reject));
+    });
+    let outcome = JsFuture::from(promise).await;
+    request.set_onsuccess(None);
+    request.set_onerror(None);
+
+    match outcome {
+        Ok(_) => request.result().map_err(utils::js_error),
+        Err(_) => match request.error() {
+            Ok(Some(e)) => Err(anyhow::Error::msg(e.message())),
+            _ => Err(anyhow::anyhow!("The IndexedDB request failed")),
+        },
+    }
+}
+
+/// Wait for an IndexedDB transaction to be committed.
+async fn commit(tx: &IdbTransaction) -> anyhow::Result<()> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        tx.set_oncomplete(Some(&resolve));
+        tx.set_onerror(Some(&reject));
+        tx.set_onabort(Some(&reject));
+    });
+    let outcome = JsFuture::from(promise).await;
+    tx.set_oncomplete(None);
+    tx.set_onerror(None);
+    tx.set_onabort(None);
+
+    match outcome {
+        Ok(_) => Ok(()),
+        Err(_) => match tx.error() {
+            Some(e) => Err(anyhow::Error::msg(e.message())),
+            None => Err(anyhow::anyhow!("The IndexedDB transaction was aborted")),
+        },
+    }
+}
@@ -1,11 +1,18 @@
 use std::{
+    net::{IpAddr, SocketAddr},
     ops::{Deref, DerefMut},
+    rc::Rc,
     sync::Arc,
 };
 
-use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::{prelude::wasm_bindgen, JsCast, JsValue};
+use wasmer_wasix::{net::forward, Runtime as _};
 
-use crate::runtime::Runtime;
+use crate::{
+    rtc_net::JsSignaling,
+    runtime::Runtime,
+    tcp::{TcpConnection, TcpConnectionHandler, TcpListener},
+};
 use utils::Error;
 
 #[derive(Clone, Debug)]
@@ -27,6 +34,18 @@ impl JsRuntime {
     pub fn into_inner(self) -> Arc<Runtime> {
         self.rt
     }
+
+    /// The address of `host`, which is an IP address or a name resolved by
+    /// the network of the runtime.
+    async fn resolve(&self, host: &str) -> Result<IpAddr, Error> {
+        if let Ok(ip) = host.parse() {
+            return Ok(ip);
+        }
+        let ips = self.rt.networking().resolve(host, None, None).await?;
+        ips.first()
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("\"{host}\" has no address").into())
+    }
 }
 
 #[wasm_bindgen(js_class = "Runtime")]
@@ -37,6 +56,72 @@ impl JsRuntime {
         Ok(JsRuntime::new(Arc::new(rt)))
     }
 
+    /// Create a runtime whose programs open their TCP connections and UDP
+    /// sockets through a relay, reached over a WebSocket at the given
+    /// `ws://` or `wss://` URL.
+    ///
+    /// All the sockets share a single WebSocket, which is connected to again
+    /// when it's lost. The TCP connections are reset when that happens.
+    #[wasm_bindgen(js_name = "withNetworkRelay")]
+    pub fn with_network_relay(url: String) -> Result<JsRuntime, Error> {
+        let rt = Runtime::with_network_relay(&url)?;
+        Ok(JsRuntime::new(Arc::new(rt)))
+    }
+
+    /// Create a runtime whose programs bridge their network with a peer,
+    /// directly over a WebRTC data channel, once they call `port_bridge`
+    /// with the name of the network to meet the peer on.
+    ///
+    /// The signaling is called on the current thread. The peers need
+    /// `iceServers` to reach each other across networks.
+    #[wasm_bindgen(js_name = "withPeerNetwork")]
+    pub fn with_peer_network(
+        signaling: PeerSignaling,
+        ice_servers: Option<js_sys::Array>,
+    ) -> JsRuntime {
+        let signaling = JsSignaling::new(signaling.unchecked_into());
+        let ice_servers = ice_servers.map_or(JsValue::UNDEFINED, JsValue::from);
+        let rt = Runtime::with_peer_network(Rc::new(signaling), ice_servers);
+        JsRuntime::new(Arc::new(rt))
+    }
+
+    /// Create a runtime whose programs share a network in memory, where they
+    /// reach each other by IP address and port, and which the page reaches
+    /// with {@link Runtime.connect} and {@link Runtime.listen}.
+    #[wasm_bindgen(js_name = "withLoopbackNetwork")]
+    pub fn with_loopback_network() -> JsRuntime {
+        JsRuntime::new(Arc::new(Runtime::with_loopback_network()))
+    }
+
+    /// Connect to `port` at `host` through the network of the runtime, for
+    /// instance to a web server run by one of its programs.
+    ///
+    /// A stream provided by the page is forwarded to the program by piping
+    /// it to and from the streams of the connection.
+    pub async fn connect(&self, host: String, port: u16) -> Result<TcpConnection, Error> {
+        let peer = SocketAddr::new(self.resolve(&host).await?, port);
+        let socket = forward::connect(self.rt.networking(), peer).await?;
+        Ok(TcpConnection::new(socket, peer))
+    }
+
+    /// Listen on `port` at `host` in the network of the runtime, handing the
+    /// connections its programs make to `on_connection`. Port 0 picks a free
+    /// port.
+    pub async fn listen(
+        &self,
+        host: String,
+        port: u16,
+        on_connection: TcpConnectionHandler,
+    ) -> Result<TcpListener, Error> {
+        let addr = SocketAddr::new(self.resolve(&host).await?, port);
+        let listener = self
+            .rt
+            .networking()
+            .listen_tcp(addr, false, false, false)
+            .await?;
+        TcpListener::new(listener, on_connection)
+    }
+
     /// Get a reference to the global runtime, optionally initializing it if
     /// requested.
     pub fn global(initialize: Option<bool>) -> Result<Option<JsRuntime>, Error> {
@@ -51,6 +136,38 @@ impl JsRuntime {
     }
 }
 
+#[wasm_bindgen(typescript_custom_section)]
+const PEER_SIGNALING_TYPE_DEF: &'static str = r#"
+/** A message exchanged by peers through a {@link PeerSignaling}. */
+export type SignalMessage =
+    | { type: "offer" | "answer"; sdp: string }
+    | { type: "candidate"; candidate: string; sdpMid?: string; sdpMLineIndex?: number };
+
+/**
+ * How the peers of a runtime created with {@link Runtime.withPeerNetwork}
+ * meet, for instance through a WebSocket server. The messages only have to
+ * be handed to the other peer of the network, in order.
+ */
+export interface PeerSignaling {
+    /**
+     * Joins a network with the token given by the program, returning
+     * whether this peer makes the offer, which is the case of the first one
+     * to join.
+     */
+    join(network: string, token: string): boolean | Promise<boolean>;
+    send(message: SignalMessage): void | Promise<void>;
+    /** The next message of the peer, or `null` once it left. */
+    receive(): Promise<SignalMessage | null>;
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "PeerSignaling", extends = js_sys::Object)]
+    #[derive(Debug, Clone, PartialEq)]
+    pub type PeerSignaling;
+}
+
 impl Deref for JsRuntime {
     type Target = Arc<Runtime>;
 
@@ -1,20 +1,28 @@
 #[cfg(test)]
 wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
 
+mod callback_fs;
 pub mod fs;
+mod http_fs;
+mod indexed_db;
 mod js_runtime;
 mod logging;
+mod opfs;
 mod options;
+mod rtc_net;
 mod run;
 mod runtime;
 mod streams;
 mod tasks;
+mod tcp;
+mod ws_net;
 
 pub use crate::{
     fs::{Directory, DirectoryInit},
     js_runtime::JsRuntime,
     logging::initialize_logger,
     options::{RunOptions, SpawnOptions},
+    tcp::{TcpConnection, TcpListener},
 };
 pub use utils::StringOrBytes;
 
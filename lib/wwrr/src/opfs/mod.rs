@@ -0,0 +1,543 @@
+//! A file system persisted in the browser's Origin Private File System.
+//!
+//! # Design
+//!
+//! The directories and files of the OPFS can only be accessed through
+//! promises, except for the contents of the files which are read and written
+//! synchronously through `FileSystemSyncAccessHandle`s. Those handles are
+//! only available on dedicated workers and lock their file, so they must be
+//! shared by every thread using the file system.
+//!
+//! All the handles are therefore owned by a dedicated web worker running an
+//! [`OpfsState`], while the [`OpfsFileSystem`] and its files are cheap
+//! handles which send it [`OpfsMsg`]s and block until it replies, the same
+//! way the [`crate::tasks::ThreadPool`] talks to its scheduler.
+//!
+//! The main thread of the browser can't block, so the [`FileSystem`] calls
+//! made there fail with [`FsError::WouldBlock`]. The JS API of a
+//! [`crate::Directory`] uses the async methods of [`OpfsFileSystem`] instead,
+//! which await the replies.
+//!
+//! [`OpfsState`]: worker::OpfsState
+
+mod worker;
+
+use std::{
+    collections::HashMap,
+    io::{self, SeekFrom},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll},
+};
+
+use futures::future::BoxFuture;
+use once_cell::sync::Lazy;
+use tokio::{
+    io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf},
+    sync::{mpsc, oneshot},
+};
+use utils::{Error, GlobalScope};
+use virtual_fs::{
+    DirEntry, FileOpener, FileSystem, FsError, FsStats, Metadata, OpenOptions, OpenOptionsConfig,
+    ReadDir, VirtualFile,
+};
+use wasmer_wasix::runtime::task_manager::InlineWaker;
+
+use self::worker::OpfsInit;
+use crate::tasks::{init_message_opfs, WORKER_URL};
+
+/// The channel an [`OpfsMsg`] is replied through.
+type Reply<T> = oneshot::Sender<virtual_fs::Result<T>>;
+
+/// Messages sent from the [`OpfsFileSystem`] handles to the worker owning
+/// the OPFS handles.
+#[derive(Debug)]
+pub(crate) enum OpfsMsg {
+    Metadata {
+        path: PathBuf,
+        reply: Reply<Metadata>,
+    },
+    ReadDir {
+        path: PathBuf,
+        reply: Reply<Vec<DirEntry>>,
+    },
+    CreateDir {
+        path: PathBuf,
+        reply: Reply<()>,
+    },
+    RemoveDir {
+        path: PathBuf,
+        reply: Reply<()>,
+    },
+    RemoveFile {
+        path: PathBuf,
+        reply: Reply<()>,
+    },
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+        reply: Reply<()>,
+    },
+    Statfs {
+        reply: Reply<FsStats>,
+    },
+    /// Opens a file, replying with the id of the opened file.
+    Open {
+        path: PathBuf,
+        conf: OpenOptionsConfig,
+        reply: Reply<u64>,
+    },
+    Read {
+        id: u64,
+        offset: u64,
+        len: usize,
+        reply: Reply<Vec<u8>>,
+    },
+    /// Writes to an opened file at `offset`, or at its end when there is no
+    /// offset, replying with the offset following the written bytes.
+    Write {
+        id: u64,
+        offset: Option<u64>,
+        data: Vec<u8>,
+        reply: Reply<u64>,
+    },
+    Size {
+        id: u64,
+        reply: Reply<u64>,
+    },
+    SetLen {
+        id: u64,
+        len: u64,
+        reply: Reply<()>,
+    },
+    Flush {
+        id: u64,
+        reply: Reply<()>,
+    },
+    /// Closes an opened file, when its last handle is dropped.
+    Close {
+        id: u64,
+    },
+}
+
+/// A [`FileSystem`] stored in a directory of the Origin Private File System,
+/// which persists across page loads.
+#[derive(Debug, Clone)]
+pub struct OpfsFileSystem {
+    msg_tx: mpsc::UnboundedSender<OpfsMsg>,
+}
+
+impl OpfsFileSystem {
+    /// Opens the directory `name` of the OPFS, which is created if needed, or
+    /// the root of the OPFS if `name` is empty.
+    ///
+    /// Opening the same directory again returns the same file system, since
+    /// the files are locked by the worker which opened them.
+    pub fn open(name: &str) -> Result<Self, Error> {
+        static OPENED: Lazy<Mutex<HashMap<String, OpfsFileSystem>>> = Lazy::new(Mutex::default);
+
+        let mut opened = OPENED.lock().unwrap();
+        if let Some(fs) = opened.get(name).filter(|fs| !fs.msg_tx.is_closed()) {
+            return Ok(fs.clone());
+        }
+
+        let fs = Self::spawn(name)?;
+        opened.insert(name.to_string(), fs.clone());
+        Ok(fs)
+    }
+
+    /// Spawns the web worker owning the handles of the file system. The
+    /// messages are queued until it's initialized.
+    fn spawn(name: &str) -> Result<Self, Error> {
+        let wo = web_sys::WorkerOptions::new();
+        wo.set_name("opfs");
+        wo.set_type(web_sys::WorkerType::Module);
+        let worker = web_sys::Worker::new_with_options(&WORKER_URL, &wo).map_err(Error::js)?;
+        worker
+            .post_message(&init_message_opfs())
+            .map_err(Error::js)?;
+
+        let (msg_tx, msg_rx) = mpsc::unbounded_channel();
+        let init = OpfsInit {
+            msg_rx,
+            name: name.to_string(),
+        };
+        worker.post_message(&init.into_js()?).map_err(Error::js)?;
+
+        Ok(Self { msg_tx })
+    }
+
+    /// Sends a message to the worker and waits for its reply.
+    async fn request<T>(&self, msg: impl FnOnce(Reply<T>) -> OpfsMsg) -> virtual_fs::Result<T> {
+        let (reply, rx) = oneshot::channel();
+        self.msg_tx
+            .send(msg(reply))
+            .map_err(|_| FsError::NoDevice)?;
+        rx.await.map_err(|_| FsError::NoDevice)?
+    }
+
+    /// Sends a message to the worker and blocks until it replies, which
+    /// fails on the main thread.
+    fn call<T>(&self, msg: impl FnOnce(Reply<T>) -> OpfsMsg) -> virtual_fs::Result<T> {
+        if !GlobalScope::current().is_wait_allowed() {
+            tracing::warn!("The OPFS can't be used synchronously on the main thread");
+            return Err(FsError::WouldBlock);
+        }
+        InlineWaker::block_on(self.request(msg))
+    }
+
+    pub(crate) async fn read_dir_async(&self, path: &Path) -> virtual_fs::Result<Vec<DirEntry>> {
+        let path = path.to_path_buf();
+        self.request(|reply| OpfsMsg::ReadDir { path, reply }).await
+    }
+
+    pub(crate) async fn create_dir_async(&self, path: &Path) -> virtual_fs::Result<()> {
+        let path = path.to_path_buf();
+        self.request(|reply| OpfsMsg::CreateDir { path, reply })
+            .await
+    }
+
+    pub(crate) async fn remove_dir_async(&self, path: &Path) -> virtual_fs::Result<()> {
+        let path = path.to_path_buf();
+        self.request(|reply| OpfsMsg::RemoveDir { path, reply })
+            .await
+    }
+
+    pub(crate) async fn remove_file_async(&self, path: &Path) -> virtual_fs::Result<()> {
+        let path = path.to_path_buf();
+        self.request(|reply| OpfsMsg::RemoveFile { path, reply })
+            .await
+    }
+
+    /// Reads the whole contents of a file.
+    pub(crate) async fn read_file_async(&self, path: &Path) -> virtual_fs::Result<Vec<u8>> {
+        let conf = OpenOptions::new(self).read(true).get_config();
+        let id = self.open_async(path, conf).await?;
+        let contents = async {
+            let len = self.request(|reply| OpfsMsg::Size { id, reply }).await?;
+            let len = usize::try_from(len).map_err(|_| FsError::InvalidInput)?;
+            self.request(|reply| OpfsMsg::Read {
+                id,
+                offset: 0,
+                len,
+                reply,
+            })
+            .await
+        }
+        .await;
+        let _ = self.msg_tx.send(OpfsMsg::Close { id });
+        contents
+    }
+
+    /// Writes `data` at the start of a file, which is created if needed.
+    pub(crate) async fn write_file_async(
+        &self,
+        path: &Path,
+        data: Vec<u8>,
+    ) -> virtual_fs::Result<()> {
+        let conf = OpenOptions::new(self).write(true).create(true).get_config();
+        let id = self.open_async(path, conf).await?;
+        let written = async {
+            self.request(|reply| OpfsMsg::Write {
+                id,
+                offset: Some(0),
+                data,
+                reply,
+            })
+            .await?;
+            self.request(|reply| OpfsMsg::Flush { id, reply }).await
+        }
+        .await;
+        let _ = self.msg_tx.send(OpfsMsg::Close { id });
+        written
+    }
+
+    async fn open_async(&self, path: &Path, conf: OpenOptionsConfig) -> virtual_fs::Result<u64> {
+        let path = path.to_path_buf();
+        self.request(|reply| OpfsMsg::Open { path, conf, reply })
+            .await
+    }
+}
+
+impl FileSystem for OpfsFileSystem {
+    fn readlink(&self, path: &Path) -> virtual_fs::Result<PathBuf> {
+        // The OPFS has no symbolic links
+        self.metadata(path)?;
+        Err(FsError::InvalidInput)
+    }
+
+    fn read_dir(&self, path: &Path) -> virtual_fs::Result<ReadDir> {
+        let path = path.to_path_buf();
+        let entries = self.call(|reply| OpfsMsg::ReadDir { path, reply })?;
+        Ok(ReadDir::new(entries))
+    }
+
+    fn create_dir(&self, path: &Path) -> virtual_fs::Result<()> {
+        let path = path.to_path_buf();
+        self.call(|reply| OpfsMsg::CreateDir { path, reply })
+    }
+
+    fn remove_dir(&self, path: &Path) -> virtual_fs::Result<()> {
+        let path = path.to_path_buf();
+        self.call(|reply| OpfsMsg::RemoveDir { path, reply })
+    }
+
+    fn rename<'a>(&'a self, from: &'a Path, to: &'a Path) -> BoxFuture<'a, virtual_fs::Result<()>> {
+        Box::pin(async move {
+            let (from, to) = (from.to_path_buf(), to.to_path_buf());
+            self.call(|reply| OpfsMsg::Rename { from, to, reply })
+        })
+    }
+
+    fn metadata(&self, path: &Path) -> virtual_fs::Result<Metadata> {
+        let path = path.to_path_buf();
+        self.call(|reply| OpfsMsg::Metadata { path, reply })
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> virtual_fs::Result<Metadata> {
+        self.metadata(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> virtual_fs::Result<()> {
+        let path = path.to_path_buf();
+        self.call(|reply| OpfsMsg::RemoveFile { path, reply })
+    }
+
+    fn statfs(&self, path: &Path) -> virtual_fs::Result<FsStats> {
+        self.metadata(path)?;
+        self.call(|reply| OpfsMsg::Statfs { reply })
+    }
+
+    fn new_open_options(&self) -> OpenOptions {
+        OpenOptions::new(self)
+    }
+
+    fn mount(
+        &self,
+        _name: String,
+        _path: &Path,
+        _fs: Box<dyn FileSystem + Send + Sync>,
+    ) -> virtual_fs::Result<()> {
+        Err(FsError::Unsupported)
+    }
+}
+
+impl FileOpener for OpfsFileSystem {
+    fn open(
+        &self,
+        path: &Path,
+        conf: &OpenOptionsConfig,
+    ) -> virtual_fs::Result<Box<dyn VirtualFile + Send + Sync + 'static>> {
+        let id = self.call(|reply| OpfsMsg::Open {
+            path: path.to_path_buf(),
+            conf: conf.clone(),
+            reply,
+        })?;
+
+        Ok(Box::new(OpfsFile {
+            fs: self.clone(),
+            id,
+            path: path.to_path_buf(),
+            cursor: 0,
+            append: conf.append,
+        }))
+    }
+}
+
+/// A file opened in an [`OpfsFileSystem`].
+#[derive(Debug)]
+struct OpfsFile {
+    fs: OpfsFileSystem,
+    id: u64,
+    path: PathBuf,
+    cursor: u64,
+    append: bool,
+}
+
+impl OpfsFile {
+    fn metadata(&self) -> Metadata {
+        self.fs.metadata(&self.path).unwrap_or_default()
+    }
+}
+
+impl VirtualFile for OpfsFile {
+    fn last_accessed(&self) -> u64 {
+        self.metadata().accessed
+    }
+
+    fn last_modified(&self) -> u64 {
+        self.metadata().modified
+    }
+
+    fn created_time(&self) -> u64 {
+        self.metadata().created
+    }
+
+    fn size(&self) -> u64 {
+        let id = self.id;
+        self.fs
+            .call(|reply| OpfsMsg::Size { id, reply })
+            .unwrap_or_default()
+    }
+
+    fn set_len(&mut self, new_size: u64) -> virtual_fs::Result<()> {
+        let id = self.id;
+        self.fs.call(|reply| OpfsMsg::SetLen {
+            id,
+            len: new_size,
+            reply,
+        })
+    }
+
+    fn unlink(&mut self) -> virtual_fs::Result<()> {
+        self.fs.remove_file(&self.path)
+    }
+
+    fn poll_read_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        let remaining = self.size().saturating_sub(self.cursor);
+        Poll::Ready(Ok(remaining.try_into().unwrap_or(usize::MAX)))
+    }
+
+    fn poll_write_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        Poll::Ready(Ok(8192))
+    }
+}
+
+impl AsyncRead for OpfsFile {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let (id, offset, len) = (self.id, self.cursor, buf.remaining());
+        let data = self.fs.call(|reply| OpfsMsg::Read {
+            id,
+            offset,
+            len,
+            reply,
+        })?;
+
+        buf.put_slice(&data);
+        self.cursor += data.len() as u64;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for OpfsFile {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let id = self.id;
+        let offset = (!self.append).then_some(self.cursor);
+        let end = self.fs.call(|reply| OpfsMsg::Write {
+            id,
+            offset,
+            data: buf.to_vec(),
+            reply,
+        })?;
+
+        let written = match offset {
+            Some(offset) => end - offset,
+            None => buf.len() as u64,
+        };
+        self.cursor = end;
+        Poll::Ready(Ok(written as usize))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let id = self.id;
+        self.fs.call(|reply| OpfsMsg::Flush { id, reply })?;
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl AsyncSeek for OpfsFile {
+    fn start_seek(mut self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        let cursor = match position {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.size().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.cursor.checked_add_signed(offset),
+        };
+        self.cursor = cursor.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Poll::Ready(Ok(self.cursor))
+    }
+}
+
+impl Drop for OpfsFile {
+    fn drop(&mut self) {
+        let _ = self.fs.msg_tx.send(OpfsMsg::Close { id: self.id });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen::{JsCast, JsValue};
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+    use crate::Directory;
+
+    /// A directory of the OPFS no other test uses, since it persists.
+    fn unique_name(prefix: &str) -> String {
+        format!("{prefix}-{}", js_sys::Math::random().to_bits())
+    }
+
+    #[wasm_bindgen_test]
+    fn blocking_calls_fail_on_the_main_thread() {
+        let fs = OpfsFileSystem::open(&unique_name("blocking")).unwrap();
+
+        assert_eq!(
+            fs.metadata(Path::new("/")).unwrap_err(),
+            FsError::WouldBlock
+        );
+        assert_eq!(
+            fs.new_open_options()
+                .read(true)
+                .open("/file.txt")
+                .unwrap_err(),
+            FsError::WouldBlock
+        );
+    }
+
+    #[wasm_bindgen_test]
+    async fn directories_are_used_from_the_main_thread() {
+        let dir = Directory::opfs(Some(unique_name("directory"))).unwrap();
+        let contents: utils::StringOrBytes = JsValue::from_str("Hello, World!").unchecked_into();
+
+        dir.create_dir("data".to_string()).await.unwrap();
+        dir.write_file("data/file.txt".to_string(), contents)
+            .await
+            .unwrap();
+        let text = dir
+            .read_text_file("/data/file.txt".to_string())
+            .await
+            .unwrap();
+        assert_eq!(String::from(text), "Hello, World!");
+        let entries: js_sys::Array = dir
+            .read_dir("data".to_string())
+            .await
+            .unwrap()
+            .unchecked_into();
+        assert_eq!(entries.length(), 1);
+
+        dir.remove_file("data/file.txt".to_string()).await.unwrap();
+        dir.remove_dir("data".to_string()).await.unwrap();
+        assert!(dir.read_file("data/file.txt".to_string()).await.is_err());
+    }
+}
@@ -0,0 +1,663 @@
+use std::{
+    collections::HashMap,
+    path::{Component, Path},
+};
+
+use js_sys::{IteratorNext, Promise};
+use tokio::sync::mpsc;
+use utils::Error;
+use virtual_fs::{DirEntry, FileType, FsError, FsStats, Metadata, OpenOptionsConfig, Result};
+use wasm_bindgen::{prelude::wasm_bindgen, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    DedicatedWorkerGlobalScope, DomException, FileSystemDirectoryHandle, FileSystemFileHandle,
+    FileSystemGetDirectoryOptions, FileSystemGetFileOptions, FileSystemHandle,
+    FileSystemHandleKind, FileSystemReadWriteOptions, FileSystemRemoveOptions,
+    FileSystemSyncAccessHandle, StorageEstimate, WorkerGlobalScope,
+};
+
+use super::OpfsMsg;
+use crate::tasks::{Deserializer, Serializer};
+
+/// The block size reported by [`OpfsMsg::Statfs`], the OPFS doesn't expose
+/// the one of the disk.
+const BLOCK_SIZE: u64 = 4096;
+
+/// Initialization message of the worker owning the OPFS handles, sent as a
+/// web worker message.
+#[derive(Debug)]
+pub(crate) struct OpfsInit {
+    /// Message receiver.
+    pub msg_rx: mpsc::UnboundedReceiver<OpfsMsg>,
+    /// The directory of the OPFS holding the file system.
+    pub name: String,
+}
+
+impl OpfsInit {
+    pub(crate) fn into_js(self) -> std::result::Result<JsValue, Error> {
+        let Self { msg_rx, name } = self;
+
+        Serializer::new(consts::TYPE_INIT)
+            .boxed(consts::MSG_RX, msg_rx)
+            .set(consts::NAME, name)
+            .finish()
+    }
+
+    pub(crate) unsafe fn try_from_js(value: JsValue) -> std::result::Result<Self, Error> {
+        let de = Deserializer::new(value);
+        if de.ty()? != consts::TYPE_INIT {
+            return Err(anyhow::anyhow!("invalid OPFS init message type").into());
+        }
+
+        Ok(Self {
+            msg_rx: de.boxed(consts::MSG_RX)?,
+            name: de.string(consts::NAME)?,
+        })
+    }
+}
+
+mod consts {
+    pub const TYPE_INIT: &str = "init-opfs";
+    pub const MSG_RX: &str = "msg-rx";
+    pub const NAME: &str = "name";
+}
+
+/// The worker owning the OPFS handles.
+#[wasm_bindgen(skip_typescript)]
+struct OpfsWorker {}
+
+#[wasm_bindgen]
+impl OpfsWorker {
+    /// Preinitializes the worker.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Handles the init message and starts serving the file system.
+    #[wasm_bindgen]
+    pub fn handle(&mut self, msg: JsValue) -> std::result::Result<(), utils::Error> {
+        let init = unsafe { OpfsInit::try_from_js(msg) }?;
+        wasm_bindgen_futures::spawn_local(OpfsState::run(init));
+        Ok(())
+    }
+}
+
+/// A file opened through a [`FileSystemSyncAccessHandle`], which is shared
+/// by all the opened handles of the file since it locks the file.
+#[derive(Debug)]
+struct SyncHandle {
+    handle: FileSystemSyncAccessHandle,
+    /// Number of opened handles of the file
+    refs: usize,
+    /// When the file was last modified, in nanoseconds since the epoch,
+    /// since it can't be queried while the file is locked
+    modified: u64,
+}
+
+impl SyncHandle {
+    fn size(&self) -> Result<u64> {
+        let size = self.handle.get_size().map_err(fs_error)?;
+        Ok(size as u64)
+    }
+
+    fn read(&self, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let options = FileSystemReadWriteOptions::new();
+        options.set_at(offset as f64);
+
+        let mut data = vec![0; len];
+        let read = self
+            .handle
+            .read_with_u8_array_and_options(&mut data, &options)
+            .map_err(fs_error)?;
+        data.truncate(read as usize);
+        Ok(data)
+    }
+
+    fn write(&mut self, offset: Option<u64>, data: &[u8]) -> Result<u64> {
+        let offset = match offset {
+            Some(offset) => offset,
+            None => self.size()?,
+        };
+        let options = FileSystemReadWriteOptions::new();
+        options.set_at(offset as f64);
+
+        let written = self
+            .handle
+            .write_with_u8_array_and_options(data, &options)
+            .map_err(fs_error)?;
+        self.modified = now();
+        Ok(offset + written as u64)
+    }
+
+    fn set_len(&mut self, len: u64) -> Result<()> {
+        self.handle.truncate_with_f64(len as f64).map_err(fs_error)?;
+        self.modified = now();
+        Ok(())
+    }
+}
+
+/// A directory or a file of the OPFS.
+enum Entry {
+    Dir(FileSystemDirectoryHandle),
+    File(FileSystemFileHandle),
+}
+
+/// The state of the worker owning the OPFS handles.
+#[derive(Debug)]
+pub(crate) struct OpfsState {
+    /// The directory holding the file system.
+    root: FileSystemDirectoryHandle,
+    /// The files locked by this worker, by their path.
+    handles: HashMap<String, SyncHandle>,
+    /// The path of each opened file.
+    files: HashMap<u64, String>,
+    /// Next opened file id.
+    next_id: u64,
+}
+
+impl OpfsState {
+    /// Serves the file system on this web worker until every handle to it
+    /// is dropped.
+    async fn run(init: OpfsInit) {
+        let OpfsInit { mut msg_rx, name } = init;
+
+        match Self::root(&name).await {
+            Ok(root) => {
+                let mut state = Self {
+                    root,
+                    handles: HashMap::new(),
+                    files: HashMap::new(),
+                    next_id: 1,
+                };
+                while let Some(msg) = msg_rx.recv().await {
+                    state.execute(msg).await;
+                }
+                for (_, handle) in state.handles.drain() {
+                    handle.handle.close();
+                }
+            }
+            Err(err) => {
+                tracing::error!(%name, error = &*err, "Unable to open the OPFS directory");
+            }
+        }
+
+        tracing::debug!(%name, "OPFS worker exiting");
+        let scope: DedicatedWorkerGlobalScope = js_sys::global().unchecked_into();
+        scope.close();
+    }
+
+    /// The directory `name` of the OPFS, or its root if `name` is empty.
+    async fn root(name: &str) -> anyhow::Result<FileSystemDirectoryHandle> {
+        let scope: WorkerGlobalScope = js_sys::global().unchecked_into();
+        let root = JsFuture::from(scope.navigator().storage().get_directory())
+            .await
+            .map_err(utils::js_error)?;
+        let root: FileSystemDirectoryHandle = root.unchecked_into();
+
+        if name.is_empty() {
+            return Ok(root);
+        }
+        Ok(get_dir(&root, name, true).await?)
+    }
+
+    /// Executes a message, a closed reply channel means the handle which
+    /// sent it is gone.
+    async fn execute(&mut self, msg: OpfsMsg) {
+        match msg {
+            OpfsMsg::Metadata { path, reply } => {
+                let _ = reply.send(self.metadata(&path).await);
+            }
+            OpfsMsg::ReadDir { path, reply } => {
+                let _ = reply.send(self.read_dir(&path).await);
+            }
+            OpfsMsg::CreateDir { path, reply } => {
+                let _ = reply.send(self.create_dir(&path).await);
+            }
+            OpfsMsg::RemoveDir { path, reply } => {
+                let _ = reply.send(self.remove_dir(&path).await);
+            }
+            OpfsMsg::RemoveFile { path, reply } => {
+                let _ = reply.send(self.remove_file(&path).await);
+            }
+            OpfsMsg::Rename { from, to, reply } => {
+                let _ = reply.send(self.rename(&from, &to).await);
+            }
+            OpfsMsg::Statfs { reply } => {
+                let _ = reply.send(statfs().await);
+            }
+            OpfsMsg::Open { path, conf, reply } => {
+                let _ = reply.send(self.open(&path, &conf).await);
+            }
+            OpfsMsg::Read {
+                id,
+                offset,
+                len,
+                reply,
+            } => {
+                let _ = reply.send(self.file(id).and_then(|file| file.read(offset, len)));
+            }
+            OpfsMsg::Write {
+                id,
+                offset,
+                data,
+                reply,
+            } => {
+                let _ = reply.send(self.file(id).and_then(|file| file.write(offset, &data)));
+            }
+            OpfsMsg::Size { id, reply } => {
+                let _ = reply.send(self.file(id).and_then(|file| file.size()));
+            }
+            OpfsMsg::SetLen { id, len, reply } => {
+                let _ = reply.send(self.file(id).and_then(|file| file.set_len(len)));
+            }
+            OpfsMsg::Flush { id, reply } => {
+                let _ = reply.send(
+                    self.file(id)
+                        .and_then(|file| file.handle.flush().map_err(fs_error)),
+                );
+            }
+            OpfsMsg::Close { id } => self.close(id),
+        }
+    }
+
+    /// The directory `path` is in and the name of `path` in it.
+    async fn parent(&self, path: &Path) -> Result<(FileSystemDirectoryHandle, String)> {
+        let mut names = components(path)?;
+        let name = names.pop().ok_or(FsError::InvalidInput)?;
+
+        let mut dir = self.root.clone();
+        for name in names {
+            dir = get_dir(&dir, &name, false).await?;
+        }
+        Ok((dir, name))
+    }
+
+    async fn entry(&self, path: &Path) -> Result<Entry> {
+        if components(path)?.is_empty() {
+            return Ok(Entry::Dir(self.root.clone()));
+        }
+
+        let (parent, name) = self.parent(path).await?;
+        match get_file(&parent, &name, false).await {
+            Ok(file) => Ok(Entry::File(file)),
+            Err(FsError::NotAFile) => Ok(Entry::Dir(get_dir(&parent, &name, false).await?)),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<Metadata> {
+        match self.entry(path).await? {
+            Entry::Dir(_) => Ok(dir_metadata()),
+            Entry::File(file) => self.file_metadata(path, &file).await,
+        }
+    }
+
+    async fn file_metadata(&self, path: &Path, file: &FileSystemFileHandle) -> Result<Metadata> {
+        let (len, modified) = match self.handles.get(&key(path)?) {
+            // The locked files can't be read through their file handle
+            Some(handle) => (handle.size()?, handle.modified),
+            None => {
+                let file: web_sys::File = JsFuture::from(file.get_file())
+                    .await
+                    .map_err(fs_error)?
+                    .unchecked_into();
+                (file.size() as u64, (file.last_modified() * 1_000_000.) as u64)
+            }
+        };
+
+        Ok(Metadata {
+            ft: FileType::new_file(),
+            accessed: modified,
+            created: modified,
+            modified,
+            len,
+        })
+    }
+
+    async fn read_dir(&self, path: &Path) -> Result<Vec<DirEntry>> {
+        let Entry::Dir(dir) = self.entry(path).await? else {
+            return Err(FsError::BaseNotDirectory);
+        };
+
+        let mut entries = Vec::new();
+        let iter = dir.values();
+        loop {
+            let next = iter.next().map_err(fs_error)?;
+            let next: IteratorNext = JsFuture::from(next)
+                .await
+                .map_err(fs_error)?
+                .unchecked_into();
+            if next.done() {
+                break;
+            }
+
+            let handle: FileSystemHandle = next.value().unchecked_into();
+            let path = path.join(handle.name());
+            let metadata = match handle.kind() {
+                FileSystemHandleKind::File => {
+                    self.file_metadata(&path, handle.unchecked_ref()).await
+                }
+                _ => Ok(dir_metadata()),
+            };
+            entries.push(DirEntry { path, metadata });
+        }
+
+        Ok(entries)
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        let (parent, name) = self.parent(path).await?;
+        if exists(&parent, &name).await? {
+            return Err(FsError::AlreadyExists);
+        }
+        get_dir(&parent, &name, true).await?;
+        Ok(())
+    }
+
+    async fn remove_dir(&self, path: &Path) -> Result<()> {
+        if let Entry::File(_) = self.entry(path).await? {
+            return Err(FsError::BaseNotDirectory);
+        }
+        let (parent, name) = self.parent(path).await?;
+        remove_entry(&parent, &name, false).await
+    }
+
+    async fn remove_file(&mut self, path: &Path) -> Result<()> {
+        if let Entry::Dir(_) = self.entry(path).await? {
+            return Err(FsError::NotAFile);
+        }
+        let (parent, name) = self.parent(path).await?;
+        // This worker is the only one locking the file, it's unlocked so
+        // that it can be removed and its opened handles fail from now on
+        self.unlock(path)?;
+        remove_entry(&parent, &name, false).await
+    }
+
+    /// Renames by copying since the OPFS can only move entries in some
+    /// browsers, the opened handles of the entries fail from now on.
+    async fn rename(&mut self, from: &Path, to: &Path) -> Result<()> {
+        let from_key = key(from)?;
+        let to_key = key(to)?;
+        if from_key == to_key {
+            return self.metadata(from).await.map(|_| ());
+        }
+        if to_key.starts_with(&format!("{from_key}/")) {
+            return Err(FsError::InvalidInput);
+        }
+
+        let entry = self.entry(from).await?;
+        let (from_parent, from_name) = self.parent(from).await?;
+        let (to_parent, to_name) = self.parent(to).await?;
+
+        // Like rename(2), a file replaces a file and a directory replaces an
+        // empty directory
+        match (&entry, self.entry(to).await) {
+            (_, Err(FsError::EntryNotFound)) => {}
+            (_, Err(err)) => return Err(err),
+            (Entry::File(_), Ok(Entry::File(_))) => {
+                self.unlock(to)?;
+                remove_entry(&to_parent, &to_name, false).await?;
+            }
+            (Entry::Dir(_), Ok(Entry::Dir(_))) => {
+                remove_entry(&to_parent, &to_name, false).await?;
+            }
+            (Entry::File(_), Ok(Entry::Dir(_))) => return Err(FsError::NotAFile),
+            (Entry::Dir(_), Ok(Entry::File(_))) => return Err(FsError::BaseNotDirectory),
+        }
+
+        self.unlock(from)?;
+        copy(entry, &to_parent, &to_name).await?;
+        remove_entry(&from_parent, &from_name, true).await
+    }
+
+    async fn open(&mut self, path: &Path, conf: &OpenOptionsConfig) -> Result<u64> {
+        let (parent, name) = self.parent(path).await?;
+        if conf.create_new && exists(&parent, &name).await? {
+            return Err(FsError::AlreadyExists);
+        }
+        let file = get_file(&parent, &name, conf.create || conf.create_new).await?;
+
+        let key = key(path)?;
+        if !self.handles.contains_key(&key) {
+            let modified = self.file_metadata(path, &file).await?.modified;
+            let handle = JsFuture::from(file.create_sync_access_handle())
+                .await
+                .map_err(fs_error)?
+                .unchecked_into();
+            let handle = SyncHandle {
+                handle,
+                refs: 0,
+                modified,
+            };
+            self.handles.insert(key.clone(), handle);
+        }
+
+        let handle = self.handles.get_mut(&key).unwrap();
+        if conf.truncate && (conf.write || conf.append) {
+            handle.set_len(0)?;
+        }
+        handle.refs += 1;
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.files.insert(id, key);
+        Ok(id)
+    }
+
+    fn file(&mut self, id: u64) -> Result<&mut SyncHandle> {
+        let key = self.files.get(&id).ok_or(FsError::InvalidFd)?;
+        self.handles.get_mut(key).ok_or(FsError::InvalidFd)
+    }
+
+    fn close(&mut self, id: u64) {
+        let Some(key) = self.files.remove(&id) else {
+            return;
+        };
+        let Some(handle) = self.handles.get_mut(&key) else {
+            return;
+        };
+
+        handle.refs -= 1;
+        if handle.refs == 0 {
+            handle.handle.close();
+            self.handles.remove(&key);
+        }
+    }
+
+    /// Unlocks the file at `path`, or the files in the directory at `path`,
+    /// forgetting their opened handles.
+    fn unlock(&mut self, path: &Path) -> Result<()> {
+        let key = key(path)?;
+        let prefix = format!("{key}/");
+        let locked = |path: &String| *path == key || path.starts_with(&prefix);
+
+        self.handles.retain(|path, handle| {
+            if locked(path) {
+                handle.handle.close();
+            }
+            !locked(path)
+        });
+        self.files.retain(|_, path| !locked(path));
+        Ok(())
+    }
+}
+
+/// Copies a directory or a file into `parent` under `name`.
+async fn copy(entry: Entry, parent: &FileSystemDirectoryHandle, name: &str) -> Result<()> {
+    match entry {
+        Entry::File(src) => {
+            let dst = get_file(parent, name, true).await?;
+            let src = sync_access_handle(&src).await?;
+            let dst = sync_access_handle(&dst).await?;
+
+            let result = copy_contents(&src, &dst);
+            src.close();
+            dst.close();
+            result
+        }
+        Entry::Dir(src) => {
+            let dst = get_dir(parent, name, true).await?;
+            let iter = src.values();
+            loop {
+                let next = iter.next().map_err(fs_error)?;
+                let next: IteratorNext = JsFuture::from(next)
+                    .await
+                    .map_err(fs_error)?
+                    .unchecked_into();
+                if next.done() {
+                    break;
+                }
+
+                let handle: FileSystemHandle = next.value().unchecked_into();
+                let name = handle.name();
+                let entry = match handle.kind() {
+                    FileSystemHandleKind::File => Entry::File(handle.unchecked_into()),
+                    _ => Entry::Dir(handle.unchecked_into()),
+                };
+                Box::pin(copy(entry, &dst, &name)).await?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn copy_contents(src: &FileSystemSyncAccessHandle, dst: &FileSystemSyncAccessHandle) -> Result<()> {
+    let len = src.get_size().map_err(fs_error)?;
+    let mut data = vec![0; len as usize];
+    src.read_with_u8_array(&mut data).map_err(fs_error)?;
+
+    dst.truncate_with_f64(0.).map_err(fs_error)?;
+    dst.write_with_u8_array(&data).map_err(fs_error)?;
+    dst.flush().map_err(fs_error)
+}
+
+async fn sync_access_handle(file: &FileSystemFileHandle) -> Result<FileSystemSyncAccessHandle> {
+    let handle = JsFuture::from(file.create_sync_access_handle())
+        .await
+        .map_err(fs_error)?;
+    Ok(handle.unchecked_into())
+}
+
+/// The quota of the origin, which is shared with its other storages.
+async fn statfs() -> Result<FsStats> {
+    let scope: WorkerGlobalScope = js_sys::global().unchecked_into();
+    let estimate: Promise = scope.navigator().storage().estimate().map_err(fs_error)?;
+    let estimate: StorageEstimate = JsFuture::from(estimate)
+        .await
+        .map_err(fs_error)?
+        .unchecked_into();
+
+    let quota = estimate.get_quota().unwrap_or_default() as u64;
+    let usage = estimate.get_usage().unwrap_or_default() as u64;
+    let blocks = quota / BLOCK_SIZE;
+    let blocks_free = quota.saturating_sub(usage) / BLOCK_SIZE;
+    Ok(FsStats {
+        block_size: BLOCK_SIZE,
+        blocks,
+        blocks_free,
+        files: blocks,
+        files_free: blocks_free,
+    })
+}
+
+async fn get_dir(
+    dir: &FileSystemDirectoryHandle,
+    name: &str,
+    create: bool,
+) -> Result<FileSystemDirectoryHandle> {
+    let options = FileSystemGetDirectoryOptions::new();
+    options.set_create(create);
+
+    let handle = JsFuture::from(dir.get_directory_handle_with_options(name, &options))
+        .await
+        .map_err(fs_error)?;
+    Ok(handle.unchecked_into())
+}
+
+async fn get_file(
+    dir: &FileSystemDirectoryHandle,
+    name: &str,
+    create: bool,
+) -> Result<FileSystemFileHandle> {
+    let options = FileSystemGetFileOptions::new();
+    options.set_create(create);
+
+    let handle = JsFuture::from(dir.get_file_handle_with_options(name, &options))
+        .await
+        .map_err(|err| match fs_error(err) {
+            FsError::BaseNotDirectory => FsError::NotAFile,
+            err => err,
+        })?;
+    Ok(handle.unchecked_into())
+}
+
+async fn exists(dir: &FileSystemDirectoryHandle, name: &str) -> Result<bool> {
+    match get_file(dir, name, false).await {
+        Ok(_) | Err(FsError::NotAFile) => Ok(true),
+        Err(FsError::EntryNotFound) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+async fn remove_entry(dir: &FileSystemDirectoryHandle, name: &str, recursive: bool) -> Result<()> {
+    let options = FileSystemRemoveOptions::new();
+    options.set_recursive(recursive);
+
+    JsFuture::from(dir.remove_entry_with_options(name, &options))
+        .await
+        .map_err(fs_error)?;
+    Ok(())
+}
+
+/// The names of the directories leading to `path` and of `path` itself.
+fn components(path: &Path) -> Result<Vec<String>> {
+    path.components()
+        .filter_map(|component| match component {
+            Component::RootDir | Component::CurDir => None,
+            Component::Normal(name) => Some(name.to_str().map(String::from)),
+            Component::ParentDir | Component::Prefix(_) => Some(None),
+        })
+        .map(|name| name.ok_or(FsError::InvalidInput))
+        .collect()
+}
+
+/// The normalized form of `path`, which identifies its file.
+fn key(path: &Path) -> Result<String> {
+    Ok(components(path)?.join("/"))
+}
+
+fn dir_metadata() -> Metadata {
+    Metadata {
+        ft: FileType::new_dir(),
+        ..Default::default()
+    }
+}
+
+/// The current time in nanoseconds since the epoch.
+fn now() -> u64 {
+    (js_sys::Date::now() * 1_000_000.) as u64
+}
+
+/// Converts the `DOMException`s thrown by the OPFS.
+fn fs_error(err: JsValue) -> FsError {
+    let name = match err.dyn_ref::<DomException>() {
+        Some(exception) => exception.name(),
+        None if err.is_instance_of::<js_sys::TypeError>() => return FsError::InvalidInput,
+        None => String::new(),
+    };
+
+    match name.as_str() {
+        "NotFoundError" => FsError::EntryNotFound,
+        "TypeMismatchError" => FsError::BaseNotDirectory,
+        "InvalidModificationError" => FsError::DirectoryNotEmpty,
+        "NoModificationAllowedError" => FsError::Lock,
+        "QuotaExceededError" => FsError::StorageFull,
+        "NotAllowedError" | "SecurityError" => FsError::PermissionDenied,
+        "InvalidStateError" => FsError::InvalidFd,
+        _ => {
+            tracing::warn!(error = ?err, "Unexpected OPFS error");
+            FsError::UnknownError
+        }
+    }
+}
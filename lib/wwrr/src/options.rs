@@ -1,9 +1,9 @@
-use std::{collections::BTreeMap, sync::Arc};
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
 
 use anyhow::Context;
-use js_sys::Array;
+use js_sys::{Array, Reflect};
 use utils::Error;
-use virtual_fs::TmpFileSystem;
+use virtual_fs::{limiter::FsQuota, TmpFileSystem};
 use wasm_bindgen::convert::TryFromJsValue;
 use wasm_bindgen::{prelude::wasm_bindgen, JsCast, JsValue, UnwrapThrowExt};
 use wasmer_wasix::WasiEnvBuilder;
@@ -42,14 +42,32 @@ type CommonOptions = {
      *
      * This maps mount locations to the {@link Directory} being mounted. As a
      * shortcut, if {@link DirectoryInit} is provided, a new {@link Directory}
-     * will be instantiated and mounted.
+     * will be instantiated and mounted. Use {@link Directory.opfs} or
+     * {@link Directory.indexedDb} to mount persistent storage.
      *
      * Avoid mounting directly to `"/"` as it may clobber a package's bundled
      * files.
      */
     mount?: Record<string, DirectoryInit | Directory>;
+    /**
+     * Limits on the in-memory root file system, writing past `bytes` or
+     * creating more than `inodes` files and directories fails with
+     * `ENOSPC`. The `tmpfs` mounted by the program also count towards
+     * `bytes`.
+     */
+    fsQuota?: { bytes?: number; inodes?: number };
     /** Number of web workers to pre-start to execute threads */
     prestarted_workers?: number;
+    /**
+     * Maximum number of threads running at the same time. Threads spawned
+     * beyond it wait for a running thread to exit.
+     */
+    maxConcurrency?: number;
+    /**
+     * Pre-started web workers which stay idle for longer than this many
+     * milliseconds are terminated.
+     */
+    idleWorkerTimeout?: number;
 };
 
 /**
@@ -110,8 +128,17 @@ extern "C" {
     #[wasm_bindgen(method, getter)]
     fn mount(this: &CommonOptions) -> OptionalDirectories;
 
+    #[wasm_bindgen(method, getter)]
+    fn fsQuota(this: &CommonOptions) -> JsValue;
+
     #[wasm_bindgen(method, getter)]
     fn prestarted_workers(this: &CommonOptions) -> Option<usize>;
+
+    #[wasm_bindgen(method, getter)]
+    fn maxConcurrency(this: &CommonOptions) -> Option<usize>;
+
+    #[wasm_bindgen(method, getter)]
+    fn idleWorkerTimeout(this: &CommonOptions) -> Option<u32>;
 }
 
 impl CommonOptions {
@@ -132,6 +159,22 @@ impl CommonOptions {
         }
     }
 
+    pub(crate) fn parse_fs_quota(&self) -> Result<FsQuota, Error> {
+        let quota = self.fsQuota();
+        if quota.is_undefined() {
+            return Ok(FsQuota::default());
+        }
+
+        let limit = |key: &str| -> Result<Option<u64>, Error> {
+            let value = Reflect::get(&quota, &JsValue::from_str(key)).map_err(Error::js)?;
+            Ok(value.as_f64().map(|limit| limit as u64))
+        };
+        Ok(FsQuota {
+            bytes: limit("bytes")?,
+            inodes: limit("inodes")?,
+        })
+    }
+
     pub(crate) fn read_stdin(&self) -> Option<Vec<u8>> {
         self.stdin().map(|s| s.as_bytes())
     }
@@ -241,12 +284,18 @@ impl RunOptions {
         }
 
         let fs = self.filesystem()?;
-        builder.set_fs(Box::new(fs));
+        builder.set_sandbox_fs(fs);
         builder.add_preopen_dir("/")?;
 
         if let Some(n) = self.prestarted_workers() {
             builder.set_prestarted_workers(n);
         }
+        if let Some(max) = self.maxConcurrency() {
+            builder.get_worker_pool_mut().max_concurrency = Some(max);
+        }
+        if let Some(millis) = self.idleWorkerTimeout() {
+            builder.get_worker_pool_mut().idle_timeout = Some(Duration::from_millis(millis.into()));
+        }
 
         builder.set_wbg_js_module_name(self.bindings());
 
@@ -255,6 +304,7 @@ impl RunOptions {
 
     pub(crate) fn filesystem(&self) -> Result<TmpFileSystem, Error> {
         let root = TmpFileSystem::new();
+        root.set_quota(self.parse_fs_quota()?);
 
         for (dest, fs) in self.mounted_directories()? {
             tracing::trace!(%dest, ?fs, "Mounting directory");
@@ -0,0 +1,56 @@
+use async_trait::async_trait;
+use js_sys::{Function, Promise, Reflect};
+use utils::Error;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+use super::{SignalMsg, Signaling};
+
+/// The [`Signaling`] of a `PeerSignaling` object.
+#[derive(Debug)]
+pub(crate) struct JsSignaling {
+    signaling: js_sys::Object,
+}
+
+impl JsSignaling {
+    pub fn new(signaling: js_sys::Object) -> Self {
+        JsSignaling { signaling }
+    }
+
+    /// Calls the method `name`, awaiting the promise it returns if any.
+    async fn call(&self, name: &str, args: &[JsValue]) -> Result<JsValue, Error> {
+        let method: Function = Reflect::get(&self.signaling, &JsValue::from_str(name))
+            .map_err(Error::js)?
+            .dyn_into()
+            .map_err(|_| anyhow::anyhow!("the signaling has no \"{name}\" method"))?;
+        let args: js_sys::Array = args.iter().collect();
+        let value = method.apply(&self.signaling, &args).map_err(Error::js)?;
+        JsFuture::from(Promise::resolve(&value))
+            .await
+            .map_err(Error::js)
+    }
+}
+
+#[async_trait(?Send)]
+impl Signaling for JsSignaling {
+    async fn join(&self, network: &str, token: &str) -> Result<bool, Error> {
+        let args = [JsValue::from_str(network), JsValue::from_str(token)];
+        let offerer = self.call("join", &args).await?;
+        Ok(offerer.is_truthy())
+    }
+
+    async fn send(&self, msg: &SignalMsg) -> Result<(), Error> {
+        let msg = serde_wasm_bindgen::to_value(msg).map_err(Error::js)?;
+        self.call("send", &[msg]).await?;
+        Ok(())
+    }
+
+    async fn receive(&self) -> Result<Option<SignalMsg>, Error> {
+        let msg = self.call("receive", &[]).await?;
+        if msg.is_null() || msg.is_undefined() {
+            return Ok(None);
+        }
+        let msg = serde_wasm_bindgen::from_value(msg).map_err(Error::js)?;
+        Ok(Some(msg))
+    }
+}
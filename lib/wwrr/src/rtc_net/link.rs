@@ -0,0 +1,324 @@
+use std::{
+    future::{poll_fn, Future},
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use js_sys::{ArrayBuffer, Uint8Array};
+use tokio::sync::mpsc;
+use utils::{Error, GlobalScope};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    MessageEvent, RtcConfiguration, RtcDataChannel, RtcDataChannelEvent, RtcDataChannelState,
+    RtcDataChannelType, RtcIceCandidateInit, RtcPeerConnection, RtcPeerConnectionIceEvent,
+    RtcPeerConnectionState, RtcSdpType, RtcSessionDescriptionInit,
+};
+
+use super::{SignalMsg, Signaling};
+use crate::ws_net::frame::Frame;
+
+/// The label of the data channel the frames are exchanged over.
+const CHANNEL_LABEL: &str = "wasix-net";
+
+/// How long the peers have to connect once they joined the network.
+const NEGOTIATION_TIMEOUT_MS: i32 = 30_000;
+
+/// What the peer connection tells.
+#[derive(Debug)]
+enum LinkEvent {
+    /// A local ICE candidate, to be sent to the peer.
+    Candidate(SignalMsg),
+    /// The data channel created by the offerer.
+    Channel(RtcDataChannel),
+    Open,
+    Frame(Bytes),
+    Closed,
+}
+
+/// What happens next while negotiating.
+enum Step {
+    Signal(Result<Option<SignalMsg>, Error>),
+    Event(Option<LinkEvent>),
+    TimedOut,
+}
+
+/// The data channel to the peer, which is closed once dropped.
+pub(crate) struct Link {
+    pc: RtcPeerConnection,
+    channel: Option<RtcDataChannel>,
+    /// Whether this end made the offer
+    pub offerer: bool,
+    events_tx: mpsc::UnboundedSender<LinkEvent>,
+    events: mpsc::UnboundedReceiver<LinkEvent>,
+    _on_ice_candidate: Closure<dyn FnMut(RtcPeerConnectionIceEvent)>,
+    _on_data_channel: Closure<dyn FnMut(RtcDataChannelEvent)>,
+    _on_state_change: Closure<dyn FnMut(JsValue)>,
+    on_open: Closure<dyn FnMut(JsValue)>,
+    on_message: Closure<dyn FnMut(MessageEvent)>,
+    on_close: Closure<dyn FnMut(JsValue)>,
+}
+
+impl Link {
+    /// Joins `network` through the signaling and connects to the peer met
+    /// there, the one which joined first making the offer.
+    pub async fn negotiate(
+        signaling: &dyn Signaling,
+        ice_servers: &JsValue,
+        network: &str,
+        token: &str,
+    ) -> Result<Self, Error> {
+        let offerer = signaling.join(network, token).await?;
+        let mut link = Link::new(ice_servers, offerer)?;
+        if offerer {
+            let channel = link.pc.create_data_channel(CHANNEL_LABEL);
+            link.watch(channel);
+            let offer = JsFuture::from(link.pc.create_offer())
+                .await
+                .map_err(Error::js)?;
+            let sdp = link.set_local(offer.unchecked_ref()).await?;
+            signaling.send(&SignalMsg::Offer { sdp }).await?;
+        }
+
+        let mut timeout = JsFuture::from(GlobalScope::current().sleep(NEGOTIATION_TIMEOUT_MS));
+        let mut receive = signaling.receive();
+        // The candidates of the peer wait for its description
+        let mut candidates = Vec::new();
+        loop {
+            let step = poll_fn(|cx| {
+                if let Poll::Ready(msg) = receive.as_mut().poll(cx) {
+                    return Poll::Ready(Step::Signal(msg));
+                }
+                if let Poll::Ready(event) = link.events.poll_recv(cx) {
+                    return Poll::Ready(Step::Event(event));
+                }
+                if std::pin::Pin::new(&mut timeout).poll(cx).is_ready() {
+                    return Poll::Ready(Step::TimedOut);
+                }
+                Poll::Pending
+            })
+            .await;
+
+            match step {
+                Step::Signal(msg) => {
+                    receive = signaling.receive();
+                    match msg? {
+                        Some(SignalMsg::Offer { sdp }) if !offerer => {
+                            link.set_remote(RtcSdpType::Offer, &sdp).await?;
+                            link.add_candidates(&mut candidates).await;
+                            let answer = JsFuture::from(link.pc.create_answer())
+                                .await
+                                .map_err(Error::js)?;
+                            let sdp = link.set_local(answer.unchecked_ref()).await?;
+                            signaling.send(&SignalMsg::Answer { sdp }).await?;
+                        }
+                        Some(SignalMsg::Answer { sdp }) if offerer => {
+                            link.set_remote(RtcSdpType::Answer, &sdp).await?;
+                            link.add_candidates(&mut candidates).await;
+                        }
+                        Some(candidate @ SignalMsg::Candidate { .. }) => {
+                            candidates.push(candidate);
+                            if link.pc.remote_description().is_some() {
+                                link.add_candidates(&mut candidates).await;
+                            }
+                        }
+                        Some(msg) => tracing::warn!(?msg, "Unexpected signaling message"),
+                        None => {
+                            return Err(anyhow::anyhow!("the signaling ended before the peer connected").into());
+                        }
+                    }
+                }
+                Step::Event(Some(LinkEvent::Candidate(candidate))) => {
+                    signaling.send(&candidate).await?;
+                }
+                Step::Event(Some(LinkEvent::Channel(channel))) => link.watch(channel),
+                Step::Event(Some(LinkEvent::Open)) => return Ok(link),
+                Step::Event(_) => {
+                    return Err(anyhow::anyhow!("the connection to the peer failed").into());
+                }
+                Step::TimedOut => {
+                    return Err(anyhow::anyhow!("timed out connecting to the peer").into());
+                }
+            }
+        }
+    }
+
+    fn new(ice_servers: &JsValue, offerer: bool) -> Result<Self, Error> {
+        let config = RtcConfiguration::new();
+        if !ice_servers.is_undefined() && !ice_servers.is_null() {
+            config.set_ice_servers(ice_servers);
+        }
+        let pc = RtcPeerConnection::new_with_configuration(&config).map_err(Error::js)?;
+
+        let (events_tx, events) = mpsc::unbounded_channel();
+        let on_ice_candidate = Closure::<dyn FnMut(RtcPeerConnectionIceEvent)>::new({
+            let events_tx = events_tx.clone();
+            move |ev: RtcPeerConnectionIceEvent| {
+                // The end of the candidates is told with an empty one
+                let Some(candidate) = ev.candidate().filter(|c| !c.candidate().is_empty()) else {
+                    return;
+                };
+                let _ = events_tx.send(LinkEvent::Candidate(SignalMsg::Candidate {
+                    candidate: candidate.candidate(),
+                    sdp_mid: candidate.sdp_mid(),
+                    sdp_m_line_index: candidate.sdp_m_line_index(),
+                }));
+            }
+        });
+        let on_data_channel = Closure::<dyn FnMut(RtcDataChannelEvent)>::new({
+            let events_tx = events_tx.clone();
+            move |ev: RtcDataChannelEvent| {
+                let _ = events_tx.send(LinkEvent::Channel(ev.channel()));
+            }
+        });
+        // The data channel isn't always closed when the connection fails
+        let on_state_change = Closure::<dyn FnMut(JsValue)>::new({
+            let events_tx = events_tx.clone();
+            let pc = pc.clone();
+            move |_| {
+                if pc.connection_state() == RtcPeerConnectionState::Failed {
+                    let _ = events_tx.send(LinkEvent::Closed);
+                }
+            }
+        });
+        pc.set_onicecandidate(Some(on_ice_candidate.as_ref().unchecked_ref()));
+        pc.set_ondatachannel(Some(on_data_channel.as_ref().unchecked_ref()));
+        pc.set_onconnectionstatechange(Some(on_state_change.as_ref().unchecked_ref()));
+
+        let on_open = Closure::<dyn FnMut(JsValue)>::new({
+            let events_tx = events_tx.clone();
+            move |_| {
+                let _ = events_tx.send(LinkEvent::Open);
+            }
+        });
+        let on_message = Closure::<dyn FnMut(MessageEvent)>::new({
+            let events_tx = events_tx.clone();
+            move |ev: MessageEvent| {
+                if let Ok(buf) = ev.data().dyn_into::<ArrayBuffer>() {
+                    let frame = Uint8Array::new(&buf).to_vec();
+                    let _ = events_tx.send(LinkEvent::Frame(frame.into()));
+                }
+            }
+        });
+        let on_close = Closure::<dyn FnMut(JsValue)>::new({
+            let events_tx = events_tx.clone();
+            move |_| {
+                let _ = events_tx.send(LinkEvent::Closed);
+            }
+        });
+
+        Ok(Self {
+            pc,
+            channel: None,
+            offerer,
+            events_tx,
+            events,
+            _on_ice_candidate: on_ice_candidate,
+            _on_data_channel: on_data_channel,
+            _on_state_change: on_state_change,
+            on_open,
+            on_message,
+            on_close,
+        })
+    }
+
+    /// Exchanges the frames over `channel`, unless there's one already.
+    fn watch(&mut self, channel: RtcDataChannel) {
+        if self.channel.is_some() {
+            tracing::warn!(label = channel.label(), "Ignoring an extra data channel");
+            return;
+        }
+        channel.set_binary_type(RtcDataChannelType::Arraybuffer);
+        channel.set_onopen(Some(self.on_open.as_ref().unchecked_ref()));
+        channel.set_onmessage(Some(self.on_message.as_ref().unchecked_ref()));
+        channel.set_onclose(Some(self.on_close.as_ref().unchecked_ref()));
+        if channel.ready_state() == RtcDataChannelState::Open {
+            let _ = self.events_tx.send(LinkEvent::Open);
+        }
+        self.channel = Some(channel);
+    }
+
+    /// Sets the local description, returning its SDP.
+    async fn set_local(&self, desc: &RtcSessionDescriptionInit) -> Result<String, Error> {
+        JsFuture::from(self.pc.set_local_description(desc))
+            .await
+            .map_err(Error::js)?;
+        let desc = self
+            .pc
+            .local_description()
+            .ok_or_else(|| anyhow::anyhow!("no local description"))?;
+        Ok(desc.sdp())
+    }
+
+    async fn set_remote(&self, ty: RtcSdpType, sdp: &str) -> Result<(), Error> {
+        let desc = RtcSessionDescriptionInit::new(ty);
+        desc.set_sdp(sdp);
+        JsFuture::from(self.pc.set_remote_description(&desc))
+            .await
+            .map_err(Error::js)?;
+        Ok(())
+    }
+
+    /// Adds the ICE candidates of the peer, those which don't fit are
+    /// skipped since others may.
+    async fn add_candidates(&self, candidates: &mut Vec<SignalMsg>) {
+        for msg in candidates.drain(..) {
+            let SignalMsg::Candidate {
+                candidate,
+                sdp_mid,
+                sdp_m_line_index,
+            } = msg
+            else {
+                continue;
+            };
+            let init = RtcIceCandidateInit::new(&candidate);
+            init.set_sdp_mid(sdp_mid.as_deref());
+            init.set_sdp_m_line_index(sdp_m_line_index);
+            let added = self
+                .pc
+                .add_ice_candidate_with_opt_rtc_ice_candidate_init(Some(&init));
+            if let Err(err) = JsFuture::from(added).await {
+                tracing::warn!(error = &*utils::js_error(err), "Unable to add an ICE candidate");
+            }
+        }
+    }
+
+    pub fn send(&self, frame: &Frame) {
+        let Some(channel) = self.channel.as_ref() else {
+            return;
+        };
+        // The frame is copied out of the shared memory, which the data
+        // channel can't send from
+        let frame = Uint8Array::from(frame.encode().as_slice());
+        if let Err(err) = channel.send_with_array_buffer(&frame.buffer()) {
+            tracing::warn!(error = &*utils::js_error(err), "Unable to send a frame to the peer");
+        }
+    }
+
+    /// Polls for the next frame received, which is `None` once the
+    /// connection is lost.
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Bytes>> {
+        loop {
+            match std::task::ready!(self.events.poll_recv(cx)) {
+                Some(LinkEvent::Frame(frame)) => return Poll::Ready(Some(frame)),
+                Some(LinkEvent::Closed) | None => return Poll::Ready(None),
+                Some(_) => {}
+            }
+        }
+    }
+}
+
+impl Drop for Link {
+    fn drop(&mut self) {
+        self.pc.set_onicecandidate(None);
+        self.pc.set_ondatachannel(None);
+        self.pc.set_onconnectionstatechange(None);
+        if let Some(channel) = self.channel.take() {
+            channel.set_onopen(None);
+            channel.set_onmessage(None);
+            channel.set_onclose(None);
+            channel.close();
+        }
+        self.pc.close();
+    }
+}
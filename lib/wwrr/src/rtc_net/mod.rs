@@ -0,0 +1,333 @@
+//! Virtual networking bridged with a peer over a WebRTC data channel, so
+//! that two instances in different browsers, or a browser and a native
+//! peer speaking the same frames, reach each other directly.
+//!
+//! # Design
+//!
+//! The peers meet through a [`Signaling`] implemented by the embedder, which
+//! `bridge` joins the network with: the peer which joined first makes the
+//! offer, and they exchange their descriptions and ICE candidates through
+//! it until the data channel is open. The two ends then form a
+//! point-to-point network where the offerer is [`OFFERER_IP`] and the
+//! answerer [`ANSWERER_IP`].
+//!
+//! The sockets are the ones of [`crate::ws_net`], whose [`Frame`]s are
+//! handled by a [`PeerState`] instead of a relay: it forwards them to the
+//! peer over the data channel, and opens the connections the peer makes to
+//! its listeners. Like the [`FsCallbacks`](crate::callback_fs::FsCallbacks),
+//! the peer connection and the signaling are driven by a task spawned on the
+//! thread which created the [`PeerNetworking`], since web workers can't
+//! always create peer connections.
+//!
+//! [`Frame`]: crate::ws_net::frame::Frame
+//! [`PeerState`]: peer::PeerState
+
+mod js;
+mod link;
+mod peer;
+
+use std::{
+    collections::VecDeque,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    rc::Rc,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+use utils::Error;
+use virtual_mio::{InterestHandler, InterestType};
+use virtual_net::{
+    Interest, IpCidr, NetworkError, Result, StreamSecurity, VirtualIoSource, VirtualNetworking,
+    VirtualTcpListener, VirtualTcpSocket, VirtualUdpSocket,
+};
+use wasm_bindgen::JsValue;
+
+pub(crate) use self::js::JsSignaling;
+use self::peer::PeerState;
+use crate::ws_net::{WsNetworking, WsTcpSocket};
+
+/// The address of the peer which made the offer.
+pub(crate) const OFFERER_IP: Ipv4Addr = Ipv4Addr::new(10, 254, 0, 1);
+
+/// The address of the peer which answered it.
+pub(crate) const ANSWERER_IP: Ipv4Addr = Ipv4Addr::new(10, 254, 0, 2);
+
+/// The prefix of the point-to-point network of the peers.
+const LINK_PREFIX: u8 = 30;
+
+/// How many connections wait to be accepted when the program doesn't say.
+const DEFAULT_BACKLOG: usize = 128;
+
+/// How the peers meet, implemented by the embedder.
+///
+/// The messages are opaque to the signaling, which only has to hand the
+/// ones sent by a peer to the other peer of the network, in order.
+#[async_trait(?Send)]
+pub trait Signaling: std::fmt::Debug {
+    /// Joins `network`, returning whether this peer makes the offer, which
+    /// is the case of the first one to join.
+    async fn join(&self, network: &str, token: &str) -> std::result::Result<bool, Error>;
+
+    async fn send(&self, msg: &SignalMsg) -> std::result::Result<(), Error>;
+
+    /// The next message sent by the peer, or `None` once it left.
+    async fn receive(&self) -> std::result::Result<Option<SignalMsg>, Error>;
+}
+
+/// A message exchanged through the [`Signaling`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SignalMsg {
+    Offer {
+        sdp: String,
+    },
+    Answer {
+        sdp: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Candidate {
+        candidate: String,
+        sdp_mid: Option<String>,
+        sdp_m_line_index: Option<u16>,
+    },
+}
+
+/// Messages sent from the [`PeerNetworking`] and its listeners to the task
+/// driving the peer connection, besides the ones of the sockets.
+#[derive(Debug)]
+pub(crate) enum PeerCtl {
+    Bridge {
+        network: String,
+        token: String,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    Unbridge {
+        reply: oneshot::Sender<Result<()>>,
+    },
+    IpList {
+        reply: oneshot::Sender<Result<Vec<IpCidr>>>,
+    },
+    /// Listens to `addr`, replying with the address listened to.
+    Listen {
+        addr: SocketAddr,
+        backlog: Arc<Mutex<Backlog>>,
+        reply: oneshot::Sender<Result<SocketAddr>>,
+    },
+    Unlisten {
+        port: u16,
+    },
+}
+
+/// The connections made by the peer which wait to be accepted.
+#[derive(Debug)]
+pub(crate) struct Backlog {
+    pending: VecDeque<(WsTcpSocket, SocketAddr)>,
+    max: usize,
+    interest: Interest,
+}
+
+impl Backlog {
+    /// Queues a connection, unless the backlog is full.
+    pub(crate) fn push(&mut self, socket: WsTcpSocket, peer: SocketAddr) -> bool {
+        if self.pending.len() >= self.max {
+            return false;
+        }
+        self.pending.push_back((socket, peer));
+        self.interest.notify(InterestType::Readable);
+        true
+    }
+}
+
+/// A TCP listener accepting the connections made by the peer, which stops
+/// listening once dropped.
+#[derive(Debug)]
+pub struct PeerTcpListener {
+    addr: SocketAddr,
+    backlog: Arc<Mutex<Backlog>>,
+    ctl_tx: mpsc::UnboundedSender<PeerCtl>,
+}
+
+impl Drop for PeerTcpListener {
+    fn drop(&mut self) {
+        let port = self.addr.port();
+        self.ctl_tx.send(PeerCtl::Unlisten { port }).ok();
+    }
+}
+
+impl VirtualIoSource for PeerTcpListener {
+    fn remove_handler(&mut self) {
+        self.backlog.lock().unwrap().interest.handler.take();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut backlog = self.backlog.lock().unwrap();
+        if !backlog.pending.is_empty() {
+            return Poll::Ready(Ok(backlog.pending.len()));
+        }
+        backlog.interest.read_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    fn poll_write_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        Poll::Pending
+    }
+}
+
+impl VirtualTcpListener for PeerTcpListener {
+    fn try_accept(&mut self) -> Result<(Box<dyn VirtualTcpSocket + Sync>, SocketAddr)> {
+        let (socket, peer) = self
+            .backlog
+            .lock()
+            .unwrap()
+            .pending
+            .pop_front()
+            .ok_or(NetworkError::WouldBlock)?;
+        Ok((Box::new(socket), peer))
+    }
+
+    fn set_handler(&mut self, mut handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        let mut backlog = self.backlog.lock().unwrap();
+        if !backlog.pending.is_empty() {
+            handler.push_interest(InterestType::Readable);
+        }
+        backlog.interest.handler = Some(handler);
+        Ok(())
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn set_ttl(&mut self, _ttl: u8) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn ttl(&self) -> Result<u8> {
+        Err(NetworkError::Unsupported)
+    }
+}
+
+/// Networking bridged with a peer met through a [`Signaling`].
+#[derive(Debug, Clone)]
+pub struct PeerNetworking {
+    /// Opens the sockets, whose frames go to the peer
+    sockets: WsNetworking,
+    ctl_tx: mpsc::UnboundedSender<PeerCtl>,
+}
+
+impl PeerNetworking {
+    /// Creates the networking, driving the signaling and the peer
+    /// connection on the current thread until every handle to it is
+    /// dropped.
+    ///
+    /// `ice_servers` are the `RTCIceServer`s of the peer connection, without
+    /// which the peers only reach each other on the same network.
+    pub(crate) fn new(signaling: Rc<dyn Signaling>, ice_servers: JsValue) -> Self {
+        let (msg_tx, msg_rx) = mpsc::unbounded_channel();
+        let (ctl_tx, ctl_rx) = mpsc::unbounded_channel();
+        let state = PeerState::new(signaling, ice_servers, msg_tx.downgrade());
+        wasm_bindgen_futures::spawn_local(state.run(ctl_rx, msg_rx));
+
+        Self {
+            sockets: WsNetworking::with_channel(msg_tx),
+            ctl_tx,
+        }
+    }
+
+    async fn request<T>(
+        &self,
+        ctl: impl FnOnce(oneshot::Sender<Result<T>>) -> PeerCtl,
+    ) -> Result<T> {
+        let (reply, rx) = oneshot::channel();
+        self.ctl_tx
+            .send(ctl(reply))
+            .map_err(|_| NetworkError::ConnectionAborted)?;
+        rx.await.map_err(|_| NetworkError::ConnectionAborted)?
+    }
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for PeerNetworking {
+    /// The data channel is always encrypted, whatever the `security` asked
+    /// for.
+    async fn bridge(
+        &self,
+        network: &str,
+        access_token: &str,
+        _security: StreamSecurity,
+    ) -> Result<()> {
+        self.request(|reply| PeerCtl::Bridge {
+            network: network.to_string(),
+            token: access_token.to_string(),
+            reply,
+        })
+        .await
+    }
+
+    async fn unbridge(&self) -> Result<()> {
+        self.request(|reply| PeerCtl::Unbridge { reply }).await
+    }
+
+    async fn ip_list(&self) -> Result<Vec<IpCidr>> {
+        self.request(|reply| PeerCtl::IpList { reply }).await
+    }
+
+    async fn listen_tcp(
+        &self,
+        addr: SocketAddr,
+        _only_v6: bool,
+        _reuse_port: bool,
+        _reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualTcpListener + Sync>> {
+        if addr.is_ipv6() {
+            return Err(NetworkError::AddressNotAvailable);
+        }
+        let backlog = Arc::new(Mutex::new(Backlog {
+            pending: VecDeque::new(),
+            max: DEFAULT_BACKLOG,
+            interest: Interest::default(),
+        }));
+        let addr = self
+            .request(|reply| PeerCtl::Listen {
+                addr,
+                backlog: backlog.clone(),
+                reply,
+            })
+            .await?;
+        Ok(Box::new(PeerTcpListener {
+            addr,
+            backlog,
+            ctl_tx: self.ctl_tx.clone(),
+        }))
+    }
+
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        reuse_port: bool,
+        reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        self.sockets.bind_udp(addr, reuse_port, reuse_addr).await
+    }
+
+    async fn connect_tcp(
+        &self,
+        addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        self.sockets.connect_tcp(addr, peer).await
+    }
+
+    /// There are no names on the network of the peers.
+    async fn resolve(
+        &self,
+        _host: &str,
+        _port: Option<u16>,
+        _dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        Err(NetworkError::Unsupported)
+    }
+}
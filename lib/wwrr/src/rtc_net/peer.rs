@@ -0,0 +1,539 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    future::poll_fn,
+    net::{IpAddr, SocketAddr},
+    ops::Range,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    task::Poll,
+};
+
+use bytes::Bytes;
+use tokio::sync::{mpsc, oneshot};
+use virtual_net::{IpCidr, NetworkError, Result};
+use wasm_bindgen::JsValue;
+
+use super::{link::Link, Backlog, PeerCtl, Signaling, ANSWERER_IP, LINK_PREFIX, OFFERER_IP};
+use crate::ws_net::{frame::Frame, Open, SocketState, WsNetMsg, WsTcpSocket, RECV_WINDOW};
+
+/// The ports given to the UDP sockets and listeners bound to port 0, and
+/// to the peer's end of the connections it makes.
+const EPHEMERAL_PORTS: Range<u16> = 49152..65535;
+
+/// The ids of the sockets opened by the peer have this bit set, since each
+/// end numbers the sockets it opens from 0.
+const PEER_ID: u32 = 1 << 31;
+
+/// Data frames are split so that no message of the data channel is larger,
+/// which every browser can take.
+const MAX_DATA_LEN: usize = 16 * 1024;
+
+/// A socket whose frames go to the peer.
+#[derive(Debug)]
+struct Peered {
+    open: Open,
+    socket: Arc<Mutex<SocketState>>,
+    /// Replied to once the peer accepted the connection
+    reply: Option<oneshot::Sender<Result<SocketAddr>>>,
+    /// How many bytes of the stream the peer didn't acknowledge yet
+    in_flight: usize,
+    /// The frames of the stream waiting for the peer to acknowledge enough
+    /// bytes, with how many of the unsent bytes of the socket they carry
+    queued: VecDeque<(Frame, usize)>,
+}
+
+impl Peered {
+    fn new(
+        open: Open,
+        socket: Arc<Mutex<SocketState>>,
+        reply: Option<oneshot::Sender<Result<SocketAddr>>>,
+    ) -> Self {
+        Self {
+            open,
+            socket,
+            reply,
+            in_flight: 0,
+            queued: VecDeque::new(),
+        }
+    }
+}
+
+/// What the task driving the peer connection is told.
+enum Event {
+    Ctl(Option<PeerCtl>),
+    Msg(Option<WsNetMsg>),
+    Frame(Option<Bytes>),
+}
+
+/// The state of the task driving the peer connection.
+pub(crate) struct PeerState {
+    signaling: Rc<dyn Signaling>,
+    ice_servers: JsValue,
+    link: Option<Link>,
+    sockets: HashMap<u32, Peered>,
+    /// The backlogs of the listeners, by port
+    listeners: HashMap<u16, Arc<Mutex<Backlog>>>,
+    /// The UDP sockets, by the port they're bound to
+    udp_ports: HashMap<u16, u32>,
+    /// Hands the sockets of the connections made by the peer to the task,
+    /// which doesn't keep itself running
+    msg_tx: mpsc::WeakUnboundedSender<WsNetMsg>,
+    next_port: u16,
+}
+
+impl PeerState {
+    pub fn new(
+        signaling: Rc<dyn Signaling>,
+        ice_servers: JsValue,
+        msg_tx: mpsc::WeakUnboundedSender<WsNetMsg>,
+    ) -> Self {
+        Self {
+            signaling,
+            ice_servers,
+            link: None,
+            sockets: HashMap::new(),
+            listeners: HashMap::new(),
+            udp_ports: HashMap::new(),
+            msg_tx,
+            next_port: EPHEMERAL_PORTS.start,
+        }
+    }
+
+    /// Drives the peer connection until every handle to the networking and
+    /// its sockets is dropped.
+    ///
+    /// Bridging is awaited before anything else is handled, the sockets
+    /// being of no use until the peer is connected.
+    pub async fn run(
+        mut self,
+        mut ctl_rx: mpsc::UnboundedReceiver<PeerCtl>,
+        mut msg_rx: mpsc::UnboundedReceiver<WsNetMsg>,
+    ) {
+        let (mut ctl_open, mut msg_open) = (true, true);
+        while ctl_open || msg_open {
+            let event = poll_fn(|cx| {
+                if ctl_open {
+                    if let Poll::Ready(ctl) = ctl_rx.poll_recv(cx) {
+                        return Poll::Ready(Event::Ctl(ctl));
+                    }
+                }
+                if msg_open {
+                    if let Poll::Ready(msg) = msg_rx.poll_recv(cx) {
+                        return Poll::Ready(Event::Msg(msg));
+                    }
+                }
+                if let Some(link) = self.link.as_mut() {
+                    if let Poll::Ready(frame) = link.poll_next(cx) {
+                        return Poll::Ready(Event::Frame(frame));
+                    }
+                }
+                Poll::Pending
+            })
+            .await;
+
+            match event {
+                Event::Ctl(Some(ctl)) => self.control(ctl).await,
+                Event::Ctl(None) => ctl_open = false,
+                Event::Msg(Some(msg)) => self.handle(msg),
+                Event::Msg(None) => msg_open = false,
+                Event::Frame(Some(frame)) => self.receive(frame),
+                Event::Frame(None) => {
+                    tracing::warn!("Lost the connection to the peer");
+                    self.unbridge();
+                }
+            }
+        }
+        tracing::debug!("Peer networking released");
+    }
+
+    async fn control(&mut self, ctl: PeerCtl) {
+        match ctl {
+            PeerCtl::Bridge {
+                network,
+                token,
+                reply,
+            } => {
+                if self.link.is_some() {
+                    let _ = reply.send(Err(NetworkError::AlreadyExists));
+                    return;
+                }
+                let link =
+                    Link::negotiate(&*self.signaling, &self.ice_servers, &network, &token).await;
+                let res = match link {
+                    Ok(link) => {
+                        tracing::debug!(%network, offerer = link.offerer, "Connected to the peer");
+                        self.link = Some(link);
+                        Ok(())
+                    }
+                    Err(err) => {
+                        tracing::warn!(%network, %err, "Unable to connect to the peer");
+                        Err(NetworkError::ConnectionRefused)
+                    }
+                };
+                let _ = reply.send(res);
+            }
+            PeerCtl::Unbridge { reply } => {
+                if self.link.is_none() {
+                    let _ = reply.send(Err(NetworkError::NotConnected));
+                    return;
+                }
+                self.unbridge();
+                let _ = reply.send(Ok(()));
+            }
+            PeerCtl::IpList { reply } => {
+                let ips = self
+                    .addrs()
+                    .map(|(local, _)| IpCidr {
+                        ip: local,
+                        prefix: LINK_PREFIX,
+                    })
+                    .into_iter()
+                    .collect();
+                let _ = reply.send(Ok(ips));
+            }
+            PeerCtl::Listen {
+                addr,
+                backlog,
+                reply,
+            } => {
+                let port = match addr.port() {
+                    0 => ephemeral_port(&mut self.next_port, |port| {
+                        self.listeners.contains_key(&port)
+                    }),
+                    port if self.listeners.contains_key(&port) => None,
+                    port => Some(port),
+                };
+                let res = match port {
+                    Some(port) => {
+                        self.listeners.insert(port, backlog);
+                        Ok(SocketAddr::new(addr.ip(), port))
+                    }
+                    None => Err(NetworkError::AddressInUse),
+                };
+                let _ = reply.send(res);
+            }
+            PeerCtl::Unlisten { port } => {
+                self.listeners.remove(&port);
+            }
+        }
+    }
+
+    fn handle(&mut self, msg: WsNetMsg) {
+        match msg {
+            WsNetMsg::Open {
+                id,
+                open: Open::Connect(addr),
+                socket,
+                reply,
+            } => match (&self.link, self.addrs()) {
+                (Some(link), Some((_, remote))) if addr.ip() == remote => {
+                    link.send(&Frame::Connect { id, addr });
+                    let peered = Peered::new(Open::Connect(addr), socket, Some(reply));
+                    self.sockets.insert(id, peered);
+                }
+                _ => {
+                    let _ = reply.send(Err(NetworkError::AddressNotAvailable));
+                }
+            },
+            WsNetMsg::Open {
+                id,
+                open: Open::BindUdp(addr),
+                socket,
+                reply,
+            } => {
+                let port = match addr.port() {
+                    0 => ephemeral_port(&mut self.next_port, |port| {
+                        self.udp_ports.contains_key(&port)
+                    }),
+                    port if self.udp_ports.contains_key(&port) => None,
+                    port => Some(port),
+                };
+                let Some(port) = port else {
+                    let _ = reply.send(Err(NetworkError::AddressInUse));
+                    return;
+                };
+                let addr = SocketAddr::new(addr.ip(), port);
+                socket.lock().unwrap().opened(addr);
+                self.udp_ports.insert(port, id);
+                self.sockets
+                    .insert(id, Peered::new(Open::BindUdp(addr), socket, None));
+                let _ = reply.send(Ok(addr));
+            }
+            WsNetMsg::Resolve { reply, .. } => {
+                let _ = reply.send(Err(NetworkError::Unsupported));
+            }
+            WsNetMsg::Send { id, frame, len } => self.send(id, frame, len),
+        }
+    }
+
+    /// Sends a frame of the socket `id` to the peer, or drops it if there's
+    /// no peer.
+    fn send(&mut self, id: u32, frame: Frame, len: usize) {
+        if let Frame::Close { .. } = frame {
+            let Some(peered) = self.sockets.remove(&id) else {
+                return;
+            };
+            match (peered.open, self.link.as_ref()) {
+                (Open::BindUdp(addr), _) => {
+                    self.udp_ports.remove(&addr.port());
+                }
+                // What was written before is still sent, like when a TCP
+                // socket is closed
+                (Open::Connect(_), Some(link)) => {
+                    for (frame, _) in peered.queued {
+                        send_stream(link, id, frame);
+                    }
+                    link.send(&frame);
+                }
+                (Open::Connect(_), None) => {}
+            }
+            return;
+        }
+
+        let remote = self.addrs().map(|(_, remote)| remote);
+        let Some(peered) = self.sockets.get_mut(&id) else {
+            return;
+        };
+        match (frame, self.link.as_ref()) {
+            // The stream is acknowledged right away, the window is kept by
+            // the peer
+            (frame @ Frame::Ack { .. }, Some(link)) => link.send(&frame),
+            (Frame::Datagram { addr, data, .. }, Some(link)) => {
+                let Open::BindUdp(local) = peered.open else {
+                    return;
+                };
+                // Datagrams to other addresses have nowhere to go, like on
+                // a network without a route
+                if Some(addr.ip()) == remote {
+                    let id = local.port() as u32;
+                    link.send(&Frame::Datagram { id, addr, data });
+                }
+                peered.socket.lock().unwrap().sent(len);
+            }
+            (frame @ (Frame::Data { .. } | Frame::Shutdown { .. }), Some(_)) => {
+                peered.queued.push_back((frame, len));
+                self.flush(id);
+            }
+            (_, _) => peered.socket.lock().unwrap().sent(len),
+        }
+    }
+
+    /// Sends the queued frames of the stream `id` which fit in the window of
+    /// the peer.
+    fn flush(&mut self, id: u32) {
+        let (Some(link), Some(peered)) = (self.link.as_ref(), self.sockets.get_mut(&id)) else {
+            return;
+        };
+        while let Some((frame, len)) = peered.queued.pop_front() {
+            if let Frame::Data { data, .. } = &frame {
+                if peered.in_flight > 0 && peered.in_flight + data.len() > RECV_WINDOW {
+                    peered.queued.push_front((frame, len));
+                    return;
+                }
+                peered.in_flight += data.len();
+            }
+            send_stream(link, id, frame);
+            peered.socket.lock().unwrap().sent(len);
+        }
+    }
+
+    /// Hands what the peer sent to the sockets.
+    fn receive(&mut self, frame: Bytes) {
+        let Some(frame) = Frame::decode(frame) else {
+            tracing::warn!("Received a malformed frame from the peer");
+            return;
+        };
+        let Some(link) = self.link.as_ref() else {
+            return;
+        };
+
+        // The peer tells its own ids, which are the other half of them here
+        match frame {
+            Frame::Connect { id, addr } => self.accept(id ^ PEER_ID, addr),
+            Frame::Opened { id, addr } => {
+                if let Some(peered) = self.sockets.get_mut(&(id ^ PEER_ID)) {
+                    peered.socket.lock().unwrap().opened(addr);
+                    if let Some(reply) = peered.reply.take() {
+                        let _ = reply.send(Ok(addr));
+                    }
+                }
+            }
+            Frame::Data { id, data } => {
+                let id = id ^ PEER_ID;
+                let Some(Peered {
+                    open: Open::Connect(peer),
+                    socket,
+                    ..
+                }) = self.sockets.get(&id)
+                else {
+                    return;
+                };
+                let mut socket = socket.lock().unwrap();
+                // The bytes which won't be read are acknowledged right away
+                if socket.is_read_shutdown() {
+                    let len = data.len() as u32;
+                    link.send(&Frame::Ack { id, len });
+                } else {
+                    socket.receive(*peer, data);
+                }
+            }
+            Frame::Ack { id, len } => {
+                let id = id ^ PEER_ID;
+                if let Some(peered) = self.sockets.get_mut(&id) {
+                    peered.in_flight = peered.in_flight.saturating_sub(len as usize);
+                    self.flush(id);
+                }
+            }
+            // The source port is sent as the id of the datagram
+            Frame::Datagram { id, addr, data } => {
+                let (Some((_, remote)), Ok(port)) = (self.addrs(), u16::try_from(id)) else {
+                    return;
+                };
+                let Some(peered) = self
+                    .udp_ports
+                    .get(&addr.port())
+                    .and_then(|id| self.sockets.get(id))
+                else {
+                    return;
+                };
+                let from = SocketAddr::new(remote, port);
+                peered.socket.lock().unwrap().receive(from, data);
+            }
+            Frame::Shutdown { id } => {
+                if let Some(peered) = self.sockets.get(&(id ^ PEER_ID)) {
+                    peered.socket.lock().unwrap().finish();
+                }
+            }
+            Frame::Close { id } => {
+                if let Some(peered) = self.sockets.remove(&(id ^ PEER_ID)) {
+                    peered.socket.lock().unwrap().close(None);
+                }
+            }
+            Frame::Closed { id, error } => {
+                if let Some(mut peered) = self.sockets.remove(&(id ^ PEER_ID)) {
+                    match peered.reply.take() {
+                        Some(reply) => {
+                            let error = error.unwrap_or(NetworkError::ConnectionRefused);
+                            let _ = reply.send(Err(error));
+                        }
+                        None => peered.socket.lock().unwrap().close(error),
+                    }
+                }
+            }
+            frame => tracing::warn!(?frame, "Received an unexpected frame from the peer"),
+        }
+    }
+
+    /// Opens the connection `id` the peer made to `addr`, if something
+    /// listens to it.
+    fn accept(&mut self, id: u32, addr: SocketAddr) {
+        let (Some(link), Some((local, remote))) = (self.link.as_ref(), self.addrs()) else {
+            return;
+        };
+        let listener = self.listeners.get(&addr.port());
+        let (Some(backlog), Some(msg_tx)) = (listener, self.msg_tx.upgrade()) else {
+            let error = Some(NetworkError::ConnectionRefused);
+            link.send(&Frame::Closed { id, error });
+            return;
+        };
+
+        let count = EPHEMERAL_PORTS.end - EPHEMERAL_PORTS.start;
+        let port = EPHEMERAL_PORTS.start + (id % count as u32) as u16;
+        let peer = SocketAddr::new(remote, port);
+        let state = Arc::new(Mutex::new(SocketState::default()));
+        state
+            .lock()
+            .unwrap()
+            .opened(SocketAddr::new(local, addr.port()));
+        let socket = WsTcpSocket::new(id, state.clone(), msg_tx, peer);
+
+        if !backlog.lock().unwrap().push(socket, peer) {
+            let error = Some(NetworkError::ConnectionRefused);
+            link.send(&Frame::Closed { id, error });
+            return;
+        }
+        link.send(&Frame::Opened { id, addr: peer });
+        self.sockets
+            .insert(id, Peered::new(Open::Connect(peer), state, None));
+    }
+
+    /// Closes the data channel, resetting the connections while the UDP
+    /// sockets are kept for the next peer.
+    fn unbridge(&mut self) {
+        self.link = None;
+        self.sockets.retain(|_, peered| match peered.open {
+            Open::BindUdp(_) => true,
+            Open::Connect(_) => {
+                match peered.reply.take() {
+                    Some(reply) => {
+                        let _ = reply.send(Err(NetworkError::ConnectionReset));
+                    }
+                    None => peered
+                        .socket
+                        .lock()
+                        .unwrap()
+                        .close(Some(NetworkError::ConnectionReset)),
+                }
+                false
+            }
+        });
+    }
+
+    /// The local and remote addresses on the network of the peers, once
+    /// bridged.
+    fn addrs(&self) -> Option<(IpAddr, IpAddr)> {
+        let link = self.link.as_ref()?;
+        Some(match link.offerer {
+            true => (OFFERER_IP.into(), ANSWERER_IP.into()),
+            false => (ANSWERER_IP.into(), OFFERER_IP.into()),
+        })
+    }
+}
+
+/// Sends a frame of the stream `id`, splitting the data frames.
+fn send_stream(link: &Link, id: u32, frame: Frame) {
+    match frame {
+        Frame::Data { data, .. } => {
+            for chunk in data.chunks(MAX_DATA_LEN) {
+                let data = data.slice_ref(chunk);
+                link.send(&Frame::Data { id, data });
+            }
+        }
+        frame => link.send(&frame),
+    }
+}
+
+/// Picks a free ephemeral port, starting from `next_port`.
+fn ephemeral_port(next_port: &mut u16, in_use: impl Fn(u16) -> bool) -> Option<u16> {
+    for _ in EPHEMERAL_PORTS {
+        let port = *next_port;
+        *next_port = match port + 1 {
+            next if EPHEMERAL_PORTS.contains(&next) => next,
+            _ => EPHEMERAL_PORTS.start,
+        };
+        if !in_use(port) {
+            return Some(port);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+
+    #[wasm_bindgen_test]
+    fn ephemeral_ports_wrap_around_and_skip_the_used_ones() {
+        let mut next_port = EPHEMERAL_PORTS.end - 2;
+        let in_use = |port| port == EPHEMERAL_PORTS.end - 1;
+
+        assert_eq!(ephemeral_port(&mut next_port, in_use), Some(65533));
+        assert_eq!(
+            ephemeral_port(&mut next_port, in_use),
+            Some(EPHEMERAL_PORTS.start)
+        );
+        assert_eq!(ephemeral_port(&mut next_port, |_| true), None);
+    }
+}
@@ -1,11 +1,23 @@
-use std::sync::{Arc, LazyLock, Mutex, Weak};
+use std::{
+    rc::Rc,
+    sync::{Arc, LazyLock, Mutex, Weak},
+};
 
 use utils::Error;
 use virtual_net::VirtualNetworking;
+use wasm_bindgen::JsValue;
 use wasmer::VERSION;
-use wasmer_wasix::{runtime::module_cache::ThreadLocalCache, VirtualTaskManager};
-
-use crate::tasks::ThreadPool;
+use wasmer_wasix::{
+    net::loopback::LoopbackNetworking,
+    runtime::module_cache::{FallbackCache, IndexedDbCache, ModuleCache, ThreadLocalCache},
+    VirtualTaskManager,
+};
+
+use crate::{
+    rtc_net::{PeerNetworking, Signaling},
+    tasks::ThreadPool,
+    ws_net::WsNetworking,
+};
 
 /// Worker thread pool.
 static THREAD_POOL: LazyLock<Arc<dyn VirtualTaskManager>> =
@@ -19,7 +31,9 @@ static GLOBAL_RUNTIME: Mutex<Weak<Runtime>> = Mutex::new(Weak::new());
 #[derivative(Debug)]
 pub struct Runtime {
     networking: Arc<dyn VirtualNetworking>,
-    module_cache: Arc<ThreadLocalCache>,
+    /// Modules are looked up in memory first, then in IndexedDB so that
+    /// repeat page loads skip compilation.
+    module_cache: Arc<FallbackCache<ThreadLocalCache, IndexedDbCache>>,
 }
 
 impl Runtime {
@@ -65,7 +79,36 @@ impl Runtime {
     pub(crate) fn new() -> Self {
         Runtime {
             networking: Arc::new(virtual_net::UnsupportedVirtualNetworking::default()),
-            module_cache: Arc::new(ThreadLocalCache::default()),
+            module_cache: Arc::new(
+                ThreadLocalCache::default().with_fallback(IndexedDbCache::default()),
+            ),
+        }
+    }
+
+    /// Creates a runtime whose sockets are opened by the relay at `url`,
+    /// reached over a WebSocket.
+    pub(crate) fn with_network_relay(url: &str) -> Result<Self, Error> {
+        Ok(Runtime {
+            networking: Arc::new(WsNetworking::new(url)?),
+            ..Runtime::new()
+        })
+    }
+
+    /// Creates a runtime whose programs share a network in memory, where
+    /// they reach each other and the page by IP address and port.
+    pub(crate) fn with_loopback_network() -> Self {
+        Runtime {
+            networking: Arc::new(LoopbackNetworking::new()),
+            ..Runtime::new()
+        }
+    }
+
+    /// Creates a runtime whose programs bridge their network with a peer
+    /// met through `signaling`, which is driven on the current thread.
+    pub(crate) fn with_peer_network(signaling: Rc<dyn Signaling>, ice_servers: JsValue) -> Self {
+        Runtime {
+            networking: Arc::new(PeerNetworking::new(signaling, ice_servers)),
+            ..Runtime::new()
         }
     }
 }
@@ -42,5 +42,12 @@ mod worker_handle;
 mod worker_message;
 
 pub(crate) use self::{
-    thread_pool::ThreadPool, worker_handle::WorkerHandle, worker_message::WorkerInit,
+    interop::{Deserializer, Serializer},
+    thread_pool::ThreadPool,
+    worker::{
+        init_message_http_fs, init_message_indexed_db, init_message_opfs, init_message_ws_net,
+        WORKER_URL,
+    },
+    worker_handle::WorkerHandle,
+    worker_message::WorkerInit,
 };
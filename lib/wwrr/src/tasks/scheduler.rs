@@ -1,11 +1,18 @@
 use std::collections::{HashMap, VecDeque};
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
 use anyhow::{anyhow, Context, Error};
+use futures::future::Either;
+use instant::Duration;
 use tokio::sync::{mpsc, oneshot, Notify};
+use utils::GlobalScope;
 use wasm_bindgen::prelude::*;
-use wasmer_wasix::runtime::task_manager::SchedulerSpawn;
+use wasm_bindgen_futures::JsFuture;
+use wasmer_wasix::runtime::task_manager::{
+    SchedulerSpawn, SpawnWasm, WorkerPoolConfig, WorkerPoolMetrics,
+};
 use web_sys::DedicatedWorkerGlobalScope;
 
 use super::scheduler_message::SchedulerMsg;
@@ -22,12 +29,12 @@ pub(crate) struct Scheduler {
 
 impl Scheduler {
     /// Spawn a web worker running the scheduler.
-    pub fn spawn(scheduler_spawn: SchedulerSpawn) -> Self {
+    pub fn spawn(scheduler_spawn: SchedulerSpawn, metrics: Arc<PoolMetrics>) -> Self {
         let SchedulerSpawn {
             module,
             memory,
             wbg_js_module_name,
-            prestarted_workers,
+            worker_pool,
         } = scheduler_spawn;
 
         // Start web worker.
@@ -48,7 +55,8 @@ impl Scheduler {
             module,
             memory,
             wbg_js_module_name,
-            prestarted_workers,
+            worker_pool,
+            metrics,
             _not_send: std::marker::PhantomData,
         };
         worker
@@ -75,6 +83,24 @@ impl Scheduler {
     }
 }
 
+/// Metrics of the thread pool, updated by the scheduler.
+#[derive(Debug, Default)]
+pub(crate) struct PoolMetrics {
+    queued_tasks: AtomicUsize,
+    active_workers: AtomicUsize,
+    idle_workers: AtomicUsize,
+}
+
+impl PoolMetrics {
+    pub fn get(&self) -> WorkerPoolMetrics {
+        WorkerPoolMetrics {
+            queued_tasks: self.queued_tasks.load(Ordering::Relaxed),
+            active_workers: self.active_workers.load(Ordering::Relaxed),
+            idle_workers: self.idle_workers.load(Ordering::Relaxed),
+        }
+    }
+}
+
 /// Scheduler worker.
 #[wasm_bindgen(skip_typescript)]
 struct SchedulerWorker {}
@@ -103,8 +129,11 @@ pub(crate) struct SchedulerState {
     next_worker_id: u32,
     /// Workers that are busy and cannot be reused afterwards.
     active_workers: HashMap<u32, WorkerHandle>,
-    /// Workers that are ready to be used.
-    ready_workers: Arc<Mutex<VecDeque<WorkerHandle>>>,
+    /// Workers that are ready to be used, with the time since which they
+    /// are idle.
+    ready_workers: Arc<Mutex<VecDeque<(WorkerHandle, f64)>>>,
+    /// Threads waiting for a worker because of the maximum concurrency.
+    queued: VecDeque<SpawnWasm>,
     /// Notification that a worker has been added to `ready_workers`.
     worker_ready: Arc<Notify>,
     /// Message sender.
@@ -115,8 +144,10 @@ pub(crate) struct SchedulerState {
     module: wasmer::Module,
     /// WebAssembly memory for spawning threads.
     memory: wasmer::Memory,
-    /// Number of workers to pre-start.
-    prestarted_workers: usize,
+    /// How the pool of workers is sized.
+    worker_pool: WorkerPoolConfig,
+    /// Metrics of the pool.
+    metrics: Arc<PoolMetrics>,
     /// wasm-bindgen generated module name.
     wbg_js_module_name: String,
 }
@@ -130,7 +161,8 @@ impl SchedulerState {
             module,
             memory,
             wbg_js_module_name,
-            prestarted_workers,
+            worker_pool,
+            metrics,
             _not_send,
         } = init;
 
@@ -138,31 +170,35 @@ impl SchedulerState {
             next_worker_id: 1,
             active_workers: HashMap::new(),
             ready_workers: Arc::new(Mutex::new(VecDeque::new())),
+            queued: VecDeque::new(),
             worker_ready: Arc::new(Notify::new()),
             msg_tx,
             msg_rx,
             module,
             memory,
             wbg_js_module_name,
-            prestarted_workers,
+            worker_pool,
+            metrics,
         };
         wasm_bindgen_futures::spawn_local(this.run());
     }
 
     /// Scheduler main loop.
     async fn run(mut self) {
-        tracing::trace!("pre-starting {} workers", self.prestarted_workers);
-        for _ in 0..self.prestarted_workers {
+        let prestarted_workers = self.worker_pool.prestarted_workers;
+        tracing::trace!("pre-starting {prestarted_workers} workers");
+        for _ in 0..prestarted_workers {
             self.start_worker();
         }
-        while self.ready_workers.lock().unwrap().len() < self.prestarted_workers {
+        while self.ready_workers.lock().unwrap().len() < prestarted_workers {
             self.worker_ready.notified().await;
         }
 
-        while let Some(msg) = self.msg_rx.recv().await {
+        while let Some(msg) = self.next_message().await {
             if let Err(e) = self.execute(msg).await {
                 tracing::error!(error = &*e, "An error occurred while handling a message");
             }
+            self.update_metrics();
         }
 
         tracing::debug!("scheduler exiting");
@@ -172,10 +208,75 @@ impl SchedulerState {
         });
     }
 
+    /// Waits for the next message, terminating the workers which stay idle
+    /// for too long in the meantime.
+    async fn next_message(&mut self) -> Option<SchedulerMsg> {
+        let Some(idle_timeout) = self.worker_pool.idle_timeout else {
+            return self.msg_rx.recv().await;
+        };
+
+        loop {
+            let millis = idle_timeout.as_millis().try_into().unwrap_or(i32::MAX);
+            let idle = JsFuture::from(GlobalScope::current().sleep(millis));
+            let recv = Box::pin(self.msg_rx.recv());
+            if let Either::Left((msg, _)) = futures::future::select(recv, idle).await {
+                return msg;
+            }
+            self.reclaim_idle_workers(idle_timeout);
+        }
+    }
+
+    /// Terminates the ready workers which are idle for longer than
+    /// `idle_timeout`.
+    fn reclaim_idle_workers(&self, idle_timeout: Duration) {
+        let deadline = GlobalScope::current().now() - idle_timeout.as_secs_f64() * 1000.;
+        let mut ready_workers = self.ready_workers.lock().unwrap();
+        let before = ready_workers.len();
+        ready_workers.retain(|(_, idle_since)| *idle_since > deadline);
+        if ready_workers.len() < before {
+            tracing::debug!(
+                reclaimed = before - ready_workers.len(),
+                "terminated idle workers"
+            );
+        }
+        drop(ready_workers);
+        self.update_metrics();
+    }
+
+    fn update_metrics(&self) {
+        let idle_workers = self.ready_workers.lock().unwrap().len();
+        self.metrics
+            .queued_tasks
+            .store(self.queued.len(), Ordering::Relaxed);
+        self.metrics
+            .active_workers
+            .store(self.active_workers.len(), Ordering::Relaxed);
+        self.metrics
+            .idle_workers
+            .store(idle_workers, Ordering::Relaxed);
+    }
+
+    /// Whether a thread can be started without exceeding the maximum
+    /// concurrency.
+    fn can_start_thread(&self) -> bool {
+        !self
+            .worker_pool
+            .max_concurrency
+            .is_some_and(|max| self.active_workers.len() >= max)
+    }
+
     /// Executes a scheduler message.
     pub async fn execute(&mut self, message: SchedulerMsg) -> Result<(), Error> {
         match message {
             SchedulerMsg::SpawnWasm(spawn_wasm) => {
+                if !self.can_start_thread() {
+                    tracing::debug!(
+                        queued = self.queued.len() + 1,
+                        "thread pool is at its maximum concurrency, queueing the thread"
+                    );
+                    self.queued.push_back(spawn_wasm);
+                    return Ok(());
+                }
                 self.post_message(WorkerMsg::SpawnWasm(spawn_wasm)).await?;
                 Ok(())
             }
@@ -183,6 +284,13 @@ impl SchedulerState {
                 let mut worker = self.active_workers.remove(&worker_id).unwrap();
                 worker.set_terminate(false);
                 tracing::trace!(worker.id = worker_id, "Worker has exited");
+
+                while self.can_start_thread() {
+                    let Some(spawn_wasm) = self.queued.pop_front() else {
+                        break;
+                    };
+                    self.post_message(WorkerMsg::SpawnWasm(spawn_wasm)).await?;
+                }
                 Ok(())
             }
             SchedulerMsg::Ping(tx) => {
@@ -210,6 +318,7 @@ impl SchedulerState {
         let id = self.next_worker_id;
         let ready_workers = self.ready_workers.clone();
         let worker_ready = self.worker_ready.clone();
+        let metrics = self.metrics.clone();
         let msg_tx = self.msg_tx.clone();
         let module = self.module.clone();
         let memory = self.memory.clone();
@@ -223,7 +332,12 @@ impl SchedulerState {
                 .await
                 .expect("starting thread worker failed");
 
-            ready_workers.lock().unwrap().push_back(handle);
+            let mut ready_workers = ready_workers.lock().unwrap();
+            ready_workers.push_back((handle, GlobalScope::current().now()));
+            metrics
+                .idle_workers
+                .store(ready_workers.len(), Ordering::Relaxed);
+            drop(ready_workers);
             worker_ready.notify_one();
         });
     }
@@ -238,9 +352,9 @@ impl SchedulerState {
         loop {
             let worker_opt = self.ready_workers.lock().unwrap().pop_front();
             match worker_opt {
-                Some(worker) => break worker,
+                Some((worker, _)) => break worker,
                 None => {
-                    if self.prestarted_workers > 0 {
+                    if self.worker_pool.prestarted_workers > 0 {
                         tracing::warn!("thread pool has run out of pre-started workers");
                     }
                     self.worker_ready.notified().await;
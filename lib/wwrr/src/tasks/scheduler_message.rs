@@ -1,4 +1,4 @@
-use std::marker::PhantomData;
+use std::{marker::PhantomData, sync::Arc};
 
 use anyhow::anyhow;
 use bytes::Bytes;
@@ -7,10 +7,11 @@ use tokio::sync::{mpsc, oneshot};
 use utils::Error;
 use wasm_bindgen::JsValue;
 use wasmer::{AsJs, MemoryType};
+use wasmer_wasix::runtime::task_manager::{SpawnWasm, WorkerPoolConfig};
 
 use crate::tasks::{
     interop::{Deserializer, Serializer},
-    task_wasm::SpawnWasm,
+    scheduler::PoolMetrics,
 };
 
 /// Messages sent from the [`crate::tasks::ThreadPool`] handle to the
@@ -38,8 +39,10 @@ pub(crate) struct SchedulerInit {
     pub memory: wasmer::Memory,
     /// wasm-bindgen generated module name.
     pub wbg_js_module_name: String,
-    /// Number of workers to pre-start.
-    pub prestarted_workers: usize,
+    /// How the pool of workers is sized.
+    pub worker_pool: WorkerPoolConfig,
+    /// Metrics of the pool, shared with the [`crate::tasks::ThreadPool`].
+    pub metrics: Arc<PoolMetrics>,
     /// [`wasmer::Module`] and friends are `!Send` in practice.
     pub _not_send: PhantomData<*const ()>,
 }
@@ -52,7 +55,8 @@ impl SchedulerInit {
             module,
             memory,
             wbg_js_module_name,
-            prestarted_workers,
+            worker_pool,
+            metrics,
             _not_send,
         } = self;
 
@@ -64,7 +68,8 @@ impl SchedulerInit {
             .boxed(consts::MEMORY_TYPE, memory.ty(&wasmer::Store::default()))
             .set(consts::MEMORY, memory.as_jsvalue(&wasmer::Store::default()))
             .boxed(consts::WBG_JS_MODULE_NAME, wbg_js_module_name)
-            .boxed(consts::PRESTARTED_WORKERS, prestarted_workers)
+            .boxed(consts::WORKER_POOL, worker_pool)
+            .boxed(consts::METRICS, metrics)
             .finish()
     }
 
@@ -81,7 +86,8 @@ impl SchedulerInit {
         let memory: JsValue = de.js(consts::MEMORY)?;
         let memory_type: MemoryType = de.boxed(consts::MEMORY_TYPE)?;
         let wbg_js_module_name: String = de.boxed(consts::WBG_JS_MODULE_NAME)?;
-        let prestarted_workers: usize = de.boxed(consts::PRESTARTED_WORKERS)?;
+        let worker_pool: WorkerPoolConfig = de.boxed(consts::WORKER_POOL)?;
+        let metrics: Arc<PoolMetrics> = de.boxed(consts::METRICS)?;
 
         Ok(Self {
             msg_tx,
@@ -94,7 +100,8 @@ impl SchedulerInit {
             )
             .map_err(Error::js)?,
             wbg_js_module_name,
-            prestarted_workers,
+            worker_pool,
+            metrics,
             _not_send: PhantomData,
         })
     }
@@ -109,5 +116,6 @@ mod consts {
     pub const MEMORY: &str = "memory";
     pub const MEMORY_TYPE: &str = "memory-type";
     pub const WBG_JS_MODULE_NAME: &str = "wbg-js-module-name";
-    pub const PRESTARTED_WORKERS: &str = "prestarted-workers";
+    pub const WORKER_POOL: &str = "worker-pool";
+    pub const METRICS: &str = "metrics";
 }
@@ -6,10 +6,10 @@ use futures::FutureExt;
 use instant::Duration;
 use virtual_mio::InlineSleep;
 use wasm_bindgen::JsCast;
-use wasmer_wasix::runtime::task_manager::SchedulerSpawn;
+use wasmer_wasix::runtime::task_manager::{SchedulerSpawn, WorkerPoolMetrics};
 use wasmer_wasix::{runtime::task_manager::TaskWasm, VirtualTaskManager, WasiThreadError};
 
-use super::scheduler::Scheduler;
+use super::scheduler::{PoolMetrics, Scheduler};
 use super::scheduler_message::SchedulerMsg;
 use crate::run::is_memory_shared;
 
@@ -20,6 +20,8 @@ pub struct ThreadPool {
     // the initialization can only take place when only the main
     // thread is running.
     scheduler: Arc<OnceLock<Scheduler>>,
+    /// Metrics of the pool, updated by the scheduler.
+    metrics: Arc<PoolMetrics>,
 }
 
 impl ThreadPool {
@@ -35,6 +37,7 @@ impl ThreadPool {
 
         Self {
             scheduler: Arc::new(OnceLock::new()),
+            metrics: Arc::new(PoolMetrics::default()),
         }
     }
 
@@ -45,9 +48,7 @@ impl ThreadPool {
 
     /// Whether threading is available.
     pub fn available() -> bool {
-        utils::GlobalScope::current()
-            .cross_origin_isolated()
-            .unwrap_or_default()
+        wasmer::EngineCapabilities::detect().threads
     }
 }
 
@@ -57,10 +58,10 @@ impl VirtualTaskManager for ThreadPool {
         async move {
             if Self::available() {
                 tracing::debug!(
-                    "initializing thread pool with {} pre-started workers",
-                    scheduler_spawn.prestarted_workers
+                    worker_pool = ?scheduler_spawn.worker_pool,
+                    "initializing thread pool"
                 );
-                let scheduler = Scheduler::spawn(scheduler_spawn);
+                let scheduler = Scheduler::spawn(scheduler_spawn, self.metrics.clone());
                 scheduler.ping().await.unwrap();
                 self.scheduler
                     .set(scheduler)
@@ -105,4 +106,8 @@ impl VirtualTaskManager for ThreadPool {
             None => Err(WasiThreadError::Unsupported),
         }
     }
+
+    fn pool_metrics(&self) -> Option<WorkerPoolMetrics> {
+        Self::available().then(|| self.metrics.get())
+    }
 }
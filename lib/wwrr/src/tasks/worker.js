@@ -32,6 +32,14 @@ globalThis.onmessage = async ev => {
       worker = new imported.SchedulerWorker();
     else if (role == "worker")
       worker = new imported.ThreadPoolWorker(id);
+    else if (role == "opfs")
+      worker = new imported.OpfsWorker();
+    else if (role == "indexed-db")
+      worker = new imported.IndexedDbWorker();
+    else if (role == "http-fs")
+      worker = new imported.HttpFsWorker();
+    else if (role == "ws-net")
+      worker = new imported.WsNetWorker();
     else
       throw new Error(`unknown role ${role}`);
 
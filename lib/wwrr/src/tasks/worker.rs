@@ -58,10 +58,38 @@ pub fn init_message_worker(id: u32) -> JsValue {
 
 /// Craft the special `"init"` message for the scheduler.
 pub fn init_message_scheduler() -> JsValue {
+    init_message_with_role("scheduler")
+}
+
+/// Craft the special `"init"` message for the worker owning the handles of
+/// an OPFS file system.
+pub fn init_message_opfs() -> JsValue {
+    init_message_with_role("opfs")
+}
+
+/// Craft the special `"init"` message for the worker owning the database of
+/// an IndexedDB file system.
+pub fn init_message_indexed_db() -> JsValue {
+    init_message_with_role("indexed-db")
+}
+
+/// Craft the special `"init"` message for the worker fetching the files of an
+/// HTTP file system.
+pub fn init_message_http_fs() -> JsValue {
+    init_message_with_role("http-fs")
+}
+
+/// Craft the special `"init"` message for the worker relaying the sockets of
+/// a WebSocket networking.
+pub fn init_message_ws_net() -> JsValue {
+    init_message_with_role("ws-net")
+}
+
+fn init_message_with_role(role: &str) -> JsValue {
     let msg = js_sys::Object::new();
 
     js_sys::Reflect::set(&msg, &JsString::from("type"), &JsString::from("init")).unwrap();
-    js_sys::Reflect::set(&msg, &JsString::from("role"), &JsString::from("scheduler")).unwrap();
+    js_sys::Reflect::set(&msg, &JsString::from("role"), &JsString::from(role)).unwrap();
     js_sys::Reflect::set(&msg, &JsString::from("memory"), &wasm_bindgen::memory()).unwrap();
     js_sys::Reflect::set(
         &msg,
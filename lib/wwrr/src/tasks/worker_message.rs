@@ -7,10 +7,11 @@ use tokio::sync::{mpsc, oneshot};
 use utils::Error;
 use wasm_bindgen::JsValue;
 use wasmer::{AsJs, MemoryType};
+use wasmer_wasix::runtime::task_manager::SpawnWasm;
 
 use super::interop::Deserializer;
 use super::scheduler::Scheduler;
-use crate::tasks::{interop::Serializer, task_wasm::SpawnWasm};
+use crate::tasks::interop::Serializer;
 
 /// A message sent from scheduler to worker.
 #[derive(Debug)]
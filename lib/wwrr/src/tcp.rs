@@ -0,0 +1,240 @@
+//! TCP connections between JavaScript and the programs of a runtime, made
+//! through the network of the runtime, so that a page talks to a server run
+//! by a program or serves the connections a program makes.
+
+use std::{
+    future::poll_fn,
+    mem::MaybeUninit,
+    net::{Shutdown, SocketAddr},
+    sync::{Arc, Mutex},
+    task::Poll,
+};
+
+use futures::future::{AbortHandle, Abortable};
+use js_sys::{Promise, Uint8Array};
+use utils::Error;
+use virtual_net::{NetworkError, VirtualTcpListener, VirtualTcpListenerExt, VirtualTcpSocket};
+use wasm_bindgen::{prelude::wasm_bindgen, JsCast, JsValue};
+use web_sys::{ReadableStream, ReadableStreamDefaultController, WritableStream};
+
+/// How many bytes are read from a connection for each chunk of its stream.
+const CHUNK_SIZE: usize = 16 * 1024;
+
+type SharedSocket = Arc<Mutex<Box<dyn VirtualTcpSocket + Sync>>>;
+
+/// A TCP connection, read and written as streams.
+#[derive(Debug)]
+#[wasm_bindgen(js_name = "TcpConnection")]
+pub struct TcpConnection {
+    /// The data received, which ends when the other end stops writing.
+    #[wasm_bindgen(getter_with_clone, readonly)]
+    pub readable: ReadableStream,
+    /// The data to send, closing it stops writing.
+    #[wasm_bindgen(getter_with_clone, readonly)]
+    pub writable: WritableStream,
+    peer: SocketAddr,
+}
+
+#[wasm_bindgen(js_class = "TcpConnection")]
+impl TcpConnection {
+    /// The IP address of the other end.
+    #[wasm_bindgen(getter, js_name = "remoteAddress")]
+    pub fn remote_address(&self) -> String {
+        self.peer.ip().to_string()
+    }
+
+    /// The port of the other end.
+    #[wasm_bindgen(getter, js_name = "remotePort")]
+    pub fn remote_port(&self) -> u16 {
+        self.peer.port()
+    }
+}
+
+impl TcpConnection {
+    pub(crate) fn new(socket: Box<dyn VirtualTcpSocket + Sync>, peer: SocketAddr) -> Self {
+        let socket = Arc::new(Mutex::new(socket));
+        let source = JsValue::from(SocketSource {
+            socket: socket.clone(),
+        });
+        let sink = JsValue::from(SocketSink { socket });
+        Self {
+            readable: ReadableStream::new_with_underlying_source(source.unchecked_ref()).unwrap(),
+            writable: WritableStream::new_with_underlying_sink(sink.unchecked_ref()).unwrap(),
+            peer,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[wasm_bindgen(skip_typescript)]
+struct SocketSource {
+    socket: SharedSocket,
+}
+
+#[wasm_bindgen]
+impl SocketSource {
+    /// Called whenever the stream wants the next chunk.
+    pub fn pull(&mut self, controller: ReadableStreamDefaultController) -> Promise {
+        let socket = self.socket.clone();
+        wasm_bindgen_futures::future_to_promise(async move {
+            let mut buf = vec![MaybeUninit::new(0); CHUNK_SIZE];
+            let received = poll_fn(|cx| {
+                let mut socket = socket.lock().unwrap();
+                loop {
+                    match socket.poll_read_ready(cx) {
+                        Poll::Ready(Ok(_)) => {}
+                        Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                        Poll::Pending => return Poll::Pending,
+                    }
+                    match socket.try_recv(&mut buf) {
+                        Err(NetworkError::WouldBlock) => {}
+                        ret => return Poll::Ready(ret),
+                    }
+                }
+            })
+            .await;
+
+            match received {
+                Ok(0) => controller.close()?,
+                Ok(amt) => {
+                    // The buffer is filled with zeros when it's created, all
+                    // its bytes are initialized
+                    let data = &buf[..amt];
+                    let data = unsafe { &*(data as *const [MaybeUninit<u8>] as *const [u8]) };
+                    controller.enqueue_with_chunk(&Uint8Array::from(data))?;
+                }
+                Err(err) => controller.error_with_e(&Error::from(err).into()),
+            }
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Called when the stream is cancelled, nothing is received anymore.
+    pub fn cancel(&mut self) {
+        self.socket.lock().unwrap().shutdown(Shutdown::Read).ok();
+    }
+}
+
+#[derive(Debug)]
+#[wasm_bindgen(skip_typescript)]
+struct SocketSink {
+    socket: SharedSocket,
+}
+
+#[wasm_bindgen]
+impl SocketSink {
+    /// Called with each chunk written to the stream, once the previous one
+    /// was sent.
+    pub fn write(&mut self, chunk: Uint8Array) -> Promise {
+        let socket = self.socket.clone();
+        let data = chunk.to_vec();
+        wasm_bindgen_futures::future_to_promise(async move {
+            let mut sent = 0;
+            poll_fn(|cx| {
+                let mut socket = socket.lock().unwrap();
+                while sent < data.len() {
+                    match socket.poll_write_ready(cx) {
+                        Poll::Ready(Ok(_)) => {}
+                        Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                        Poll::Pending => return Poll::Pending,
+                    }
+                    match socket.try_send(&data[sent..]) {
+                        Ok(amt) => sent += amt,
+                        Err(NetworkError::WouldBlock) => {}
+                        Err(err) => return Poll::Ready(Err(err)),
+                    }
+                }
+                Poll::Ready(Ok(()))
+            })
+            .await
+            .map_err(Error::from)?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Called once the stream is closed, the other end then reaches the end
+    /// of what it receives.
+    pub fn close(&mut self) -> Result<(), Error> {
+        self.socket.lock().unwrap().shutdown(Shutdown::Write)?;
+        Ok(())
+    }
+
+    /// Called when the stream is aborted, which closes the connection.
+    pub fn abort(&mut self) {
+        self.socket.lock().unwrap().close().ok();
+    }
+}
+
+/// A TCP socket listening for the connections of the programs of a runtime,
+/// which stops listening once it's closed.
+#[derive(Debug)]
+#[wasm_bindgen(js_name = "TcpListener")]
+pub struct TcpListener {
+    addr: SocketAddr,
+    abort: AbortHandle,
+}
+
+#[wasm_bindgen(js_class = "TcpListener")]
+impl TcpListener {
+    /// The port the listener listens on, which is picked when listening on
+    /// port 0.
+    #[wasm_bindgen(getter)]
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// Stops listening.
+    pub fn close(&self) {
+        self.abort.abort();
+    }
+}
+
+impl TcpListener {
+    /// Hands the connections accepted by `listener` to `on_connection`,
+    /// until it's closed.
+    pub(crate) fn new(
+        mut listener: Box<dyn VirtualTcpListener + Sync>,
+        on_connection: TcpConnectionHandler,
+    ) -> Result<Self, Error> {
+        let addr = listener.addr_local()?;
+        let (abort, registration) = AbortHandle::new_pair();
+        let accept = async move {
+            loop {
+                let (socket, peer) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(err) => {
+                        tracing::debug!(%addr, %err, "listener failed");
+                        break;
+                    }
+                };
+                let connection = TcpConnection::new(socket, peer);
+                if let Err(err) = on_connection.call1(&JsValue::NULL, &connection.into()) {
+                    tracing::warn!(?err, "connection handler failed");
+                }
+            }
+        };
+        wasm_bindgen_futures::spawn_local(async move {
+            Abortable::new(accept, registration).await.ok();
+        });
+        Ok(Self { addr, abort })
+    }
+}
+
+impl Drop for TcpListener {
+    fn drop(&mut self) {
+        self.abort.abort();
+    }
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const TCP_CONNECTION_HANDLER_TYPE_DEF: &'static str = r#"
+/** Called with each connection accepted by a {@link TcpListener}. */
+export type TcpConnectionHandler = (connection: TcpConnection) => void;
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "TcpConnectionHandler", extends = js_sys::Function)]
+    #[derive(Debug, Clone, PartialEq)]
+    pub type TcpConnectionHandler;
+}
@@ -0,0 +1,314 @@
+//! The frames exchanged with the relay, each sent as a binary WebSocket
+//! message.
+//!
+//! A frame starts with its type and the id of the socket it's about, in
+//! big-endian order:
+//!
+//! ```text
+//! +------+----------+---------+
+//! | type | id (u32) | payload |
+//! +------+----------+---------+
+//! ```
+//!
+//! Addresses are encoded as the IP version (4 or 6), the bytes of the IP
+//! and the port as a `u16`. Errors are encoded as a single byte, see
+//! [`error_code`].
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use virtual_net::NetworkError;
+
+mod ty {
+    pub const CONNECT: u8 = 1;
+    pub const BIND_UDP: u8 = 2;
+    pub const DATA: u8 = 3;
+    pub const DATAGRAM: u8 = 4;
+    pub const ACK: u8 = 5;
+    pub const SHUTDOWN: u8 = 6;
+    pub const CLOSE: u8 = 7;
+    pub const RESOLVE: u8 = 8;
+    pub const OPENED: u8 = 9;
+    pub const RESOLVED: u8 = 10;
+    pub const CLOSED: u8 = 11;
+}
+
+/// A frame exchanged with the relay.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Frame {
+    /// Opens a TCP connection to `addr`.
+    Connect { id: u32, addr: SocketAddr },
+    /// Binds a UDP socket to `addr`.
+    BindUdp { id: u32, addr: SocketAddr },
+    /// Bytes of a TCP stream, in either direction.
+    Data { id: u32, data: Bytes },
+    /// A datagram sent to, or received from, `addr`.
+    Datagram {
+        id: u32,
+        addr: SocketAddr,
+        data: Bytes,
+    },
+    /// Tells the relay that `len` more bytes of the stream were read, so
+    /// that it may send them again. The relay has at most
+    /// [`RECV_WINDOW`](super::RECV_WINDOW) bytes in flight on a stream.
+    Ack { id: u32, len: u32 },
+    /// The sender won't write to the stream anymore.
+    Shutdown { id: u32 },
+    /// Closes the socket.
+    Close { id: u32 },
+    /// Resolves the IP addresses of `host`.
+    Resolve { id: u32, host: String },
+    /// The socket was opened with the local address `addr`.
+    Opened { id: u32, addr: SocketAddr },
+    /// The addresses `host` resolved to.
+    Resolved { id: u32, addrs: Vec<IpAddr> },
+    /// The socket was closed by the relay, or failed to be opened or
+    /// resolved, with the error if any.
+    Closed {
+        id: u32,
+        error: Option<NetworkError>,
+    },
+}
+
+impl Frame {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = BytesMut::new();
+        match self {
+            Frame::Connect { id, addr } => {
+                header(&mut buf, ty::CONNECT, *id);
+                put_addr(&mut buf, addr);
+            }
+            Frame::BindUdp { id, addr } => {
+                header(&mut buf, ty::BIND_UDP, *id);
+                put_addr(&mut buf, addr);
+            }
+            Frame::Data { id, data } => {
+                header(&mut buf, ty::DATA, *id);
+                buf.put_slice(data);
+            }
+            Frame::Datagram { id, addr, data } => {
+                header(&mut buf, ty::DATAGRAM, *id);
+                put_addr(&mut buf, addr);
+                buf.put_slice(data);
+            }
+            Frame::Ack { id, len } => {
+                header(&mut buf, ty::ACK, *id);
+                buf.put_u32(*len);
+            }
+            Frame::Shutdown { id } => header(&mut buf, ty::SHUTDOWN, *id),
+            Frame::Close { id } => header(&mut buf, ty::CLOSE, *id),
+            Frame::Resolve { id, host } => {
+                header(&mut buf, ty::RESOLVE, *id);
+                buf.put_slice(host.as_bytes());
+            }
+            Frame::Opened { id, addr } => {
+                header(&mut buf, ty::OPENED, *id);
+                put_addr(&mut buf, addr);
+            }
+            Frame::Resolved { id, addrs } => {
+                header(&mut buf, ty::RESOLVED, *id);
+                for addr in addrs {
+                    put_ip(&mut buf, addr);
+                }
+            }
+            Frame::Closed { id, error } => {
+                header(&mut buf, ty::CLOSED, *id);
+                buf.put_u8(error.map_or(0, error_code));
+            }
+        }
+        buf.to_vec()
+    }
+
+    /// Decodes a frame, or returns `None` if it's malformed.
+    pub fn decode(mut buf: Bytes) -> Option<Self> {
+        if buf.remaining() < 5 {
+            return None;
+        }
+        let ty = buf.get_u8();
+        let id = buf.get_u32();
+        let frame = match ty {
+            ty::CONNECT => Frame::Connect {
+                id,
+                addr: get_addr(&mut buf)?,
+            },
+            ty::BIND_UDP => Frame::BindUdp {
+                id,
+                addr: get_addr(&mut buf)?,
+            },
+            ty::DATA => Frame::Data {
+                id,
+                data: std::mem::take(&mut buf),
+            },
+            ty::DATAGRAM => Frame::Datagram {
+                id,
+                addr: get_addr(&mut buf)?,
+                data: std::mem::take(&mut buf),
+            },
+            ty::ACK if buf.remaining() >= 4 => Frame::Ack {
+                id,
+                len: buf.get_u32(),
+            },
+            ty::SHUTDOWN => Frame::Shutdown { id },
+            ty::CLOSE => Frame::Close { id },
+            ty::RESOLVE => Frame::Resolve {
+                id,
+                host: String::from_utf8(std::mem::take(&mut buf).to_vec()).ok()?,
+            },
+            ty::OPENED => Frame::Opened {
+                id,
+                addr: get_addr(&mut buf)?,
+            },
+            ty::RESOLVED => {
+                let mut addrs = Vec::new();
+                while buf.has_remaining() {
+                    addrs.push(get_ip(&mut buf)?);
+                }
+                Frame::Resolved { id, addrs }
+            }
+            ty::CLOSED if buf.has_remaining() => Frame::Closed {
+                id,
+                error: match buf.get_u8() {
+                    0 => None,
+                    code => Some(code_error(code)),
+                },
+            },
+            _ => return None,
+        };
+        Some(frame)
+    }
+}
+
+fn header(buf: &mut BytesMut, ty: u8, id: u32) {
+    buf.put_u8(ty);
+    buf.put_u32(id);
+}
+
+fn put_ip(buf: &mut BytesMut, ip: &IpAddr) {
+    match ip {
+        IpAddr::V4(ip) => {
+            buf.put_u8(4);
+            buf.put_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            buf.put_u8(6);
+            buf.put_slice(&ip.octets());
+        }
+    }
+}
+
+fn put_addr(buf: &mut BytesMut, addr: &SocketAddr) {
+    put_ip(buf, &addr.ip());
+    buf.put_u16(addr.port());
+}
+
+fn get_ip(buf: &mut Bytes) -> Option<IpAddr> {
+    if !buf.has_remaining() {
+        return None;
+    }
+    match buf.get_u8() {
+        4 if buf.remaining() >= 4 => {
+            let mut octets = [0; 4];
+            buf.copy_to_slice(&mut octets);
+            Some(Ipv4Addr::from(octets).into())
+        }
+        6 if buf.remaining() >= 16 => {
+            let mut octets = [0; 16];
+            buf.copy_to_slice(&mut octets);
+            Some(Ipv6Addr::from(octets).into())
+        }
+        _ => None,
+    }
+}
+
+fn get_addr(buf: &mut Bytes) -> Option<SocketAddr> {
+    let ip = get_ip(buf)?;
+    if buf.remaining() < 2 {
+        return None;
+    }
+    Some(SocketAddr::new(ip, buf.get_u16()))
+}
+
+/// The code of an error in the frames, `0` stands for no error.
+fn error_code(error: NetworkError) -> u8 {
+    match error {
+        NetworkError::ConnectionRefused => 1,
+        NetworkError::ConnectionReset => 2,
+        NetworkError::ConnectionAborted => 3,
+        NetworkError::TimedOut => 4,
+        NetworkError::AddressInUse => 5,
+        NetworkError::AddressNotAvailable => 6,
+        NetworkError::PermissionDenied => 7,
+        NetworkError::Unsupported => 8,
+        NetworkError::InvalidInput => 9,
+        _ => 255,
+    }
+}
+
+fn code_error(code: u8) -> NetworkError {
+    match code {
+        1 => NetworkError::ConnectionRefused,
+        2 => NetworkError::ConnectionReset,
+        3 => NetworkError::ConnectionAborted,
+        4 => NetworkError::TimedOut,
+        5 => NetworkError::AddressInUse,
+        6 => NetworkError::AddressNotAvailable,
+        7 => NetworkError::PermissionDenied,
+        8 => NetworkError::Unsupported,
+        9 => NetworkError::InvalidInput,
+        _ => NetworkError::UnknownError,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+
+    fn round_trip(frame: Frame) {
+        let encoded = Bytes::from(frame.encode());
+        assert_eq!(Frame::decode(encoded), Some(frame));
+    }
+
+    #[wasm_bindgen_test]
+    fn frames_survive_a_round_trip() {
+        let v4 = SocketAddr::from(([10, 0, 0, 1], 443));
+        let v6 = SocketAddr::from((Ipv6Addr::LOCALHOST, 53));
+
+        round_trip(Frame::Connect { id: 1, addr: v4 });
+        round_trip(Frame::BindUdp { id: 2, addr: v6 });
+        round_trip(Frame::Data {
+            id: 3,
+            data: Bytes::from_static(b"hello"),
+        });
+        round_trip(Frame::Datagram {
+            id: 4,
+            addr: v6,
+            data: Bytes::from_static(b"world"),
+        });
+        round_trip(Frame::Ack { id: 5, len: 65536 });
+        round_trip(Frame::Resolve {
+            id: 6,
+            host: "example.com".to_string(),
+        });
+        round_trip(Frame::Resolved {
+            id: 6,
+            addrs: vec![v4.ip(), v6.ip()],
+        });
+        round_trip(Frame::Closed {
+            id: 7,
+            error: Some(NetworkError::ConnectionRefused),
+        });
+        round_trip(Frame::Closed { id: 8, error: None });
+    }
+
+    #[wasm_bindgen_test]
+    fn malformed_frames_are_rejected() {
+        assert_eq!(Frame::decode(Bytes::from_static(&[ty::DATA, 0, 0])), None);
+        assert_eq!(
+            Frame::decode(Bytes::from_static(&[ty::CONNECT, 0, 0, 0, 1, 4, 127])),
+            None
+        );
+        assert_eq!(Frame::decode(Bytes::from_static(&[42, 0, 0, 0, 1])), None);
+    }
+}
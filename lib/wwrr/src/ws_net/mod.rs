@@ -0,0 +1,770 @@
+//! Virtual networking tunnelled over a WebSocket to a relay, which opens TCP
+//! connections and UDP sockets on behalf of the programs so that they get
+//! outbound sockets in the browser.
+//!
+//! # Design
+//!
+//! All the sockets of a [`WsNetworking`] are multiplexed over a single
+//! WebSocket, as [`Frame`]s tagged with the id of their socket. The
+//! WebSocket is owned by a dedicated web worker running a [`WsNetState`],
+//! which the sockets send [`WsNetMsg`]s to, and which hands them what the
+//! relay sends through the [`SocketState`] they share with it.
+//!
+//! The worker only connects to the relay when there's something to relay.
+//! When the WebSocket is lost, it reconnects with an exponential backoff:
+//! the TCP connections can't survive it and are reset, while the UDP sockets
+//! are bound again once the relay is back. The requests made while the relay
+//! can't be reached fail.
+//!
+//! The relay sends at most [`RECV_WINDOW`] bytes of a stream which weren't
+//! acknowledged with [`Frame::Ack`] yet, so that a stream which isn't read
+//! doesn't grow without bounds.
+//!
+//! [`WsNetState`]: worker::WsNetState
+
+pub(crate) mod frame;
+mod worker;
+
+use std::{
+    collections::VecDeque,
+    mem::MaybeUninit,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use bytes::{Buf, Bytes};
+use tokio::sync::{mpsc, oneshot};
+use utils::Error;
+use virtual_mio::{InterestHandler, InterestType};
+use virtual_net::{
+    Interest, NetworkError, Result, SocketStatus, VirtualConnectedSocket,
+    VirtualConnectionlessSocket, VirtualIoSource, VirtualNetworking, VirtualSocket,
+    VirtualTcpSocket, VirtualUdpSocket,
+};
+
+use self::{frame::Frame, worker::WsNetInit};
+use crate::tasks::{init_message_ws_net, WORKER_URL};
+
+/// How many bytes of a stream the relay sends before waiting for them to be
+/// read.
+pub(crate) const RECV_WINDOW: usize = 256 * 1024;
+
+/// How many bytes are handed to the worker before the writers of a socket
+/// have to wait, the default `SO_SNDBUF` of Linux.
+const SEND_BUF_SIZE: usize = 212_992;
+
+/// Messages sent from the [`WsNetworking`] and its sockets to the worker
+/// relaying them.
+#[derive(Debug)]
+pub(crate) enum WsNetMsg {
+    /// Opens the socket `id`, replying with its local address.
+    Open {
+        id: u32,
+        open: Open,
+        socket: Arc<Mutex<SocketState>>,
+        reply: oneshot::Sender<Result<SocketAddr>>,
+    },
+    /// Resolves the IP addresses of `host`.
+    Resolve {
+        id: u32,
+        host: String,
+        reply: oneshot::Sender<Result<Vec<IpAddr>>>,
+    },
+    /// Sends a frame of the socket `id`, which carries `len` of its unsent
+    /// bytes.
+    Send { id: u32, frame: Frame, len: usize },
+}
+
+/// How a socket is opened by the relay.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Open {
+    /// A TCP connection to the address.
+    Connect(SocketAddr),
+    /// A UDP socket bound to the address.
+    BindUdp(SocketAddr),
+}
+
+impl Open {
+    fn frame(&self, id: u32) -> Frame {
+        match *self {
+            Open::Connect(addr) => Frame::Connect { id, addr },
+            Open::BindUdp(addr) => Frame::BindUdp { id, addr },
+        }
+    }
+}
+
+/// The state of a socket, shared with the worker relaying it.
+#[derive(Debug)]
+pub(crate) struct SocketState {
+    /// What was received and not read yet, with the address it came from
+    incoming: VecDeque<(SocketAddr, Bytes)>,
+    /// The local address the relay opened the socket with
+    addr_local: Option<SocketAddr>,
+    /// How many bytes were handed to the worker and not sent yet
+    unsent: usize,
+    send_buf_size: usize,
+    recv_buf_size: usize,
+    /// How many bytes of the stream were read since they were last
+    /// acknowledged
+    unacked: usize,
+    /// Whether the relay won't send more bytes of the stream
+    eof: bool,
+    read_shutdown: bool,
+    write_shutdown: bool,
+    /// Why the socket was closed by the relay
+    error: Option<NetworkError>,
+    linger: Option<Duration>,
+    nodelay: bool,
+    keepalive: bool,
+    interest: Interest,
+}
+
+impl Default for SocketState {
+    fn default() -> Self {
+        Self {
+            incoming: VecDeque::new(),
+            addr_local: None,
+            unsent: 0,
+            send_buf_size: SEND_BUF_SIZE,
+            recv_buf_size: RECV_WINDOW,
+            unacked: 0,
+            eof: false,
+            read_shutdown: false,
+            write_shutdown: false,
+            error: None,
+            linger: None,
+            nodelay: false,
+            keepalive: false,
+            interest: Interest::default(),
+        }
+    }
+}
+
+impl SocketState {
+    fn is_readable(&self) -> bool {
+        !self.incoming.is_empty() || self.eof || self.read_shutdown || self.error.is_some()
+    }
+
+    fn free(&self) -> usize {
+        self.send_buf_size.saturating_sub(self.unsent)
+    }
+
+    /// Reports the local address the socket was opened with.
+    pub(crate) fn opened(&mut self, addr: SocketAddr) {
+        self.addr_local = Some(addr);
+    }
+
+    /// Receives bytes or a datagram from `addr`.
+    pub(crate) fn receive(&mut self, addr: SocketAddr, data: Bytes) {
+        self.incoming.push_back((addr, data));
+        self.interest.notify(InterestType::Readable);
+    }
+
+    /// Whether the stream stopped reading, the bytes received are then
+    /// acknowledged right away.
+    pub(crate) fn is_read_shutdown(&self) -> bool {
+        self.read_shutdown
+    }
+
+    /// The relay won't send more bytes of the stream.
+    pub(crate) fn finish(&mut self) {
+        self.eof = true;
+        self.interest.notify(InterestType::Readable);
+    }
+
+    /// Counts `len` bytes handed to the worker as sent.
+    pub(crate) fn sent(&mut self, len: usize) {
+        if len > 0 {
+            self.unsent = self.unsent.saturating_sub(len);
+            self.interest.notify(InterestType::Writable);
+        }
+    }
+
+    /// Closes the socket as the relay did, the bytes received before can
+    /// still be read.
+    pub(crate) fn close(&mut self, error: Option<NetworkError>) {
+        match error {
+            Some(error) => {
+                self.error = Some(error);
+                self.interest.notify(InterestType::Error);
+            }
+            None => {
+                self.eof = true;
+                self.error = Some(NetworkError::BrokenPipe);
+                self.interest.notify(InterestType::Closed);
+            }
+        }
+    }
+}
+
+/// A handle to a socket opened by the relay, which is closed once dropped.
+#[derive(Debug)]
+struct WsSocket {
+    id: u32,
+    state: Arc<Mutex<SocketState>>,
+    msg_tx: mpsc::UnboundedSender<WsNetMsg>,
+}
+
+impl Drop for WsSocket {
+    fn drop(&mut self) {
+        self.send(Frame::Close { id: self.id }, 0).ok();
+    }
+}
+
+impl WsSocket {
+    fn lock(&self) -> MutexGuard<'_, SocketState> {
+        self.state.lock().unwrap()
+    }
+
+    fn send(&self, frame: Frame, len: usize) -> Result<()> {
+        self.msg_tx
+            .send(WsNetMsg::Send {
+                id: self.id,
+                frame,
+                len,
+            })
+            .map_err(|_| NetworkError::ConnectionAborted)
+    }
+
+    fn remove_handler(&self) {
+        self.lock().interest.handler.take();
+    }
+
+    fn poll_read_ready(&self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut state = self.lock();
+        if state.is_readable() {
+            let len = state.incoming.iter().map(|(_, data)| data.len()).sum();
+            return Poll::Ready(Ok(len));
+        }
+        state.interest.read_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    fn poll_write_ready(&self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut state = self.lock();
+        if let Some(error) = state.error {
+            return Poll::Ready(Err(error));
+        }
+        if state.write_shutdown {
+            return Poll::Ready(Err(NetworkError::BrokenPipe));
+        }
+        let free = state.free();
+        if free > 0 {
+            return Poll::Ready(Ok(free));
+        }
+        state.interest.write_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.lock().addr_local.ok_or(NetworkError::NotConnected)
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        let state = self.lock();
+        Ok(match state.error {
+            Some(NetworkError::BrokenPipe) => SocketStatus::Closed,
+            Some(_) => SocketStatus::Failed,
+            None => SocketStatus::Opened,
+        })
+    }
+
+    fn set_handler(&self, mut handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        let mut state = self.lock();
+        if state.is_readable() {
+            handler.push_interest(InterestType::Readable);
+        }
+        if state.free() > 0 {
+            handler.push_interest(InterestType::Writable);
+        }
+        state.interest.handler = Some(handler);
+        Ok(())
+    }
+
+    /// Reads from the stream, acknowledging the bytes read to the relay once
+    /// half of its window was read.
+    fn recv(&self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<usize> {
+        let mut state = self.lock();
+        let Some((_, data)) = state.incoming.front_mut() else {
+            return match state.error {
+                _ if state.eof || state.read_shutdown => Ok(0),
+                Some(error) => Err(error),
+                None => Err(NetworkError::WouldBlock),
+            };
+        };
+        let amt = data.len().min(buf.len());
+        for (dst, src) in buf.iter_mut().zip(&data[..amt]) {
+            dst.write(*src);
+        }
+        if peek {
+            return Ok(amt);
+        }
+        data.advance(amt);
+        if data.is_empty() {
+            state.incoming.pop_front();
+        }
+        state.unacked += amt;
+        if state.unacked >= RECV_WINDOW / 2 {
+            let len = std::mem::take(&mut state.unacked) as u32;
+            self.send(Frame::Ack { id: self.id, len }, 0)?;
+        }
+        Ok(amt)
+    }
+
+    /// Reads a datagram, what doesn't fit in the buffer is discarded.
+    fn recv_from(&self, buf: &mut [MaybeUninit<u8>], peek: bool) -> Result<(usize, SocketAddr)> {
+        let mut state = self.lock();
+        let Some((addr, data)) = state.incoming.front() else {
+            return Err(state.error.unwrap_or(NetworkError::WouldBlock));
+        };
+        let (addr, amt) = (*addr, data.len().min(buf.len()));
+        for (dst, src) in buf.iter_mut().zip(&data[..amt]) {
+            dst.write(*src);
+        }
+        if !peek {
+            state.incoming.pop_front();
+        }
+        Ok((amt, addr))
+    }
+}
+
+/// A TCP connection opened by the relay.
+#[derive(Debug)]
+pub struct WsTcpSocket {
+    socket: WsSocket,
+    peer: SocketAddr,
+}
+
+impl WsTcpSocket {
+    /// A connection with `peer` whose frames are handed to the receiver of
+    /// `msg_tx`, as the socket `id`.
+    pub(crate) fn new(
+        id: u32,
+        state: Arc<Mutex<SocketState>>,
+        msg_tx: mpsc::UnboundedSender<WsNetMsg>,
+        peer: SocketAddr,
+    ) -> Self {
+        let socket = WsSocket { id, state, msg_tx };
+        Self { socket, peer }
+    }
+}
+
+impl VirtualIoSource for WsTcpSocket {
+    fn remove_handler(&mut self) {
+        self.socket.remove_handler();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.socket.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.socket.poll_write_ready(cx)
+    }
+}
+
+impl VirtualSocket for WsTcpSocket {
+    fn set_ttl(&mut self, _ttl: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.socket.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.socket.status()
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.socket.set_handler(handler)
+    }
+}
+
+impl VirtualConnectedSocket for WsTcpSocket {
+    fn set_linger(&mut self, linger: Option<Duration>) -> Result<()> {
+        self.socket.lock().linger = linger;
+        Ok(())
+    }
+
+    fn linger(&self) -> Result<Option<Duration>> {
+        Ok(self.socket.lock().linger)
+    }
+
+    fn try_send(&mut self, data: &[u8]) -> Result<usize> {
+        let mut state = self.socket.lock();
+        if let Some(error) = state.error {
+            return Err(error);
+        }
+        if state.write_shutdown {
+            return Err(NetworkError::BrokenPipe);
+        }
+        let amt = state.free().min(data.len());
+        if amt == 0 && !data.is_empty() {
+            return Err(NetworkError::WouldBlock);
+        }
+        let data = Bytes::copy_from_slice(&data[..amt]);
+        self.socket.send(
+            Frame::Data {
+                id: self.socket.id,
+                data,
+            },
+            amt,
+        )?;
+        state.unsent += amt;
+        Ok(amt)
+    }
+
+    fn try_flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.shutdown(Shutdown::Both)
+    }
+
+    fn try_recv(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        self.socket.recv(buf, false)
+    }
+
+    fn try_peek(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<usize> {
+        self.socket.recv(buf, true)
+    }
+}
+
+impl VirtualTcpSocket for WsTcpSocket {
+    /// The relay's window stays the same, the size is only reported back.
+    fn set_recv_buf_size(&mut self, size: usize) -> Result<()> {
+        self.socket.lock().recv_buf_size = size;
+        Ok(())
+    }
+
+    fn recv_buf_size(&self) -> Result<usize> {
+        Ok(self.socket.lock().recv_buf_size)
+    }
+
+    fn set_send_buf_size(&mut self, size: usize) -> Result<()> {
+        self.socket.lock().send_buf_size = size;
+        Ok(())
+    }
+
+    fn send_buf_size(&self) -> Result<usize> {
+        Ok(self.socket.lock().send_buf_size)
+    }
+
+    fn set_nodelay(&mut self, nodelay: bool) -> Result<()> {
+        self.socket.lock().nodelay = nodelay;
+        Ok(())
+    }
+
+    fn nodelay(&self) -> Result<bool> {
+        Ok(self.socket.lock().nodelay)
+    }
+
+    fn set_keepalive(&mut self, keepalive: bool) -> Result<()> {
+        self.socket.lock().keepalive = keepalive;
+        Ok(())
+    }
+
+    fn keepalive(&self) -> Result<bool> {
+        Ok(self.socket.lock().keepalive)
+    }
+
+    fn set_dontroute(&mut self, _dontroute: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn dontroute(&self) -> Result<bool> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn addr_peer(&self) -> Result<SocketAddr> {
+        Ok(self.peer)
+    }
+
+    fn shutdown(&mut self, how: Shutdown) -> Result<()> {
+        let mut state = self.socket.lock();
+        if matches!(how, Shutdown::Read | Shutdown::Both) && !state.read_shutdown {
+            // The bytes which won't be read are acknowledged so that the
+            // relay doesn't wait for them, the worker does the same with the
+            // ones received later
+            state.read_shutdown = true;
+            let len = state.unacked
+                + state
+                    .incoming
+                    .drain(..)
+                    .map(|(_, data)| data.len())
+                    .sum::<usize>();
+            state.unacked = 0;
+            if len > 0 {
+                self.socket.send(
+                    Frame::Ack {
+                        id: self.socket.id,
+                        len: len as u32,
+                    },
+                    0,
+                )?;
+            }
+            state.interest.notify(InterestType::Readable);
+        }
+        if matches!(how, Shutdown::Write | Shutdown::Both) && !state.write_shutdown {
+            state.write_shutdown = true;
+            self.socket
+                .send(Frame::Shutdown { id: self.socket.id }, 0)?;
+            state.interest.notify(InterestType::Writable);
+        }
+        Ok(())
+    }
+
+    fn is_closed(&self) -> bool {
+        let state = self.socket.lock();
+        state.incoming.is_empty() && (state.eof || state.error.is_some())
+    }
+}
+
+/// A UDP socket bound by the relay.
+#[derive(Debug)]
+pub struct WsUdpSocket {
+    socket: WsSocket,
+}
+
+impl VirtualIoSource for WsUdpSocket {
+    fn remove_handler(&mut self) {
+        self.socket.remove_handler();
+    }
+
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.socket.poll_read_ready(cx)
+    }
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        self.socket.poll_write_ready(cx)
+    }
+}
+
+impl VirtualSocket for WsUdpSocket {
+    fn set_ttl(&mut self, _ttl: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn addr_local(&self) -> Result<SocketAddr> {
+        self.socket.addr_local()
+    }
+
+    fn status(&self) -> Result<SocketStatus> {
+        self.socket.status()
+    }
+
+    fn set_handler(&mut self, handler: Box<dyn InterestHandler + Send + Sync>) -> Result<()> {
+        self.socket.set_handler(handler)
+    }
+}
+
+impl VirtualConnectionlessSocket for WsUdpSocket {
+    fn try_send_to(&mut self, data: &[u8], addr: SocketAddr) -> Result<usize> {
+        let mut state = self.socket.lock();
+        if let Some(error) = state.error {
+            return Err(error);
+        }
+        // Datagrams aren't split, they wait for enough room to be sent whole
+        if state.unsent > 0 && state.free() < data.len() {
+            return Err(NetworkError::WouldBlock);
+        }
+        let frame = Frame::Datagram {
+            id: self.socket.id,
+            addr,
+            data: Bytes::copy_from_slice(data),
+        };
+        self.socket.send(frame, data.len())?;
+        state.unsent += data.len();
+        Ok(data.len())
+    }
+
+    fn try_recv_from(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)> {
+        self.socket.recv_from(buf, false)
+    }
+
+    fn try_peek_from(&mut self, buf: &mut [MaybeUninit<u8>]) -> Result<(usize, SocketAddr)> {
+        self.socket.recv_from(buf, true)
+    }
+}
+
+impl VirtualUdpSocket for WsUdpSocket {
+    fn set_broadcast(&mut self, _broadcast: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn broadcast(&self) -> Result<bool> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn set_multicast_loop_v4(&mut self, _val: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_loop_v4(&self) -> Result<bool> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn set_multicast_loop_v6(&mut self, _val: bool) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_loop_v6(&self) -> Result<bool> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn set_multicast_ttl_v4(&mut self, _ttl: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn multicast_ttl_v4(&self) -> Result<u32> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn join_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v4(&mut self, _multiaddr: Ipv4Addr, _iface: Ipv4Addr) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn join_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn leave_multicast_v6(&mut self, _multiaddr: Ipv6Addr, _iface: u32) -> Result<()> {
+        Err(NetworkError::Unsupported)
+    }
+
+    fn addr_peer(&self) -> Result<Option<SocketAddr>> {
+        Ok(None)
+    }
+}
+
+/// [`VirtualNetworking`] whose sockets are opened by a relay reached over a
+/// WebSocket.
+#[derive(Debug, Clone)]
+pub struct WsNetworking {
+    next_id: Arc<AtomicU32>,
+    msg_tx: mpsc::UnboundedSender<WsNetMsg>,
+}
+
+impl WsNetworking {
+    /// Spawns the web worker relaying the sockets to the relay at `url`,
+    /// which is only connected to once a socket is opened.
+    pub(crate) fn new(url: &str) -> std::result::Result<Self, Error> {
+        let url = web_sys::Url::new(url).map_err(Error::js)?;
+        if !matches!(url.protocol().as_str(), "ws:" | "wss:") {
+            return Err(anyhow::anyhow!("\"{}\" is not a WebSocket URL", url.href()).into());
+        }
+
+        let wo = web_sys::WorkerOptions::new();
+        wo.set_name("ws-net");
+        wo.set_type(web_sys::WorkerType::Module);
+        let worker = web_sys::Worker::new_with_options(&WORKER_URL, &wo).map_err(Error::js)?;
+        worker
+            .post_message(&init_message_ws_net())
+            .map_err(Error::js)?;
+
+        let (msg_tx, msg_rx) = mpsc::unbounded_channel();
+        let init = WsNetInit {
+            msg_rx,
+            url: url.href(),
+        };
+        worker.post_message(&init.into_js()?).map_err(Error::js)?;
+
+        Ok(Self::with_channel(msg_tx))
+    }
+
+    /// Networking whose sockets are relayed by the receiver of `msg_tx`.
+    pub(crate) fn with_channel(msg_tx: mpsc::UnboundedSender<WsNetMsg>) -> Self {
+        Self {
+            next_id: Arc::new(AtomicU32::new(0)),
+            msg_tx,
+        }
+    }
+
+    /// Has the relay open a socket, which is closed again if this is
+    /// cancelled.
+    async fn open(&self, open: Open) -> Result<(WsSocket, SocketAddr)> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let state = Arc::new(Mutex::new(SocketState::default()));
+        let (reply, opened) = oneshot::channel();
+        self.msg_tx
+            .send(WsNetMsg::Open {
+                id,
+                open,
+                socket: state.clone(),
+                reply,
+            })
+            .map_err(|_| NetworkError::ConnectionAborted)?;
+
+        let socket = WsSocket {
+            id,
+            state,
+            msg_tx: self.msg_tx.clone(),
+        };
+        let addr = opened
+            .await
+            .map_err(|_| NetworkError::ConnectionAborted)??;
+        Ok((socket, addr))
+    }
+}
+
+#[async_trait::async_trait]
+impl VirtualNetworking for WsNetworking {
+    async fn bind_udp(
+        &self,
+        addr: SocketAddr,
+        _reuse_port: bool,
+        _reuse_addr: bool,
+    ) -> Result<Box<dyn VirtualUdpSocket + Sync>> {
+        let (socket, _) = self.open(Open::BindUdp(addr)).await?;
+        Ok(Box::new(WsUdpSocket { socket }))
+    }
+
+    /// The relay picks the local address of the connection.
+    async fn connect_tcp(
+        &self,
+        _addr: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Box<dyn VirtualTcpSocket + Sync>> {
+        let (socket, _) = self.open(Open::Connect(peer)).await?;
+        Ok(Box::new(WsTcpSocket { socket, peer }))
+    }
+
+    async fn resolve(
+        &self,
+        host: &str,
+        _port: Option<u16>,
+        _dns_server: Option<IpAddr>,
+    ) -> Result<Vec<IpAddr>> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (reply, resolved) = oneshot::channel();
+        self.msg_tx
+            .send(WsNetMsg::Resolve {
+                id,
+                host: host.to_string(),
+                reply,
+            })
+            .map_err(|_| NetworkError::ConnectionAborted)?;
+        resolved
+            .await
+            .map_err(|_| NetworkError::ConnectionAborted)?
+    }
+}
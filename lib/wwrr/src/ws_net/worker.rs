@@ -0,0 +1,435 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+};
+
+use bytes::Bytes;
+use futures::future::Either;
+use js_sys::{ArrayBuffer, Uint8Array};
+use tokio::sync::{mpsc, oneshot};
+use utils::{Error, GlobalScope};
+use virtual_net::{NetworkError, Result};
+use wasm_bindgen::{closure::Closure, prelude::wasm_bindgen, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{BinaryType, DedicatedWorkerGlobalScope, MessageEvent, WebSocket};
+
+use super::{frame::Frame, Open, SocketState, WsNetMsg};
+use crate::tasks::{Deserializer, Serializer};
+
+/// How long to wait before connecting to the relay again the first time,
+/// the wait doubles with every attempt up to [`MAX_BACKOFF_MS`].
+const MIN_BACKOFF_MS: i32 = 250;
+const MAX_BACKOFF_MS: i32 = 30_000;
+
+/// Initialization message of the worker relaying the sockets, sent as a web
+/// worker message.
+#[derive(Debug)]
+pub(crate) struct WsNetInit {
+    /// Message receiver.
+    pub msg_rx: mpsc::UnboundedReceiver<WsNetMsg>,
+    /// The URL of the relay.
+    pub url: String,
+}
+
+impl WsNetInit {
+    pub(crate) fn into_js(self) -> std::result::Result<JsValue, Error> {
+        let Self { msg_rx, url } = self;
+
+        Serializer::new(consts::TYPE_INIT)
+            .boxed(consts::MSG_RX, msg_rx)
+            .set(consts::URL, url)
+            .finish()
+    }
+
+    pub(crate) unsafe fn try_from_js(value: JsValue) -> std::result::Result<Self, Error> {
+        let de = Deserializer::new(value);
+        if de.ty()? != consts::TYPE_INIT {
+            return Err(anyhow::anyhow!("invalid WebSocket networking init message type").into());
+        }
+
+        Ok(Self {
+            msg_rx: de.boxed(consts::MSG_RX)?,
+            url: de.string(consts::URL)?,
+        })
+    }
+}
+
+mod consts {
+    pub const TYPE_INIT: &str = "init-ws-net";
+    pub const MSG_RX: &str = "msg-rx";
+    pub const URL: &str = "url";
+}
+
+/// The worker relaying the sockets.
+#[wasm_bindgen(skip_typescript)]
+struct WsNetWorker {}
+
+#[wasm_bindgen]
+impl WsNetWorker {
+    /// Preinitializes the worker.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Handles the init message and starts relaying the sockets.
+    #[wasm_bindgen]
+    pub fn handle(&mut self, msg: JsValue) -> std::result::Result<(), utils::Error> {
+        let init = unsafe { WsNetInit::try_from_js(msg) }?;
+        wasm_bindgen_futures::spawn_local(WsNetState::run(init));
+        Ok(())
+    }
+}
+
+/// What the WebSocket to the relay tells.
+#[derive(Debug)]
+enum RelayEvent {
+    Open,
+    Frame(Bytes),
+    Closed,
+}
+
+/// The WebSocket to the relay, which is closed once dropped.
+struct Relay {
+    ws: WebSocket,
+    events: mpsc::UnboundedReceiver<RelayEvent>,
+    _on_open: Closure<dyn FnMut(JsValue)>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_close: Closure<dyn FnMut(JsValue)>,
+}
+
+impl Relay {
+    async fn connect(url: &str) -> std::result::Result<Self, Error> {
+        let ws = WebSocket::new(url).map_err(Error::js)?;
+        ws.set_binary_type(BinaryType::Arraybuffer);
+
+        let (events_tx, events) = mpsc::unbounded_channel();
+        let on_open = Closure::<dyn FnMut(JsValue)>::new({
+            let events_tx = events_tx.clone();
+            move |_| {
+                let _ = events_tx.send(RelayEvent::Open);
+            }
+        });
+        let on_message = Closure::<dyn FnMut(MessageEvent)>::new({
+            let events_tx = events_tx.clone();
+            move |ev: MessageEvent| {
+                if let Ok(buf) = ev.data().dyn_into::<ArrayBuffer>() {
+                    let frame = Uint8Array::new(&buf).to_vec();
+                    let _ = events_tx.send(RelayEvent::Frame(frame.into()));
+                }
+            }
+        });
+        // A failure is always followed by the close event
+        let on_close = Closure::<dyn FnMut(JsValue)>::new(move |_| {
+            let _ = events_tx.send(RelayEvent::Closed);
+        });
+        ws.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        ws.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        ws.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+        let mut relay = Self {
+            ws,
+            events,
+            _on_open: on_open,
+            _on_message: on_message,
+            _on_close: on_close,
+        };
+        match relay.events.recv().await {
+            Some(RelayEvent::Open) => Ok(relay),
+            _ => Err(anyhow::anyhow!("the WebSocket was closed").into()),
+        }
+    }
+
+    fn send(&self, frame: &Frame) {
+        // The frame is copied out of the shared memory, which the WebSocket
+        // can't send from
+        let frame = Uint8Array::from(frame.encode().as_slice());
+        if let Err(err) = self.ws.send_with_array_buffer(&frame.buffer()) {
+            tracing::warn!(
+                error = &*utils::js_error(err),
+                "Unable to send a frame to the relay"
+            );
+        }
+    }
+
+    /// The next frame received, or `None` once the WebSocket is closed.
+    async fn next(&mut self) -> Option<Bytes> {
+        match self.events.recv().await? {
+            RelayEvent::Frame(frame) => Some(frame),
+            RelayEvent::Open | RelayEvent::Closed => None,
+        }
+    }
+}
+
+impl Drop for Relay {
+    fn drop(&mut self) {
+        self.ws.set_onopen(None);
+        self.ws.set_onmessage(None);
+        self.ws.set_onclose(None);
+        self.ws.close().ok();
+    }
+}
+
+/// A socket opened by the relay.
+#[derive(Debug)]
+struct Relayed {
+    open: Open,
+    socket: Arc<Mutex<SocketState>>,
+    /// Replied to once the relay opened the socket
+    reply: Option<oneshot::Sender<Result<SocketAddr>>>,
+}
+
+/// The state of the worker relaying the sockets.
+#[derive(Debug, Default)]
+pub(crate) struct WsNetState {
+    sockets: HashMap<u32, Relayed>,
+    resolving: HashMap<u32, oneshot::Sender<Result<Vec<IpAddr>>>>,
+}
+
+impl WsNetState {
+    /// Relays the sockets on this web worker until every handle to them is
+    /// dropped, connecting to the relay again whenever the WebSocket is lost.
+    async fn run(init: WsNetInit) {
+        let WsNetInit { mut msg_rx, url } = init;
+        let scope = GlobalScope::current();
+        let mut state = WsNetState::default();
+        let mut backoff = MIN_BACKOFF_MS;
+        let mut first = None;
+
+        'relay: loop {
+            // The relay is only connected to when there's something to
+            // relay, the frames of the sockets closed meanwhile are dropped
+            while state.sockets.is_empty() {
+                match msg_rx.recv().await {
+                    Some(WsNetMsg::Send { .. }) => {}
+                    Some(msg) => {
+                        first = Some(msg);
+                        break;
+                    }
+                    None => break 'relay,
+                }
+            }
+
+            match Relay::connect(&url).await {
+                Ok(mut relay) => {
+                    tracing::debug!(%url, "Connected to the network relay");
+                    backoff = MIN_BACKOFF_MS;
+                    state.reopen(&relay);
+                    if let Some(msg) = first.take() {
+                        state.handle(&relay, msg);
+                    }
+                    let handles_left = state.serve(&mut relay, &mut msg_rx).await;
+                    state.disconnected();
+                    if !handles_left {
+                        break 'relay;
+                    }
+                    tracing::warn!(%url, "Lost the connection to the network relay");
+                }
+                Err(err) => {
+                    tracing::warn!(%url, %err, "Unable to connect to the network relay");
+                    state.unreachable(first.take(), &mut msg_rx);
+                }
+            }
+
+            JsFuture::from(scope.sleep(backoff)).await.ok();
+            backoff = (backoff * 2).min(MAX_BACKOFF_MS);
+        }
+
+        tracing::debug!("WebSocket networking worker exiting");
+        let scope: DedicatedWorkerGlobalScope = js_sys::global().unchecked_into();
+        scope.close();
+    }
+
+    /// Relays the messages and the frames until the WebSocket is lost,
+    /// returns whether handles to the sockets are left.
+    async fn serve(
+        &mut self,
+        relay: &mut Relay,
+        msg_rx: &mut mpsc::UnboundedReceiver<WsNetMsg>,
+    ) -> bool {
+        loop {
+            let next = match futures::future::select(
+                Box::pin(msg_rx.recv()),
+                Box::pin(relay.next()),
+            )
+            .await
+            {
+                Either::Left((msg, _)) => Either::Left(msg),
+                Either::Right((frame, _)) => Either::Right(frame),
+            };
+            match next {
+                Either::Left(Some(msg)) => self.handle(relay, msg),
+                Either::Left(None) => return false,
+                Either::Right(Some(frame)) => self.receive(relay, frame),
+                Either::Right(None) => return true,
+            }
+        }
+    }
+
+    fn handle(&mut self, relay: &Relay, msg: WsNetMsg) {
+        match msg {
+            WsNetMsg::Open {
+                id,
+                open,
+                socket,
+                reply,
+            } => {
+                relay.send(&open.frame(id));
+                let reply = Some(reply);
+                self.sockets.insert(
+                    id,
+                    Relayed {
+                        open,
+                        socket,
+                        reply,
+                    },
+                );
+            }
+            WsNetMsg::Resolve { id, host, reply } => {
+                relay.send(&Frame::Resolve { id, host });
+                self.resolving.insert(id, reply);
+            }
+            WsNetMsg::Send { id, frame, len } => {
+                // The frames of the sockets closed by the relay are dropped
+                if let Some(socket) = self.sender(id, &frame) {
+                    relay.send(&frame);
+                    socket.lock().unwrap().sent(len);
+                }
+            }
+        }
+    }
+
+    /// The socket sending `frame`, which is forgotten once it's closed.
+    fn sender(&mut self, id: u32, frame: &Frame) -> Option<Arc<Mutex<SocketState>>> {
+        match frame {
+            Frame::Close { .. } => self.sockets.remove(&id).map(|relayed| relayed.socket),
+            _ => self.sockets.get(&id).map(|relayed| relayed.socket.clone()),
+        }
+    }
+
+    /// Hands what the relay sent to the sockets.
+    fn receive(&mut self, relay: &Relay, frame: Bytes) {
+        let Some(frame) = Frame::decode(frame) else {
+            tracing::warn!("Received a malformed frame from the network relay");
+            return;
+        };
+
+        match frame {
+            Frame::Opened { id, addr } => {
+                if let Some(relayed) = self.sockets.get_mut(&id) {
+                    relayed.socket.lock().unwrap().opened(addr);
+                    if let Some(reply) = relayed.reply.take() {
+                        let _ = reply.send(Ok(addr));
+                    }
+                }
+            }
+            Frame::Data { id, data } => {
+                let Some(Relayed {
+                    open: Open::Connect(peer),
+                    socket,
+                    ..
+                }) = self.sockets.get(&id)
+                else {
+                    return;
+                };
+                let mut socket = socket.lock().unwrap();
+                // The bytes which won't be read are acknowledged right away
+                if socket.is_read_shutdown() {
+                    let len = data.len() as u32;
+                    relay.send(&Frame::Ack { id, len });
+                } else {
+                    socket.receive(*peer, data);
+                }
+            }
+            Frame::Datagram { id, addr, data } => {
+                if let Some(relayed) = self.sockets.get(&id) {
+                    relayed.socket.lock().unwrap().receive(addr, data);
+                }
+            }
+            Frame::Shutdown { id } => {
+                if let Some(relayed) = self.sockets.get(&id) {
+                    relayed.socket.lock().unwrap().finish();
+                }
+            }
+            Frame::Closed { id, error } => {
+                if let Some(reply) = self.resolving.remove(&id) {
+                    let _ = reply.send(Err(error.unwrap_or(NetworkError::AddressNotAvailable)));
+                } else if let Some(mut relayed) = self.sockets.remove(&id) {
+                    match relayed.reply.take() {
+                        Some(reply) => {
+                            let error = error.unwrap_or(NetworkError::ConnectionRefused);
+                            let _ = reply.send(Err(error));
+                        }
+                        None => relayed.socket.lock().unwrap().close(error),
+                    }
+                }
+            }
+            Frame::Resolved { id, addrs } => {
+                if let Some(reply) = self.resolving.remove(&id) {
+                    let _ = reply.send(Ok(addrs));
+                }
+            }
+            frame => tracing::warn!(
+                ?frame,
+                "Received an unexpected frame from the network relay"
+            ),
+        }
+    }
+
+    /// Binds the UDP sockets again once the relay is back.
+    fn reopen(&self, relay: &Relay) {
+        for (id, relayed) in &self.sockets {
+            relay.send(&relayed.open.frame(*id));
+        }
+    }
+
+    /// Resets the connections and fails the requests relayed over the lost
+    /// WebSocket, while the UDP sockets are kept to be bound again.
+    fn disconnected(&mut self) {
+        for (_, reply) in self.resolving.drain() {
+            let _ = reply.send(Err(NetworkError::ConnectionAborted));
+        }
+        self.sockets.retain(|_, relayed| match relayed.open {
+            Open::BindUdp(_) => true,
+            Open::Connect(_) => {
+                match relayed.reply.take() {
+                    Some(reply) => {
+                        let _ = reply.send(Err(NetworkError::ConnectionReset));
+                    }
+                    None => relayed
+                        .socket
+                        .lock()
+                        .unwrap()
+                        .close(Some(NetworkError::ConnectionReset)),
+                }
+                false
+            }
+        });
+    }
+
+    /// Fails the requests made while the relay can't be reached, the bytes
+    /// sent meanwhile are dropped.
+    fn unreachable(
+        &mut self,
+        first: Option<WsNetMsg>,
+        msg_rx: &mut mpsc::UnboundedReceiver<WsNetMsg>,
+    ) {
+        let pending = std::iter::from_fn(|| msg_rx.try_recv().ok());
+        for msg in first.into_iter().chain(pending) {
+            match msg {
+                WsNetMsg::Open { reply, .. } => {
+                    let _ = reply.send(Err(NetworkError::ConnectionRefused));
+                }
+                WsNetMsg::Resolve { reply, .. } => {
+                    let _ = reply.send(Err(NetworkError::ConnectionRefused));
+                }
+                WsNetMsg::Send { id, frame, len } => {
+                    if let Some(socket) = self.sender(id, &frame) {
+                        socket.lock().unwrap().sent(len);
+                    }
+                }
+            }
+        }
+    }
+}